@@ -25,7 +25,13 @@
 */
 
 use {
-    crate::{cli::Cli, runner::Runner, tokenizer},
+    crate::{
+        browser,
+        cli::Cli,
+        export::{Exporter, Format},
+        runner::Runner,
+        tokenizer,
+    },
     clap::Parser,
     crossterm::{
         cursor, execute,
@@ -69,6 +75,9 @@ the server. These enable you to do convenient things like:
 Apart from these, you can use the following shell commands:
 - "!pipe": Lets you create a pipeline. Terminate with a semicolon (`;`)
 - "!help": Brings up this help menu
+- "!browse": Lets you browse spaces, models, keys and values interactively
+- "!o <file> [csv|json]": Writes the results of the following queries to a file.
+  Run "!o" again to go back to printing them
 - "?<command name>": Describes what the built-in shell command is for
 
 With Skytable in your hands, the sky is the only limit on what you can create!"#;
@@ -88,6 +97,7 @@ pub async fn start_repl() {
     let mut skysh_blank: String = "                     > ".to_owned();
     let mut skysh_prompt: String = "skysh@default:default> ".to_owned();
     let mut did_swap = false;
+    let mut exporter: Option<Exporter> = None;
 
     macro_rules! readln {
         ($editor:expr) => {
@@ -188,9 +198,14 @@ pub async fn start_repl() {
                                 match &line.as_bytes()[1..] {
                                     b"" => eskysh!("Bad shell command"),
                                     b"help" => println!("{}", HELP_TEXT),
+                                    b"browse" => browser::browse(&mut runner).await,
+                                    [b'o', args @ ..] if args.is_empty() || args[0] == b' ' => {
+                                        set_exporter(&mut exporter, &line[2..])
+                                    }
                                     b"pipe" => {
                                         // so we need to handle a pipeline
                                         let mut pipeline = Pipeline::new();
+                                        let mut queries = Vec::new();
                                         line = readln!(editor);
                                         loop {
                                             did_swap = line
@@ -203,6 +218,7 @@ pub async fn start_repl() {
                                                 } else {
                                                     let q: Query = tokenize!();
                                                     pipeline.push(q);
+                                                    queries.push(line.clone());
                                                 }
                                             }
                                             line = readln!(editor);
@@ -211,8 +227,16 @@ pub async fn start_repl() {
                                             line.drain(line.len() - 1..);
                                             let q: Query = tokenize!();
                                             pipeline.push(q);
+                                            queries.push(line.clone());
+                                        }
+                                        match exporter.as_mut() {
+                                            Some(exporter) => {
+                                                runner
+                                                    .export_pipeline(pipeline, &queries, exporter)
+                                                    .await
+                                            }
+                                            None => runner.run_pipeline(pipeline).await,
                                         }
-                                        runner.run_pipeline(pipeline).await;
                                         checkswap!();
                                     }
                                     _ => eskysh!("Unknown shell command"),
@@ -236,7 +260,10 @@ pub async fn start_repl() {
                             .get(..3)
                             .map(|v| v.eq_ignore_ascii_case("use"))
                             .unwrap_or(did_swap);
-                        runner.run_query(&line).await;
+                        match exporter.as_mut() {
+                            Some(exporter) => runner.export_query(&line, exporter).await,
+                            None => runner.run_query(&line).await,
+                        }
                         checkswap!();
                     }
                 }
@@ -253,6 +280,42 @@ pub async fn start_repl() {
         .unwrap();
 }
 
+/// Handle `!o [<file> [csv|json]]`: start exporting results to the given file, or go back to
+/// printing them if no file is given
+fn set_exporter(exporter: &mut Option<Exporter>, args: &str) {
+    if let Some(mut current) = exporter.take() {
+        match current.finish() {
+            Ok(()) => println!("Results were written to `{}`", current.path()),
+            Err(e) => eskysh!(format!("Failed to write to `{}`: {}", current.path(), e)),
+        }
+    }
+    let mut args = args.split_whitespace();
+    let path = match args.next() {
+        Some(path) => path,
+        None => return,
+    };
+    let format = args.next().map(|name| (name, Format::from_name(name)));
+    let format = match format {
+        Some((_, Some(format))) => Some(format),
+        Some((name, None)) => {
+            eskysh!(format!("Unknown export format `{}`", name));
+            return;
+        }
+        None => None,
+    };
+    if args.next().is_some() {
+        eskysh!("Usage: !o <file> [csv|json]");
+        return;
+    }
+    match Exporter::create(path, format) {
+        Ok(new) => {
+            println!("Writing results to `{}`. Run `!o` to stop", new.path());
+            *exporter = Some(new);
+        }
+        Err(e) => eskysh!(e),
+    }
+}
+
 fn print_help(line: &str) {
     match &line.as_bytes()[1..] {
         b"" => eskysh!("Bad shell command"),
@@ -260,6 +323,8 @@ fn print_help(line: &str) {
         b"exit" => println!("`exit` ends the shell session"),
         b"clear" => println!("`clear` clears the terminal screen"),
         b"pipe" | b"!pipe" => println!("`!pipe` lets you run pipelines using the shell"),
+        b"browse" | b"!browse" => println!("`!browse` lets you browse the database interactively"),
+        b"o" | b"!o" => println!("`!o <file> [csv|json]` exports the results of queries to a file"),
         _ => eskysh!("Unknown shell command"),
     }
 }
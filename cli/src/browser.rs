@@ -0,0 +1,467 @@
+/*
+ * Created on Sun Oct 30 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Entity browser
+//!
+//! The `!browse` shell command opens an interactive browser that lists the spaces and models
+//! in the database (using `INSPECT`), pages through the keys of a model (using `SCAN`) and
+//! previews values along with their type and size.
+
+use {
+    crate::runner::{BinaryData, Runner},
+    crossterm::{
+        cursor,
+        event::{self, Event, KeyCode, KeyEventKind},
+        queue,
+        style::{Attribute, Print, SetAttribute},
+        terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+    },
+    skytable::{error::Error, types::Array, types::RawString, Element, Query, RespCode},
+    std::io::{self, Write},
+};
+
+/// The number of keys shown on a page
+pub const PAGE_SIZE: usize = 20;
+/// The maximum number of bytes of a value that we preview
+pub const PREVIEW_LIMIT: usize = 512;
+/// The maximum number of list items that we preview
+const PREVIEW_LIST_LIMIT: usize = 20;
+
+const HELP_MENU: &str = "[up/down] move  [enter] open  [esc] back  [q] quit";
+const HELP_KEYS: &str =
+    "[up/down] move  [enter] preview  [n/p] next/previous page  [esc] back  [q] quit";
+const HELP_PREVIEW: &str = "[any key] back  [q] quit";
+
+type BrowseResult<T> = Result<T, BrowseError>;
+
+#[derive(Debug)]
+pub enum BrowseError {
+    /// An I/O error, either with the connection or with the terminal
+    Io(String),
+    /// The server returned something that we didn't expect
+    BadResponse(String),
+}
+
+impl From<Error> for BrowseError {
+    fn from(e: Error) -> Self {
+        Self::Io(e.to_string())
+    }
+}
+
+impl From<io::Error> for BrowseError {
+    fn from(e: io::Error) -> Self {
+        Self::Io(e.to_string())
+    }
+}
+
+impl core::fmt::Display for BrowseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "I/O error: {e}"),
+            Self::BadResponse(e) => write!(f, "unexpected response: {e}"),
+        }
+    }
+}
+
+/// What the user wants to do next
+#[derive(Debug, PartialEq)]
+pub enum Nav {
+    Select(usize),
+    NextPage,
+    PrevPage,
+    Back,
+    Quit,
+}
+
+/// Puts the terminal into raw mode (on an alternate screen) and restores it when dropped
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        crossterm::execute!(io::stdout(), EnterAlternateScreen, cursor::Hide)?;
+        Ok(Self)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = crossterm::execute!(io::stdout(), cursor::Show, LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Build a query from its raw arguments
+fn query(args: &[&[u8]]) -> Query {
+    let mut query = Query::new();
+    for arg in args {
+        query.push(RawString::from(arg.to_vec()));
+    }
+    query
+}
+
+fn bad_response(what: &str, el: &Element) -> BrowseError {
+    BrowseError::BadResponse(format!("{what} returned {el:?}"))
+}
+
+/// Returns the items in an array of strings (or binary strings)
+fn string_array(what: &str, el: Element) -> BrowseResult<Vec<Vec<u8>>> {
+    match el {
+        Element::Array(Array::NonNullStr(items)) => {
+            Ok(items.into_iter().map(String::into_bytes).collect())
+        }
+        Element::Array(Array::NonNullBin(items)) => Ok(items),
+        Element::RespCode(RespCode::ErrorString(e)) => Err(BrowseError::BadResponse(e)),
+        el => Err(bad_response(what, &el)),
+    }
+}
+
+/// Returns a printable representation of a key or an entity name
+pub fn display_bytes(bytes: &[u8]) -> String {
+    match core::str::from_utf8(bytes) {
+        Ok(s) => s.to_owned(),
+        Err(_) => BinaryData(bytes.to_vec()).to_string(),
+    }
+}
+
+/// Split a `SCAN` response into the cursor of the next call and the keys
+pub fn scan_page(el: Element) -> BrowseResult<(u64, Vec<Vec<u8>>)> {
+    let mut items = string_array("scan", el)?.into_iter();
+    let cursor = items
+        .next()
+        .and_then(|cursor| String::from_utf8(cursor).ok())
+        .and_then(|cursor| cursor.parse().ok())
+        .ok_or_else(|| BrowseError::BadResponse("scan returned a bad cursor".to_owned()))?;
+    Ok((cursor, items.collect()))
+}
+
+/// Describe a value: returns its type, its size and a (possibly truncated) preview
+pub fn describe_value(el: Element) -> BrowseResult<(String, String, Vec<String>)> {
+    fn preview_str(bytes: Vec<u8>, is_str: bool) -> String {
+        let truncated = bytes.len() > PREVIEW_LIMIT;
+        let mut bytes = bytes;
+        bytes.truncate(PREVIEW_LIMIT);
+        let mut preview = if is_str {
+            format!("\"{}\"", String::from_utf8_lossy(&bytes))
+        } else {
+            BinaryData(bytes).to_string()
+        };
+        if truncated {
+            preview.push_str(" ...");
+        }
+        preview
+    }
+    fn describe_list(
+        kind: &str,
+        items: Vec<Vec<u8>>,
+        is_str: bool,
+    ) -> (String, String, Vec<String>) {
+        let bytes: usize = items.iter().map(Vec::len).sum();
+        let size = format!("{} items ({bytes} bytes)", items.len());
+        let count = items.len();
+        let mut preview: Vec<String> = items
+            .into_iter()
+            .take(PREVIEW_LIST_LIMIT)
+            .enumerate()
+            .map(|(idx, item)| format!("({}) {}", idx + 1, preview_str(item, is_str)))
+            .collect();
+        if count > PREVIEW_LIST_LIMIT {
+            preview.push(format!("... and {} more", count - PREVIEW_LIST_LIMIT));
+        }
+        (format!("list<{kind}>"), size, preview)
+    }
+    let ret = match el {
+        Element::String(s) => {
+            let size = format!("{} bytes", s.len());
+            (
+                "str".to_owned(),
+                size,
+                vec![preview_str(s.into_bytes(), true)],
+            )
+        }
+        Element::Binstr(b) => {
+            let size = format!("{} bytes", b.len());
+            ("binstr".to_owned(), size, vec![preview_str(b, false)])
+        }
+        Element::Array(Array::NonNullStr(items)) => describe_list(
+            "str",
+            items.into_iter().map(String::into_bytes).collect(),
+            true,
+        ),
+        Element::Array(Array::NonNullBin(items)) => describe_list("binstr", items, false),
+        Element::RespCode(RespCode::NotFound) => {
+            return Err(BrowseError::BadResponse(
+                "the key was removed while browsing".to_owned(),
+            ))
+        }
+        el => return Err(bad_response("reading the value", &el)),
+    };
+    Ok(ret)
+}
+
+/// Draw a screen with a title, some lines of body text, an optional menu (with the index of
+/// the selected item) and a help line
+fn draw(
+    title: &str,
+    body: &[String],
+    menu: Option<(&[String], usize)>,
+    help: &str,
+) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    let (_, rows) = terminal::size()?;
+    // title, body and a blank line at the top and a blank line and the help at the bottom
+    let visible = (rows as usize).saturating_sub(body.len() + 4).max(1);
+    queue!(stdout, Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+    queue!(
+        stdout,
+        SetAttribute(Attribute::Bold),
+        Print(title),
+        SetAttribute(Attribute::Reset),
+        Print("\r\n")
+    )?;
+    for line in body {
+        queue!(stdout, Print(line), Print("\r\n"))?;
+    }
+    queue!(stdout, Print("\r\n"))?;
+    if let Some((items, selected)) = menu {
+        draw_menu(&mut stdout, items, selected, visible)?;
+        queue!(stdout, Print("\r\n"))?;
+    }
+    queue!(stdout, Print(help))?;
+    stdout.flush()
+}
+
+/// Draw a menu, scrolling it so that the selected item is visible
+fn draw_menu(
+    stdout: &mut io::Stdout,
+    items: &[String],
+    selected: usize,
+    visible: usize,
+) -> io::Result<()> {
+    if items.is_empty() {
+        queue!(stdout, Print("  (empty)\r\n"))?;
+    }
+    let first = selected.saturating_sub(visible - 1);
+    for (idx, item) in items.iter().enumerate().skip(first).take(visible) {
+        if idx == selected {
+            queue!(
+                stdout,
+                SetAttribute(Attribute::Reverse),
+                Print(format!("> {item}")),
+                SetAttribute(Attribute::Reset),
+                Print("\r\n")
+            )?;
+        } else {
+            queue!(stdout, Print(format!("  {item}\r\n")))?;
+        }
+    }
+    Ok(())
+}
+
+/// Block until the user presses a key
+fn read_key() -> io::Result<KeyCode> {
+    loop {
+        if let Event::Key(key) = event::read()? {
+            // some platforms also report releases
+            if key.kind != KeyEventKind::Release {
+                return Ok(key.code);
+            }
+        }
+    }
+}
+
+/// Show a menu and wait for the user to pick something
+fn menu(title: &str, body: &[String], items: &[String], paged: bool) -> io::Result<Nav> {
+    let help = if paged { HELP_KEYS } else { HELP_MENU };
+    let mut selected = 0;
+    loop {
+        draw(title, body, Some((items, selected)), help)?;
+        match read_key()? {
+            KeyCode::Up | KeyCode::Char('k') => selected = selected.saturating_sub(1),
+            KeyCode::Down | KeyCode::Char('j') if selected + 1 < items.len() => selected += 1,
+            KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') if !items.is_empty() => {
+                return Ok(Nav::Select(selected))
+            }
+            KeyCode::PageDown | KeyCode::Char('n') if paged => return Ok(Nav::NextPage),
+            KeyCode::PageUp | KeyCode::Char('p') if paged => return Ok(Nav::PrevPage),
+            KeyCode::Esc | KeyCode::Left | KeyCode::Backspace | KeyCode::Char('h') => {
+                return Ok(Nav::Back)
+            }
+            KeyCode::Char('q') => return Ok(Nav::Quit),
+            _ => {}
+        }
+    }
+}
+
+/// Browse the database. The current entity is restored once the user is done
+pub async fn browse(runner: &mut Runner) {
+    let origin = match runner.query(&query(&[b"whereami"])).await {
+        Ok(el) => string_array("whereami", el),
+        Err(e) => Err(e.into()),
+    };
+    let ret = match origin {
+        Ok(origin) => {
+            let ret = match TerminalGuard::enter() {
+                Ok(_guard) => browse_spaces(runner).await,
+                Err(e) => Err(e.into()),
+            };
+            // go back to where we were
+            let origin: Vec<String> = origin.iter().map(|id| display_bytes(id)).collect();
+            let restore = query(&[b"use", origin.join(".").as_bytes()]);
+            ret.and(runner.query(&restore).await.map(|_| ()).map_err(Into::into))
+        }
+        Err(e) => Err(e),
+    };
+    if let Err(e) = ret {
+        eskysh!(format!("browser failed with {e}"));
+    }
+}
+
+async fn browse_spaces(runner: &mut Runner) -> BrowseResult<()> {
+    loop {
+        let spaces = string_array(
+            "inspect spaces",
+            runner.query(&query(&[b"inspect", b"spaces"])).await?,
+        )?;
+        let names: Vec<String> = spaces.iter().map(|space| display_bytes(space)).collect();
+        match menu("Spaces", &[], &names, false)? {
+            Nav::Select(idx) => {
+                if browse_models(runner, &names[idx]).await? == Nav::Quit {
+                    return Ok(());
+                }
+            }
+            Nav::Back | Nav::Quit => return Ok(()),
+            _ => {}
+        }
+    }
+}
+
+async fn browse_models(runner: &mut Runner, space: &str) -> BrowseResult<Nav> {
+    loop {
+        let models = string_array(
+            "inspect space",
+            runner
+                .query(&query(&[b"inspect", b"space", space.as_bytes()]))
+                .await?,
+        )?;
+        let names: Vec<String> = models.iter().map(|model| display_bytes(model)).collect();
+        match menu(&format!("Models in `{space}`"), &[], &names, false)? {
+            Nav::Select(idx) => {
+                let entity = format!("{space}.{}", names[idx]);
+                if browse_keys(runner, &entity).await? == Nav::Quit {
+                    return Ok(Nav::Quit);
+                }
+            }
+            nav @ (Nav::Back | Nav::Quit) => return Ok(nav),
+            _ => {}
+        }
+    }
+}
+
+async fn browse_keys(runner: &mut Runner, entity: &str) -> BrowseResult<Nav> {
+    let description = match runner
+        .query(&query(&[b"inspect", b"model", entity.as_bytes()]))
+        .await?
+    {
+        Element::String(description) => description,
+        el => return Err(bad_response("inspect model", &el)),
+    };
+    // values are read with GET/LGET, which work on the current model
+    match runner.query(&query(&[b"use", entity.as_bytes()])).await? {
+        Element::RespCode(RespCode::Okay) => {}
+        el => return Err(bad_response("use", &el)),
+    }
+    let is_list = description.contains("list<");
+    // the cursor that each page starts at, so that we can go back
+    let mut pages = vec![0];
+    loop {
+        let page = pages.len() - 1;
+        let (keys, next) = fetch_page(runner, pages[page]).await?;
+        let names: Vec<String> = keys.iter().map(|key| display_bytes(key)).collect();
+        let title = format!("Keys in `{entity}` (page {})", page + 1);
+        match menu(&title, &[description.clone()], &names, true)? {
+            Nav::Select(idx) => {
+                if preview(runner, &keys[idx], is_list).await? == Nav::Quit {
+                    return Ok(Nav::Quit);
+                }
+            }
+            Nav::NextPage if next != 0 => pages.push(next),
+            Nav::PrevPage if page != 0 => {
+                pages.pop();
+            }
+            nav @ (Nav::Back | Nav::Quit) => return Ok(nav),
+            _ => {}
+        }
+    }
+}
+
+/// Fetch a page of up to [`PAGE_SIZE`] keys of the current model, starting at `cursor`. Returns
+/// the keys along with the cursor that the next page starts at (zero if this is the last page).
+/// A key that is added or removed while we page may show up twice
+async fn fetch_page(runner: &mut Runner, mut cursor: u64) -> BrowseResult<(Vec<Vec<u8>>, u64)> {
+    let mut keys = Vec::with_capacity(PAGE_SIZE);
+    loop {
+        // SCAN returns at most COUNT keys, so the page ends exactly at a cursor
+        let (start, count) = (cursor.to_string(), (PAGE_SIZE - keys.len()).to_string());
+        let (next, page) = scan_page(
+            runner
+                .query(&query(&[
+                    b"scan",
+                    start.as_bytes(),
+                    b"count",
+                    count.as_bytes(),
+                ]))
+                .await?,
+        )?;
+        keys.extend(page);
+        cursor = next;
+        if cursor == 0 || keys.len() >= PAGE_SIZE {
+            return Ok((keys, cursor));
+        }
+    }
+}
+
+async fn preview(runner: &mut Runner, key: &[u8], is_list: bool) -> BrowseResult<Nav> {
+    let action: &[u8] = if is_list { b"lget" } else { b"get" };
+    let value = runner.query(&query(&[action, key])).await?;
+    let (kind, size, preview) = describe_value(value)?;
+    let mut body = vec![
+        format!("type: {kind}"),
+        format!("size: {size}"),
+        String::new(),
+    ];
+    body.extend(preview);
+    draw(
+        &format!("Key {}", display_bytes(key)),
+        &body,
+        None,
+        HELP_PREVIEW,
+    )?;
+    match read_key()? {
+        KeyCode::Char('q') => Ok(Nav::Quit),
+        _ => Ok(Nav::Back),
+    }
+}
@@ -0,0 +1,317 @@
+/*
+ * Created on Mon Oct 31 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Query result export
+//!
+//! The `!o <file> [csv|json]` shell command writes the results of all subsequent queries to a
+//! file instead of the terminal, until `!o` is run without a file. The format is picked from the
+//! file extension unless it is passed explicitly:
+//! - **JSON**: the file holds an array of `{"query": ..., "result": ...}` objects. Binary strings
+//! are written as `{"base64": ...}` objects and response codes as `{"status": "okay"}` or
+//! `{"error": ...}` objects
+//! - **CSV**: every scalar result is a row with a single field, every item in an array is a row
+//! and nested arrays are rows with multiple fields. Binary strings are written as `base64:...`
+
+use {
+    skytable::{
+        types::{Array, FlatElement},
+        Element, RespCode,
+    },
+    std::{
+        fs::File,
+        io::{self, BufWriter, Write},
+    },
+};
+
+/// The prefix for binary strings in CSV files
+pub const CSV_BINARY_PREFIX: &str = "base64:";
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    Csv,
+    Json,
+}
+
+impl Format {
+    /// Returns the format with the given name (case insensitive)
+    pub fn from_name(name: &str) -> Option<Self> {
+        if name.eq_ignore_ascii_case("csv") {
+            Some(Self::Csv)
+        } else if name.eq_ignore_ascii_case("json") {
+            Some(Self::Json)
+        } else {
+            None
+        }
+    }
+    /// Returns the format for the given path, based on its extension
+    pub fn from_path(path: &str) -> Option<Self> {
+        path.rsplit_once('.')
+            .and_then(|(_, extension)| Self::from_name(extension))
+    }
+}
+
+/// A value in an exported result
+#[derive(Debug, PartialEq)]
+pub enum Value {
+    Null,
+    Str(String),
+    Bin(Vec<u8>),
+    Int(u64),
+    Float(f32),
+    Okay,
+    Error(String),
+    Array(Vec<Value>),
+}
+
+fn rcode_value(rcode: RespCode) -> Value {
+    let error = match rcode {
+        RespCode::Okay => return Value::Okay,
+        RespCode::ErrorString(st) => return Value::Error(st),
+        RespCode::ActionError => "Action Error",
+        RespCode::OtherError => "Other Error",
+        RespCode::NotFound => "Not Found",
+        RespCode::OverwriteError => "Overwrite Error",
+        RespCode::PacketError => "Packet Error",
+        RespCode::ServerError => "Server Error",
+        RespCode::UnknownDataType => "Unknown data type",
+        RespCode::EncodingError => "Encoding error",
+        RespCode::AuthBadCredentials => "auth bad credentials",
+        RespCode::AuthPermissionError => "auth permission error",
+        _ => "Unknown error",
+    };
+    Value::Error(error.to_owned())
+}
+
+impl Value {
+    /// Returns the value for the given element, if we know how to export it
+    pub fn from_element(el: Element) -> Option<Self> {
+        let value = match el {
+            Element::String(st) => Self::Str(st),
+            Element::Binstr(bin) => Self::Bin(bin),
+            Element::UnsignedInt(int) => Self::Int(int),
+            Element::Float(float) => Self::Float(float),
+            Element::RespCode(rcode) => rcode_value(rcode),
+            Element::Array(Array::Str(srr)) => Self::Array(
+                srr.into_iter()
+                    .map(|st| st.map_or(Self::Null, Self::Str))
+                    .collect(),
+            ),
+            Element::Array(Array::Bin(brr)) => Self::Array(
+                brr.into_iter()
+                    .map(|bin| bin.map_or(Self::Null, Self::Bin))
+                    .collect(),
+            ),
+            Element::Array(Array::NonNullStr(srr)) => {
+                Self::Array(srr.into_iter().map(Self::Str).collect())
+            }
+            Element::Array(Array::NonNullBin(brr)) => {
+                Self::Array(brr.into_iter().map(Self::Bin).collect())
+            }
+            Element::Array(Array::Flat(frr)) => Self::Array(
+                frr.into_iter()
+                    .map(|item| match item {
+                        FlatElement::String(st) => Some(Self::Str(st)),
+                        FlatElement::Binstr(bin) => Some(Self::Bin(bin)),
+                        FlatElement::UnsignedInt(int) => Some(Self::Int(int)),
+                        FlatElement::RespCode(rcode) => Some(rcode_value(rcode)),
+                        _ => None,
+                    })
+                    .collect::<Option<_>>()?,
+            ),
+            Element::Array(Array::Recursive(arr)) => Self::Array(
+                arr.into_iter()
+                    .map(Self::from_element)
+                    .collect::<Option<_>>()?,
+            ),
+            _ => return None,
+        };
+        Some(value)
+    }
+    /// Write this value as JSON
+    pub fn write_json(&self, w: &mut impl Write) -> io::Result<()> {
+        match self {
+            Self::Null => w.write_all(b"null"),
+            Self::Str(st) => write_json_str(w, st),
+            Self::Bin(bin) => {
+                w.write_all(b"{\"base64\": ")?;
+                write_json_str(w, &base64::encode(bin))?;
+                w.write_all(b"}")
+            }
+            Self::Int(int) => write!(w, "{int}"),
+            Self::Float(float) if float.is_finite() => write!(w, "{float}"),
+            Self::Float(_) => w.write_all(b"null"),
+            Self::Okay => w.write_all(b"{\"status\": \"okay\"}"),
+            Self::Error(e) => {
+                w.write_all(b"{\"error\": ")?;
+                write_json_str(w, e)?;
+                w.write_all(b"}")
+            }
+            Self::Array(items) => {
+                w.write_all(b"[")?;
+                for (idx, item) in items.iter().enumerate() {
+                    if idx != 0 {
+                        w.write_all(b", ")?;
+                    }
+                    item.write_json(w)?;
+                }
+                w.write_all(b"]")
+            }
+        }
+    }
+    /// Returns this value as a (unescaped) CSV field
+    fn csv_field(&self) -> String {
+        match self {
+            Self::Null => String::new(),
+            Self::Str(st) => st.clone(),
+            Self::Bin(bin) => format!("{CSV_BINARY_PREFIX}{}", base64::encode(bin)),
+            Self::Int(int) => int.to_string(),
+            Self::Float(float) => float.to_string(),
+            Self::Okay => "(Okay)".to_owned(),
+            Self::Error(e) => format!("({e})"),
+            Self::Array(items) => {
+                let fields: Vec<String> = items.iter().map(Self::csv_field).collect();
+                fields.join(" ")
+            }
+        }
+    }
+    /// Returns the CSV rows for this value
+    pub fn csv_rows(&self) -> Vec<Vec<String>> {
+        match self {
+            Self::Array(items) => items
+                .iter()
+                .map(|item| match item {
+                    Self::Array(fields) => fields.iter().map(Self::csv_field).collect(),
+                    item => vec![item.csv_field()],
+                })
+                .collect(),
+            value => vec![vec![value.csv_field()]],
+        }
+    }
+}
+
+fn write_json_str(w: &mut impl Write, st: &str) -> io::Result<()> {
+    w.write_all(b"\"")?;
+    for chr in st.chars() {
+        match chr {
+            '"' => w.write_all(b"\\\"")?,
+            '\\' => w.write_all(b"\\\\")?,
+            '\n' => w.write_all(b"\\n")?,
+            '\r' => w.write_all(b"\\r")?,
+            '\t' => w.write_all(b"\\t")?,
+            chr if (chr as u32) < 0x20 => write!(w, "\\u{:04x}", chr as u32)?,
+            chr => write!(w, "{chr}")?,
+        }
+    }
+    w.write_all(b"\"")
+}
+
+/// Escape a CSV field (RFC 4180)
+pub fn escape_csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\r', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+/// Writes query results to a file
+pub struct Exporter {
+    path: String,
+    format: Format,
+    file: BufWriter<File>,
+    written: usize,
+    finished: bool,
+}
+
+impl Exporter {
+    /// Create (or truncate) the file at `path`. If `format` isn't provided, it is picked from the
+    /// file extension
+    pub fn create(path: &str, format: Option<Format>) -> Result<Self, String> {
+        let format = format
+            .or_else(|| Format::from_path(path))
+            .ok_or_else(|| format!("can't tell the format for `{path}`; pass `csv` or `json`"))?;
+        let mut file = File::create(path)
+            .map(BufWriter::new)
+            .map_err(|e| format!("failed to create `{path}`: {e}"))?;
+        if format == Format::Json {
+            file.write_all(b"[")
+                .map_err(|e| format!("failed to write to `{path}`: {e}"))?;
+        }
+        Ok(Self {
+            path: path.to_owned(),
+            format,
+            file,
+            written: 0,
+            finished: false,
+        })
+    }
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    /// Write the result of a query
+    pub fn write(&mut self, query: &str, result: &Value) -> io::Result<()> {
+        match self.format {
+            Format::Json => {
+                if self.written != 0 {
+                    self.file.write_all(b",")?;
+                }
+                self.file.write_all(b"\n  {\"query\": ")?;
+                write_json_str(&mut self.file, query)?;
+                self.file.write_all(b", \"result\": ")?;
+                result.write_json(&mut self.file)?;
+                self.file.write_all(b"}")?;
+            }
+            Format::Csv => {
+                for row in result.csv_rows() {
+                    let row: Vec<String> = row.iter().map(|f| escape_csv_field(f)).collect();
+                    self.file.write_all(row.join(",").as_bytes())?;
+                    self.file.write_all(b"\r\n")?;
+                }
+            }
+        }
+        self.written += 1;
+        // don't hold on to results if the shell goes away
+        self.file.flush()
+    }
+    /// Terminate the file and flush it. This is also done when the exporter is dropped, but
+    /// errors are ignored then
+    pub fn finish(&mut self) -> io::Result<()> {
+        if !self.finished {
+            self.finished = true;
+            if self.format == Format::Json {
+                self.file.write_all(b"\n]\n")?;
+            }
+            self.file.flush()?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Exporter {
+    fn drop(&mut self) {
+        let _ = self.finish();
+    }
+}
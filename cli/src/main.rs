@@ -30,7 +30,9 @@
 #[macro_use]
 mod macros;
 mod argparse;
+mod browser;
 mod cli;
+mod export;
 mod runner;
 mod tokenizer;
 
@@ -25,7 +25,10 @@
 */
 
 use {
-    crate::tokenizer,
+    crate::{
+        export::{Exporter, Value},
+        tokenizer,
+    },
     core::fmt,
     crossterm::style::{Color, Print, ResetColor, SetForegroundColor},
     skytable::{
@@ -67,6 +70,26 @@ impl Runner {
             print_element(resp);
         }
     }
+    /// Run a pipeline and write the responses to the exporter instead of printing them.
+    /// `queries` are the queries in the pipeline, as entered
+    pub async fn export_pipeline(
+        &mut self,
+        pipeline: Pipeline,
+        queries: &[String],
+        exporter: &mut Exporter,
+    ) {
+        let ret = match self {
+            Self::Insecure(con) => con.run_pipeline(pipeline).await,
+            Self::Secure(con) => con.run_pipeline(pipeline).await,
+        };
+        let retok = match ret {
+            Ok(r) => r,
+            Err(e) => fatal!("An I/O error occurred while querying: {}", e),
+        };
+        for (query, resp) in queries.iter().zip(retok) {
+            export_element(query, resp, exporter);
+        }
+    }
     pub async fn run_query(&mut self, unescaped: &str) {
         let query: Query = match tokenizer::get_query(unescaped.as_bytes()) {
             Ok(q) => q,
@@ -84,6 +107,27 @@ impl Runner {
             Err(e) => fatal!("An I/O error occurred while querying: {}", e),
         }
     }
+    /// Run a query and write the response to the exporter instead of printing it
+    pub async fn export_query(&mut self, unescaped: &str, exporter: &mut Exporter) {
+        let query: Query = match tokenizer::get_query(unescaped.as_bytes()) {
+            Ok(q) => q,
+            Err(e) => {
+                err!(format!("[Syntax Error: {}]\n", e));
+                return;
+            }
+        };
+        match self.query(&query).await {
+            Ok(resp) => export_element(unescaped, resp, exporter),
+            Err(e) => fatal!("An I/O error occurred while querying: {}", e),
+        }
+    }
+    /// Run a query and return the response instead of printing it
+    pub async fn query(&mut self, query: &Query) -> SkyResult<Element> {
+        match self {
+            Self::Insecure(con) => con.run_query_raw(query).await,
+            Self::Secure(con) => con.run_query_raw(query).await,
+        }
+    }
     pub async fn check_entity(&mut self, blank: &mut String, prompt: &mut String) {
         let query: Query = tokenizer::get_query(b"whereami").unwrap();
         let ret = match self {
@@ -144,6 +188,17 @@ fn print_element(el: Element) {
     }
 }
 
+fn export_element(query: &str, el: Element, exporter: &mut Exporter) {
+    match Value::from_element(el) {
+        Some(value) => {
+            if let Err(e) = exporter.write(query, &value) {
+                eskysh!(format!("Failed to write to `{}`: {}", exporter.path(), e));
+            }
+        }
+        None => eskysh!("The server possibly sent a newer data type that we can't export"),
+    }
+}
+
 fn print_rcode(rcode: RespCode, idx: Option<usize>) {
     match rcode {
         RespCode::Okay => write_okay!(),
@@ -229,7 +284,7 @@ fn print_array(array: Vec<Element>) {
     }
 }
 
-pub struct BinaryData(Vec<u8>);
+pub struct BinaryData(pub(crate) Vec<u8>);
 
 impl fmt::Display for BinaryData {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> Result<(), fmt::Error> {
@@ -194,3 +194,116 @@ fn test_blueql_query() {
         vec!["create model mymodel(string, binary)"]
     );
 }
+
+mod browser {
+    use crate::browser::{describe_value, display_bytes, scan_page, PREVIEW_LIMIT};
+    use skytable::{types::Array, Element, RespCode};
+
+    #[test]
+    fn test_scan_page() {
+        let page = vec![
+            "9223372036854775813".to_owned(),
+            "a".to_owned(),
+            "b".to_owned(),
+        ];
+        assert_eq!(
+            scan_page(Element::Array(Array::NonNullStr(page))).unwrap(),
+            ((1 << 63) + 5, vec![b"a".to_vec(), b"b".to_vec()])
+        );
+        let last = vec![b"0".to_vec(), vec![0xFF]];
+        assert_eq!(
+            scan_page(Element::Array(Array::NonNullBin(last))).unwrap(),
+            (0, vec![vec![0xFF]])
+        );
+        assert!(scan_page(Element::Array(Array::NonNullStr(vec!["x".to_owned()]))).is_err());
+        assert!(scan_page(Element::Array(Array::NonNullStr(vec![]))).is_err());
+        assert!(scan_page(Element::RespCode(RespCode::ErrorString(
+            "container-not-found".to_owned()
+        )))
+        .is_err());
+    }
+
+    #[test]
+    fn test_display_bytes() {
+        assert_eq!(display_bytes(b"sayan"), "sayan");
+        assert_eq!(display_bytes(&[0xFF, b'\n']), r#"b"\xff\n""#);
+    }
+
+    #[test]
+    fn test_describe_value() {
+        let (kind, size, preview) = describe_value(Element::String("hello".to_owned())).unwrap();
+        assert_eq!(kind, "str");
+        assert_eq!(size, "5 bytes");
+        assert_eq!(preview, vec!["\"hello\"".to_owned()]);
+        let (kind, size, preview) =
+            describe_value(Element::Binstr(vec![b'x'; PREVIEW_LIMIT + 1])).unwrap();
+        assert_eq!(kind, "binstr");
+        assert_eq!(size, format!("{} bytes", PREVIEW_LIMIT + 1));
+        assert!(preview[0].ends_with("\" ..."));
+        let list = vec!["a".to_owned(), "bc".to_owned()];
+        let (kind, size, preview) =
+            describe_value(Element::Array(Array::NonNullStr(list))).unwrap();
+        assert_eq!(kind, "list<str>");
+        assert_eq!(size, "2 items (3 bytes)");
+        assert_eq!(
+            preview,
+            vec!["(1) \"a\"".to_owned(), "(2) \"bc\"".to_owned()]
+        );
+    }
+}
+
+mod export {
+    use crate::export::{escape_csv_field, Format, Value};
+    use skytable::{types::Array, Element, RespCode};
+
+    fn json(value: &Value) -> String {
+        let mut buf = Vec::new();
+        value.write_json(&mut buf).unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(Format::from_path("results.json"), Some(Format::Json));
+        assert_eq!(Format::from_path("results.CSV"), Some(Format::Csv));
+        assert_eq!(Format::from_path("results"), None);
+        assert_eq!(Format::from_name("txt"), None);
+    }
+
+    #[test]
+    fn test_json_export() {
+        let value = Value::from_element(Element::Array(Array::Bin(vec![
+            Some(vec![0xFF, 0x00]),
+            None,
+        ])))
+        .unwrap();
+        assert_eq!(json(&value), r#"[{"base64": "/wA="}, null]"#);
+        let value = Value::from_element(Element::String("say \"hi\"\n".to_owned())).unwrap();
+        assert_eq!(json(&value), r#""say \"hi\"\n""#);
+        let value = Value::from_element(Element::RespCode(RespCode::NotFound)).unwrap();
+        assert_eq!(json(&value), r#"{"error": "Not Found"}"#);
+        let value = Value::from_element(Element::RespCode(RespCode::Okay)).unwrap();
+        assert_eq!(json(&value), r#"{"status": "okay"}"#);
+    }
+
+    #[test]
+    fn test_csv_export() {
+        assert_eq!(escape_csv_field("plain"), "plain");
+        assert_eq!(escape_csv_field("a,b"), "\"a,b\"");
+        assert_eq!(escape_csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        let value = Value::from_element(Element::Array(Array::NonNullBin(vec![
+            b"x".to_vec(),
+            vec![0xFF],
+        ])))
+        .unwrap();
+        assert_eq!(
+            value.csv_rows(),
+            vec![
+                vec!["base64:eA==".to_owned()],
+                vec!["base64:/w==".to_owned()]
+            ]
+        );
+        let value = Value::from_element(Element::UnsignedInt(100)).unwrap();
+        assert_eq!(value.csv_rows(), vec![vec!["100".to_owned()]]);
+    }
+}
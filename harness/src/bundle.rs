@@ -27,6 +27,8 @@
 use {
     crate::{
         build::{self, BuildMode},
+        sbom::{self, SBOM_FILE_NAME},
+        sign::{ChecksumManifest, SigningKey, MANIFEST_FILE_NAME, SIGNATURE_EXTENSION},
         util, HarnessError, HarnessResult,
     },
     libsky::VERSION,
@@ -51,14 +53,29 @@ pub fn get_bundle_name() -> String {
 
 /// Create a bundle using the provided mode
 pub fn bundle(mode: BuildMode) -> HarnessResult<()> {
+    // load the key first so that we fail before building if it is bad
+    let signing_key = SigningKey::from_env()?;
+    match signing_key {
+        Some(ref key) => info!("Bundle will be signed with:\n{}", key.public_key()?),
+        None => warn!("No signing key was provided. The bundle will not be signed"),
+    }
     let target_folder = build::build(mode)?;
     // now package
-    package_binaries(target_folder, mode)?;
+    let bundle_file_name = package_binaries(target_folder, mode, signing_key.as_ref())?;
+    // sign the bundle itself
+    if let Some(key) = signing_key {
+        key.sign_file(&bundle_file_name)?;
+    }
     Ok(())
 }
 
-/// Package the binaries into a ZIP file
-fn package_binaries(target_folder: PathBuf, mode: BuildMode) -> HarnessResult<()> {
+/// Package the binaries into a ZIP file along with the SBOM, the checksum manifest and (if
+/// a key is available) the manifest's signature. Returns the name of the bundle
+fn package_binaries(
+    target_folder: PathBuf,
+    mode: BuildMode,
+    signing_key: Option<&SigningKey>,
+) -> HarnessResult<String> {
     // get the file index
     let file_index = build::get_files_index(&target_folder);
     // get the bundle file name
@@ -70,6 +87,8 @@ fn package_binaries(target_folder: PathBuf, mode: BuildMode) -> HarnessResult<()
     let mut zip = ZipWriter::new(bundle_file);
     // create a temp buffer
     let mut buffer = Vec::new();
+    // the checksums of everything in the bundle
+    let mut manifest = ChecksumManifest::new();
     // ZIP settings
     let options = FileOptions::default()
         .unix_permissions(0o755)
@@ -87,8 +106,28 @@ fn package_binaries(target_folder: PathBuf, mode: BuildMode) -> HarnessResult<()
         })?;
         f.read_to_end(&mut buffer).unwrap();
         zip.write_all(&*buffer).unwrap();
+        manifest.add(&name.to_string_lossy(), &buffer);
         buffer.clear();
     }
+    let options = options.unix_permissions(0o644);
+    // add the SBOM
+    info!("Generating SBOM");
+    let sbom = sbom::generate()?;
+    zip.start_file(SBOM_FILE_NAME, options).unwrap();
+    zip.write_all(&sbom).unwrap();
+    manifest.add(SBOM_FILE_NAME, &sbom);
+    // add the manifest and its signature
+    zip.start_file(MANIFEST_FILE_NAME, options).unwrap();
+    zip.write_all(manifest.as_bytes()).unwrap();
+    if let Some(key) = signing_key {
+        let signature = key.sign(manifest.as_bytes(), MANIFEST_FILE_NAME)?;
+        zip.start_file(
+            format!("{MANIFEST_FILE_NAME}.{SIGNATURE_EXTENSION}"),
+            options,
+        )
+        .unwrap();
+        zip.write_all(signature.as_bytes()).unwrap();
+    }
     zip.finish().unwrap();
-    Ok(())
+    Ok(bundle_file_name)
 }
@@ -39,7 +39,8 @@ OPTIONS:
 SUBCOMMANDS:
     test       Run the full test suite
     bundle     Build the bundle
-    bundle-dbg Build the debug bundle \
+    bundle-dbg Build the debug bundle
+    profile    Profile the server and generate flamegraphs \
 ";
 
 #[derive(Copy, Clone)]
@@ -47,6 +48,7 @@ pub enum HarnessWhat {
     Test,
     Bundle(BuildMode),
     LinuxPackage(LinuxPackageType),
+    Profile,
 }
 
 impl HarnessWhat {
@@ -54,6 +56,7 @@ impl HarnessWhat {
     const CLI_BUNDLE: &'static str = "bundle";
     const CLI_BUNDLE_DEBUG: &'static str = "bundle-dbg";
     const CLI_DEB: &'static str = "deb";
+    const CLI_PROFILE: &'static str = "profile";
     const CLI_ARG_HELP: &'static str = "--help";
     const CLI_ARG_HELP_SHORT: &'static str = "-h";
     /// Returns the target _harness mode_ from env
@@ -73,6 +76,7 @@ impl HarnessWhat {
             Self::CLI_BUNDLE_DEBUG => HarnessWhat::Bundle(BuildMode::Debug),
             Self::CLI_ARG_HELP_SHORT | Self::CLI_ARG_HELP => display_help(),
             Self::CLI_DEB => HarnessWhat::LinuxPackage(LinuxPackageType::Deb),
+            Self::CLI_PROFILE => HarnessWhat::Profile,
             unknown_arg => return Err(HarnessError::UnknownCommand(unknown_arg.to_string())),
         };
         Ok(ret)
@@ -82,6 +86,7 @@ impl HarnessWhat {
             HarnessWhat::Test => "test suite".to_owned(),
             HarnessWhat::Bundle(mode) => format!("{} bundle", mode.to_string()),
             HarnessWhat::LinuxPackage(pkg) => format!("Linux package {}", pkg.to_string()),
+            HarnessWhat::Profile => "profiles".to_owned(),
         }
     }
 }
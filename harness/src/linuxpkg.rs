@@ -27,6 +27,7 @@
 use {
     crate::{
         build::{self, BuildMode},
+        sign::SigningKey,
         {util, HarnessResult},
     },
     libsky::VERSION,
@@ -68,6 +69,12 @@ impl ToString for LinuxPackageType {
 
 /// Creates a Linux package for the provided Linux package type
 pub fn create_linuxpkg(package_type: LinuxPackageType) -> HarnessResult<()> {
+    // load the key first so that we fail before building if it is bad
+    let signing_key = SigningKey::from_env()?;
+    match signing_key {
+        Some(ref key) => info!("Package will be signed with:\n{}", key.public_key()?),
+        None => warn!("No signing key was provided. The package will not be signed"),
+    }
     info!("Building binaries for Linux package");
     let _ = build::build(BuildMode::Release)?;
     info!("Creating Linux package");
@@ -92,6 +99,9 @@ pub fn create_linuxpkg(package_type: LinuxPackageType) -> HarnessResult<()> {
             util::handle_child("build dpkg", command)?;
         }
     }
+    if let Some(key) = signing_key {
+        key.sign_file(&filename)?;
+    }
     info!("Done building Linux package: {filename}");
     Ok(())
 }
@@ -34,6 +34,9 @@ mod cli;
 mod error;
 mod linuxpkg;
 mod presetup;
+mod profile;
+mod sbom;
+mod sign;
 mod test;
 #[cfg(test)]
 mod tests;
@@ -67,6 +70,7 @@ fn runner() -> HarnessResult<()> {
         HarnessWhat::Test => test::run_test()?,
         HarnessWhat::Bundle(bundle_mode) => bundle::bundle(bundle_mode)?,
         HarnessWhat::LinuxPackage(pkg) => linuxpkg::create_linuxpkg(pkg)?,
+        HarnessWhat::Profile => profile::run_profile()?,
     }
     info!(
         "Successfully finished running harness for {}",
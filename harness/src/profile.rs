@@ -0,0 +1,327 @@
+/*
+ * Created on Fri Oct 28 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Profiling
+//!
+//! This module runs `skyd` under a sampling profiler (`perf` on Linux and `dtrace` on macOS)
+//! while `sky-bench` drives a fixed set of workloads (see [`SCENARIOS`]), and then renders a
+//! flamegraph for every scenario using [inferno](https://github.com/jonhoo/inferno). The folded
+//! stacks are kept alongside the flamegraphs so that profiles from two runs can be compared with
+//! `inferno-diff-folded`.
+
+use {
+    crate::{util, HarnessError, HarnessResult},
+    libsky::VERSION,
+    std::{
+        fs,
+        net::TcpStream,
+        path::{Path, PathBuf},
+        process::Command,
+    },
+};
+
+/// The host that the profiled server listens on
+const PROFILE_HOST: &str = "127.0.0.1";
+/// The port that the profiled server listens on
+const PROFILE_PORT: u16 = 2003;
+/// The working directory for the profiled server
+const PROFILE_SERVER_DIR: &str = "profile-server";
+
+/// A standardized benchmark workload
+pub struct Scenario {
+    /// the name of the scenario (also used for the output files)
+    pub name: &'static str,
+    /// the number of simultaneous clients
+    pub connections: usize,
+    /// the size of each key and value
+    pub kvsize: usize,
+    /// the number of queries to run
+    pub queries: usize,
+}
+
+impl Scenario {
+    const fn new(name: &'static str, connections: usize, kvsize: usize, queries: usize) -> Self {
+        Self {
+            name,
+            connections,
+            kvsize,
+            queries,
+        }
+    }
+    /// Returns the `sky-bench` arguments for this scenario
+    pub fn bench_args(&self) -> Vec<String> {
+        vec![
+            "--host".to_owned(),
+            PROFILE_HOST.to_owned(),
+            "--port".to_owned(),
+            PROFILE_PORT.to_string(),
+            "--connections".to_owned(),
+            self.connections.to_string(),
+            "--kvsize".to_owned(),
+            self.kvsize.to_string(),
+            "--queries".to_owned(),
+            self.queries.to_string(),
+            "--runs".to_owned(),
+            "1".to_owned(),
+        ]
+    }
+}
+
+/// The scenarios that we profile. Changing these makes profiles incomparable with older ones,
+/// so only do so if you have a good reason to
+pub const SCENARIOS: [Scenario; 3] = [
+    Scenario::new("default", 10, 3, 100_000),
+    Scenario::new("large-values", 10, 1024, 50_000),
+    Scenario::new("high-concurrency", 64, 3, 200_000),
+];
+
+/// A sampling profiler
+#[derive(Copy, Clone)]
+enum Profiler {
+    Perf,
+    Dtrace,
+}
+
+impl Profiler {
+    /// Returns the profiler for this platform
+    fn for_platform() -> HarnessResult<Self> {
+        if cfg!(target_os = "linux") {
+            Ok(Self::Perf)
+        } else if cfg!(target_os = "macos") {
+            Ok(Self::Dtrace)
+        } else {
+            Err(HarnessError::Other(
+                "Profiling is only supported on Linux (perf) and macOS (dtrace)".to_owned(),
+            ))
+        }
+    }
+    /// Returns the command that runs `skyd` with `server_args` under the profiler, writing
+    /// the samples to `output`
+    fn record_cmd(&self, server_args: &[String], output: &Path) -> Command {
+        let output = output.to_string_lossy().to_string();
+        match self {
+            Self::Perf => {
+                let mut cmd = cmd!(
+                    "perf",
+                    "record",
+                    "-F",
+                    "997",
+                    "--call-graph",
+                    "dwarf",
+                    "-o",
+                    output,
+                    "--"
+                );
+                cmd.args(server_args);
+                cmd
+            }
+            Self::Dtrace => cmd!(
+                "dtrace",
+                "-x",
+                "ustackframes=100",
+                "-n",
+                "profile-997 /pid == $target/ { @[ustack()] = count(); }",
+                "-o",
+                output,
+                "-c",
+                server_args.join(" ")
+            ),
+        }
+    }
+    /// Collapse the samples in `raw` into folded stacks
+    fn fold(&self, raw: &Path) -> HarnessResult<Vec<u8>> {
+        match self {
+            Self::Perf => {
+                let script =
+                    util::get_child_output("perf script", cmd!("perf", "script", "-i", raw))?;
+                let script_path = raw.with_extension("script");
+                fs::write(&script_path, script).map_err(|e| {
+                    HarnessError::Other(format!("Failed to write perf script with error: {e}"))
+                })?;
+                util::get_child_output(
+                    "collapse perf stacks",
+                    cmd!("inferno-collapse-perf", &script_path),
+                )
+            }
+            Self::Dtrace => util::get_child_output(
+                "collapse dtrace stacks",
+                cmd!("inferno-collapse-dtrace", raw),
+            ),
+        }
+    }
+}
+
+/// Returns the name of the directory that the profiles are written to
+pub fn get_profile_dir_name() -> String {
+    let mut dirname = format!("sky-profile-v{VERSION}");
+    if let Some(artifact) = util::get_var(util::VAR_ARTIFACT) {
+        dirname.push('-');
+        dirname.push_str(&artifact);
+    }
+    dirname
+}
+
+/// Build optimized binaries that still have debug info, so that the stacks can be symbolized
+fn build_binaries() -> HarnessResult<PathBuf> {
+    let mut build_args = vec![
+        "build".to_owned(),
+        "--release".to_owned(),
+        "-p".to_owned(),
+        "skyd".to_owned(),
+        "-p".to_owned(),
+        "sky-bench".to_owned(),
+    ];
+    if let Some(t) = util::get_var(util::VAR_TARGET) {
+        build_args.push("--target".to_owned());
+        build_args.push(t);
+    }
+    let mut cmd = Command::new("cargo");
+    cmd.args(&build_args)
+        .env("CARGO_PROFILE_RELEASE_DEBUG", "true")
+        .env("CARGO_PROFILE_RELEASE_STRIP", "none");
+    util::handle_child("build binaries with debug info", cmd)?;
+    Ok(util::get_target_folder(crate::build::BuildMode::Release))
+}
+
+/// Waits for the profiled server to start accepting connections
+fn wait_for_startup() -> HarnessResult<()> {
+    let mut backoff = 1;
+    while TcpStream::connect((PROFILE_HOST, PROFILE_PORT)).is_err() {
+        if backoff > 64 {
+            return Err(HarnessError::Other(format!(
+                "Startup backoff elapsed. Server at {PROFILE_HOST}:{PROFILE_PORT} did not respond."
+            )));
+        }
+        info!("Profiled server not started. Sleeping for {backoff} second(s) ...");
+        util::sleep_sec(backoff);
+        backoff *= 2;
+    }
+    Ok(())
+}
+
+/// Run the scenario under the profiler and return the path to the raw samples. `out_dir`
+/// must be absolute since the server runs in its own directory
+fn record(
+    profiler: Profiler,
+    scenario: &Scenario,
+    target_folder: &Path,
+    out_dir: &Path,
+) -> HarnessResult<PathBuf> {
+    // start from an empty data directory every time
+    let _ = fs::remove_dir_all(PROFILE_SERVER_DIR);
+    fs::create_dir_all(PROFILE_SERVER_DIR).map_err(|e| {
+        HarnessError::Other(format!(
+            "Failed to create `{PROFILE_SERVER_DIR}` dir with error: {e}"
+        ))
+    })?;
+    let raw = util::concat_path(&format!("{}.raw", scenario.name), out_dir);
+    let server_args = [
+        util::concat_path(&util::add_extension("skyd"), target_folder)
+            .to_string_lossy()
+            .to_string(),
+        "--host".to_owned(),
+        PROFILE_HOST.to_owned(),
+        "--port".to_owned(),
+        PROFILE_PORT.to_string(),
+        "--noart".to_owned(),
+        "--nosave".to_owned(),
+    ];
+    let mut cmd = profiler.record_cmd(&server_args, &raw);
+    cmd.current_dir(PROFILE_SERVER_DIR);
+    let mut child = util::get_child(format!("profile {}", scenario.name), cmd)?;
+    let ret = wait_for_startup().and_then(|_| {
+        let mut bench = Command::new(util::concat_path(
+            &util::add_extension("sky-bench"),
+            target_folder,
+        ));
+        bench.args(scenario.bench_args());
+        util::handle_child(&format!("sky-bench ({})", scenario.name), bench)
+    });
+    // stop the server even if the benchmark failed; the profiler exits along with it. pkill
+    // exits with 1 if there was nothing to stop (say, because the server crashed)
+    let stopped = match cmd!("pkill", "skyd").status() {
+        Ok(status) if status.success() || status.code() == Some(1) => Ok(()),
+        Ok(status) => Err(HarnessError::ChildError(
+            "stop server".to_owned(),
+            status.code(),
+        )),
+        Err(e) => Err(HarnessError::Other(format!(
+            "Failed to run `pkill` with error: {e}"
+        ))),
+    };
+    // always reap the profiler, or it would be left behind if we failed to stop the server
+    let waited = child
+        .wait()
+        .map_err(|e| HarnessError::Other(format!("Failed to wait for profiler with error: {e}")));
+    let _ = fs::remove_dir_all(PROFILE_SERVER_DIR);
+    ret.and(stopped).and(waited).map(|_| raw)
+}
+
+/// Profile all the scenarios and write the flamegraphs into the profile directory
+pub fn run_profile() -> HarnessResult<()> {
+    let profiler = Profiler::for_platform()?;
+    util::handle_child("install inferno", cmd!("cargo", "install", "inferno"))?;
+    let target_folder = build_binaries()?;
+    let out_dir = get_profile_dir_name();
+    let out_dir = fs::create_dir_all(&out_dir)
+        .and_then(|_| fs::canonicalize(&out_dir))
+        .map_err(|e| {
+            HarnessError::Other(format!(
+                "Failed to create profile directory with error: {e}"
+            ))
+        })?;
+    for scenario in SCENARIOS.iter() {
+        info!("Profiling scenario `{}` ...", scenario.name);
+        let raw = record(profiler, scenario, &target_folder, &out_dir)?;
+        let folded = profiler.fold(&raw)?;
+        let folded_path = util::concat_path(&format!("{}.folded", scenario.name), &out_dir);
+        fs::write(&folded_path, folded).map_err(|e| {
+            HarnessError::Other(format!("Failed to write folded stacks with error: {e}"))
+        })?;
+        let svg = util::get_child_output(
+            "render flamegraph",
+            cmd!(
+                "inferno-flamegraph",
+                "--title",
+                format!("skyd v{VERSION}: {}", scenario.name),
+                &folded_path
+            ),
+        )?;
+        let svg_path = util::concat_path(&format!("{}.svg", scenario.name), &out_dir);
+        fs::write(&svg_path, svg).map_err(|e| {
+            HarnessError::Other(format!("Failed to write flamegraph with error: {e}"))
+        })?;
+        // the raw samples are large and can be regenerated; drop them
+        let _ = fs::remove_file(&raw);
+        let _ = fs::remove_file(raw.with_extension("script"));
+        info!(
+            "Wrote flamegraph for `{}` to {}",
+            scenario.name,
+            svg_path.to_string_lossy()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,245 @@
+/*
+ * Created on Wed Oct 26 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # SBOM generation
+//!
+//! This module generates a [CycloneDX](https://cyclonedx.org) SBOM for the bundled binaries
+//! using the dependency graph from `cargo metadata`. Only packages that are reachable from
+//! the bundled binaries through normal and build dependencies are included. No timestamp or
+//! serial number is emitted so that the SBOM for a given `Cargo.lock` is reproducible.
+
+use {
+    crate::{build, util, HarnessError, HarnessResult},
+    libsky::VERSION,
+    serde_json::{json, Value},
+    std::{
+        collections::{BTreeMap, BTreeSet},
+        process::Command,
+    },
+};
+
+/// The name of the SBOM in the bundle
+pub const SBOM_FILE_NAME: &str = "sbom.cdx.json";
+/// The CycloneDX specification version that we emit
+const SPEC_VERSION: &str = "1.4";
+/// The `bom-ref` for the bundle itself
+const BUNDLE_REF: &str = "sky-bundle";
+/// The repository that path dependencies (our workspace members) come from
+const REPOSITORY: &str = "https://github.com/skytable/skytable";
+
+/// Run `cargo metadata` and generate the SBOM for the bundle
+pub fn generate() -> HarnessResult<Vec<u8>> {
+    let mut cmd = Command::new("cargo");
+    cmd.args(["metadata", "--format-version", "1", "--locked"])
+        .current_dir(util::WORKSPACE_ROOT);
+    if let Some(target) = util::get_var(util::VAR_TARGET) {
+        cmd.args(["--filter-platform", &target]);
+    }
+    let metadata = util::get_child_output("cargo metadata", cmd)?;
+    let metadata: Value = serde_json::from_slice(&metadata).map_err(|e| {
+        HarnessError::Other(format!("Failed to parse cargo metadata with error: {e}"))
+    })?;
+    let bom = sbom_from_metadata(&metadata)?;
+    serde_json::to_vec_pretty(&bom)
+        .map_err(|e| HarnessError::Other(format!("Failed to serialize SBOM with error: {e}")))
+}
+
+fn bad_metadata(what: &str) -> HarnessError {
+    HarnessError::Other(format!("Bad cargo metadata: missing or invalid `{what}`"))
+}
+
+fn get_array<'a>(value: &'a Value, key: &str) -> HarnessResult<&'a Vec<Value>> {
+    value[key].as_array().ok_or_else(|| bad_metadata(key))
+}
+
+fn get_str<'a>(value: &'a Value, key: &str) -> HarnessResult<&'a str> {
+    value[key].as_str().ok_or_else(|| bad_metadata(key))
+}
+
+fn get_object<'a>(value: &'a Value, key: &str) -> HarnessResult<&'a Value> {
+    let value = &value[key];
+    if value.is_object() {
+        Ok(value)
+    } else {
+        Err(bad_metadata(key))
+    }
+}
+
+fn purl_of(packages: &BTreeMap<&str, &Value>, id: &str) -> HarnessResult<String> {
+    purl(packages.get(id).ok_or_else(|| bad_metadata("packages"))?)
+}
+
+/// Returns the package URL for a cargo package. A package that doesn't come from a registry
+/// gets a `vcs_url` qualifier, so that packages with the same name and version from different
+/// places can be told apart
+fn purl(package: &Value) -> HarnessResult<String> {
+    let mut purl = format!(
+        "pkg:cargo/{}@{}",
+        get_str(package, "name")?,
+        get_str(package, "version")?
+    );
+    if let Some(vcs_url) = vcs_url(package)? {
+        purl.push_str("?vcs_url=");
+        purl.push_str(&percent_encode(&vcs_url));
+    }
+    Ok(purl)
+}
+
+/// Returns the `vcs_url` of a git or path dependency (`None` for registry packages)
+fn vcs_url(package: &Value) -> HarnessResult<Option<String>> {
+    let source = match &package["source"] {
+        // path dependencies are the workspace members, released under the bundle's tag
+        Value::Null => return Ok(Some(format!("git+{REPOSITORY}@v{VERSION}"))),
+        source => source.as_str().ok_or_else(|| bad_metadata("source"))?,
+    };
+    if !source.starts_with("git+") {
+        return Ok(None);
+    }
+    // `git+<url>?<branch, tag or rev>#<commit>` becomes `git+<url>@<commit>`
+    let (url, commit) = source
+        .split_once('#')
+        .ok_or_else(|| bad_metadata("source"))?;
+    let url = url.split_once('?').map_or(url, |(url, _)| url);
+    Ok(Some(format!("{url}@{commit}")))
+}
+
+/// Percent-encode a qualifier value of a package URL
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~:/@".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    encoded
+}
+
+/// Generate a CycloneDX document from the output of `cargo metadata`
+pub fn sbom_from_metadata(metadata: &Value) -> HarnessResult<Value> {
+    let packages: BTreeMap<&str, &Value> = get_array(metadata, "packages")?
+        .iter()
+        .map(|package| Ok((get_str(package, "id")?, package)))
+        .collect::<HarnessResult<_>>()?;
+    // package ID -> IDs of the (non-dev) dependencies
+    let mut graph: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for node in get_array(get_object(metadata, "resolve")?, "nodes")? {
+        let mut deps = Vec::new();
+        for dep in get_array(node, "deps")? {
+            let is_dev_only = get_array(dep, "dep_kinds")?
+                .iter()
+                .all(|kind| kind["kind"].as_str() == Some("dev"));
+            if !is_dev_only {
+                deps.push(get_str(dep, "pkg")?);
+            }
+        }
+        graph.insert(get_str(node, "id")?, deps);
+    }
+    // the roots are the workspace members that we bundle
+    let mut roots = BTreeSet::new();
+    for member in get_array(metadata, "workspace_members")? {
+        let id = member
+            .as_str()
+            .ok_or_else(|| bad_metadata("workspace_members"))?;
+        let package = packages.get(id).ok_or_else(|| bad_metadata("packages"))?;
+        if build::BINARIES.contains(&get_str(package, "name")?) {
+            roots.insert(id);
+        }
+    }
+    if roots.len() != build::BINARIES.len() {
+        return Err(HarnessError::Other(
+            "Bad cargo metadata: not all bundled binaries are workspace members".to_owned(),
+        ));
+    }
+    // now find everything reachable from the roots
+    let mut reachable = BTreeSet::new();
+    let mut stack: Vec<&str> = roots.iter().copied().collect();
+    while let Some(id) = stack.pop() {
+        if reachable.insert(id) {
+            stack.extend(graph.get(id).into_iter().flatten());
+        }
+    }
+    let mut components = Vec::with_capacity(reachable.len());
+    let mut dependencies = Vec::with_capacity(reachable.len() + 1);
+    let root_refs = roots
+        .iter()
+        .map(|id| purl_of(&packages, id))
+        .collect::<HarnessResult<Vec<_>>>()?;
+    dependencies.push(json!({ "ref": BUNDLE_REF, "dependsOn": root_refs }));
+    for id in reachable {
+        let package = packages.get(id).ok_or_else(|| bad_metadata("packages"))?;
+        let purl = purl(package)?;
+        let kind = if roots.contains(id) {
+            "application"
+        } else {
+            "library"
+        };
+        let mut component = json!({
+            "type": kind,
+            "bom-ref": purl,
+            "name": get_str(package, "name")?,
+            "version": get_str(package, "version")?,
+            "purl": purl,
+        });
+        if let Some(description) = package["description"].as_str() {
+            component["description"] = json!(description.trim());
+        }
+        if let Some(license) = package["license"].as_str() {
+            component["licenses"] = json!([{ "expression": license }]);
+        }
+        components.push(component);
+        let mut depends_on = graph
+            .get(id)
+            .into_iter()
+            .flatten()
+            .map(|dep| purl_of(&packages, dep))
+            .collect::<HarnessResult<Vec<_>>>()?;
+        depends_on.sort();
+        depends_on.dedup();
+        dependencies.push(json!({ "ref": purl, "dependsOn": depends_on }));
+    }
+    Ok(json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": SPEC_VERSION,
+        "version": 1,
+        "metadata": {
+            "tools": [{
+                "vendor": "Skytable",
+                "name": "harness",
+                "version": env!("CARGO_PKG_VERSION"),
+            }],
+            "component": {
+                "type": "application",
+                "bom-ref": BUNDLE_REF,
+                "name": BUNDLE_REF,
+                "version": VERSION,
+            },
+        },
+        "components": components,
+        "dependencies": dependencies,
+    }))
+}
@@ -0,0 +1,221 @@
+/*
+ * Created on Wed Oct 26 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Artifact signing
+//!
+//! This module creates the checksum manifest that is shipped with every bundle and signs
+//! release artifacts. Signatures use the [minisign](https://jedisct1.github.io/minisign/)
+//! format (with the non-prehashed `Ed` algorithm) so that packagers can verify them with
+//! `minisign -V` using our published public key.
+
+use {
+    crate::{util, HarnessError, HarnessResult},
+    openssl::{
+        pkey::{Id, PKey, Private},
+        sha::{self, Sha256},
+        sign::Signer,
+    },
+    std::{
+        fs,
+        time::{SystemTime, UNIX_EPOCH},
+    },
+};
+
+/// The name of the checksum manifest in the bundle
+pub const MANIFEST_FILE_NAME: &str = "SHA256SUMS";
+/// The extension used for detached signatures
+pub const SIGNATURE_EXTENSION: &str = "minisig";
+/// The minisign signature algorithm identifier for Ed25519
+const SIG_ALG: [u8; 2] = *b"Ed";
+/// Length of a raw Ed25519 seed
+const SEED_LEN: usize = 32;
+/// Length of a decoded minisign secret key
+const MINISIGN_SK_LEN: usize = 158;
+/// Offset of the key number (key ID followed by the secret key) in a minisign secret key
+const MINISIGN_SK_KEYNUM: usize = 54;
+
+/// A `sha256sum` compatible checksum manifest
+#[derive(Default)]
+pub struct ChecksumManifest {
+    manifest: String,
+}
+
+impl ChecksumManifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Add the file with the given name and contents to the manifest
+    pub fn add(&mut self, name: &str, data: &[u8]) {
+        let mut hasher = Sha256::new();
+        hasher.update(data);
+        self.manifest.push_str(&hex(&hasher.finish()));
+        self.manifest.push_str("  ");
+        self.manifest.push_str(name);
+        self.manifest.push('\n');
+    }
+    pub fn as_bytes(&self) -> &[u8] {
+        self.manifest.as_bytes()
+    }
+}
+
+/// An Ed25519 signing key
+pub struct SigningKey {
+    key_id: [u8; 8],
+    pkey: PKey<Private>,
+}
+
+impl SigningKey {
+    /// Load the signing key from the environment, if one was provided. See [`Self::decode`]
+    /// for the accepted formats
+    pub fn from_env() -> HarnessResult<Option<Self>> {
+        // an empty key is what CI passes when the secret is unavailable (such as on forks)
+        match util::get_var(util::VAR_SIGNING_KEY) {
+            Some(key) if !key.trim().is_empty() => Self::decode(key.trim()).map(Some),
+            _ => Ok(None),
+        }
+    }
+    /// Decode a base64 encoded signing key. This is either:
+    /// - a raw 32-byte Ed25519 seed, in which case the key ID is derived from the public key
+    /// - an unencrypted minisign secret key (the second line of a key generated with
+    ///   `minisign -G -W`)
+    pub fn decode(encoded: &str) -> HarnessResult<Self> {
+        let raw = base64::decode(encoded)
+            .map_err(|e| HarnessError::Other(format!("Failed to decode signing key: {e}")))?;
+        match raw.len() {
+            SEED_LEN => {
+                let pkey = keypair_from_seed(&raw)?;
+                let public = raw_public_key(&pkey)?;
+                let mut key_id = [0u8; 8];
+                key_id.copy_from_slice(&sha::sha256(&public)[..8]);
+                Ok(Self { key_id, pkey })
+            }
+            MINISIGN_SK_LEN => {
+                if raw[..2] != SIG_ALG {
+                    return Err(HarnessError::Other(
+                        "Unsupported signature algorithm in minisign key".to_owned(),
+                    ));
+                }
+                if raw[2..4] != [0, 0] {
+                    return Err(HarnessError::Other(
+                        "Encrypted minisign keys are not supported (generate one with `minisign -G -W`)"
+                            .to_owned(),
+                    ));
+                }
+                let keynum = &raw[MINISIGN_SK_KEYNUM..];
+                let mut key_id = [0u8; 8];
+                key_id.copy_from_slice(&keynum[..8]);
+                let (seed, public) = (&keynum[8..40], &keynum[40..72]);
+                let pkey = keypair_from_seed(seed)?;
+                if raw_public_key(&pkey)? != public {
+                    return Err(HarnessError::Other(
+                        "The minisign key is corrupted (public key mismatch)".to_owned(),
+                    ));
+                }
+                Ok(Self { key_id, pkey })
+            }
+            len => Err(HarnessError::Other(format!(
+                "Bad signing key: expected a {SEED_LEN}-byte seed or a {MINISIGN_SK_LEN}-byte minisign key, found {len} bytes"
+            ))),
+        }
+    }
+    /// Returns the public key in the minisign format
+    pub fn public_key(&self) -> HarnessResult<String> {
+        let mut key = SIG_ALG.to_vec();
+        key.extend(self.key_id);
+        key.extend(raw_public_key(&self.pkey)?);
+        Ok(format!(
+            "untrusted comment: minisign public key {}\n{}\n",
+            self.key_id_hex(),
+            base64::encode(key)
+        ))
+    }
+    /// Returns the key ID as minisign displays it
+    fn key_id_hex(&self) -> String {
+        let mut key_id = self.key_id;
+        key_id.reverse();
+        hex(&key_id).to_uppercase()
+    }
+    /// Sign `data` and return the detached signature for `file_name`
+    pub fn sign(&self, data: &[u8], file_name: &str) -> HarnessResult<String> {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        self.sign_with_timestamp(data, file_name, timestamp)
+    }
+    pub(crate) fn sign_with_timestamp(
+        &self,
+        data: &[u8],
+        file_name: &str,
+        timestamp: u64,
+    ) -> HarnessResult<String> {
+        let signature = self.sign_raw(data)?;
+        let trusted_comment = format!("timestamp:{timestamp}\tfile:{file_name}");
+        // the global signature covers the signature and the trusted comment
+        let mut global = signature.clone();
+        global.extend(trusted_comment.as_bytes());
+        let global_signature = self.sign_raw(&global)?;
+        let mut sig = SIG_ALG.to_vec();
+        sig.extend(self.key_id);
+        sig.extend(signature);
+        Ok(format!(
+            "untrusted comment: signature from skytable harness (key {})\n{}\ntrusted comment: {trusted_comment}\n{}\n",
+            self.key_id_hex(),
+            base64::encode(sig),
+            base64::encode(global_signature)
+        ))
+    }
+    /// Sign the file `file_name` and write its detached signature next to it (as
+    /// `<file_name>.minisig`)
+    pub fn sign_file(&self, file_name: &str) -> HarnessResult<()> {
+        let data = fs::read(file_name).map_err(|e| {
+            HarnessError::Other(format!("Failed to read `{file_name}` with error: {e}"))
+        })?;
+        let signature = self.sign(&data, file_name)?;
+        fs::write(format!("{file_name}.{SIGNATURE_EXTENSION}"), signature)
+            .map_err(|e| HarnessError::Other(format!("Failed to write signature with error: {e}")))
+    }
+    fn sign_raw(&self, data: &[u8]) -> HarnessResult<Vec<u8>> {
+        Signer::new_without_digest(&self.pkey)
+            .and_then(|mut signer| signer.sign_oneshot_to_vec(data))
+            .map_err(|e| HarnessError::Other(format!("Failed to sign artifact with error: {e}")))
+    }
+}
+
+fn keypair_from_seed(seed: &[u8]) -> HarnessResult<PKey<Private>> {
+    PKey::private_key_from_raw_bytes(seed, Id::ED25519)
+        .map_err(|e| HarnessError::Other(format!("Bad signing key: {e}")))
+}
+
+fn raw_public_key(pkey: &PKey<Private>) -> HarnessResult<Vec<u8>> {
+    pkey.raw_public_key()
+        .map_err(|e| HarnessError::Other(format!("Failed to derive public key: {e}")))
+}
+
+/// Returns the lowercase hex encoding of `bytes`
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
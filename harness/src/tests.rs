@@ -27,10 +27,17 @@
 use {
     crate::{
         build::{self, BuildMode},
-        bundle, linuxpkg, util,
+        bundle, linuxpkg, profile, sbom,
+        sign::{ChecksumManifest, SigningKey},
+        util,
         util::WORKSPACE_ROOT,
     },
     libsky::VERSION,
+    openssl::{
+        pkey::{Id, PKey},
+        sign::Verifier,
+    },
+    serde_json::{json, Value},
     std::{env, path::PathBuf},
 };
 
@@ -83,6 +90,9 @@ fn file_names() {
     // linux package name
     let name = linuxpkg::LinuxPackageType::Deb.get_file_name();
     assert_eq!(name, format!("skytable-v{VERSION}.deb"));
+    // profile directory name
+    let name = profile::get_profile_dir_name();
+    assert_eq!(name, format!("sky-profile-v{VERSION}"));
 
     // with a target
     env::set_var(util::VAR_ARTIFACT, ARTIFACT); // check bundle name
@@ -130,4 +140,199 @@ fn file_names() {
     // linux package name
     let name = linuxpkg::LinuxPackageType::Deb.get_file_name();
     assert_eq!(name, format!("skytable-v{VERSION}-{ARTIFACT}.deb"));
+    // profile directory name
+    let name = profile::get_profile_dir_name();
+    assert_eq!(name, format!("sky-profile-v{VERSION}-{ARTIFACT}"));
+}
+
+#[test]
+fn checksum_manifest() {
+    let mut manifest = ChecksumManifest::new();
+    manifest.add("hello", b"hello");
+    manifest.add("empty", b"");
+    assert_eq!(
+        manifest.as_bytes(),
+        b"2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824  hello\n\
+        e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855  empty\n"
+    );
+}
+
+const SEED: [u8; 32] = [7; 32];
+
+/// Returns the decoded second line of a minisign public key or signature
+fn decode_line(minisign: &str, line: usize) -> Vec<u8> {
+    base64::decode(minisign.lines().nth(line).unwrap()).unwrap()
+}
+
+#[test]
+fn sign_and_verify() {
+    let key = SigningKey::decode(&base64::encode(SEED)).unwrap();
+    let signature = key
+        .sign_with_timestamp(b"bundle", "bundle.zip", 1666742400)
+        .unwrap();
+    let lines: Vec<&str> = signature.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].starts_with("untrusted comment: "));
+    assert_eq!(
+        lines[2],
+        "trusted comment: timestamp:1666742400\tfile:bundle.zip"
+    );
+    let public = decode_line(&key.public_key().unwrap(), 1);
+    assert_eq!(&public[..2], b"Ed");
+    let sig = decode_line(&signature, 1);
+    // same algorithm and key ID
+    assert_eq!(sig[..10], public[..10]);
+    let pkey = PKey::public_key_from_raw_bytes(&public[10..], Id::ED25519).unwrap();
+    let verify = |data: &[u8], signature: &[u8]| {
+        Verifier::new_without_digest(&pkey)
+            .unwrap()
+            .verify_oneshot(signature, data)
+            .unwrap()
+    };
+    assert!(verify(b"bundle", &sig[10..]));
+    assert!(!verify(b"bundle2", &sig[10..]));
+    let mut global = sig[10..].to_vec();
+    global.extend(b"timestamp:1666742400\tfile:bundle.zip");
+    assert!(verify(&global, &decode_line(&signature, 3)));
+}
+
+#[test]
+fn decode_minisign_key() {
+    let public = decode_line(
+        &SigningKey::decode(&base64::encode(SEED))
+            .unwrap()
+            .public_key()
+            .unwrap(),
+        1,
+    );
+    let mut sk = Vec::new();
+    sk.extend(b"Ed\0\0B2");
+    sk.extend([0; 48]);
+    sk.extend([1, 2, 3, 4, 5, 6, 7, 8]);
+    sk.extend(SEED);
+    sk.extend(&public[10..]);
+    sk.extend([0; 32]);
+    let key = SigningKey::decode(&base64::encode(&sk)).unwrap();
+    let key_public = key.public_key().unwrap();
+    assert!(key_public.starts_with("untrusted comment: minisign public key 0807060504030201\n"));
+    assert_eq!(decode_line(&key_public, 1)[10..], public[10..]);
+    // a public key that doesn't match
+    let mut corrupted = sk.clone();
+    corrupted[100] ^= 0xFF;
+    assert!(SigningKey::decode(&base64::encode(&corrupted)).is_err());
+    // encrypted keys
+    sk[2..4].copy_from_slice(b"Sc");
+    assert!(SigningKey::decode(&base64::encode(&sk)).is_err());
+    // bad lengths
+    assert!(SigningKey::decode(&base64::encode([0; 31])).is_err());
+    assert!(SigningKey::decode("not base64!").is_err());
+}
+
+#[test]
+fn sbom_from_metadata() {
+    const REGISTRY: Option<&str> = Some("registry+https://github.com/rust-lang/crates.io-index");
+    fn package(name: &str, license: Option<&str>, source: Option<&str>) -> Value {
+        json!({
+            "id": format!("{name} 0.1.0"),
+            "name": name,
+            "version": "0.1.0",
+            "license": license,
+            "description": null,
+            "source": source,
+        })
+    }
+    fn node(name: &str, deps: &[(&str, Option<&str>)]) -> Value {
+        let deps: Vec<Value> = deps
+            .iter()
+            .map(|(dep, kind)| {
+                json!({
+                    "name": dep,
+                    "pkg": format!("{dep} 0.1.0"),
+                    "dep_kinds": [{ "kind": kind, "target": null }],
+                })
+            })
+            .collect();
+        json!({ "id": format!("{name} 0.1.0"), "deps": deps })
+    }
+    let mut packages = vec![
+        package("libsky", Some("AGPL-3.0"), None),
+        package("cc", Some("MIT OR Apache-2.0"), REGISTRY),
+        package("rand", None, REGISTRY),
+        package("harness", None, None),
+        package(
+            "skytable",
+            None,
+            Some("git+https://github.com/skytable/client-rust?branch=next#0123abc"),
+        ),
+    ];
+    let mut nodes = vec![
+        node("libsky", &[("cc", Some("build"))]),
+        node("cc", &[]),
+        node("rand", &[]),
+        node("harness", &[("libsky", None)]),
+        node("skytable", &[]),
+    ];
+    for binary in build::BINARIES {
+        packages.push(package(binary, Some("AGPL-3.0"), None));
+        nodes.push(node(
+            binary,
+            &[("libsky", None), ("rand", Some("dev")), ("skytable", None)],
+        ));
+    }
+    let mut members: Vec<String> = build::BINARIES
+        .iter()
+        .map(|bin| format!("{bin} 0.1.0"))
+        .collect();
+    members.push("harness 0.1.0".to_owned());
+    let metadata = json!({
+        "packages": packages,
+        "workspace_members": members,
+        "resolve": { "nodes": nodes },
+    });
+    let bom = sbom::sbom_from_metadata(&metadata).unwrap();
+    assert_eq!(bom["bomFormat"], "CycloneDX");
+    let components = bom["components"].as_array().unwrap();
+    let mut names: Vec<&str> = components
+        .iter()
+        .map(|c| c["name"].as_str().unwrap())
+        .collect();
+    names.sort_unstable();
+    let mut expected = build::BINARIES.to_vec();
+    expected.extend(["cc", "libsky", "skytable"]);
+    expected.sort_unstable();
+    // no dev dependencies or unbundled workspace members
+    assert_eq!(names, expected);
+    let libsky = components.iter().find(|c| c["name"] == "libsky").unwrap();
+    assert_eq!(libsky["type"], "library");
+    let libsky_purl = format!(
+        "pkg:cargo/libsky@0.1.0?vcs_url=git%2Bhttps://github.com/skytable/skytable@v{VERSION}"
+    );
+    assert_eq!(libsky["purl"], libsky_purl);
+    // git dependencies are pinned to their commit, and registry packages have no qualifiers
+    let skytable = components.iter().find(|c| c["name"] == "skytable").unwrap();
+    let skytable_purl =
+        "pkg:cargo/skytable@0.1.0?vcs_url=git%2Bhttps://github.com/skytable/client-rust@0123abc";
+    assert_eq!(skytable["purl"], skytable_purl);
+    let cc = components.iter().find(|c| c["name"] == "cc").unwrap();
+    assert_eq!(cc["purl"], "pkg:cargo/cc@0.1.0");
+    assert_eq!(libsky["licenses"][0]["expression"], "AGPL-3.0");
+    let skyd = components.iter().find(|c| c["name"] == "skyd").unwrap();
+    assert_eq!(skyd["type"], "application");
+    let dependencies = bom["dependencies"].as_array().unwrap();
+    let skyd_deps = dependencies
+        .iter()
+        .find(|d| d["ref"] == skyd["purl"])
+        .unwrap();
+    assert_eq!(skyd_deps["dependsOn"], json!([libsky_purl, skytable_purl]));
+    assert_eq!(
+        dependencies[0]["dependsOn"].as_array().unwrap().len(),
+        build::BINARIES.len()
+    );
+    // a binary that isn't in the workspace
+    let metadata = json!({
+        "packages": [package("libsky", None, None)],
+        "workspace_members": ["libsky 0.1.0"],
+        "resolve": { "nodes": [node("libsky", &[])] },
+    });
+    assert!(sbom::sbom_from_metadata(&metadata).is_err());
 }
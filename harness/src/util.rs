@@ -47,6 +47,10 @@ pub const VAR_TARGET: &str = "TARGET_TESTSUITE";
 pub const VAR_ARTIFACT: &str = "ARTIFACT";
 #[cfg(test)]
 pub const VAR_ARTIFACT: &str = "ARTIFACT_TESTSUITE";
+#[cfg(not(test))]
+pub const VAR_SIGNING_KEY: &str = "SKYHARNESS_SIGNING_KEY";
+#[cfg(test)]
+pub const VAR_SIGNING_KEY: &str = "SKYHARNESS_SIGNING_KEY_TESTSUITE";
 pub const WORKSPACE_ROOT: &str = env!("ROOT_DIR");
 
 pub fn get_var(var: &str) -> Option<String> {
@@ -96,6 +100,18 @@ pub fn handle_child(desc: &str, input: Command) -> HarnessResult<()> {
     ensure_child_success(desc, child)
 }
 
+/// Run the command to completion and return its stdout
+pub fn get_child_output(desc: &str, mut input: Command) -> HarnessResult<Vec<u8>> {
+    let r = input
+        .output()
+        .map_err(|e| HarnessError::Other(format!("Failed to run `{desc}` with error: {e}")))?;
+    if r.status.success() {
+        Ok(r.stdout)
+    } else {
+        check_child_err(desc, r).map(|_| Vec::new())
+    }
+}
+
 pub fn sleep_sec(secs: u64) {
     std::thread::sleep(std::time::Duration::from_secs(secs))
 }
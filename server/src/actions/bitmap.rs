@@ -0,0 +1,115 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `SETBIT`, `GETBIT` and `BITCOUNT` queries
+//! This module provides functions to use binary values as bitmaps (see
+//! [`kvengine::bitmap`](crate::kvengine::bitmap) for how the bits are numbered)
+
+use {
+    super::range::{parse_offset, MAX_RANGE_END},
+    crate::dbnet::prelude::*,
+};
+
+action! {
+    /// Run a `SETBIT` query, which sets (`1`) or clears (`0`) bit `<offset>` of a value and
+    /// returns the previous state of the bit. The value is padded with zero bytes if it's too
+    /// short, and a key that doesn't exist is created
+    /// Syntax: `SETBIT <key> <offset> <0|1>`
+    fn setbit(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 3)?;
+        let key = unsafe { act.next_unchecked_bytes() };
+        let offset = match self::parse_bit_offset(unsafe { act.next_unchecked() }) {
+            Some(offset) => offset,
+            None => return util::err(P::RCODE_WRONGTYPE_ERR),
+        };
+        let bit = match unsafe { act.next_unchecked() } {
+            b"0" => false,
+            b"1" => true,
+            _ => return util::err(P::RCODE_WRONGTYPE_ERR),
+        };
+        let kve = handle.get_table_with::<P, KVEBlob>()?;
+        let len = offset / 8 + 1;
+        if len > MAX_RANGE_END || !kve.size_limits().allows_value(len) {
+            return util::err(P::RSTRING_TOO_LARGE);
+        }
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        match kve.set_bit(key, offset, bit) {
+            Ok(was_set) => con.write_usize(was_set as usize).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run a `GETBIT` query, which returns the state of bit `<offset>` of a value (`0` if the
+    /// key doesn't exist or the value is too short)
+    /// Syntax: `GETBIT <key> <offset>`
+    fn getbit(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 2)?;
+        let key = unsafe { act.next_unchecked() };
+        let offset = match self::parse_bit_offset(unsafe { act.next_unchecked() }) {
+            Some(offset) => offset,
+            None => return util::err(P::RCODE_WRONGTYPE_ERR),
+        };
+        let kve = handle.get_table_with::<P, KVEBlob>()?;
+        match kve.get_bit(key, offset) {
+            Ok(bit) => con.write_usize(bit as usize).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run a `BITCOUNT` query, which returns the number of set bits in a value, or in its bytes
+    /// from `<start>` to `<end>` (both inclusive, like `GETRANGE`). A missing key has no set bits
+    /// Syntax: `BITCOUNT <key> [<start> <end>]`
+    fn bitcount(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 1 || len == 3)?;
+        let key = unsafe { act.next_unchecked() };
+        let (start, end) = if act.is_empty() {
+            (0, -1)
+        } else {
+            match (
+                parse_offset(unsafe { act.next_unchecked() }),
+                parse_offset(unsafe { act.next_unchecked() }),
+            ) {
+                (Some(start), Some(end)) => (start, end),
+                _ => return util::err(P::RCODE_WRONGTYPE_ERR),
+            }
+        };
+        let kve = handle.get_table_with::<P, KVEBlob>()?;
+        match kve.bit_count(key, start, end) {
+            Ok(count) => con.write_usize(count).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+}
+
+/// Parse a bit offset, which is an unsigned integer in decimal
+fn parse_bit_offset(offset: &[u8]) -> Option<usize> {
+    core::str::from_utf8(offset).ok()?.parse().ok()
+}
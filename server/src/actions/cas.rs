@@ -0,0 +1,104 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `CAS`, `VERSION` and `SETIFEQ` queries
+//! This module provides functions for optimistic concurrency on a key/value table. Every key has
+//! a version that changes on every write to it (see [`kvengine::version`]), so a client can read
+//! the version of a key (with `VERSION`) before reading its value, and then use `CAS` to write a
+//! new value only if no one else has written to the key in the meantime. Clients that don't
+//! care about intermediate writes can use `SETIFEQ` instead, which compares the values
+//!
+//! [`kvengine::version`]: crate::kvengine::version
+
+use crate::dbnet::prelude::*;
+
+const ERR_VERSION_MISMATCH: &[u8] = b"!20\nerr-version-mismatch\n";
+const ERR_VALUE_MISMATCH: &[u8] = b"!18\nerr-value-mismatch\n";
+
+action! {
+    /// Run a `CAS` query, which sets the value of a key if its version is the expected one
+    /// (zero if the key must not exist) and returns the new version
+    /// Syntax: `CAS <key> <expected version> <value>`
+    fn cas(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 3)?;
+        let key = unsafe { act.next_unchecked_bytes() };
+        let expected = match self::parse_version(unsafe { act.next_unchecked() }) {
+            Some(expected) => expected,
+            None => return util::err(P::RCODE_WRONGTYPE_ERR),
+        };
+        let value = unsafe { act.next_unchecked_bytes() };
+        let kve = handle.get_table_with::<P, KVEBlob>()?;
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        match kve.compare_and_swap(key, expected, value) {
+            Ok(Ok(version)) => con.write_int64(version).await?,
+            Ok(Err(_)) => return util::err(ERR_VERSION_MISMATCH),
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run a `VERSION` query, which returns the version of a key
+    /// Syntax: `VERSION <key>`
+    fn version(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 1)?;
+        let key = unsafe { act.next_unchecked() };
+        let kve = handle.get_table_with::<P, KVEBlob>()?;
+        match kve.version_of(key) {
+            Ok(Some(version)) => con.write_int64(version).await?,
+            Ok(None) => return util::err(P::RCODE_NIL),
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run a `SETIFEQ` query, which sets the value of a key if its current value is the expected
+    /// one. Returns nil if the key doesn't exist
+    /// Syntax: `SETIFEQ <key> <expected value> <value>`
+    fn setifeq(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 3)?;
+        let key = unsafe { act.next_unchecked_bytes() };
+        let expected = unsafe { act.next_unchecked() };
+        let value = unsafe { act.next_unchecked_bytes() };
+        let kve = handle.get_table_with::<P, KVEBlob>()?;
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        match kve.compare_value_and_swap(key, expected, value) {
+            Ok(Some(true)) => con._write_raw(P::RCODE_OKAY).await?,
+            Ok(Some(false)) => return util::err(ERR_VALUE_MISMATCH),
+            Ok(None) => return util::err(P::RCODE_NIL),
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+}
+
+/// Parse a version, which is a 64-bit unsigned integer in decimal
+fn parse_version(version: &[u8]) -> Option<u64> {
+    core::str::from_utf8(version).ok()?.parse().ok()
+}
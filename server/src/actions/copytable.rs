@@ -0,0 +1,63 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `COPYTABLE` queries
+//! This module provides a function to copy a table into a new table of the same keyspace,
+//! which comes in handy when staging a migration or when taking a quick backup of a table
+
+use crate::{blueql::Entity, corestore::memstore::ObjectID, dbnet::prelude::*};
+
+const VOLATILE: &[u8] = b"volatile";
+const PERSISTENT: &[u8] = b"persistent";
+
+action!(
+    /// Copy the entries of a table into a new table in the same keyspace, with the same model
+    /// and (unless overridden) the same volatility
+    ///
+    /// ## Syntax
+    /// `COPYTABLE <entity> <new table> [VOLATILE|PERSISTENT]`
+    fn copytable(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 2 || len == 3)?;
+        let (raw_src, raw_dst) = unsafe { (act.next_unchecked(), act.next_unchecked()) };
+        let src = handle_entity!(con, raw_src);
+        let dst = match handle_entity!(con, raw_dst).as_ref() {
+            Entity::Current(tblid) => unsafe { ObjectID::from_slice(tblid.as_slice()) },
+            Entity::Full(..) => return util::err(P::RSTRING_BAD_CONTAINER_NAME),
+        };
+        let volatile = match act.next() {
+            None => None,
+            Some(v) if v.eq_ignore_ascii_case(VOLATILE) => Some(true),
+            Some(p) if p.eq_ignore_ascii_case(PERSISTENT) => Some(false),
+            Some(_) => return util::err(P::RSTRING_UNKNOWN_PROPERTY),
+        };
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        translate_ddl_error::<P, ()>(handle.copy_table(&src, dst, volatile))?;
+        con._write_raw(P::RCODE_OKAY).await?;
+        Ok(())
+    }
+);
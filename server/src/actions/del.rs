@@ -73,6 +73,18 @@ action!(
             DataModel::KVExtListmap(kvlmap) => {
                 remove!(kvlmap)
             }
+            DataModel::KVExtMap(kvmap) => {
+                remove!(kvmap)
+            }
+            DataModel::KVExtSet(kvset) => {
+                remove!(kvset)
+            }
+            DataModel::KVExtSortedSet(kvzset) => {
+                remove!(kvzset)
+            }
+            DataModel::KVExtDocument(kvdoc) => {
+                remove!(kvdoc)
+            }
             #[allow(unreachable_patterns)]
             _ => return util::err(P::RSTRING_WRONG_MODEL),
         }
@@ -0,0 +1,108 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `DELMATCH` queries
+//! This module provides a function to remove the keys of a table that match a glob pattern.
+//! Every invocation removes at most [`MAX_REMOVALS`] keys (or fewer, with `LIMIT`) so that a
+//! careless pattern can't empty a large table in one go. The table is visited [`CHUNK`] keys at
+//! a time, and other tasks get to run between two chunks
+
+use crate::{
+    corestore::table::DataModel,
+    dbnet::prelude::*,
+    kvengine::{KVEValue, KVEngine},
+};
+
+/// The most keys that a single `DELMATCH` will remove
+pub const MAX_REMOVALS: usize = 10_000;
+/// The number of keys that are visited before yielding to other tasks
+const CHUNK: usize = 1024;
+const COUNT: &[u8] = b"count";
+const LIMIT: &[u8] = b"limit";
+
+action!(
+    /// Run a `DELMATCH` query, which removes the keys of the current table that match the glob
+    /// `<pattern>` (see [`util::glob_match`]) and returns how many were removed. At most
+    /// `<limit>` keys (capped to [`MAX_REMOVALS`]) are removed, so a client can repeat the query
+    /// until it returns 0. With `COUNT`, nothing is removed and the number of matching keys is
+    /// returned instead
+    ///
+    /// ## Syntax
+    /// `DELMATCH <pattern> [COUNT | LIMIT <limit>]`
+    fn delmatch(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| (1..=3).contains(&len))?;
+        let pattern = unsafe {
+            // UNSAFE(@ohsayan): we've already checked that there's at least one argument
+            act.next_unchecked()
+        };
+        let limit = match (act.next(), act.next()) {
+            (None, _) => Some(MAX_REMOVALS),
+            (Some(option), None) if option.eq_ignore_ascii_case(COUNT) => None,
+            (Some(option), Some(limit)) if option.eq_ignore_ascii_case(LIMIT) => {
+                match String::from_utf8_lossy(limit).parse::<usize>() {
+                    Ok(limit) if limit != 0 => Some(limit.min(MAX_REMOVALS)),
+                    _ => return util::err(P::RCODE_ACTION_ERR),
+                }
+            }
+            _ => return util::err(P::RSTRING_UNKNOWN_PROPERTY),
+        };
+        if limit.is_some() && !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        let table = get_tbl_ref!(handle, con);
+        let howmany = match table.get_model_ref() {
+            DataModel::KV(kve) => run(kve, pattern, limit).await,
+            DataModel::KVExtListmap(kvlmap) => run(kvlmap, pattern, limit).await,
+            DataModel::KVExtMap(kvmap) => run(kvmap, pattern, limit).await,
+            DataModel::KVExtSet(kvset) => run(kvset, pattern, limit).await,
+            DataModel::KVExtSortedSet(kvzset) => run(kvzset, pattern, limit).await,
+            DataModel::KVExtDocument(kvdoc) => run(kvdoc, pattern, limit).await,
+        };
+        con.write_usize(howmany).await?;
+        Ok(())
+    }
+);
+
+/// Remove at most `limit` of the keys that match `pattern`, or count them if there's no limit,
+/// one [`CHUNK`] at a time. Returns how many keys were removed (or counted)
+async fn run<T: KVEValue>(engine: &KVEngine<T>, pattern: &[u8], limit: Option<usize>) -> usize {
+    let (mut howmany, mut cursor) = (0, 0);
+    loop {
+        let (found, next) = match limit {
+            Some(limit) => engine.remove_matching(pattern, cursor, CHUNK, limit - howmany),
+            None => {
+                let (keys, next) = engine.scan_matching(pattern, cursor, CHUNK);
+                (keys.len(), next)
+            }
+        };
+        howmany += found;
+        cursor = next;
+        if cursor == 0 || limit == Some(howmany) {
+            return howmany;
+        }
+        tokio::task::yield_now().await;
+    }
+}
@@ -0,0 +1,92 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `JSET` and `JGET` queries
+//! This module provides functions to work with the documents in a `KVExt/Document` table (that
+//! is, a table declared with a `json` value type). A path picks a value in a document: `$` is
+//! the document itself, `.name` picks a field of an object and `[index]` picks an element of an
+//! array (for example, `$.users[0].name`)
+
+use crate::{
+    dbnet::prelude::*,
+    kvengine::document::{Json, Path, SetError},
+};
+
+const ERR_BAD_JSON: &[u8] = b"!8\nbad-json\n";
+const ERR_BAD_PATH: &[u8] = b"!8\nbad-path\n";
+const ERR_PATH_NOT_FOUND: &[u8] = b"!14\npath-not-found\n";
+const ERR_TOO_DEEP: &[u8] = b"!17\ndocument-too-deep\n";
+
+action! {
+    /// Run a `JSET` query, which sets the value at `<path>` in a document. Setting the document
+    /// itself (at `$`) creates the document if it doesn't exist. Otherwise, the value that holds
+    /// the path has to exist: a missing field is added to an object and an index just past the
+    /// end of an array appends to it
+    /// Syntax: `JSET <key> <path> <json>`
+    fn jset(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 3)?;
+        let docs = handle.get_table_with::<P, KVEDocumentT>()?;
+        let key = unsafe { act.next_unchecked_bytes() };
+        let path = match Path::parse(unsafe { act.next_unchecked() }) {
+            Some(path) => path,
+            None => return util::err(ERR_BAD_PATH),
+        };
+        let value = match Json::parse(unsafe { act.next_unchecked() }) {
+            Some(value) => value,
+            None => return util::err(ERR_BAD_JSON),
+        };
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        match docs.doc_set(key, &path, value) {
+            Ok(Ok(())) => con._write_raw(P::RCODE_OKAY).await?,
+            Ok(Err(SetError::NoDocument)) => con._write_raw(P::RCODE_NIL).await?,
+            Ok(Err(SetError::NoParent)) => return util::err(ERR_PATH_NOT_FOUND),
+            Ok(Err(SetError::TooDeep)) => return util::err(ERR_TOO_DEEP),
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run a `JGET` query, which returns the value at `<path>` (the document itself if no
+    /// path is given) as JSON
+    /// Syntax: `JGET <key> [<path>]`
+    fn jget(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 1 || len == 2)?;
+        let docs = handle.get_table_with::<P, KVEDocumentT>()?;
+        let key = unsafe { act.next_unchecked() };
+        let path = match Path::parse(act.next().unwrap_or(b"$")) {
+            Some(path) => path,
+            None => return util::err(ERR_BAD_PATH),
+        };
+        match docs.doc_get(key, &path) {
+            Ok(Some(json)) => con.write_string(&json).await?,
+            Ok(None) => con._write_raw(P::RCODE_NIL).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+}
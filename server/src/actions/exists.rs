@@ -54,6 +54,10 @@ action!(
         match tbl.get_model_ref() {
             DataModel::KV(kve) => exists!(kve),
             DataModel::KVExtListmap(kve) => exists!(kve),
+            DataModel::KVExtMap(kve) => exists!(kve),
+            DataModel::KVExtSet(kve) => exists!(kve),
+            DataModel::KVExtSortedSet(kve) => exists!(kve),
+            DataModel::KVExtDocument(kve) => exists!(kve),
             #[allow(unreachable_patterns)]
             _ => return util::err(P::RSTRING_WRONG_MODEL),
         }
@@ -0,0 +1,159 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `EXPIRE`, `PEXPIRE`, `TTL`, `PTTL` and `PERSIST` queries
+//! This module provides functions to work with the time to live of keys, in seconds or in
+//! milliseconds. See [`kvengine::expiry`](crate::kvengine::expiry) for how keys are expired
+
+use {
+    super::ActionResult,
+    crate::{
+        corestore::table::DataModel,
+        dbnet::{prelude::*, BufferedSocketStream},
+    },
+};
+
+/// The number of milliseconds in a second
+const MS_PER_SEC: u64 = 1000;
+
+action! {
+    /// Run an `EXPIRE` query
+    /// Syntax: `EXPIRE <key> <seconds>`
+    fn expire(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 2)?;
+        let key = unsafe { act.next_unchecked() };
+        let secs = unsafe { act.next_unchecked() };
+        set_expiry(handle, con, key, secs, MS_PER_SEC).await
+    }
+
+    /// Run a `PEXPIRE` query
+    /// Syntax: `PEXPIRE <key> <milliseconds>`
+    fn pexpire(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 2)?;
+        let key = unsafe { act.next_unchecked() };
+        let ms = unsafe { act.next_unchecked() };
+        set_expiry(handle, con, key, ms, 1).await
+    }
+
+    /// Run a `TTL` query, which returns the number of seconds until a key expires
+    /// Syntax: `TTL <key>`
+    fn ttl(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 1)?;
+        let key = unsafe { act.next_unchecked() };
+        write_ttl(handle, con, key, MS_PER_SEC).await
+    }
+
+    /// Run a `PTTL` query, which returns the number of milliseconds until a key expires
+    /// Syntax: `PTTL <key>`
+    fn pttl(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 1)?;
+        let key = unsafe { act.next_unchecked() };
+        write_ttl(handle, con, key, 1).await
+    }
+
+    /// Run a `PERSIST` query, which removes the time to live of a key
+    /// Syntax: `PERSIST <key>`
+    fn persist(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 1)?;
+        let key = unsafe { act.next_unchecked() };
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        let table = get_tbl_ref!(handle, con);
+        let ret = match table.get_model_ref() {
+            DataModel::KV(kve) => kve.persist(key),
+            DataModel::KVExtListmap(kvl) => kvl.persist(key),
+            DataModel::KVExtMap(kvm) => kvm.persist(key),
+            DataModel::KVExtSet(kvs) => kvs.persist(key),
+            DataModel::KVExtSortedSet(kvz) => kvz.persist(key),
+            DataModel::KVExtDocument(kvd) => kvd.persist(key),
+        };
+        match ret {
+            Ok(true) => con._write_raw(P::RCODE_OKAY).await?,
+            Ok(false) => con._write_raw(P::RCODE_NIL).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+}
+
+/// Make `key` expire after `ttl` units of `unit_ms` milliseconds each
+async fn set_expiry<C: BufferedSocketStream, P: ProtocolSpec>(
+    handle: &Corestore,
+    con: &mut Connection<C, P>,
+    key: &[u8],
+    ttl: &[u8],
+    unit_ms: u64,
+) -> ActionResult<()> {
+    let ttl_ms = match String::from_utf8_lossy(ttl).parse::<u64>() {
+        Ok(ttl) if ttl != 0 => ttl.saturating_mul(unit_ms),
+        _ => return util::err(P::RCODE_WRONGTYPE_ERR),
+    };
+    if !registry::state_okay() {
+        return util::err(P::RCODE_SERVER_ERR);
+    }
+    let table = get_tbl_ref!(handle, con);
+    let ret = match table.get_model_ref() {
+        DataModel::KV(kve) => kve.set_expiry(key, ttl_ms),
+        DataModel::KVExtListmap(kvl) => kvl.set_expiry(key, ttl_ms),
+        DataModel::KVExtMap(kvm) => kvm.set_expiry(key, ttl_ms),
+        DataModel::KVExtSet(kvs) => kvs.set_expiry(key, ttl_ms),
+        DataModel::KVExtSortedSet(kvz) => kvz.set_expiry(key, ttl_ms),
+        DataModel::KVExtDocument(kvd) => kvd.set_expiry(key, ttl_ms),
+    };
+    match ret {
+        Ok(true) => con._write_raw(P::RCODE_OKAY).await?,
+        Ok(false) => con._write_raw(P::RCODE_NIL).await?,
+        Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+    }
+    Ok(())
+}
+
+/// Write the time until `key` expires, in units of `unit_ms` milliseconds
+async fn write_ttl<C: BufferedSocketStream, P: ProtocolSpec>(
+    handle: &Corestore,
+    con: &mut Connection<C, P>,
+    key: &[u8],
+    unit_ms: u64,
+) -> ActionResult<()> {
+    let table = get_tbl_ref!(handle, con);
+    let ret = match table.get_model_ref() {
+        DataModel::KV(kve) => kve.ttl(key),
+        DataModel::KVExtListmap(kvl) => kvl.ttl(key),
+        DataModel::KVExtMap(kvm) => kvm.ttl(key),
+        DataModel::KVExtSet(kvs) => kvs.ttl(key),
+        DataModel::KVExtSortedSet(kvz) => kvz.ttl(key),
+        DataModel::KVExtDocument(kvd) => kvd.ttl(key),
+    };
+    match ret {
+        // round up, so that a key that hasn't expired never has a TTL of zero
+        Ok(Some(Some(ttl_ms))) => con.write_int64(ttl_ms.div_ceil(unit_ms)).await?,
+        Ok(Some(None)) => con._write_raw(P::RSTRING_NO_EXPIRY).await?,
+        Ok(None) => con._write_raw(P::RCODE_NIL).await?,
+        Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+    }
+    Ok(())
+}
@@ -0,0 +1,73 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `FETCH` queries
+//! This module provides the action that reads the next page of a cursor (see
+//! [`dbnet::cursor`](crate::dbnet::cursor)), and writes pages for the actions that open cursors
+
+use {
+    super::ActionResult,
+    crate::dbnet::{cursor::Page, prelude::*, BufferedSocketStream},
+};
+
+action!(
+    /// Run a `FETCH` query, which returns the cursor followed by the next page of the cursor.
+    /// The returned cursor is zero once there's nothing left
+    ///
+    /// ## Syntax
+    /// `FETCH <cursor>`
+    fn fetch(_handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 1)?;
+        let cursor = unsafe {
+            // UNSAFE(@ohsayan): we've already checked the number of arguments is one
+            act.next_unchecked()
+        };
+        let cursor = match String::from_utf8_lossy(cursor).parse::<u64>() {
+            Ok(cursor) => cursor,
+            Err(_) => return util::err(P::RCODE_WRONGTYPE_ERR),
+        };
+        match con.cursors().fetch(cursor) {
+            Some(page) => write_page(con, page).await,
+            None => util::err(P::RSTRING_CURSOR_NOT_FOUND),
+        }
+    }
+);
+
+/// Write a page as a typed array of the cursor followed by the items (just like `SCAN`)
+pub async fn write_page<C: BufferedSocketStream, P: ProtocolSpec>(
+    con: &mut Connection<C, P>,
+    page: Page,
+) -> ActionResult<()> {
+    con.deadline().check::<P>()?;
+    con.write_typed_non_null_array_header(page.items.len() + 1, page.tsymbol)
+        .await?;
+    con.write_typed_non_null_array_element(page.cursor.to_string().as_bytes())
+        .await?;
+    for item in page.items {
+        con.write_typed_non_null_array_element(&item).await?;
+    }
+    Ok(())
+}
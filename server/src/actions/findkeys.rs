@@ -0,0 +1,83 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `FINDKEYS` queries
+//! This module provides functions to look up keys by their value, using the value index of the
+//! table (see [`kvengine::index`](crate::kvengine::index))
+
+use crate::dbnet::prelude::*;
+
+const ERR_NO_INDEX: &[u8] = b"!12\nerr-no-index\n";
+const PAGE: &[u8] = b"page";
+
+action!(
+    /// Run a `FINDKEYS` query, which returns every key whose value is `<value>`
+    /// Syntax: `FINDKEYS <value> [PAGE <size>]`
+    ///
+    /// The table has to have its value index turned on (with `SYS INDEX ON`). With `PAGE`, only
+    /// the first `<size>` keys are returned, after the ID of a cursor over the rest (see
+    /// [`fetch`](super::fetch))
+    fn findkeys(
+        handle: &crate::corestore::Corestore,
+        con: &mut Connection<C, P>,
+        mut act: ActionIter<'a>,
+    ) {
+        ensure_length::<P>(act.len(), |len| len == 1 || len == 3)?;
+        let kve = handle.get_table_with::<P, KVEBlob>()?;
+        let value = unsafe {
+            // UNSAFE(@ohsayan): we've already checked that there's at least one argument
+            act.next_unchecked()
+        };
+        let page_size = match (act.next(), act.next()) {
+            (Some(option), Some(size)) if option.eq_ignore_ascii_case(PAGE) => {
+                match String::from_utf8_lossy(size).parse::<usize>() {
+                    Ok(size) if size != 0 => Some(size),
+                    _ => return util::err(P::RCODE_WRONGTYPE_ERR),
+                }
+            }
+            (Some(_), _) => return util::err(P::RSTRING_UNKNOWN_PROPERTY),
+            _ => None,
+        };
+        if !kve.is_val_ok(value) {
+            return util::err(P::RCODE_ENCODING_ERROR);
+        }
+        let keys = match kve.find_keys(value) {
+            Some(keys) => keys,
+            None => return util::err(ERR_NO_INDEX),
+        };
+        if let Some(page_size) = page_size {
+            let page = con.cursors().open(keys, kve.get_key_tsymbol(), page_size);
+            return super::fetch::write_page(con, page).await;
+        }
+        con.deadline().check::<P>()?;
+        con.write_typed_non_null_array_header(keys.len(), kve.get_key_tsymbol())
+            .await?;
+        for key in keys {
+            con.write_typed_non_null_array_element(&key).await?;
+        }
+        Ok(())
+    }
+);
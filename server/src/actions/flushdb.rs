@@ -31,15 +31,19 @@ action!(
     fn flushdb(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
         ensure_length::<P>(act.len(), |len| len < 2)?;
         if registry::state_okay() {
-            if act.is_empty() {
+            let table = if act.is_empty() {
                 // flush the current table
-                get_tbl_ref!(handle, con).truncate_table();
+                get_tbl!(handle, con)
             } else {
                 // flush the entity
                 let raw_entity = unsafe { act.next_unchecked() };
                 let entity = handle_entity!(con, raw_entity);
-                get_tbl!(&entity, handle, con).truncate_table();
+                get_tbl!(&entity, handle, con)
+            };
+            if table.rejects_writes() {
+                return util::err(P::RSTRING_READ_ONLY);
             }
+            table.truncate_table();
             con._write_raw(P::RCODE_OKAY).await?;
         } else {
             con._write_raw(P::RCODE_SERVER_ERR).await?;
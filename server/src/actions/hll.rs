@@ -0,0 +1,90 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `PFADD`, `PFCOUNT` and `PFMERGE` queries
+//! This module provides functions to count distinct elements approximately with HyperLogLog
+//! sketches (see [`kvengine::hll`](crate::kvengine::hll)), which are held by the keys of
+//! key/value tables
+
+use crate::{dbnet::prelude::*, kvengine::hll};
+
+action! {
+    /// Run a `PFADD` query, which adds elements to the sketch held by a key (creating it if the
+    /// key doesn't exist) and returns `1` if the sketch was created or changed, and `0` otherwise
+    /// Syntax: `PFADD <key> <element1> <element2> ...`
+    fn pfadd(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len != 0)?;
+        let key = unsafe { act.next_unchecked_bytes() };
+        let kve = handle.get_table_with::<P, KVEBlob>()?;
+        if !kve.size_limits().allows_value(hll::MAX_ENCODED_LEN) {
+            return util::err(P::RSTRING_TOO_LARGE);
+        }
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        match kve.pf_add(key, act) {
+            Ok(Some(changed)) => con.write_usize(changed as usize).await?,
+            Ok(None) => return util::err(P::RCODE_WRONGTYPE_ERR),
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run a `PFCOUNT` query, which returns the approximate number of distinct elements in the
+    /// union of the sketches held by the given keys (a missing key counts as an empty sketch)
+    /// Syntax: `PFCOUNT <key1> <key2> ...`
+    fn pfcount(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len != 0)?;
+        let kve = handle.get_table_with::<P, KVEBlob>()?;
+        match kve.pf_count(&mut act) {
+            Ok(Some(count)) => con.write_usize(count as usize).await?,
+            Ok(None) => return util::err(P::RCODE_WRONGTYPE_ERR),
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run a `PFMERGE` query, which merges the sketches held by the source keys into the sketch
+    /// held by the destination key (creating it if it doesn't exist)
+    /// Syntax: `PFMERGE <destination> <source1> <source2> ...`
+    fn pfmerge(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len != 0)?;
+        let dest = unsafe { act.next_unchecked_bytes() };
+        let kve = handle.get_table_with::<P, KVEBlob>()?;
+        if !kve.size_limits().allows_value(hll::MAX_ENCODED_LEN) {
+            return util::err(P::RSTRING_TOO_LARGE);
+        }
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        match kve.pf_merge(dest, act) {
+            Ok(Some(())) => con._write_raw(P::RCODE_OKAY).await?,
+            Ok(None) => return util::err(P::RCODE_WRONGTYPE_ERR),
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,92 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `INCRBY` and `DECRBY` queries
+//! This module provides functions to use the values of a key/value table as counters. A counter
+//! is a value that holds a 64-bit signed integer in decimal (like `SET counter 10`), and a key
+//! that doesn't exist is a counter at zero
+
+use crate::{dbnet::prelude::*, kvengine::IncrError};
+
+const ERR_OVERFLOW: &[u8] = b"!12\nerr-overflow\n";
+
+macro_rules! incr {
+    ($handle:expr, $con:expr, $key:expr, $delta:expr) => {{
+        let kve = $handle.get_table_with::<P, KVEBlob>()?;
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        match kve.incr_by($key, $delta) {
+            Ok(Ok(new)) => {
+                // the new value is typed like any other value in the table
+                $con.write_mono_length_prefixed_with_tsymbol(
+                    new.to_string().as_bytes(),
+                    kve.get_value_tsymbol(),
+                )
+                .await?
+            }
+            Ok(Err(IncrError::NotAnInteger)) => return util::err(P::RCODE_WRONGTYPE_ERR),
+            Ok(Err(IncrError::Overflow)) => return util::err(ERR_OVERFLOW),
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+    }};
+}
+
+action! {
+    /// Run an `INCRBY` query, which adds `<delta>` to a counter and returns its new value
+    /// Syntax: `INCRBY <key> <delta>`
+    fn incrby(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 2)?;
+        let key = unsafe { act.next_unchecked_bytes() };
+        let delta = match self::parse_delta(unsafe { act.next_unchecked() }) {
+            Some(delta) => delta,
+            None => return util::err(P::RCODE_WRONGTYPE_ERR),
+        };
+        incr!(handle, con, key, delta);
+        Ok(())
+    }
+
+    /// Run a `DECRBY` query, which subtracts `<delta>` from a counter and returns its new value
+    /// Syntax: `DECRBY <key> <delta>`
+    fn decrby(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 2)?;
+        let key = unsafe { act.next_unchecked_bytes() };
+        let delta = match self::parse_delta(unsafe { act.next_unchecked() }) {
+            Some(delta) => delta,
+            None => return util::err(P::RCODE_WRONGTYPE_ERR),
+        };
+        match delta.checked_neg() {
+            Some(delta) => incr!(handle, con, key, delta),
+            None => return util::err(ERR_OVERFLOW),
+        }
+        Ok(())
+    }
+}
+
+/// Parse a delta, which is a 64-bit signed integer in decimal
+fn parse_delta(delta: &[u8]) -> Option<i64> {
+    core::str::from_utf8(delta).ok()?.parse().ok()
+}
@@ -0,0 +1,80 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `KEYS` queries
+//! This module provides a function to list the keys of a table that match a glob pattern,
+//! which is evaluated on the server so that clients don't have to pull every key to filter
+//! a handful
+
+use crate::{corestore::table::DataModel, dbnet::prelude::*};
+
+const PAGE: &[u8] = b"page";
+
+action!(
+    /// Run a `KEYS` query, which returns every key of the current table that matches the glob
+    /// `<pattern>` (see [`util::glob_match`]). With `PAGE`, only the first `<size>` keys are
+    /// returned, after the ID of a cursor over the rest (see [`fetch`](super::fetch))
+    ///
+    /// ## Syntax
+    /// `KEYS <pattern> [PAGE <size>]`
+    fn keys(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 1 || len == 3)?;
+        let pattern = unsafe {
+            // UNSAFE(@ohsayan): we've already checked that there's at least one argument
+            act.next_unchecked()
+        };
+        let page_size = match (act.next(), act.next()) {
+            (Some(option), Some(size)) if option.eq_ignore_ascii_case(PAGE) => {
+                match String::from_utf8_lossy(size).parse::<usize>() {
+                    Ok(size) if size != 0 => Some(size),
+                    _ => return util::err(P::RCODE_WRONGTYPE_ERR),
+                }
+            }
+            (Some(_), _) => return util::err(P::RSTRING_UNKNOWN_PROPERTY),
+            _ => None,
+        };
+        let table = get_tbl!(handle, con);
+        let (tsymbol, keys) = match table.get_model_ref() {
+            DataModel::KV(kv) => (kv.get_key_tsymbol(), kv.match_keys(pattern)),
+            DataModel::KVExtListmap(kv) => (kv.get_key_tsymbol(), kv.match_keys(pattern)),
+            DataModel::KVExtMap(kv) => (kv.get_key_tsymbol(), kv.match_keys(pattern)),
+            DataModel::KVExtSet(kv) => (kv.get_key_tsymbol(), kv.match_keys(pattern)),
+            DataModel::KVExtSortedSet(kv) => (kv.get_key_tsymbol(), kv.match_keys(pattern)),
+            DataModel::KVExtDocument(kv) => (kv.get_key_tsymbol(), kv.match_keys(pattern)),
+        };
+        if let Some(page_size) = page_size {
+            let page = con.cursors().open(keys, tsymbol, page_size);
+            return super::fetch::write_page(con, page).await;
+        }
+        con.deadline().check::<P>()?;
+        con.write_typed_non_null_array_header(keys.len(), tsymbol)
+            .await?;
+        for key in keys {
+            con.write_typed_non_null_array_element(&key).await?;
+        }
+        Ok(())
+    }
+);
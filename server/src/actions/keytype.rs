@@ -0,0 +1,75 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `TYPE` queries
+//! This module provides a function to find out what a key holds, so that generic tooling can
+//! pick the actions to use for a key without guessing from failed queries
+
+use crate::{corestore::table::DataModel, dbnet::prelude::*};
+
+action!(
+    /// Run a `TYPE` query, which returns the type of the value of a key in the current table
+    /// (or nil if the key doesn't exist). The types are named like in model declarations:
+    /// `str`, `binstr`, `list<str>`, `list<binstr>`, `map<..>`, `set<..>`, `zset<..>` or `json`
+    ///
+    /// ## Syntax
+    /// `TYPE <key>`
+    fn keytype(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 1)?;
+        let key = unsafe { act.next_unchecked() };
+        let table = get_tbl!(handle, con);
+        let pick = |names: [&'static str; 2], e_v: bool| names[e_v as usize];
+        let (exists, name) = match table.get_model_ref() {
+            DataModel::KV(kv) => (
+                kv.exists(key),
+                pick(["binstr", "str"], kv.get_encoding_tuple().1),
+            ),
+            DataModel::KVExtListmap(kv) => (
+                kv.exists(key),
+                pick(["list<binstr>", "list<str>"], kv.get_encoding_tuple().1),
+            ),
+            DataModel::KVExtMap(kv) => (
+                kv.exists(key),
+                pick(["map<binstr>", "map<str>"], kv.get_encoding_tuple().1),
+            ),
+            DataModel::KVExtSet(kv) => (
+                kv.exists(key),
+                pick(["set<binstr>", "set<str>"], kv.get_encoding_tuple().1),
+            ),
+            DataModel::KVExtSortedSet(kv) => (
+                kv.exists(key),
+                pick(["zset<binstr>", "zset<str>"], kv.get_encoding_tuple().1),
+            ),
+            DataModel::KVExtDocument(kv) => (kv.exists(key), "json"),
+        };
+        match exists {
+            Ok(true) => con.write_string(name).await?,
+            Ok(false) => con._write_raw(P::RCODE_NIL).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+);
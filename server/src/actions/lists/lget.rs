@@ -32,6 +32,8 @@ const VALUEAT: &[u8] = "VALUEAT".as_bytes();
 const LAST: &[u8] = "LAST".as_bytes();
 const FIRST: &[u8] = "FIRST".as_bytes();
 const RANGE: &[u8] = "RANGE".as_bytes();
+const PAGE: &[u8] = "PAGE".as_bytes();
+const MAXLEN: &[u8] = "MAXLEN".as_bytes();
 
 struct Range {
     start: usize,
@@ -57,10 +59,14 @@ action! {
     /// ## Syntax
     /// - `LGET <mylist>` will return the full list
     /// - `LGET <mylist> LEN` will return the length of the list
+    /// - `LGET <mylist> MAXLEN` will return the maximum length of the list (0 if it doesn't
+    /// have one)
     /// - `LGET <mylist> LIMIT <limit>` will return a maximum of `limit` elements
     /// - `LGET <mylist> VALUEAT <index>` will return the value at the provided index
     /// - `LGET <mylist> FIRST` will return the first item
     /// - `LGET <mylist> LAST` will return the last item
+    /// - `LGET <mylist> PAGE <size>` will return the ID of a cursor over the list followed by
+    /// its first `size` elements (see [`fetch`](crate::actions::fetch))
     /// if it exists
     fn lget(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
         ensure_length::<P>(act.len(), |len| len != 0)?;
@@ -96,6 +102,14 @@ action! {
                             Err(()) => return Err(P::RCODE_ENCODING_ERROR.into()),
                         }
                     }
+                    MAXLEN => {
+                        ensure_length::<P>(act.len(), |len| len == 0)?;
+                        match listmap.list_bound(listname) {
+                            Ok(Some(bound)) => con.write_usize(bound.unwrap_or(0)).await?,
+                            Ok(None) => return Err(P::RCODE_NIL.into()),
+                            Err(()) => return Err(P::RCODE_ENCODING_ERROR.into()),
+                        }
+                    }
                     LIMIT => {
                         ensure_length::<P>(act.len(), |len| len == 1)?;
                         let count = get_numeric_count!();
@@ -105,6 +119,22 @@ action! {
                             Err(()) => return Err(P::RCODE_ENCODING_ERROR.into()),
                         }
                     }
+                    PAGE => {
+                        ensure_length::<P>(act.len(), |len| len == 1)?;
+                        let page_size = get_numeric_count!();
+                        if page_size == 0 {
+                            return util::err(P::RCODE_WRONGTYPE_ERR);
+                        }
+                        let items = match listmap.list_cloned_full(listname) {
+                            Ok(Some(list)) => list,
+                            Ok(None) => return Err(P::RCODE_NIL.into()),
+                            Err(()) => return Err(P::RCODE_ENCODING_ERROR.into()),
+                        };
+                        let page = con
+                            .cursors()
+                            .open(items, listmap.get_value_tsymbol(), page_size);
+                        crate::actions::fetch::write_page(con, page).await?;
+                    }
                     VALUEAT => {
                         ensure_length::<P>(act.len(), |len| len == 1)?;
                         let idx = get_numeric_count!();
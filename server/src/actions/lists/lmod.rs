@@ -24,13 +24,14 @@
  *
 */
 
-use crate::{corestore::SharedSlice, dbnet::prelude::*, util::compiler};
+use crate::{corestore::SharedSlice, dbnet::prelude::*, kvengine::eviction, util::compiler};
 
 const CLEAR: &[u8] = "CLEAR".as_bytes();
 const PUSH: &[u8] = "PUSH".as_bytes();
 const REMOVE: &[u8] = "REMOVE".as_bytes();
 const INSERT: &[u8] = "INSERT".as_bytes();
 const POP: &[u8] = "POP".as_bytes();
+const MAXLEN: &[u8] = "MAXLEN".as_bytes();
 
 action! {
     /// Handle `LMOD` queries
@@ -40,11 +41,13 @@ action! {
     /// - `LMOD <mylist> insert <index> <value>`
     /// - `LMOD <mylist> remove <index>`
     /// - `LMOD <mylist> clear`
+    /// - `LMOD <mylist> maxlen <len>` (where a `<len>` of 0 removes the bound)
     fn lmod(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
         ensure_length::<P>(act.len(), |len| len > 1)?;
         let listmap = handle.get_table_with::<P, KVEList>()?;
         // get the list name
         let listname = unsafe { act.next_unchecked() };
+        listmap.expire_if_due(listname);
         macro_rules! get_numeric_count {
             () => {
                 match unsafe { String::from_utf8_lossy(act.next_unchecked()) }.parse::<usize>() {
@@ -62,7 +65,13 @@ action! {
                     _ => return Err(P::RCODE_NIL.into()),
                 };
                 let okay = if registry::state_okay() {
-                    list.write().clear();
+                    let freed = list
+                        .write()
+                        .drain(..)
+                        .map(|element| eviction::element_size(&element))
+                        .sum();
+                    listmap.memory().release(freed);
+                    listmap.mark_dirty();
                     P::RCODE_OKAY
                 } else {
                     P::RCODE_SERVER_ERR
@@ -78,7 +87,15 @@ action! {
                 let venc_ok = listmap.get_val_encoder();
                 let ret = if compiler::likely(act.as_ref().all(venc_ok)) {
                     if registry::state_okay() {
-                        list.write().extend(act.map(SharedSlice::new));
+                        let mut grown = 0;
+                        let mut wlock = list.write();
+                        wlock.extend(act.map(|element| {
+                            grown += eviction::element_size(element);
+                            SharedSlice::new(element)
+                        }));
+                        listmap.memory().charge(grown);
+                        listmap.enforce_bound(listname, &mut wlock);
+                        listmap.mark_dirty();
                         P::RCODE_OKAY
                     } else {
                         P::RCODE_SERVER_ERR
@@ -95,7 +112,9 @@ action! {
                     let maybe_value = listmap.get_inner_ref().get(listname).map(|list| {
                         let mut wlock = list.write();
                         if idx_to_remove < wlock.len() {
-                            wlock.remove(idx_to_remove);
+                            let removed = wlock.remove(idx_to_remove);
+                            listmap.memory().release(eviction::element_size(&removed));
+                            listmap.mark_dirty();
                             true
                         } else {
                             false
@@ -119,6 +138,9 @@ action! {
                                 if idx_to_insert_at < wlock.len() {
                                     // we can insert
                                     wlock.insert(idx_to_insert_at, SharedSlice::new(bts));
+                                    listmap.memory().charge(eviction::element_size(bts));
+                                    listmap.enforce_bound(listname, &mut wlock);
+                                    listmap.mark_dirty();
                                     true
                                 } else {
                                     // oops, out of bounds
@@ -164,6 +186,10 @@ action! {
                         }),
                         Err(()) => return Err(P::RCODE_ENCODING_ERROR.into()),
                     };
+                    if let Some(Some(popped)) = &maybe_pop {
+                        listmap.memory().release(eviction::element_size(popped));
+                        listmap.mark_dirty();
+                    }
                     match maybe_pop {
                         Some(Some(val)) => {
                             con.write_mono_length_prefixed_with_tsymbol(
@@ -179,6 +205,23 @@ action! {
                     con._write_raw(P::RCODE_SERVER_ERR).await?
                 }
             }
+            MAXLEN => {
+                ensure_length::<P>(act.len(), |len| len == 1)?;
+                let bound = match get_numeric_count!() {
+                    0 => None,
+                    bound => Some(bound),
+                };
+                if registry::state_okay() {
+                    let okay = match listmap.set_list_bound(listname, bound) {
+                        Ok(true) => P::RCODE_OKAY,
+                        Ok(false) => P::RCODE_NIL,
+                        Err(()) => P::RCODE_ENCODING_ERROR,
+                    };
+                    con._write_raw(okay).await?
+                } else {
+                    con._write_raw(P::RCODE_SERVER_ERR).await?
+                }
+            }
             _ => con._write_raw(P::RCODE_UNKNOWN_ACTION).await?,
         }
         Ok(())
@@ -26,6 +26,7 @@
 
 macro_rules! writelist {
     ($con:expr, $listmap:expr, $items:expr) => {{
+        $con.deadline().check::<P>()?;
         $con.write_typed_non_null_array_header($items.len(), $listmap.get_value_tsymbol())
             .await?;
         for item in $items {
@@ -29,8 +29,14 @@ mod macros;
 // modules
 pub mod lget;
 pub mod lmod;
+pub mod sort;
 
-use crate::{corestore::SharedSlice, dbnet::prelude::*, kvengine::LockedVec};
+use crate::{
+    actions::range::parse_offset,
+    corestore::SharedSlice,
+    dbnet::prelude::*,
+    kvengine::{eviction, KVEValue, LockedVec},
+};
 
 action! {
     /// Handle an `LSET` query for the list model
@@ -39,11 +45,17 @@ action! {
         ensure_length::<P>(act.len(), |len| len > 0)?;
         let listmap = handle.get_table_with::<P, KVEList>()?;
         let listname = unsafe { act.next_unchecked_bytes() };
+        listmap.expire_if_due(&listname);
         let list = listmap.get_inner_ref();
         if registry::state_okay() {
-            let did = if let Some(entry) = list.fresh_entry(listname) {
+            let did = if let Some(entry) = list.fresh_entry(listname.clone()) {
                 let v: Vec<SharedSlice> = act.map(SharedSlice::new).collect();
-                entry.insert(LockedVec::new(v));
+                let value = LockedVec::new(v);
+                let size = eviction::entry_size(&listname, value.footprint());
+                listmap.expiry().remove(&listname);
+                entry.insert(value);
+                listmap.memory().inserted(&listname, size);
+                listmap.mark_dirty();
                 true
             } else {
                 false
@@ -55,3 +67,88 @@ action! {
         Ok(())
     }
 }
+
+action! {
+    /// Handle an `LTRIM` query for the list model, which keeps only the elements from `start`
+    /// to `stop` (both inclusive, and counting from the end of the list if they're negative)
+    /// Syntax: `LTRIM <listname> <start> <stop>`
+    fn ltrim(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 3)?;
+        let listmap = handle.get_table_with::<P, KVEList>()?;
+        let (listname, start, stop) = unsafe {
+            // UNSAFE(@ohsayan): we've already checked that there are three arguments
+            (act.next_unchecked(), act.next_unchecked(), act.next_unchecked())
+        };
+        let (start, stop) = match (parse_offset(start), parse_offset(stop)) {
+            (Some(start), Some(stop)) => (start, stop),
+            _ => return util::err(P::RCODE_WRONGTYPE_ERR),
+        };
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        let ret = match listmap.trim_list(listname, start, stop) {
+            Ok(Some(_)) => P::RCODE_OKAY,
+            Ok(None) => P::RCODE_NIL,
+            Err(()) => P::RCODE_ENCODING_ERROR,
+        };
+        con._write_raw(ret).await?;
+        Ok(())
+    }
+}
+
+action! {
+    /// Handle an `LINSERT` query for the list model, which inserts a value at the given index
+    /// (shifting the elements after it to the right) and returns the new length of the list
+    /// Syntax: `LINSERT <listname> <index> <value>`
+    fn linsert(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 3)?;
+        let listmap = handle.get_table_with::<P, KVEList>()?;
+        let (listname, index, value) = unsafe {
+            // UNSAFE(@ohsayan): we've already checked that there are three arguments
+            (act.next_unchecked(), act.next_unchecked(), act.next_unchecked())
+        };
+        let index = match String::from_utf8_lossy(index).parse::<usize>() {
+            Ok(index) => index,
+            Err(_) => return util::err(P::RCODE_WRONGTYPE_ERR),
+        };
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        match listmap.list_insert(listname, index, value) {
+            Ok(Some(Some(len))) => con.write_usize(len).await?,
+            Ok(Some(None)) => con._write_raw(P::RSTRING_LISTMAP_BAD_INDEX).await?,
+            Ok(None) => con._write_raw(P::RCODE_NIL).await?,
+            Err(()) => con._write_raw(P::RCODE_ENCODING_ERROR).await?,
+        }
+        Ok(())
+    }
+}
+
+action! {
+    /// Handle an `LREM` query for the list model, which removes up to `count` elements equal to
+    /// the value (from the front if `count` is positive, from the back if it's negative and all
+    /// of them if it's 0 or missing) and returns the number of elements that were removed
+    /// Syntax: `LREM <listname> <value> [count]`
+    fn lrem(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 2 || len == 3)?;
+        let listmap = handle.get_table_with::<P, KVEList>()?;
+        let (listname, value) = unsafe {
+            // UNSAFE(@ohsayan): we've already checked that there are at least two arguments
+            (act.next_unchecked(), act.next_unchecked())
+        };
+        let count = match act.next().map(parse_offset) {
+            Some(Some(count)) => count,
+            Some(None) => return util::err(P::RCODE_WRONGTYPE_ERR),
+            None => 0,
+        };
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        match listmap.list_remove(listname, value, count) {
+            Ok(Some(removed)) => con.write_usize(removed).await?,
+            Ok(None) => con._write_raw(P::RCODE_NIL).await?,
+            Err(()) => con._write_raw(P::RCODE_ENCODING_ERROR).await?,
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,124 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `SORT` queries
+//! This module provides a function to sort the elements of a list on the server, so that
+//! clients don't have to pull a large list just to sort it (and throw most of it away)
+
+use crate::dbnet::prelude::*;
+use core::cmp::Ordering;
+
+const ALPHA: &[u8] = "ALPHA".as_bytes();
+const ASC: &[u8] = "ASC".as_bytes();
+const DESC: &[u8] = "DESC".as_bytes();
+const LIMIT: &[u8] = "LIMIT".as_bytes();
+
+action! {
+    /// Handle a `SORT` query, which returns the elements of a list sorted by their numeric
+    /// value (with the elements that aren't numbers after all the numbers) or by their bytes
+    /// with `ALPHA`. The list itself isn't changed
+    /// ## Syntax
+    /// `SORT <mylist> [ALPHA] [ASC|DESC] [LIMIT <offset> <count>]`
+    fn sort(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len != 0)?;
+        let listmap = handle.get_table_with::<P, KVEList>()?;
+        let listname = unsafe { act.next_unchecked() };
+        let (mut alpha, mut descending) = (false, false);
+        let (mut offset, mut count) = (0, usize::MAX);
+        while let Some(option) = act.next_uppercase() {
+            match option.as_ref() {
+                ALPHA => alpha = true,
+                ASC => descending = false,
+                DESC => descending = true,
+                LIMIT => {
+                    ensure_length::<P>(act.len(), |len| len >= 2)?;
+                    let (start, limit) = unsafe {
+                        // UNSAFE(@ohsayan): we've just checked that there are two more arguments
+                        (act.next_unchecked(), act.next_unchecked())
+                    };
+                    match (parse_usize(start), parse_usize(limit)) {
+                        (Some(start), Some(limit)) => (offset, count) = (start, limit),
+                        _ => return util::err(P::RCODE_WRONGTYPE_ERR),
+                    }
+                }
+                _ => return util::err(P::RSTRING_UNKNOWN_PROPERTY),
+            }
+        }
+        let mut items = match listmap.list_cloned_full(listname) {
+            Ok(Some(list)) => list,
+            Ok(None) => return util::err(P::RCODE_NIL),
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        };
+        con.deadline().check::<P>()?;
+        if alpha {
+            items.sort_unstable_by(|a, b| a[..].cmp(&b[..]));
+        } else {
+            items.sort_unstable_by(|a, b| compare_numeric(a, b));
+        }
+        if descending {
+            items.reverse();
+        }
+        let items: Vec<_> = items.into_iter().skip(offset).take(count).collect();
+        writelist!(con, listmap, items);
+        Ok(())
+    }
+}
+
+/// Compare two elements by their numeric value. Elements that aren't numbers are ordered after
+/// all the numbers, and ties are broken by the raw bytes so that this is a total order
+fn compare_numeric(a: &[u8], b: &[u8]) -> Ordering {
+    let ord = match (parse_number(a), parse_number(b)) {
+        (Some(x), Some(y)) => x.total_cmp(&y),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    };
+    ord.then_with(|| a.cmp(b))
+}
+
+fn parse_number(element: &[u8]) -> Option<f64> {
+    core::str::from_utf8(element)
+        .ok()
+        .and_then(|element| element.parse::<f64>().ok())
+        .filter(|num| num.is_finite())
+}
+
+fn parse_usize(bytes: &[u8]) -> Option<usize> {
+    core::str::from_utf8(bytes).ok()?.parse().ok()
+}
+
+#[test]
+fn test_compare_numeric() {
+    assert_eq!(compare_numeric(b"9", b"10"), Ordering::Less);
+    assert_eq!(compare_numeric(b"-2.5", b"-2"), Ordering::Less);
+    assert_eq!(compare_numeric(b"100", b"abc"), Ordering::Less);
+    assert_eq!(compare_numeric(b"abc", b"abd"), Ordering::Less);
+    // numerically equal; ordered by bytes
+    assert_eq!(compare_numeric(b"01", b"1"), Ordering::Less);
+    let mut items: Vec<&[u8]> = vec![b"x", b"10", b"2", b"-1", b"1.5"];
+    items.sort_by(|a, b| compare_numeric(a, b));
+    assert_eq!(items, vec![&b"-1"[..], b"1.5", b"2", b"10", b"x"]);
+}
@@ -68,11 +68,20 @@ action!(
         let tsymbol = match table.get_model_ref() {
             DataModel::KV(kv) => kv.get_value_tsymbol(),
             DataModel::KVExtListmap(kv) => kv.get_value_tsymbol(),
+            DataModel::KVExtMap(kv) => kv.get_value_tsymbol(),
+            DataModel::KVExtSet(kv) => kv.get_value_tsymbol(),
+            DataModel::KVExtSortedSet(kv) => kv.get_value_tsymbol(),
+            DataModel::KVExtDocument(kv) => kv.get_value_tsymbol(),
         };
         let items: Vec<SharedSlice> = match table.get_model_ref() {
-            DataModel::KV(kv) => kv.get_inner_ref().get_keys(count),
+            DataModel::KV(kv) => kv.get_keys(count),
             DataModel::KVExtListmap(kv) => kv.get_inner_ref().get_keys(count),
+            DataModel::KVExtMap(kv) => kv.get_inner_ref().get_keys(count),
+            DataModel::KVExtSet(kv) => kv.get_inner_ref().get_keys(count),
+            DataModel::KVExtSortedSet(kv) => kv.get_inner_ref().get_keys(count),
+            DataModel::KVExtDocument(kv) => kv.get_inner_ref().get_keys(count),
         };
+        con.deadline().check::<P>()?;
         con.write_typed_non_null_array_header(items.len(), tsymbol)
             .await?;
         for key in items {
@@ -0,0 +1,111 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `HSET`, `HGET`, `HDEL` and `HGETALL` queries
+//! This module provides functions to work with the fields of the maps in a `KVExt/Map` table
+//! (that is, a table declared with a `map<...>` value type)
+
+use crate::{corestore::SharedSlice, dbnet::prelude::*};
+
+action! {
+    /// Run an `HSET` query, which returns the number of fields that were added
+    /// Syntax: `HSET <map> <field> <value> [<field> <value> ...]`
+    fn hset(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len > 2 && len % 2 == 1)?;
+        let mapper = handle.get_table_with::<P, KVEMapT>()?;
+        let key = unsafe { act.next_unchecked_bytes() };
+        let mut pairs = Vec::with_capacity(act.len() / 2);
+        while let (Some(field), Some(value)) = (act.next(), act.next()) {
+            pairs.push((SharedSlice::new(field), SharedSlice::new(value)));
+        }
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        match mapper.map_set(key, pairs) {
+            Ok(added) => con.write_usize(added).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run an `HGET` query
+    /// Syntax: `HGET <map> <field>`
+    fn hget(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 2)?;
+        let mapper = handle.get_table_with::<P, KVEMapT>()?;
+        let key = unsafe { act.next_unchecked() };
+        let field = unsafe { act.next_unchecked() };
+        match mapper.map_get(key, field) {
+            Ok(Some(Some(value))) => {
+                con.write_mono_length_prefixed_with_tsymbol(&value, mapper.get_value_tsymbol())
+                    .await?
+            }
+            Ok(_) => con._write_raw(P::RCODE_NIL).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run an `HDEL` query, which returns the number of fields that were removed. A map is
+    /// removed along with its last field
+    /// Syntax: `HDEL <map> <field> [<field> ...]`
+    fn hdel(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len > 1)?;
+        let mapper = handle.get_table_with::<P, KVEMapT>()?;
+        let key = unsafe { act.next_unchecked() };
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        match mapper.map_del(key, act) {
+            Ok(Some(removed)) => con.write_usize(removed).await?,
+            Ok(None) => con._write_raw(P::RCODE_NIL).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run an `HGETALL` query, which returns the fields of a map along with their values, as
+    /// `[<field>, <value>, ...]`
+    /// Syntax: `HGETALL <map>`
+    fn hgetall(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 1)?;
+        let mapper = handle.get_table_with::<P, KVEMapT>()?;
+        let key = unsafe { act.next_unchecked() };
+        match mapper.map_cloned_full(key) {
+            Ok(Some(fields)) => {
+                con.write_typed_non_null_array_header(fields.len() * 2, mapper.get_value_tsymbol())
+                    .await?;
+                for (field, value) in fields {
+                    con.write_typed_non_null_array_element(&field).await?;
+                    con.write_typed_non_null_array_element(&value).await?;
+                }
+            }
+            Ok(None) => con._write_raw(P::RCODE_NIL).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+}
@@ -24,9 +24,16 @@
  *
 */
 
-use crate::{
-    dbnet::prelude::*, kvengine::encoding::ENCODING_LUT_ITER, queryengine::ActionIter,
-    util::compiler,
+use {
+    crate::{
+        blueql,
+        corestore::table::{DescribeTable, Table},
+        dbnet::prelude::*,
+        kvengine::encoding::ENCODING_LUT_ITER,
+        queryengine::ActionIter,
+        util::compiler,
+    },
+    std::sync::Arc,
 };
 
 action!(
@@ -50,4 +57,88 @@ action!(
         }
         Ok(())
     }
+
+    /// Run an `XMGET` query, which is an `MGET` across tables: every argument names a key along
+    /// with the table that holds it. The values are written as a typed array of the type of the
+    /// values of the tables, or of binary strings if the tables don't agree on one
+    ///
+    /// ## Syntax
+    /// `XMGET <keyspace:table:key1> <keyspace:table:key2> ...`
+    fn xmget(
+        handle: &crate::corestore::Corestore,
+        con: &mut Connection<C, P>,
+        act: ActionIter<'a>,
+    ) {
+        ensure_length::<P>(act.len(), |size| size != 0)?;
+        // the same tables tend to show up again and again, so they're only looked up once
+        let mut tables: Vec<(&[u8], &[u8], Arc<Table>)> = Vec::new();
+        let mut values = Vec::with_capacity(act.len());
+        let mut tsymbol = None;
+        for triplet in act {
+            let (ksid, tblid, key) = match self::split_triplet(triplet) {
+                Some(split) => split,
+                None => return util::err(P::RSTRING_BAD_CONTAINER_NAME),
+            };
+            let cached = tables
+                .iter()
+                .position(|(ks, tbl, _)| *ks == ksid && *tbl == tblid);
+            let idx = match cached {
+                Some(idx) => idx,
+                None => {
+                    blueql::util::validate_entity_name::<P>(ksid)?;
+                    blueql::util::validate_entity_name::<P>(tblid)?;
+                    let table = handle
+                        .get_keyspace(ksid)
+                        .and_then(|ks| ks.get_table_atomic_ref(tblid));
+                    match table {
+                        Some(table) => tables.push((ksid, tblid, table)),
+                        None => return util::err(P::RSTRING_CONTAINER_NOT_FOUND),
+                    }
+                    tables.len() - 1
+                }
+            };
+            let kve = match KVEBlob::try_get(&tables[idx].2) {
+                Some(kve) => kve,
+                None => return util::err(P::RSTRING_WRONG_MODEL),
+            };
+            tsymbol = match tsymbol {
+                Some(tsymbol) if tsymbol != kve.get_value_tsymbol() => Some(P::TSYMBOL_BINARY),
+                Some(tsymbol) => Some(tsymbol),
+                None => Some(kve.get_value_tsymbol()),
+            };
+            match kve.get_cloned(key) {
+                Ok(value) => values.push(value),
+                Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+            }
+        }
+        // UNWRAP: there's at least one key
+        con.write_typed_array_header(values.len(), tsymbol.unwrap())
+            .await?;
+        for value in values {
+            match value {
+                Some(v) => con.write_typed_array_element(&v).await?,
+                None => con.write_typed_array_element_null().await?,
+            }
+        }
+        Ok(())
+    }
 );
+
+/// Split a `keyspace:table:key` triplet. The key can hold colons, but the names can't
+fn split_triplet(triplet: &[u8]) -> Option<(&[u8], &[u8], &[u8])> {
+    let mut parts = triplet.splitn(3, |byte| *byte == b':');
+    Some((parts.next()?, parts.next()?, parts.next()?))
+}
+
+#[test]
+fn test_split_triplet() {
+    assert_eq!(
+        split_triplet(b"ks:tbl:key"),
+        Some((b"ks".as_slice(), b"tbl".as_slice(), b"key".as_slice()))
+    );
+    assert_eq!(
+        split_triplet(b"ks:tbl:a:b"),
+        Some((b"ks".as_slice(), b"tbl".as_slice(), b"a:b".as_slice()))
+    );
+    assert_eq!(split_triplet(b"ks:key"), None);
+}
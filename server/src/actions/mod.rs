@@ -32,24 +32,44 @@
 
 #[macro_use]
 mod macros;
+pub mod bitmap;
+pub mod cas;
+pub mod copytable;
 pub mod dbsize;
 pub mod del;
+pub mod delmatch;
+pub mod documents;
 pub mod exists;
+pub mod expire;
+pub mod fetch;
+pub mod findkeys;
 pub mod flushdb;
 pub mod get;
+pub mod hll;
+pub mod incr;
 pub mod keylen;
+pub mod keys;
+pub mod keytype;
 pub mod lists;
 pub mod lskeys;
+pub mod maps;
 pub mod mget;
 pub mod mpop;
 pub mod mset;
 pub mod mupdate;
 pub mod pop;
+pub mod pubsub;
+pub mod randomkey;
+pub mod range;
+pub mod scan;
 pub mod set;
+pub mod sets;
 pub mod strong;
+pub mod txn;
 pub mod update;
 pub mod uset;
 pub mod whereami;
+pub mod zsets;
 use {
     crate::{corestore::memstore::DdlError, protocol::interface::ProtocolSpec, util},
     std::io::Error as IoError,
@@ -100,6 +120,7 @@ fn map_ddl_error_to_status<P: ProtocolSpec>(e: DdlError) -> ActionError {
         DdlError::ProtectedObject => P::RSTRING_PROTECTED_OBJECT,
         DdlError::StillInUse => P::RSTRING_STILL_IN_USE,
         DdlError::WrongModel => P::RSTRING_WRONG_MODEL,
+        DdlError::ReadOnly => P::RSTRING_READ_ONLY,
     };
     ActionError::ActionError(r)
 }
@@ -130,17 +151,41 @@ pub fn ensure_boolean_or_aerr<P: ProtocolSpec>(boolean: bool) -> ActionResult<()
 
 pub mod heya {
     //! Respond to `HEYA` queries
-    use crate::dbnet::prelude::*;
+    use crate::dbnet::{compress::Codec, prelude::*};
+    const COMPRESS: &[u8] = b"compress";
+    const COMPRESS_NONE: &[u8] = b"none";
     action!(
-        /// Returns a `HEY!` `Response`
+        /// Returns a `HEY!` `Response`, or negotiates the compression of responses with
+        /// `HEYA COMPRESS <algorithm> [<algorithm> ...]`
         fn heya(_handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
-            ensure_length::<P>(act.len(), |len| len == 0 || len == 1)?;
-            if act.len() == 1 {
-                let raw_byte = unsafe { act.next_unchecked() };
-                con.write_mono_length_prefixed_with_tsymbol(raw_byte, b'+')
-                    .await?;
-            } else {
-                con._write_raw(P::ELEMRESP_HEYA).await?;
+            match act.len() {
+                0 => con._write_raw(P::ELEMRESP_HEYA).await?,
+                1 => {
+                    let raw_byte = unsafe { act.next_unchecked() };
+                    con.write_mono_length_prefixed_with_tsymbol(raw_byte, b'+')
+                        .await?;
+                }
+                _ => {
+                    ensure_boolean_or_aerr::<P>(
+                        unsafe { act.next_lowercase_unchecked() }.as_ref() == COMPRESS,
+                    )?;
+                    // if compression is already on, the client gets what it already has
+                    let codec = con.compression().or_else(|| {
+                        act.find_map(|algorithm| Codec::from_name(&algorithm.to_ascii_lowercase()))
+                    });
+                    match codec {
+                        Some(codec) => {
+                            con.write_mono_length_prefixed_with_tsymbol(codec.name(), b'+')
+                                .await?;
+                            // the response above is still sent as-is
+                            con.enable_compression(codec);
+                        }
+                        None => {
+                            con.write_mono_length_prefixed_with_tsymbol(COMPRESS_NONE, b'+')
+                                .await?
+                        }
+                    }
+                }
             }
             Ok(())
         }
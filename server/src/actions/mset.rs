@@ -38,12 +38,11 @@ action!(
         let encoding_is_okay = ENCODING_LUT_ITER_PAIR[kve.get_encoding_tuple()](&act);
         if compiler::likely(encoding_is_okay) {
             let done_howmany: Option<usize> = if registry::state_okay() {
-                let mut didmany = 0;
+                let mut entries = Vec::with_capacity(howmany / 2);
                 while let (Some(key), Some(val)) = (act.next(), act.next()) {
-                    didmany +=
-                        kve.set_unchecked(SharedSlice::new(key), SharedSlice::new(val)) as usize;
+                    entries.push((SharedSlice::new(key), SharedSlice::new(val)));
                 }
-                Some(didmany)
+                Some(kve.set_many_unchecked(entries))
             } else {
                 None
             };
@@ -27,6 +27,8 @@
 use crate::dbnet::prelude::*;
 
 action! {
+    /// Run a `POP` (or `GETDEL`) query, which removes a key and returns its value in a single
+    /// step, so that only one of several clients racing for a key can get its value
     fn pop(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
         ensure_length::<P>(act.len(), |len| len == 1)?;
         let key = unsafe {
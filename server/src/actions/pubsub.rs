@@ -0,0 +1,91 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Pub/sub
+//!
+//! `SUBSCRIBE`, `PSUBSCRIBE`, `UNSUBSCRIBE` and `PUNSUBSCRIBE` change the subscriptions of the
+//! connection and return the number of channels and patterns that it's subscribed to after the
+//! change, while `PUBLISH` returns the number of subscriptions that the message was delivered
+//! to. See [`crate::dbnet::pubsub`] for how messages are delivered
+
+use crate::dbnet::prelude::*;
+
+action! {
+    /// Run a `SUBSCRIBE <channel> ...` query
+    fn subscribe(_handle: &Corestore, con: &mut Connection<C, P>, act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len != 0)?;
+        act.for_each(|channel| con.subscriber().subscribe(channel));
+        let count = con.subscriber().count();
+        con.write_usize(count).await?;
+        Ok(())
+    }
+    /// Run a `PSUBSCRIBE <pattern> ...` query
+    fn psubscribe(_handle: &Corestore, con: &mut Connection<C, P>, act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len != 0)?;
+        act.for_each(|pattern| con.subscriber().psubscribe(pattern));
+        let count = con.subscriber().count();
+        con.write_usize(count).await?;
+        Ok(())
+    }
+    /// Run an `UNSUBSCRIBE [<channel> ...]` query. Without any channels, this unsubscribes from
+    /// every channel
+    fn unsubscribe(_handle: &Corestore, con: &mut Connection<C, P>, act: ActionIter<'a>) {
+        if act.is_empty() {
+            con.subscriber().unsubscribe_all();
+        } else {
+            act.for_each(|channel| con.subscriber().unsubscribe(channel));
+        }
+        let count = con.subscriber().count();
+        con.write_usize(count).await?;
+        Ok(())
+    }
+    /// Run a `PUNSUBSCRIBE [<pattern> ...]` query. Without any patterns, this unsubscribes from
+    /// every pattern
+    fn punsubscribe(_handle: &Corestore, con: &mut Connection<C, P>, act: ActionIter<'a>) {
+        if act.is_empty() {
+            con.subscriber().punsubscribe_all();
+        } else {
+            act.for_each(|pattern| con.subscriber().punsubscribe(pattern));
+        }
+        let count = con.subscriber().count();
+        con.write_usize(count).await?;
+        Ok(())
+    }
+}
+
+action! {
+    /// Run a `PUBLISH <channel> <message>` query
+    fn publish(_handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 2)?;
+        let (channel, message) = unsafe {
+            // UNSAFE(@ohsayan): We have checked for there to be exactly two arguments
+            (act.next_unchecked(), act.next_unchecked())
+        };
+        let delivered = crate::dbnet::pubsub::publish(channel, message);
+        con.write_usize(delivered).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,59 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `RANDOMKEY` queries
+//! This module provides a function to pick a random key out of a table, which is handy for
+//! sampling a large table (to look at the shape of its values, say) without listing every key
+
+use crate::{corestore::table::DataModel, dbnet::prelude::*};
+
+action!(
+    /// Run a `RANDOMKEY` query, which returns a random key of the current table (or
+    /// nil if the table is empty)
+    ///
+    /// ## Syntax
+    /// `RANDOMKEY`
+    fn randomkey(handle: &Corestore, con: &mut Connection<C, P>, act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 0)?;
+        let table = get_tbl!(handle, con);
+        let (tsymbol, key) = match table.get_model_ref() {
+            DataModel::KV(kv) => (kv.get_key_tsymbol(), kv.random_key()),
+            DataModel::KVExtListmap(kv) => (kv.get_key_tsymbol(), kv.random_key()),
+            DataModel::KVExtMap(kv) => (kv.get_key_tsymbol(), kv.random_key()),
+            DataModel::KVExtSet(kv) => (kv.get_key_tsymbol(), kv.random_key()),
+            DataModel::KVExtSortedSet(kv) => (kv.get_key_tsymbol(), kv.random_key()),
+            DataModel::KVExtDocument(kv) => (kv.get_key_tsymbol(), kv.random_key()),
+        };
+        match key {
+            Some(key) => {
+                con.write_mono_length_prefixed_with_tsymbol(&key, tsymbol)
+                    .await?
+            }
+            None => con._write_raw(P::RCODE_NIL).await?,
+        }
+        Ok(())
+    }
+);
@@ -0,0 +1,102 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `GETRANGE` and `SETRANGE` queries
+//! This module provides functions to read or overwrite a range of bytes of a value, without
+//! sending the whole value back and forth
+
+use crate::dbnet::prelude::*;
+
+/// The largest value that `SETRANGE` can extend a value to, so that a large offset can't make
+/// the server allocate an unbounded amount of memory
+pub(super) const MAX_RANGE_END: usize = 512 * 1024 * 1024;
+
+action! {
+    /// Run a `GETRANGE` query, which returns the bytes of a value from `<start>` to `<end>`
+    /// (both inclusive). Negative offsets count from the end of the value, so `GETRANGE <key> 0 -1`
+    /// returns the whole value
+    /// Syntax: `GETRANGE <key> <start> <end>`
+    fn getrange(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 3)?;
+        let key = unsafe { act.next_unchecked() };
+        let (start, end) = match (
+            self::parse_offset(unsafe { act.next_unchecked() }),
+            self::parse_offset(unsafe { act.next_unchecked() }),
+        ) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return util::err(P::RCODE_WRONGTYPE_ERR),
+        };
+        let kve = handle.get_table_with::<P, KVEBlob>()?;
+        let value = match kve.get_cloned(key) {
+            Ok(Some(value)) => value,
+            Ok(None) => return util::err(P::RCODE_NIL),
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        };
+        let range = &value[util::resolve_range(value.len(), start, end)];
+        if kve.get_encoding_tuple().1 && core::str::from_utf8(range).is_err() {
+            // the range splits one of the characters of a string
+            return util::err(P::RCODE_ENCODING_ERROR);
+        }
+        con.write_mono_length_prefixed_with_tsymbol(range, kve.get_value_tsymbol())
+            .await?;
+        Ok(())
+    }
+
+    /// Run a `SETRANGE` query, which overwrites the bytes of a value starting at `<offset>` and
+    /// returns the new length of the value. The value is padded with zero bytes if it's shorter
+    /// than `<offset>`, and a key that doesn't exist is created
+    /// Syntax: `SETRANGE <key> <offset> <value>`
+    fn setrange(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 3)?;
+        let key = unsafe { act.next_unchecked_bytes() };
+        let offset = match core::str::from_utf8(unsafe { act.next_unchecked() })
+            .ok()
+            .and_then(|offset| offset.parse::<usize>().ok())
+        {
+            Some(offset) => offset,
+            None => return util::err(P::RCODE_WRONGTYPE_ERR),
+        };
+        let data = unsafe { act.next_unchecked() };
+        let kve = handle.get_table_with::<P, KVEBlob>()?;
+        match offset.checked_add(data.len()) {
+            Some(end) if end <= MAX_RANGE_END && kve.size_limits().allows_value(end) => {}
+            _ => return util::err(P::RSTRING_TOO_LARGE),
+        }
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        match kve.set_range(key, offset, data) {
+            Ok(len) => con.write_usize(len).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+}
+
+/// Parse an offset, which is a 64-bit signed integer in decimal
+pub(super) fn parse_offset(offset: &[u8]) -> Option<i64> {
+    core::str::from_utf8(offset).ok()?.parse().ok()
+}
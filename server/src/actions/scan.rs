@@ -0,0 +1,93 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `SCAN` queries
+//! This module provides a function to page through the keys of a table with a cursor, without
+//! ever holding on to more than a page of keys
+
+use crate::{
+    corestore::{table::DataModel, SharedSlice},
+    dbnet::prelude::*,
+};
+
+const DEFAULT_COUNT: usize = 10;
+const MATCH: &[u8] = b"match";
+const COUNT: &[u8] = b"count";
+
+action!(
+    /// Run a `SCAN` query, which returns the cursor for the next call followed by a page of the
+    /// keys of the current table. Start with a zero cursor and stop once the returned cursor is
+    /// zero. Every key that exists throughout the scan is returned at least once
+    ///
+    /// ## Syntax
+    /// `SCAN <cursor> [MATCH <pattern>] [COUNT <count>]`
+    fn scan(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len % 2 == 1 && len <= 5)?;
+        let cursor = unsafe { act.next_unchecked() };
+        let cursor = match String::from_utf8_lossy(cursor).parse::<u64>() {
+            Ok(cursor) => cursor,
+            Err(_) => return util::err(P::RCODE_WRONGTYPE_ERR),
+        };
+        let (mut pattern, mut count) = (None, DEFAULT_COUNT);
+        while let (Some(option), Some(value)) = (act.next(), act.next()) {
+            if option.eq_ignore_ascii_case(MATCH) {
+                pattern = Some(value);
+            } else if option.eq_ignore_ascii_case(COUNT) {
+                count = match String::from_utf8_lossy(value).parse::<usize>() {
+                    Ok(count) if count != 0 => count,
+                    _ => return util::err(P::RCODE_WRONGTYPE_ERR),
+                };
+            } else {
+                return util::err(P::RSTRING_UNKNOWN_PROPERTY);
+            }
+        }
+        let table = get_tbl!(handle, con);
+        let (tsymbol, (keys, next)) = match table.get_model_ref() {
+            DataModel::KV(kv) => (kv.get_key_tsymbol(), kv.scan_keys(cursor, count)),
+            DataModel::KVExtListmap(kv) => (kv.get_key_tsymbol(), kv.scan_keys(cursor, count)),
+            DataModel::KVExtMap(kv) => (kv.get_key_tsymbol(), kv.scan_keys(cursor, count)),
+            DataModel::KVExtSet(kv) => (kv.get_key_tsymbol(), kv.scan_keys(cursor, count)),
+            DataModel::KVExtSortedSet(kv) => (kv.get_key_tsymbol(), kv.scan_keys(cursor, count)),
+            DataModel::KVExtDocument(kv) => (kv.get_key_tsymbol(), kv.scan_keys(cursor, count)),
+        };
+        let keys: Vec<SharedSlice> = match pattern {
+            Some(pattern) => keys
+                .into_iter()
+                .filter(|key| util::glob_match(pattern, key))
+                .collect(),
+            None => keys,
+        };
+        con.deadline().check::<P>()?;
+        con.write_typed_non_null_array_header(keys.len() + 1, tsymbol)
+            .await?;
+        con.write_typed_non_null_array_element(next.to_string().as_bytes())
+            .await?;
+        for key in keys {
+            con.write_typed_non_null_array_element(&key).await?;
+        }
+        Ok(())
+    }
+);
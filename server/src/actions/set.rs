@@ -25,9 +25,18 @@
 */
 
 //! # `SET` queries
-//! This module provides functions to work with `SET` queries
+//! This module provides functions to work with `SET` queries, `SETX` queries which also
+//! give the key a time to live and `GETSET` queries which also return the previous value
 
-use crate::{corestore::SharedSlice, dbnet::prelude::*, queryengine::ActionIter};
+use crate::{
+    corestore::SharedSlice, dbnet::prelude::*, kvengine::SetCondition, queryengine::ActionIter,
+};
+
+const EX: &[u8] = b"ex";
+const PX: &[u8] = b"px";
+const NX: &[u8] = b"nx";
+const XX: &[u8] = b"xx";
+const ERR_UNKNOWN_PROPERTY: &[u8] = b"!16\nunknown-property\n";
 
 action!(
     /// Run a `SET` query
@@ -55,4 +64,78 @@ action!(
         }
         Ok(())
     }
+
+    /// Run a `SETX` query, which writes a value and gives the key a time to live atomically
+    /// (the value is never seen without its time to live). By default the value is always
+    /// written (like `USET`); with `NX` it's only written if the key doesn't exist (like `SET`)
+    /// and with `XX` only if it does (like `UPDATE`)
+    ///
+    /// ## Syntax
+    /// `SETX <key> <value> EX <seconds>|PX <milliseconds> [NX|XX]`
+    fn setx(handle: &crate::corestore::Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 4 || len == 5)?;
+        let key = unsafe { act.next_unchecked_bytes() };
+        let value = unsafe { act.next_unchecked_bytes() };
+        let unit_ms = match unsafe { act.next_lowercase_unchecked() }.as_ref() {
+            EX => 1000,
+            PX => 1,
+            _ => return util::err(ERR_UNKNOWN_PROPERTY),
+        };
+        let ttl_ms = match core::str::from_utf8(unsafe { act.next_unchecked() })
+            .ok()
+            .and_then(|ttl| ttl.parse::<u64>().ok())
+        {
+            Some(ttl) if ttl != 0 => ttl.saturating_mul(unit_ms),
+            _ => return util::err(P::RCODE_WRONGTYPE_ERR),
+        };
+        let condition = match act.next_lowercase().as_deref() {
+            None => SetCondition::Always,
+            Some(NX) => SetCondition::Missing,
+            Some(XX) => SetCondition::Exists,
+            Some(_) => return util::err(ERR_UNKNOWN_PROPERTY),
+        };
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        let writer = handle.get_table_with::<P, KVEBlob>()?;
+        match writer.set_with_expiry(key, value, condition, ttl_ms) {
+            Ok(true) => con._write_raw(P::RCODE_OKAY).await?,
+            // just like a `SET` or an `UPDATE` that didn't write anything
+            Ok(false) if condition == SetCondition::Missing => {
+                con._write_raw(P::RCODE_OVERWRITE_ERR).await?
+            }
+            Ok(false) => con._write_raw(P::RCODE_NIL).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run a `GETSET` query, which writes a value (like `USET`) and returns the value that it
+    /// replaced in a single step. Nil is returned if the key didn't exist
+    ///
+    /// ## Syntax
+    /// `GETSET <key> <value>`
+    fn getset(handle: &crate::corestore::Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 2)?;
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        let writer = handle.get_table_with::<P, KVEBlob>()?;
+        let (key, value) = unsafe {
+            // UNSAFE(@ohsayan): We have checked for there to be exactly 2 arguments
+            (
+                SharedSlice::new(act.next_unchecked()),
+                SharedSlice::new(act.next_unchecked()),
+            )
+        };
+        match writer.swap(key, value) {
+            Ok(Some(old)) => {
+                con.write_mono_length_prefixed_with_tsymbol(&old, writer.get_value_tsymbol())
+                    .await?
+            }
+            Ok(None) => con._write_raw(P::RCODE_NIL).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
 );
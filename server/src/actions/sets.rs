@@ -0,0 +1,135 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `SADD`, `SREM`, `SISMEMBER`, `SMEMBERS`, `SUNION` and `SINTER` queries
+//! This module provides functions to work with the members of the sets in a `KVExt/Set` table
+//! (that is, a table declared with a `set<...>` value type)
+
+use crate::{corestore::SharedSlice, dbnet::prelude::*};
+
+macro_rules! writeset {
+    ($con:expr, $setmap:expr, $members:expr) => {{
+        $con.write_typed_non_null_array_header($members.len(), $setmap.get_value_tsymbol())
+            .await?;
+        for member in $members {
+            $con.write_typed_non_null_array_element(&member).await?;
+        }
+    }};
+}
+
+action! {
+    /// Run an `SADD` query, which returns the number of members that were added
+    /// Syntax: `SADD <set> <member1> <member2> ...`
+    fn sadd(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len > 1)?;
+        let setmap = handle.get_table_with::<P, KVESetT>()?;
+        let key = unsafe { act.next_unchecked_bytes() };
+        let members = act.map(SharedSlice::new).collect();
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        match setmap.set_add(key, members) {
+            Ok(added) => con.write_usize(added).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run an `SREM` query, which returns the number of members that were removed. A set is
+    /// removed along with its last member
+    /// Syntax: `SREM <set> <member1> <member2> ...`
+    fn srem(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len > 1)?;
+        let setmap = handle.get_table_with::<P, KVESetT>()?;
+        let key = unsafe { act.next_unchecked() };
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        match setmap.set_remove(key, act) {
+            Ok(Some(removed)) => con.write_usize(removed).await?,
+            Ok(None) => con._write_raw(P::RCODE_NIL).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run an `SISMEMBER` query, which returns 1 if the member is in the set and 0 if it isn't
+    /// (or if the set doesn't exist)
+    /// Syntax: `SISMEMBER <set> <member>`
+    fn sismember(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 2)?;
+        let setmap = handle.get_table_with::<P, KVESetT>()?;
+        let key = unsafe { act.next_unchecked() };
+        let member = unsafe { act.next_unchecked() };
+        match setmap.set_contains(key, member) {
+            Ok(contains) => con.write_usize(contains as usize).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run an `SMEMBERS` query
+    /// Syntax: `SMEMBERS <set>`
+    fn smembers(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 1)?;
+        let setmap = handle.get_table_with::<P, KVESetT>()?;
+        let key = unsafe { act.next_unchecked() };
+        match setmap.set_members(key) {
+            Ok(Some(members)) => writeset!(con, setmap, members),
+            Ok(None) => con._write_raw(P::RCODE_NIL).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+}
+
+action! {
+    /// Run an `SUNION` query, which returns the members that are in any of the sets. Sets that
+    /// don't exist are treated as empty sets
+    /// Syntax: `SUNION <set1> <set2> ...`
+    fn sunion(handle: &Corestore, con: &mut Connection<C, P>, act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len > 0)?;
+        let setmap = handle.get_table_with::<P, KVESetT>()?;
+        match setmap.set_union(act) {
+            Ok(members) => writeset!(con, setmap, members),
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run an `SINTER` query, which returns the members that are in all of the sets. Sets that
+    /// don't exist are treated as empty sets
+    /// Syntax: `SINTER <set1> <set2> ...`
+    fn sinter(handle: &Corestore, con: &mut Connection<C, P>, act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len > 0)?;
+        let setmap = handle.get_table_with::<P, KVESetT>()?;
+        match setmap.set_intersection(act) {
+            Ok(members) => writeset!(con, setmap, members),
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+}
@@ -28,7 +28,7 @@ use {
     crate::{
         actions::strong::StrongActionResult,
         dbnet::prelude::*,
-        kvengine::{KVEStandard, SingleEncoder},
+        kvengine::{eviction, KVEStandard, SingleEncoder},
         protocol::iter::DerefUnsafeSlice,
         util::compiler,
     },
@@ -127,8 +127,20 @@ pub(super) fn snapshot_and_del<'a, T: 'a + DerefUnsafeSlice>(
                 // value after we snapshotted it. In that case, let this key
                 // be whatever the "newer" value is. Since our snapshot is a "happens-before"
                 // thing, this is absolutely fine
-                let _ = lowtable.remove_if(key, |_, val| val.eq(&snapshot));
+                let removed = lowtable.remove_if(key, |key, val| {
+                    let matches = val.eq(&snapshot);
+                    if matches {
+                        kve.expiry().remove(key);
+                        kve.changed(key, Some(val), None);
+                    }
+                    matches
+                });
+                if let Some((key, val)) = removed {
+                    kve.memory()
+                        .removed(&key, eviction::entry_size(&key, val.len()));
+                }
             });
+            kve.mark_dirty();
             StrongActionResult::Okay
         } else {
             StrongActionResult::Nil
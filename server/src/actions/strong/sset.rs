@@ -29,7 +29,7 @@ use {
         actions::strong::StrongActionResult,
         corestore::SharedSlice,
         dbnet::prelude::*,
-        kvengine::{DoubleEncoder, KVEStandard},
+        kvengine::{eviction, DoubleEncoder, KVEStandard},
         protocol::iter::DerefUnsafeSlice,
         util::compiler,
     },
@@ -87,7 +87,7 @@ pub(super) fn snapshot_and_insert<'a, T: 'a + DerefUnsafeSlice>(
             let key = ucidx!(kv, 0).deref_slice();
             let value = ucidx!(kv, 1).deref_slice();
             if compiler::likely(encoder(key, value)) {
-                lowtable.get(key).is_none()
+                !kve.exists_unchecked(key)
             } else {
                 enc_err = true;
                 false
@@ -108,15 +108,20 @@ pub(super) fn snapshot_and_insert<'a, T: 'a + DerefUnsafeSlice>(
             // fine, the keys were non-existent when we looked at them
             while let (Some(key), Some(value)) = (act.next(), act.next()) {
                 unsafe {
-                    if let Some(fresh) =
-                        lowtable.fresh_entry(SharedSlice::new(key.deref_slice()))
-                    {
-                        fresh.insert(SharedSlice::new(value.deref_slice()));
+                    let key = SharedSlice::new(key.deref_slice());
+                    if let Some(fresh) = lowtable.fresh_entry(key.clone()) {
+                        let value = SharedSlice::new(value.deref_slice());
+                        let size = eviction::entry_size(&key, value.len());
+                        kve.expiry().remove(&key);
+                        kve.changed(&key, None, Some(&value));
+                        fresh.insert(value);
+                        kve.memory().inserted(&key, size);
                     }
                     // we don't care if some other thread initialized the value we checked
                     // it. We expected a fresh entry, so that's what we'll check and use
                 }
             }
+            kve.mark_dirty();
             StrongActionResult::Okay
         } else {
             StrongActionResult::OverwriteError
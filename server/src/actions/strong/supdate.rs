@@ -123,17 +123,23 @@ pub(super) fn snapshot_and_update<'a, T: 'a + DerefUnsafeSlice>(
                 unsafe {
                     // When we snapshotted, we looked at `snapshot`. If the value is still the
                     // same, then we'll update it. Otherwise, let it be
-                    if let Some(mut mutable) =
-                        lowtable.mut_entry(SharedSlice::new(key.deref_slice()))
-                    {
+                    let key = SharedSlice::new(key.deref_slice());
+                    if let Some(mut mutable) = lowtable.mut_entry(key.clone()) {
                         if mutable.value().eq(&snapshot) {
-                            mutable.insert(SharedSlice::new(value.deref_slice()));
+                            let value = SharedSlice::new(value.deref_slice());
+                            let size = value.len();
+                            kve.expiry().remove(&key);
+                            kve.changed(&key, Some(&snapshot), Some(&value));
+                            let old = mutable.insert(value);
+                            drop(mutable);
+                            kve.memory().resize(old.len(), size);
                         } else {
                             drop(mutable);
                         }
                     }
                 }
             }
+            kve.mark_dirty();
             StrongActionResult::Okay
         } else {
             StrongActionResult::Nil
@@ -0,0 +1,150 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `MULTI`, `EXEC` and `DISCARD` queries
+//! This module provides functions to run transactions on a key/value table. After `MULTI`, the
+//! writes that a connection sends (`SET`, `UPDATE`, `USET` and `DEL`) are queued instead of being
+//! run, until `EXEC` commits them all at once or `DISCARD` drops them. A transaction either makes
+//! all of its writes or none of them, and readers never see some of its writes without the
+//! others (see [`kvengine::txn`](crate::kvengine::txn))
+
+use crate::{
+    actions::ActionResult,
+    corestore::SharedSlice,
+    dbnet::{prelude::*, BufferedSocketStream},
+    kvengine::txn::TxnError,
+    protocol::{iter::AnyArrayIter, UnsafeSlice},
+};
+
+const ERR_NESTED_MULTI: &[u8] = b"!16\nerr-nested-multi\n";
+const ERR_NO_MULTI: &[u8] = b"!12\nerr-no-multi\n";
+const ERR_NOT_QUEUEABLE: &[u8] = b"!17\nerr-not-queueable\n";
+/// The response to a write that was queued
+const QUEUED: &str = "QUEUED";
+
+action! {
+    /// Run a `MULTI` query, which starts a transaction on the current table
+    /// Syntax: `MULTI`
+    fn multi(handle: &mut Corestore, con: &mut Connection<C, P>, act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 0)?;
+        handle.get_table_with::<P, KVEBlob>()?;
+        if !handle.begin_txn() {
+            return util::err(ERR_NESTED_MULTI);
+        }
+        con._write_raw(P::RCODE_OKAY).await?;
+        Ok(())
+    }
+
+    /// Run an `EXEC` query, which commits the writes queued since `MULTI`
+    /// Syntax: `EXEC`
+    fn exec(handle: &mut Corestore, con: &mut Connection<C, P>, act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 0)?;
+        let txn = match handle.take_txn() {
+            Some(txn) => txn,
+            None => return util::err(ERR_NO_MULTI),
+        };
+        let kve = handle.get_table_with::<P, KVEBlob>()?;
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        self::translate_txn_result::<P>(kve.commit(txn))?;
+        con._write_raw(P::RCODE_OKAY).await?;
+        Ok(())
+    }
+
+    /// Run a `DISCARD` query, which drops the writes queued since `MULTI`
+    /// Syntax: `DISCARD`
+    fn discard(handle: &mut Corestore, con: &mut Connection<C, P>, act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 0)?;
+        if handle.take_txn().is_none() {
+            return util::err(ERR_NO_MULTI);
+        }
+        con._write_raw(P::RCODE_OKAY).await?;
+        Ok(())
+    }
+}
+
+/// Map the outcome of a commit to the response code that the client gets for it
+pub fn translate_txn_result<P: ProtocolSpec>(
+    r: Result<Result<(), TxnError>, ()>,
+) -> ActionResult<()> {
+    match r {
+        Ok(Ok(())) => Ok(()),
+        Ok(Err(TxnError::Exists)) => util::err(P::RCODE_OVERWRITE_ERR),
+        Ok(Err(TxnError::Missing)) => util::err(P::RCODE_NIL),
+        Err(()) => util::err(P::RCODE_ENCODING_ERROR),
+    }
+}
+
+/// Queue a write in the transaction that the connection has started. Anything other than
+/// `SET`, `UPDATE`, `USET` and `DEL` is refused
+pub async fn queue<C: BufferedSocketStream, P: ProtocolSpec>(
+    handle: &mut Corestore,
+    con: &mut Connection<C, P>,
+    buf: &[UnsafeSlice],
+) -> ActionResult<()> {
+    let mut act = unsafe {
+        // UNSAFE(@ohsayan): The presence of the connection guarantees that this
+        // won't suddenly become invalid
+        AnyArrayIter::new(buf.iter())
+    };
+    let action = act
+        .next_uppercase()
+        .unwrap_or_custom_aerr(P::RCODE_PACKET_ERR)?;
+    let txn = match handle.txn_mut() {
+        Some(txn) => txn,
+        None => return util::err(ERR_NO_MULTI),
+    };
+    match action.as_ref() {
+        b"SET" | b"UPDATE" => {
+            ensure_length::<P>(act.len(), |len| len == 2)?;
+            let (key, value) = unsafe {
+                // UNSAFE(@ohsayan): we've already checked that there are two arguments
+                (act.next_unchecked_bytes(), act.next_unchecked_bytes())
+            };
+            if action.as_ref() == b"SET" {
+                txn.set(key, value);
+            } else {
+                txn.update(key, value);
+            }
+        }
+        b"USET" => {
+            ensure_length::<P>(act.len(), |len| len != 0 && len % 2 == 0)?;
+            while let (Some(key), Some(value)) = (act.next(), act.next()) {
+                txn.upsert(SharedSlice::new(key), SharedSlice::new(value));
+            }
+        }
+        b"DEL" => {
+            ensure_length::<P>(act.len(), |len| len != 0)?;
+            for key in act {
+                txn.delete(SharedSlice::new(key));
+            }
+        }
+        _ => return util::err(ERR_NOT_QUEUEABLE),
+    }
+    con.write_string(QUEUED).await?;
+    Ok(())
+}
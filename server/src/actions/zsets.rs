@@ -0,0 +1,172 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `ZADD`, `ZREM`, `ZSCORE`, `ZRANK`, `ZCARD`, `ZRANGE` and `ZRANGEBYSCORE` queries
+//! This module provides functions to work with the members of the sorted sets in a
+//! `KVExt/SortedSet` table (that is, a table declared with a `zset<...>` value type). Members
+//! are ordered by their scores, from the lowest to the highest
+
+use crate::{corestore::SharedSlice, dbnet::prelude::*, kvengine::sortedset::parse_score};
+
+macro_rules! writezset {
+    ($con:expr, $zsetmap:expr, $members:expr) => {{
+        $con.write_typed_non_null_array_header($members.len(), $zsetmap.get_value_tsymbol())
+            .await?;
+        for member in $members {
+            $con.write_typed_non_null_array_element(&member).await?;
+        }
+    }};
+}
+
+action! {
+    /// Run a `ZADD` query, which returns the number of members that were added (the scores of
+    /// members that are already present are updated)
+    /// Syntax: `ZADD <zset> <score> <member> [<score> <member> ...]`
+    fn zadd(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len > 2 && len % 2 == 1)?;
+        let zsetmap = handle.get_table_with::<P, KVESortedSetT>()?;
+        let key = unsafe { act.next_unchecked_bytes() };
+        let mut members = Vec::with_capacity(act.len() / 2);
+        while let (Some(score), Some(member)) = (act.next(), act.next()) {
+            match parse_score(score) {
+                Some(score) => members.push((SharedSlice::new(member), score)),
+                None => return util::err(P::RCODE_WRONGTYPE_ERR),
+            }
+        }
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        match zsetmap.zset_add(key, members) {
+            Ok(added) => con.write_usize(added).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run a `ZREM` query, which returns the number of members that were removed. A sorted set
+    /// is removed along with its last member
+    /// Syntax: `ZREM <zset> <member> [<member> ...]`
+    fn zrem(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len > 1)?;
+        let zsetmap = handle.get_table_with::<P, KVESortedSetT>()?;
+        let key = unsafe { act.next_unchecked() };
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        match zsetmap.zset_remove(key, act) {
+            Ok(Some(removed)) => con.write_usize(removed).await?,
+            Ok(None) => con._write_raw(P::RCODE_NIL).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run a `ZSCORE` query
+    /// Syntax: `ZSCORE <zset> <member>`
+    fn zscore(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 2)?;
+        let zsetmap = handle.get_table_with::<P, KVESortedSetT>()?;
+        let key = unsafe { act.next_unchecked() };
+        let member = unsafe { act.next_unchecked() };
+        match zsetmap.zset_score(key, member) {
+            Ok(Some(score)) => con.write_double(score).await?,
+            Ok(None) => con._write_raw(P::RCODE_NIL).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run a `ZRANK` query, which returns the (zero-based) rank of a member, counting from the
+    /// lowest score
+    /// Syntax: `ZRANK <zset> <member>`
+    fn zrank(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 2)?;
+        let zsetmap = handle.get_table_with::<P, KVESortedSetT>()?;
+        let key = unsafe { act.next_unchecked() };
+        let member = unsafe { act.next_unchecked() };
+        match zsetmap.zset_rank(key, member) {
+            Ok(Some(rank)) => con.write_usize(rank).await?,
+            Ok(None) => con._write_raw(P::RCODE_NIL).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run a `ZCARD` query, which returns the number of members in a sorted set (zero if it
+    /// doesn't exist)
+    /// Syntax: `ZCARD <zset>`
+    fn zcard(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 1)?;
+        let zsetmap = handle.get_table_with::<P, KVESortedSetT>()?;
+        let key = unsafe { act.next_unchecked() };
+        match zsetmap.zset_len(key) {
+            Ok(len) => con.write_usize(len).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run a `ZRANGE` query, which returns the members with a rank between `start` and `stop`
+    /// (both inclusive). Negative ranks count from the highest score, so `ZRANGE <zset> 0 -1`
+    /// returns all the members
+    /// Syntax: `ZRANGE <zset> <start> <stop>`
+    fn zrange(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 3)?;
+        let zsetmap = handle.get_table_with::<P, KVESortedSetT>()?;
+        let key = unsafe { act.next_unchecked() };
+        let mut rank = || String::from_utf8_lossy(unsafe { act.next_unchecked() }).parse::<i64>();
+        let (start, stop) = match (rank(), rank()) {
+            (Ok(start), Ok(stop)) => (start, stop),
+            _ => return util::err(P::RCODE_WRONGTYPE_ERR),
+        };
+        match zsetmap.zset_range(key, start, stop) {
+            Ok(Some(members)) => writezset!(con, zsetmap, members),
+            Ok(None) => con._write_raw(P::RCODE_NIL).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+
+    /// Run a `ZRANGEBYSCORE` query, which returns the members with a score between `min` and
+    /// `max` (both inclusive). `-inf` and `inf` can be used for open ranges
+    /// Syntax: `ZRANGEBYSCORE <zset> <min> <max>`
+    fn zrangebyscore(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 3)?;
+        let zsetmap = handle.get_table_with::<P, KVESortedSetT>()?;
+        let key = unsafe { act.next_unchecked() };
+        let mut score = || parse_score(unsafe { act.next_unchecked() });
+        let (min, max) = match (score(), score()) {
+            (Some(min), Some(max)) => (min, max),
+            _ => return util::err(P::RCODE_WRONGTYPE_ERR),
+        };
+        match zsetmap.zset_range_by_score(key, min, max) {
+            Ok(Some(members)) => writezset!(con, zsetmap, members),
+            Ok(None) => con._write_raw(P::RCODE_NIL).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+}
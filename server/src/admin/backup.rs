@@ -0,0 +1,68 @@
+/*
+ * Created on Tue Oct 25 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+use crate::{blueql, corestore::memstore::ObjectID, dbnet::prelude::*};
+
+const ERR_ILLEGAL_NAME: &[u8] = b"!19\nillegal-backup-name\n";
+
+action!(
+    /// Create a backup of the given keyspaces
+    ///
+    /// ## Syntax
+    /// `BACKUP <name> <keyspace1> <keyspace2> ...`
+    fn backup(
+        handle: &crate::corestore::Corestore,
+        con: &mut Connection<C, P>,
+        mut act: ActionIter<'a>,
+    ) {
+        ensure_length::<P>(act.len(), |len| len > 1)?;
+        let name = unsafe {
+            // SAFETY: We have already checked that there are at least two items
+            act.next_unchecked()
+        };
+        // the name is used as a file name, so only allow what an identifier would
+        let name_okay = !name.is_empty()
+            && name.len() <= 64
+            && name
+                .iter()
+                .all(|byte| byte.is_ascii_alphanumeric() || *byte == b'_');
+        if !name_okay {
+            return util::err(ERR_ILLEGAL_NAME);
+        }
+        let name = unsafe { String::from_utf8_unchecked(name.to_owned()) };
+        let mut keyspaces = Vec::with_capacity(act.len());
+        for ksid in act {
+            blueql::util::validate_entity_name::<P>(ksid)?;
+            keyspaces.push(unsafe { ObjectID::from_slice(ksid) });
+        }
+        if !registry::state_okay() {
+            return util::err(P::RCODE_SERVER_ERR);
+        }
+        translate_ddl_error::<P, ()>(handle.backup(name, keyspaces).await)?;
+        con._write_raw(P::RCODE_OKAY).await?;
+        Ok(())
+    }
+);
@@ -0,0 +1,126 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `CLIENT` queries
+//! This module provides the actions that list the connected clients and forcibly disconnect
+//! them (see [`dbnet::clients`](crate::dbnet::clients))
+
+use crate::{
+    actions::ActionResult, auth::AuthProvider, dbnet::clients, dbnet::prelude::*, util::os,
+};
+
+const LIST: &[u8] = b"list";
+const KILL: &[u8] = b"kill";
+const ID: &[u8] = b"id";
+const ERR_UNKNOWN_PROPERTY: &[u8] = b"!16\nunknown-property\n";
+
+action!(
+    /// Run a `CLIENT` query:
+    /// - `CLIENT LIST` returns a line for every connected client (see
+    ///   [`ClientInfo::render`](crate::dbnet::clients::ClientInfo::render))
+    /// - `CLIENT KILL <id>` disconnects a client once its running query (if any) completes, and
+    ///   returns nil if there's no such client
+    /// - `CLIENT ID` returns the ID of the current connection
+    ///
+    /// With authn enabled, only root can list or disconnect clients
+    fn client(
+        _handle: &Corestore,
+        con: &mut Connection<C, P>,
+        auth: &mut AuthProviderHandle,
+        act: ActionIter<'a>,
+    ) {
+        let mut act = act;
+        ensure_length::<P>(act.len(), |len| len == 1 || len == 2)?;
+        let subcommand = unsafe {
+            // UNSAFE(@ohsayan): we've already checked that there's at least one argument
+            act.next_lowercase_unchecked()
+        };
+        match (subcommand.as_ref(), act.next()) {
+            (LIST, None) => {
+                self::ensure_admin::<P>(auth.provider())?;
+                let now = os::get_epoch_secs();
+                let lines: Vec<String> = clients::list().iter().map(|c| c.render(now)).collect();
+                con.write_typed_non_null_array(lines, b'+').await?;
+            }
+            (KILL, Some(id)) => {
+                self::ensure_admin::<P>(auth.provider())?;
+                let id = match String::from_utf8_lossy(id).parse::<u64>() {
+                    Ok(id) => id,
+                    Err(_) => return util::err(P::RCODE_WRONGTYPE_ERR),
+                };
+                if clients::kill(id) {
+                    con._write_raw(P::RCODE_OKAY).await?;
+                } else {
+                    con._write_raw(P::RCODE_NIL).await?;
+                }
+            }
+            (ID, None) => match con.client() {
+                Some(client) => con.write_int64(client.id()).await?,
+                None => con._write_raw(P::RCODE_NIL).await?,
+            },
+            (LIST | ID, Some(_)) | (KILL, None) => return util::err(P::RCODE_ACTION_ERR),
+            _ => return util::err(ERR_UNKNOWN_PROPERTY),
+        }
+        Ok(())
+    }
+);
+
+/// Ensure that the current user can see and disconnect other clients. If authn is disabled,
+/// anyone can
+fn ensure_admin<P: ProtocolSpec>(provider: &AuthProvider) -> ActionResult<()> {
+    if provider.is_enabled() {
+        provider.ensure_root::<P>()
+    } else {
+        Ok(())
+    }
+}
+
+#[test]
+fn test_ensure_admin() {
+    use crate::{actions::ActionError, protocol::Skyhash2};
+    const ORIG: &[u8; 40] = b"c4299d190fb9a00626797fcc138c56eae9971664";
+    assert!(ensure_admin::<Skyhash2>(&AuthProvider::new_disabled()).is_ok());
+    let mut provider = AuthProvider::new_blank(Some(*ORIG));
+    let rootkey = provider.claim_root::<Skyhash2>(ORIG).unwrap();
+    assert!(ensure_admin::<Skyhash2>(&provider).is_ok());
+    let userkey = provider.claim_user::<Skyhash2>(b"user").unwrap();
+    provider
+        .login::<Skyhash2>(b"user", userkey.as_bytes())
+        .unwrap();
+    assert_eq!(
+        ensure_admin::<Skyhash2>(&provider).unwrap_err(),
+        ActionError::ActionError(Skyhash2::AUTH_CODE_PERMS)
+    );
+    provider.logout::<Skyhash2>().unwrap();
+    assert_eq!(
+        ensure_admin::<Skyhash2>(&provider).unwrap_err(),
+        ActionError::ActionError(Skyhash2::AUTH_CODE_PERMS)
+    );
+    provider
+        .login::<Skyhash2>(b"root", rootkey.as_bytes())
+        .unwrap();
+    assert!(ensure_admin::<Skyhash2>(&provider).is_ok());
+}
@@ -0,0 +1,97 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # `DEBUG` queries
+//! This module provides actions that look at the internals of the database, to help with
+//! diagnosing problems like keys that take far more memory than expected
+
+use crate::{corestore::table::DataModel, dbnet::prelude::*, kvengine::EntryInfo};
+
+const OBJECT: &[u8] = b"object";
+
+action!(
+    /// Run a `DEBUG` query:
+    /// - `DEBUG OBJECT <key>` returns a line describing the entry of a key in the current table
+    ///   (or nil if there's no such key), like
+    ///   `shard=3/16 footprint=152 encoding=int ttl=-`, where `footprint` is the approximate
+    ///   memory taken by the entry in bytes and `ttl` is the time it has left to live in
+    ///   milliseconds (`-` if it has no deadline)
+    fn debug(handle: &Corestore, con: &mut Connection<C, P>, mut act: ActionIter<'a>) {
+        ensure_length::<P>(act.len(), |len| len == 2)?;
+        let (subcommand, key) = unsafe {
+            // UNSAFE(@ohsayan): we've already checked that there are two arguments
+            (act.next_lowercase_unchecked(), act.next_unchecked())
+        };
+        if subcommand.as_ref() != OBJECT {
+            return util::err(P::RSTRING_UNKNOWN_PROPERTY);
+        }
+        let table = get_tbl!(handle, con);
+        let info = match table.get_model_ref() {
+            DataModel::KV(kv) => kv.inspect_entry(key),
+            DataModel::KVExtListmap(kv) => kv.inspect_entry(key),
+            DataModel::KVExtMap(kv) => kv.inspect_entry(key),
+            DataModel::KVExtSet(kv) => kv.inspect_entry(key),
+            DataModel::KVExtSortedSet(kv) => kv.inspect_entry(key),
+            DataModel::KVExtDocument(kv) => kv.inspect_entry(key),
+        };
+        match info {
+            Ok(Some(info)) => con.write_string(&self::render(&info)).await?,
+            Ok(None) => con._write_raw(P::RCODE_NIL).await?,
+            Err(()) => return util::err(P::RCODE_ENCODING_ERROR),
+        }
+        Ok(())
+    }
+);
+
+/// Render the diagnostics of an entry as a line of `name=value` pairs
+fn render(info: &EntryInfo) -> String {
+    format!(
+        "shard={}/{} footprint={} encoding={} ttl={}",
+        info.shard,
+        info.shards,
+        info.footprint,
+        info.encoding,
+        info.ttl_ms
+            .map_or_else(|| "-".to_owned(), |ttl| ttl.to_string()),
+    )
+}
+
+#[test]
+fn test_render() {
+    let mut info = EntryInfo {
+        shard: 3,
+        shards: 16,
+        footprint: 152,
+        encoding: "int",
+        ttl_ms: None,
+    };
+    assert_eq!(render(&info), "shard=3/16 footprint=152 encoding=int ttl=-");
+    info.ttl_ms = Some(1500);
+    assert_eq!(
+        render(&info),
+        "shard=3/16 footprint=152 encoding=int ttl=1500"
+    );
+}
@@ -0,0 +1,77 @@
+/*
+ * Created on Sat Nov 05 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+use crate::{
+    blueql,
+    corestore::{
+        export::{ExportFormat, ExportTarget},
+        memstore::ObjectID,
+    },
+    dbnet::prelude::*,
+};
+
+const KEYSPACE: &[u8] = b"keyspace";
+const ERR_UNKNOWN_FORMAT: &[u8] = b"!14\nunknown-format\n";
+
+action!(
+    /// Export a table or a keyspace to newline-delimited JSON or CSV and return the path to the
+    /// export
+    ///
+    /// ## Syntax
+    /// - `EXPORT JSON|CSV <entity>` exports a table
+    /// - `EXPORT JSON|CSV KEYSPACE <keyspace>` exports every table in a keyspace
+    fn export(
+        handle: &crate::corestore::Corestore,
+        con: &mut Connection<C, P>,
+        mut act: ActionIter<'a>,
+    ) {
+        ensure_length::<P>(act.len(), |len| len == 2 || len == 3)?;
+        let format = unsafe {
+            // SAFETY: We have already checked that there are at least two items
+            act.next_unchecked()
+        };
+        let format = match ExportFormat::from_bytes(format) {
+            Some(format) => format,
+            None => return util::err(ERR_UNKNOWN_FORMAT),
+        };
+        let target = if act.len() == 2 {
+            if !unsafe { act.next_unchecked() }.eq_ignore_ascii_case(KEYSPACE) {
+                return util::err(P::RSTRING_UNKNOWN_PROPERTY);
+            }
+            let ksid = unsafe { act.next_unchecked() };
+            blueql::util::validate_entity_name::<P>(ksid)?;
+            ExportTarget::Keyspace(unsafe { ObjectID::from_slice(ksid) })
+        } else {
+            let raw_entity = unsafe { act.next_unchecked() };
+            let entity = handle_entity!(con, raw_entity);
+            let (ksid, tblid) = translate_ddl_error::<P, _>(handle.get_entity_ids(&entity))?;
+            ExportTarget::Table(ksid, tblid)
+        };
+        let path = translate_ddl_error::<P, String>(handle.export(target, format).await)?;
+        con.write_string(&path).await?;
+        Ok(())
+    }
+);
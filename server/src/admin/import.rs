@@ -0,0 +1,96 @@
+/*
+ * Created on Sun Nov 06 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+use {
+    super::restore::is_legal_snapshot_name,
+    crate::{
+        corestore::{export::ExportFormat, import::ImportReport},
+        dbnet::prelude::*,
+        kvengine::encoding,
+        storage::v1::interface::DIR_IMPORTS,
+    },
+    core::str,
+    std::path::Path,
+};
+
+const CONTINUE: &[u8] = b"continue";
+const ERR_UNKNOWN_FORMAT: &[u8] = b"!14\nunknown-format\n";
+const ERR_FILE_NOT_FOUND: &[u8] = b"!14\nfile-not-found\n";
+
+action!(
+    /// Import a newline-delimited JSON or CSV file from the `imports` folder in the data
+    /// directory into a table, and return a report of the imported and bad records
+    ///
+    /// ## Syntax
+    /// - `IMPORT JSON|CSV <file> <entity>` stops at the first bad record
+    /// - `IMPORT JSON|CSV <file> <entity> CONTINUE` skips bad records
+    fn import(
+        handle: &crate::corestore::Corestore,
+        con: &mut Connection<C, P>,
+        mut act: ActionIter<'a>,
+    ) {
+        ensure_length::<P>(act.len(), |len| len == 3 || len == 4)?;
+        let format = unsafe {
+            // SAFETY: We have already checked that there are at least three items
+            act.next_unchecked()
+        };
+        let format = match ExportFormat::from_bytes(format) {
+            Some(format) => format,
+            None => return util::err(ERR_UNKNOWN_FORMAT),
+        };
+        let file = unsafe { act.next_unchecked() };
+        if !encoding::is_utf8(file) {
+            return util::err(P::RCODE_ENCODING_ERROR);
+        }
+        let file = unsafe {
+            // SAFETY: We have already checked for UTF-8 validity
+            str::from_utf8_unchecked(file)
+        };
+        // SECURITY: only files in the imports folder can be read
+        if !is_legal_snapshot_name(file) {
+            return util::err(P::RSTRING_SNAPSHOT_ILLEGAL_NAME);
+        }
+        let path = Path::new(DIR_IMPORTS).join(file);
+        if !path.is_file() {
+            return util::err(ERR_FILE_NOT_FOUND);
+        }
+        let raw_entity = unsafe { act.next_unchecked() };
+        let entity = handle_entity!(con, raw_entity);
+        let continue_on_error = match act.next() {
+            Some(flag) if flag.eq_ignore_ascii_case(CONTINUE) => true,
+            Some(_) => return util::err(P::RSTRING_UNKNOWN_PROPERTY),
+            None => false,
+        };
+        let path = path.to_string_lossy().into_owned();
+        let report = handle
+            .import(&entity, path, format, continue_on_error)
+            .await;
+        let report = translate_ddl_error::<P, ImportReport>(report)?;
+        con.write_typed_non_null_array(report.render(), b'+')
+            .await?;
+        Ok(())
+    }
+);
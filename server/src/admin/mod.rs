@@ -26,5 +26,11 @@
 
 //! Modules for administration of Skytable
 
+pub mod backup;
+pub mod client;
+pub mod debug;
+pub mod export;
+pub mod import;
 pub mod mksnap;
+pub mod restore;
 pub mod sys;
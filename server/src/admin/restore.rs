@@ -0,0 +1,81 @@
+/*
+ * Created on Thu Oct 27 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+use {
+    crate::{blueql, corestore::memstore::ObjectID, dbnet::prelude::*, kvengine::encoding},
+    core::str,
+    std::path::{Component, Path},
+};
+
+/// Returns true if the snapshot name is safe to use as a path
+pub(super) fn is_legal_snapshot_name(snapshot: &str) -> bool {
+    // SECURITY: Only allow plain relative paths to avoid directory traversal
+    !snapshot.is_empty()
+        && Path::new(snapshot)
+            .components()
+            .all(|component| matches!(component, Component::Normal(_)))
+}
+
+action!(
+    /// Restore a keyspace from a snapshot, optionally under a different name
+    ///
+    /// ## Syntax
+    /// `RESTORE <snapshot> <keyspace> [<target keyspace>]`
+    fn restore(
+        handle: &crate::corestore::Corestore,
+        con: &mut Connection<C, P>,
+        mut act: ActionIter<'a>,
+    ) {
+        ensure_length::<P>(act.len(), |len| len == 2 || len == 3)?;
+        let snapshot = unsafe {
+            // SAFETY: We have already checked that there are at least two items
+            act.next_unchecked()
+        };
+        if !encoding::is_utf8(snapshot) {
+            return util::err(P::RCODE_ENCODING_ERROR);
+        }
+        let snapshot = unsafe {
+            // SAFETY: We have already checked for UTF-8 validity
+            str::from_utf8_unchecked(snapshot)
+        };
+        if !is_legal_snapshot_name(snapshot) {
+            return util::err(P::RSTRING_SNAPSHOT_ILLEGAL_NAME);
+        }
+        let ksid = unsafe {
+            // SAFETY: We have already checked that there are at least two items
+            act.next_unchecked()
+        };
+        let target = act.next().unwrap_or(ksid);
+        // SECURITY: The target name ends up in paths, so hold both names to the same rules as
+        // `create space` to avoid directory traversal
+        blueql::util::validate_entity_name::<P>(ksid)?;
+        blueql::util::validate_entity_name::<P>(target)?;
+        let (ksid, target) = unsafe { (ObjectID::from_slice(ksid), ObjectID::from_slice(target)) };
+        translate_ddl_error::<P, ()>(handle.restore(snapshot.to_owned(), ksid, target).await)?;
+        con._write_raw(P::RCODE_OKAY).await?;
+        Ok(())
+    }
+);
@@ -25,32 +25,140 @@
 */
 
 use {
+    super::restore::is_legal_snapshot_name,
     crate::{
-        corestore::booltable::BoolTable, dbnet::prelude::*,
-        storage::v1::interface::DIR_ROOT,
+        blueql,
+        corestore::{
+            booltable::BoolTable,
+            compare::{Baseline, TableDiff},
+            memstore::{Memstore, ObjectID, SYSTEM},
+            table::{DataModel, Table},
+            usage,
+        },
+        dbnet::prelude::*,
+        kvengine::{encoding, events::KeyEvent, eviction},
+        storage::v1::{interface::DIR_ROOT, spacearchive::SpaceArchiveError, stats},
     },
+    core::{cmp::Reverse, str, time::Duration},
     libsky::VERSION,
 };
 
 const INFO: &[u8] = b"info";
 const METRIC: &[u8] = b"metric";
+const ANALYZE: &[u8] = b"analyze";
+const CONFIG: &[u8] = b"config";
+const THROTTLE: &[u8] = b"throttle";
+const DEDUP: &[u8] = b"dedup";
+const INDEX: &[u8] = b"index";
+const COMPARE: &[u8] = b"compare";
+const EXPORT: &[u8] = b"export";
+const IMPORT: &[u8] = b"import";
+const USAGE: &[u8] = b"usage";
+const STATS: &[u8] = b"stats";
+const READONLY: &[u8] = b"readonly";
+const MAXSIZE: &[u8] = b"maxsize";
+const MEMORY: &[u8] = b"memory";
+const NOTIFY: &[u8] = b"notify";
 const INFO_PROTOCOL: &[u8] = b"protocol";
 const INFO_PROTOVER: &[u8] = b"protover";
 const INFO_VERSION: &[u8] = b"version";
 const METRIC_HEALTH: &[u8] = b"health";
 const METRIC_STORAGE_USAGE: &[u8] = b"storage";
+const METRIC_ARCHIVED: &[u8] = b"archived";
+const METRIC_HOT: &[u8] = b"hot";
+const METRIC_MEMORY: &[u8] = b"memory";
+const METRIC_THROTTLED: &[u8] = b"throttled";
+const METRIC_DEDUPLICATED: &[u8] = b"deduplicated";
+const METRIC_ALL: &[u8] = b"all";
+const CONFIG_EFFECTIVE: &[u8] = b"effective";
+const STATS_STORAGE: &[u8] = b"storage";
+const ANALYZE_HOTSPOTS: &[u8] = b"hotspots";
+const HOTSPOTS_START: &[u8] = b"start";
+const HOTSPOTS_STOP: &[u8] = b"stop";
+const THROTTLE_OFF: &[u8] = b"off";
+const DEDUP_OFF: &[u8] = b"off";
+const READONLY_ON: &[u8] = b"on";
+const READONLY_OFF: &[u8] = b"off";
+const READONLY_NODE: &[u8] = b"node";
+const MAXSIZE_KEY: &[u8] = b"key";
+const MAXSIZE_VALUE: &[u8] = b"value";
+const MAXSIZE_OFF: &[u8] = b"off";
+const MEMORY_TABLES: &[u8] = b"tables";
+const MEMORY_TABLE: &[u8] = b"table";
+const NOTIFY_ALL: &[u8] = b"all";
+const NOTIFY_OFF: &[u8] = b"off";
+const INDEX_ON: &[u8] = b"on";
+const INDEX_OFF: &[u8] = b"off";
+const COMPARE_SNAPSHOT: &[u8] = b"snapshot";
+const SPACE: &[u8] = b"space";
+/// The number of keys reported by `SYS ANALYZE HOTSPOTS`
+const HOTSPOTS_TOP_KEYS: usize = 10;
 const ERR_UNKNOWN_PROPERTY: &[u8] = b"!16\nunknown-property\n";
 const ERR_UNKNOWN_METRIC: &[u8] = b"!14\nunknown-metric\n";
+const ERR_ARCHIVE_CORRUPTED: &[u8] = b"!17\narchive-corrupted\n";
 
 const HEALTH_TABLE: BoolTable<&str> = BoolTable::new("good", "critical");
 
 action! {
-    fn sys(_handle: &Corestore, con: &mut Connection<C, P>, iter: ActionIter<'_>) {
+    fn sys(handle: &Corestore, con: &mut Connection<C, P>, iter: ActionIter<'_>) {
         let mut iter = iter;
-        ensure_boolean_or_aerr::<P>(iter.len() == 2)?;
+        ensure_boolean_or_aerr::<P>(iter.len() >= 2)?;
         match unsafe { iter.next_lowercase_unchecked() }.as_ref() {
-            INFO => sys_info(con, &mut iter).await,
-            METRIC => sys_metric(con, &mut iter).await,
+            INFO => {
+                ensure_boolean_or_aerr::<P>(iter.len() == 1)?;
+                sys_info(con, &mut iter).await
+            }
+            METRIC => {
+                ensure_boolean_or_aerr::<P>(iter.len() == 1)?;
+                sys_metric(handle, con, &mut iter).await
+            }
+            ANALYZE => sys_analyze(handle, con, &mut iter).await,
+            CONFIG => {
+                ensure_boolean_or_aerr::<P>(iter.len() == 1)?;
+                sys_config(con, &mut iter).await
+            }
+            THROTTLE => {
+                ensure_boolean_or_aerr::<P>(iter.len() == 1)?;
+                sys_throttle(handle, con, &mut iter).await
+            }
+            DEDUP => {
+                ensure_boolean_or_aerr::<P>(iter.len() == 1)?;
+                sys_dedup(handle, con, &mut iter).await
+            }
+            INDEX => {
+                ensure_boolean_or_aerr::<P>(iter.len() == 1)?;
+                sys_index(handle, con, &mut iter).await
+            }
+            COMPARE => sys_compare(handle, con, &mut iter).await,
+            EXPORT => {
+                ensure_boolean_or_aerr::<P>(iter.len() == 3)?;
+                sys_export_space(handle, con, &mut iter).await
+            }
+            IMPORT => {
+                ensure_boolean_or_aerr::<P>(iter.len() == 2 || iter.len() == 3)?;
+                sys_import_space(handle, con, &mut iter).await
+            }
+            USAGE => {
+                ensure_boolean_or_aerr::<P>(iter.len() == 2)?;
+                sys_usage(handle, con, &mut iter).await
+            }
+            STATS => {
+                ensure_boolean_or_aerr::<P>(iter.len() == 1)?;
+                sys_stats(con, &mut iter).await
+            }
+            READONLY => {
+                ensure_boolean_or_aerr::<P>(iter.len() == 1 || iter.len() == 2)?;
+                sys_readonly(handle, con, &mut iter).await
+            }
+            MAXSIZE => {
+                ensure_boolean_or_aerr::<P>(iter.len() == 2)?;
+                sys_maxsize(handle, con, &mut iter).await
+            }
+            MEMORY => {
+                ensure_boolean_or_aerr::<P>(iter.len() == 1 || iter.len() == 2)?;
+                sys_memory(handle, con, &mut iter).await
+            }
+            NOTIFY => sys_notify(handle, con, &mut iter).await,
             _ => util::err(P::RCODE_UNKNOWN_ACTION),
         }
     }
@@ -63,7 +171,7 @@ action! {
         }
         Ok(())
     }
-    fn sys_metric(con: &mut Connection<C, P>, iter: &mut ActionIter<'_>) {
+    fn sys_metric(handle: &Corestore, con: &mut Connection<C, P>, iter: &mut ActionIter<'_>) {
         match unsafe { iter.next_lowercase_unchecked() }.as_ref() {
             METRIC_HEALTH => {
                 con.write_string(HEALTH_TABLE[registry::state_okay()]).await?
@@ -77,8 +185,437 @@ action! {
                     },
                 }
             }
+            METRIC_ARCHIVED => con.write_int64(archived_bytes(handle.get_store())).await?,
+            METRIC_HOT => con.write_int64(hot_bytes(handle.get_store())).await?,
+            METRIC_MEMORY => con.write_int64(eviction::used()).await?,
+            METRIC_THROTTLED => {
+                con.write_int64(throttled_writes(handle.get_store())).await?
+            }
+            METRIC_DEDUPLICATED => {
+                con.write_int64(deduplicated_writes(handle.get_store())).await?
+            }
+            METRIC_ALL => {
+                let storage = match util::os::dirsize(DIR_ROOT) {
+                    Ok(size) => size,
+                    Err(e) => {
+                        log::error!("Failed to get storage usage with: {e}");
+                        return util::err(P::RCODE_SERVER_ERR);
+                    }
+                };
+                let store = handle.get_store();
+                let metrics = [
+                    ("storage", storage),
+                    ("archived", archived_bytes(store)),
+                    ("hot", hot_bytes(store)),
+                    ("memory", eviction::used()),
+                    ("throttled", throttled_writes(store)),
+                    ("deduplicated", deduplicated_writes(store)),
+                ];
+                con.write_map_header(metrics.len() + 1).await?;
+                con.write_string("healthy").await?;
+                con.write_bool(registry::state_okay()).await?;
+                for (metric, value) in metrics {
+                    con.write_string(metric).await?;
+                    con.write_int64(value).await?;
+                }
+            }
             _ => return util::err(ERR_UNKNOWN_METRIC),
         }
         Ok(())
     }
+    /// Handle `SYS CONFIG EFFECTIVE`, which returns the effective configuration with the tuning
+    /// profile expanded
+    fn sys_config(con: &mut Connection<C, P>, iter: &mut ActionIter<'_>) {
+        match unsafe { iter.next_lowercase_unchecked() }.as_ref() {
+            CONFIG_EFFECTIVE => {
+                con.write_typed_non_null_array(registry::get_effective_config(), b'+').await?
+            }
+            _ => return util::err(ERR_UNKNOWN_PROPERTY),
+        }
+        Ok(())
+    }
+    /// Handle `SYS STATS STORAGE`, which returns the storage engine's statistics
+    fn sys_stats(con: &mut Connection<C, P>, iter: &mut ActionIter<'_>) {
+        match unsafe { iter.next_lowercase_unchecked() }.as_ref() {
+            STATS_STORAGE => {
+                let report = stats::stats().report_now();
+                con.write_typed_non_null_array(report.entries(), b'+').await?
+            }
+            _ => return util::err(ERR_UNKNOWN_PROPERTY),
+        }
+        Ok(())
+    }
+    /// Handle `SYS THROTTLE` on the current table
+    /// ## Syntax
+    /// - `SYS THROTTLE <writes/sec>` caps the writes per second
+    /// - `SYS THROTTLE OFF` removes the cap
+    fn sys_throttle(handle: &Corestore, con: &mut Connection<C, P>, iter: &mut ActionIter<'_>) {
+        let table = crate::get_tbl_ref!(handle, con);
+        let rate = unsafe { iter.next_lowercase_unchecked() };
+        if rate.as_ref() == THROTTLE_OFF {
+            table.write_throttle().set_rate(0);
+        } else {
+            match String::from_utf8_lossy(&rate).parse::<u64>() {
+                Ok(rate) if rate != 0 => table.write_throttle().set_rate(rate),
+                _ => return util::err(P::RCODE_WRONGTYPE_ERR),
+            }
+        }
+        con._write_raw(P::RCODE_OKAY).await?;
+        Ok(())
+    }
+    /// Handle `SYS MAXSIZE` on the current table, which rejects writes with a key or value
+    /// over the limit with `err-too-large`
+    /// ## Syntax
+    /// - `SYS MAXSIZE KEY|VALUE <size>` caps the size of keys or values (like `512` or `1m`)
+    /// - `SYS MAXSIZE KEY|VALUE OFF` falls back to the node-wide limit
+    fn sys_maxsize(handle: &Corestore, con: &mut Connection<C, P>, iter: &mut ActionIter<'_>) {
+        let table = crate::get_tbl_ref!(handle, con);
+        let which = unsafe { iter.next_lowercase_unchecked() };
+        let size = unsafe { iter.next_lowercase_unchecked() };
+        let size = if size.as_ref() == MAXSIZE_OFF {
+            0
+        } else {
+            match util::parse_size(&String::from_utf8_lossy(&size)) {
+                Some(size) if size != 0 => size,
+                _ => return util::err(P::RCODE_WRONGTYPE_ERR),
+            }
+        };
+        let limits = table.size_limits();
+        match which.as_ref() {
+            MAXSIZE_KEY => limits.set_max_key_size(size),
+            MAXSIZE_VALUE => limits.set_max_value_size(size),
+            _ => return util::err(ERR_UNKNOWN_PROPERTY),
+        }
+        con._write_raw(P::RCODE_OKAY).await?;
+        Ok(())
+    }
+    /// Handle `SYS MEMORY`, which reports the approximate memory taken by the entries of tables
+    /// (keys, values and a fixed overhead for every entry)
+    /// ## Syntax
+    /// - `SYS MEMORY TABLES` returns `<keyspace>:<table> = <bytes>` for every table, largest
+    /// first
+    /// - `SYS MEMORY TABLE <entity>` returns the memory taken by the given table
+    fn sys_memory(handle: &Corestore, con: &mut Connection<C, P>, iter: &mut ActionIter<'_>) {
+        let with_entity = iter.len() == 2;
+        match unsafe { iter.next_lowercase_unchecked() }.as_ref() {
+            MEMORY_TABLES if !with_entity => {
+                let usage = table_memory_usage(handle.get_store());
+                con.write_typed_non_null_array(usage, b'+').await?
+            }
+            MEMORY_TABLE if with_entity => {
+                let raw_entity = unsafe { iter.next_unchecked() };
+                let entity = handle_entity!(con, raw_entity);
+                con.write_int64(get_tbl!(&entity, handle, con).memory_usage())
+                    .await?
+            }
+            _ => return util::err(ERR_UNKNOWN_PROPERTY),
+        }
+        Ok(())
+    }
+    /// Handle `SYS DEDUP` on the current table
+    /// ## Syntax
+    /// - `SYS DEDUP <seconds>` suppresses writes tagged (with `ONCE <request ID>`) with a
+    /// request ID seen within the last `<seconds>` seconds
+    /// - `SYS DEDUP OFF` turns deduplication off
+    fn sys_dedup(handle: &Corestore, con: &mut Connection<C, P>, iter: &mut ActionIter<'_>) {
+        let table = crate::get_tbl_ref!(handle, con);
+        let window = unsafe { iter.next_lowercase_unchecked() };
+        if window.as_ref() == DEDUP_OFF {
+            table.dedup_window().set_window(Duration::ZERO);
+        } else {
+            match String::from_utf8_lossy(&window).parse::<u64>() {
+                Ok(window) if window != 0 => {
+                    table.dedup_window().set_window(Duration::from_secs(window))
+                }
+                _ => return util::err(P::RCODE_WRONGTYPE_ERR),
+            }
+        }
+        con._write_raw(P::RCODE_OKAY).await?;
+        Ok(())
+    }
+    /// Handle `SYS READONLY`, which makes writes fail with `err-read-only`. The flag isn't
+    /// persisted, so it's cleared by a restart
+    /// ## Syntax
+    /// - `SYS READONLY ON|OFF` rejects (or allows) writes to the current table
+    /// - `SYS READONLY NODE ON|OFF` rejects (or allows) writes to every table
+    fn sys_readonly(handle: &Corestore, con: &mut Connection<C, P>, iter: &mut ActionIter<'_>) {
+        let node = iter.len() == 2;
+        if node && unsafe { iter.next_lowercase_unchecked() }.as_ref() != READONLY_NODE {
+            return util::err(ERR_UNKNOWN_PROPERTY);
+        }
+        let read_only = match unsafe { iter.next_lowercase_unchecked() }.as_ref() {
+            READONLY_ON => true,
+            READONLY_OFF => false,
+            _ => return util::err(ERR_UNKNOWN_PROPERTY),
+        };
+        if node {
+            registry::set_read_only(read_only);
+        } else {
+            crate::get_tbl_ref!(handle, con).set_read_only(read_only);
+        }
+        con._write_raw(P::RCODE_OKAY).await?;
+        Ok(())
+    }
+    /// Handle `SYS NOTIFY`, which publishes keyspace events for the current table (see
+    /// [`events`](crate::kvengine::events)). The events aren't persisted, so they're turned off by a restart
+    /// ## Syntax
+    /// - `SYS NOTIFY <event> ...` publishes the given events (`set`, `del`, `expired` or `drop`)
+    /// and stops publishing any other event
+    /// - `SYS NOTIFY ALL` publishes every event
+    /// - `SYS NOTIFY OFF` stops publishing events
+    fn sys_notify(handle: &Corestore, con: &mut Connection<C, P>, iter: &mut ActionIter<'_>) {
+        let table = crate::get_tbl_ref!(handle, con);
+        let mut mask = 0;
+        for event in iter.map(|event| event.to_ascii_lowercase()) {
+            mask |= match event.as_slice() {
+                NOTIFY_ALL => KeyEvent::ALL,
+                NOTIFY_OFF => 0,
+                event => match KeyEvent::from_name(event) {
+                    Some(event) => event as u8,
+                    None => return util::err(ERR_UNKNOWN_PROPERTY),
+                },
+            };
+        }
+        match handle.get_ids() {
+            (Some(ksid), Some(tblid)) => {
+                table.key_events().set_mask(ksid.clone(), tblid.clone(), mask)
+            }
+            _ => return util::err(P::RSTRING_DEFAULT_UNSET),
+        }
+        con._write_raw(P::RCODE_OKAY).await?;
+        Ok(())
+    }
+    /// Handle `SYS INDEX` on the current table (which has to be a key/value table)
+    /// ## Syntax
+    /// - `SYS INDEX ON` starts maintaining the value index used by `FINDKEYS`
+    /// - `SYS INDEX OFF` drops the value index
+    fn sys_index(handle: &Corestore, con: &mut Connection<C, P>, iter: &mut ActionIter<'_>) {
+        let kve = handle.get_table_with::<P, KVEBlob>()?;
+        match unsafe { iter.next_lowercase_unchecked() }.as_ref() {
+            INDEX_ON => {
+                if let Err(e) = kve.enable_index() {
+                    log::error!("Failed to build value index with: {e}");
+                    return util::err(P::RCODE_SERVER_ERR);
+                }
+            }
+            INDEX_OFF => kve.index().disable(),
+            _ => return util::err(ERR_UNKNOWN_PROPERTY),
+        }
+        con._write_raw(P::RCODE_OKAY).await?;
+        Ok(())
+    }
+    /// Handle `SYS COMPARE`, which reports the keys of a table that were added, removed or
+    /// changed relative to a baseline
+    /// ## Syntax
+    /// - `SYS COMPARE <entity> <baseline entity>` compares against another table
+    /// - `SYS COMPARE <entity> SNAPSHOT <snapshot>` compares against the same table in a snapshot
+    fn sys_compare(handle: &Corestore, con: &mut Connection<C, P>, iter: &mut ActionIter<'_>) {
+        ensure_boolean_or_aerr::<P>(iter.len() == 2 || iter.len() == 3)?;
+        let raw_entity = unsafe { iter.next_unchecked() };
+        let entity = handle_entity!(con, raw_entity);
+        let diff = if iter.len() == 1 {
+            let raw_baseline = unsafe { iter.next_unchecked() };
+            let baseline = handle_entity!(con, raw_baseline);
+            handle.compare(&entity, Baseline::Table(&baseline)).await
+        } else {
+            if unsafe { iter.next_lowercase_unchecked() }.as_ref() != COMPARE_SNAPSHOT {
+                return util::err(ERR_UNKNOWN_PROPERTY);
+            }
+            let snapshot = unsafe { iter.next_unchecked() };
+            if !encoding::is_utf8(snapshot) {
+                return util::err(P::RCODE_ENCODING_ERROR);
+            }
+            let snapshot = unsafe {
+                // SAFETY: We have already checked for UTF-8 validity
+                str::from_utf8_unchecked(snapshot)
+            };
+            if !is_legal_snapshot_name(snapshot) {
+                return util::err(P::RSTRING_SNAPSHOT_ILLEGAL_NAME);
+            }
+            handle.compare(&entity, Baseline::Snapshot(snapshot.to_owned())).await
+        };
+        let diff = translate_ddl_error::<P, TableDiff>(diff)?;
+        con.write_typed_non_null_array(diff.render(), b'+').await?;
+        Ok(())
+    }
+    /// Handle `SYS EXPORT SPACE <keyspace> <archive>`, which exports a keyspace to a space
+    /// archive
+    fn sys_export_space(handle: &Corestore, con: &mut Connection<C, P>, iter: &mut ActionIter<'_>) {
+        if unsafe { iter.next_lowercase_unchecked() }.as_ref() != SPACE {
+            return util::err(ERR_UNKNOWN_PROPERTY);
+        }
+        let ksid = unsafe { iter.next_unchecked() };
+        blueql::util::validate_entity_name::<P>(ksid)?;
+        let ksid = unsafe { ObjectID::from_slice(ksid) };
+        let name = match archive_name(unsafe { iter.next_unchecked() }) {
+            Some(name) => name,
+            None => return util::err(P::RSTRING_SNAPSHOT_ILLEGAL_NAME),
+        };
+        let ret = handle.export_space(ksid, name).await;
+        space_archive_result(con, ret).await
+    }
+    /// Handle `SYS IMPORT SPACE <archive> [<keyspace>]`, which imports the keyspace in a space
+    /// archive, optionally under a different name
+    fn sys_import_space(handle: &Corestore, con: &mut Connection<C, P>, iter: &mut ActionIter<'_>) {
+        if unsafe { iter.next_lowercase_unchecked() }.as_ref() != SPACE {
+            return util::err(ERR_UNKNOWN_PROPERTY);
+        }
+        let name = match archive_name(unsafe { iter.next_unchecked() }) {
+            Some(name) => name,
+            None => return util::err(P::RSTRING_SNAPSHOT_ILLEGAL_NAME),
+        };
+        let target = match iter.next() {
+            Some(target) => {
+                blueql::util::validate_entity_name::<P>(target)?;
+                Some(unsafe { ObjectID::from_slice(target) })
+            }
+            None => None,
+        };
+        let ret = handle.import_space(name, target).await;
+        space_archive_result(con, ret).await
+    }
+    /// Handle `SYS USAGE <keyspace> <window>`, which reports the usage of a keyspace in the
+    /// given window (for example, `SYS USAGE default 24h`)
+    fn sys_usage(handle: &Corestore, con: &mut Connection<C, P>, iter: &mut ActionIter<'_>) {
+        let ksid = unsafe { iter.next_unchecked() };
+        blueql::util::validate_entity_name::<P>(ksid)?;
+        let ksid = unsafe { ObjectID::from_slice(ksid) };
+        let window = match usage::parse_window(unsafe { iter.next_unchecked() }) {
+            Some(window) => window,
+            None => return util::err(P::RCODE_WRONGTYPE_ERR),
+        };
+        let live = handle.get_keyspace(&ksid).map(|ks| ks.usage().peek());
+        let ledger = handle.get_store().setup_usage();
+        match usage::summarize(&ledger, &ksid, window, util::os::get_epoch_secs(), live) {
+            Some(report) => con.write_typed_non_null_array(report.render(), b'+').await?,
+            None => return util::err(P::RSTRING_CONTAINER_NOT_FOUND),
+        }
+        Ok(())
+    }
+    fn space_archive_result(con: &mut Connection<C, P>, ret: Result<(), SpaceArchiveError>) {
+        match ret {
+            Ok(()) => con._write_raw(P::RCODE_OKAY).await?,
+            Err(SpaceArchiveError::Ddl(e)) => return translate_ddl_error::<P, ()>(Err(e)),
+            Err(SpaceArchiveError::Corrupted(_)) => return util::err(ERR_ARCHIVE_CORRUPTED),
+            Err(SpaceArchiveError::Io(_)) => return util::err(P::RCODE_SERVER_ERR),
+        }
+        Ok(())
+    }
+    /// Handle `SYS ANALYZE HOTSPOTS` on the current table
+    /// ## Syntax
+    /// - `SYS ANALYZE HOTSPOTS START <seconds>` starts a new sampling window
+    /// - `SYS ANALYZE HOTSPOTS STOP` stops sampling and discards the samples
+    /// - `SYS ANALYZE HOTSPOTS` returns a heatmap summary of the current (or last) window
+    fn sys_analyze(handle: &Corestore, con: &mut Connection<C, P>, iter: &mut ActionIter<'_>) {
+        match unsafe { iter.next_lowercase_unchecked() }.as_ref() {
+            ANALYZE_HOTSPOTS => {}
+            _ => return util::err(ERR_UNKNOWN_PROPERTY),
+        }
+        let table = crate::get_tbl_ref!(handle, con);
+        match iter.next_lowercase().as_deref() {
+            None => match table.hotspots().summary(HOTSPOTS_TOP_KEYS) {
+                Some(summary) => {
+                    con.write_typed_non_null_array(summary.render(), b'+').await?
+                }
+                None => con._write_raw(P::RCODE_NIL).await?,
+            },
+            Some(HOTSPOTS_START) => {
+                ensure_boolean_or_aerr::<P>(iter.len() == 1)?;
+                let window = unsafe { String::from_utf8_lossy(iter.next_unchecked()) };
+                match window.parse::<u64>() {
+                    Ok(window) if window != 0 => {
+                        table.start_hotspot_sampling(window);
+                        con._write_raw(P::RCODE_OKAY).await?
+                    }
+                    _ => return util::err(P::RCODE_WRONGTYPE_ERR),
+                }
+            }
+            Some(HOTSPOTS_STOP) => {
+                ensure_boolean_or_aerr::<P>(iter.next().is_none())?;
+                table.hotspots().stop();
+                con._write_raw(P::RCODE_OKAY).await?
+            }
+            Some(_) => return util::err(ERR_UNKNOWN_PROPERTY),
+        }
+        Ok(())
+    }
+}
+
+/// Sum `f` over every table in the store
+fn sum_over_tables(store: &Memstore, f: impl Fn(&Table) -> u64) -> u64 {
+    let mut sum = 0;
+    for ks in store.keyspaces.iter() {
+        for tbl in ks.value().tables.iter() {
+            sum += f(tbl.value());
+        }
+    }
+    sum
+}
+
+/// Returns `<keyspace>:<table> = <bytes>` for every table (outside the system keyspace), from
+/// the one taking the most memory to the one taking the least
+fn table_memory_usage(store: &Memstore) -> Vec<String> {
+    let mut usage = Vec::new();
+    for ks in store.keyspaces.iter() {
+        if ks.key() == &SYSTEM {
+            continue;
+        }
+        for tbl in ks.value().tables.iter() {
+            usage.push((
+                ks.key().clone(),
+                tbl.key().clone(),
+                tbl.value().memory_usage(),
+            ));
+        }
+    }
+    usage.sort_unstable_by_key(|(_, _, bytes)| Reverse(*bytes));
+    usage
+        .into_iter()
+        .map(|(ksid, tblid, bytes)| {
+            let (ksid, tblid) = unsafe { (ksid.as_str(), tblid.as_str()) };
+            format!("{ksid}:{tblid} = {bytes}")
+        })
+        .collect()
+}
+
+/// Returns the total size of the archived values across all tables
+fn archived_bytes(store: &Memstore) -> u64 {
+    sum_over_tables(store, |table| match table.get_model_ref() {
+        DataModel::KV(kve) => kve.archive().archived_bytes(),
+        DataModel::KVExtListmap(_)
+        | DataModel::KVExtMap(_)
+        | DataModel::KVExtSet(_)
+        | DataModel::KVExtSortedSet(_)
+        | DataModel::KVExtDocument(_) => 0,
+    })
+}
+
+/// Returns the total size of the data held in memory across all tables
+fn hot_bytes(store: &Memstore) -> u64 {
+    sum_over_tables(store, |table| match table.get_model_ref() {
+        DataModel::KV(kve) => kve.hot_bytes(),
+        DataModel::KVExtListmap(kvl) => kvl.hot_bytes(),
+        DataModel::KVExtMap(kvm) => kvm.hot_bytes(),
+        DataModel::KVExtSet(kvs) => kvs.hot_bytes(),
+        DataModel::KVExtSortedSet(kvz) => kvz.hot_bytes(),
+        DataModel::KVExtDocument(kvd) => kvd.hot_bytes(),
+    })
+}
+
+/// Returns the name of a space archive if it's a plain relative path
+fn archive_name(name: &[u8]) -> Option<String> {
+    str::from_utf8(name)
+        .ok()
+        .filter(|name| is_legal_snapshot_name(name))
+        .map(str::to_owned)
+}
+
+/// Returns the total number of writes that were throttled across all tables
+fn throttled_writes(store: &Memstore) -> u64 {
+    sum_over_tables(store, |table| table.write_throttle().throttled())
+}
+
+/// Returns the total number of writes that were suppressed as duplicates across all tables
+fn deduplicated_writes(store: &Memstore) -> u64 {
+    sum_over_tables(store, |table| table.dedup_window().suppressed())
 }
@@ -27,12 +27,15 @@
 use {
     crate::{
         auth::AuthProvider,
-        config::{ConfigurationSet, SnapshotConfig, SnapshotPref},
-        corestore::Corestore,
+        config::{
+            ConfigurationSet, MemoryLimit, SnapshotConfig, SnapshotPref, SnapshotSinkConfig,
+            TuningProfile,
+        },
+        corestore::{map, Corestore},
         dbnet,
         diskstore::flock::FileLock,
-        services,
-        storage::v1::sengine::SnapshotEngine,
+        kvengine, protocol, queryengine, registry, services,
+        storage::v1::{flush, fsync, sengine::SnapshotEngine},
         util::{
             error::{Error, SkyResult},
             os::TerminationSignal,
@@ -43,6 +46,7 @@ use {
         sync::{
             broadcast,
             mpsc::{self, Sender},
+            Semaphore,
         },
         task::{self, JoinHandle},
         time::Duration,
@@ -60,6 +64,26 @@ pub async fn run(
         maxcon,
         auth,
         protocol,
+        strict_protocol,
+        archive,
+        snapshot_sink,
+        flush_workers,
+        profile,
+        sync,
+        memory,
+        limits,
+        default_entity,
+        resp,
+        unixsock,
+        websocket,
+        http,
+        maxcon_per_ip,
+        ratelimit,
+        listen,
+        proxy_protocol,
+        proxy_trusted,
+        query_timeout,
+        max_query_size,
         ..
     }: ConfigurationSet,
     restore_filepath: Option<String>,
@@ -67,17 +91,93 @@ pub async fn run(
     // Intialize the broadcast channel
     let (signal, _) = broadcast::channel(1);
     let engine = match &snapshot {
-        SnapshotConfig::Enabled(SnapshotPref { atmost, .. }) => SnapshotEngine::new(*atmost),
+        SnapshotConfig::Enabled(SnapshotPref {
+            atmost,
+            name_template,
+            ..
+        }) => match name_template {
+            Some(template) => SnapshotEngine::new(*atmost).with_name_template(template.clone()),
+            None => SnapshotEngine::new(*atmost),
+        },
         SnapshotConfig::Disabled => SnapshotEngine::new_disabled(),
     };
+    let engine = match snapshot_sink {
+        SnapshotSinkConfig::Local => engine,
+        SnapshotSinkConfig::S3(cfg) => {
+            let sink = services::snapshot::s3::S3Sink::new(cfg)
+                .map_err(|e| Error::ioerror_extra(e, "configuring the snapshot sink"))?;
+            engine.with_sink(Arc::new(sink))
+        }
+    };
     let engine = Arc::new(engine);
     // restore data
     services::restore_data(restore_filepath)
         .map_err(|e| Error::ioerror_extra(e, "restoring data from backup"))?;
+    // set the archive policy
+    kvengine::archive::init(archive.idle_days().unwrap_or(0))
+        .map_err(|e| Error::ioerror_extra(e, "initializing archive"))?;
+    // set the memory limit
+    kvengine::eviction::init(memory.maxmemory(), memory.eviction());
+    if let MemoryLimit::Limited {
+        maxmemory,
+        eviction,
+    } = memory
+    {
+        log::info!(
+            "Memory limit is {maxmemory} bytes with the `{}` eviction policy",
+            eviction.name()
+        );
+    }
+    // set the size limits
+    kvengine::limits::init(limits.max_key_size, limits.max_value_size);
+    // set the rate limits
+    queryengine::ratelimit::init(&ratelimit);
+    // set the query timeout
+    queryengine::deadline::init(query_timeout);
+    // set the number of flush workers
+    flush::set_flush_workers(flush_workers);
+    // set the sync policy
+    fsync::set_policy(sync);
+    // set the protocol conformance mode
+    protocol::set_strict(strict_protocol);
+    protocol::set_max_query_size(max_query_size as usize);
+    if strict_protocol {
+        log::info!("Strict protocol mode is enabled");
+    }
+    // apply the rest of the tuning profile (this must happen before any map is created)
+    let knobs = profile.knobs();
+    dbnet::set_buffer_size(knobs.buffer_size);
+    map::set_shards_per_core(knobs.shards_per_core);
+    if profile != TuningProfile::Default {
+        log::info!("Using the `{}` tuning profile", profile.name());
+    }
     // init the store
     let db = Corestore::init_with_snapcfg(engine.clone())?;
+    // point new connections at the default entity
+    if !default_entity.is_default() {
+        let store = db.get_store();
+        let exists = match store.get_keyspace_atomic_ref(&default_entity.keyspace) {
+            Some(ks) => match &default_entity.table {
+                Some(tblid) => ks.get_table_atomic_ref(tblid).is_some(),
+                None => true,
+            },
+            None => false,
+        };
+        if exists {
+            log::info!("New connections start in `{}`", default_entity.name());
+        } else {
+            log::warn!(
+                "The default entity `{}` doesn't exist (yet). New connections will start in `default.default` until it's created",
+                default_entity.name()
+            );
+        }
+        registry::set_default_entity(default_entity);
+    }
     // refresh the snapshotengine state
     engine.parse_dir()?;
+    // load plugins (if any)
+    #[cfg(all(feature = "plugins", unix))]
+    crate::plugins::load_dir(crate::plugins::DIR_PLUGINS)?;
     let auth_provider = match auth.origin_key {
         Some(key) => {
             let authref = db.get_store().setup_auth();
@@ -98,15 +198,113 @@ pub async fn run(
         snapshot,
         signal.subscribe(),
     ));
+    let archive_handle = tokio::spawn(services::archive::archive_service(
+        db.clone(),
+        archive,
+        signal.subscribe(),
+    ));
+    let fsync_handle = tokio::spawn(services::fsync::fsync_service(sync, signal.subscribe()));
+    let expiry_handle = tokio::spawn(services::expiry::expiry_service(
+        db.clone(),
+        signal.subscribe(),
+    ));
+    let usage_handle = tokio::spawn(services::usage::usage_service(
+        db.clone(),
+        signal.subscribe(),
+    ));
 
+    // reload the TLS certificates on SIGHUP
+    #[cfg(unix)]
+    if !ports.insecure_only() {
+        tokio::spawn(dbnet::tls::reload_on_sighup());
+    }
     // bind to signals
     let termsig =
         TerminationSignal::init().map_err(|e| Error::ioerror_extra(e, "binding to signals"))?;
+    // every listener takes its connections out of the same budget, and so does every IP
+    let climit = Arc::new(Semaphore::new(maxcon));
+    let iplimit = dbnet::IpLimiter::new(maxcon_per_ip);
+    // start the RESP listener (if enabled)
+    let mut resp_server = match resp.port() {
+        Some(port) => Some(
+            dbnet::resp::connect(
+                ports.get_host(),
+                port,
+                climit.clone(),
+                iplimit.clone(),
+                db.clone(),
+                auth_provider.clone(),
+                signal.clone(),
+            )
+            .await?,
+        ),
+        None => None,
+    };
+    // start the WebSocket listener (if enabled)
+    let mut websocket_server = match websocket.port() {
+        Some(port) => Some(
+            dbnet::websocket::connect(
+                ports.get_host(),
+                port,
+                protocol,
+                climit.clone(),
+                iplimit.clone(),
+                db.clone(),
+                auth_provider.clone(),
+                signal.clone(),
+            )
+            .await?,
+        ),
+        None => None,
+    };
+    // start the HTTP listener (if enabled)
+    let mut http_server = match http.port() {
+        Some(port) => Some(
+            dbnet::http::connect(
+                ports.get_host(),
+                port,
+                climit.clone(),
+                iplimit.clone(),
+                db.clone(),
+                auth_provider.clone(),
+                signal.clone(),
+            )
+            .await?,
+        ),
+        None => None,
+    };
+    // start the Unix domain socket listener (if enabled)
+    #[cfg(unix)]
+    let mut unix_server = match unixsock.path() {
+        Some(path) => Some(
+            dbnet::unix::connect(
+                path,
+                protocol,
+                climit.clone(),
+                db.clone(),
+                auth_provider.clone(),
+                signal.clone(),
+            )
+            .await?,
+        ),
+        None => None,
+    };
+    #[cfg(unix)]
+    let unix_server_run = dbnet::unix::run(&mut unix_server);
+    #[cfg(not(unix))]
+    let unix_server_run = {
+        // the configuration doesn't allow a socket on other platforms
+        drop(unixsock);
+        core::future::pending::<crate::IoResult<()>>()
+    };
     // start the server (single or multiple listeners)
     let mut server = dbnet::connect(
         ports,
+        listen,
         protocol,
-        maxcon,
+        climit.clone(),
+        iplimit,
+        proxy_protocol.then(|| Arc::new(proxy_trusted)),
         db.clone(),
         auth_provider,
         signal.clone(),
@@ -115,6 +313,10 @@ pub async fn run(
 
     tokio::select! {
         _ = server.run_server() => {},
+        _ = dbnet::resp::run(&mut resp_server) => {},
+        _ = dbnet::websocket::run(&mut websocket_server) => {},
+        _ = dbnet::http::run(&mut http_server) => {},
+        _ = unix_server_run => {},
         _ = termsig => {}
     }
 
@@ -122,10 +324,27 @@ pub async fn run(
     // drop the signal and let others exit
     drop(signal);
     server.finish_with_termsig().await;
+    if let Some(resp_server) = resp_server {
+        resp_server.base.release_self().await;
+    }
+    if let Some(websocket_server) = websocket_server {
+        websocket_server.base.release_self().await;
+    }
+    if let Some(http_server) = http_server {
+        http_server.base.release_self().await;
+    }
+    #[cfg(unix)]
+    if let Some(unix_server) = unix_server {
+        unix_server.release_self().await;
+    }
 
     // wait for the background services to terminate
     let _ = snapshot_handle.await;
     let _ = bgsave_handle.await;
+    let _ = archive_handle.await;
+    let _ = fsync_handle.await;
+    let _ = expiry_handle.await;
+    let _ = usage_handle.await;
     Ok(db)
 }
 
@@ -135,7 +354,10 @@ fn spawn_task(tx: Sender<bool>, db: Corestore, do_sleep: bool) -> JoinHandle<()>
             log::info!("Waiting for 10 seconds before retrying ...");
             sleep(Duration::from_secs(10));
         }
-        let ret = match crate::services::bgsave::run_bgsave(&db) {
+        let ret = crate::services::bgsave::run_bgsave(&db)
+            // files deferred by the sync policy must hit the disk before we exit
+            .and_then(|_| fsync::sync_pending());
+        let ret = match ret {
             Ok(()) => {
                 log::info!("Save before termination successful");
                 true
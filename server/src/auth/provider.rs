@@ -175,6 +175,21 @@ impl AuthProvider {
             }
         }
     }
+    /// Log in as `account` without a token, because the identity was already established
+    /// by a verified client certificate. Returns `false` if authn is disabled or the user
+    /// doesn't exist
+    pub fn login_with_certificate(&mut self, account: &[u8]) -> bool {
+        if !self.is_enabled() || account.len() > AUTHID_SIZE || !account.is_ascii() {
+            return false;
+        }
+        if self.authmap.contains_key(account) {
+            // we just verified the length
+            self.whoami = Some(unsafe { AuthID::from_slice(account) });
+            true
+        } else {
+            false
+        }
+    }
     pub fn regenerate_using_origin<P: ProtocolSpec>(
         &self,
         origin: &[u8],
@@ -233,7 +248,7 @@ impl AuthProvider {
             None => err(P::AUTH_ERROR_DISABLED),
         }
     }
-    fn ensure_root<P: ProtocolSpec>(&self) -> ActionResult<()> {
+    pub fn ensure_root<P: ProtocolSpec>(&self) -> ActionResult<()> {
         if self.are_you_root::<P>()? {
             Ok(())
         } else {
@@ -260,6 +275,10 @@ impl AuthProvider {
             .map(|kv| String::from_utf8_lossy(kv.key()).to_string())
             .collect())
     }
+    /// Returns the AuthID of the current user (if logged in)
+    pub fn current_user(&self) -> Option<&[u8]> {
+        self.whoami.as_deref()
+    }
     /// Return the AuthID of the current user
     pub fn whoami<P: ProtocolSpec>(&self) -> ActionResult<String> {
         self.ensure_enabled::<P>()?;
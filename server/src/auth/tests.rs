@@ -121,4 +121,20 @@ mod authn {
             ActionError::ActionError(Skyhash2::AUTH_CODE_PERMS)
         );
     }
+    #[test]
+    fn login_with_certificate() {
+        let mut provider = AuthProvider::new_blank(Some(*ORIG));
+        let _ = provider.claim_root::<Skyhash2>(ORIG).unwrap();
+        let mut provider = provider.clone();
+        // no such user
+        assert!(!provider.login_with_certificate(b"sayan"));
+        assert!(provider.current_user().is_none());
+        assert!(provider.login_with_certificate(b"root"));
+        assert_eq!(provider.current_user().unwrap(), b"root");
+    }
+    #[test]
+    fn login_with_certificate_disabled() {
+        let mut provider = AuthProvider::new_disabled();
+        assert!(!provider.login_with_certificate(b"root"));
+    }
 }
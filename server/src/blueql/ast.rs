@@ -30,8 +30,15 @@ use {
         lexer::{Keyword, Lexer, Token, Type, TypeExpression},
         RawSlice,
     },
-    crate::util::{compiler, Life},
-    core::{marker::PhantomData, mem::transmute, ptr},
+    crate::{
+        corestore::memstore::{cluster::ReplicationStrategy, ObjectID},
+        util::{compiler, Life},
+    },
+    core::{
+        marker::PhantomData,
+        mem::{transmute, ManuallyDrop},
+        ptr,
+    },
 };
 
 #[derive(Debug)]
@@ -46,6 +53,7 @@ pub enum Statement {
         entity: Entity,
         model: FieldConfig,
         volatile: bool,
+        shards: Option<u64>,
     },
     /// Drop the given model
     DropModel { entity: Entity, force: bool },
@@ -57,12 +65,69 @@ pub enum Statement {
     InspectModel(Option<Entity>),
     /// Inspect all the spaces in the database
     InspectSpaces,
+    /// Inspect what the entity catalog records for the given model
+    InspectMetadata(Option<Entity>),
     /// Switch to the given entity
     Use(Entity),
+    /// Rename the given model
+    RenameModel { entity: Entity, name: RawSlice },
+    /// Rename the given space
+    RenameSpace { space: RawSlice, name: RawSlice },
+    /// Alter a property of the given space
+    AlterSpace {
+        space: RawSlice,
+        property: SpaceProperty,
+    },
+    /// Back up the given spaces into an archive with the provided name
+    Backup {
+        spaces: Vec<RawSlice>,
+        name: RawSlice,
+    },
+    /// Apply the given writes to the current model, either all of them or none
+    Transaction(Vec<TxnWrite>),
 }
 
 pub type StatementLT<'a> = Life<'a, Statement>;
 
+#[derive(Debug, PartialEq)]
+/// A write in a transaction block
+pub enum TxnWrite {
+    /// `set "<key>" "<value>"`
+    Set(String, String),
+    /// `update "<key>" "<value>"`
+    Update(String, String),
+    /// `uset "<key>" "<value>"`
+    Upsert(String, String),
+    /// `del "<key>"`
+    Delete(String),
+}
+
+#[derive(Debug, PartialEq)]
+/// A property of a space that can be changed with `alter space`
+pub enum SpaceProperty {
+    /// The flush interval in seconds (zero means that the space is flushed on every
+    /// BGSAVE cycle)
+    FlushInterval(u64),
+    /// The maximum number of keys across the tables of the space (zero means no limit)
+    MaxKeys(u64),
+    /// The maximum memory (in bytes) taken by the entries of the tables of the space (zero
+    /// means no limit)
+    MaxBytes(u64),
+    /// The model that `use <space>` switches to (`None` if `use <space>` shouldn't switch to
+    /// any model)
+    DefaultModel(Option<String>),
+    /// The replication strategy of the space
+    ReplicationStrategy(ReplicationStrategy),
+}
+
+impl SpaceProperty {
+    const FLUSH_INTERVAL: &'static [u8] = b"flush_interval";
+    const MAX_KEYS: &'static [u8] = b"max_keys";
+    const MAX_BYTES: &'static [u8] = b"max_bytes";
+    const DEFAULT_MODEL: &'static [u8] = b"default_model";
+    const REPLICATION_STRATEGY: &'static [u8] = b"replication_strategy";
+}
+
 #[derive(Debug)]
 #[cfg_attr(test, derive(PartialEq))]
 pub enum Entity {
@@ -71,10 +136,26 @@ pub enum Entity {
 }
 
 impl Entity {
-    const MAX_LENGTH_EX: usize = 65;
+    /// The maximum length of a space or model name
+    pub const MAX_LENGTH: usize = ObjectID::MAX_LENGTH;
     pub fn from_slice(slice: &[u8]) -> LangResult<Self> {
         Compiler::new(&Lexer::lex(slice)?).parse_entity_name()
     }
+    /// Check that the given space or model name fits in an `ObjectID` and that it only
+    /// contains bytes that the lexer would accept in an identifier
+    pub fn validate_name(name: &[u8]) -> LangResult<()> {
+        if compiler::unlikely(name.len() > Self::MAX_LENGTH) {
+            return Err(LangError::EntityNameTooLong);
+        }
+        let valid = matches!(name.first(), Some(byte) if byte.is_ascii_alphabetic())
+            && name
+                .iter()
+                .all(|byte| byte.is_ascii_alphanumeric() || *byte == b'_');
+        if compiler::unlikely(!valid) {
+            return Err(LangError::BadEntityName);
+        }
+        Ok(())
+    }
 }
 
 #[derive(Debug)]
@@ -98,23 +179,28 @@ impl FieldConfig {
     // TODO(@ohsayan): Completely deprecate the model-code based API
     pub fn get_model_code(&self) -> LangResult<u8> {
         let Self { types, names } = self;
+        let is_compound =
+            |ty: Type| matches!(ty, Type::List | Type::Map | Type::Set | Type::SortedSet);
         let invalid_expr = {
             // the model API doesn't support named fields (it's super limited; we need to drop it)
             !names.is_empty()
             || types.len() != 2
             // the key type cannot be compound
             || types[0].0.len() != 1
-            // the key type cannot be a list
-            || types[0].0[0] == Type::List
+            // the key type cannot be a list, a map, a set, a sorted set or a document
+            || is_compound(types[0].0[0])
+            || types[0].0[0] == Type::Json
             // the value cannot have a depth more than two
             || types[1].0.len() > 2
-            // if the value is a string or binary, it cannot have a depth more than 1
-            || ((types[1].0[0] == Type::Binary || types[1].0[0] == Type::String) && types[1].0.len() != 1)
-            // if the value is a list, it must have a depth of two
-            || (types[1].0[0] == Type::List && types[1].0.len() != 2)
-            // if the value is a list, the type argument cannot be a list (it's stupid, I know; that's exactly
-            // why I'll be ditching this API in the next two PRs)
-            || (types[1].0[0] == Type::List && types[1].0[1] == Type::List)
+            // if the value is a string, binary or a document, it cannot have a depth more than 1
+            || (matches!(types[1].0[0], Type::Binary | Type::String | Type::Json) && types[1].0.len() != 1)
+            // if the value is a list, a map, a set or a sorted set, it must have a depth of two
+            || (is_compound(types[1].0[0]) && types[1].0.len() != 2)
+            // if the value is compound, the type argument cannot be compound (it's stupid, I know;
+            // that's exactly why I'll be ditching this API in the next two PRs)
+            || (is_compound(types[1].0[0]) && is_compound(types[1].0[1]))
+            // compound values can't hold documents
+            || (is_compound(types[1].0[0]) && types[1].0[1] == Type::Json)
         };
         if compiler::unlikely(invalid_expr) {
             // the value type cannot have a depth more than 2
@@ -122,10 +208,21 @@ impl FieldConfig {
         }
         let key_expr = &types[0].0;
         let value_expr = &types[1].0;
-        if value_expr[0] == Type::List {
+        if value_expr[0] == Type::Json {
+            // documents are always valid UTF-8, so only the key has an encoding
+            let k_enc = key_expr[0] == Type::String;
+            Ok(k_enc as u8 + 20)
+        } else if is_compound(value_expr[0]) {
+            // the type argument of a map applies to both its fields and its values
             let k_enc = key_expr[0] == Type::String;
             let v_enc = value_expr[1] == Type::String;
-            Ok(((k_enc as u8) << 1) + (v_enc as u8) + 4)
+            let base = match value_expr[0] {
+                Type::List => 4,
+                Type::Map => 8,
+                Type::Set => 12,
+                _ => 16,
+            };
+            Ok(((k_enc as u8) << 1) + (v_enc as u8) + base)
         } else {
             let k_enc = key_expr[0] == Type::String;
             let v_enc = value_expr[0] == Type::String;
@@ -185,6 +282,16 @@ impl<'a> Compiler<'a> {
         next_is_eq
     }
     #[inline(always)]
+    /// Check if the token ahead is the given identifier (ignoring case), moving the cursor
+    /// ahead if so
+    fn next_ident_eq(&mut self, ident: &[u8]) -> bool {
+        let next_is_eq = self.not_exhausted()
+            && matches!(unsafe { self.deref_cursor() },
+                Token::Identifier(id) if unsafe { id.as_slice() }.eq_ignore_ascii_case(ident));
+        unsafe { self.incr_cursor_if(next_is_eq) };
+        next_is_eq
+    }
+    #[inline(always)]
     /// Increment the cursor if the condition is true
     unsafe fn incr_cursor_if(&mut self, cond: bool) {
         self.incr_cursor_by(cond as usize)
@@ -238,6 +345,19 @@ impl<'a> Compiler<'a> {
         }
     }
     #[inline(always)]
+    /// Read the quoted string ahead. The string is cloned because the token stream still owns it
+    fn next_string(&mut self) -> LangResult<String> {
+        if compiler::unlikely(!self.not_exhausted()) {
+            return Err(LangError::UnexpectedEOF);
+        }
+        let string = match unsafe { self.deref_cursor() } {
+            Token::QuotedString(string) => string.clone(),
+            _ => return Err(LangError::InvalidSyntax),
+        };
+        unsafe { self.incr_cursor() };
+        Ok(string)
+    }
+    #[inline(always)]
     /// Returns the remaining number of tokens
     fn remaining(&self) -> usize {
         self.end_ptr as usize - self.cursor as usize
@@ -277,12 +397,15 @@ impl<'a> Compiler<'a> {
                 Token::Keyword(Keyword::Drop) => self.parse_drop0(),
                 Token::Keyword(Keyword::Inspect) => self.parse_inspect0(),
                 Token::Keyword(Keyword::Use) => self.parse_use0(),
+                Token::Keyword(Keyword::Alter) => self.parse_alter0(),
+                Token::Keyword(Keyword::Backup) => self.parse_backup0(),
+                Token::Keyword(Keyword::Transaction) => self.parse_transaction0(),
                 _ => Err(LangError::ExpectedStatement),
             },
             None => Err(LangError::UnexpectedEOF),
-        };
+        }?;
         if compiler::likely(self.remaining() == 0 && extra_len == 0) {
-            stmt
+            Ok(stmt)
         } else {
             Err(LangError::InvalidSyntax)
         }
@@ -292,6 +415,145 @@ impl<'a> Compiler<'a> {
         Ok(Statement::Use(self.parse_entity_name()?))
     }
     #[inline(always)]
+    /// Parse an alter statement
+    fn parse_alter0(&mut self) -> LangResult<Statement> {
+        match (self.next(), self.next()) {
+            (Some(Token::Keyword(Keyword::Space)), Some(Token::Identifier(space))) => {
+                self.parse_alter_space0(space)
+            }
+            (Some(Token::Keyword(Keyword::Model)), Some(Token::Identifier(model))) => {
+                let entity = self.parse_entity_name_with_start(model)?;
+                self.parse_alter_model0(entity)
+            }
+            _ => Err(LangError::InvalidSyntax),
+        }
+    }
+    #[inline(always)]
+    /// Parse `alter model <model> rename to <name>`
+    fn parse_alter_model0(&mut self, entity: Entity) -> LangResult<Statement> {
+        if !self.next_ident_eq(b"rename") {
+            return Err(LangError::InvalidSyntax);
+        }
+        let name = self.parse_rename_to0()?;
+        Ok(Statement::RenameModel { entity, name })
+    }
+    #[inline(always)]
+    /// Parse the `to <name>` that follows a `rename`
+    fn parse_rename_to0(&mut self) -> LangResult<RawSlice> {
+        if !self.next_ident_eq(b"to") {
+            return Err(LangError::InvalidSyntax);
+        }
+        self.next_entity_ident()
+    }
+    #[inline(always)]
+    /// Parse `alter space <space> with <property> <value>` or `alter space <space> rename to <name>`
+    fn parse_alter_space0(&mut self, space: RawSlice) -> LangResult<Statement> {
+        if self.next_ident_eq(b"rename") {
+            let name = self.parse_rename_to0()?;
+            return Ok(Statement::RenameSpace { space, name });
+        }
+        if !self.next_eq(&Token::Keyword(Keyword::With)) {
+            return Err(LangError::InvalidSyntax);
+        }
+        // the token stream still owns the value, so it must not be dropped
+        let (prop, value) = (self.next_ident()?, ManuallyDrop::new(self.next_result()?));
+        let property = match unsafe { prop.as_slice() }.to_ascii_lowercase().as_slice() {
+            SpaceProperty::FLUSH_INTERVAL => {
+                Self::space_number0(&value, SpaceProperty::FlushInterval)
+            }
+            SpaceProperty::MAX_KEYS => Self::space_number0(&value, SpaceProperty::MaxKeys),
+            SpaceProperty::MAX_BYTES => Self::space_number0(&value, SpaceProperty::MaxBytes),
+            SpaceProperty::DEFAULT_MODEL => match &*value {
+                Token::Identifier(model) => {
+                    if compiler::unlikely(model.len() > Entity::MAX_LENGTH) {
+                        return Err(LangError::EntityNameTooLong);
+                    }
+                    let model = unsafe { String::from_utf8_lossy(model.as_slice()) };
+                    Ok(SpaceProperty::DefaultModel(Some(model.into_owned())))
+                }
+                // `default_model ""` unsets the default model
+                Token::QuotedString(model) if model.is_empty() => {
+                    Ok(SpaceProperty::DefaultModel(None))
+                }
+                _ => Err(LangError::BadExpression),
+            },
+            SpaceProperty::REPLICATION_STRATEGY => match &*value {
+                Token::Identifier(name) => {
+                    ReplicationStrategy::from_name(unsafe { name.as_slice() })
+                        .map(SpaceProperty::ReplicationStrategy)
+                        .ok_or(LangError::BadExpression)
+                }
+                _ => Err(LangError::BadExpression),
+            },
+            _ => Err(LangError::UnknownProperty),
+        }?;
+        Ok(Statement::AlterSpace { space, property })
+    }
+    #[inline(always)]
+    /// Get the numeric value of a space property
+    fn space_number0(
+        value: &Token,
+        property: fn(u64) -> SpaceProperty,
+    ) -> LangResult<SpaceProperty> {
+        match value {
+            Token::Number(value) => Ok(property(*value)),
+            _ => Err(LangError::BadExpression),
+        }
+    }
+    #[inline(always)]
+    /// Parse `backup space <space1>, <space2>, ... into <name>`
+    fn parse_backup0(&mut self) -> LangResult<Statement> {
+        if !self.next_eq(&Token::Keyword(Keyword::Space)) {
+            return Err(LangError::InvalidSyntax);
+        }
+        let mut spaces = vec![self.next_entity_ident()?];
+        while self.next_eq(&Token::Comma) {
+            spaces.push(self.next_entity_ident()?);
+        }
+        if !self.next_eq(&Token::Keyword(Keyword::Into)) {
+            return Err(LangError::InvalidSyntax);
+        }
+        let name = self.next_entity_ident()?;
+        Ok(Statement::Backup { spaces, name })
+    }
+    #[inline(always)]
+    /// Parse `transaction (<write>, <write>, ...)`
+    fn parse_transaction0(&mut self) -> LangResult<Statement> {
+        if !self.next_eq(&Token::OpenParen) {
+            return Err(LangError::InvalidSyntax);
+        }
+        let mut writes = vec![self.parse_txn_write0()?];
+        while self.next_eq(&Token::Comma) {
+            writes.push(self.parse_txn_write0()?);
+        }
+        if !self.next_eq(&Token::CloseParen) {
+            return Err(LangError::InvalidSyntax);
+        }
+        Ok(Statement::Transaction(writes))
+    }
+    #[inline(always)]
+    /// Parse a single write in a transaction block
+    fn parse_txn_write0(&mut self) -> LangResult<TxnWrite> {
+        if compiler::unlikely(!self.not_exhausted()) {
+            return Err(LangError::UnexpectedEOF);
+        }
+        // don't read the token out: it may own a string
+        let op = match unsafe { self.deref_cursor() } {
+            Token::Keyword(Keyword::Type(Type::Set)) => b"set".to_vec(),
+            Token::Identifier(op) => unsafe { op.as_slice() }.to_ascii_lowercase(),
+            _ => return Err(LangError::InvalidSyntax),
+        };
+        unsafe { self.incr_cursor() };
+        let write = match op.as_slice() {
+            b"set" => TxnWrite::Set(self.next_string()?, self.next_string()?),
+            b"update" => TxnWrite::Update(self.next_string()?, self.next_string()?),
+            b"uset" => TxnWrite::Upsert(self.next_string()?, self.next_string()?),
+            b"del" => TxnWrite::Delete(self.next_string()?),
+            _ => return Err(LangError::InvalidSyntax),
+        };
+        Ok(write)
+    }
+    #[inline(always)]
     /// Parse an inspect statement
     fn parse_inspect0(&mut self) -> LangResult<Statement> {
         match self.next_result()? {
@@ -302,18 +564,26 @@ impl<'a> Compiler<'a> {
             {
                 Ok(Statement::InspectSpaces)
             }
+            Token::Identifier(metadata)
+                if unsafe { metadata.as_slice() }.eq_ignore_ascii_case(b"metadata") =>
+            {
+                Ok(Statement::InspectMetadata(self.parse_optional_entity0()?))
+            }
             _ => Err(LangError::InvalidSyntax),
         }
     }
     #[inline(always)]
     /// Parse `inspect model <model>`
     fn parse_inspect_model0(&mut self) -> LangResult<Statement> {
+        Ok(Statement::InspectModel(self.parse_optional_entity0()?))
+    }
+    #[inline(always)]
+    /// Parse an entity name if one follows (`None` refers to the current model)
+    fn parse_optional_entity0(&mut self) -> LangResult<Option<Entity>> {
         match self.next() {
-            Some(Token::Identifier(ident)) => Ok(Statement::InspectModel(Some(
-                self.parse_entity_name_with_start(ident)?,
-            ))),
+            Some(Token::Identifier(ident)) => Ok(Some(self.parse_entity_name_with_start(ident)?)),
             Some(_) => Err(LangError::InvalidSyntax),
-            None => Ok(Statement::InspectModel(None)),
+            None => Ok(None),
         }
     }
     #[inline(always)]
@@ -395,17 +665,35 @@ impl<'a> Compiler<'a> {
         // right name sounds like an outrageous idea)
         is_good_expr &= fc.names.is_empty() || fc.names.len() == fc.types.len();
         let volatile = self.next_eq(&Token::Keyword(Keyword::Volatile));
+        let shards = if is_good_expr && self.next_eq(&Token::Keyword(Keyword::With)) {
+            Some(self.parse_shards0()?)
+        } else {
+            None
+        };
         if compiler::likely(is_good_expr) {
             Ok(Statement::CreateModel {
                 entity,
                 model: fc,
                 volatile,
+                shards,
             })
         } else {
             Err(LangError::BadExpression)
         }
     }
     #[inline(always)]
+    /// Parse `shards <count>` (after the `with` of a `create model`)
+    fn parse_shards0(&mut self) -> LangResult<u64> {
+        let (prop, value) = (self.next_ident()?, self.next_result()?);
+        if !unsafe { prop.as_slice() }.eq_ignore_ascii_case(b"shards") {
+            return Err(LangError::UnknownProperty);
+        }
+        match value {
+            Token::Number(shards) if shards != 0 => Ok(shards),
+            _ => Err(LangError::BadExpression),
+        }
+    }
+    #[inline(always)]
     /// Parse a type expression return a `TypeExpression`
     fn parse_type_expression(&mut self, first_type: Type) -> LangResult<TypeExpression> {
         let mut expr = Vec::with_capacity(2);
@@ -454,32 +742,38 @@ impl<'a> Compiler<'a> {
     /// Parse a `create space` statement
     fn parse_create_space0(&mut self) -> LangResult<Statement> {
         match self.next() {
-            Some(Token::Identifier(model_name)) => Ok(Statement::CreateSpace(model_name)),
+            Some(Token::Identifier(model_name)) => Ok(Statement::CreateSpace(
+                Self::check_entity_ident(model_name)?,
+            )),
             Some(_) => Err(LangError::InvalidSyntax),
             None => Err(LangError::UnexpectedEOF),
         }
     }
     #[inline(always)]
     fn parse_entity_name_with_start(&mut self, start: RawSlice) -> LangResult<Entity> {
+        let start = Self::check_entity_ident(start)?;
         if self.peek_eq(&Token::Period) {
             unsafe { self.incr_cursor() };
-            Ok(Entity::Full(start, self.next_ident()?))
+            Ok(Entity::Full(start, self.next_entity_ident()?))
         } else {
             Ok(Entity::Current(start))
         }
     }
     #[inline(always)]
     pub(super) fn parse_entity_name(&mut self) -> LangResult<Entity> {
-        // let's peek the next token
-        match self.next_ident()? {
-            id if self.peek_eq(&Token::Period)
-                && compiler::likely(id.len() < Entity::MAX_LENGTH_EX) =>
-            {
-                unsafe { self.incr_cursor() };
-                Ok(Entity::Full(id, self.next_ident()?))
-            }
-            id if compiler::likely(id.len() < Entity::MAX_LENGTH_EX) => Ok(Entity::Current(id)),
-            _ => Err(LangError::InvalidSyntax),
+        let start = self.next_ident()?;
+        self.parse_entity_name_with_start(start)
+    }
+    #[inline(always)]
+    /// Get the next identifier, making sure that it can be used as a space or model name
+    fn next_entity_ident(&mut self) -> LangResult<RawSlice> {
+        Self::check_entity_ident(self.next_ident()?)
+    }
+    #[inline(always)]
+    fn check_entity_ident(ident: RawSlice) -> LangResult<RawSlice> {
+        if compiler::unlikely(ident.len() > Entity::MAX_LENGTH) {
+            return Err(LangError::EntityNameTooLong);
         }
+        Ok(ident)
     }
 }
@@ -51,6 +51,12 @@ pub enum LangError {
     UnsupportedModelDeclaration,
     /// Unexpected character
     UnexpectedChar,
+    /// Unknown property
+    UnknownProperty,
+    /// A space or model name is longer than the maximum length
+    EntityNameTooLong,
+    /// A space or model name contains bytes that aren't allowed in an identifier
+    BadEntityName,
 }
 
 /// Results for BlueQL
@@ -69,6 +75,9 @@ pub(super) const fn cold_err<P: ProtocolSpec>(e: LangError) -> &'static [u8] {
         LangError::UnknownCreateQuery => P::BQL_UNKNOWN_CREATE_QUERY,
         LangError::UnsupportedModelDeclaration => P::BQL_UNSUPPORTED_MODEL_DECL,
         LangError::UnexpectedChar => P::BQL_UNEXPECTED_CHAR,
+        LangError::UnknownProperty => P::RSTRING_UNKNOWN_PROPERTY,
+        LangError::EntityNameTooLong => P::RSTRING_CONTAINER_NAME_TOO_LONG,
+        LangError::BadEntityName => P::RSTRING_BAD_CONTAINER_NAME,
     }
 }
 
@@ -28,14 +28,15 @@ use crate::dbnet::BufferedSocketStream;
 
 use {
     super::{
-        ast::{Statement, StatementLT},
+        ast::{Statement, StatementLT, TxnWrite},
         error,
     },
     crate::{
         actions::{self, ActionError, ActionResult},
         blueql,
-        corestore::memstore::ObjectID,
+        corestore::{memstore::ObjectID, SharedSlice},
         dbnet::prelude::*,
+        kvengine::txn::Transaction,
     },
 };
 
@@ -67,6 +68,34 @@ where
                 handle.drop_keyspace(entity)
             }
         }
+        Statement::RenameSpace { space, name } if system_health_okay => {
+            // ret okay
+            let (space, name) = unsafe {
+                (
+                    ObjectID::from_slice(space.as_slice()),
+                    ObjectID::from_slice(name.as_slice()),
+                )
+            };
+            handle.rename_keyspace(space, name)
+        }
+        Statement::AlterSpace { space, property } if system_health_okay => {
+            // ret okay
+            let space = unsafe { ObjectID::from_slice(space.as_slice()) };
+            handle.alter_keyspace(space, property)
+        }
+        Statement::Backup { spaces, name } if system_health_okay => {
+            // ret okay
+            let name = unsafe { String::from_utf8_lossy(name.as_slice()) }.into_owned();
+            let spaces = spaces
+                .iter()
+                .map(|space| unsafe { ObjectID::from_slice(space.as_slice()) })
+                .collect();
+            handle.backup(name, spaces).await
+        }
+        Statement::RenameModel { entity, name } if system_health_okay => {
+            // ret okay
+            handle.rename_table(entity, unsafe { ObjectID::from_slice(name.as_slice()) })
+        }
         Statement::DropModel { entity, force } if system_health_okay => {
             // ret okay
             handle.drop_table(entity, *force)
@@ -75,13 +104,38 @@ where
             entity,
             model,
             volatile,
+            shards,
         } if system_health_okay => {
             match model.get_model_code() {
                 // ret okay
-                Ok(code) => handle.create_table(entity, code, *volatile),
+                Ok(code) => {
+                    handle.create_table(entity, code, *volatile, shards.map(|s| s as usize))
+                }
                 Err(e) => return Err(ActionError::ActionError(error::cold_err::<P>(e))),
             }
         }
+        Statement::Transaction(writes) if system_health_okay => {
+            // ret directly
+            let mut txn = Transaction::new();
+            for write in writes {
+                match write {
+                    TxnWrite::Set(key, value) => txn.set(shared(key), shared(value)),
+                    TxnWrite::Update(key, value) => txn.update(shared(key), shared(value)),
+                    TxnWrite::Upsert(key, value) => txn.upsert(shared(key), shared(value)),
+                    TxnWrite::Delete(key) => txn.delete(shared(key)),
+                };
+            }
+            let kve = handle.get_table_with::<P, KVEBlob>()?;
+            if handle
+                .get_ctable_ref()
+                .is_some_and(|table| table.rejects_writes())
+            {
+                return util::err(P::RSTRING_READ_ONLY);
+            }
+            actions::txn::translate_txn_result::<P>(kve.commit(txn))?;
+            con._write_raw(P::RCODE_OKAY).await?;
+            return Ok(());
+        }
         Statement::InspectSpaces => {
             // ret directly
             con.write_typed_non_null_array(&handle.get_store().list_keyspaces(), b'+')
@@ -103,6 +157,12 @@ where
                 .await?;
             return Ok(());
         }
+        Statement::InspectMetadata(model) => {
+            // ret directly
+            con.write_typed_non_null_array(handle.describe_entity::<P>(model)?, b'+')
+                .await?;
+            return Ok(());
+        }
         _ => {
             // the server is broken
             con._write_raw(P::RCODE_SERVER_ERR).await?;
@@ -113,3 +173,7 @@ where
     con._write_raw(P::RCODE_OKAY).await?;
     Ok(())
 }
+
+fn shared(string: &str) -> SharedSlice {
+    SharedSlice::new(string.as_bytes())
+}
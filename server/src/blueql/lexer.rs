@@ -87,6 +87,11 @@ pub enum Keyword {
     Space,
     Volatile,
     Force,
+    Alter,
+    With,
+    Backup,
+    Into,
+    Transaction,
     Type(Type),
 }
 
@@ -97,6 +102,10 @@ pub enum Type {
     String,
     Binary,
     List,
+    Map,
+    Set,
+    SortedSet,
+    Json,
 }
 
 #[derive(Debug, PartialEq)]
@@ -117,8 +126,17 @@ impl Keyword {
             b"string" => Keyword::Type(Type::String),
             b"binary" => Keyword::Type(Type::Binary),
             b"list" => Keyword::Type(Type::List),
+            b"map" => Keyword::Type(Type::Map),
+            b"set" => Keyword::Type(Type::Set),
+            b"zset" => Keyword::Type(Type::SortedSet),
+            b"json" => Keyword::Type(Type::Json),
             b"force" => Keyword::Force,
             b"use" => Keyword::Use,
+            b"alter" => Keyword::Alter,
+            b"with" => Keyword::With,
+            b"backup" => Keyword::Backup,
+            b"into" => Keyword::Into,
+            b"transaction" => Keyword::Transaction,
             _ => return None,
         };
         Some(r)
@@ -37,7 +37,7 @@ use {
     self::{ast::Statement, error::LangResult},
     crate::util::Life,
 };
-pub use {ast::Compiler, ast::Entity, executor::execute};
+pub use {ast::Compiler, ast::Entity, ast::SpaceProperty, executor::execute};
 
 #[cfg(test)]
 use core::fmt;
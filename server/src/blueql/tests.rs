@@ -24,10 +24,13 @@
  *
 */
 
-use super::{
-    ast::{Compiler, Entity, FieldConfig, Statement},
-    error::LangError,
-    lexer::{Keyword, Lexer, Token, Type, TypeExpression},
+use {
+    super::{
+        ast::{Compiler, Entity, FieldConfig, SpaceProperty, Statement, TxnWrite},
+        error::LangError,
+        lexer::{Keyword, Lexer, Token, Type, TypeExpression},
+    },
+    crate::corestore::memstore::cluster::ReplicationStrategy,
 };
 
 macro_rules! src {
@@ -196,6 +199,57 @@ mod ast {
             Entity::Full("hello".into(), "world".into())
         );
     }
+    #[test]
+    fn parse_entity_name_too_long() {
+        let long = "a".repeat(Entity::MAX_LENGTH + 1);
+        let max = "a".repeat(Entity::MAX_LENGTH);
+        for src in [
+            long.clone(),
+            format!("{long}.world"),
+            format!("hello.{long}"),
+        ] {
+            assert_eq!(
+                Entity::from_slice(src.as_bytes()).unwrap_err(),
+                LangError::EntityNameTooLong
+            );
+        }
+        assert_eq!(
+            Entity::from_slice(format!("{max}.{max}").as_bytes()).unwrap(),
+            Entity::Full(max.as_str().into(), max.as_str().into())
+        );
+        assert_eq!(
+            Compiler::compile(format!("create space {long}").as_bytes()).unwrap_err(),
+            LangError::EntityNameTooLong
+        );
+        assert_eq!(
+            Compiler::compile(format!("create model hello.{long}(string, string)").as_bytes())
+                .unwrap_err(),
+            LangError::EntityNameTooLong
+        );
+    }
+    #[test]
+    fn validate_entity_name() {
+        assert!(Entity::validate_name(b"twitter_v2").is_ok());
+        assert!(Entity::validate_name("a".repeat(Entity::MAX_LENGTH).as_bytes()).is_ok());
+        assert_eq!(
+            Entity::validate_name("a".repeat(Entity::MAX_LENGTH + 1).as_bytes()).unwrap_err(),
+            LangError::EntityNameTooLong
+        );
+        for bad in [
+            &b""[..],
+            b"2fast",
+            b"_hidden",
+            b"../etc",
+            b"a.b",
+            b"tab\0le",
+            b"sp ace",
+        ] {
+            assert_eq!(
+                Entity::validate_name(bad).unwrap_err(),
+                LangError::BadEntityName
+            );
+        }
+    }
 
     use super::*;
     #[cfg(test)]
@@ -214,6 +268,7 @@ mod ast {
                 names: vec!["username".into(), "password".into(), "posts".into()],
             },
             volatile: true,
+            shards: None,
         };
         (src, stmt)
     }
@@ -239,10 +294,39 @@ mod ast {
                 ],
             },
             volatile: false,
+            shards: None,
         };
         assert_eq!(Compiler::compile(&src).unwrap(), expected);
     }
     #[test]
+    fn stmt_create_with_shards() {
+        let src =
+            b"create model twitter.passwords(string, binary) volatile with SHARDS 64".to_vec();
+        let expected = Statement::CreateModel {
+            entity: Entity::Full("twitter".into(), "passwords".into()),
+            model: FieldConfig {
+                names: vec![],
+                types: vec![
+                    TypeExpression(vec![Type::String]),
+                    TypeExpression(vec![Type::Binary]),
+                ],
+            },
+            volatile: true,
+            shards: Some(64),
+        };
+        assert_eq!(Compiler::compile(&src).unwrap(), expected);
+        assert_eq!(
+            Compiler::compile(b"create model twitter.passwords(string, binary) with shards 0")
+                .unwrap_err(),
+            LangError::BadExpression
+        );
+        assert_eq!(
+            Compiler::compile(b"create model twitter.passwords(string, binary) with stripes 8")
+                .unwrap_err(),
+            LangError::UnknownProperty
+        );
+    }
+    #[test]
     fn stmt_drop_space() {
         assert_eq!(
             Compiler::compile(b"drop space twitter force").unwrap(),
@@ -277,6 +361,162 @@ mod ast {
         );
     }
     #[test]
+    fn stmt_inspect_metadata() {
+        assert_eq!(
+            Compiler::compile(b"inspect metadata twitter.tweet").unwrap(),
+            Statement::InspectMetadata(Some(Entity::Full("twitter".into(), "tweet".into())))
+        );
+        assert_eq!(
+            Compiler::compile(b"inspect METADATA").unwrap(),
+            Statement::InspectMetadata(None)
+        );
+    }
+    #[test]
+    fn stmt_alter_space() {
+        assert_eq!(
+            Compiler::compile(b"alter space twitter with flush_interval 300").unwrap(),
+            Statement::AlterSpace {
+                space: "twitter".into(),
+                property: SpaceProperty::FlushInterval(300)
+            }
+        );
+        assert_eq!(
+            Compiler::compile(b"alter space twitter with flush_interval twitter").unwrap_err(),
+            LangError::BadExpression
+        );
+        assert_eq!(
+            Compiler::compile(b"alter space twitter with MAX_KEYS 1000").unwrap(),
+            Statement::AlterSpace {
+                space: "twitter".into(),
+                property: SpaceProperty::MaxKeys(1000)
+            }
+        );
+        assert_eq!(
+            Compiler::compile(b"alter space twitter with max_bytes 1048576").unwrap(),
+            Statement::AlterSpace {
+                space: "twitter".into(),
+                property: SpaceProperty::MaxBytes(1048576)
+            }
+        );
+        assert_eq!(
+            Compiler::compile(b"alter space twitter with default_model tweets").unwrap(),
+            Statement::AlterSpace {
+                space: "twitter".into(),
+                property: SpaceProperty::DefaultModel(Some("tweets".to_owned()))
+            }
+        );
+        assert_eq!(
+            Compiler::compile(br#"alter space twitter with default_model """#).unwrap(),
+            Statement::AlterSpace {
+                space: "twitter".into(),
+                property: SpaceProperty::DefaultModel(None)
+            }
+        );
+        assert_eq!(
+            Compiler::compile(br#"alter space twitter with default_model "tweets""#).unwrap_err(),
+            LangError::BadExpression
+        );
+        assert_eq!(
+            Compiler::compile(b"alter space twitter with replication_strategy DEFAULT").unwrap(),
+            Statement::AlterSpace {
+                space: "twitter".into(),
+                property: SpaceProperty::ReplicationStrategy(ReplicationStrategy::Default)
+            }
+        );
+        assert_eq!(
+            Compiler::compile(b"alter space twitter with replication_strategy raft").unwrap_err(),
+            LangError::BadExpression
+        );
+        assert_eq!(
+            Compiler::compile(b"alter space twitter with replication 3").unwrap_err(),
+            LangError::UnknownProperty
+        );
+    }
+    #[test]
+    fn stmt_alter_model_rename() {
+        assert_eq!(
+            Compiler::compile(b"alter model twitter.tweet rename to tweet_v2").unwrap(),
+            Statement::RenameModel {
+                entity: Entity::Full("twitter".into(), "tweet".into()),
+                name: "tweet_v2".into()
+            }
+        );
+        assert_eq!(
+            Compiler::compile(b"alter model tweet RENAME TO tweet_v2").unwrap(),
+            Statement::RenameModel {
+                entity: Entity::Current("tweet".into()),
+                name: "tweet_v2".into()
+            }
+        );
+        assert_eq!(
+            Compiler::compile(b"alter model tweet rename tweet_v2").unwrap_err(),
+            LangError::InvalidSyntax
+        );
+    }
+    #[test]
+    fn stmt_alter_space_rename() {
+        assert_eq!(
+            Compiler::compile(b"alter space twiter rename to twitter").unwrap(),
+            Statement::RenameSpace {
+                space: "twiter".into(),
+                name: "twitter".into()
+            }
+        );
+        assert_eq!(
+            Compiler::compile(b"alter space twiter rename twitter").unwrap_err(),
+            LangError::InvalidSyntax
+        );
+    }
+    #[test]
+    fn stmt_backup() {
+        assert_eq!(
+            Compiler::compile(b"backup space twitter, chat into nightly").unwrap(),
+            Statement::Backup {
+                spaces: vec!["twitter".into(), "chat".into()],
+                name: "nightly".into()
+            }
+        );
+        assert_eq!(
+            Compiler::compile(b"backup space twitter").unwrap_err(),
+            LangError::InvalidSyntax
+        );
+        assert_eq!(
+            Compiler::compile(b"backup space into nightly").unwrap_err(),
+            LangError::InvalidSyntax
+        );
+    }
+    #[test]
+    fn stmt_transaction() {
+        assert_eq!(
+            Compiler::compile(
+                br#"transaction (set "a" "1", update "b" "2", uset "c" "3", del "d")"#
+            )
+            .unwrap(),
+            Statement::Transaction(vec![
+                TxnWrite::Set("a".into(), "1".into()),
+                TxnWrite::Update("b".into(), "2".into()),
+                TxnWrite::Upsert("c".into(), "3".into()),
+                TxnWrite::Delete("d".into()),
+            ])
+        );
+        assert_eq!(
+            Compiler::compile(br#"transaction ()"#).unwrap_err(),
+            LangError::InvalidSyntax
+        );
+        assert_eq!(
+            Compiler::compile(br#"transaction (set "a")"#).unwrap_err(),
+            LangError::InvalidSyntax
+        );
+        assert_eq!(
+            Compiler::compile(br#"transaction (get "a")"#).unwrap_err(),
+            LangError::InvalidSyntax
+        );
+        assert_eq!(
+            Compiler::compile(br#"transaction (del a)"#).unwrap_err(),
+            LangError::InvalidSyntax
+        );
+    }
+    #[test]
     fn compile_full() {
         let (src, stmt) = setup_src_stmt();
         assert_eq!(Compiler::compile(&src).unwrap(), stmt)
@@ -309,7 +549,26 @@ mod ast {
             // rule: fields can't be named
             "(id: string, posts: list<string>)",
             // rule: nested lists are disallowed
-            "(string, list<list<string>>)"
+            "(string, list<list<string>>)",
+            // rule: first cannot be map
+            "(map<string>, string)",
+            // rule: maps must have a type argument
+            "(string, map)",
+            // rule: maps can't hold lists or maps
+            "(string, map<list<string>>)",
+            "(string, map<map<string>>)",
+            // rule: sets can't hold sets
+            "(string, set<set<string>>)",
+            "(set<string>, string)",
+            // rule: sorted sets must have a type argument that isn't compound
+            "(string, zset)",
+            "(string, zset<set<string>>)",
+            "(zset<string>, string)",
+            // rule: documents can't be keys, and can't have a type argument or be held by
+            // compound values
+            "(json, string)",
+            "(string, json<string>)",
+            "(string, list<json>)"
         );
         for src in SRC {
             assert_eq!(
@@ -320,4 +579,30 @@ mod ast {
             );
         }
     }
+    #[test]
+    fn compound_model_code() {
+        let get_model_code = |src: &[u8]| {
+            let l = Lexer::lex(src).unwrap();
+            match Compiler::new(&l)
+                .parse_create_model1(Entity::Current("jotsy".into()))
+                .unwrap()
+            {
+                Statement::CreateModel { model, .. } => model.get_model_code().unwrap(),
+                x => panic!("Expected model found {:?}", x),
+            }
+        };
+        assert_eq!(get_model_code(b"(binary, map<binary>)"), 8);
+        assert_eq!(get_model_code(b"(binary, map<string>)"), 9);
+        assert_eq!(get_model_code(b"(string, map<binary>)"), 10);
+        assert_eq!(get_model_code(b"(string, map<string>)"), 11);
+        assert_eq!(get_model_code(b"(binary, set<binary>)"), 12);
+        assert_eq!(get_model_code(b"(string, set<binary>)"), 14);
+        assert_eq!(get_model_code(b"(string, set<string>)"), 15);
+        assert_eq!(get_model_code(b"(binary, zset<string>)"), 17);
+        assert_eq!(get_model_code(b"(string, zset<string>)"), 19);
+        assert_eq!(get_model_code(b"(binary, json)"), 20);
+        assert_eq!(get_model_code(b"(string, json)"), 21);
+        // lists are untouched
+        assert_eq!(get_model_code(b"(string, list<string>)"), 7);
+    }
 }
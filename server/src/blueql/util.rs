@@ -39,3 +39,8 @@ pub fn from_slice_action_result<P: ProtocolSpec>(slice: &[u8]) -> ActionResult<L
         Err(e) => Err(ActionError::ActionError(error::cold_err::<P>(e))),
     }
 }
+
+/// Validate a space or model name that was passed as a raw action argument
+pub fn validate_entity_name<P: ProtocolSpec>(name: &[u8]) -> ActionResult<()> {
+    Entity::validate_name(name).map_err(|e| ActionError::ActionError(error::cold_err::<P>(e)))
+}
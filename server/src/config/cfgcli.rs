@@ -74,11 +74,15 @@ pub(super) fn parse_cli_args(matches: ArgMatches) -> Configset {
             )
         };
     }
+    // the tuning profile goes first since it sets the defaults for other settings
+    fcli!(tuning_profile, matches.value_of("profile"), "--profile");
     // protocol settings
     fcli! {
         protocol_settings,
         matches.value_of("protover"),
-        "--protover"
+        "--protover",
+        Flag::<true>::new(matches.is_present("strictprotocol")),
+        "--strict-protocol"
     };
     // server settings
     fcli!(
@@ -95,6 +99,57 @@ pub(super) fn parse_cli_args(matches: ArgMatches) -> Configset {
     );
     fcli!(server_mode, matches.value_of("mode"), "--mode");
     fcli!(server_maxcon, matches.value_of("maxcon"), "--maxcon");
+    fcli!(
+        server_maxcon_per_ip,
+        matches.value_of("maxconperip"),
+        "--maxcon-per-ip"
+    );
+    fcli!(
+        server_query_timeout,
+        matches.value_of("querytimeout"),
+        "--query-timeout"
+    );
+    fcli!(
+        server_max_query_size,
+        matches.value_of("maxquerysize"),
+        "--max-query-size"
+    );
+    fcli!(
+        server_flush_workers,
+        matches.value_of("flushworkers"),
+        "--flush-workers"
+    );
+    fcli!(server_sync, matches.value_of("sync"), "--sync");
+    fcli!(
+        server_memory,
+        matches.value_of("maxmemory"),
+        "--maxmemory",
+        matches.value_of("eviction"),
+        "--eviction"
+    );
+    fcli!(
+        server_limits,
+        matches.value_of("maxkeysize"),
+        "--max-key-size",
+        matches.value_of("maxvaluesize"),
+        "--max-value-size"
+    );
+    fcli!(
+        server_default_entity,
+        matches.value_of("defaultentity"),
+        "--default-entity"
+    );
+    fcli!(server_unixsock, matches.value_of("unixsock"), "--unixsock");
+    fcli!(
+        server_proxy_protocol,
+        Flag::<true>::new(matches.is_present("proxyprotocol")),
+        "--proxy-protocol"
+    );
+    fcli!(
+        server_proxy_trusted,
+        matches.value_of("proxytrusted"),
+        "--proxy-trusted"
+    );
     // bgsave settings
     fcli!(
         bgsave_settings,
@@ -111,7 +166,33 @@ pub(super) fn parse_cli_args(matches: ArgMatches) -> Configset {
         matches.value_of("snapkeep"),
         "--snapkeep",
         matches.value_of("stop-write-on-fail"),
-        "--stop-write-on-fail"
+        "--stop-write-on-fail",
+        matches.value_of("snapkeepfor"),
+        "--snap-keep-for",
+        matches.value_of("snapnametemplate"),
+        "--snap-name-template"
+    );
+    // archive settings
+    fcli!(
+        archive_settings,
+        matches.value_of("archiveidledays"),
+        "--archive-idle-days"
+    );
+    // RESP settings
+    fcli!(resp_settings, matches.value_of("respport"), "--resp-port");
+    // WebSocket settings
+    fcli!(
+        websocket_settings,
+        matches.value_of("websocketport"),
+        "--websocket-port"
+    );
+    // HTTP settings
+    fcli!(http_settings, matches.value_of("httpport"), "--http-port");
+    // rate limits
+    fcli!(
+        rate_limit_settings,
+        matches.value_of("ratelimit"),
+        "--rate-limit"
     );
     // TLS settings
     fcli!(
@@ -125,8 +206,12 @@ pub(super) fn parse_cli_args(matches: ArgMatches) -> Configset {
         Flag::<true>::new(matches.is_present("sslonly")),
         "--sslonly",
         matches.value_of("tlspass"),
-        "--tlspassin"
+        "--tlspassin",
+        matches.value_of("tlsclientca"),
+        "--tls-clientca"
     );
+    // the additional listeners (after TLS, since the secure ones need it)
+    fcli!(server_listen, matches.value_of("listen"), "--listen");
     // auth settings
     fcli!(
         auth_settings,
@@ -44,12 +44,29 @@ pub(super) fn parse_env_config() -> Configset {
             );
         };
     }
+    // the tuning profile goes first since it sets the defaults for other settings
+    fenv!(tuning_profile, SKY_SYSTEM_PROFILE);
     // protocol settings
-    fenv!(protocol_settings, SKY_PROTOCOL_VERSION);
+    fenv!(protocol_settings, SKY_PROTOCOL_VERSION, SKY_PROTOCOL_STRICT);
     // server settings
     fenv!(server_tcp, SKY_SYSTEM_HOST, SKY_SYSTEM_PORT);
     fenv!(server_noart, SKY_SYSTEM_NOART);
     fenv!(server_maxcon, SKY_SYSTEM_MAXCON);
+    fenv!(server_maxcon_per_ip, SKY_SYSTEM_MAXCON_PER_IP);
+    fenv!(server_query_timeout, SKY_SYSTEM_QUERY_TIMEOUT);
+    fenv!(server_max_query_size, SKY_SYSTEM_MAX_QUERY_SIZE);
+    fenv!(server_flush_workers, SKY_SYSTEM_FLUSH_WORKERS);
+    fenv!(server_sync, SKY_SYSTEM_SYNC);
+    fenv!(server_memory, SKY_SYSTEM_MAXMEMORY, SKY_SYSTEM_EVICTION);
+    fenv!(
+        server_limits,
+        SKY_SYSTEM_MAX_KEY_SIZE,
+        SKY_SYSTEM_MAX_VALUE_SIZE
+    );
+    fenv!(server_default_entity, SKY_SYSTEM_DEFAULT_ENTITY);
+    fenv!(server_unixsock, SKY_SYSTEM_UNIXSOCK);
+    fenv!(server_proxy_protocol, SKY_SYSTEM_PROXY_PROTOCOL);
+    fenv!(server_proxy_trusted, SKY_SYSTEM_PROXY_TRUSTED);
     fenv!(server_mode, SKY_DEPLOY_MODE);
     // bgsave settings
     fenv!(bgsave_settings, SKY_BGSAVE_ENABLED, SKY_BGSAVE_DURATION);
@@ -58,7 +75,28 @@ pub(super) fn parse_env_config() -> Configset {
         snapshot_settings,
         SKY_SNAPSHOT_DURATION,
         SKY_SNAPSHOT_KEEP,
-        SKY_SNAPSHOT_FAILSAFE
+        SKY_SNAPSHOT_FAILSAFE,
+        SKY_SNAPSHOT_KEEP_FOR,
+        SKY_SNAPSHOT_NAME_TEMPLATE
+    );
+    // archive settings
+    fenv!(archive_settings, SKY_ARCHIVE_IDLE_DAYS);
+    // RESP settings
+    fenv!(resp_settings, SKY_RESP_PORT);
+    // WebSocket settings
+    fenv!(websocket_settings, SKY_WEBSOCKET_PORT);
+    // HTTP settings
+    fenv!(http_settings, SKY_HTTP_PORT);
+    // rate limits
+    fenv!(rate_limit_settings, SKY_RATE_LIMIT);
+    // snapshot sink settings
+    fenv!(
+        snapshot_sink_settings,
+        SKY_SNAPSHOT_S3_ENDPOINT,
+        SKY_SNAPSHOT_S3_BUCKET,
+        SKY_SNAPSHOT_S3_REGION,
+        SKY_SNAPSHOT_S3_ACCESS_KEY,
+        SKY_SNAPSHOT_S3_SECRET_KEY
     );
     // TLS settings
     fenv!(
@@ -67,8 +105,11 @@ pub(super) fn parse_env_config() -> Configset {
         SKY_TLS_CERT,
         SKY_TLS_PORT,
         SKY_TLS_ONLY,
-        SKY_TLS_PASSIN
+        SKY_TLS_PASSIN,
+        SKY_TLS_CLIENTCA
     );
+    // the additional listeners (after TLS, since the secure ones need it)
+    fenv!(server_listen, SKY_SYSTEM_LISTEN);
     fenv!(auth_settings, SKY_AUTH_ORIGIN_KEY);
     defset
 }
@@ -26,11 +26,11 @@
 
 use {
     super::{
-        AuthSettings, ConfigSourceParseResult, Configset, Modeset, OptString, ProtocolVersion,
-        TryFromConfigSource,
+        AuthSettings, ConfigSourceParseResult, Configset, DurationSecs, EvictionPolicy, Modeset,
+        OptString, ProtocolVersion, SizeBytes, SyncPolicy, TryFromConfigSource, TuningProfile,
     },
     serde::Deserialize,
-    std::net::IpAddr,
+    std::{collections::HashMap, net::IpAddr},
 };
 
 /// This struct is an _object representation_ used for parsing the TOML file
@@ -42,6 +42,18 @@ pub struct Config {
     pub(super) bgsave: Option<ConfigKeyBGSAVE>,
     /// The snapshot key
     pub(super) snapshot: Option<ConfigKeySnapshot>,
+    /// The archive key
+    pub(super) archive: Option<ConfigKeyArchive>,
+    /// The RESP key
+    pub(super) resp: Option<ConfigKeyResp>,
+    /// The WebSocket key
+    pub(super) websocket: Option<ConfigKeyWebSocket>,
+    /// The HTTP key
+    pub(super) http: Option<ConfigKeyHttp>,
+    /// The rate limits key
+    pub(super) ratelimit: Option<ConfigKeyRateLimit>,
+    /// The S3 snapshot sink key
+    pub(super) snapshot_s3: Option<ConfigKeySnapshotS3>,
     /// SSL configuration
     pub(super) ssl: Option<KeySslOpts>,
     /// auth settings
@@ -60,9 +72,41 @@ pub struct ConfigKeyServer {
     pub(super) noart: Option<bool>,
     /// The maximum number of clients
     pub(super) maxclient: Option<usize>,
+    /// The maximum number of clients from a single IP
+    pub(super) maxcon_per_ip: Option<usize>,
     /// The deployment mode
     pub(super) mode: Option<Modeset>,
     pub(super) protocol: Option<ProtocolVersion>,
+    /// If set, non-conforming queries are rejected early
+    pub(super) strict_protocol: Option<bool>,
+    /// The maximum number of threads used to flush tables
+    pub(super) flush_workers: Option<usize>,
+    /// The tuning profile
+    pub(super) profile: Option<TuningProfile>,
+    /// The sync policy for flushed files
+    pub(super) sync: Option<SyncPolicy>,
+    /// The memory limit for the data
+    pub(super) maxmemory: Option<SizeBytes>,
+    /// The eviction policy for writes over the memory limit
+    pub(super) eviction: Option<EvictionPolicy>,
+    /// The maximum key size
+    pub(super) max_key_size: Option<SizeBytes>,
+    /// The maximum value size
+    pub(super) max_value_size: Option<SizeBytes>,
+    /// The entity that new connections start in
+    pub(super) default_entity: Option<String>,
+    /// The path of the Unix domain socket to listen on
+    pub(super) unixsock: Option<String>,
+    /// The additional addresses to listen on
+    pub(super) listen: Option<Vec<String>>,
+    /// Whether connections start with a PROXY protocol header
+    pub(super) proxy_protocol: Option<bool>,
+    /// The proxies that are trusted to send PROXY protocol headers
+    pub(super) proxy_trusted: Option<Vec<String>>,
+    /// The maximum execution time of a query in milliseconds
+    pub(super) query_timeout: Option<u64>,
+    /// The maximum size of a query
+    pub(super) max_query_size: Option<SizeBytes>,
 }
 
 /// The BGSAVE section in the config file
@@ -90,6 +134,60 @@ pub struct ConfigKeySnapshot {
     pub(super) atmost: usize,
     /// Prevent writes to the database if snapshotting fails
     pub(super) failsafe: Option<bool>,
+    /// Remove snapshots that are older than this, even if there are less than `atmost` of them
+    #[serde(alias = "keep-for")]
+    pub(super) keep_for: Option<DurationSecs>,
+    /// The template for snapshot names, like `{hostname}-{date}-{seq}`
+    pub(super) name_template: Option<String>,
+}
+
+/// The archive section in the TOML file
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct ConfigKeyArchive {
+    /// The number of days a key should be idle for before its value is archived
+    pub(super) idle_days: u64,
+}
+
+/// The RESP section in the TOML file
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct ConfigKeyResp {
+    /// The port that the RESP2 listener (for Redis clients) listens on
+    pub(super) port: u16,
+}
+
+/// The WebSocket section in the TOML file
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct ConfigKeyWebSocket {
+    /// The port that the WebSocket listener listens on
+    pub(super) port: u16,
+}
+
+/// The HTTP section in the TOML file
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct ConfigKeyHttp {
+    /// The port that the HTTP listener listens on
+    pub(super) port: u16,
+}
+
+/// The rate limits section in the TOML file
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct ConfigKeyRateLimit {
+    /// The limit for every connection (queries per second)
+    pub(super) connection: Option<u64>,
+    /// The limits for users (queries per second across all of a user's connections)
+    pub(super) users: Option<HashMap<String, u64>>,
+}
+
+/// The S3 snapshot sink section in the TOML file
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct ConfigKeySnapshotS3 {
+    /// The endpoint, in the form `http(s)://host[:port]`
+    pub(super) endpoint: String,
+    pub(super) bucket: String,
+    /// The region (defaults to `us-east-1`)
+    pub(super) region: Option<String>,
+    pub(super) access_key: String,
+    pub(super) secret_key: String,
 }
 
 #[derive(Deserialize, Debug, PartialEq)]
@@ -99,6 +197,7 @@ pub struct KeySslOpts {
     pub(super) port: u16,
     pub(super) only: Option<bool>,
     pub(super) passin: Option<String>,
+    pub(super) clientca: Option<String>,
 }
 
 /// A custom non-null type for config files
@@ -169,9 +268,17 @@ pub fn from_file(file: ConfigFile) -> Configset {
         server,
         bgsave,
         snapshot,
+        archive,
+        resp,
+        websocket,
+        http,
+        ratelimit,
+        snapshot_s3,
         ssl,
         auth,
     } = file;
+    // the tuning profile goes first since it sets the defaults for other settings
+    set.tuning_profile(Optional::from(server.profile), "server.profile");
     // server settings
     set.server_tcp(
         Optional::some(server.host),
@@ -179,10 +286,43 @@ pub fn from_file(file: ConfigFile) -> Configset {
         Optional::some(server.port),
         "server.port",
     );
-    set.protocol_settings(server.protocol, "server.protocol");
+    set.protocol_settings(
+        server.protocol,
+        "server.protocol",
+        Optional::from(server.strict_protocol),
+        "server.strict_protocol",
+    );
     set.server_maxcon(Optional::from(server.maxclient), "server.maxcon");
+    set.server_maxcon_per_ip(Optional::from(server.maxcon_per_ip), "server.maxcon_per_ip");
+    set.server_query_timeout(Optional::from(server.query_timeout), "server.query_timeout");
+    set.server_max_query_size(
+        Optional::from(server.max_query_size),
+        "server.max_query_size",
+    );
     set.server_noart(Optional::from(server.noart), "server.noart");
     set.server_mode(Optional::from(server.mode), "server.mode");
+    set.server_flush_workers(Optional::from(server.flush_workers), "server.flush_workers");
+    set.server_sync(Optional::from(server.sync), "server.sync");
+    set.server_memory(
+        Optional::from(server.maxmemory),
+        "server.maxmemory",
+        Optional::from(server.eviction),
+        "server.eviction",
+    );
+    set.server_limits(
+        Optional::from(server.max_key_size),
+        "server.max_key_size",
+        Optional::from(server.max_value_size),
+        "server.max_value_size",
+    );
+    set.server_default_entity(server.default_entity.as_deref(), "server.default_entity");
+    set.server_unixsock(server.unixsock.as_deref(), "server.unixsock");
+    set.server_proxy_protocol(
+        Optional::from(server.proxy_protocol),
+        "server.proxy_protocol",
+    );
+    let proxy_trusted = server.proxy_trusted.map(|nets| nets.join(","));
+    set.server_proxy_trusted(proxy_trusted.as_deref(), "server.proxy_trusted");
     // bgsave settings
     if let Some(bgsave) = bgsave {
         let ConfigKeyBGSAVE { enabled, every } = bgsave;
@@ -199,6 +339,8 @@ pub fn from_file(file: ConfigFile) -> Configset {
             every,
             atmost,
             failsafe,
+            keep_for,
+            name_template,
         } = snapshot;
         set.snapshot_settings(
             NonNull::from(every),
@@ -207,6 +349,60 @@ pub fn from_file(file: ConfigFile) -> Configset {
             "snapshot.atmost",
             Optional::from(failsafe),
             "snapshot.failsafe",
+            Optional::from(keep_for),
+            "snapshot.keep_for",
+            name_template.as_deref(),
+            "snapshot.name_template",
+        );
+    }
+    // archive settings
+    if let Some(archive) = archive {
+        let ConfigKeyArchive { idle_days } = archive;
+        set.archive_settings(NonNull::from(idle_days), "archive.idle_days");
+    }
+    // RESP settings
+    if let Some(resp) = resp {
+        let ConfigKeyResp { port } = resp;
+        set.resp_settings(NonNull::from(port), "resp.port");
+    }
+    // WebSocket settings
+    if let Some(websocket) = websocket {
+        let ConfigKeyWebSocket { port } = websocket;
+        set.websocket_settings(NonNull::from(port), "websocket.port");
+    }
+    // HTTP settings
+    if let Some(http) = http {
+        let ConfigKeyHttp { port } = http;
+        set.http_settings(NonNull::from(port), "http.port");
+    }
+    // rate limits
+    if let Some(ratelimit) = ratelimit {
+        let ConfigKeyRateLimit { connection, users } = ratelimit;
+        set.rate_limit_settings(Optional::from(connection), "ratelimit.connection");
+        if let Some(users) = users {
+            set.rate_limit_users(users, "ratelimit.users");
+        }
+    }
+    // snapshot sink settings
+    if let Some(s3) = snapshot_s3 {
+        let ConfigKeySnapshotS3 {
+            endpoint,
+            bucket,
+            region,
+            access_key,
+            secret_key,
+        } = s3;
+        set.snapshot_sink_settings(
+            NonNull::from(endpoint),
+            "snapshot_s3.endpoint",
+            NonNull::from(bucket),
+            "snapshot_s3.bucket",
+            Optional::from(region),
+            "snapshot_s3.region",
+            NonNull::from(access_key),
+            "snapshot_s3.access_key",
+            NonNull::from(secret_key),
+            "snapshot_s3.secret_key",
         );
     }
     // TLS settings
@@ -217,6 +413,7 @@ pub fn from_file(file: ConfigFile) -> Configset {
             port,
             only,
             passin,
+            clientca,
         } = tls;
         set.tls_settings(
             NonNull::from(key),
@@ -229,8 +426,13 @@ pub fn from_file(file: ConfigFile) -> Configset {
             "ssl.only",
             OptString::from(passin),
             "ssl.passin",
+            OptString::from(clientca),
+            "ssl.clientca",
         );
     }
+    // the additional listeners go after the TLS settings, which the secure ones use
+    let listen = server.listen.map(|addrs| addrs.join(","));
+    set.server_listen(listen.as_deref(), "server.listen");
     if let Some(auth) = auth {
         let AuthSettings { origin_key } = auth;
         set.auth_settings(Optional::from(origin_key), "auth.origin")
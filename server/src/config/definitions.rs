@@ -25,14 +25,26 @@
 */
 
 use {
-    super::{feedback::WarningStack, DEFAULT_IPV4, DEFAULT_PORT},
-    crate::{config::AuthkeyWrapper, dbnet::MAXIMUM_CONNECTION_LIMIT},
+    super::{feedback::WarningStack, DEFAULT_BGSAVE_DURATION, DEFAULT_IPV4, DEFAULT_PORT},
+    crate::{
+        blueql::Entity,
+        config::AuthkeyWrapper,
+        corestore::{
+            export::ExportFormat,
+            map::DEFAULT_SHARDS_PER_CORE,
+            memstore::{ObjectID, DEFAULT},
+        },
+        dbnet::{DEFAULT_BUFFER_SIZE, MAXIMUM_CONNECTION_LIMIT},
+        storage::v1::{flush::DEFAULT_FLUSH_WORKERS, snapname::NameTemplate},
+        util,
+    },
     core::{fmt, str::FromStr},
     serde::{
         de::{self, Deserializer, Visitor},
         Deserialize,
     },
     std::net::IpAddr,
+    std::net::SocketAddr,
 };
 
 /// The BGSAVE configuration
@@ -68,8 +80,478 @@ impl BGSave {
     }
 }
 
+/// The archive configuration
+///
+/// If archiving is enabled, then the number of days after which an idle key's value is moved
+/// to the archive is wrapped in the `Enabled` variant
+#[derive(PartialEq, Debug)]
+pub enum ArchivePolicy {
+    Enabled(u64),
+    Disabled,
+}
+
+impl ArchivePolicy {
+    /// The default archive configuration (disabled)
+    pub const fn default() -> Self {
+        Self::Disabled
+    }
+    /// Returns the number of days after which an idle key is archived, if archiving is enabled
+    pub const fn idle_days(&self) -> Option<u64> {
+        match self {
+            Self::Enabled(days) => Some(*days),
+            Self::Disabled => None,
+        }
+    }
+}
+
+/// The RESP configuration
+///
+/// If the RESP2 listener (for Redis clients) is enabled, then the port it listens on is
+/// wrapped in the `Enabled` variant
+#[derive(PartialEq, Debug)]
+pub enum RespConfig {
+    Enabled(u16),
+    Disabled,
+}
+
+impl RespConfig {
+    /// The default RESP configuration (disabled)
+    pub const fn default() -> Self {
+        Self::Disabled
+    }
+    /// Returns the port of the RESP2 listener, if it is enabled
+    pub const fn port(&self) -> Option<u16> {
+        match self {
+            Self::Enabled(port) => Some(*port),
+            Self::Disabled => None,
+        }
+    }
+}
+
+/// The WebSocket configuration
+///
+/// If the WebSocket listener is enabled, then the port it listens on is wrapped in the
+/// `Enabled` variant
+#[derive(PartialEq, Debug)]
+pub enum WebSocketConfig {
+    Enabled(u16),
+    Disabled,
+}
+
+impl WebSocketConfig {
+    /// The default WebSocket configuration (disabled)
+    pub const fn default() -> Self {
+        Self::Disabled
+    }
+    /// Returns the port of the WebSocket listener, if it is enabled
+    pub const fn port(&self) -> Option<u16> {
+        match self {
+            Self::Enabled(port) => Some(*port),
+            Self::Disabled => None,
+        }
+    }
+}
+
+/// The HTTP configuration
+///
+/// If the HTTP listener is enabled, then the port it listens on is wrapped in the `Enabled`
+/// variant
+#[derive(PartialEq, Debug)]
+pub enum HttpConfig {
+    Enabled(u16),
+    Disabled,
+}
+
+impl HttpConfig {
+    /// The default HTTP configuration (disabled)
+    pub const fn default() -> Self {
+        Self::Disabled
+    }
+    /// Returns the port of the HTTP listener, if it is enabled
+    pub const fn port(&self) -> Option<u16> {
+        match self {
+            Self::Enabled(port) => Some(*port),
+            Self::Disabled => None,
+        }
+    }
+}
+
+/// The rate limits (in queries per second). A rate of zero means that there is no limit
+#[derive(PartialEq, Debug)]
+pub struct RateLimitConfig {
+    /// the limit for every connection
+    pub connection: u64,
+    /// the limits for users (each shared by all of the user's connections)
+    pub users: Vec<(String, u64)>,
+}
+
+impl RateLimitConfig {
+    /// The default rate limits (none)
+    pub const fn default() -> Self {
+        Self {
+            connection: 0,
+            users: Vec::new(),
+        }
+    }
+}
+
+/// An address to listen on, in addition to the ones set up by the [`PortConfig`]. Secure
+/// listeners use the same TLS settings as the main secure listener
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct ListenAddr {
+    pub addr: SocketAddr,
+    pub secure: bool,
+}
+
+impl fmt::Display for ListenAddr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.secure {
+            write!(f, "skyhash-secure://{}", self.addr)
+        } else {
+            write!(f, "skyhash://{}", self.addr)
+        }
+    }
+}
+
+impl FromStr for ListenAddr {
+    type Err = ();
+    fn from_str(st: &str) -> Result<Self, Self::Err> {
+        let (addr, secure) = match st.strip_prefix("skyhash-secure://") {
+            Some(addr) => (addr, true),
+            None => (st.strip_prefix("skyhash://").unwrap_or(st), false),
+        };
+        addr.parse()
+            .map(|addr| Self { addr, secure })
+            .map_err(|_| ())
+    }
+}
+
+/// The additional addresses to listen on
+#[derive(PartialEq, Debug)]
+pub struct ListenConfig {
+    pub addrs: Vec<ListenAddr>,
+}
+
+impl ListenConfig {
+    /// The default listen configuration (no additional addresses)
+    pub const fn default() -> Self {
+        Self { addrs: Vec::new() }
+    }
+}
+
+impl FromStr for ListenConfig {
+    type Err = ();
+    /// Parse a comma separated list of addresses
+    fn from_str(st: &str) -> Result<Self, Self::Err> {
+        let addrs = st
+            .split(',')
+            .map(str::trim)
+            .filter(|addr| !addr.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<ListenAddr>, ()>>()?;
+        if addrs.is_empty() {
+            Err(())
+        } else {
+            Ok(Self { addrs })
+        }
+    }
+}
+
+/// A network (like `10.0.0.0/8`) or a single address
+#[derive(PartialEq, Debug, Clone, Copy)]
+pub struct IpNet {
+    pub addr: IpAddr,
+    pub prefix: u8,
+}
+
+impl IpNet {
+    /// Returns true if `ip` is in this network
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        // IPv4 clients of dual-stack listeners show up as IPv4-mapped IPv6 addresses
+        match (self.addr, ip.to_canonical()) {
+            (IpAddr::V4(net), IpAddr::V4(ip)) => {
+                let mask = u32::MAX.checked_shl(32 - self.prefix as u32).unwrap_or(0);
+                u32::from(net) & mask == u32::from(ip) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(ip)) => {
+                let mask = u128::MAX.checked_shl(128 - self.prefix as u32).unwrap_or(0);
+                u128::from(net) & mask == u128::from(ip) & mask
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for IpNet {
+    type Err = ();
+    fn from_str(st: &str) -> Result<Self, Self::Err> {
+        let (addr, prefix) = match st.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (st, None),
+        };
+        let addr: IpAddr = addr.parse().map_err(|_| ())?;
+        let max = if addr.is_ipv4() { 32 } else { 128 };
+        let prefix = match prefix {
+            Some(prefix) => prefix.parse().map_err(|_| ())?,
+            None => max,
+        };
+        if prefix > max {
+            return Err(());
+        }
+        Ok(Self { addr, prefix })
+    }
+}
+
+/// The proxies that are trusted to send PROXY protocol headers
+#[derive(PartialEq, Debug, Clone)]
+pub struct TrustedProxies {
+    pub nets: Vec<IpNet>,
+}
+
+impl TrustedProxies {
+    /// The default (no trusted proxies)
+    pub const fn default() -> Self {
+        Self { nets: Vec::new() }
+    }
+    /// Returns true if connections from `ip` are trusted to start with a PROXY protocol header
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        self.nets.iter().any(|net| net.contains(ip))
+    }
+}
+
+impl FromStr for TrustedProxies {
+    type Err = ();
+    /// Parse a comma separated list of networks
+    fn from_str(st: &str) -> Result<Self, Self::Err> {
+        let nets = st
+            .split(',')
+            .map(str::trim)
+            .filter(|net| !net.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<IpNet>, ()>>()?;
+        if nets.is_empty() {
+            Err(())
+        } else {
+            Ok(Self { nets })
+        }
+    }
+}
+
+/// The Unix domain socket configuration
+///
+/// If the Unix domain socket listener is enabled, then the path of the socket file is wrapped
+/// in the `Enabled` variant
+#[derive(PartialEq, Debug)]
+pub enum UnixSockConfig {
+    Enabled(String),
+    Disabled,
+}
+
+impl UnixSockConfig {
+    /// The default Unix domain socket configuration (disabled)
+    pub const fn default() -> Self {
+        Self::Disabled
+    }
+    /// Returns the path of the socket file, if the listener is enabled
+    pub fn path(&self) -> Option<&str> {
+        match self {
+            Self::Enabled(path) => Some(path),
+            Self::Disabled => None,
+        }
+    }
+}
+
+impl FromStr for UnixSockConfig {
+    type Err = ();
+    fn from_str(st: &str) -> Result<Self, Self::Err> {
+        if st.is_empty() {
+            Err(())
+        } else {
+            Ok(Self::Enabled(st.to_owned()))
+        }
+    }
+}
+
+/// The memory limit configuration
+///
+/// When `maxmemory` is set, writes that would go over it either evict keys or are rejected,
+/// depending on the eviction policy
+#[derive(PartialEq, Debug)]
+pub enum MemoryLimit {
+    Limited {
+        maxmemory: u64,
+        eviction: EvictionPolicy,
+    },
+    Unlimited,
+}
+
+impl MemoryLimit {
+    /// The default memory limit configuration (unlimited)
+    pub const fn default() -> Self {
+        Self::Unlimited
+    }
+    /// Returns the memory limit in bytes, or zero if there is no limit
+    pub const fn maxmemory(&self) -> u64 {
+        match self {
+            Self::Limited { maxmemory, .. } => *maxmemory,
+            Self::Unlimited => 0,
+        }
+    }
+    /// Returns the eviction policy (which only matters if there's a limit)
+    pub const fn eviction(&self) -> EvictionPolicy {
+        match self {
+            Self::Limited { eviction, .. } => *eviction,
+            Self::Unlimited => EvictionPolicy::default(),
+        }
+    }
+}
+
+/// The node-wide size limits for keys and values (see [`crate::kvengine::limits`])
+#[derive(PartialEq, Debug)]
+pub struct SizeLimitConfig {
+    /// The maximum key size in bytes, or zero if there is no limit
+    pub max_key_size: u64,
+    /// The maximum value size in bytes, or zero if there is no limit
+    pub max_value_size: u64,
+}
+
+impl SizeLimitConfig {
+    pub const fn new(max_key_size: u64, max_value_size: u64) -> Self {
+        Self {
+            max_key_size,
+            max_value_size,
+        }
+    }
+    /// The default size limits (none)
+    pub const fn default() -> Self {
+        Self::new(0, 0)
+    }
+}
+
+/// The entity that new connections start in: either a keyspace (with no table selected) or a
+/// table in a keyspace, written like the argument of `use`
+#[derive(PartialEq, Debug, Clone)]
+pub struct DefaultEntity {
+    pub keyspace: ObjectID,
+    pub table: Option<ObjectID>,
+}
+
+impl DefaultEntity {
+    /// The default entity (`default.default`)
+    pub const fn default() -> Self {
+        Self {
+            keyspace: DEFAULT,
+            table: Some(DEFAULT),
+        }
+    }
+    /// Returns true if this is `default.default`, which is where connections start anyway
+    pub fn is_default(&self) -> bool {
+        self == &Self::default()
+    }
+    /// Returns the entity as it would be passed to `use`
+    pub fn name(&self) -> String {
+        let keyspace = unsafe { self.keyspace.as_str() };
+        match &self.table {
+            Some(table) => format!("{keyspace}.{}", unsafe { table.as_str() }),
+            None => keyspace.to_owned(),
+        }
+    }
+}
+
+impl FromStr for DefaultEntity {
+    type Err = ();
+    fn from_str(st: &str) -> Result<DefaultEntity, Self::Err> {
+        // the parser makes sure that the names fit into an object ID
+        let entity = Entity::from_slice(st.as_bytes()).map_err(|_| ())?;
+        let ret = unsafe {
+            match entity {
+                Entity::Current(keyspace) => Self {
+                    keyspace: ObjectID::from_slice(keyspace.as_slice()),
+                    table: None,
+                },
+                Entity::Full(keyspace, table) => Self {
+                    keyspace: ObjectID::from_slice(keyspace.as_slice()),
+                    table: Some(ObjectID::from_slice(table.as_slice())),
+                },
+            }
+        };
+        Ok(ret)
+    }
+}
+
+/// The eviction policy, deciding what happens to writes once the memory limit is hit (see
+/// [`crate::kvengine::eviction`])
 #[repr(u8)]
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EvictionPolicy {
+    /// Reject writes until memory is freed
+    Reject = 0,
+    /// Evict the (approximately) least recently used keys
+    Lru = 1,
+    /// Evict the (approximately) least frequently used keys
+    Lfu = 2,
+}
+
+impl EvictionPolicy {
+    pub const fn default() -> Self {
+        Self::Reject
+    }
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Reject => "reject",
+            Self::Lru => "lru",
+            Self::Lfu => "lfu",
+        }
+    }
+    pub const fn from_u8(v: u8) -> Self {
+        match v {
+            1 => Self::Lru,
+            2 => Self::Lfu,
+            _ => Self::Reject,
+        }
+    }
+}
+
+impl FromStr for EvictionPolicy {
+    type Err = ();
+    fn from_str(st: &str) -> Result<EvictionPolicy, Self::Err> {
+        match st {
+            "reject" => Ok(Self::Reject),
+            "lru" => Ok(Self::Lru),
+            "lfu" => Ok(Self::Lfu),
+            _ => Err(()),
+        }
+    }
+}
+
+struct EvictionPolicyVisitor;
+
+impl<'de> Visitor<'de> for EvictionPolicyVisitor {
+    type Value = EvictionPolicy;
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Expecting a string with the eviction policy")
+    }
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        value
+            .parse()
+            .map_err(|_| E::custom(format!("Bad value `{value}` for eviction policy")))
+    }
+}
+
+impl<'de> Deserialize<'de> for EvictionPolicy {
+    fn deserialize<D>(deserializer: D) -> Result<EvictionPolicy, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(EvictionPolicyVisitor)
+    }
+}
+
+#[repr(u8)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub enum ProtocolVersion {
     V1,
     V2,
@@ -137,6 +619,46 @@ pub struct ConfigurationSet {
     pub auth: AuthSettings,
     /// The protocol version
     pub protocol: ProtocolVersion,
+    /// If set, non-conforming queries are rejected early instead of being handled leniently
+    pub strict_protocol: bool,
+    /// The archive configuration
+    pub archive: ArchivePolicy,
+    /// The snapshot sink configuration
+    pub snapshot_sink: SnapshotSinkConfig,
+    /// The maximum number of threads used to flush tables
+    pub flush_workers: usize,
+    /// The tuning profile
+    pub profile: TuningProfile,
+    /// The sync policy for flushed files
+    pub sync: SyncPolicy,
+    /// The memory limit configuration
+    pub memory: MemoryLimit,
+    /// The size limits for keys and values
+    pub limits: SizeLimitConfig,
+    /// The entity that new connections start in
+    pub default_entity: DefaultEntity,
+    /// The RESP configuration
+    pub resp: RespConfig,
+    /// The Unix domain socket configuration
+    pub unixsock: UnixSockConfig,
+    /// The WebSocket configuration
+    pub websocket: WebSocketConfig,
+    /// The HTTP configuration
+    pub http: HttpConfig,
+    /// The maximum number of connections from a single IP (zero means no limit)
+    pub maxcon_per_ip: usize,
+    /// The rate limits
+    pub ratelimit: RateLimitConfig,
+    /// The additional addresses to listen on
+    pub listen: ListenConfig,
+    /// Whether connections start with a PROXY protocol header
+    pub proxy_protocol: bool,
+    /// The proxies that are trusted to send PROXY protocol headers
+    pub proxy_trusted: TrustedProxies,
+    /// The maximum execution time of a query in milliseconds (zero means no limit)
+    pub query_timeout: u64,
+    /// The maximum size of a query in bytes (zero means no limit)
+    pub max_query_size: u64,
 }
 
 impl ConfigurationSet {
@@ -150,6 +672,26 @@ impl ConfigurationSet {
         mode: Modeset,
         auth: AuthSettings,
         protocol: ProtocolVersion,
+        strict_protocol: bool,
+        archive: ArchivePolicy,
+        snapshot_sink: SnapshotSinkConfig,
+        flush_workers: usize,
+        profile: TuningProfile,
+        sync: SyncPolicy,
+        memory: MemoryLimit,
+        limits: SizeLimitConfig,
+        default_entity: DefaultEntity,
+        resp: RespConfig,
+        unixsock: UnixSockConfig,
+        websocket: WebSocketConfig,
+        http: HttpConfig,
+        maxcon_per_ip: usize,
+        ratelimit: RateLimitConfig,
+        listen: ListenConfig,
+        proxy_protocol: bool,
+        proxy_trusted: TrustedProxies,
+        query_timeout: u64,
+        max_query_size: u64,
     ) -> Self {
         Self {
             noart,
@@ -160,6 +702,26 @@ impl ConfigurationSet {
             mode,
             auth,
             protocol,
+            strict_protocol,
+            archive,
+            snapshot_sink,
+            flush_workers,
+            profile,
+            sync,
+            memory,
+            limits,
+            default_entity,
+            resp,
+            unixsock,
+            websocket,
+            http,
+            maxcon_per_ip,
+            ratelimit,
+            listen,
+            proxy_protocol,
+            proxy_trusted,
+            query_timeout,
+            max_query_size,
         }
     }
     /// Create a default `ConfigurationSet` with the following setup defaults:
@@ -169,6 +731,24 @@ impl ConfigurationSet {
     /// - `bgsave_enabled` : true
     /// - `bgsave_duration` : 120
     /// - `ssl` : disabled
+    /// - `strict_protocol` : false
+    /// - `flush_workers` : 4
+    /// - `profile` : default
+    /// - `sync` : always
+    /// - `memory` : unlimited
+    /// - `limits` : none
+    /// - `default_entity` : `default.default`
+    /// - `resp` : disabled
+    /// - `unixsock` : disabled
+    /// - `websocket` : disabled
+    /// - `http` : disabled
+    /// - `maxcon_per_ip` : 0 (no limit)
+    /// - `ratelimit` : none
+    /// - `listen` : no additional addresses
+    /// - `proxy_protocol` : false
+    /// - `proxy_trusted` : none
+    /// - `query_timeout` : 0 (no limit)
+    /// - `max_query_size` : 0 (no limit)
     pub const fn default() -> Self {
         Self::new(
             false,
@@ -179,12 +759,72 @@ impl ConfigurationSet {
             Modeset::Dev,
             AuthSettings::default(),
             ProtocolVersion::V2,
+            false,
+            ArchivePolicy::default(),
+            SnapshotSinkConfig::default(),
+            DEFAULT_FLUSH_WORKERS,
+            TuningProfile::default(),
+            SyncPolicy::default(),
+            MemoryLimit::default(),
+            SizeLimitConfig::default(),
+            DefaultEntity::default(),
+            RespConfig::default(),
+            UnixSockConfig::default(),
+            WebSocketConfig::default(),
+            HttpConfig::default(),
+            0,
+            RateLimitConfig::default(),
+            ListenConfig::default(),
+            false,
+            TrustedProxies::default(),
+            0,
+            0,
         )
     }
     /// Returns `false` if `noart` is enabled. Otherwise it returns `true`
     pub const fn is_artful(&self) -> bool {
         !self.noart
     }
+    /// Returns the effective values of the tunable settings (as `key = value` lines), with the
+    /// tuning profile expanded
+    pub fn effective_settings(&self) -> Vec<String> {
+        let knobs = self.profile.knobs();
+        let mut settings = vec![format!("server.profile = {}", self.profile.name())];
+        match self.bgsave {
+            BGSave::Enabled(every) => {
+                settings.push("bgsave.enabled = true".to_owned());
+                settings.push(format!("bgsave.every = {every}"));
+            }
+            BGSave::Disabled => settings.push("bgsave.enabled = false".to_owned()),
+        }
+        settings.push(format!("server.maxcon = {}", self.maxcon));
+        settings.push(format!("server.maxcon_per_ip = {}", self.maxcon_per_ip));
+        settings.push(format!("server.flush_workers = {}", self.flush_workers));
+        settings.push(format!("server.sync = {}", self.sync.name()));
+        settings.push(format!("server.maxmemory = {}", self.memory.maxmemory()));
+        settings.push(format!(
+            "server.eviction = {}",
+            self.memory.eviction().name()
+        ));
+        settings.push(format!(
+            "server.max_key_size = {}",
+            self.limits.max_key_size
+        ));
+        settings.push(format!(
+            "server.max_value_size = {}",
+            self.limits.max_value_size
+        ));
+        settings.push(format!(
+            "server.default_entity = {}",
+            self.default_entity.name()
+        ));
+        settings.push(format!("server.buffer_size = {}", knobs.buffer_size));
+        settings.push(format!(
+            "server.shards_per_core = {}",
+            knobs.shards_per_core
+        ));
+        settings
+    }
 }
 
 /// Port configuration
@@ -273,21 +913,31 @@ impl PortConfig {
     }
 }
 
-#[derive(Deserialize, Debug, PartialEq)]
+#[derive(Deserialize, Debug, PartialEq, Clone)]
 pub struct SslOpts {
     pub key: String,
     pub chain: String,
     pub port: u16,
     pub passfile: Option<String>,
+    /// The CA bundle that client certificates are verified against. If set, clients must
+    /// present a certificate
+    pub clientca: Option<String>,
 }
 
 impl SslOpts {
-    pub const fn new(key: String, chain: String, port: u16, passfile: Option<String>) -> Self {
+    pub const fn new(
+        key: String,
+        chain: String,
+        port: u16,
+        passfile: Option<String>,
+        clientca: Option<String>,
+    ) -> Self {
         SslOpts {
             key,
             chain,
             port,
             passfile,
+            clientca,
         }
     }
     pub const fn get_port(&self) -> u16 {
@@ -305,20 +955,140 @@ pub struct SnapshotPref {
     pub atmost: usize,
     /// Lock writes if snapshotting fails
     pub poison: bool,
+    /// Remove snapshots that are older than these many seconds
+    pub keep_for: Option<u64>,
+    /// The template for snapshot names (the default template is used if this is `None`)
+    pub name_template: Option<NameTemplate>,
 }
 
 impl SnapshotPref {
     /// Create a new a new `SnapshotPref` instance
-    pub const fn new(every: u64, atmost: usize, poison: bool) -> Self {
+    pub const fn new(
+        every: u64,
+        atmost: usize,
+        poison: bool,
+        keep_for: Option<u64>,
+        name_template: Option<NameTemplate>,
+    ) -> Self {
         SnapshotPref {
             every,
             atmost,
             poison,
+            keep_for,
+            name_template,
         }
     }
-    /// Returns `every,almost` as a tuple for pattern matching
-    pub const fn decompose(self) -> (u64, usize, bool) {
-        (self.every, self.atmost, self.poison)
+    /// Returns `every,almost,poison,keep_for,name_template` as a tuple for pattern matching
+    pub fn decompose(self) -> (u64, usize, bool, Option<u64>, Option<NameTemplate>) {
+        (
+            self.every,
+            self.atmost,
+            self.poison,
+            self.keep_for,
+            self.name_template,
+        )
+    }
+}
+
+/// A duration in seconds, written as `90` (seconds), `30s`, `15m`, `6h` or `7d`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct DurationSecs(pub u64);
+
+impl FromStr for DurationSecs {
+    type Err = ();
+    fn from_str(st: &str) -> Result<DurationSecs, Self::Err> {
+        util::parse_duration(st).map(DurationSecs).ok_or(())
+    }
+}
+
+struct DurationSecsVisitor;
+
+impl<'de> Visitor<'de> for DurationSecsVisitor {
+    type Value = DurationSecs;
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Expecting a duration like `7d` or a number of seconds")
+    }
+    fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(DurationSecs(value))
+    }
+    fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        u64::try_from(value)
+            .map(DurationSecs)
+            .map_err(|_| E::custom(format!("Bad value `{value}` for duration")))
+    }
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        value
+            .parse()
+            .map_err(|_| E::custom(format!("Bad value `{value}` for duration")))
+    }
+}
+
+impl<'de> Deserialize<'de> for DurationSecs {
+    fn deserialize<D>(deserializer: D) -> Result<DurationSecs, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(DurationSecsVisitor)
+    }
+}
+
+/// A size in bytes, written as `1048576` (bytes), `512k`, `64m`, `2g` or `1t`
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct SizeBytes(pub u64);
+
+impl FromStr for SizeBytes {
+    type Err = ();
+    fn from_str(st: &str) -> Result<SizeBytes, Self::Err> {
+        util::parse_size(st).map(SizeBytes).ok_or(())
+    }
+}
+
+struct SizeBytesVisitor;
+
+impl<'de> Visitor<'de> for SizeBytesVisitor {
+    type Value = SizeBytes;
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Expecting a size like `512m` or a number of bytes")
+    }
+    fn visit_u64<E>(self, value: u64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        Ok(SizeBytes(value))
+    }
+    fn visit_i64<E>(self, value: i64) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        u64::try_from(value)
+            .map(SizeBytes)
+            .map_err(|_| E::custom(format!("Bad value `{value}` for size")))
+    }
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        value
+            .parse()
+            .map_err(|_| E::custom(format!("Bad value `{value}` for size")))
+    }
+}
+
+impl<'de> Deserialize<'de> for SizeBytes {
+    fn deserialize<D>(deserializer: D) -> Result<SizeBytes, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(SizeBytesVisitor)
     }
 }
 
@@ -344,8 +1114,62 @@ impl SnapshotConfig {
     }
 }
 
+#[derive(PartialEq)]
+/// The connection details for an S3-compatible object store
+pub struct S3Config {
+    /// The endpoint, in the form `http(s)://host[:port]`
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key: String,
+    pub secret_key: String,
+}
+
+impl fmt::Debug for S3Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // keep the secret out of logs
+        f.debug_struct("S3Config")
+            .field("endpoint", &self.endpoint)
+            .field("bucket", &self.bucket)
+            .field("region", &self.region)
+            .field("access_key", &self.access_key)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, PartialEq)]
+/// Where snapshots are shipped to after they've been written to the local snapshot directory
+pub enum SnapshotSinkConfig {
+    /// Snapshots are only kept in the local snapshot directory
+    Local,
+    /// Snapshots are also uploaded to an S3-compatible object store
+    S3(S3Config),
+}
+
+impl SnapshotSinkConfig {
+    pub const fn default() -> Self {
+        SnapshotSinkConfig::Local
+    }
+}
+
 type RestoreFile = Option<String>;
 
+#[derive(Debug, PartialEq, Eq)]
+/// A task that is run on the data directory instead of starting up
+pub enum OfflineTask {
+    /// Salvage a damaged data directory
+    Repair,
+    /// Export a keyspace or a table (`<keyspace>` or `<keyspace>:<table>`)
+    Export(String, ExportFormat),
+    /// Import a file into a table (`<keyspace>:<table>`)
+    Import {
+        target: String,
+        file: String,
+        format: ExportFormat,
+        continue_on_error: bool,
+    },
+}
+
 #[derive(Debug, PartialEq)]
 /// The type of configuration:
 /// - The default configuration
@@ -353,6 +1177,7 @@ type RestoreFile = Option<String>;
 pub struct ConfigType {
     pub(super) config: ConfigurationSet,
     restore: RestoreFile,
+    task: Option<OfflineTask>,
     is_custom: bool,
     warnings: Option<WarningStack>,
 }
@@ -367,6 +1192,7 @@ impl ConfigType {
         Self {
             config,
             restore,
+            task: None,
             is_custom,
             warnings,
         }
@@ -376,8 +1202,13 @@ impl ConfigType {
             warnings.print_warnings()
         }
     }
-    pub fn finish(self) -> (ConfigurationSet, Option<String>) {
-        (self.config, self.restore)
+    /// Run `task` on the data directory (and exit) instead of starting up
+    pub fn with_task(mut self, task: Option<OfflineTask>) -> Self {
+        self.task = task;
+        self
+    }
+    pub fn finish(self) -> (ConfigurationSet, Option<String>, Option<OfflineTask>) {
+        (self.config, self.restore, self.task)
     }
     pub fn is_custom(&self) -> bool {
         self.is_custom
@@ -458,6 +1289,191 @@ impl<'de> Deserialize<'de> for Modeset {
     }
 }
 
+/// A tuning profile
+///
+/// A profile sets the defaults for a group of knobs (see [`TuningProfile::knobs`]). The BGSAVE
+/// interval, the number of flush workers and the sync policy can still be set explicitly, in which case they
+/// override the profile's value
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum TuningProfile {
+    /// The defaults for every knob
+    Default,
+    /// Avoids contention and keeps background work from competing with queries
+    Latency,
+    /// Larger buffers and more background parallelism for bulk workloads
+    Throughput,
+    /// Smaller buffers and fewer shards for memory constrained hosts
+    Memory,
+}
+
+/// The knobs set by a tuning profile
+#[derive(Debug, PartialEq)]
+pub struct TuningKnobs {
+    /// The BGSAVE interval (in seconds)
+    pub bgsave_every: u64,
+    /// The number of threads used to flush tables
+    pub flush_workers: usize,
+    /// The size of the read and write buffers of every connection (in bytes)
+    pub buffer_size: usize,
+    /// The number of shards per core in every map
+    pub shards_per_core: usize,
+    /// The sync policy for flushed files
+    pub sync: SyncPolicy,
+}
+
+impl TuningProfile {
+    pub const fn default() -> Self {
+        Self::Default
+    }
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Default => "default",
+            Self::Latency => "latency",
+            Self::Throughput => "throughput",
+            Self::Memory => "memory",
+        }
+    }
+    /// Returns the knobs set by this profile
+    pub const fn knobs(&self) -> TuningKnobs {
+        let (bgsave_every, flush_workers, buffer_size, shards_per_core, sync) = match self {
+            Self::Default => (
+                DEFAULT_BGSAVE_DURATION,
+                DEFAULT_FLUSH_WORKERS,
+                DEFAULT_BUFFER_SIZE,
+                DEFAULT_SHARDS_PER_CORE,
+                SyncPolicy::Always,
+            ),
+            Self::Latency => (
+                DEFAULT_BGSAVE_DURATION,
+                2,
+                DEFAULT_BUFFER_SIZE,
+                32,
+                SyncPolicy::EverySec,
+            ),
+            Self::Throughput => (300, 8, 65536, DEFAULT_SHARDS_PER_CORE, SyncPolicy::EverySec),
+            Self::Memory => (DEFAULT_BGSAVE_DURATION, 1, 2048, 4, SyncPolicy::Always),
+        };
+        TuningKnobs {
+            bgsave_every,
+            flush_workers,
+            buffer_size,
+            shards_per_core,
+            sync,
+        }
+    }
+}
+
+impl FromStr for TuningProfile {
+    type Err = ();
+    fn from_str(st: &str) -> Result<TuningProfile, Self::Err> {
+        match st {
+            "default" => Ok(Self::Default),
+            "latency" => Ok(Self::Latency),
+            "throughput" => Ok(Self::Throughput),
+            "memory" => Ok(Self::Memory),
+            _ => Err(()),
+        }
+    }
+}
+
+struct TuningProfileVisitor;
+
+impl<'de> Visitor<'de> for TuningProfileVisitor {
+    type Value = TuningProfile;
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Expecting a string with the tuning profile")
+    }
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        value
+            .parse()
+            .map_err(|_| E::custom(format!("Bad value `{value}` for tuning profile")))
+    }
+}
+
+impl<'de> Deserialize<'de> for TuningProfile {
+    fn deserialize<D>(deserializer: D) -> Result<TuningProfile, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(TuningProfileVisitor)
+    }
+}
+
+/// The sync policy, deciding when flushed files are synced to disk (see
+/// [`crate::storage::v1::fsync`])
+#[repr(u8)]
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SyncPolicy {
+    /// Sync every file before it replaces the previous version
+    Always = 0,
+    /// Sync every file before it replaces the previous version, but sync the rename in the
+    /// background, about once every second
+    EverySec = 1,
+    /// Never sync explicitly and leave it to the operating system
+    Os = 2,
+}
+
+impl SyncPolicy {
+    pub const fn default() -> Self {
+        Self::Always
+    }
+    pub const fn name(&self) -> &'static str {
+        match self {
+            Self::Always => "always",
+            Self::EverySec => "everysec",
+            Self::Os => "os",
+        }
+    }
+    pub const fn from_u8(v: u8) -> Self {
+        match v {
+            0 => Self::Always,
+            1 => Self::EverySec,
+            _ => Self::Os,
+        }
+    }
+}
+
+impl FromStr for SyncPolicy {
+    type Err = ();
+    fn from_str(st: &str) -> Result<SyncPolicy, Self::Err> {
+        match st {
+            "always" => Ok(Self::Always),
+            "everysec" => Ok(Self::EverySec),
+            "os" => Ok(Self::Os),
+            _ => Err(()),
+        }
+    }
+}
+
+struct SyncPolicyVisitor;
+
+impl<'de> Visitor<'de> for SyncPolicyVisitor {
+    type Value = SyncPolicy;
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Expecting a string with the sync policy")
+    }
+    fn visit_str<E>(self, value: &str) -> Result<Self::Value, E>
+    where
+        E: de::Error,
+    {
+        value
+            .parse()
+            .map_err(|_| E::custom(format!("Bad value `{value}` for sync policy")))
+    }
+}
+
+impl<'de> Deserialize<'de> for SyncPolicy {
+    fn deserialize<D>(deserializer: D) -> Result<SyncPolicy, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_str(SyncPolicyVisitor)
+    }
+}
+
 #[derive(Debug, PartialEq, Deserialize)]
 pub struct AuthSettings {
     pub origin_key: Option<AuthkeyWrapper>,
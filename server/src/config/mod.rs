@@ -25,10 +25,11 @@
 */
 
 use {
-    crate::auth::provider::Authkey,
+    crate::auth::provider::{Authkey, AUTHID_SIZE},
     clap::{load_yaml, App},
     core::str::FromStr,
     std::{
+        collections::HashMap,
         env::VarError,
         fs,
         net::{IpAddr, Ipv4Addr},
@@ -48,7 +49,10 @@ mod tests;
 use self::cfgfile::Config as ConfigFile;
 pub use self::definitions::*;
 use self::feedback::{ConfigError, ErrorStack, WarningStack};
-use crate::dbnet::MAXIMUM_CONNECTION_LIMIT;
+use crate::{
+    corestore::export::ExportFormat, dbnet::MAXIMUM_CONNECTION_LIMIT,
+    storage::v1::snapname::NameTemplate,
+};
 
 // server defaults
 const DEFAULT_IPV4: IpAddr = IpAddr::V4(Ipv4Addr::new(127, 0, 0, 1));
@@ -57,6 +61,11 @@ const DEFAULT_PORT: u16 = 2003;
 const DEFAULT_BGSAVE_DURATION: u64 = 120;
 // snapshot defaults
 const DEFAULT_SNAPSHOT_FAILSAFE: bool = true;
+/// What we expect for the snapshot name template (see [`NameTemplate`])
+const TEMPLATE_EXPECTED: &str =
+    "a template like `{hostname}-{date}-{seq}` with a `{time}`, `{timestamp}` or `{seq}`";
+// snapshot sink defaults
+const DEFAULT_S3_REGION: &str = "us-east-1";
 // TLS defaults
 const DEFAULT_SSL_PORT: u16 = 2004;
 
@@ -417,15 +426,20 @@ impl Configset {
         &mut self,
         nproto: impl TryFromConfigSource<ProtocolVersion>,
         nproto_key: StaticStr,
+        nstrict: impl TryFromConfigSource<bool>,
+        nstrict_key: StaticStr,
     ) {
         let mut proto = ProtocolVersion::default();
+        let mut strict = false;
         self.try_mutate(
             nproto,
             &mut proto,
             nproto_key,
             "a protocol version like 2.0 or 1.0",
         );
+        self.try_mutate(nstrict, &mut strict, nstrict_key, "true/false");
         self.cfg.protocol = proto;
+        self.cfg.strict_protocol = strict;
     }
 }
 
@@ -449,6 +463,37 @@ impl Configset {
         self.try_mutate(nart, &mut noart, nart_key, "true/false");
         self.cfg.noart = noart;
     }
+    pub fn server_proxy_protocol(
+        &mut self,
+        nproxy: impl TryFromConfigSource<bool>,
+        nproxy_key: StaticStr,
+    ) {
+        let mut proxy_protocol = false;
+        self.try_mutate(nproxy, &mut proxy_protocol, nproxy_key, "true/false");
+        self.cfg.proxy_protocol = proxy_protocol;
+    }
+    /// This has to be called after the PROXY protocol setting, since it's required by it
+    pub fn server_proxy_trusted(
+        &mut self,
+        ntrusted: impl TryFromConfigSource<TrustedProxies>,
+        ntrusted_key: StaticStr,
+    ) {
+        let mut trusted = TrustedProxies::default();
+        self.try_mutate(
+            ntrusted,
+            &mut trusted,
+            ntrusted_key,
+            "a comma separated list of addresses or networks like `10.0.0.1` or `10.0.0.0/8`",
+        );
+        if self.cfg.proxy_protocol && trusted.nets.is_empty() {
+            // otherwise, any client could claim to be any other client
+            self.estack.push(format!(
+                "The PROXY protocol is enabled, but `{ntrusted_key}` doesn't list any trusted proxies"
+            ));
+            self.cfg.proxy_protocol = false;
+        }
+        self.cfg.proxy_trusted = trusted;
+    }
     pub fn server_maxcon(
         &mut self,
         nmaxcon: impl TryFromConfigSource<usize>,
@@ -464,6 +509,189 @@ impl Configset {
         );
         self.cfg.maxcon = maxcon;
     }
+    pub fn server_maxcon_per_ip(
+        &mut self,
+        nmaxcon: impl TryFromConfigSource<usize>,
+        nmaxcon_key: StaticStr,
+    ) {
+        let mut maxcon = 0;
+        self.try_mutate(
+            nmaxcon,
+            &mut maxcon,
+            nmaxcon_key,
+            "a positive integer (or zero for no limit)",
+        );
+        self.cfg.maxcon_per_ip = maxcon;
+    }
+    pub fn server_query_timeout(
+        &mut self,
+        ntimeout: impl TryFromConfigSource<u64>,
+        ntimeout_key: StaticStr,
+    ) {
+        let mut timeout = 0;
+        self.try_mutate(
+            ntimeout,
+            &mut timeout,
+            ntimeout_key,
+            "a positive integer (or zero for no limit)",
+        );
+        self.cfg.query_timeout = timeout;
+    }
+    pub fn server_max_query_size(
+        &mut self,
+        nsize: impl TryFromConfigSource<SizeBytes>,
+        nsize_key: StaticStr,
+    ) {
+        let mut size = SizeBytes(0);
+        self.try_mutate(
+            nsize,
+            &mut size,
+            nsize_key,
+            "a size like `64m` (or zero for no limit)",
+        );
+        self.cfg.max_query_size = size.0;
+    }
+    pub fn server_flush_workers(
+        &mut self,
+        nworkers: impl TryFromConfigSource<usize>,
+        nworkers_key: StaticStr,
+    ) {
+        // the tuning profile may have set this already
+        let mut workers = self.cfg.flush_workers;
+        self.try_mutate_with_condcheck(
+            nworkers,
+            &mut workers,
+            nworkers_key,
+            "a positive integer greater than zero",
+            |workers| *workers > 0,
+        );
+        self.cfg.flush_workers = workers;
+    }
+    pub fn server_sync(
+        &mut self,
+        nsync: impl TryFromConfigSource<SyncPolicy>,
+        nsync_key: StaticStr,
+    ) {
+        // the tuning profile may have set this already
+        let mut sync = self.cfg.sync;
+        self.try_mutate(
+            nsync,
+            &mut sync,
+            nsync_key,
+            "one of `always`, `everysec` or `os`",
+        );
+        self.cfg.sync = sync;
+    }
+    pub fn server_memory(
+        &mut self,
+        nmaxmemory: impl TryFromConfigSource<SizeBytes>,
+        nmaxmemory_key: StaticStr,
+        neviction: impl TryFromConfigSource<EvictionPolicy>,
+        neviction_key: StaticStr,
+    ) {
+        let mut maxmemory = SizeBytes(0);
+        let mut eviction = EvictionPolicy::default();
+        let has_maxmemory = nmaxmemory.is_present();
+        let has_eviction = neviction.is_present();
+        self.try_mutate_with_condcheck(
+            nmaxmemory,
+            &mut maxmemory,
+            nmaxmemory_key,
+            "a size greater than zero like `512m` or `2g`",
+            |size| size.0 > 0,
+        );
+        self.try_mutate(
+            neviction,
+            &mut eviction,
+            neviction_key,
+            "one of `reject`, `lru` or `lfu`",
+        );
+        if has_maxmemory && maxmemory.0 != 0 {
+            self.cfg.memory = MemoryLimit::Limited {
+                maxmemory: maxmemory.0,
+                eviction,
+            };
+        } else if has_eviction && !has_maxmemory {
+            self.wstack.push(format!(
+                "Specifying `{neviction_key}` is useless when `{nmaxmemory_key}` is not set"
+            ));
+        }
+    }
+    pub fn server_limits(
+        &mut self,
+        nmax_key_size: impl TryFromConfigSource<SizeBytes>,
+        nmax_key_size_key: StaticStr,
+        nmax_value_size: impl TryFromConfigSource<SizeBytes>,
+        nmax_value_size_key: StaticStr,
+    ) {
+        let mut max_key_size = SizeBytes(self.cfg.limits.max_key_size);
+        let mut max_value_size = SizeBytes(self.cfg.limits.max_value_size);
+        self.try_mutate_with_condcheck(
+            nmax_key_size,
+            &mut max_key_size,
+            nmax_key_size_key,
+            "a size greater than zero like `512` or `1k`",
+            |size| size.0 > 0,
+        );
+        self.try_mutate_with_condcheck(
+            nmax_value_size,
+            &mut max_value_size,
+            nmax_value_size_key,
+            "a size greater than zero like `1m` or `64m`",
+            |size| size.0 > 0,
+        );
+        self.cfg.limits = SizeLimitConfig::new(max_key_size.0, max_value_size.0);
+    }
+    pub fn server_default_entity(
+        &mut self,
+        nentity: impl TryFromConfigSource<DefaultEntity>,
+        nentity_key: StaticStr,
+    ) {
+        let mut entity = DefaultEntity::default();
+        self.try_mutate(
+            nentity,
+            &mut entity,
+            nentity_key,
+            "a keyspace like `app` or a table like `app.users`",
+        );
+        self.cfg.default_entity = entity;
+    }
+    pub fn server_unixsock(
+        &mut self,
+        npath: impl TryFromConfigSource<UnixSockConfig>,
+        npath_key: StaticStr,
+    ) {
+        let mut unixsock = UnixSockConfig::default();
+        self.try_mutate(npath, &mut unixsock, npath_key, "the path of a socket file");
+        if cfg!(not(unix)) && unixsock.path().is_some() {
+            self.estack.push(format!(
+                "`{npath_key}` is set, but Unix domain sockets aren't supported on this platform"
+            ));
+            unixsock = UnixSockConfig::Disabled;
+        }
+        self.cfg.unixsock = unixsock;
+    }
+    /// This has to be called after the TLS settings, since secure listeners need them
+    pub fn server_listen(
+        &mut self,
+        naddrs: impl TryFromConfigSource<ListenConfig>,
+        naddrs_key: StaticStr,
+    ) {
+        let mut listen = ListenConfig::default();
+        self.try_mutate(
+            naddrs,
+            &mut listen,
+            naddrs_key,
+            "a comma separated list of addresses like `skyhash://127.0.0.1:2003` or `skyhash-secure://[::1]:2004`",
+        );
+        if self.cfg.ports.insecure_only() && listen.addrs.iter().any(|addr| addr.secure) {
+            self.estack.push(format!(
+                "`{naddrs_key}` has secure addresses, but TLS isn't configured"
+            ));
+            listen = ListenConfig::default();
+        }
+        self.cfg.listen = listen;
+    }
     pub fn server_mode(&mut self, nmode: impl TryFromConfigSource<Modeset>, nmode_key: StaticStr) {
         let mut modeset = Modeset::Dev;
         self.try_mutate(
@@ -476,6 +704,30 @@ impl Configset {
     }
 }
 
+// tuning profile
+impl Configset {
+    /// Set the tuning profile. This sets the defaults for the knobs that the profile covers, so
+    /// it must be run before any of those knobs are set
+    pub fn tuning_profile(
+        &mut self,
+        nprofile: impl TryFromConfigSource<TuningProfile>,
+        nprofile_key: StaticStr,
+    ) {
+        let mut profile = TuningProfile::default();
+        self.try_mutate(
+            nprofile,
+            &mut profile,
+            nprofile_key,
+            "one of `default`, `latency`, `throughput` or `memory`",
+        );
+        let knobs = profile.knobs();
+        self.cfg.profile = profile;
+        self.cfg.bgsave = BGSave::Enabled(knobs.bgsave_every);
+        self.cfg.flush_workers = knobs.flush_workers;
+        self.cfg.sync = knobs.sync;
+    }
+}
+
 // bgsave settings
 impl Configset {
     pub fn bgsave_settings(
@@ -486,7 +738,11 @@ impl Configset {
         nduration_key: StaticStr,
     ) {
         let mut enabled = true;
-        let mut duration = DEFAULT_BGSAVE_DURATION;
+        // the tuning profile may have set this already
+        let mut duration = match self.cfg.bgsave {
+            BGSave::Enabled(every) => every,
+            BGSave::Disabled => DEFAULT_BGSAVE_DURATION,
+        };
         let has_custom_duration = nduration.is_present();
         self.try_mutate(nenabled, &mut enabled, nenabled_key, "true/false");
         self.try_mutate_with_condcheck(
@@ -510,8 +766,124 @@ impl Configset {
     }
 }
 
+// archive settings
+impl Configset {
+    pub fn archive_settings(&mut self, nidle: impl TryFromConfigSource<u64>, nidle_key: StaticStr) {
+        if nidle.is_present() {
+            let mut idle = 0;
+            self.try_mutate_with_condcheck(
+                nidle,
+                &mut idle,
+                nidle_key,
+                "a positive integer greater than zero",
+                |days| *days > 0,
+            );
+            if idle != 0 {
+                self.cfg.archive = ArchivePolicy::Enabled(idle);
+            }
+        }
+    }
+}
+
+// RESP settings
+impl Configset {
+    pub fn resp_settings(&mut self, nport: impl TryFromConfigSource<u16>, nport_key: StaticStr) {
+        if nport.is_present() {
+            let mut port = 0;
+            self.try_mutate_with_condcheck(
+                nport,
+                &mut port,
+                nport_key,
+                "a 16-bit positive integer greater than zero",
+                |port| *port > 0,
+            );
+            if port != 0 {
+                self.cfg.resp = RespConfig::Enabled(port);
+            }
+        }
+    }
+}
+
+// WebSocket settings
+impl Configset {
+    pub fn websocket_settings(
+        &mut self,
+        nport: impl TryFromConfigSource<u16>,
+        nport_key: StaticStr,
+    ) {
+        if nport.is_present() {
+            let mut port = 0;
+            self.try_mutate_with_condcheck(
+                nport,
+                &mut port,
+                nport_key,
+                "a 16-bit positive integer greater than zero",
+                |port| *port > 0,
+            );
+            if port != 0 {
+                self.cfg.websocket = WebSocketConfig::Enabled(port);
+            }
+        }
+    }
+}
+
+// HTTP settings
+impl Configset {
+    pub fn http_settings(&mut self, nport: impl TryFromConfigSource<u16>, nport_key: StaticStr) {
+        if nport.is_present() {
+            let mut port = 0;
+            self.try_mutate_with_condcheck(
+                nport,
+                &mut port,
+                nport_key,
+                "a 16-bit positive integer greater than zero",
+                |port| *port > 0,
+            );
+            if port != 0 {
+                self.cfg.http = HttpConfig::Enabled(port);
+            }
+        }
+    }
+}
+
+// rate limits
+impl Configset {
+    pub fn rate_limit_settings(
+        &mut self,
+        nrate: impl TryFromConfigSource<u64>,
+        nrate_key: StaticStr,
+    ) {
+        let mut rate = 0;
+        self.try_mutate(
+            nrate,
+            &mut rate,
+            nrate_key,
+            "a positive integer (or zero for no limit)",
+        );
+        self.cfg.ratelimit.connection = rate;
+    }
+    pub fn rate_limit_users(&mut self, users: HashMap<String, u64>, users_key: StaticStr) {
+        let mut users: Vec<(String, u64)> = users
+            .into_iter()
+            .filter(|(user, rate)| {
+                if *rate == 0 || user.is_empty() || user.len() > AUTHID_SIZE {
+                    self.estack.push(format!(
+                        "Bad value for `{users_key}.{user}`. Expected a positive integer greater than zero for a valid username"
+                    ));
+                    false
+                } else {
+                    true
+                }
+            })
+            .collect();
+        users.sort();
+        self.cfg.ratelimit.users = users;
+    }
+}
+
 // snapshot settings
 impl Configset {
+    #[allow(clippy::too_many_arguments)]
     pub fn snapshot_settings(
         &mut self,
         nevery: impl TryFromConfigSource<u64>,
@@ -520,6 +892,10 @@ impl Configset {
         natmost_key: StaticStr,
         nfailsafe: impl TryFromConfigSource<bool>,
         nfailsafe_key: StaticStr,
+        nkeepfor: impl TryFromConfigSource<DurationSecs>,
+        nkeepfor_key: StaticStr,
+        ntemplate: impl TryFromConfigSource<NameTemplate>,
+        ntemplate_key: StaticStr,
     ) {
         match (nevery.is_present(), natmost.is_present()) {
             (false, false) => {
@@ -533,6 +909,20 @@ impl Configset {
                         "Specifying `{nfailsafe_key}` is usless when snapshots are disabled"
                     ));
                 }
+                if nkeepfor.is_present() {
+                    let mut _keepfor = DurationSecs(0);
+                    self.try_mutate(nkeepfor, &mut _keepfor, nkeepfor_key, "a duration");
+                    self.wstack.push(format!(
+                        "Specifying `{nkeepfor_key}` is usless when snapshots are disabled"
+                    ));
+                }
+                if ntemplate.is_present() {
+                    let mut _template = NameTemplate::new_default();
+                    self.try_mutate(ntemplate, &mut _template, ntemplate_key, TEMPLATE_EXPECTED);
+                    self.wstack.push(format!(
+                        "Specifying `{ntemplate_key}` is usless when snapshots are disabled"
+                    ));
+                }
             }
             (true, true) => {
                 let mut every = 0;
@@ -552,8 +942,33 @@ impl Configset {
                     "a positive integer. 0 indicates that all snapshots will be kept",
                 );
                 self.try_mutate(nfailsafe, &mut failsafe, nfailsafe_key, "true/false");
-                self.cfg.snapshot =
-                    SnapshotConfig::Enabled(SnapshotPref::new(every, atmost, failsafe));
+                let keep_for = if nkeepfor.is_present() {
+                    let mut keep_for = DurationSecs(0);
+                    self.try_mutate_with_condcheck(
+                        nkeepfor,
+                        &mut keep_for,
+                        nkeepfor_key,
+                        "a duration greater than 0 like `7d`, `12h`, `30m` or `3600s`",
+                        |dur| dur.0 > 0,
+                    );
+                    Some(keep_for.0)
+                } else {
+                    None
+                };
+                let name_template = if ntemplate.is_present() {
+                    let mut template = NameTemplate::new_default();
+                    self.try_mutate(ntemplate, &mut template, ntemplate_key, TEMPLATE_EXPECTED);
+                    Some(template)
+                } else {
+                    None
+                };
+                self.cfg.snapshot = SnapshotConfig::Enabled(SnapshotPref::new(
+                    every,
+                    atmost,
+                    failsafe,
+                    keep_for,
+                    name_template,
+                ));
             }
             (false, true) | (true, false) => {
                 // no changes, but still attempted to change
@@ -566,6 +981,62 @@ impl Configset {
     }
 }
 
+// snapshot sink settings
+impl Configset {
+    #[allow(clippy::too_many_arguments)]
+    pub fn snapshot_sink_settings(
+        &mut self,
+        nendpoint: impl TryFromConfigSource<String>,
+        nendpoint_key: StaticStr,
+        nbucket: impl TryFromConfigSource<String>,
+        nbucket_key: StaticStr,
+        nregion: impl TryFromConfigSource<String>,
+        nregion_key: StaticStr,
+        naccess: impl TryFromConfigSource<String>,
+        naccess_key: StaticStr,
+        nsecret: impl TryFromConfigSource<String>,
+        nsecret_key: StaticStr,
+    ) {
+        let required = [
+            nendpoint.is_present(),
+            nbucket.is_present(),
+            naccess.is_present(),
+            nsecret.is_present(),
+        ];
+        if required.iter().all(|present| *present) {
+            let mut endpoint = String::new();
+            let mut bucket = String::new();
+            let mut region = DEFAULT_S3_REGION.to_owned();
+            let mut access_key = String::new();
+            let mut secret_key = String::new();
+            self.try_mutate_with_condcheck(
+                nendpoint,
+                &mut endpoint,
+                nendpoint_key,
+                "an endpoint like `https://s3.amazonaws.com`",
+                |endpoint| endpoint.starts_with("http://") || endpoint.starts_with("https://"),
+            );
+            self.try_mutate(nbucket, &mut bucket, nbucket_key, "a bucket name");
+            self.try_mutate(nregion, &mut region, nregion_key, "a region name");
+            self.try_mutate(naccess, &mut access_key, naccess_key, "an access key ID");
+            self.try_mutate(nsecret, &mut secret_key, nsecret_key, "a secret access key");
+            self.cfg.snapshot_sink = SnapshotSinkConfig::S3(S3Config {
+                endpoint,
+                bucket,
+                region,
+                access_key,
+                secret_key,
+            });
+        } else if required.iter().any(|present| *present) || nregion.is_present() {
+            self.mutated();
+            self.estack.push(format!(
+                "To ship snapshots to an S3-compatible store, pass values for `{nendpoint_key}`, \
+                `{nbucket_key}`, `{naccess_key}` and `{nsecret_key}`"
+            ));
+        }
+    }
+}
+
 // TLS settings
 #[allow(clippy::too_many_arguments)]
 impl Configset {
@@ -581,6 +1052,8 @@ impl Configset {
         nonly_key: StaticStr,
         npass: impl TryFromConfigSource<OptString>,
         npass_key: StaticStr,
+        nclientca: impl TryFromConfigSource<OptString>,
+        nclientca_key: StaticStr,
     ) {
         match (nkey.is_present(), ncert.is_present()) {
             (true, true) => {
@@ -607,7 +1080,16 @@ impl Configset {
                     "path to TLS cert passphrase",
                 );
 
-                let sslopts = SslOpts::new(key, cert, port, tls_pass.base);
+                // check if client certificates have to be verified
+                let mut clientca = OptString::new_null();
+                self.try_mutate(
+                    nclientca,
+                    &mut clientca,
+                    nclientca_key,
+                    "path to the CA bundle for client certificates",
+                );
+
+                let sslopts = SslOpts::new(key, cert, port, tls_pass.base, clientca.base);
                 // now check if TLS only
                 if tls_only {
                     let host = self.cfg.ports.get_host();
@@ -642,6 +1124,12 @@ impl Configset {
                         "Specifying `{npass_key}` is pointless when TLS is disabled"
                     ));
                 }
+                if nclientca.is_present() {
+                    self.mutated();
+                    self.wstack.push(format!(
+                        "Specifying `{nclientca_key}` is pointless when TLS is disabled"
+                    ));
+                }
             }
         }
     }
@@ -669,6 +1157,31 @@ pub fn get_config() -> Result<ConfigType, ConfigError> {
     let cfg_layout = load_yaml!("../cli.yml");
     let matches = App::from_yaml(cfg_layout).get_matches();
     let restore_file = matches.value_of("restore").map(|v| v.to_string());
+    let task = if matches.is_present("repair") {
+        Some(OfflineTask::Repair)
+    } else if let Some(target) = matches.value_of("import") {
+        // clap has already checked that there's a file, and the format
+        let file = matches.value_of("importfile").unwrap_or_default();
+        let format = matches
+            .value_of("importformat")
+            .and_then(|format| format.parse().ok())
+            .unwrap_or_else(|| ExportFormat::from_path(file));
+        Some(OfflineTask::Import {
+            target: target.to_owned(),
+            file: file.to_owned(),
+            format,
+            continue_on_error: matches.is_present("continueonerror"),
+        })
+    } else {
+        matches.value_of("export").map(|target| {
+            // clap has already checked the format
+            let format = matches
+                .value_of("exportformat")
+                .and_then(|format| format.parse().ok())
+                .unwrap_or(ExportFormat::Json);
+            OfflineTask::Export(target.to_owned(), format)
+        })
+    };
 
     // get config from file
     let cfg_from_file = if let Some(file) = matches.value_of("config") {
@@ -692,12 +1205,13 @@ pub fn get_config() -> Result<ConfigType, ConfigError> {
     if has_conflict {
         return Err(ConfigError::Conflict);
     }
-    if cfg_degree == 0 {
+    let cfg = if cfg_degree == 0 {
         // no configuration, use default
         Ok(ConfigType::new_default(restore_file))
     } else {
         cfg_from_file
             .unwrap_or_else(|| cfg_from_env.and_then(cfg_from_cli))
             .into_result(restore_file)
-    }
+    };
+    cfg.map(|cfg| cfg.with_task(task))
 }
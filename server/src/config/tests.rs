@@ -25,7 +25,12 @@
 */
 
 use {
-    super::{BGSave, Configset, PortConfig, SnapshotConfig, SnapshotPref, SslOpts, DEFAULT_IPV4},
+    super::{
+        ArchivePolicy, BGSave, Configset, DefaultEntity, EvictionPolicy, HttpConfig, ListenAddr,
+        ListenConfig, MemoryLimit, PortConfig, RateLimitConfig, RespConfig, S3Config,
+        SizeLimitConfig, SnapshotConfig, SnapshotPref, SnapshotSinkConfig, SslOpts, SyncPolicy,
+        TrustedProxies, TuningProfile, UnixSockConfig, WebSocketConfig, DEFAULT_IPV4,
+    },
     crate::ROOT_DIR,
     std::fs,
 };
@@ -139,6 +144,74 @@ fn server_noart_fail() {
     assert!(cfgset.is_mutated());
 }
 
+// PROXY protocol
+#[test]
+fn server_proxy_protocol_okay() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_proxy_protocol(Some("true"), "SKY_SYSTEM_PROXY_PROTOCOL");
+    assert!(cfgset.cfg.proxy_protocol);
+    assert!(cfgset.is_okay());
+    assert!(cfgset.is_mutated());
+}
+
+#[test]
+fn server_proxy_protocol_fail() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_proxy_protocol(Some("yes"), "SKY_SYSTEM_PROXY_PROTOCOL");
+    assert!(!cfgset.cfg.proxy_protocol);
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_SYSTEM_PROXY_PROTOCOL`. Expected true/false"
+    );
+    assert!(cfgset.is_mutated());
+}
+
+#[test]
+fn server_proxy_trusted_okay() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_proxy_protocol(Some("true"), "SKY_SYSTEM_PROXY_PROTOCOL");
+    cfgset.server_proxy_trusted(
+        Some("10.0.0.1, 10.1.0.0/16,::1"),
+        "SKY_SYSTEM_PROXY_TRUSTED",
+    );
+    assert!(cfgset.is_okay());
+    assert!(cfgset.cfg.proxy_protocol);
+    let trusted = &cfgset.cfg.proxy_trusted;
+    assert_eq!(trusted.nets.len(), 3);
+    for ip in ["10.0.0.1", "10.1.2.3", "::1", "::ffff:10.1.0.1"] {
+        assert!(trusted.contains(ip.parse().unwrap()), "{ip}");
+    }
+    for ip in ["10.0.0.2", "10.2.0.1", "::2"] {
+        assert!(!trusted.contains(ip.parse().unwrap()), "{ip}");
+    }
+}
+
+#[test]
+fn server_proxy_trusted_fail() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_proxy_trusted(Some("10.0.0.0/33"), "SKY_SYSTEM_PROXY_TRUSTED");
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_SYSTEM_PROXY_TRUSTED`. Expected a comma separated list of addresses or networks like `10.0.0.1` or `10.0.0.0/8`"
+    );
+    assert_eq!(cfgset.cfg.proxy_trusted, TrustedProxies::default());
+}
+
+#[test]
+fn server_proxy_trusted_fail_missing() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_proxy_protocol(Some("true"), "SKY_SYSTEM_PROXY_PROTOCOL");
+    cfgset.server_proxy_trusted(None, "SKY_SYSTEM_PROXY_TRUSTED");
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "The PROXY protocol is enabled, but `SKY_SYSTEM_PROXY_TRUSTED` doesn't list any trusted proxies"
+    );
+    assert!(!cfgset.cfg.proxy_protocol);
+}
+
 #[test]
 fn server_maxcon_okay() {
     let mut cfgset = Configset::new_env();
@@ -161,6 +234,322 @@ fn server_maxcon_fail() {
     assert_eq!(cfgset.cfg.maxcon, 50000);
 }
 
+#[test]
+fn server_maxcon_per_ip_okay() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_maxcon_per_ip(Some("16"), "SKY_SYSTEM_MAXCON_PER_IP");
+    assert!(cfgset.is_mutated());
+    assert!(cfgset.is_okay());
+    assert_eq!(cfgset.cfg.maxcon_per_ip, 16);
+}
+
+#[test]
+fn server_maxcon_per_ip_fail() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_maxcon_per_ip(Some("-1"), "SKY_SYSTEM_MAXCON_PER_IP");
+    assert!(cfgset.is_mutated());
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_SYSTEM_MAXCON_PER_IP`. Expected a positive integer (or zero for no limit)"
+    );
+    assert_eq!(cfgset.cfg.maxcon_per_ip, 0);
+}
+
+#[test]
+fn server_query_timeout_okay() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_query_timeout(Some("2500"), "SKY_SYSTEM_QUERY_TIMEOUT");
+    assert!(cfgset.is_mutated());
+    assert!(cfgset.is_okay());
+    assert_eq!(cfgset.cfg.query_timeout, 2500);
+}
+
+#[test]
+fn server_query_timeout_fail() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_query_timeout(Some("5s"), "SKY_SYSTEM_QUERY_TIMEOUT");
+    assert!(cfgset.is_mutated());
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_SYSTEM_QUERY_TIMEOUT`. Expected a positive integer (or zero for no limit)"
+    );
+    assert_eq!(cfgset.cfg.query_timeout, 0);
+}
+
+#[test]
+fn server_max_query_size_okay() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_max_query_size(Some("64m"), "SKY_SYSTEM_MAX_QUERY_SIZE");
+    assert!(cfgset.is_mutated());
+    assert!(cfgset.is_okay());
+    assert_eq!(cfgset.cfg.max_query_size, 64 * 1024 * 1024);
+}
+
+#[test]
+fn server_max_query_size_fail() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_max_query_size(Some("huge"), "SKY_SYSTEM_MAX_QUERY_SIZE");
+    assert!(cfgset.is_mutated());
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_SYSTEM_MAX_QUERY_SIZE`. Expected a size like `64m` (or zero for no limit)"
+    );
+    assert_eq!(cfgset.cfg.max_query_size, 0);
+}
+
+#[test]
+fn server_flush_workers_okay() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_flush_workers(Some("16"), "SKY_SYSTEM_FLUSH_WORKERS");
+    assert!(cfgset.is_mutated());
+    assert!(cfgset.is_okay());
+    assert_eq!(cfgset.cfg.flush_workers, 16);
+}
+
+#[test]
+fn server_flush_workers_fail() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_flush_workers(Some("0"), "SKY_SYSTEM_FLUSH_WORKERS");
+    assert!(cfgset.is_mutated());
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_SYSTEM_FLUSH_WORKERS`. Expected a positive integer greater than zero"
+    );
+}
+
+#[test]
+fn server_sync_okay() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_sync(Some("everysec"), "SKY_SYSTEM_SYNC");
+    assert!(cfgset.is_mutated());
+    assert!(cfgset.is_okay());
+    assert_eq!(cfgset.cfg.sync, SyncPolicy::EverySec);
+}
+
+#[test]
+fn server_sync_fail() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_sync(Some("never"), "SKY_SYSTEM_SYNC");
+    assert!(cfgset.is_mutated());
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_SYSTEM_SYNC`. Expected one of `always`, `everysec` or `os`"
+    );
+}
+
+#[test]
+fn server_memory_okay() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_memory(
+        Some("512m"),
+        "SKY_SYSTEM_MAXMEMORY",
+        Some("lfu"),
+        "SKY_SYSTEM_EVICTION",
+    );
+    assert!(cfgset.is_mutated());
+    assert!(cfgset.is_okay());
+    assert_eq!(
+        cfgset.cfg.memory,
+        MemoryLimit::Limited {
+            maxmemory: 512 * 1024 * 1024,
+            eviction: EvictionPolicy::Lfu
+        }
+    );
+}
+
+#[test]
+fn server_memory_fail() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_memory(
+        Some("0"),
+        "SKY_SYSTEM_MAXMEMORY",
+        Some("random"),
+        "SKY_SYSTEM_EVICTION",
+    );
+    assert!(cfgset.is_mutated());
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_SYSTEM_MAXMEMORY`. Expected a size greater than zero like `512m` or `2g`"
+    );
+    assert_eq!(
+        cfgset.estack[1],
+        "Bad value for `SKY_SYSTEM_EVICTION`. Expected one of `reject`, `lru` or `lfu`"
+    );
+    assert_eq!(cfgset.cfg.memory, MemoryLimit::Unlimited);
+}
+
+#[test]
+fn server_memory_eviction_without_limit() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_memory(
+        None::<&str>,
+        "SKY_SYSTEM_MAXMEMORY",
+        Some("lru"),
+        "SKY_SYSTEM_EVICTION",
+    );
+    assert!(cfgset.is_okay());
+    assert_eq!(cfgset.cfg.memory, MemoryLimit::Unlimited);
+    assert_eq!(
+        cfgset.wstack[0],
+        "Specifying `SKY_SYSTEM_EVICTION` is useless when `SKY_SYSTEM_MAXMEMORY` is not set"
+    );
+}
+
+#[test]
+fn server_limits_okay() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_limits(
+        Some("1k"),
+        "SKY_SYSTEM_MAX_KEY_SIZE",
+        Some("64m"),
+        "SKY_SYSTEM_MAX_VALUE_SIZE",
+    );
+    assert!(cfgset.is_mutated());
+    assert!(cfgset.is_okay());
+    assert_eq!(
+        cfgset.cfg.limits,
+        SizeLimitConfig::new(1024, 64 * 1024 * 1024)
+    );
+}
+
+#[test]
+fn server_limits_fail() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_limits(
+        Some("0"),
+        "SKY_SYSTEM_MAX_KEY_SIZE",
+        None::<&str>,
+        "SKY_SYSTEM_MAX_VALUE_SIZE",
+    );
+    assert!(cfgset.is_mutated());
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_SYSTEM_MAX_KEY_SIZE`. Expected a size greater than zero like `512` or `1k`"
+    );
+    assert_eq!(cfgset.cfg.limits, SizeLimitConfig::default());
+}
+
+#[test]
+fn server_default_entity_okay() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_default_entity(Some("app.users"), "SKY_SYSTEM_DEFAULT_ENTITY");
+    assert!(cfgset.is_mutated());
+    assert!(cfgset.is_okay());
+    assert_eq!(cfgset.cfg.default_entity.name(), "app.users");
+    let mut cfgset = Configset::new_env();
+    cfgset.server_default_entity(Some("app"), "SKY_SYSTEM_DEFAULT_ENTITY");
+    assert!(cfgset.is_okay());
+    assert_eq!(cfgset.cfg.default_entity.table, None);
+    assert_eq!(cfgset.cfg.default_entity.name(), "app");
+}
+
+#[test]
+fn server_default_entity_fail() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_default_entity(Some("app..users"), "SKY_SYSTEM_DEFAULT_ENTITY");
+    assert!(cfgset.is_mutated());
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_SYSTEM_DEFAULT_ENTITY`. Expected a keyspace like `app` or a table like `app.users`"
+    );
+    assert!(cfgset.cfg.default_entity.is_default());
+    assert_eq!(DefaultEntity::default().name(), "default.default");
+}
+
+#[cfg(unix)]
+#[test]
+fn server_unixsock_okay() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_unixsock(Some("/run/skyd.sock"), "SKY_SYSTEM_UNIXSOCK");
+    assert!(cfgset.is_mutated());
+    assert!(cfgset.is_okay());
+    assert_eq!(cfgset.cfg.unixsock.path(), Some("/run/skyd.sock"));
+}
+
+#[test]
+fn server_unixsock_fail() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_unixsock(Some(""), "SKY_SYSTEM_UNIXSOCK");
+    assert!(cfgset.is_mutated());
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_SYSTEM_UNIXSOCK`. Expected the path of a socket file"
+    );
+    assert_eq!(cfgset.cfg.unixsock, UnixSockConfig::Disabled);
+}
+
+// tuning profile
+#[test]
+fn tuning_profile_okay() {
+    let mut cfgset = Configset::new_env();
+    cfgset.tuning_profile(Some("throughput"), "SKY_SYSTEM_PROFILE");
+    assert!(cfgset.is_mutated());
+    assert!(cfgset.is_okay());
+    assert_eq!(cfgset.cfg.profile, TuningProfile::Throughput);
+    assert_eq!(cfgset.cfg.bgsave, BGSave::Enabled(300));
+    assert_eq!(cfgset.cfg.flush_workers, 8);
+    assert_eq!(cfgset.cfg.sync, SyncPolicy::EverySec);
+}
+
+#[test]
+fn tuning_profile_explicit_settings_override() {
+    let mut cfgset = Configset::new_env();
+    cfgset.tuning_profile(Some("memory"), "SKY_SYSTEM_PROFILE");
+    cfgset.server_flush_workers(Some("3"), "SKY_SYSTEM_FLUSH_WORKERS");
+    // absent values, like an unset environment variable
+    cfgset.bgsave_settings(
+        None::<&str>,
+        "SKY_BGSAVE_ENABLED",
+        None::<&str>,
+        "SKY_BGSAVE_DURATION",
+    );
+    assert!(cfgset.is_okay());
+    assert_eq!(cfgset.cfg.profile, TuningProfile::Memory);
+    assert_eq!(cfgset.cfg.flush_workers, 3);
+    // not set explicitly, so this comes from the profile
+    assert_eq!(cfgset.cfg.bgsave, BGSave::Enabled(120));
+    assert_eq!(
+        cfgset.cfg.effective_settings(),
+        vec![
+            "server.profile = memory",
+            "bgsave.enabled = true",
+            "bgsave.every = 120",
+            "server.maxcon = 50000",
+            "server.maxcon_per_ip = 0",
+            "server.flush_workers = 3",
+            "server.sync = always",
+            "server.maxmemory = 0",
+            "server.eviction = reject",
+            "server.max_key_size = 0",
+            "server.max_value_size = 0",
+            "server.default_entity = default.default",
+            "server.buffer_size = 2048",
+            "server.shards_per_core = 4",
+        ]
+    );
+}
+
+#[test]
+fn tuning_profile_fail() {
+    let mut cfgset = Configset::new_env();
+    cfgset.tuning_profile(Some("fast"), "SKY_SYSTEM_PROFILE");
+    assert!(cfgset.is_mutated());
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_SYSTEM_PROFILE`. Expected one of `default`, `latency`, `throughput` or `memory`"
+    );
+}
+
 // bgsave settings
 #[test]
 fn bgsave_okay() {
@@ -205,12 +594,16 @@ fn snapshot_okay() {
         "SKY_SNAPSHOT_ATMOST",
         Some("false"),
         "SKY_SNAPSHOT_FAILSAFE",
+        None,
+        "SKY_SNAPSHOT_KEEP_FOR",
+        None,
+        "SKY_SNAPSHOT_NAME_TEMPLATE",
     );
     assert!(cfgset.is_mutated());
     assert!(cfgset.is_okay());
     assert_eq!(
         cfgset.cfg.snapshot,
-        SnapshotConfig::Enabled(SnapshotPref::new(3600, 0, false))
+        SnapshotConfig::Enabled(SnapshotPref::new(3600, 0, false, None, None))
     );
 }
 
@@ -224,6 +617,10 @@ fn snapshot_fail() {
         "SKY_SNAPSHOT_ATMOST",
         Some("falsee"),
         "SKY_SNAPSHOT_FAILSAFE",
+        None,
+        "SKY_SNAPSHOT_KEEP_FOR",
+        None,
+        "SKY_SNAPSHOT_NAME_TEMPLATE",
     );
     assert!(cfgset.is_mutated());
     assert!(!cfgset.is_okay());
@@ -233,7 +630,94 @@ fn snapshot_fail() {
     );
     assert_eq!(
         cfgset.cfg.snapshot,
-        SnapshotConfig::Enabled(SnapshotPref::new(3600, 0, true))
+        SnapshotConfig::Enabled(SnapshotPref::new(3600, 0, true, None, None))
+    );
+}
+
+#[test]
+fn snapshot_keep_for() {
+    let mut cfgset = Configset::new_env();
+    cfgset.snapshot_settings(
+        Some("3600"),
+        "SKY_SNAPSHOT_EVERY",
+        Some("0"),
+        "SKY_SNAPSHOT_ATMOST",
+        None,
+        "SKY_SNAPSHOT_FAILSAFE",
+        Some("7d"),
+        "SKY_SNAPSHOT_KEEP_FOR",
+        None,
+        "SKY_SNAPSHOT_NAME_TEMPLATE",
+    );
+    assert!(cfgset.is_okay());
+    assert_eq!(
+        cfgset.cfg.snapshot,
+        SnapshotConfig::Enabled(SnapshotPref::new(3600, 0, true, Some(7 * 24 * 3600), None))
+    );
+    let mut cfgset = Configset::new_env();
+    cfgset.snapshot_settings(
+        Some("3600"),
+        "SKY_SNAPSHOT_EVERY",
+        Some("0"),
+        "SKY_SNAPSHOT_ATMOST",
+        None,
+        "SKY_SNAPSHOT_FAILSAFE",
+        Some("7 days"),
+        "SKY_SNAPSHOT_KEEP_FOR",
+        None,
+        "SKY_SNAPSHOT_NAME_TEMPLATE",
+    );
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_SNAPSHOT_KEEP_FOR`. Expected a duration greater than 0 like `7d`, `12h`, `30m` or `3600s`"
+    );
+}
+
+#[test]
+fn snapshot_name_template() {
+    let mut cfgset = Configset::new_env();
+    cfgset.snapshot_settings(
+        Some("3600"),
+        "SKY_SNAPSHOT_EVERY",
+        Some("4"),
+        "SKY_SNAPSHOT_ATMOST",
+        None,
+        "SKY_SNAPSHOT_FAILSAFE",
+        None,
+        "SKY_SNAPSHOT_KEEP_FOR",
+        Some("{hostname}-{date}-{seq}"),
+        "SKY_SNAPSHOT_NAME_TEMPLATE",
+    );
+    assert!(cfgset.is_okay());
+    assert_eq!(
+        cfgset.cfg.snapshot,
+        SnapshotConfig::Enabled(SnapshotPref::new(
+            3600,
+            4,
+            true,
+            None,
+            Some("{hostname}-{date}-{seq}".parse().unwrap())
+        ))
+    );
+    // two snapshots on the same day would collide
+    let mut cfgset = Configset::new_env();
+    cfgset.snapshot_settings(
+        Some("3600"),
+        "SKY_SNAPSHOT_EVERY",
+        Some("4"),
+        "SKY_SNAPSHOT_ATMOST",
+        None,
+        "SKY_SNAPSHOT_FAILSAFE",
+        None,
+        "SKY_SNAPSHOT_KEEP_FOR",
+        Some("{hostname}-{date}"),
+        "SKY_SNAPSHOT_NAME_TEMPLATE",
+    );
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_SNAPSHOT_NAME_TEMPLATE`. Expected a template like `{hostname}-{date}-{seq}` with a `{time}`, `{timestamp}` or `{seq}`"
     );
 }
 
@@ -247,6 +731,10 @@ fn snapshot_fail_with_missing_required_values() {
         "SKY_SNAPSHOT_ATMOST",
         None,
         "SKY_SNAPSHOT_FAILSAFE",
+        None,
+        "SKY_SNAPSHOT_KEEP_FOR",
+        None,
+        "SKY_SNAPSHOT_NAME_TEMPLATE",
     );
     assert!(cfgset.is_mutated());
     assert!(!cfgset.is_okay());
@@ -272,6 +760,8 @@ fn tls_settings_okay() {
         "SKY_TLS_ONLY",
         None,
         "SKY_TLS_PASSIN",
+        None,
+        "SKY_TLS_CLIENTCA",
     );
     assert!(cfg.is_mutated());
     assert!(cfg.is_okay());
@@ -282,6 +772,7 @@ fn tls_settings_okay() {
             "cert.pem".to_owned(),
             2005,
             None,
+            None,
         ));
         pf
     });
@@ -301,6 +792,8 @@ fn tls_settings_fail() {
         "SKY_TLS_ONLY",
         None,
         "SKY_TLS_PASSIN",
+        None,
+        "SKY_TLS_CLIENTCA",
     );
     assert!(cfg.is_mutated());
     assert!(!cfg.is_okay());
@@ -311,6 +804,7 @@ fn tls_settings_fail() {
             "cert.pem".to_owned(),
             2004,
             None,
+            None,
         ));
         pf
     });
@@ -330,12 +824,304 @@ fn tls_settings_fail_with_missing_required_values() {
         "SKY_TLS_ONLY",
         None,
         "SKY_TLS_PASSIN",
+        None,
+        "SKY_TLS_CLIENTCA",
     );
     assert!(cfg.is_mutated());
     assert!(!cfg.is_okay());
     assert_eq!(cfg.cfg.ports, PortConfig::default());
 }
 
+#[test]
+fn tls_settings_with_client_ca() {
+    let mut cfg = Configset::new_env();
+    cfg.tls_settings(
+        Some("key.pem"),
+        "SKY_TLS_KEY",
+        Some("cert.pem"),
+        "SKY_TLS_CERT",
+        Some("2005"),
+        "SKY_TLS_PORT",
+        Some("false"),
+        "SKY_TLS_ONLY",
+        None,
+        "SKY_TLS_PASSIN",
+        Some("ca.pem"),
+        "SKY_TLS_CLIENTCA",
+    );
+    assert!(cfg.is_mutated());
+    assert!(cfg.is_okay());
+    assert_eq!(cfg.cfg.ports, {
+        let mut pf = PortConfig::default();
+        pf.upgrade_to_tls(SslOpts::new(
+            "key.pem".to_owned(),
+            "cert.pem".to_owned(),
+            2005,
+            None,
+            Some("ca.pem".to_owned()),
+        ));
+        pf
+    });
+}
+
+// archive settings
+#[test]
+fn archive_okay() {
+    let mut cfgset = Configset::new_env();
+    cfgset.archive_settings(Some("30"), "SKY_ARCHIVE_IDLE_DAYS");
+    assert!(cfgset.is_mutated());
+    assert!(cfgset.is_okay());
+    assert_eq!(cfgset.cfg.archive, ArchivePolicy::Enabled(30));
+}
+
+#[test]
+fn archive_fail() {
+    let mut cfgset = Configset::new_env();
+    cfgset.archive_settings(Some("0"), "SKY_ARCHIVE_IDLE_DAYS");
+    assert!(cfgset.is_mutated());
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_ARCHIVE_IDLE_DAYS`. Expected a positive integer greater than zero"
+    );
+    assert_eq!(cfgset.cfg.archive, ArchivePolicy::Disabled);
+}
+
+// RESP settings
+#[test]
+fn resp_okay() {
+    let mut cfgset = Configset::new_env();
+    cfgset.resp_settings(Some("6379"), "SKY_RESP_PORT");
+    assert!(cfgset.is_mutated());
+    assert!(cfgset.is_okay());
+    assert_eq!(cfgset.cfg.resp, RespConfig::Enabled(6379));
+    assert_eq!(cfgset.cfg.resp.port(), Some(6379));
+}
+
+#[test]
+fn resp_fail() {
+    let mut cfgset = Configset::new_env();
+    cfgset.resp_settings(Some("0"), "SKY_RESP_PORT");
+    assert!(cfgset.is_mutated());
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_RESP_PORT`. Expected a 16-bit positive integer greater than zero"
+    );
+    assert_eq!(cfgset.cfg.resp, RespConfig::Disabled);
+}
+
+// WebSocket settings
+#[test]
+fn websocket_okay() {
+    let mut cfgset = Configset::new_env();
+    cfgset.websocket_settings(Some("2005"), "SKY_WEBSOCKET_PORT");
+    assert!(cfgset.is_mutated());
+    assert!(cfgset.is_okay());
+    assert_eq!(cfgset.cfg.websocket, WebSocketConfig::Enabled(2005));
+}
+
+#[test]
+fn websocket_fail() {
+    let mut cfgset = Configset::new_env();
+    cfgset.websocket_settings(Some("ws"), "SKY_WEBSOCKET_PORT");
+    assert!(cfgset.is_mutated());
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_WEBSOCKET_PORT`. Expected a 16-bit positive integer greater than zero"
+    );
+    assert_eq!(cfgset.cfg.websocket, WebSocketConfig::Disabled);
+}
+
+// HTTP settings
+#[test]
+fn http_okay() {
+    let mut cfgset = Configset::new_env();
+    cfgset.http_settings(Some("2006"), "SKY_HTTP_PORT");
+    assert!(cfgset.is_mutated());
+    assert!(cfgset.is_okay());
+    assert_eq!(cfgset.cfg.http, HttpConfig::Enabled(2006));
+}
+
+#[test]
+fn http_fail() {
+    let mut cfgset = Configset::new_env();
+    cfgset.http_settings(Some("0"), "SKY_HTTP_PORT");
+    assert!(cfgset.is_mutated());
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_HTTP_PORT`. Expected a 16-bit positive integer greater than zero"
+    );
+    assert_eq!(cfgset.cfg.http, HttpConfig::Disabled);
+}
+
+// rate limits
+#[test]
+fn rate_limit_okay() {
+    let mut cfgset = Configset::new_env();
+    cfgset.rate_limit_settings(Some("1000"), "SKY_RATE_LIMIT");
+    assert!(cfgset.is_mutated());
+    assert!(cfgset.is_okay());
+    assert_eq!(cfgset.cfg.ratelimit.connection, 1000);
+}
+
+#[test]
+fn rate_limit_fail() {
+    let mut cfgset = Configset::new_env();
+    cfgset.rate_limit_settings(Some("fast"), "SKY_RATE_LIMIT");
+    assert!(cfgset.is_mutated());
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_RATE_LIMIT`. Expected a positive integer (or zero for no limit)"
+    );
+    assert_eq!(cfgset.cfg.ratelimit, RateLimitConfig::default());
+}
+
+#[test]
+fn rate_limit_users() {
+    let mut cfgset = Configset::new_file();
+    let users = [("beta", 10), ("alpha", 5), ("nobody", 0)]
+        .into_iter()
+        .map(|(user, rate)| (user.to_owned(), rate))
+        .collect();
+    cfgset.rate_limit_users(users, "ratelimit.users");
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `ratelimit.users.nobody`. Expected a positive integer greater than zero for a valid username"
+    );
+    assert_eq!(
+        cfgset.cfg.ratelimit.users,
+        vec![("alpha".to_owned(), 5), ("beta".to_owned(), 10)]
+    );
+}
+
+// additional listeners
+#[test]
+fn listen_okay() {
+    let mut cfgset = Configset::new_env();
+    cfgset.tls_settings(
+        Some("key.pem"),
+        "SKY_TLS_KEY",
+        Some("cert.pem"),
+        "SKY_TLS_CERT",
+        None,
+        "SKY_TLS_PORT",
+        None,
+        "SKY_TLS_ONLY",
+        None,
+        "SKY_TLS_PASSIN",
+        None,
+        "SKY_TLS_CLIENTCA",
+    );
+    cfgset.server_listen(
+        Some("10.0.0.1:2003, skyhash://10.0.0.1:2005,skyhash-secure://[::1]:2004"),
+        "SKY_SYSTEM_LISTEN",
+    );
+    assert!(cfgset.is_mutated());
+    assert!(cfgset.is_okay());
+    assert_eq!(
+        cfgset.cfg.listen.addrs,
+        vec![
+            ListenAddr {
+                addr: "10.0.0.1:2003".parse().unwrap(),
+                secure: false
+            },
+            ListenAddr {
+                addr: "10.0.0.1:2005".parse().unwrap(),
+                secure: false
+            },
+            ListenAddr {
+                addr: "[::1]:2004".parse().unwrap(),
+                secure: true
+            },
+        ]
+    );
+    assert_eq!(
+        cfgset.cfg.listen.addrs[2].to_string(),
+        "skyhash-secure://[::1]:2004"
+    );
+}
+
+#[test]
+fn listen_fail() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_listen(Some("skyhash://10.0.0.1"), "SKY_SYSTEM_LISTEN");
+    assert!(cfgset.is_mutated());
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "Bad value for `SKY_SYSTEM_LISTEN`. Expected a comma separated list of addresses like `skyhash://127.0.0.1:2003` or `skyhash-secure://[::1]:2004`"
+    );
+    assert_eq!(cfgset.cfg.listen, ListenConfig::default());
+}
+
+#[test]
+fn listen_fail_secure_without_tls() {
+    let mut cfgset = Configset::new_env();
+    cfgset.server_listen(Some("skyhash-secure://10.0.0.1:2004"), "SKY_SYSTEM_LISTEN");
+    assert!(cfgset.is_mutated());
+    assert!(!cfgset.is_okay());
+    assert_eq!(
+        cfgset.estack[0],
+        "`SKY_SYSTEM_LISTEN` has secure addresses, but TLS isn't configured"
+    );
+    assert_eq!(cfgset.cfg.listen, ListenConfig::default());
+}
+
+// snapshot sink settings
+#[test]
+fn snapshot_sink_okay() {
+    let mut cfgset = Configset::new_env();
+    cfgset.snapshot_sink_settings(
+        Some("http://127.0.0.1:9000"),
+        "SKY_SNAPSHOT_S3_ENDPOINT",
+        Some("backups"),
+        "SKY_SNAPSHOT_S3_BUCKET",
+        None,
+        "SKY_SNAPSHOT_S3_REGION",
+        Some("minio"),
+        "SKY_SNAPSHOT_S3_ACCESS_KEY",
+        Some("minio123"),
+        "SKY_SNAPSHOT_S3_SECRET_KEY",
+    );
+    assert!(cfgset.is_mutated());
+    assert!(cfgset.is_okay());
+    assert_eq!(
+        cfgset.cfg.snapshot_sink,
+        SnapshotSinkConfig::S3(S3Config {
+            endpoint: "http://127.0.0.1:9000".to_owned(),
+            bucket: "backups".to_owned(),
+            region: "us-east-1".to_owned(),
+            access_key: "minio".to_owned(),
+            secret_key: "minio123".to_owned(),
+        })
+    );
+}
+
+#[test]
+fn snapshot_sink_fail_with_missing_required_values() {
+    let mut cfgset = Configset::new_env();
+    cfgset.snapshot_sink_settings(
+        Some("http://127.0.0.1:9000"),
+        "SKY_SNAPSHOT_S3_ENDPOINT",
+        Some("backups"),
+        "SKY_SNAPSHOT_S3_BUCKET",
+        None,
+        "SKY_SNAPSHOT_S3_REGION",
+        None,
+        "SKY_SNAPSHOT_S3_ACCESS_KEY",
+        None,
+        "SKY_SNAPSHOT_S3_SECRET_KEY",
+    );
+    assert!(cfgset.is_mutated());
+    assert!(!cfgset.is_okay());
+    assert_eq!(cfgset.cfg.snapshot_sink, SnapshotSinkConfig::Local);
+}
+
 /// Gets a `toml` file from `WORKSPACEROOT/examples/config-files`
 fn get_toml_from_examples_dir(filename: &str) -> String {
     let path = format!("{ROOT_DIR}examples/config-files/{filename}");
@@ -346,10 +1132,14 @@ mod cfg_file_tests {
     use super::get_toml_from_examples_dir;
     use crate::config::AuthkeyWrapper;
     use crate::config::{
-        cfgfile, AuthSettings, BGSave, Configset, ConfigurationSet, Modeset, PortConfig,
-        ProtocolVersion, SnapshotConfig, SnapshotPref, SslOpts, DEFAULT_IPV4, DEFAULT_PORT,
+        cfgfile, ArchivePolicy, AuthSettings, BGSave, Configset, ConfigurationSet, DefaultEntity,
+        HttpConfig, ListenConfig, MemoryLimit, Modeset, PortConfig, ProtocolVersion,
+        RateLimitConfig, RespConfig, SizeLimitConfig, SnapshotConfig, SnapshotPref,
+        SnapshotSinkConfig, SslOpts, SyncPolicy, TrustedProxies, TuningProfile, UnixSockConfig,
+        WebSocketConfig, DEFAULT_IPV4, DEFAULT_PORT,
     };
     use crate::dbnet::MAXIMUM_CONNECTION_LIMIT;
+    use crate::storage::v1::flush::DEFAULT_FLUSH_WORKERS;
     use std::net::{IpAddr, Ipv6Addr};
 
     fn cfgset_from_toml_str(file: String) -> Result<Configset, toml::de::Error> {
@@ -366,7 +1156,7 @@ mod cfg_file_tests {
         assert!(cfg_from_file.is_okay());
         // expected
         let mut expected = ConfigurationSet::default();
-        expected.snapshot = SnapshotConfig::Enabled(SnapshotPref::new(3600, 4, true));
+        expected.snapshot = SnapshotConfig::Enabled(SnapshotPref::new(3600, 4, true, None, None));
         expected.ports = PortConfig::new_secure_only(
             crate::config::DEFAULT_IPV4,
             SslOpts::new(
@@ -374,10 +1164,13 @@ mod cfg_file_tests {
                 "/path/to/chain.pem".to_owned(),
                 2004,
                 Some("/path/to/cert/passphrase.txt".to_owned()),
+                None,
             ),
         );
         expected.auth.origin_key =
             Some(AuthkeyWrapper::try_new(crate::TEST_AUTH_ORIGIN_KEY).unwrap());
+        expected.archive = ArchivePolicy::Enabled(30);
+        expected.flush_workers = 8;
         // check
         assert_eq!(cfg_from_file.cfg, expected);
     }
@@ -404,6 +1197,26 @@ mod cfg_file_tests {
                 mode: Modeset::Dev,
                 auth: AuthSettings::default(),
                 protocol: ProtocolVersion::default(),
+                strict_protocol: false,
+                archive: ArchivePolicy::default(),
+                snapshot_sink: SnapshotSinkConfig::default(),
+                flush_workers: DEFAULT_FLUSH_WORKERS,
+                profile: TuningProfile::default(),
+                sync: SyncPolicy::default(),
+                memory: MemoryLimit::default(),
+                limits: SizeLimitConfig::default(),
+                default_entity: DefaultEntity::default(),
+                resp: RespConfig::default(),
+                unixsock: UnixSockConfig::default(),
+                websocket: WebSocketConfig::default(),
+                http: HttpConfig::default(),
+                maxcon_per_ip: 0,
+                ratelimit: RateLimitConfig::default(),
+                listen: ListenConfig::default(),
+                proxy_protocol: false,
+                proxy_trusted: TrustedProxies::default(),
+                query_timeout: 0,
+                max_query_size: 0,
             }
         );
     }
@@ -426,6 +1239,26 @@ mod cfg_file_tests {
                 mode: Modeset::Dev,
                 auth: AuthSettings::default(),
                 protocol: ProtocolVersion::default(),
+                strict_protocol: false,
+                archive: ArchivePolicy::default(),
+                snapshot_sink: SnapshotSinkConfig::default(),
+                flush_workers: DEFAULT_FLUSH_WORKERS,
+                profile: TuningProfile::default(),
+                sync: SyncPolicy::default(),
+                memory: MemoryLimit::default(),
+                limits: SizeLimitConfig::default(),
+                default_entity: DefaultEntity::default(),
+                resp: RespConfig::default(),
+                unixsock: UnixSockConfig::default(),
+                websocket: WebSocketConfig::default(),
+                http: HttpConfig::default(),
+                maxcon_per_ip: 0,
+                ratelimit: RateLimitConfig::default(),
+                listen: ListenConfig::default(),
+                proxy_protocol: false,
+                proxy_trusted: TrustedProxies::default(),
+                query_timeout: 0,
+                max_query_size: 0,
             }
         );
     }
@@ -439,20 +1272,41 @@ mod cfg_file_tests {
             ConfigurationSet::new(
                 false,
                 BGSave::default(),
-                SnapshotConfig::Enabled(SnapshotPref::new(3600, 4, true)),
+                SnapshotConfig::Enabled(SnapshotPref::new(3600, 4, true, None, None)),
                 PortConfig::new_secure_only(
                     DEFAULT_IPV4,
                     SslOpts::new(
                         "/path/to/keyfile.pem".into(),
                         "/path/to/chain.pem".into(),
                         2004,
-                        Some("/path/to/cert/passphrase.txt".to_owned())
+                        Some("/path/to/cert/passphrase.txt".to_owned()),
+                        None
                     )
                 ),
                 MAXIMUM_CONNECTION_LIMIT,
                 Modeset::Dev,
                 AuthSettings::new(AuthkeyWrapper::try_new(crate::TEST_AUTH_ORIGIN_KEY).unwrap()),
-                ProtocolVersion::default()
+                ProtocolVersion::default(),
+                false,
+                ArchivePolicy::Enabled(30),
+                SnapshotSinkConfig::default(),
+                8,
+                TuningProfile::default(),
+                SyncPolicy::default(),
+                MemoryLimit::default(),
+                SizeLimitConfig::default(),
+                DefaultEntity::default(),
+                RespConfig::default(),
+                UnixSockConfig::default(),
+                WebSocketConfig::default(),
+                HttpConfig::default(),
+                0,
+                RateLimitConfig::default(),
+                ListenConfig::default(),
+                false,
+                TrustedProxies::default(),
+                0,
+                0
             )
         );
     }
@@ -479,6 +1333,26 @@ mod cfg_file_tests {
                 mode: Modeset::Dev,
                 auth: AuthSettings::default(),
                 protocol: ProtocolVersion::default(),
+                strict_protocol: false,
+                archive: ArchivePolicy::default(),
+                snapshot_sink: SnapshotSinkConfig::default(),
+                flush_workers: DEFAULT_FLUSH_WORKERS,
+                profile: TuningProfile::default(),
+                sync: SyncPolicy::default(),
+                memory: MemoryLimit::default(),
+                limits: SizeLimitConfig::default(),
+                default_entity: DefaultEntity::default(),
+                resp: RespConfig::default(),
+                unixsock: UnixSockConfig::default(),
+                websocket: WebSocketConfig::default(),
+                http: HttpConfig::default(),
+                maxcon_per_ip: 0,
+                ratelimit: RateLimitConfig::default(),
+                listen: ListenConfig::default(),
+                proxy_protocol: false,
+                proxy_trusted: TrustedProxies::default(),
+                query_timeout: 0,
+                max_query_size: 0,
             }
         );
     }
@@ -502,6 +1376,26 @@ mod cfg_file_tests {
                 mode: Modeset::Dev,
                 auth: AuthSettings::default(),
                 protocol: ProtocolVersion::default(),
+                strict_protocol: false,
+                archive: ArchivePolicy::default(),
+                snapshot_sink: SnapshotSinkConfig::default(),
+                flush_workers: DEFAULT_FLUSH_WORKERS,
+                profile: TuningProfile::default(),
+                sync: SyncPolicy::default(),
+                memory: MemoryLimit::default(),
+                limits: SizeLimitConfig::default(),
+                default_entity: DefaultEntity::default(),
+                resp: RespConfig::default(),
+                unixsock: UnixSockConfig::default(),
+                websocket: WebSocketConfig::default(),
+                http: HttpConfig::default(),
+                maxcon_per_ip: 0,
+                ratelimit: RateLimitConfig::default(),
+                listen: ListenConfig::default(),
+                proxy_protocol: false,
+                proxy_trusted: TrustedProxies::default(),
+                query_timeout: 0,
+                max_query_size: 0,
             }
         )
     }
@@ -525,6 +1419,26 @@ mod cfg_file_tests {
                 mode: Modeset::Dev,
                 auth: AuthSettings::default(),
                 protocol: ProtocolVersion::default(),
+                strict_protocol: false,
+                archive: ArchivePolicy::default(),
+                snapshot_sink: SnapshotSinkConfig::default(),
+                flush_workers: DEFAULT_FLUSH_WORKERS,
+                profile: TuningProfile::default(),
+                sync: SyncPolicy::default(),
+                memory: MemoryLimit::default(),
+                limits: SizeLimitConfig::default(),
+                default_entity: DefaultEntity::default(),
+                resp: RespConfig::default(),
+                unixsock: UnixSockConfig::default(),
+                websocket: WebSocketConfig::default(),
+                http: HttpConfig::default(),
+                maxcon_per_ip: 0,
+                ratelimit: RateLimitConfig::default(),
+                listen: ListenConfig::default(),
+                proxy_protocol: false,
+                proxy_trusted: TrustedProxies::default(),
+                query_timeout: 0,
+                max_query_size: 0,
             }
         )
     }
@@ -536,7 +1450,7 @@ mod cfg_file_tests {
         assert_eq!(
             cfg.cfg,
             ConfigurationSet {
-                snapshot: SnapshotConfig::Enabled(SnapshotPref::new(3600, 4, true)),
+                snapshot: SnapshotConfig::Enabled(SnapshotPref::new(3600, 4, true, None, None)),
                 bgsave: BGSave::default(),
                 noart: false,
                 ports: PortConfig::default(),
@@ -544,6 +1458,26 @@ mod cfg_file_tests {
                 mode: Modeset::Dev,
                 auth: AuthSettings::default(),
                 protocol: ProtocolVersion::default(),
+                strict_protocol: false,
+                archive: ArchivePolicy::default(),
+                snapshot_sink: SnapshotSinkConfig::default(),
+                flush_workers: DEFAULT_FLUSH_WORKERS,
+                profile: TuningProfile::default(),
+                sync: SyncPolicy::default(),
+                memory: MemoryLimit::default(),
+                limits: SizeLimitConfig::default(),
+                default_entity: DefaultEntity::default(),
+                resp: RespConfig::default(),
+                unixsock: UnixSockConfig::default(),
+                websocket: WebSocketConfig::default(),
+                http: HttpConfig::default(),
+                maxcon_per_ip: 0,
+                ratelimit: RateLimitConfig::default(),
+                listen: ListenConfig::default(),
+                proxy_protocol: false,
+                proxy_trusted: TrustedProxies::default(),
+                query_timeout: 0,
+                max_query_size: 0,
             }
         );
     }
@@ -575,6 +1509,85 @@ mod cli_arg_tests {
         assert!(ret.is_okay());
     }
     #[test]
+    fn cli_args_strict_protocol() {
+        let cfg_layout = load_yaml!("../cli.yml");
+        let matches = App::from_yaml(cfg_layout).get_matches_from(["skyd", "--strict-protocol"]);
+        let ret = cfgcli::parse_cli_args(matches);
+        assert!(ret.cfg.strict_protocol);
+        assert!(ret.is_mutated());
+        assert!(ret.is_okay());
+    }
+    #[test]
+    fn cli_args_repair() {
+        let cfg_layout = load_yaml!("../cli.yml");
+        let matches = App::from_yaml(cfg_layout).get_matches_from(["skyd", "--repair"]);
+        assert!(matches.is_present("repair"));
+        let ret = cfgcli::parse_cli_args(matches);
+        assert!(!ret.is_mutated());
+        assert!(ret.is_okay());
+        // repairing and restoring at the same time makes no sense
+        let cli_args = ["skyd", "--repair", "--restore", "/some/restore/path"];
+        assert!(App::from_yaml(cfg_layout)
+            .get_matches_from_safe(cli_args)
+            .is_err());
+    }
+    #[test]
+    fn cli_args_export() {
+        let cfg_layout = load_yaml!("../cli.yml");
+        let cli_args = ["skyd", "--export", "ks:tbl", "--export-format", "csv"];
+        let matches = App::from_yaml(cfg_layout).get_matches_from(cli_args);
+        assert_eq!(matches.value_of("export"), Some("ks:tbl"));
+        assert_eq!(matches.value_of("exportformat"), Some("csv"));
+        let ret = cfgcli::parse_cli_args(matches);
+        assert!(!ret.is_mutated());
+        // unknown formats are rejected, and so is a format without an export
+        for cli_args in [
+            &["skyd", "--export", "ks:tbl", "--export-format", "xml"][..],
+            &["skyd", "--export-format", "csv"],
+        ] {
+            assert!(App::from_yaml(cfg_layout)
+                .get_matches_from_safe(cli_args)
+                .is_err());
+        }
+    }
+    #[test]
+    fn cli_args_import() {
+        let cfg_layout = load_yaml!("../cli.yml");
+        let cli_args = [
+            "skyd",
+            "--import",
+            "ks:tbl",
+            "--import-file",
+            "/tmp/data.csv",
+            "--continue-on-error",
+        ];
+        let matches = App::from_yaml(cfg_layout).get_matches_from(cli_args);
+        assert_eq!(matches.value_of("import"), Some("ks:tbl"));
+        assert_eq!(matches.value_of("importfile"), Some("/tmp/data.csv"));
+        assert!(matches.is_present("continueonerror"));
+        let ret = cfgcli::parse_cli_args(matches);
+        assert!(!ret.is_mutated());
+        // an import needs a file, and the other flags need an import
+        for cli_args in [
+            &["skyd", "--import", "ks:tbl"][..],
+            &["skyd", "--import-file", "/tmp/data.csv"],
+            &["skyd", "--continue-on-error"],
+            &[
+                "skyd",
+                "--import",
+                "ks:tbl",
+                "--import-file",
+                "f",
+                "--export",
+                "ks",
+            ],
+        ] {
+            assert!(App::from_yaml(cfg_layout)
+                .get_matches_from_safe(cli_args)
+                .is_err());
+        }
+    }
+    #[test]
     fn cli_args_fail() {
         let cfg_layout = load_yaml!("../cli.yml");
         let cli_args = ["skyd", "--port", "port2003"];
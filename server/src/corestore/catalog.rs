@@ -0,0 +1,213 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # The entity catalog
+//!
+//! The `system:entities` table records the metadata of every table: when it was created, its
+//! model bytemark, roughly how many rows it has and when its keyspace was last flushed. Tables
+//! are recorded as they're created (or renamed), and BGSAVE refreshes the row counts and flush
+//! times (along with recording tables that were created in some other way, like a restore, and
+//! forgetting dropped tables). The catalog is persisted like any other system table and
+//! `inspect metadata <entity>` reports what it holds for a table.
+
+use {
+    crate::corestore::{htable::Coremap, memstore::Memstore, SharedSlice},
+    std::{collections::HashSet, sync::Arc},
+};
+
+/// The entity catalog. The keys are `<keyspace>:<table>`
+pub type EntityCatalog = Arc<Coremap<SharedSlice, SharedSlice>>;
+
+/// What the catalog knows about a table
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct EntityRecord {
+    /// When the table was created (or first seen), in seconds since the epoch
+    pub created: u64,
+    /// The model bytemark of the table
+    pub model_code: u8,
+    /// The number of rows in the table as of the last refresh
+    pub rows: u64,
+    /// When the keyspace of the table was last flushed (or loaded), in seconds since the epoch.
+    /// Zero for volatile tables
+    pub last_flush: u64,
+}
+
+impl EntityRecord {
+    const SIZE: usize = 25;
+    fn encode(&self) -> Vec<u8> {
+        let mut ret = Vec::with_capacity(Self::SIZE);
+        ret.extend_from_slice(&self.created.to_le_bytes());
+        ret.push(self.model_code);
+        ret.extend_from_slice(&self.rows.to_le_bytes());
+        ret.extend_from_slice(&self.last_flush.to_le_bytes());
+        ret
+    }
+    fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() != Self::SIZE {
+            return None;
+        }
+        let field = |at: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&data[at..at + 8]);
+            u64::from_le_bytes(bytes)
+        };
+        Some(Self {
+            created: field(0),
+            model_code: data[8],
+            rows: field(9),
+            last_flush: field(17),
+        })
+    }
+    /// Render the record as `key = value` lines
+    pub fn render(&self) -> Vec<String> {
+        vec![
+            format!("created = {}", self.created),
+            format!("model = {}", self.model_code),
+            format!("rows = {}", self.rows),
+            format!("last_flush = {}", self.last_flush),
+        ]
+    }
+}
+
+fn entity_key(ksid: &[u8], tblid: &[u8]) -> SharedSlice {
+    let mut key = ksid.to_vec();
+    key.push(b':');
+    key.extend_from_slice(tblid);
+    SharedSlice::from(key)
+}
+
+/// Returns the record of a table, if there is one
+pub fn get(catalog: &EntityCatalog, ksid: &[u8], tblid: &[u8]) -> Option<EntityRecord> {
+    catalog
+        .get(&entity_key(ksid, tblid))
+        .and_then(|record| EntityRecord::decode(record.value()))
+}
+
+fn put(catalog: &EntityCatalog, ksid: &[u8], tblid: &[u8], record: EntityRecord) {
+    catalog.upsert(entity_key(ksid, tblid), SharedSlice::from(record.encode()))
+}
+
+/// Record a table that was just created
+pub fn record_created(
+    catalog: &EntityCatalog,
+    ksid: &[u8],
+    tblid: &[u8],
+    model_code: u8,
+    now: u64,
+) {
+    let record = EntityRecord {
+        created: now,
+        model_code,
+        ..Default::default()
+    };
+    self::put(catalog, ksid, tblid, record)
+}
+
+/// Move the record of a table that was renamed (if it has one)
+pub fn record_renamed(catalog: &EntityCatalog, ksid: &[u8], old: &[u8], new: &[u8]) {
+    if let Some((_, record)) = catalog.remove(&entity_key(ksid, old)) {
+        catalog.upsert(entity_key(ksid, new), record);
+    }
+}
+
+/// Forget a table that was dropped
+pub fn record_dropped(catalog: &EntityCatalog, ksid: &[u8], tblid: &[u8]) {
+    catalog.true_if_removed(&entity_key(ksid, tblid));
+}
+
+/// Bring the catalog in line with the tables in `store`: record the current row count and flush
+/// time of every table (recording tables that aren't in the catalog as created `now`) and forget
+/// the tables that no longer exist
+pub fn refresh(catalog: &EntityCatalog, store: &Memstore, now: u64) {
+    let mut live = HashSet::new();
+    for ks in store.keyspaces.iter() {
+        let last_flush = ks.value().last_flushed();
+        for table in ks.value().tables.iter() {
+            let (ksid, tblid) = (ks.key().as_ref(), table.key().as_ref());
+            let created = self::get(catalog, ksid, tblid).map_or(now, |record| record.created);
+            let record = EntityRecord {
+                created,
+                model_code: table.value().get_model_code(),
+                rows: table.value().count() as u64,
+                last_flush: if table.value().is_volatile() {
+                    0
+                } else {
+                    last_flush
+                },
+            };
+            self::put(catalog, ksid, tblid, record);
+            live.insert(entity_key(ksid, tblid));
+        }
+    }
+    let dropped: Vec<SharedSlice> = catalog
+        .iter()
+        .map(|record| record.key().clone())
+        .filter(|key| !live.contains(key))
+        .collect();
+    dropped.iter().for_each(|key| {
+        catalog.true_if_removed(key);
+    });
+}
+
+#[test]
+fn test_entity_catalog() {
+    use crate::corestore::{
+        memstore::{ObjectID, DEFAULT},
+        table::Table,
+    };
+    let catalog: EntityCatalog = Arc::new(Coremap::new());
+    let store = Memstore::new_default();
+    let ks = store.get_keyspace_atomic_ref(&DEFAULT).unwrap();
+    let tbl = unsafe { ObjectID::from_slice("apps") };
+    assert!(ks.create_table(tbl.clone(), Table::new_default_kve()));
+    self::record_created(&catalog, b"default", b"apps", 0, 100);
+    self::record_renamed(&catalog, b"default", b"apps", b"apps2");
+    assert!(self::get(&catalog, b"default", b"apps").is_none());
+    self::record_renamed(&catalog, b"default", b"apps2", b"apps");
+    self::record_created(&catalog, b"default", b"gone", 0, 100);
+    self::record_dropped(&catalog, b"default", b"gone");
+    assert!(self::get(&catalog, b"default", b"gone").is_none());
+    ks.get_table_atomic_ref(&tbl)
+        .unwrap()
+        .get_kvstore()
+        .unwrap()
+        .set("hello".into(), "world".into())
+        .unwrap();
+    // a stale record of a dropped table
+    self::record_created(&catalog, b"default", b"gone", 0, 100);
+    self::refresh(&catalog, &store, 200);
+    let record = self::get(&catalog, b"default", b"apps").unwrap();
+    assert_eq!(record.created, 100);
+    assert_eq!(record.rows, 1);
+    assert_eq!(record.last_flush, ks.last_flushed());
+    // the default table was only seen now
+    assert_eq!(
+        self::get(&catalog, b"default", b"default").unwrap().created,
+        200
+    );
+    assert!(self::get(&catalog, b"default", b"gone").is_none());
+    assert_eq!(EntityRecord::decode(&record.encode()), Some(record));
+}
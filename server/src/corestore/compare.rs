@@ -0,0 +1,346 @@
+/*
+ * Created on Thu Nov 03 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Table comparison
+//!
+//! Compares the contents of a table against a baseline table (for example, the same table in a
+//! snapshot) and counts the keys that were added, removed or changed.
+//!
+//! The keys are spread over [`DIGEST_SHARDS`] shards by a hash that is the same for every
+//! table (unlike the shards of the map itself, which are seeded per map). Every shard gets an
+//! order-independent digest of its entries, so only the shards whose digests differ need to be
+//! compared key by key.
+
+use {
+    crate::{
+        blueql::Entity,
+        corestore::{
+            memstore::DdlError,
+            table::{DataModel, Table},
+        },
+        IoResult,
+    },
+    std::{
+        collections::{hash_map::DefaultHasher, HashMap},
+        hash::{Hash, Hasher},
+    },
+};
+
+/// The number of digest shards
+const DIGEST_SHARDS: usize = 64;
+/// The maximum number of differing keys reported
+const SAMPLE_KEYS: usize = 10;
+
+/// What a table is compared against
+pub enum Baseline<'a> {
+    /// Another table
+    Table(&'a Entity),
+    /// The same table in the snapshot with this name
+    Snapshot(String),
+}
+
+/// An error that occurred while comparing tables
+#[derive(Debug)]
+pub enum CompareError {
+    /// The tables can't be compared
+    Ddl(DdlError),
+    /// Reading archived values failed
+    Io(std::io::Error),
+}
+
+impl From<DdlError> for CompareError {
+    fn from(e: DdlError) -> Self {
+        Self::Ddl(e)
+    }
+}
+
+impl From<std::io::Error> for CompareError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+/// How a key differs from the baseline
+pub enum DiffKind {
+    /// The key isn't in the baseline
+    Added,
+    /// The key is only in the baseline
+    Removed,
+    /// The key has a different value in the baseline
+    Changed,
+}
+
+impl DiffKind {
+    const fn name(&self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Removed => "removed",
+            Self::Changed => "changed",
+        }
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+/// The differences between a table and its baseline
+pub struct TableDiff {
+    /// keys that aren't in the baseline
+    pub added: u64,
+    /// keys that are only in the baseline
+    pub removed: u64,
+    /// keys with a different value in the baseline
+    pub changed: u64,
+    /// the number of shards that were skipped because their digests matched
+    pub identical_shards: usize,
+    /// at most [`SAMPLE_KEYS`] of the differing keys
+    pub sample: Vec<(DiffKind, Box<[u8]>)>,
+}
+
+impl TableDiff {
+    fn record(&mut self, kind: DiffKind, key: &[u8]) {
+        match kind {
+            DiffKind::Added => self.added += 1,
+            DiffKind::Removed => self.removed += 1,
+            DiffKind::Changed => self.changed += 1,
+        }
+        if self.sample.len() < SAMPLE_KEYS {
+            self.sample.push((kind, key.into()));
+        }
+    }
+    /// Render the counts followed by the sampled keys
+    pub fn render(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("added = {}", self.added),
+            format!("removed = {}", self.removed),
+            format!("changed = {}", self.changed),
+            format!(
+                "identical_shards = {}/{DIGEST_SHARDS}",
+                self.identical_shards
+            ),
+        ];
+        lines.extend(
+            self.sample
+                .iter()
+                .map(|(kind, key)| format!("{}: {}", kind.name(), String::from_utf8_lossy(key))),
+        );
+        lines
+    }
+}
+
+fn hash_of<T: Hash + ?Sized>(item: &T) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    item.hash(&mut hasher);
+    hasher.finish()
+}
+
+fn shard_of(key: &[u8]) -> usize {
+    (hash_of(key) % DIGEST_SHARDS as u64) as usize
+}
+
+/// Calls `f` with every key in the table and a hash of its value
+fn for_each_entry(table: &Table, mut f: impl FnMut(&[u8], u64)) -> IoResult<()> {
+    match table.get_model_ref() {
+        DataModel::KV(kve) => {
+            // freezing the archive stops values from being archived while we look. values that
+            // are faulted in meanwhile are seen in the archive
+            let mut archive = kve.archive().freeze();
+            kve.get_inner_ref()
+                .iter()
+                .filter(|kv| !archive.contains(kv.key()))
+                .for_each(|kv| f(kv.key(), hash_of(kv.value().as_ref())));
+            archive.for_each(|key, value| {
+                f(key, hash_of(value));
+                Ok(())
+            })
+        }
+        DataModel::KVExtListmap(kvl) => {
+            kvl.get_inner_ref().iter().for_each(|kv| {
+                let list = kv.value().read();
+                let mut hasher = DefaultHasher::new();
+                list.len().hash(&mut hasher);
+                list.iter().for_each(|item| item.as_ref().hash(&mut hasher));
+                f(kv.key(), hasher.finish())
+            });
+            Ok(())
+        }
+        DataModel::KVExtMap(kvm) => {
+            kvm.get_inner_ref().iter().for_each(|kv| {
+                // the fields aren't ordered, so their hashes are combined in a way that isn't
+                // either
+                let map = kv.value().read();
+                let digest = map.iter().fold(map.len() as u64, |digest, (field, value)| {
+                    digest.wrapping_add(hash_of(&(field.as_ref(), value.as_ref())))
+                });
+                f(kv.key(), digest)
+            });
+            Ok(())
+        }
+        DataModel::KVExtSet(kvs) => {
+            kvs.get_inner_ref().iter().for_each(|kv| {
+                // just like the fields of a map, the members aren't ordered
+                let set = kv.value().read();
+                let digest = set.iter().fold(set.len() as u64, |digest, member| {
+                    digest.wrapping_add(hash_of(member.as_ref()))
+                });
+                f(kv.key(), digest)
+            });
+            Ok(())
+        }
+        DataModel::KVExtSortedSet(kvz) => {
+            kvz.get_inner_ref().iter().for_each(|kv| {
+                // members are always iterated in order, so the scores can be hashed as we go
+                let zset = kv.value().read();
+                let mut hasher = DefaultHasher::new();
+                zset.len().hash(&mut hasher);
+                zset.iter().for_each(|(member, score)| {
+                    member.as_ref().hash(&mut hasher);
+                    score.to_bits().hash(&mut hasher);
+                });
+                f(kv.key(), hasher.finish())
+            });
+            Ok(())
+        }
+        DataModel::KVExtDocument(kvd) => {
+            let mut encoded = Vec::new();
+            kvd.get_inner_ref().iter().for_each(|kv| {
+                // documents with the same fields in a different order are different
+                encoded.clear();
+                kv.value().read().encode(&mut encoded);
+                f(kv.key(), hash_of(encoded.as_slice()))
+            });
+            Ok(())
+        }
+    }
+}
+
+/// Returns the (entry count, digest) of every shard
+fn digests(table: &Table) -> IoResult<Vec<(u64, u64)>> {
+    let mut digests = vec![(0u64, 0u64); DIGEST_SHARDS];
+    self::for_each_entry(table, |key, value| {
+        let (count, digest) = &mut digests[shard_of(key)];
+        *count += 1;
+        *digest = digest.wrapping_add(hash_of(&(key, value)));
+    })?;
+    Ok(digests)
+}
+
+/// Compare `table` against `baseline`. Both tables must use the same data model
+pub fn compare(table: &Table, baseline: &Table) -> Result<TableDiff, CompareError> {
+    let same_model = matches!(
+        (table.get_model_ref(), baseline.get_model_ref()),
+        (DataModel::KV(_), DataModel::KV(_))
+            | (DataModel::KVExtListmap(_), DataModel::KVExtListmap(_))
+            | (DataModel::KVExtMap(_), DataModel::KVExtMap(_))
+            | (DataModel::KVExtSet(_), DataModel::KVExtSet(_))
+            | (DataModel::KVExtSortedSet(_), DataModel::KVExtSortedSet(_))
+            | (DataModel::KVExtDocument(_), DataModel::KVExtDocument(_))
+    );
+    if !same_model {
+        return Err(DdlError::WrongModel.into());
+    }
+    let differing: Vec<bool> = self::digests(table)?
+        .into_iter()
+        .zip(self::digests(baseline)?)
+        .map(|(ours, theirs)| ours != theirs)
+        .collect();
+    let mut diff = TableDiff {
+        identical_shards: differing.iter().filter(|differs| !**differs).count(),
+        ..Default::default()
+    };
+    if diff.identical_shards == DIGEST_SHARDS {
+        return Ok(diff);
+    }
+    // only hold on to the entries of the shards that differ
+    let mut theirs: HashMap<Box<[u8]>, u64> = HashMap::new();
+    self::for_each_entry(baseline, |key, value| {
+        if differing[shard_of(key)] {
+            theirs.insert(key.into(), value);
+        }
+    })?;
+    self::for_each_entry(table, |key, value| {
+        if differing[shard_of(key)] {
+            match theirs.remove(key) {
+                None => diff.record(DiffKind::Added, key),
+                Some(their_value) if their_value != value => diff.record(DiffKind::Changed, key),
+                Some(_) => {}
+            }
+        }
+    })?;
+    theirs
+        .keys()
+        .for_each(|key| diff.record(DiffKind::Removed, key));
+    Ok(diff)
+}
+
+#[test]
+fn test_compare_tables() {
+    let table = Table::new_default_kve();
+    let baseline = Table::new_default_kve();
+    for i in 0..100 {
+        let kv = format!("key{i}");
+        table
+            .get_kvstore()
+            .unwrap()
+            .set(kv.as_str().into(), kv.as_str().into())
+            .unwrap();
+        baseline
+            .get_kvstore()
+            .unwrap()
+            .set(kv.as_str().into(), kv.as_str().into())
+            .unwrap();
+    }
+    let diff = compare(&table, &baseline).unwrap();
+    assert_eq!(
+        diff,
+        TableDiff {
+            identical_shards: DIGEST_SHARDS,
+            ..Default::default()
+        }
+    );
+    let kve = table.get_kvstore().unwrap();
+    kve.set("new".into(), "value".into()).unwrap();
+    kve.remove("key1").unwrap();
+    kve.update("key2".into(), "changed".into()).unwrap();
+    let diff = compare(&table, &baseline).unwrap();
+    assert_eq!((diff.added, diff.removed, diff.changed), (1, 1, 1));
+    assert!(diff.identical_shards >= DIGEST_SHARDS - 3);
+    let mut sample = diff.sample.clone();
+    sample.sort_by_key(|(kind, _)| kind.name());
+    assert_eq!(
+        sample,
+        vec![
+            (DiffKind::Added, b"new".to_vec().into_boxed_slice()),
+            (DiffKind::Changed, b"key2".to_vec().into_boxed_slice()),
+            (DiffKind::Removed, b"key1".to_vec().into_boxed_slice()),
+        ]
+    );
+    // different models can't be compared
+    let listmap = Table::from_model_code(4, false).unwrap();
+    assert!(matches!(
+        compare(&table, &listmap),
+        Err(CompareError::Ddl(DdlError::WrongModel))
+    ));
+}
@@ -0,0 +1,494 @@
+/*
+ * Created on Sat Nov 05 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Exports
+//!
+//! Dumps a table or a keyspace to newline-delimited JSON or CSV under [`DIR_EXPORTS`], for
+//! analytics pipelines and ad-hoc inspection. Every record holds the table (as `ks:table`), the
+//! key and the value. Keys and values of `str` columns are written as is, while those of
+//! `binstr` columns are written in (standard) base64, so that every export is valid UTF-8.
+//! The value of a list (or a set) is a JSON array of its elements, the value of a map is a JSON
+//! object and the value of a sorted set is a JSON object of its members (in order) along with
+//! their scores (in CSV too, as a quoted field). Infinite scores are written as strings. The
+//! value of a document is the document itself.
+
+use {
+    crate::{
+        corestore::{
+            memstore::{DdlError, Memstore, ObjectID, SYSTEM},
+            table::{DataModel, Table},
+            SharedSlice,
+        },
+        storage::v1::interface::DIR_EXPORTS,
+        IoResult,
+    },
+    chrono::prelude::Utc,
+    std::{
+        borrow::Cow,
+        fs::{self, File},
+        io::{BufWriter, Write},
+        str::FromStr,
+        sync::Arc,
+    },
+};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+/// The format of an export
+pub enum ExportFormat {
+    /// Newline-delimited JSON
+    Json,
+    /// CSV with a header
+    Csv,
+}
+
+impl ExportFormat {
+    pub const fn extension(&self) -> &'static str {
+        match self {
+            Self::Json => "ndjson",
+            Self::Csv => "csv",
+        }
+    }
+    pub fn from_bytes(format: &[u8]) -> Option<Self> {
+        if format.eq_ignore_ascii_case(b"json") {
+            Some(Self::Json)
+        } else if format.eq_ignore_ascii_case(b"csv") {
+            Some(Self::Csv)
+        } else {
+            None
+        }
+    }
+    /// Guess the format of a file from its extension, defaulting to JSON
+    pub fn from_path(path: &str) -> Self {
+        if path.to_ascii_lowercase().ends_with(".csv") {
+            Self::Csv
+        } else {
+            Self::Json
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = ();
+    fn from_str(format: &str) -> Result<Self, Self::Err> {
+        Self::from_bytes(format.as_bytes()).ok_or(())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+/// What is exported
+pub enum ExportTarget {
+    /// Every table in a keyspace
+    Keyspace(ObjectID),
+    /// A single table
+    Table(ObjectID, ObjectID),
+}
+
+impl ExportTarget {
+    /// Parse `<keyspace>` or `<keyspace>:<table>`
+    pub fn parse(target: &str) -> Option<Self> {
+        let (ksid, tblid) = match target.split_once(':') {
+            Some((ksid, tblid)) => (ksid, Some(tblid)),
+            None => (target, None),
+        };
+        let ksid = ObjectID::try_from_slice(ksid).filter(|ksid| !ksid.is_empty())?;
+        match tblid {
+            Some(tblid) => {
+                let tblid = ObjectID::try_from_slice(tblid).filter(|tblid| !tblid.is_empty())?;
+                Some(Self::Table(ksid, tblid))
+            }
+            None => Some(Self::Keyspace(ksid)),
+        }
+    }
+}
+
+/// An error that occurred while exporting
+#[derive(Debug)]
+pub enum ExportError {
+    /// The target can't be exported
+    Ddl(DdlError),
+    /// Writing the export (or reading archived values) failed
+    Io(std::io::Error),
+}
+
+impl From<DdlError> for ExportError {
+    fn from(e: DdlError) -> Self {
+        Self::Ddl(e)
+    }
+}
+
+impl From<std::io::Error> for ExportError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Export `target` from the store and return the path to the export
+pub fn export(
+    store: &Memstore,
+    target: &ExportTarget,
+    format: ExportFormat,
+) -> Result<String, ExportError> {
+    let ksid = match target {
+        ExportTarget::Keyspace(ksid) | ExportTarget::Table(ksid, _) => ksid,
+    };
+    if ksid.eq(&SYSTEM) {
+        return Err(DdlError::ProtectedObject.into());
+    }
+    let ks = store
+        .get_keyspace_atomic_ref(ksid)
+        .ok_or(DdlError::ObjectNotFound)?;
+    let ks_name = unsafe { ksid.as_str() };
+    let (stem, tables): (String, Vec<(ObjectID, Arc<Table>)>) = match target {
+        ExportTarget::Keyspace(_) => {
+            let mut tables: Vec<_> = ks
+                .tables
+                .iter()
+                .map(|kv| (kv.key().clone(), kv.value().clone()))
+                .collect();
+            tables.sort_by(|(a, _), (b, _)| a.cmp(b));
+            (ks_name.to_owned(), tables)
+        }
+        ExportTarget::Table(_, tblid) => {
+            let table = ks
+                .get_table_atomic_ref(tblid)
+                .ok_or(DdlError::ObjectNotFound)?;
+            let stem = format!("{ks_name}.{tbl}", tbl = unsafe { tblid.as_str() });
+            (stem, vec![(tblid.clone(), table)])
+        }
+    };
+    fs::create_dir_all(DIR_EXPORTS)?;
+    let path = format!(
+        "{DIR_EXPORTS}/{stem}-{time}.{ext}",
+        time = Utc::now().format("%Y%m%d-%H%M%S"),
+        ext = format.extension()
+    );
+    // write to a temporary file first, so that a failed export never leaves a partial file
+    let tmp = format!("{path}_");
+    let mut file = BufWriter::new(File::create(&tmp)?);
+    if format == ExportFormat::Csv {
+        file.write_all(b"table,key,value\n")?;
+    }
+    for (tblid, table) in tables {
+        let name = format!("{ks_name}:{tbl}", tbl = unsafe { tblid.as_str() });
+        self::export_table(&mut file, format, &name, &table)?;
+    }
+    file.flush()?;
+    drop(file);
+    fs::rename(&tmp, &path)?;
+    Ok(path)
+}
+
+/// Write the records of `table` (called `name`) to `w`
+pub fn export_table<W: Write>(
+    w: &mut W,
+    format: ExportFormat,
+    name: &str,
+    table: &Table,
+) -> IoResult<()> {
+    match table.get_model_ref() {
+        DataModel::KV(kve) => {
+            let (key_is_str, value_is_str) = kve.get_encoding_tuple();
+            // freezing the archive stops values from being archived while we look. values that
+            // are faulted in meanwhile are written from the archive
+            let mut archive = kve.archive().freeze();
+            for kv in kve
+                .get_inner_ref()
+                .iter()
+                .filter(|kv| !archive.contains(kv.key()))
+            {
+                let key = text(kv.key(), key_is_str);
+                let value = text(kv.value(), value_is_str);
+                write_record(w, format, name, &key, &value, false)?;
+            }
+            archive.for_each(|key, value| {
+                let (key, value) = (text(key, key_is_str), text(value, value_is_str));
+                write_record(w, format, name, &key, &value, false)
+            })
+        }
+        DataModel::KVExtListmap(kvl) => {
+            let (key_is_str, value_is_str) = kvl.get_encoding_tuple();
+            for kv in kvl.get_inner_ref().iter() {
+                let list = json_array(kv.value().read().iter(), value_is_str);
+                let key = text(kv.key(), key_is_str);
+                write_record(w, format, name, &key, &list, true)?;
+            }
+            Ok(())
+        }
+        DataModel::KVExtMap(kvm) => {
+            let (key_is_str, value_is_str) = kvm.get_encoding_tuple();
+            for kv in kvm.get_inner_ref().iter() {
+                // sort the fields so that exports of the same data are the same
+                let mut fields: Vec<_> = kv
+                    .value()
+                    .read()
+                    .iter()
+                    .map(|(f, v)| (text(f, value_is_str).into_owned(), v.clone()))
+                    .collect();
+                fields.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+                let mut map = String::from("{");
+                for (i, (field, value)) in fields.iter().enumerate() {
+                    if i != 0 {
+                        map.push(',');
+                    }
+                    json_string(&mut map, field);
+                    map.push(':');
+                    json_string(&mut map, &text(value, value_is_str));
+                }
+                map.push('}');
+                let key = text(kv.key(), key_is_str);
+                write_record(w, format, name, &key, &map, true)?;
+            }
+            Ok(())
+        }
+        DataModel::KVExtSet(kvs) => {
+            let (key_is_str, value_is_str) = kvs.get_encoding_tuple();
+            for kv in kvs.get_inner_ref().iter() {
+                // sort the members so that exports of the same data are the same
+                let mut members: Vec<SharedSlice> = kv.value().read().iter().cloned().collect();
+                members.sort_unstable_by(|a, b| a.as_slice().cmp(b.as_slice()));
+                let set = json_array(members.iter(), value_is_str);
+                let key = text(kv.key(), key_is_str);
+                write_record(w, format, name, &key, &set, true)?;
+            }
+            Ok(())
+        }
+        DataModel::KVExtSortedSet(kvz) => {
+            let (key_is_str, value_is_str) = kvz.get_encoding_tuple();
+            for kv in kvz.get_inner_ref().iter() {
+                let mut zset = String::from("{");
+                for (i, (member, score)) in kv.value().read().iter().enumerate() {
+                    if i != 0 {
+                        zset.push(',');
+                    }
+                    json_string(&mut zset, &text(member, value_is_str));
+                    zset.push(':');
+                    if score.is_finite() {
+                        zset.push_str(&score.to_string());
+                    } else {
+                        json_string(&mut zset, &score.to_string());
+                    }
+                }
+                zset.push('}');
+                let key = text(kv.key(), key_is_str);
+                write_record(w, format, name, &key, &zset, true)?;
+            }
+            Ok(())
+        }
+        DataModel::KVExtDocument(kvd) => {
+            let key_is_str = kvd.is_key_encoded();
+            for kv in kvd.get_inner_ref().iter() {
+                let doc = kv.value().read().render();
+                let key = text(kv.key(), key_is_str);
+                write_record(w, format, name, &key, &doc, true)?;
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Returns the text for a key or value; `str` data (which is valid UTF-8) is used as is, while
+/// anything else is encoded in base64
+fn text(data: &[u8], is_str: bool) -> Cow<'_, str> {
+    if is_str {
+        String::from_utf8_lossy(data)
+    } else {
+        Cow::Owned(base64::encode(data))
+    }
+}
+
+/// Write a record. If `value_is_json` is set, the value is already a JSON value
+fn write_record<W: Write>(
+    w: &mut W,
+    format: ExportFormat,
+    table: &str,
+    key: &str,
+    value: &str,
+    value_is_json: bool,
+) -> IoResult<()> {
+    let mut record = String::with_capacity(table.len() + key.len() + value.len() + 32);
+    match format {
+        ExportFormat::Json => {
+            record.push_str("{\"table\":");
+            json_string(&mut record, table);
+            record.push_str(",\"key\":");
+            json_string(&mut record, key);
+            record.push_str(",\"value\":");
+            if value_is_json {
+                record.push_str(value);
+            } else {
+                json_string(&mut record, value);
+            }
+            record.push('}');
+        }
+        ExportFormat::Csv => {
+            csv_field(&mut record, table);
+            record.push(',');
+            csv_field(&mut record, key);
+            record.push(',');
+            csv_field(&mut record, value);
+        }
+    }
+    record.push('\n');
+    w.write_all(record.as_bytes())
+}
+
+/// Push `s` as a quoted JSON string
+pub(crate) fn json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+/// Returns a JSON array of `elements`
+fn json_array<'a>(elements: impl Iterator<Item = &'a SharedSlice>, is_str: bool) -> String {
+    let mut array = String::from("[");
+    for (i, element) in elements.enumerate() {
+        if i != 0 {
+            array.push(',');
+        }
+        json_string(&mut array, &text(element, is_str));
+    }
+    array.push(']');
+    array
+}
+
+/// Push `s` as a CSV field, quoting it if needed (RFC 4180)
+fn csv_field(out: &mut String, s: &str) {
+    if s.contains([',', '"', '\n', '\r']) {
+        out.push('"');
+        out.push_str(&s.replace('"', "\"\""));
+        out.push('"');
+    } else {
+        out.push_str(s);
+    }
+}
+
+#[test]
+fn test_export_table() {
+    use crate::{
+        corestore::htable::Coremap,
+        kvengine::document::{Json, Path},
+    };
+    let table = Table::new_pure_kve_with_data(Coremap::new(), false, true, false);
+    let kve = table.get_kvstore().unwrap();
+    kve.set("hello".into(), SharedSlice::from(&[0xFF, 0x00][..]))
+        .unwrap();
+    let mut out = Vec::new();
+    export_table(&mut out, ExportFormat::Json, "ks:tbl", &table).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "{\"table\":\"ks:tbl\",\"key\":\"hello\",\"value\":\"/wA=\"}\n"
+    );
+    let table = Table::new_kve_listmap_with_data(Coremap::new(), false, true, true);
+    if let DataModel::KVExtListmap(kvl) = table.get_model_ref() {
+        kvl.add_list("my,list".into()).unwrap();
+        let list = kvl.get("my,list".as_bytes()).unwrap().unwrap();
+        list.write().push("say \"hi\"".into());
+        list.write().push("line\nbreak".into());
+    }
+    let mut out = Vec::new();
+    export_table(&mut out, ExportFormat::Csv, "ks:lists", &table).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "ks:lists,\"my,list\",\"[\"\"say \\\"\"hi\\\"\"\"\",\"\"line\\nbreak\"\"]\"\n"
+    );
+    let table = Table::new_kve_map_with_data(Coremap::new(), false, true, true);
+    if let DataModel::KVExtMap(kvm) = table.get_model_ref() {
+        let fields = vec![("b".into(), "2".into()), ("a".into(), "1".into())];
+        kvm.map_set("user".into(), fields).unwrap();
+    }
+    let mut out = Vec::new();
+    export_table(&mut out, ExportFormat::Json, "ks:maps", &table).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "{\"table\":\"ks:maps\",\"key\":\"user\",\"value\":{\"a\":\"1\",\"b\":\"2\"}}\n"
+    );
+    let table = Table::new_kve_set_with_data(Coremap::new(), false, true, true);
+    if let DataModel::KVExtSet(kvs) = table.get_model_ref() {
+        let members = vec!["go".into(), "c".into(), "rust".into()];
+        kvs.set_add("langs".into(), members).unwrap();
+    }
+    let mut out = Vec::new();
+    export_table(&mut out, ExportFormat::Json, "ks:sets", &table).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "{\"table\":\"ks:sets\",\"key\":\"langs\",\"value\":[\"c\",\"go\",\"rust\"]}\n"
+    );
+    let table = Table::new_kve_sorted_set_with_data(Coremap::new(), false, true, true);
+    if let DataModel::KVExtSortedSet(kvz) = table.get_model_ref() {
+        let members = vec![("bob".into(), 2.5), ("alice".into(), 10.0)];
+        kvz.zset_add("board".into(), members).unwrap();
+        kvz.zset_add("board".into(), vec![("carol".into(), f64::NEG_INFINITY)])
+            .unwrap();
+    }
+    let mut out = Vec::new();
+    export_table(&mut out, ExportFormat::Json, "ks:zsets", &table).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "{\"table\":\"ks:zsets\",\"key\":\"board\",\"value\":{\"carol\":\"-inf\",\"bob\":2.5,\"alice\":10}}\n"
+    );
+    let table = Table::new_kve_document_with_data(Coremap::new(), false, true);
+    if let DataModel::KVExtDocument(kvd) = table.get_model_ref() {
+        let doc = Json::parse(br#"{"name":"sky","tags":["db"],"stars":null}"#).unwrap();
+        kvd.doc_set("repo".into(), &Path::parse(b"$").unwrap(), doc)
+            .unwrap()
+            .unwrap();
+    }
+    let mut out = Vec::new();
+    export_table(&mut out, ExportFormat::Csv, "ks:docs", &table).unwrap();
+    assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "ks:docs,repo,\"{\"\"name\"\":\"\"sky\"\",\"\"tags\"\":[\"\"db\"\"],\"\"stars\"\":null}\"\n"
+    );
+}
+
+#[test]
+fn test_export_target_parse() {
+    let (ks, tbl) = unsafe { (ObjectID::from_slice("ks"), ObjectID::from_slice("tbl")) };
+    assert_eq!(
+        ExportTarget::parse("ks"),
+        Some(ExportTarget::Keyspace(ks.clone()))
+    );
+    assert_eq!(
+        ExportTarget::parse("ks:tbl"),
+        Some(ExportTarget::Table(ks, tbl))
+    );
+    assert_eq!(ExportTarget::parse("ks:"), None);
+    assert_eq!(
+        ExportTarget::parse(&"a".repeat(ObjectID::MAX_LENGTH + 1)),
+        None
+    );
+}
@@ -30,7 +30,7 @@ use {
     crate::corestore::map::{
         bref::{Entry, OccupiedEntry, Ref, VacantEntry},
         iter::{BorrowedIter, OwnedIter},
-        Skymap,
+        LockedShards, Skymap,
     },
     ahash::RandomState,
     std::{borrow::Borrow, hash::Hash, iter::FromIterator, ops::Deref},
@@ -58,6 +58,12 @@ impl<K: Eq + Hash, V> Coremap<K, V> {
     pub fn new() -> Self {
         Self::default()
     }
+    /// Create an empty coremap with (about) `shards` shards instead of the default
+    pub fn with_shards(shards: usize) -> Self {
+        Coremap {
+            inner: HashTable::with_shards(shards),
+        }
+    }
     pub fn with_capacity(cap: usize) -> Self {
         Coremap {
             inner: HashTable::with_capacity(cap),
@@ -100,6 +106,18 @@ where
     {
         self.inner.remove(key).is_some()
     }
+    /// Returns the index of the shard that `key` would live in
+    pub fn shard_of<Q>(&self, key: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Hash + ?Sized,
+    {
+        self.inner.shard_of(key)
+    }
+    /// Returns the number of shards in the table
+    pub fn shard_count(&self) -> usize {
+        self.inner.shard_count()
+    }
     /// Check if a table contains a key
     pub fn contains_key<Q>(&self, key: &Q) -> bool
     where
@@ -147,6 +165,10 @@ where
     pub fn upsert(&self, k: K, v: V) {
         let _ = self.inner.insert(k, v);
     }
+    /// Update or insert, returning the previous value (if any)
+    pub fn replace(&self, k: K, v: V) -> Option<V> {
+        self.inner.insert(k, v)
+    }
     /// Returns true if the value was updated
     pub fn true_if_update(&self, k: K, v: V) -> bool {
         if let Entry::Occupied(mut oe) = self.inner.entry(k) {
@@ -156,6 +178,10 @@ where
             false
         }
     }
+    /// Returns the (locked) entry for `key`, whether or not it exists
+    pub fn entry(&self, key: K) -> Entry<'_, K, V, RandomState> {
+        self.inner.entry(key)
+    }
     pub fn mut_entry(&self, key: K) -> Option<OccupiedEntry<K, V, RandomState>> {
         if let Entry::Occupied(oe) = self.inner.entry(key) {
             Some(oe)
@@ -170,6 +196,17 @@ where
             None
         }
     }
+    /// Write-lock the shards that hold the given keys (see [`Skymap::lock_shards_of`])
+    pub fn lock_shards_of<'k, Q>(
+        &self,
+        keys: impl Iterator<Item = &'k Q>,
+    ) -> LockedShards<'_, K, V, RandomState>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized + 'k,
+    {
+        self.inner.lock_shards_of(keys)
+    }
 }
 
 impl<K: Eq + Hash, V: Clone> Coremap<K, V> {
@@ -192,6 +229,30 @@ impl<K: Eq + Hash + Clone, V> Coremap<K, V> {
             .for_each(|key| v.push(key));
         v
     }
+    /// Returns a page of at most `count` keys starting at `cursor`, along with the cursor of the
+    /// next page (see [`Skymap::scan`])
+    pub fn scan_keys(&self, cursor: u64, count: usize) -> (Vec<K>, u64) {
+        self.inner.scan(cursor, count)
+    }
+    /// Returns a uniformly random key, if the table isn't empty
+    pub fn random_key(&self) -> Option<K> {
+        self.inner.random_key()
+    }
+    /// Returns up to `count` keys, starting with the keys in the shard `shard`
+    pub fn sample_keys(&self, shard: usize, count: usize) -> Vec<K> {
+        let mut keys: Vec<K> = self
+            .inner
+            .get_iter_from(shard)
+            .take(count)
+            .map(|kv| kv.key().clone())
+            .collect();
+        if keys.len() < count {
+            // wrap around (which can return a key twice if there are only a few)
+            let rest = count - keys.len();
+            keys.extend(self.iter().take(rest).map(|kv| kv.key().clone()));
+        }
+        keys
+    }
 }
 
 impl<K: Eq + Hash, V> IntoIterator for Coremap<K, V> {
@@ -0,0 +1,983 @@
+/*
+ * Created on Sun Nov 06 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Imports
+//!
+//! Streams newline-delimited JSON or CSV into a table, in the format written by
+//! [exports](super::export): every record has a key and a value (a `table` field or column is
+//! ignored, so that an export can be imported into any table). Keys and values for `binstr`
+//! columns must be in base64. The value for a list (or a set) must be a JSON array of its elements
+//! and the value for a map must be a JSON object of its fields. The value for a sorted set must be
+//! a JSON object of its members with their scores (numbers, or strings like `"inf"`), and the
+//! value for a document can be any JSON value.
+//!
+//! Records are inserted in batches of [`BATCH_SIZE`], overwriting existing keys. A bad record
+//! (or CSV header) stops the import, with everything before it imported; with
+//! `continue_on_error`, bad records are skipped instead. Either way, the report lists (the first
+//! few) bad records by line.
+//!
+//! Every batch goes through the same write gates as a query (see
+//! [`Memstore::admit_write`]): a throttled import waits for the throttle, and an import that
+//! runs into a read-only table, `maxmemory` or the keyspace quota stops there (with everything
+//! before it imported), which the report says.
+
+use {
+    crate::{
+        corestore::{
+            export::ExportFormat,
+            memstore::{Memstore, WriteRejection},
+            table::{DataModel, Table},
+            SharedSlice,
+        },
+        kvengine::{
+            document::Json as Document,
+            sortedset::{self, SortedSet},
+            LockedDocument, LockedMap, LockedSet, LockedSortedSet, LockedVec,
+        },
+        IoResult,
+    },
+    std::{
+        borrow::Cow,
+        collections::{HashMap, HashSet},
+        fmt,
+        fs::File,
+        io::{BufRead, BufReader},
+        mem,
+        path::Path,
+        thread,
+        time::Duration,
+    },
+};
+
+/// The number of records inserted at a time
+pub const BATCH_SIZE: usize = 1024;
+/// The number of bad records listed in a report
+const MAX_REPORTED_ERRORS: usize = 10;
+
+type RecordResult<T> = Result<T, Cow<'static, str>>;
+
+#[derive(Debug, PartialEq)]
+/// A record that couldn't be imported
+pub struct RecordError {
+    /// the line that the record starts on
+    pub line: usize,
+    pub reason: Cow<'static, str>,
+}
+
+impl fmt::Display for RecordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+#[derive(Debug, Default, PartialEq)]
+/// The outcome of an import
+pub struct ImportReport {
+    /// the number of records imported
+    pub imported: usize,
+    /// the number of bad records
+    pub failed: usize,
+    /// the first [`MAX_REPORTED_ERRORS`] bad records
+    pub errors: Vec<RecordError>,
+    /// the write gate that stopped the import, if any
+    pub stopped: Option<WriteRejection>,
+}
+
+impl ImportReport {
+    /// Render the report as lines of text
+    pub fn render(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("imported = {}", self.imported),
+            format!("failed = {}", self.failed),
+        ];
+        if let Some(rejection) = self.stopped {
+            lines.push(format!("stopped = {}", rejection.as_str()));
+        }
+        lines.extend(self.errors.iter().map(ToString::to_string));
+        lines
+    }
+    fn record_error(&mut self, error: RecordError) {
+        self.failed += 1;
+        if self.errors.len() < MAX_REPORTED_ERRORS {
+            self.errors.push(error);
+        }
+    }
+}
+
+/// Import the file at `path` into `table`
+pub fn import_file(
+    store: &Memstore,
+    table: &Table,
+    path: impl AsRef<Path>,
+    format: ExportFormat,
+    continue_on_error: bool,
+) -> IoResult<ImportReport> {
+    let file = BufReader::new(File::open(path)?);
+    self::import(store, table, file, format, continue_on_error)
+}
+
+/// Import the records read from `r` into `table`
+pub fn import<R: BufRead>(
+    store: &Memstore,
+    table: &Table,
+    r: R,
+    format: ExportFormat,
+    continue_on_error: bool,
+) -> IoResult<ImportReport> {
+    let ((key_is_str, value_is_str), shape) = match table.get_model_ref() {
+        DataModel::KV(kve) => (kve.get_encoding_tuple(), Shape::Value),
+        DataModel::KVExtListmap(kvl) => (kvl.get_encoding_tuple(), Shape::List),
+        DataModel::KVExtMap(kvm) => (kvm.get_encoding_tuple(), Shape::Map),
+        DataModel::KVExtSet(kvs) => (kvs.get_encoding_tuple(), Shape::Set),
+        DataModel::KVExtSortedSet(kvz) => (kvz.get_encoding_tuple(), Shape::SortedSet),
+        DataModel::KVExtDocument(kvd) => (kvd.get_encoding_tuple(), Shape::Document),
+    };
+    let mut report = ImportReport::default();
+    let mut records = match Records::new(r, format, shape == Shape::Document)? {
+        Ok(records) => records,
+        Err(e) => {
+            report.record_error(e);
+            return Ok(report);
+        }
+    };
+    let mut batch = Vec::with_capacity(BATCH_SIZE);
+    while let Some((line, record)) = records.next_record()? {
+        let entry = record.and_then(|(key, value)| {
+            let key = data(key, key_is_str)?;
+            let value = match shape {
+                Shape::List | Shape::Set => {
+                    let elements = match value {
+                        Value::List(elements) => elements,
+                        Value::Text(text) if format == ExportFormat::Csv => json_list(&text)?,
+                        _ => return Err("the value of a list or a set must be an array".into()),
+                    };
+                    let elements = elements
+                        .into_iter()
+                        .map(|element| data(element, value_is_str));
+                    match shape {
+                        Shape::Set => Entry::Set(elements.collect::<RecordResult<_>>()?),
+                        _ => Entry::List(elements.collect::<RecordResult<_>>()?),
+                    }
+                }
+                Shape::Map => {
+                    let fields = match value {
+                        Value::Map(fields) => fields,
+                        Value::Text(text) if format == ExportFormat::Csv => json_map(&text)?,
+                        _ => return Err("the value of a map must be an object".into()),
+                    };
+                    let fields = fields
+                        .into_iter()
+                        .map(|(f, v)| Ok((data(f, value_is_str)?, data(v, value_is_str)?)))
+                        .collect::<RecordResult<_>>()?;
+                    Entry::Map(fields)
+                }
+                Shape::SortedSet => {
+                    let members = match value {
+                        Value::Map(members) => members,
+                        Value::Text(text) if format == ExportFormat::Csv => json_map(&text)?,
+                        _ => return Err("the value of a sorted set must be an object".into()),
+                    };
+                    let members = members
+                        .into_iter()
+                        .map(|(member, score)| {
+                            let score = sortedset::parse_score(score.as_bytes())
+                                .ok_or("the scores of a sorted set must be numbers")?;
+                            Ok((data(member, value_is_str)?, score))
+                        })
+                        .collect::<RecordResult<_>>()?;
+                    Entry::SortedSet(members)
+                }
+                Shape::Document => match value {
+                    Value::Document(doc) => Entry::Document(doc),
+                    Value::Text(text) if format == ExportFormat::Csv => Entry::Document(
+                        Document::parse(text.as_bytes())
+                            .ok_or("the value of a document must be valid JSON")?,
+                    ),
+                    _ => return Err("the value of a document must be valid JSON".into()),
+                },
+                Shape::Value => match value {
+                    Value::Text(text) => Entry::Value(data(text, value_is_str)?),
+                    _ => return Err("the value must be a string".into()),
+                },
+            };
+            Ok((key, value))
+        });
+        match entry {
+            Ok(entry) => {
+                batch.push(entry);
+                if batch.len() == BATCH_SIZE {
+                    if let Err(rejection) = self::admit_batch(store, table) {
+                        report.stopped = Some(rejection);
+                        return Ok(report);
+                    }
+                    report.imported += self::insert_batch(table, mem::take(&mut batch));
+                }
+            }
+            Err(reason) => {
+                report.record_error(RecordError { line, reason });
+                if !continue_on_error {
+                    break;
+                }
+            }
+        }
+    }
+    if !batch.is_empty() {
+        match self::admit_batch(store, table) {
+            Ok(()) => report.imported += self::insert_batch(table, batch),
+            Err(rejection) => report.stopped = Some(rejection),
+        }
+    }
+    Ok(report)
+}
+
+/// Run the write gates for a batch, waiting for the write throttle if it has run out of tokens
+fn admit_batch(store: &Memstore, table: &Table) -> Result<(), WriteRejection> {
+    loop {
+        match store.admit_write(table, true) {
+            Err(WriteRejection::Throttled(retry_after_ms)) => {
+                thread::sleep(Duration::from_millis(retry_after_ms))
+            }
+            ret => return ret,
+        }
+    }
+}
+
+/// The shape of the values that a table holds
+#[derive(Clone, Copy, PartialEq)]
+enum Shape {
+    Value,
+    List,
+    Map,
+    Set,
+    SortedSet,
+    Document,
+}
+
+/// The value of a record
+enum Value {
+    Text(String),
+    List(Vec<String>),
+    Map(Vec<(String, String)>),
+    Document(Document),
+}
+
+/// A key and its value
+type Record = (String, Value);
+
+/// A decoded record, ready to be inserted
+enum Entry {
+    Value(SharedSlice),
+    List(Vec<SharedSlice>),
+    Map(HashMap<SharedSlice, SharedSlice>),
+    Set(HashSet<SharedSlice>),
+    SortedSet(SortedSet),
+    Document(Document),
+}
+
+/// Insert a batch of records into `table`, returning the number of records inserted
+fn insert_batch(table: &Table, batch: Vec<(SharedSlice, Entry)>) -> usize {
+    let count = batch.len();
+    match table.get_model_ref() {
+        DataModel::KV(kve) => {
+            kve.upsert_many_unchecked(
+                batch
+                    .into_iter()
+                    .filter_map(|(key, entry)| match entry {
+                        Entry::Value(value) => Some((key, value)),
+                        _ => None,
+                    })
+                    .collect(),
+            );
+        }
+        DataModel::KVExtListmap(kvl) => {
+            kvl.upsert_many_unchecked(
+                batch
+                    .into_iter()
+                    .filter_map(|(key, entry)| match entry {
+                        Entry::List(elements) => Some((key, LockedVec::new(elements))),
+                        _ => None,
+                    })
+                    .collect(),
+            );
+        }
+        DataModel::KVExtMap(kvm) => {
+            kvm.upsert_many_unchecked(
+                batch
+                    .into_iter()
+                    .filter_map(|(key, entry)| match entry {
+                        Entry::Map(fields) => Some((key, LockedMap::new(fields))),
+                        _ => None,
+                    })
+                    .collect(),
+            );
+        }
+        DataModel::KVExtSet(kvs) => {
+            kvs.upsert_many_unchecked(
+                batch
+                    .into_iter()
+                    .filter_map(|(key, entry)| match entry {
+                        Entry::Set(members) => Some((key, LockedSet::new(members))),
+                        _ => None,
+                    })
+                    .collect(),
+            );
+        }
+        DataModel::KVExtSortedSet(kvz) => {
+            kvz.upsert_many_unchecked(
+                batch
+                    .into_iter()
+                    .filter_map(|(key, entry)| match entry {
+                        Entry::SortedSet(members) => Some((key, LockedSortedSet::new(members))),
+                        _ => None,
+                    })
+                    .collect(),
+            );
+        }
+        DataModel::KVExtDocument(kvd) => {
+            kvd.upsert_many_unchecked(
+                batch
+                    .into_iter()
+                    .filter_map(|(key, entry)| match entry {
+                        Entry::Document(doc) => Some((key, LockedDocument::new(doc))),
+                        _ => None,
+                    })
+                    .collect(),
+            );
+        }
+    }
+    count
+}
+
+/// Returns the data for a key or value; `str` data is used as is, while anything else must be
+/// in base64
+fn data(text: String, is_str: bool) -> RecordResult<SharedSlice> {
+    if is_str {
+        Ok(SharedSlice::from(text))
+    } else {
+        base64::decode(text)
+            .map(SharedSlice::from)
+            .map_err(|_| "a binstr key or value must be in base64".into())
+    }
+}
+
+/// Reads records one at a time
+struct Records<R> {
+    r: R,
+    format: ExportFormat,
+    /// the number of lines read so far
+    line: usize,
+    /// the positions of the key and value columns (for CSV)
+    columns: (usize, usize),
+    /// whether the values are documents (for JSON)
+    documents: bool,
+}
+
+impl<R: BufRead> Records<R> {
+    /// Start reading records, reading the header first if there is one
+    fn new(r: R, format: ExportFormat, documents: bool) -> IoResult<Result<Self, RecordError>> {
+        let mut records = Self {
+            r,
+            format,
+            line: 0,
+            columns: (0, 1),
+            documents,
+        };
+        if format == ExportFormat::Csv {
+            let bad_header = |line| RecordError {
+                line,
+                reason: "the header must have a `key` and a `value` column".into(),
+            };
+            let (line, header) = match records.read_csv_record()? {
+                Some((line, Ok(header))) => (line, header),
+                Some((line, Err(_))) => return Ok(Err(bad_header(line))),
+                None => return Ok(Err(bad_header(1))),
+            };
+            let column = |name: &str| header.iter().position(|column| column.trim() == name);
+            records.columns = match (column("key"), column("value")) {
+                (Some(key), Some(value)) => (key, value),
+                _ => return Ok(Err(bad_header(line))),
+            };
+        }
+        Ok(Ok(records))
+    }
+    /// Returns the next record (skipping blank lines) and the line that it starts on
+    fn next_record(&mut self) -> IoResult<Option<(usize, RecordResult<Record>)>> {
+        match self.format {
+            ExportFormat::Json => loop {
+                let (line, text) = match self.read_line()? {
+                    Some(line) => line,
+                    None => return Ok(None),
+                };
+                let record = match text {
+                    Ok(text) if text.trim().is_empty() => continue,
+                    Ok(text) if self.documents => self::document_record(&text),
+                    Ok(text) => self::json_record(&text),
+                    Err(e) => Err(e),
+                };
+                return Ok(Some((line, record)));
+            },
+            ExportFormat::Csv => {
+                let (line, fields) = match self.read_csv_record()? {
+                    Some(record) => record,
+                    None => return Ok(None),
+                };
+                let (key, value) = self.columns;
+                let record = fields.and_then(|mut fields| {
+                    if fields.len() <= key.max(value) {
+                        return Err("the record is missing a column".into());
+                    }
+                    let value = mem::take(&mut fields[value]);
+                    Ok((mem::take(&mut fields[key]), Value::Text(value)))
+                });
+                Ok(Some((line, record)))
+            }
+        }
+    }
+    /// Read a line, without its line break
+    fn read_line(&mut self) -> IoResult<Option<(usize, RecordResult<String>)>> {
+        let mut buf = Vec::new();
+        if self.r.read_until(b'\n', &mut buf)? == 0 {
+            return Ok(None);
+        }
+        self.line += 1;
+        if buf.ends_with(b"\n") {
+            buf.pop();
+            if buf.ends_with(b"\r") {
+                buf.pop();
+            }
+        }
+        let text = String::from_utf8(buf).map_err(|_| "the record isn't valid UTF-8".into());
+        Ok(Some((self.line, text)))
+    }
+    /// Read a CSV record (skipping blank lines). A record spans several lines if a quoted field
+    /// has line breaks
+    fn read_csv_record(&mut self) -> IoResult<Option<(usize, RecordResult<Vec<String>>)>> {
+        let (line, mut record) = loop {
+            match self.read_line()? {
+                Some((_, Ok(text))) if text.is_empty() => continue,
+                Some((line, Ok(text))) => break (line, text),
+                Some((line, Err(e))) => return Ok(Some((line, Err(e)))),
+                None => return Ok(None),
+            }
+        };
+        // quotes in a quoted field are doubled, so an odd count means that the field goes on
+        while record.matches('"').count() % 2 != 0 {
+            match self.read_line()? {
+                Some((_, Ok(text))) => {
+                    record.push('\n');
+                    record.push_str(&text);
+                }
+                Some((_, Err(e))) => return Ok(Some((line, Err(e)))),
+                None => break,
+            }
+        }
+        Ok(Some((line, self::csv_fields(&record))))
+    }
+}
+
+/// Split a CSV record into its fields (RFC 4180)
+fn csv_fields(record: &str) -> RecordResult<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut chars = record.chars().peekable();
+    let (mut quoted, mut at_start) = (false, true);
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    quoted = false;
+                }
+            }
+            '"' if at_start => quoted = true,
+            '"' => return Err("a quote in an unquoted field".into()),
+            ',' if !quoted => {
+                fields.push(mem::take(&mut field));
+                at_start = true;
+                continue;
+            }
+            c => field.push(c),
+        }
+        at_start = false;
+    }
+    if quoted {
+        return Err("a quoted field isn't terminated".into());
+    }
+    fields.push(field);
+    Ok(fields)
+}
+
+/// Parse a JSON record: an object with a string `key` and a `value` that is a string or an
+/// array of strings
+fn json_record(text: &str) -> RecordResult<Record> {
+    let mut parser = JsonParser::new(text);
+    let fields = match parser.parse_document()? {
+        Json::Object(fields) => fields,
+        _ => return Err("the record must be an object".into()),
+    };
+    let (mut key, mut value) = (None, None);
+    for (name, field) in fields {
+        match name.as_str() {
+            "key" => key = Some(field),
+            "value" => value = Some(field),
+            _ => {}
+        }
+    }
+    let key = match key {
+        Some(Json::Str(key)) => key,
+        Some(_) => return Err("the key must be a string".into()),
+        None => return Err("the record has no key".into()),
+    };
+    let value = match value {
+        Some(Json::Str(value)) => Value::Text(value),
+        Some(Json::Array(elements)) => Value::List(self::json_strings(elements)?),
+        Some(Json::Object(fields)) => Value::Map(self::json_fields(fields)?),
+        None => return Err("the record has no value".into()),
+    };
+    Ok((key, value))
+}
+
+/// Parse a JSON record whose value is a document
+fn document_record(text: &str) -> RecordResult<Record> {
+    let fields = match Document::parse(text.as_bytes()) {
+        Some(Document::Object(fields)) => fields,
+        Some(_) => return Err("the record must be an object".into()),
+        None => return Err("the record isn't valid JSON".into()),
+    };
+    let (mut key, mut value) = (None, None);
+    for (name, field) in fields {
+        match name.as_str() {
+            "key" => key = Some(field),
+            "value" => value = Some(field),
+            _ => {}
+        }
+    }
+    let key = match key {
+        Some(Document::Str(key)) => key,
+        Some(_) => return Err("the key must be a string".into()),
+        None => return Err("the record has no key".into()),
+    };
+    match value {
+        Some(value) => Ok((key, Value::Document(value))),
+        None => Err("the record has no value".into()),
+    }
+}
+
+/// Parse the value of a list (or a set) in a CSV record
+fn json_list(text: &str) -> RecordResult<Vec<String>> {
+    match JsonParser::new(text).parse_document()? {
+        Json::Array(elements) => self::json_strings(elements),
+        _ => Err("the value of a list or a set must be an array".into()),
+    }
+}
+
+/// Parse the value of a map in a CSV record
+fn json_map(text: &str) -> RecordResult<Vec<(String, String)>> {
+    match JsonParser::new(text).parse_document()? {
+        Json::Object(fields) => self::json_fields(fields),
+        _ => Err("the value of a map must be an object".into()),
+    }
+}
+
+fn json_fields(fields: Vec<(String, Json)>) -> RecordResult<Vec<(String, String)>> {
+    fields
+        .into_iter()
+        .map(|(name, value)| match value {
+            Json::Str(value) => Ok((name, value)),
+            _ => Err("the values of a map must be strings".into()),
+        })
+        .collect()
+}
+
+fn json_strings(elements: Vec<Json>) -> RecordResult<Vec<String>> {
+    elements
+        .into_iter()
+        .map(|element| match element {
+            Json::Str(element) => Ok(element),
+            _ => Err("the elements of a list must be strings".into()),
+        })
+        .collect()
+}
+
+/// A JSON value. Numbers and booleans are kept as their text, since that is how they'd be
+/// stored
+enum Json {
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+/// Just enough of a JSON parser for records
+struct JsonParser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> JsonParser<'a> {
+    /// The depth of nested arrays and objects allowed (records only need two levels)
+    const MAX_DEPTH: usize = 8;
+    fn new(text: &'a str) -> Self {
+        Self { text, pos: 0 }
+    }
+    /// Parse a value that takes up the whole text
+    fn parse_document(&mut self) -> RecordResult<Json> {
+        let value = self.parse_value(0)?;
+        self.skip_whitespace();
+        if self.pos != self.text.len() {
+            return Err("unexpected data after the JSON value".into());
+        }
+        Ok(value)
+    }
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+    fn expect(&mut self, byte: u8) -> RecordResult<()> {
+        self.skip_whitespace();
+        if self.peek() == Some(byte) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(format!("expected `{}` at column {}", byte as char, self.pos + 1).into())
+        }
+    }
+    fn parse_value(&mut self, depth: usize) -> RecordResult<Json> {
+        if depth > Self::MAX_DEPTH {
+            return Err("the JSON value is nested too deeply".into());
+        }
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'"') => self.parse_string().map(Json::Str),
+            Some(b'[') => {
+                self.pos += 1;
+                let mut elements = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b']') {
+                    self.pos += 1;
+                    return Ok(Json::Array(elements));
+                }
+                loop {
+                    elements.push(self.parse_value(depth + 1)?);
+                    self.skip_whitespace();
+                    if self.peek() == Some(b',') {
+                        self.pos += 1;
+                    } else {
+                        self.expect(b']')?;
+                        return Ok(Json::Array(elements));
+                    }
+                }
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut fields = Vec::new();
+                self.skip_whitespace();
+                if self.peek() == Some(b'}') {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() != Some(b'"') {
+                        return Err(format!("expected a name at column {}", self.pos + 1).into());
+                    }
+                    let name = self.parse_string()?;
+                    self.expect(b':')?;
+                    fields.push((name, self.parse_value(depth + 1)?));
+                    self.skip_whitespace();
+                    if self.peek() == Some(b',') {
+                        self.pos += 1;
+                    } else {
+                        self.expect(b'}')?;
+                        return Ok(Json::Object(fields));
+                    }
+                }
+            }
+            Some(b'-' | b'0'..=b'9' | b't' | b'f') => {
+                let start = self.pos;
+                while matches!(
+                    self.peek(),
+                    Some(b'-' | b'+' | b'.' | b'0'..=b'9' | b'a'..=b'z' | b'A'..=b'Z')
+                ) {
+                    self.pos += 1;
+                }
+                let literal = &self.text[start..self.pos];
+                let is_number = literal.parse::<f64>().is_ok() && !literal.starts_with(['i', 'n']);
+                if is_number || literal == "true" || literal == "false" {
+                    Ok(Json::Str(literal.to_owned()))
+                } else {
+                    Err(format!("unexpected `{literal}` at column {}", start + 1).into())
+                }
+            }
+            Some(b'n') => Err("null values aren't supported".into()),
+            Some(_) => Err(format!("unexpected data at column {}", self.pos + 1).into()),
+            None => Err("unexpected end of the JSON value".into()),
+        }
+    }
+    /// Parse a string, starting at its opening quote
+    fn parse_string(&mut self) -> RecordResult<String> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let rest = &self.text[self.pos..];
+            let end = match rest.find(['"', '\\']) {
+                Some(end) => end,
+                None => return Err("a string isn't terminated".into()),
+            };
+            out.push_str(&rest[..end]);
+            self.pos += end + 1;
+            if rest.as_bytes()[end] == b'"' {
+                return Ok(out);
+            }
+            let escaped = match self.peek() {
+                Some(b'"') => '"',
+                Some(b'\\') => '\\',
+                Some(b'/') => '/',
+                Some(b'b') => '\u{8}',
+                Some(b'f') => '\u{c}',
+                Some(b'n') => '\n',
+                Some(b'r') => '\r',
+                Some(b't') => '\t',
+                Some(b'u') => {
+                    self.pos += 1;
+                    let high = self.parse_hex4()?;
+                    let c = if (0xD800..0xDC00).contains(&high) {
+                        // a surrogate pair
+                        if !self.text[self.pos..].starts_with("\\u") {
+                            return Err("unpaired surrogate in a string".into());
+                        }
+                        self.pos += 2;
+                        let low = self.parse_hex4()?;
+                        if !(0xDC00..0xE000).contains(&low) {
+                            return Err("unpaired surrogate in a string".into());
+                        }
+                        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+                    } else {
+                        char::from_u32(high)
+                    };
+                    out.push(c.ok_or("unpaired surrogate in a string")?);
+                    continue;
+                }
+                _ => return Err("bad escape in a string".into()),
+            };
+            out.push(escaped);
+            self.pos += 1;
+        }
+    }
+    fn parse_hex4(&mut self) -> RecordResult<u32> {
+        let hex = self
+            .text
+            .get(self.pos..self.pos + 4)
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())
+            .ok_or("bad unicode escape in a string")?;
+        self.pos += 4;
+        Ok(hex)
+    }
+}
+
+#[test]
+fn test_import() {
+    use crate::corestore::htable::Coremap;
+    let table = Table::new_pure_kve_with_data(Coremap::new(), false, true, false);
+    let store = Memstore::new_empty();
+    let json = concat!(
+        "{\"table\":\"ks:tbl\",\"key\":\"hello\",\"value\":\"/wA=\"}\n",
+        "\n",
+        "{\"key\":\"caf\\u00e9\",\"value\":\"d29ybGQ=\"}\n",
+        "{\"key\":\"bad\",\"value\":\"not base64!\"}\n",
+        "{\"key\":\"last\",\"value\":\"\"}\n",
+    );
+    // stops at the first bad record
+    let report = import(&store, &table, json.as_bytes(), ExportFormat::Json, false).unwrap();
+    assert_eq!((report.imported, report.failed), (2, 1));
+    assert_eq!(report.errors[0].line, 4);
+    let kve = table.get_kvstore().unwrap();
+    assert_eq!(kve.get_cloned("hello").unwrap().unwrap(), &[0xFF, 0x00][..]);
+    assert_eq!(kve.get_cloned("café").unwrap().unwrap(), "world".as_bytes());
+    assert!(kve.get_cloned("last").unwrap().is_none());
+    // or skips it
+    let report = import(&store, &table, json.as_bytes(), ExportFormat::Json, true).unwrap();
+    assert_eq!((report.imported, report.failed), (3, 1));
+    assert!(kve.get_cloned("last").unwrap().is_some());
+}
+
+#[test]
+fn test_import_csv_lists() {
+    use crate::corestore::htable::Coremap;
+    let table = Table::new_kve_listmap_with_data(Coremap::new(), false, true, true);
+    let store = Memstore::new_empty();
+    let csv = concat!(
+        "table,key,value\n",
+        "ks:lists,\"my,list\",\"[\"\"say \\\"\"hi\\\"\"\"\",\"\"line\n",
+        "break\"\"]\"\n",
+        "ks:lists,empty,[]\n",
+        "ks:lists,bad,\"not a list\"\n",
+        "ks:lists,short\n",
+    );
+    let report = import(&store, &table, csv.as_bytes(), ExportFormat::Csv, true).unwrap();
+    assert_eq!((report.imported, report.failed), (2, 2));
+    assert_eq!(
+        report.errors.iter().map(|e| e.line).collect::<Vec<_>>(),
+        [5, 6]
+    );
+    if let DataModel::KVExtListmap(kvl) = table.get_model_ref() {
+        let list = kvl.list_cloned_full("my,list".as_bytes()).unwrap().unwrap();
+        assert_eq!(
+            list,
+            ["say \"hi\"".into(), "line\nbreak".into()] as [SharedSlice; 2]
+        );
+        assert_eq!(kvl.list_len("empty".as_bytes()).unwrap(), Some(0));
+    }
+    // the header must name the key and value columns
+    let report = import(
+        &store,
+        &table,
+        "a,b\nx,y\n".as_bytes(),
+        ExportFormat::Csv,
+        true,
+    )
+    .unwrap();
+    assert_eq!((report.imported, report.failed), (0, 1));
+}
+
+#[test]
+fn test_import_maps() {
+    use crate::corestore::htable::Coremap;
+    let table = Table::new_kve_map_with_data(Coremap::new(), false, true, true);
+    let store = Memstore::new_empty();
+    let json = concat!(
+        "{\"key\":\"user:1\",\"value\":{\"name\":\"sayan\",\"lang\":\"rust\"}}\n",
+        "{\"key\":\"empty\",\"value\":{}}\n",
+        "{\"key\":\"list\",\"value\":[\"a\"]}\n",
+        "{\"key\":\"nested\",\"value\":{\"a\":[\"b\"]}}\n",
+    );
+    let report = import(&store, &table, json.as_bytes(), ExportFormat::Json, true).unwrap();
+    assert_eq!((report.imported, report.failed), (2, 2));
+    let csv = "key,value\nuser:2,\"{\"\"name\"\":\"\"ferris\"\"}\"\n";
+    let report = import(&store, &table, csv.as_bytes(), ExportFormat::Csv, false).unwrap();
+    assert_eq!((report.imported, report.failed), (1, 0));
+    if let DataModel::KVExtMap(kvm) = table.get_model_ref() {
+        let name = |key: &str| kvm.map_get(key.as_bytes(), b"name").unwrap().unwrap();
+        assert_eq!(name("user:1"), Some("sayan".into()));
+        assert_eq!(name("user:2"), Some("ferris".into()));
+        let lang = kvm.map_get(b"user:1", b"lang").unwrap().unwrap();
+        assert_eq!(lang, Some("rust".into()));
+        assert_eq!(kvm.map_cloned_full(b"empty").unwrap(), Some(vec![]));
+    }
+}
+
+#[test]
+fn test_import_sets() {
+    use crate::corestore::htable::Coremap;
+    let table = Table::new_kve_set_with_data(Coremap::new(), false, true, true);
+    let store = Memstore::new_empty();
+    let json = concat!(
+        "{\"key\":\"langs\",\"value\":[\"rust\",\"go\",\"rust\"]}\n",
+        "{\"key\":\"map\",\"value\":{\"a\":\"b\"}}\n",
+    );
+    let report = import(&store, &table, json.as_bytes(), ExportFormat::Json, true).unwrap();
+    assert_eq!((report.imported, report.failed), (1, 1));
+    if let DataModel::KVExtSet(kvs) = table.get_model_ref() {
+        assert!(kvs.set_contains(b"langs", b"go").unwrap());
+        assert_eq!(kvs.set_members(b"langs").unwrap().unwrap().len(), 2);
+    }
+}
+
+#[test]
+fn test_import_sorted_sets() {
+    use crate::corestore::htable::Coremap;
+    let table = Table::new_kve_sorted_set_with_data(Coremap::new(), false, true, true);
+    let store = Memstore::new_empty();
+    let json = concat!(
+        "{\"key\":\"board\",\"value\":{\"alice\":10,\"bob\":2.5,\"carol\":\"-inf\"}}\n",
+        "{\"key\":\"bad\",\"value\":{\"alice\":\"ten\"}}\n",
+        "{\"key\":\"list\",\"value\":[\"alice\"]}\n",
+    );
+    let report = import(&store, &table, json.as_bytes(), ExportFormat::Json, true).unwrap();
+    assert_eq!((report.imported, report.failed), (1, 2));
+    if let DataModel::KVExtSortedSet(kvz) = table.get_model_ref() {
+        assert_eq!(kvz.zset_score(b"board", b"bob").unwrap(), Some(2.5));
+        assert_eq!(kvz.zset_rank(b"board", b"carol").unwrap(), Some(0));
+        assert_eq!(kvz.zset_rank(b"board", b"alice").unwrap(), Some(2));
+    }
+}
+
+#[test]
+fn test_import_documents() {
+    use crate::{corestore::htable::Coremap, kvengine::document::Path};
+    let table = Table::new_kve_document_with_data(Coremap::new(), false, true);
+    let store = Memstore::new_empty();
+    let json = concat!(
+        "{\"key\":\"user:1\",\"value\":{\"name\":\"sayan\",\"langs\":[\"rust\",1.5,null]}}\n",
+        "{\"key\":\"answer\",\"value\":42}\n",
+        "{\"key\":\"dup\",\"value\":{\"a\":1,\"a\":2}}\n",
+    );
+    let report = import(&store, &table, json.as_bytes(), ExportFormat::Json, true).unwrap();
+    assert_eq!((report.imported, report.failed), (2, 1));
+    let csv = "key,value\nuser:2,\"{\"\"name\"\":\"\"ferris\"\"}\"\nbad,{\n";
+    let report = import(&store, &table, csv.as_bytes(), ExportFormat::Csv, true).unwrap();
+    assert_eq!((report.imported, report.failed), (1, 1));
+    if let DataModel::KVExtDocument(kvd) = table.get_model_ref() {
+        let get = |key: &str, path: &str| {
+            let path = Path::parse(path.as_bytes()).unwrap();
+            kvd.doc_get(key.as_bytes(), &path).unwrap()
+        };
+        assert_eq!(get("user:1", "$.langs[1]"), Some("1.5".into()));
+        assert_eq!(get("user:2", "$.name"), Some("\"ferris\"".into()));
+        assert_eq!(get("answer", "$"), Some("42".into()));
+    }
+}
+
+#[test]
+fn test_import_over_quota() {
+    use crate::corestore::{
+        htable::Coremap,
+        memstore::{Keyspace, ObjectID},
+    };
+    let store = Memstore::new_empty();
+    let keyspace = Keyspace::empty();
+    keyspace.quota().set_max_keys(1);
+    let tbl = unsafe { ObjectID::from_slice("tbl") };
+    let table = Table::new_pure_kve_with_data(Coremap::new(), false, true, true);
+    assert!(keyspace.create_table(tbl.clone(), table));
+    let table = keyspace.get_table_atomic_ref(&tbl).unwrap();
+    let json = "{\"key\":\"a\",\"value\":\"1\"}\n";
+    let report = import(&store, &table, json.as_bytes(), ExportFormat::Json, false).unwrap();
+    assert_eq!((report.imported, report.stopped), (1, None));
+    // the keyspace is at its quota now, so nothing else gets in
+    let json = "{\"key\":\"b\",\"value\":\"2\"}\n";
+    let report = import(&store, &table, json.as_bytes(), ExportFormat::Json, false).unwrap();
+    assert_eq!(report.imported, 0);
+    assert_eq!(report.stopped, Some(WriteRejection::QuotaExceeded));
+    assert_eq!(report.render().last().unwrap(), "stopped = quota-exceeded");
+    assert_eq!(table.count(), 1);
+}
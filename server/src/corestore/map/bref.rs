@@ -31,6 +31,7 @@ use {
         hash::{BuildHasher, Hash},
         mem,
         ops::{Deref, DerefMut},
+        sync::atomic::AtomicU32,
     },
     parking_lot::{RwLockReadGuard, RwLockWriteGuard},
     std::{collections::hash_map::RandomState, sync::Arc},
@@ -157,17 +158,29 @@ pub struct VacantEntry<'a, K, V, S> {
     guard: RwLockWriteGuard<'a, LowMap<K, V>>,
     key: K,
     hasher: S,
+    relocations: &'a AtomicU32,
 }
 
 impl<'a, K: Hash + Eq, V, S: BuildHasher> VacantEntry<'a, K, V, S> {
     /// Create a vacant entry ref
-    pub(super) fn new(guard: RwLockWriteGuard<'a, LowMap<K, V>>, key: K, hasher: S) -> Self {
-        Self { guard, key, hasher }
+    pub(super) fn new(
+        guard: RwLockWriteGuard<'a, LowMap<K, V>>,
+        key: K,
+        hasher: S,
+        relocations: &'a AtomicU32,
+    ) -> Self {
+        Self {
+            guard,
+            key,
+            hasher,
+            relocations,
+        }
     }
     /// Insert a value into this bucket
     pub fn insert(mut self, value: V) -> RefMut<'a, K, V> {
         unsafe {
             let hash = super::make_insert_hash::<K, S>(&self.hasher, &self.key);
+            super::note_insert(&self.guard, self.relocations);
             let &mut (ref mut k, ref mut v) = self.guard.insert_entry(
                 hash,
                 (self.key, value),
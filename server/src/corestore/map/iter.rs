@@ -86,9 +86,13 @@ pub struct BorrowedIter<'a, K, V, S = ahash::RandomState> {
 
 impl<'a, K, V, S> BorrowedIter<'a, K, V, S> {
     pub const fn new(map: &'a Skymap<K, V, S>) -> Self {
+        Self::starting_at(map, 0)
+    }
+    /// Get an iterator that skips the shards before `shard`
+    pub const fn starting_at(map: &'a Skymap<K, V, S>, shard: usize) -> Self {
         Self {
             map,
-            cs: 0usize,
+            cs: shard,
             citer: None,
         }
     }
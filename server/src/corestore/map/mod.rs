@@ -40,8 +40,10 @@ use {
         iter::FromIterator,
         mem,
         num::NonZeroUsize,
+        sync::atomic::{AtomicU32, AtomicUsize, Ordering},
     },
     parking_lot::{RwLock, RwLockReadGuard, RwLockWriteGuard},
+    rand::Rng,
     std::{collections::hash_map::RandomState, thread::available_parallelism},
 };
 
@@ -93,8 +95,32 @@ where
     move |x| k.eq(x.0.borrow())
 }
 
+/// The default number of shards per core
+pub const DEFAULT_SHARDS_PER_CORE: usize = 16;
+/// The number of shards per core for new maps
+static SHARDS_PER_CORE: AtomicUsize = AtomicUsize::new(DEFAULT_SHARDS_PER_CORE);
+
+/// Set the number of shards per core for new maps
+pub fn set_shards_per_core(shards: usize) {
+    SHARDS_PER_CORE.store(shards.max(1), Ordering::Release)
+}
+
+/// The number of random buckets that [`Skymap::random_key`] looks at before it falls back to
+/// taking the first key of a shard
+const RANDOM_KEY_ATTEMPTS: usize = 32;
+
+/// The largest number of shards a map can have
+pub const MAX_SHARDS: usize = 1 << 16;
+
 fn get_shard_count() -> usize {
-    (available_parallelism().map_or(1, usize::from) * 16).next_power_of_two()
+    let shards_per_core = SHARDS_PER_CORE.load(Ordering::Acquire);
+    self::normalize_shard_count(available_parallelism().map_or(1, usize::from) * shards_per_core)
+}
+
+/// Returns the number of shards a map asked to have `shards` shards will actually have: the
+/// next power of two, within `1..=MAX_SHARDS`
+pub fn normalize_shard_count(shards: usize) -> usize {
+    shards.clamp(1, MAX_SHARDS).next_power_of_two()
 }
 
 const fn cttz(amount: usize) -> usize {
@@ -104,10 +130,22 @@ const fn cttz(amount: usize) -> usize {
 /// A striped in-memory map
 pub struct Skymap<K, V, S = RandomState> {
     shards: Box<ShardSlice<K, V>>,
+    /// for every shard, the number of times its entries may have moved to other buckets (see
+    /// [`Skymap::scan`])
+    relocations: Box<[AtomicU32]>,
     hasher: S,
     shift: usize,
 }
 
+/// Call this before inserting into `lowtable`: if it has no room left, the insert will move its
+/// entries (to a bigger table, or around the same one to get rid of tombstones), which is
+/// recorded in `relocations`
+fn note_insert<K, V>(lowtable: &LowMap<K, V>, relocations: &AtomicU32) {
+    if lowtable.len() == lowtable.capacity() {
+        relocations.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
 impl<K, V> Default for Skymap<K, V, RandomState> {
     fn default() -> Self {
         Self::with_hasher(RandomState::default())
@@ -161,9 +199,18 @@ where
     pub fn with_capacity(cap: usize) -> Self {
         Self::with_capacity_and_hasher(cap, S::default())
     }
+    /// Create a new Skymap with (about, see [`normalize_shard_count`]) the provided number of
+    /// shards instead of the default
+    pub fn with_shards(shards: usize) -> Self {
+        Self::with_shards_capacity_and_hasher(shards, DEFAULT_CAP, S::default())
+    }
     /// Create a new Skymap with the provided cap and hasher
-    pub fn with_capacity_and_hasher(mut cap: usize, hasher: S) -> Self {
-        let shard_count = get_shard_count();
+    pub fn with_capacity_and_hasher(cap: usize, hasher: S) -> Self {
+        Self::with_shards_capacity_and_hasher(get_shard_count(), cap, hasher)
+    }
+    /// Create a new Skymap with the provided number of shards, cap and hasher
+    pub fn with_shards_capacity_and_hasher(shards: usize, mut cap: usize, hasher: S) -> Self {
+        let shard_count = self::normalize_shard_count(shards);
         let shift = BITS_IN_USIZE - cttz(shard_count);
         if cap != 0 {
             cap = (cap + (shard_count - 1)) & !(shard_count - 1);
@@ -174,6 +221,7 @@ where
             shards: (0..shard_count)
                 .map(|_| RwLock::new(LowMap::with_capacity(cap_per_shard)))
                 .collect(),
+            relocations: (0..shard_count).map(|_| AtomicU32::new(0)).collect(),
             hasher,
             shift,
         }
@@ -186,6 +234,10 @@ where
     pub fn len(&self) -> usize {
         self.shards.iter().map(|s| s.read().len()).sum()
     }
+    /// Get the number of shards in the Skymap
+    pub fn shard_count(&self) -> usize {
+        self.shards.len()
+    }
     /// Get the capacity of the Skymap
     pub fn capacity(&self) -> usize {
         self.shards.iter().map(|s| s.read().capacity()).sum()
@@ -198,6 +250,10 @@ where
     pub fn get_iter(&self) -> BorrowedIter<K, V, S> {
         BorrowedIter::new(self)
     }
+    /// Get a borrowed iterator for the Skymap that starts at the shard `shard`
+    pub fn get_iter_from(&self, shard: usize) -> BorrowedIter<'_, K, V, S> {
+        BorrowedIter::starting_at(self, shard.min(self.shard_count()))
+    }
     /// Get an owned iterator to the Skymap
     pub fn get_owned_iter(self) -> OwnedIter<K, V, S> {
         OwnedIter::new(self)
@@ -213,7 +269,11 @@ impl<K, V, S> Skymap<K, V, S> {
     /// Determine the shard
     const fn determine_shard(&self, hash: usize) -> usize {
         // the idea of the shift was inspired by Joel's idea
-        (hash << 7) >> self.shift
+        match (hash << 7).checked_shr(self.shift as u32) {
+            Some(shard) => shard,
+            // there's a single shard
+            None => 0,
+        }
     }
     /// Get a ref to the underlying hasher
     const fn h(&self) -> &S {
@@ -238,6 +298,7 @@ where
             if let Some((_, item)) = lowtable.get_mut(hash, ceq(&k)) {
                 Some(mem::replace(item, v))
             } else {
+                self::note_insert(&lowtable, &self.relocations[idx]);
                 lowtable.insert(hash, (k, v), make_hasher::<K, _, V, S>(self.h()));
                 None
             }
@@ -287,6 +348,14 @@ where
             // end critical section
         }
     }
+    /// Get the index of the shard that `k` would live in
+    pub fn shard_of<Q>(&self, k: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Hash + ?Sized,
+    {
+        self.determine_shard(make_hash::<K, Q, S>(self.h(), k) as usize)
+    }
 }
 
 // lt impls
@@ -354,7 +423,12 @@ impl<'a, K: 'a + Hash + Eq, V: 'a, S: BuildHasher + Clone> Skymap<K, V, S> {
                     self.hasher.clone(),
                 ))
             } else {
-                Entry::Vacant(VacantEntry::new(lowtable, key, self.hasher.clone()))
+                Entry::Vacant(VacantEntry::new(
+                    lowtable,
+                    key,
+                    self.hasher.clone(),
+                    &self.relocations[idx],
+                ))
             }
             // end critical section
         }
@@ -373,6 +447,189 @@ impl<'a, K: 'a + Hash + Eq, V: 'a, S: BuildHasher + Clone> Skymap<K, V, S> {
     }
 }
 
+// batch impls
+impl<'a, K: 'a + Hash + Eq, V: 'a, S: BuildHasher + Clone> Skymap<K, V, S> {
+    /// Write-lock the shards that hold the given keys, so that a batch of changes to those keys
+    /// can be made without anyone seeing it half-done. Shards are locked in order (and only once
+    /// each), so two batches can't deadlock each other
+    pub fn lock_shards_of<'k, Q>(
+        &'a self,
+        keys: impl Iterator<Item = &'k Q>,
+    ) -> LockedShards<'a, K, V, S>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized + 'k,
+    {
+        let mut shards: Vec<usize> = keys.map(|key| self.shard_of(key)).collect();
+        shards.sort_unstable();
+        shards.dedup();
+        let locks = shards
+            .into_iter()
+            .map(|shard| (shard, unsafe { self.get_wshard_unchecked(shard) }))
+            .collect();
+        LockedShards { map: self, locks }
+    }
+}
+
+// scan impls
+
+/// The bit of a scan cursor that is left for the users of [`Skymap::scan`] (it's never set in the
+/// cursors that we return)
+pub const SCAN_CURSOR_USER_BIT: u64 = 1 << 63;
+/// A scan cursor is laid out as `[user bit (1)][shard (16)][relocations (7)][bucket (40)]`
+const SCAN_SHARD_SHIFT: u32 = 47;
+const SCAN_RELOCATIONS_SHIFT: u32 = 40;
+const SCAN_RELOCATIONS_MASK: u64 = (1 << (SCAN_SHARD_SHIFT - SCAN_RELOCATIONS_SHIFT)) - 1;
+const SCAN_BUCKET_MASK: u64 = (1 << SCAN_RELOCATIONS_SHIFT) - 1;
+
+/// Returns true if the bucket at `index` holds an entry. hashbrown doesn't expose this (as of
+/// 0.12), so we read the control byte ourselves: the control bytes start where the buckets end
+/// (at [`LowMap::data_end`]) and the control byte of a full bucket has its top bit cleared
+///
+/// ## Safety
+/// `index` must be less than the number of buckets in `lowtable`
+unsafe fn is_bucket_full<K, V>(lowtable: &LowMap<K, V>, index: usize) -> bool {
+    let ctrl = lowtable.data_end().as_ptr() as *const u8;
+    *ctrl.add(index) & 0x80 == 0
+}
+
+impl<'a, K: 'a + Hash + Eq + Clone, V: 'a, S: BuildHasher + Clone> Skymap<K, V, S> {
+    /// Returns at most `count` keys starting at `cursor` along with the cursor for the next call,
+    /// which is zero once every key has been returned. Start with a zero cursor.
+    ///
+    /// The cursor is a position in the buckets of a shard, so a call only looks at the buckets
+    /// after the cursor until it has found `count` keys. A key that is in the map for the whole
+    /// scan is returned at least once. If the entries of a shard move between two calls (because
+    /// it grew or got rid of its tombstones), we start that shard over, so its keys may be
+    /// returned again
+    pub fn scan(&'a self, cursor: u64, count: usize) -> (Vec<K>, u64) {
+        let count = count.max(1);
+        let mut shard = ((cursor & !SCAN_CURSOR_USER_BIT) >> SCAN_SHARD_SHIFT) as usize;
+        let mut relocations = (cursor >> SCAN_RELOCATIONS_SHIFT) & SCAN_RELOCATIONS_MASK;
+        let mut bucket = (cursor & SCAN_BUCKET_MASK) as usize;
+        let mut found = Vec::with_capacity(count.min(DEFAULT_CAP));
+        while shard < self.shards().len() {
+            let lowtable = unsafe {
+                // UNSAFE(@ohsayan): the shard index is within bounds
+                self.get_rshard_unchecked(shard)
+            };
+            // this can't change while we hold the read lock
+            let moved = self.relocations[shard].load(Ordering::Relaxed) as u64;
+            if moved & SCAN_RELOCATIONS_MASK != relocations {
+                bucket = 0;
+                relocations = moved & SCAN_RELOCATIONS_MASK;
+            }
+            while bucket < lowtable.buckets() && found.len() < count {
+                unsafe {
+                    // UNSAFE(@ohsayan): the bucket is within bounds and we only read it if it's
+                    // full. The key is cloned before we let go of the read lock
+                    if self::is_bucket_full(&lowtable, bucket) {
+                        found.push(lowtable.bucket(bucket).as_ref().0.clone());
+                    }
+                }
+                bucket += 1;
+            }
+            if found.len() == count {
+                let next = ((shard as u64) << SCAN_SHARD_SHIFT)
+                    | (relocations << SCAN_RELOCATIONS_SHIFT)
+                    | bucket as u64;
+                return (found, next);
+            }
+            shard += 1;
+            bucket = 0;
+        }
+        (found, 0)
+    }
+    /// Returns a random key, or `None` if the map is empty.
+    ///
+    /// We pick a random shard and then a random bucket in it, and try again if the bucket is
+    /// empty, so a call only ever holds one read lock at a time and doesn't walk the shard. If
+    /// none of the attempts land on a key (because the map is empty or very sparse), we fall
+    /// back to the first key of a shard, starting from a random one
+    pub fn random_key(&'a self) -> Option<K> {
+        let mut rng = rand::thread_rng();
+        let shards = self.shards().len();
+        for _ in 0..RANDOM_KEY_ATTEMPTS {
+            let lowtable = unsafe {
+                // UNSAFE(@ohsayan): the shard index is within bounds
+                self.get_rshard_unchecked(rng.gen_range(0..shards))
+            };
+            if lowtable.is_empty() {
+                continue;
+            }
+            let bucket = rng.gen_range(0..lowtable.buckets());
+            unsafe {
+                // UNSAFE(@ohsayan): the bucket is within bounds and we only read it if it's
+                // full. The key is cloned before we let go of the read lock
+                if self::is_bucket_full(&lowtable, bucket) {
+                    return Some(lowtable.bucket(bucket).as_ref().0.clone());
+                }
+            }
+        }
+        let start = rng.gen_range(0..shards);
+        (0..shards).find_map(|i| unsafe {
+            // UNSAFE(@ohsayan): the shard index is within bounds and the iterator doesn't
+            // outlive the read lock
+            let lowtable = self.get_rshard_unchecked((start + i) % shards);
+            lowtable
+                .iter()
+                .next()
+                .map(|bucket| bucket.as_ref().0.clone())
+        })
+    }
+}
+
+/// The write locks on some shards of a [`Skymap`] (see [`Skymap::lock_shards_of`]). Only the
+/// keys that live in these shards can be used, and using any other key panics
+pub struct LockedShards<'a, K, V, S> {
+    map: &'a Skymap<K, V, S>,
+    /// the locks, ordered by the index of their shards
+    locks: Vec<(usize, SWlock<'a, K, V>)>,
+}
+
+impl<'a, K: Hash + Eq, V, S: BuildHasher + Clone> LockedShards<'a, K, V, S> {
+    /// Returns the position of the lock for the shard that `hash` belongs to
+    fn position(&self, hash: u64) -> usize {
+        let shard = self.map.determine_shard(hash as usize);
+        self.locks
+            .binary_search_by_key(&shard, |(shard, _)| *shard)
+            .expect("the shard of the key isn't locked")
+    }
+    /// Get a ref to the value of a key
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = make_hash::<K, Q, S>(self.map.h(), k);
+        let lowtable = &self.locks[self.position(hash)].1;
+        lowtable.get(hash, ceq(k)).map(|(_, v)| v)
+    }
+    /// Insert a key/value, returning the previous value (if any)
+    pub fn insert(&mut self, k: K, v: V) -> Option<V> {
+        let hash = make_insert_hash::<K, S>(self.map.h(), &k);
+        let position = self.position(hash);
+        let (shard, lowtable) = &mut self.locks[position];
+        if let Some((_, item)) = lowtable.get_mut(hash, ceq(&k)) {
+            Some(mem::replace(item, v))
+        } else {
+            self::note_insert(lowtable, &self.map.relocations[*shard]);
+            lowtable.insert(hash, (k, v), make_hasher::<K, _, V, S>(self.map.h()));
+            None
+        }
+    }
+    /// Remove a key/value
+    pub fn remove<Q>(&mut self, k: &Q) -> Option<(K, V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let hash = make_hash::<K, Q, S>(self.map.h(), k);
+        let position = self.position(hash);
+        self.locks[position].1.remove_entry(hash, ceq(k))
+    }
+}
+
 // cloned impls
 impl<'a, K, V: Clone, S: BuildHasher> Skymap<K, V, S> {
     pub fn get_cloned<Q>(&'a self, k: &Q) -> Option<V>
@@ -437,3 +694,123 @@ fn test_entry() {
     assert!(map.entry("hello").is_occupied());
     assert!(map.entry("world").is_vacant());
 }
+
+#[test]
+fn test_with_shards() {
+    for (shards, expected) in [(0, 1), (1, 1), (3, 4), (64, 64), (usize::MAX, MAX_SHARDS)] {
+        let map: Skymap<usize, usize> = Skymap::with_shards(shards);
+        assert_eq!(map.shard_count(), expected);
+        for i in 0..100 {
+            map.insert(i, i);
+        }
+        assert_eq!(map.len(), 100);
+        assert!((0..100).all(|i| *map.get(&i).unwrap() == i));
+    }
+}
+
+#[test]
+fn test_scan() {
+    let map = Skymap::default();
+    for i in 0..1000 {
+        map.insert(i, i);
+    }
+    let (mut seen, mut cursor) = (Vec::new(), 0);
+    loop {
+        let (keys, next) = map.scan(cursor, 64);
+        assert!(keys.len() == 64 || next == 0);
+        seen.extend(keys);
+        if next == 0 {
+            break;
+        }
+        assert_eq!(next & SCAN_CURSOR_USER_BIT, 0);
+        cursor = next;
+    }
+    // nothing changed, so every key is returned exactly once
+    seen.sort_unstable();
+    assert_eq!(seen, (0..1000).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_scan_with_changes() {
+    let map: Skymap<usize, usize> = Skymap::with_shards(4);
+    for i in 0..1000 {
+        map.insert(i, i);
+    }
+    let (mut seen, mut cursor, mut added) = (Vec::new(), 0, 1000);
+    loop {
+        let (keys, next) = map.scan(cursor, 64);
+        // add enough keys to make the shards grow, and remove some of them
+        for _ in 0..200 {
+            map.insert(added, 0);
+            added += 1;
+        }
+        for key in (added - 200..added).step_by(2) {
+            map.remove(&key);
+        }
+        seen.extend(keys.into_iter().filter(|key| *key < 1000));
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+    }
+    // keys may be returned again after a shard grows, but none are skipped
+    seen.sort_unstable();
+    seen.dedup();
+    assert_eq!(seen, (0..1000).collect::<Vec<_>>());
+}
+
+#[test]
+fn test_is_bucket_full() {
+    let map: Skymap<usize, usize> = Skymap::with_shards(1);
+    for i in 0..100 {
+        map.insert(i, i);
+    }
+    for i in (0..100).step_by(3) {
+        map.remove(&i);
+    }
+    let lowtable = map.shards[0].read();
+    unsafe {
+        let full: Vec<usize> = (0..lowtable.buckets())
+            .filter(|bucket| self::is_bucket_full(&lowtable, *bucket))
+            .collect();
+        let mut expected: Vec<usize> = lowtable
+            .iter()
+            .map(|bucket| lowtable.bucket_index(&bucket))
+            .collect();
+        expected.sort_unstable();
+        assert_eq!(full, expected);
+    }
+}
+
+#[test]
+fn test_random_key() {
+    let map = Skymap::default();
+    assert!(map.random_key().is_none());
+    for i in 0..100 {
+        map.insert(i, i);
+    }
+    let mut seen = [false; 100];
+    for _ in 0..5000 {
+        seen[map.random_key().unwrap()] = true;
+    }
+    // missing a key in 5000 picks is (very nearly) impossible
+    assert!(seen.iter().all(|seen| *seen));
+    // a sparse map falls back to the first key of a shard
+    for i in 1..100 {
+        map.remove(&i);
+    }
+    assert_eq!(map.random_key(), Some(0));
+}
+
+#[test]
+fn test_locked_shards() {
+    let map = Skymap::default();
+    map.insert("hello", "world");
+    let mut shards = map.lock_shards_of(["hello", "sayan"].iter());
+    assert_eq!(shards.get("hello"), Some(&"world"));
+    assert_eq!(shards.insert("sayan", "writes code"), None);
+    assert_eq!(shards.remove("hello"), Some(("hello", "world")));
+    drop(shards);
+    assert!(map.get("hello").is_none());
+    assert_eq!(*map.get("sayan").unwrap(), "writes code");
+}
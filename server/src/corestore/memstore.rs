@@ -58,15 +58,24 @@ use {
     super::KeyspaceResult,
     crate::{
         auth::Authmap,
+        config::EvictionPolicy,
         corestore::{
-            array::Array,
+            catalog::EntityCatalog,
             htable::Coremap,
             table::{SystemDataModel, SystemTable, Table},
+            usage::{UsageCounters, UsageLedger},
         },
+        kvengine::{eviction, quota::Quota},
         registry,
-        util::Wrapper,
+        storage::v1::unflush,
+        util::{compiler, os, Wrapper},
     },
-    core::{borrow::Borrow, hash::Hash},
+    core::{
+        borrow::Borrow,
+        hash::Hash,
+        sync::atomic::{AtomicU64, Ordering},
+    },
+    parking_lot::{Mutex, MutexGuard, RwLock},
     std::sync::Arc,
 };
 
@@ -74,24 +83,32 @@ uninit_array! {
     const DEFAULT_ARRAY: [u8; 64] = [b'd', b'e', b'f', b'a', b'u', b'l', b't'];
     const SYSTEM_ARRAY: [u8; 64] = [b's', b'y', b's', b't', b'e', b'm'];
     const SYSTEM_AUTH_ARRAY: [u8; 64] = [b'a', b'u', b't', b'h'];
+    const SYSTEM_USAGE_ARRAY: [u8; 64] = [b'u', b's', b'a', b'g', b'e'];
+    const SYSTEM_ENTITIES_ARRAY: [u8; 64] = [b'e', b'n', b't', b'i', b't', b'i', b'e', b's'];
 }
 
-/// typedef for the keyspace/table IDs. We don't need too much fancy here,
-/// no atomic pointers and all. Just a nice array. With amazing gurantees
-pub type ObjectID = Array<u8, 64>;
+pub use super::objectid::ObjectID;
 
 /// The `DEFAULT` array (with the rest uninit)
 pub const DEFAULT: ObjectID = unsafe {
     // SAFETY: known init len
-    Array::from_const(DEFAULT_ARRAY, 7)
+    ObjectID::from_const(DEFAULT_ARRAY, 7)
 };
 pub const SYSTEM: ObjectID = unsafe {
     // SAFETY: known init len
-    Array::from_const(SYSTEM_ARRAY, 6)
+    ObjectID::from_const(SYSTEM_ARRAY, 6)
 };
 pub const AUTH: ObjectID = unsafe {
     // SAFETY: known init len
-    Array::from_const(SYSTEM_AUTH_ARRAY, 4)
+    ObjectID::from_const(SYSTEM_AUTH_ARRAY, 4)
+};
+pub const USAGE: ObjectID = unsafe {
+    // SAFETY: known init len
+    ObjectID::from_const(SYSTEM_USAGE_ARRAY, 5)
+};
+pub const ENTITIES: ObjectID = unsafe {
+    // SAFETY: known init len
+    ObjectID::from_const(SYSTEM_ENTITIES_ARRAY, 8)
 };
 
 #[test]
@@ -107,7 +124,7 @@ fn test_def_macro_sanity() {
     }
 }
 
-mod cluster {
+pub mod cluster {
     /// This is for the future where every node will be allocated a shard
     #[derive(Debug)]
     pub enum ClusterShardRange {
@@ -121,12 +138,36 @@ mod cluster {
     }
 
     /// This is for the future for determining the replication strategy
-    #[derive(Debug)]
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
     pub enum ReplicationStrategy {
         /// Single node, no replica sets
         Default,
     }
 
+    impl ReplicationStrategy {
+        const NAME_DEFAULT: &'static [u8] = b"default";
+        /// Returns the strategy called `name` (as used in `alter space`)
+        pub fn from_name(name: &[u8]) -> Option<Self> {
+            match name.to_ascii_lowercase().as_slice() {
+                Self::NAME_DEFAULT => Some(Self::Default),
+                _ => None,
+            }
+        }
+        /// Returns the code of this strategy (as stored in the `KSMETA`)
+        pub const fn code(&self) -> u8 {
+            match self {
+                Self::Default => 0,
+            }
+        }
+        /// Returns the strategy with the given code
+        pub const fn from_code(code: u8) -> Option<Self> {
+            match code {
+                0 => Some(Self::Default),
+                _ => None,
+            }
+        }
+    }
+
     impl Default for ReplicationStrategy {
         fn default() -> Self {
             Self::Default
@@ -156,6 +197,36 @@ pub enum DdlError {
     NotEmpty,
     /// The DDL transaction failed
     DdlTransactionFailure,
+    /// The target object (or the whole node) is read-only
+    ReadOnly,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+/// Why a write was turned away by the write gates (see [`Memstore::admit_write`])
+pub enum WriteRejection {
+    /// The table was dropped
+    Dropped,
+    /// The table (or the whole node) is read-only
+    ReadOnly,
+    /// The table's write throttle ran out of tokens; retry after this many milliseconds
+    Throttled(u64),
+    /// The memory limit was hit and no keys could be evicted
+    OutOfMemory,
+    /// The table's keyspace hit its quota
+    QuotaExceeded,
+}
+
+impl WriteRejection {
+    /// Returns the name that the rejection is reported with
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Dropped => "container-not-found",
+            Self::ReadOnly => "read-only",
+            Self::Throttled(_) => "throttled",
+            Self::OutOfMemory => "out-of-memory",
+            Self::QuotaExceeded => "quota-exceeded",
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -226,7 +297,34 @@ impl Memstore {
             }
             None => match self.system.tables.get(&AUTH).unwrap().data {
                 SystemDataModel::Auth(ref am) => am.clone(),
-                #[allow(unreachable_patterns)]
+                _ => unsafe { impossible!() },
+            },
+        }
+    }
+    /// Returns the usage ledger, creating the `usage` system table if it doesn't exist
+    pub fn setup_usage(&self) -> UsageLedger {
+        match self.system.tables.fresh_entry(USAGE) {
+            Some(fresh) => {
+                let ledger = UsageLedger::new(Coremap::new());
+                fresh.insert(Wrapper::new(SystemTable::new_usage(ledger.clone())));
+                ledger
+            }
+            None => match self.system.tables.get(&USAGE).unwrap().data {
+                SystemDataModel::Usage(ref ledger) => ledger.clone(),
+                _ => unsafe { impossible!() },
+            },
+        }
+    }
+    /// Returns the entity catalog, creating the `entities` system table if it doesn't exist
+    pub fn setup_entities(&self) -> EntityCatalog {
+        match self.system.tables.fresh_entry(ENTITIES) {
+            Some(fresh) => {
+                let catalog = EntityCatalog::new(Coremap::new());
+                fresh.insert(Wrapper::new(SystemTable::new_entities(catalog.clone())));
+                catalog
+            }
+            None => match self.system.tables.get(&ENTITIES).unwrap().data {
+                SystemDataModel::Entities(ref catalog) => catalog.clone(),
                 _ => unsafe { impossible!() },
             },
         }
@@ -244,6 +342,31 @@ impl Memstore {
         self.keyspaces
             .true_if_insert(keyspace_identifier, Arc::new(Keyspace::empty()))
     }
+    /// Rename the keyspace `old` to `new`, re-keying it while holding the locks on both
+    /// shards. Connections that have already switched to the keyspace keep their
+    /// reference to it.
+    ///
+    /// This does not touch the disk; see [`Corestore::rename_keyspace`](super::Corestore::rename_keyspace)
+    pub fn rename_keyspace(&self, old: &ObjectID, new: ObjectID) -> KeyspaceResult<()> {
+        let is_protected = |ksid: &ObjectID| ksid.eq(&SYSTEM) || ksid.eq(&DEFAULT);
+        if is_protected(old) || is_protected(&new) {
+            return Err(DdlError::ProtectedObject);
+        }
+        let mut shards = self.keyspaces.lock_shards_of([old, &new].into_iter());
+        if shards.get(old).is_none() {
+            Err(DdlError::ObjectNotFound)
+        } else if shards.get(&new).is_some() {
+            Err(DdlError::AlreadyExists)
+        } else {
+            let (_, keyspace) = shards.remove(old).unwrap();
+            keyspace
+                .tables
+                .iter()
+                .for_each(|table| table.value().key_events().renamed_keyspace(&new));
+            shards.insert(new, keyspace);
+            Ok(())
+        }
+    }
     /// Drop a keyspace only if it is empty and has no clients connected to it
     ///
     /// The invariants maintained here are:
@@ -333,6 +456,64 @@ impl Memstore {
     pub fn list_keyspaces(&self) -> Vec<ObjectID> {
         self.keyspaces.iter().map(|kv| kv.key().clone()).collect()
     }
+    /// Run the write gates for a write to `table` that doesn't go through the query
+    /// dispatcher (like an import): the dropped and read-only flags and the write throttle,
+    /// and if the write allocates, `maxmemory` (evicting keys to make room) and the keyspace
+    /// quota
+    pub fn admit_write(&self, table: &Table, allocating: bool) -> Result<(), WriteRejection> {
+        if table.is_dropped() {
+            return Err(WriteRejection::Dropped);
+        }
+        if table.rejects_writes() {
+            return Err(WriteRejection::ReadOnly);
+        }
+        let throttle = table.write_throttle();
+        if throttle.is_enabled() {
+            throttle
+                .try_acquire()
+                .map_err(|wait| WriteRejection::Throttled((wait.as_millis() as u64).max(1)))?;
+        }
+        if allocating && !self.make_room(Some(table)) {
+            return Err(WriteRejection::OutOfMemory);
+        }
+        if allocating && table.is_over_quota() {
+            return Err(WriteRejection::QuotaExceeded);
+        }
+        Ok(())
+    }
+    /// If the memory limit has been hit, this evicts keys to make room (starting with `table`)
+    /// if the eviction policy allows it. Returns false if a write that allocates should be
+    /// rejected
+    pub fn make_room(&self, table: Option<&Table>) -> bool {
+        if compiler::likely(!eviction::is_over_limit()) {
+            return true;
+        }
+        let policy = eviction::policy();
+        if policy == EvictionPolicy::Reject {
+            return false;
+        }
+        let mut evictions = 0;
+        let mut evict_from = |table: &Table| {
+            while eviction::is_over_limit()
+                && evictions < eviction::MAX_EVICTIONS_PER_WRITE
+                && table.evict(policy)
+            {
+                evictions += 1;
+            }
+        };
+        if let Some(table) = table {
+            evict_from(table);
+        }
+        if eviction::is_over_limit() {
+            // the table has nothing left to give
+            for ks in self.keyspaces.iter() {
+                for table in ks.value().tables.iter() {
+                    evict_from(table.value());
+                }
+            }
+        }
+        !eviction::is_over_limit()
+    }
 }
 
 /// System keyspace
@@ -353,8 +534,20 @@ pub struct Keyspace {
     /// the tables
     pub tables: Coremap<ObjectID, Arc<Table>>,
     /// the replication strategy for this keyspace
-    #[allow(dead_code)] // TODO: Remove this once we're ready with replication
-    replication_strategy: cluster::ReplicationStrategy,
+    replication_strategy: RwLock<cluster::ReplicationStrategy>,
+    /// the table that `use <keyspace>` switches to, if any
+    default_model: RwLock<Option<ObjectID>>,
+    /// the flush interval (in seconds) for this keyspace. if zero, the keyspace is flushed
+    /// on every BGSAVE cycle
+    flush_interval: AtomicU64,
+    /// the time (in seconds since the epoch) when this keyspace was last flushed by BGSAVE
+    last_flushed: AtomicU64,
+    /// the usage counters that haven't been moved to the usage ledger yet
+    usage: UsageCounters,
+    /// the quota that all the tables in this keyspace count towards
+    quota: Arc<Quota>,
+    /// serializes the DDL queries on the tables of this keyspace
+    ddl_lock: Mutex<()>,
 }
 
 #[cfg(test)]
@@ -367,32 +560,90 @@ macro_rules! unsafe_objectid_from_slice {
 impl Keyspace {
     /// Create a new empty keyspace with the default tables: a `default` table
     pub fn empty_default() -> Self {
-        Self {
-            tables: {
-                let ht = Coremap::new();
-                // add the default table
-                ht.true_if_insert(DEFAULT, Arc::new(Table::new_default_kve()));
-                ht
-            },
-            replication_strategy: cluster::ReplicationStrategy::default(),
-        }
+        let ht = Coremap::new();
+        // add the default table
+        ht.true_if_insert(DEFAULT, Arc::new(Table::new_default_kve()));
+        Self::init_with_all_def_strategy(ht)
     }
     pub fn init_with_all_def_strategy(tables: Coremap<ObjectID, Arc<Table>>) -> Self {
+        Self::init_with_all(tables, 0)
+    }
+    pub fn init_with_all(tables: Coremap<ObjectID, Arc<Table>>, flush_interval: u64) -> Self {
+        let quota = Arc::new(Quota::default());
+        for table in tables.iter() {
+            table.value().attach_quota(quota.clone());
+        }
         Self {
             tables,
-            replication_strategy: cluster::ReplicationStrategy::default(),
+            replication_strategy: RwLock::new(cluster::ReplicationStrategy::default()),
+            default_model: RwLock::new(None),
+            flush_interval: AtomicU64::new(flush_interval),
+            last_flushed: AtomicU64::new(os::get_epoch_secs()),
+            usage: UsageCounters::default(),
+            quota,
+            ddl_lock: Mutex::new(()),
         }
     }
     /// Create a new empty keyspace with zero tables
     pub fn empty() -> Self {
-        Self {
-            tables: Coremap::new(),
-            replication_strategy: cluster::ReplicationStrategy::default(),
-        }
+        Self::init_with_all_def_strategy(Coremap::new())
+    }
+    /// Returns the flush interval (in seconds) for this keyspace. Zero means that the keyspace
+    /// follows the BGSAVE cadence
+    pub fn flush_interval(&self) -> u64 {
+        self.flush_interval.load(Ordering::Acquire)
+    }
+    /// Set the flush interval (in seconds) for this keyspace
+    pub fn set_flush_interval(&self, interval: u64) {
+        self.flush_interval.store(interval, Ordering::Release)
+    }
+    /// Check if a BGSAVE cycle running at `now` should flush this keyspace
+    pub fn is_flush_due(&self, now: u64) -> bool {
+        let interval = self.flush_interval();
+        interval == 0 || now.saturating_sub(self.last_flushed.load(Ordering::Acquire)) >= interval
+    }
+    /// Record that BGSAVE flushed this keyspace at `now`
+    pub fn mark_flushed(&self, now: u64) {
+        self.last_flushed.store(now, Ordering::Release)
+    }
+    /// Returns the time (in seconds since the epoch) when this keyspace was last flushed by
+    /// BGSAVE (or loaded, if it hasn't been flushed since)
+    pub fn last_flushed(&self) -> u64 {
+        self.last_flushed.load(Ordering::Acquire)
+    }
+    /// Returns the replication strategy of this keyspace
+    pub fn replication_strategy(&self) -> cluster::ReplicationStrategy {
+        *self.replication_strategy.read()
+    }
+    /// Set the replication strategy of this keyspace
+    pub fn set_replication_strategy(&self, strategy: cluster::ReplicationStrategy) {
+        *self.replication_strategy.write() = strategy
+    }
+    /// Returns the table that `use <keyspace>` switches to, if any. The table may have been
+    /// dropped since
+    pub fn default_model(&self) -> Option<ObjectID> {
+        self.default_model.read().clone()
+    }
+    /// Set (or unset) the table that `use <keyspace>` switches to
+    pub fn set_default_model(&self, model: Option<ObjectID>) {
+        *self.default_model.write() = model
+    }
+    /// Returns the usage counters of this keyspace
+    pub fn usage(&self) -> &UsageCounters {
+        &self.usage
+    }
+    /// Returns the quota of this keyspace
+    pub fn quota(&self) -> &Quota {
+        &self.quota
     }
     pub fn table_count(&self) -> usize {
         self.tables.len()
     }
+    /// Lock this keyspace for a DDL query. This is always taken **before** the global flush
+    /// lock, so that a DDL query doesn't hold up BGSAVE while it waits for another one
+    pub fn lock_ddl(&self) -> MutexGuard<'_, ()> {
+        self.ddl_lock.lock()
+    }
     /// Get an atomic reference to a table in this keyspace if it exists
     pub fn get_table_atomic_ref<Q>(&self, table_identifier: &Q) -> Option<Arc<Table>>
     where
@@ -403,13 +654,44 @@ impl Keyspace {
     }
     /// Create a new table
     pub fn create_table(&self, tableid: ObjectID, table: Table) -> bool {
+        table.attach_quota(self.quota.clone());
         self.tables.true_if_insert(tableid, Arc::new(table))
     }
-    /// Drop a table if it exists, if it is not forbidden and if no one references
-    /// back to it. We don't want any looming table references i.e table gets deleted
-    /// for the current connection and newer connections, but older instances still
-    /// refer to the table.
-    // FIXME(@ohsayan): Should we actually care?
+    /// Create the table `dst` with a copy of the entries of the table `src`. The copy has the
+    /// same model as `src` and, unless `volatile` says otherwise, the same volatility. Writes
+    /// to `src` that race with the copy may or may not make it into `dst`
+    ///
+    /// **Trip switch handled:** Yes
+    pub fn copy_table(
+        &self,
+        src: &ObjectID,
+        dst: ObjectID,
+        volatile: Option<bool>,
+    ) -> KeyspaceResult<()> {
+        let table = self
+            .get_table_atomic_ref(src)
+            .ok_or(DdlError::ObjectNotFound)?;
+        if self.tables.contains_key(&dst) {
+            return Err(DdlError::AlreadyExists);
+        }
+        let volatile = volatile.unwrap_or_else(|| table.is_volatile());
+        let copy = unflush::copy_table(&table, volatile).map_err(|e| {
+            log::error!("Failed to copy table with error: {e}");
+            DdlError::DdlTransactionFailure
+        })?;
+        if self.create_table(dst, copy) {
+            // we need to re-init tree; so trip
+            registry::get_preload_tripswitch().trip();
+            Ok(())
+        } else {
+            Err(DdlError::AlreadyExists)
+        }
+    }
+    /// Drop a table if it exists, if it is not forbidden and if it is empty (or the drop is
+    /// forced). The table is removed from the keyspace right away, so new lookups fail, but
+    /// connections that still refer to it keep their reference until they switch away. The
+    /// table is marked as dropped so that those connections can't write to it, and its
+    /// memory is released along with the last reference
     ///
     /// **Trip switch handled:** Yes
     fn drop_table_inner<Q>(&self, table_identifier: &Q, should_force: bool) -> KeyspaceResult<()>
@@ -423,21 +705,21 @@ impl Keyspace {
             Err(DdlError::ObjectNotFound)
         } else {
             // has table
-            let did_remove =
-                self.tables
-                    .true_remove_if(table_identifier, |_table_id, table_atomic_ref| {
-                        // 1 because this should just be us, the one instance
-                        Arc::strong_count(table_atomic_ref) == 1
-                            && (table_atomic_ref.is_empty() || should_force)
-                    });
-            if did_remove {
-                // we need to re-init tree; so trip
-                registry::get_preload_tripswitch().trip();
-                // we need to cleanup tree; so trip
-                registry::get_cleanup_tripswitch().trip();
-                Ok(())
-            } else {
-                Err(DdlError::StillInUse)
+            let removed = self
+                .tables
+                .remove_if(table_identifier, |_table_id, table_atomic_ref| {
+                    table_atomic_ref.is_empty() || should_force
+                });
+            match removed {
+                Some((_, table)) => {
+                    table.mark_dropped();
+                    // we need to re-init tree; so trip
+                    registry::get_preload_tripswitch().trip();
+                    // we need to cleanup tree; so trip
+                    registry::get_cleanup_tripswitch().trip();
+                    Ok(())
+                }
+                None => Err(DdlError::StillInUse),
             }
         }
     }
@@ -448,6 +730,102 @@ impl Keyspace {
     {
         self.drop_table_inner(tblid, force)
     }
+    /// Rename the table `old` to `new`. Both shards are locked for the duration of the
+    /// re-keying, so other connections either see the table under `old` or under `new`
+    /// but never under both or neither. Connections that have already switched to the
+    /// table keep their reference to it.
+    ///
+    /// This does not touch the disk; see [`Corestore::rename_table`](super::Corestore::rename_table)
+    pub fn rename_table(&self, old: &ObjectID, new: ObjectID) -> KeyspaceResult<()> {
+        if old.eq(&DEFAULT) || new.eq(&DEFAULT) {
+            return Err(DdlError::ProtectedObject);
+        }
+        let mut shards = self.tables.lock_shards_of([old, &new].into_iter());
+        if shards.get(old).is_none() {
+            Err(DdlError::ObjectNotFound)
+        } else if shards.get(&new).is_some() {
+            Err(DdlError::AlreadyExists)
+        } else {
+            let mut default_model = self.default_model.write();
+            if default_model.as_ref() == Some(old) {
+                *default_model = Some(new.clone());
+            }
+            let (_, table) = shards.remove(old).unwrap();
+            table.key_events().renamed_table(&new);
+            shards.insert(new, table);
+            Ok(())
+        }
+    }
+}
+
+#[test]
+fn test_keyspace_rename_table() {
+    let our_keyspace = Keyspace::empty_default();
+    let apps = unsafe_objectid_from_slice!("apps");
+    let apps2 = unsafe_objectid_from_slice!("apps2");
+    assert!(our_keyspace.create_table(apps.clone(), Table::new_default_kve()));
+    let tbl = our_keyspace.get_table_atomic_ref(&apps).unwrap();
+    // renames go through even if someone holds a reference
+    our_keyspace.rename_table(&apps, apps2.clone()).unwrap();
+    assert!(our_keyspace.get_table_atomic_ref(&apps).is_none());
+    assert!(Arc::ptr_eq(
+        &tbl,
+        &our_keyspace.get_table_atomic_ref(&apps2).unwrap()
+    ));
+    assert_eq!(
+        our_keyspace.rename_table(&apps, apps2.clone()).unwrap_err(),
+        DdlError::ObjectNotFound
+    );
+    assert!(our_keyspace.create_table(apps.clone(), Table::new_default_kve()));
+    assert_eq!(
+        our_keyspace.rename_table(&apps, apps2.clone()).unwrap_err(),
+        DdlError::AlreadyExists
+    );
+    assert_eq!(
+        our_keyspace.rename_table(&DEFAULT, apps).unwrap_err(),
+        DdlError::ProtectedObject
+    );
+    // the default model follows the table it refers to
+    let apps3 = unsafe_objectid_from_slice!("apps3");
+    our_keyspace.set_default_model(Some(apps2.clone()));
+    our_keyspace.rename_table(&apps2, apps3.clone()).unwrap();
+    assert_eq!(our_keyspace.default_model(), Some(apps3));
+}
+
+#[test]
+fn test_keyspace_copy_table() {
+    let our_keyspace = Keyspace::empty_default();
+    let apps = unsafe_objectid_from_slice!("apps");
+    let apps_copy = unsafe_objectid_from_slice!("apps_copy");
+    assert!(our_keyspace.create_table(apps.clone(), Table::new_default_kve()));
+    let tbl = our_keyspace.get_table_atomic_ref(&apps).unwrap();
+    let kve = tbl.get_kvstore().unwrap();
+    kve.set("hello".into(), "world".into()).unwrap();
+    our_keyspace
+        .copy_table(&apps, apps_copy.clone(), Some(true))
+        .unwrap();
+    kve.set("hey".into(), "there".into()).unwrap();
+    let copy = our_keyspace.get_table_atomic_ref(&apps_copy).unwrap();
+    assert!(copy.is_volatile());
+    assert_eq!(copy.count(), 1);
+    assert_eq!(
+        copy.get_kvstore()
+            .unwrap()
+            .get_cloned("hello")
+            .unwrap()
+            .unwrap(),
+        "world"
+    );
+    assert_eq!(
+        our_keyspace.copy_table(&apps, apps_copy, None).unwrap_err(),
+        DdlError::AlreadyExists
+    );
+    assert_eq!(
+        our_keyspace
+            .copy_table(&unsafe_objectid_from_slice!("nope"), apps, None)
+            .unwrap_err(),
+        DdlError::ObjectNotFound
+    );
 }
 
 #[test]
@@ -463,21 +841,49 @@ fn test_keyspace_drop_no_atomic_ref() {
 }
 
 #[test]
-fn test_keyspace_drop_fail_with_atomic_ref() {
+fn test_keyspace_drop_with_atomic_ref() {
     let our_keyspace = Keyspace::empty_default();
     assert!(our_keyspace.create_table(
         unsafe_objectid_from_slice!("apps"),
         Table::new_default_kve()
     ));
-    let _atomic_tbl_ref = our_keyspace
+    let atomic_tbl_ref = our_keyspace
         .get_table_atomic_ref(&unsafe_objectid_from_slice!("apps"))
         .unwrap();
+    assert!(our_keyspace
+        .drop_table(&unsafe_objectid_from_slice!("apps"), false)
+        .is_ok());
+    // gone for new lookups, but still around for us
+    assert!(our_keyspace
+        .get_table_atomic_ref(&unsafe_objectid_from_slice!("apps"))
+        .is_none());
+    // but we can't write to it anymore
+    assert!(atomic_tbl_ref.is_dropped());
     assert_eq!(
-        our_keyspace
-            .drop_table(&unsafe_objectid_from_slice!("apps"), false)
-            .unwrap_err(),
+        Memstore::new_empty().admit_write(&atomic_tbl_ref, false),
+        Err(WriteRejection::Dropped)
+    );
+}
+
+#[test]
+fn test_keyspace_drop_nonempty_fails() {
+    use crate::corestore::table::DataModel;
+    let our_keyspace = Keyspace::empty_default();
+    let apps = unsafe_objectid_from_slice!("apps");
+    assert!(our_keyspace.create_table(apps.clone(), Table::new_default_kve()));
+    match our_keyspace
+        .get_table_atomic_ref(&apps)
+        .unwrap()
+        .get_model_ref()
+    {
+        DataModel::KV(kve) => kve.set("k".into(), "v".into()).unwrap(),
+        _ => panic!("not a key/value table"),
+    };
+    assert_eq!(
+        our_keyspace.drop_table(&apps, false).unwrap_err(),
         DdlError::StillInUse
     );
+    assert!(our_keyspace.get_table_atomic_ref(&apps).is_some());
 }
 
 #[test]
@@ -490,3 +896,46 @@ fn test_keyspace_try_delete_protected_table() {
         DdlError::ProtectedObject
     );
 }
+
+#[test]
+fn test_keyspace_flush_interval() {
+    let our_keyspace = Keyspace::empty_default();
+    let now = os::get_epoch_secs();
+    // no interval; always due
+    assert!(our_keyspace.is_flush_due(now));
+    our_keyspace.set_flush_interval(60);
+    assert!(!our_keyspace.is_flush_due(now));
+    assert!(our_keyspace.is_flush_due(now + 60));
+    our_keyspace.mark_flushed(now + 60);
+    assert!(!our_keyspace.is_flush_due(now + 90));
+}
+
+#[test]
+fn test_keyspace_quota() {
+    use crate::corestore::table::DataModel;
+    let our_keyspace = Keyspace::empty_default();
+    our_keyspace.quota().set_max_keys(2);
+    let apps = unsafe_objectid_from_slice!("apps");
+    assert!(our_keyspace.create_table(apps.clone(), Table::new_default_kve()));
+    let kve = |table: &ObjectID| match our_keyspace.get_table_atomic_ref(table) {
+        Some(table) => match table.get_model_ref() {
+            DataModel::KV(kve) => kve.set("k".into(), "v".into()).unwrap(),
+            _ => panic!("not a key/value table"),
+        },
+        None => panic!("no such table"),
+    };
+    // every table in the keyspace counts towards the same quota
+    kve(&DEFAULT);
+    assert!(!our_keyspace.quota().is_over_limit());
+    kve(&apps);
+    assert_eq!(our_keyspace.quota().keys(), 2);
+    assert!(our_keyspace.quota().is_over_limit());
+    assert!(our_keyspace
+        .get_table_atomic_ref(&apps)
+        .unwrap()
+        .is_over_quota());
+    // dropping a table gives its keys back (the table isn't empty, so it has to be forced)
+    our_keyspace.drop_table(&apps, true).unwrap();
+    assert_eq!(our_keyspace.quota().keys(), 1);
+    assert!(!our_keyspace.quota().is_over_limit());
+}
@@ -27,16 +27,29 @@
 use {
     crate::{
         actions::{translate_ddl_error, ActionResult},
-        blueql::Entity,
+        blueql::{self, Entity, SpaceProperty},
         corestore::{
-            memstore::{DdlError, Keyspace, Memstore, ObjectID, DEFAULT},
+            catalog::EntityCatalog,
+            compare::{Baseline, CompareError, TableDiff},
+            export::{ExportError, ExportFormat, ExportTarget},
+            import::ImportReport,
+            memstore::{DdlError, Keyspace, Memstore, ObjectID, DEFAULT, SYSTEM},
             table::{DescribeTable, Table},
         },
+        dbnet::pubsub,
+        kvengine::txn::Transaction,
         protocol::interface::ProtocolSpec,
         registry,
         storage::{
             self,
-            v1::{error::StorageEngineResult, sengine::SnapshotEngine},
+            v1::{
+                backup::{self, BackupError},
+                error::StorageEngineResult,
+                interface,
+                sengine::SnapshotEngine,
+                spacearchive::{self, SpaceArchiveError},
+                unflush,
+            },
         },
         util::{self, Unwrappable},
     },
@@ -48,17 +61,23 @@ pub mod array;
 pub mod backoff;
 pub mod booltable;
 pub mod buffers;
+pub mod catalog;
+pub mod compare;
+pub mod export;
 pub mod heap_array;
 pub mod htable;
 pub mod iarray;
+pub mod import;
 pub mod lazy;
 pub mod lock;
 pub mod map;
 pub mod memstore;
+pub mod objectid;
 pub mod rc;
 pub mod table;
 #[cfg(test)]
 mod tests;
+pub mod usage;
 
 pub use self::rc::SharedSlice;
 
@@ -79,9 +98,12 @@ impl ConnectionEntityState {
             ks: Some((DEFAULT, ks)),
         }
     }
+    /// Switch to the keyspace, and to its default model if it has one (and it still exists)
     fn set_ks(&mut self, ks: Arc<Keyspace>, ksid: ObjectID) {
+        self.table = ks
+            .default_model()
+            .and_then(|tblid| Some((tblid.clone(), ks.get_table_atomic_ref(&tblid)?)));
         self.ks = Some((ksid, ks));
-        self.table = None;
     }
     fn set_table(&mut self, ks: Arc<Keyspace>, ksid: ObjectID, tbl: Arc<Table>, tblid: ObjectID) {
         self.ks = Some((ksid, ks));
@@ -102,6 +124,8 @@ impl ConnectionEntityState {
 #[derive(Debug, Clone)]
 pub struct Corestore {
     estate: ConnectionEntityState,
+    /// the transaction that the connection has started with `MULTI`, if any
+    txn: Option<Transaction>,
     /// an atomic reference to the actual backing storage
     store: Arc<Memstore>,
     /// the snapshot engine
@@ -123,10 +147,32 @@ impl Corestore {
         let ctable = unsafe { cks.get_table_atomic_ref(&DEFAULT).unsafe_unwrap() };
         Self {
             estate: ConnectionEntityState::default(cks, ctable),
+            txn: None,
             store: Arc::new(store),
             sengine,
         }
     }
+    /// Switch to the entity that new connections start in (`server.default_entity`). If it
+    /// wasn't set or doesn't exist (anymore), this stays in `default.default`
+    pub fn use_default_entity(&mut self) {
+        let entity = match registry::get_default_entity() {
+            Some(entity) => entity,
+            None => return,
+        };
+        let ks = match self.store.get_keyspace_atomic_ref(&entity.keyspace) {
+            Some(ks) => ks,
+            None => return,
+        };
+        match &entity.table {
+            Some(tblid) => {
+                if let Some(tbl) = ks.get_table_atomic_ref(tblid) {
+                    self.estate
+                        .set_table(ks, entity.keyspace.clone(), tbl, tblid.clone());
+                }
+            }
+            None => self.estate.set_ks(ks, entity.keyspace.clone()),
+        }
+    }
     pub fn get_engine(&self) -> &SnapshotEngine {
         &self.sengine
     }
@@ -219,6 +265,22 @@ impl Corestore {
     pub fn get_ctable_ref(&self) -> Option<&Table> {
         self.estate.table.as_ref().map(|(_, tbl)| tbl.as_ref())
     }
+    /// Start a transaction for this connection. Returns false if one has already been started
+    pub fn begin_txn(&mut self) -> bool {
+        let fresh = self.txn.is_none();
+        if fresh {
+            self.txn = Some(Transaction::new());
+        }
+        fresh
+    }
+    /// Returns the transaction that this connection has started, if any
+    pub fn txn_mut(&mut self) -> Option<&mut Transaction> {
+        self.txn.as_mut()
+    }
+    /// End the transaction that this connection has started, returning it
+    pub fn take_txn(&mut self) -> Option<Transaction> {
+        self.txn.take()
+    }
     /// Returns a table with the provided specification
     pub fn get_table_with<P: ProtocolSpec, T: DescribeTable>(&self) -> ActionResult<&T::Table> {
         T::get::<P>(self)
@@ -228,7 +290,8 @@ impl Corestore {
     /// system is close to a flush cycle -- then we are in luck: we pause the flush cycle
     /// through a global flush lock and then allow it to resume once we're done adding the table.
     /// This enables the flush routine to permanently write the table to disk. But it's all about
-    /// luck -- the next mutual access may be yielded to the next `create table` command
+    /// luck -- the next mutual access may be yielded to the next `create table` command.
+    /// The table gets the default number of shards unless `shards` is provided
     ///
     /// **Trip switch handled:** Yes
     pub fn create_table(
@@ -236,81 +299,129 @@ impl Corestore {
         entity: &Entity,
         modelcode: u8,
         volatile: bool,
+        shards: Option<usize>,
     ) -> KeyspaceResult<()> {
-        // first lock the global flush state
+        let new_table = || {
+            let tbl = Table::from_model_code(modelcode, volatile)?;
+            Some(match shards {
+                Some(shards) => tbl.with_shards(shards),
+                None => tbl,
+            })
+        };
+        let (ksid, ks, tblid) = self.get_keyspace_of(entity)?;
+        let table = new_table().ok_or(DdlError::WrongModel)?;
+        // first serialize with the other DDL queries on the keyspace, and then lock the global
+        // flush state so that BGSAVE never sees a half-created table
+        let ddl_lock = ks.lock_ddl();
         let flush_lock = registry::lock_flush_state();
-        let ret = match entity {
-            // Important: create table <tblname> is only ks
-            Entity::Current(tblid) => {
-                match &self.estate.ks {
-                    Some((_, ks)) => {
-                        let tbl = Table::from_model_code(modelcode, volatile);
-                        if let Some(tbl) = tbl {
-                            if ks.create_table(
-                                unsafe { ObjectID::from_slice(tblid.as_slice()) },
-                                tbl,
-                            ) {
-                                // we need to re-init tree; so trip
-                                registry::get_preload_tripswitch().trip();
-                                Ok(())
-                            } else {
-                                Err(DdlError::AlreadyExists)
-                            }
-                        } else {
-                            Err(DdlError::WrongModel)
-                        }
-                    }
-                    None => Err(DdlError::DefaultNotFound),
-                }
-            }
-            Entity::Full(ksid, tblid) => {
-                match self
-                    .store
-                    .get_keyspace_atomic_ref(unsafe { ksid.as_slice() })
-                {
-                    Some(kspace) => {
-                        let tbl = Table::from_model_code(modelcode, volatile);
-                        if let Some(tbl) = tbl {
-                            if kspace.create_table(
-                                unsafe { ObjectID::from_slice(tblid.as_slice()) },
-                                tbl,
-                            ) {
-                                // trip the preload switch
-                                registry::get_preload_tripswitch().trip();
-                                Ok(())
-                            } else {
-                                Err(DdlError::AlreadyExists)
-                            }
-                        } else {
-                            Err(DdlError::WrongModel)
-                        }
-                    }
-                    None => Err(DdlError::ObjectNotFound),
-                }
-            }
+        let ret = if ks.create_table(tblid.clone(), table) {
+            // we need to re-init tree; so trip
+            registry::get_preload_tripswitch().trip();
+            let now = util::os::get_epoch_secs();
+            catalog::record_created(&self.entities(), &ksid, &tblid, modelcode, now);
+            Ok(())
+        } else {
+            Err(DdlError::AlreadyExists)
         };
-        // free the global flush lock
         drop(flush_lock);
+        drop(ddl_lock);
         ret
     }
 
     /// Drop a table
     pub fn drop_table(&self, entity: &Entity, force: bool) -> KeyspaceResult<()> {
-        match entity {
-            Entity::Current(tblid) => match &self.estate.ks {
-                Some((_, ks)) => ks.drop_table(unsafe { tblid.as_slice() }, force),
-                None => Err(DdlError::DefaultNotFound),
-            },
-            Entity::Full(ksid, tblid) => {
-                match self
-                    .store
-                    .get_keyspace_atomic_ref(unsafe { ksid.as_slice() })
-                {
-                    Some(ks) => ks.drop_table(unsafe { tblid.as_slice() }, force),
-                    None => Err(DdlError::ObjectNotFound),
+        let (ksid, ks, tblid) = self.get_keyspace_of(entity)?;
+        // lock the keyspace and the global flush state (see comment in create_table to know why)
+        let ddl_lock = ks.lock_ddl();
+        let flush_lock = registry::lock_flush_state();
+        let drop_event = ks
+            .get_table_atomic_ref(&tblid)
+            .and_then(|table| table.key_events().drop_event());
+        let ret = ks.drop_table(&tblid, force);
+        if ret.is_ok() {
+            catalog::record_dropped(&self.entities(), &ksid, &tblid);
+        }
+        drop(flush_lock);
+        drop(ddl_lock);
+        if let (Ok(()), Some((channel, table))) = (&ret, drop_event) {
+            pubsub::publish(&channel, &table);
+        }
+        ret
+    }
+
+    /// Resolve `entity` into the ID and a reference of its keyspace, along with the ID of the
+    /// table. The table itself may or may not exist
+    fn get_keyspace_of(
+        &self,
+        entity: &Entity,
+    ) -> KeyspaceResult<(ObjectID, Arc<Keyspace>, ObjectID)> {
+        let (ksid, tblid) = self.get_entity_ids(entity)?;
+        let ks = match entity {
+            Entity::Current(_) => self.estate.ks.as_ref().map(|(_, ks)| ks.clone()),
+            Entity::Full(..) => self.store.get_keyspace_atomic_ref(&ksid),
+        };
+        ks.map(|ks| (ksid, ks, tblid))
+            .ok_or(DdlError::ObjectNotFound)
+    }
+
+    /// Copy the table `src` into the new table `dst` of the same keyspace (see
+    /// [`Keyspace::copy_table`])
+    ///
+    /// **Trip switch handled:** Yes
+    pub fn copy_table(
+        &self,
+        src: &Entity,
+        dst: ObjectID,
+        volatile: Option<bool>,
+    ) -> KeyspaceResult<()> {
+        let (ksid, ks, src) = self.get_keyspace_of(src)?;
+        if ksid.eq(&SYSTEM) {
+            return Err(DdlError::ProtectedObject);
+        }
+        if registry::is_read_only() {
+            return Err(DdlError::ReadOnly);
+        }
+        // lock the keyspace and the global flush state (see comment in create_table to know why)
+        let ddl_lock = ks.lock_ddl();
+        let flush_lock = registry::lock_flush_state();
+        let ret = ks.copy_table(&src, dst.clone(), volatile);
+        if let (Ok(()), Some(table)) = (&ret, ks.get_table_atomic_ref(&src)) {
+            let (model_code, now) = (table.get_model_code(), util::os::get_epoch_secs());
+            catalog::record_created(&self.entities(), &ksid, &dst, model_code, now);
+        }
+        drop(flush_lock);
+        drop(ddl_lock);
+        ret
+    }
+
+    /// Rename a table. The flush lock is held while the table is re-keyed in memory and
+    /// while its files are moved on disk, so a BGSAVE cycle never sees one without the other.
+    /// If the files can't be moved, the table keeps its old name
+    pub fn rename_table(&self, entity: &Entity, new: ObjectID) -> KeyspaceResult<()> {
+        let (ksid, ks, old) = self.get_keyspace_of(entity)?;
+        if ksid.eq(&SYSTEM) {
+            return Err(DdlError::ProtectedObject);
+        }
+        let ddl_lock = ks.lock_ddl();
+        let flush_lock = registry::lock_flush_state();
+        let ret = ks.rename_table(&old, new.clone()).and_then(|()| {
+            interface::rename_table(&ksid, &ks, &old, &new).map_err(|e| {
+                log::error!("Failed to rename table files on disk with error: {e}");
+                // the files still (at least partly) go by the old name, so put the table
+                // back under it and move whatever was already moved on disk back too
+                let _ = ks.rename_table(&new, old.clone());
+                if let Err(e) = interface::rename_table(&ksid, &ks, &new, &old) {
+                    log::error!("Failed to undo the rename of table files with error: {e}");
                 }
-            }
+                DdlError::DdlTransactionFailure
+            })
+        });
+        if ret.is_ok() {
+            catalog::record_renamed(&self.entities(), &ksid, &old, &new);
         }
+        drop(flush_lock);
+        drop(ddl_lock);
+        ret
     }
 
     /// Create a keyspace **without any transactional guarantees**
@@ -332,16 +443,355 @@ impl Corestore {
         ret
     }
 
-    /// Drop a keyspace
+    /// Drop a keyspace. This doesn't need the keyspace's DDL lock: a DDL query on the
+    /// keyspace holds a reference to it for as long as it runs, and the drop fails with
+    /// [`DdlError::StillInUse`] while the keyspace is referenced
     pub fn drop_keyspace(&self, ksid: ObjectID) -> KeyspaceResult<()> {
+        let _flush_lock = registry::lock_flush_state();
         // trip switch is handled by memstore here
         self.store.drop_keyspace(ksid)
     }
 
-    /// Force drop a keyspace
+    /// Rename a keyspace. Like [`Corestore::rename_table`], this holds the flush lock while
+    /// the keyspace is re-keyed in memory and while its directory is moved on disk
+    pub fn rename_keyspace(&self, old: ObjectID, new: ObjectID) -> KeyspaceResult<()> {
+        let ks = self.store.get_keyspace_atomic_ref(&old);
+        let ddl_lock = ks.as_ref().map(|ks| ks.lock_ddl());
+        let flush_lock = registry::lock_flush_state();
+        let ret = self
+            .store
+            .rename_keyspace(&old, new.clone())
+            .and_then(|()| {
+                interface::rename_keyspace(&self.store, &old, &new).map_err(|e| {
+                    log::error!("Failed to rename keyspace directory on disk with error: {e}");
+                    DdlError::DdlTransactionFailure
+                })
+            });
+        drop(flush_lock);
+        drop(ddl_lock);
+        ret
+    }
+
+    /// Alter a property of a keyspace
+    pub fn alter_keyspace(&self, ksid: ObjectID, property: &SpaceProperty) -> KeyspaceResult<()> {
+        if ksid.eq(&SYSTEM) {
+            return Err(DdlError::ProtectedObject);
+        }
+        let ks = self
+            .store
+            .get_keyspace_atomic_ref(&ksid)
+            .ok_or(DdlError::ObjectNotFound)?;
+        let _ddl_lock = ks.lock_ddl();
+        match property {
+            // picked up by the next BGSAVE cycle (which also persists it)
+            SpaceProperty::FlushInterval(interval) => ks.set_flush_interval(*interval),
+            // enforced right away
+            SpaceProperty::MaxKeys(max_keys) => ks.quota().set_max_keys(*max_keys),
+            SpaceProperty::MaxBytes(max_bytes) => ks.quota().set_max_bytes(*max_bytes),
+            SpaceProperty::DefaultModel(model) => {
+                let model = model
+                    .as_ref()
+                    .map(|model| unsafe { ObjectID::from_slice(model) });
+                // the model has to exist when it's set, but it may be dropped later
+                if let Some(tblid) = &model {
+                    if ks.get_table_atomic_ref(tblid).is_none() {
+                        return Err(DdlError::ObjectNotFound);
+                    }
+                }
+                ks.set_default_model(model)
+            }
+            SpaceProperty::ReplicationStrategy(strategy) => ks.set_replication_strategy(*strategy),
+        }
+        Ok(())
+    }
+
+    /// Back up the given keyspaces into an archive called `name` (see [`backup`])
+    pub async fn backup(&self, name: String, keyspaces: Vec<ObjectID>) -> KeyspaceResult<()> {
+        let store = self.clone_store();
+        let ret = tokio::task::spawn_blocking(move || backup::create(&store, &name, &keyspaces))
+            .await
+            .expect("backup thread panicked");
+        match ret {
+            Ok(()) => {
+                log::info!("Successfully created backup");
+                Ok(())
+            }
+            Err(BackupError::Ddl(e)) => Err(e),
+            Err(BackupError::Io(e)) => {
+                log::error!("Failed to create backup with error: {e}");
+                Err(DdlError::DdlTransactionFailure)
+            }
+        }
+    }
+
+    /// Export `target` as `format` and return the path to the export (see [`export`])
+    pub async fn export(
+        &self,
+        target: ExportTarget,
+        format: ExportFormat,
+    ) -> KeyspaceResult<String> {
+        let store = self.clone_store();
+        let ret = tokio::task::spawn_blocking(move || export::export(&store, &target, format))
+            .await
+            .expect("export thread panicked");
+        match ret {
+            Ok(path) => {
+                log::info!("Exported data to `{path}`");
+                Ok(path)
+            }
+            Err(ExportError::Ddl(e)) => Err(e),
+            Err(ExportError::Io(e)) => {
+                log::error!("Failed to export data with error: {e}");
+                Err(DdlError::DdlTransactionFailure)
+            }
+        }
+    }
+
+    /// Import the file at `path` into the table `entity` (see [`import`])
+    pub async fn import(
+        &self,
+        entity: &Entity,
+        path: String,
+        format: ExportFormat,
+        continue_on_error: bool,
+    ) -> KeyspaceResult<ImportReport> {
+        let (ksid, _) = self.get_entity_ids(entity)?;
+        if ksid.eq(&SYSTEM) {
+            return Err(DdlError::ProtectedObject);
+        }
+        let table = self.get_table(entity)?;
+        if table.rejects_writes() {
+            return Err(DdlError::ReadOnly);
+        }
+        let store = self.store.clone();
+        let ret = tokio::task::spawn_blocking(move || {
+            import::import_file(&store, &table, &path, format, continue_on_error)
+        })
+        .await
+        .expect("import thread panicked");
+        match ret {
+            Ok(report) => {
+                log::info!(
+                    "Imported {} record(s) with {} bad record(s)",
+                    report.imported,
+                    report.failed
+                );
+                if let Some(rejection) = report.stopped {
+                    log::warn!("Import stopped early: {}", rejection.as_str());
+                }
+                Ok(report)
+            }
+            Err(e) => {
+                log::error!("Failed to import data with error: {e}");
+                Err(DdlError::DdlTransactionFailure)
+            }
+        }
+    }
+
+    /// Compare the table `entity` against a baseline (see [`compare`](self::compare))
+    pub async fn compare(
+        &self,
+        entity: &Entity,
+        baseline: Baseline<'_>,
+    ) -> KeyspaceResult<TableDiff> {
+        let table = self.get_table(entity)?;
+        // either the baseline table, or where to find it in a snapshot
+        let baseline = match baseline {
+            Baseline::Table(baseline) => Ok(self.get_table(baseline)?),
+            Baseline::Snapshot(snapshot) => Err((snapshot, self.get_entity_ids(entity)?)),
+        };
+        // this walks over both tables (and maybe reads a snapshot), so don't block the runtime
+        let ret = tokio::task::spawn_blocking(move || {
+            let baseline = match baseline {
+                Ok(baseline) => baseline,
+                Err((snapshot, (ksid, tblid))) => {
+                    let keyspace = match unflush::read_keyspace_from_snapshot(&snapshot, &ksid) {
+                        Ok(Some(keyspace)) => keyspace,
+                        Ok(None) => return Err(DdlError::ObjectNotFound.into()),
+                        Err(e) => {
+                            log::error!("Failed to read snapshot for comparison with error: {e}");
+                            return Err(DdlError::DdlTransactionFailure.into());
+                        }
+                    };
+                    match keyspace.get_table_atomic_ref(&tblid) {
+                        Some(table) => table,
+                        None => return Err(DdlError::ObjectNotFound.into()),
+                    }
+                }
+            };
+            compare::compare(&table, &baseline)
+        })
+        .await
+        .expect("compare thread panicked");
+        match ret {
+            Ok(diff) => Ok(diff),
+            Err(CompareError::Ddl(e)) => Err(e),
+            Err(CompareError::Io(e)) => {
+                log::error!("Failed to compare tables with error: {e}");
+                Err(DdlError::DdlTransactionFailure)
+            }
+        }
+    }
+    /// Returns the keyspace and table IDs for the given entity
+    pub fn get_entity_ids(&self, entity: &Entity) -> KeyspaceResult<(ObjectID, ObjectID)> {
+        match entity {
+            Entity::Full(ksid, tblid) => unsafe {
+                Ok((
+                    ObjectID::from_slice(ksid.as_slice()),
+                    ObjectID::from_slice(tblid.as_slice()),
+                ))
+            },
+            Entity::Current(tblid) => match self.estate.get_id_pack() {
+                (Some(ksid), _) => Ok((ksid.clone(), unsafe {
+                    ObjectID::from_slice(tblid.as_slice())
+                })),
+                (None, _) => Err(DdlError::DefaultNotFound),
+            },
+        }
+    }
+
+    /// Restore the keyspace `ksid` from the snapshot called `snapshot` as `target`. The target
+    /// keyspace must not exist
+    pub async fn restore(
+        &self,
+        snapshot: String,
+        ksid: ObjectID,
+        target: ObjectID,
+    ) -> KeyspaceResult<()> {
+        if ksid.eq(&SYSTEM) || target.eq(&SYSTEM) {
+            return Err(DdlError::ProtectedObject);
+        }
+        if registry::is_read_only() {
+            return Err(DdlError::ReadOnly);
+        }
+        if self.store.keyspaces.contains_key(&target) {
+            return Err(DdlError::AlreadyExists);
+        }
+        // reading the snapshot can take a while, so don't block the runtime
+        let ret = tokio::task::spawn_blocking(move || {
+            unflush::read_keyspace_from_snapshot(&snapshot, &ksid)
+        })
+        .await
+        .expect("restore thread panicked");
+        let keyspace = match ret {
+            Ok(Some(keyspace)) => keyspace,
+            Ok(None) => return Err(DdlError::ObjectNotFound),
+            Err(e) => {
+                log::error!("Failed to restore keyspace with error: {e}");
+                return Err(DdlError::DdlTransactionFailure);
+            }
+        };
+        self.install_keyspace(target, keyspace)?;
+        log::info!("Successfully restored keyspace");
+        Ok(())
+    }
+
+    /// Add a keyspace that was read from elsewhere (a snapshot or an archive) as `target`
+    fn install_keyspace(&self, target: ObjectID, keyspace: Keyspace) -> KeyspaceResult<()> {
+        // lock the global flush lock (see comment in create_table to know why)
+        let flush_lock = registry::lock_flush_state();
+        let created = self
+            .store
+            .keyspaces
+            .true_if_insert(target, Arc::new(keyspace));
+        let ret = if created {
+            // the new keyspace needs its tree and a place in the preload
+            registry::get_preload_tripswitch().trip();
+            Ok(())
+        } else {
+            // someone created it while we were reading it
+            Err(DdlError::AlreadyExists)
+        };
+        drop(flush_lock);
+        ret
+    }
+
+    /// Export the keyspace `ksid` to a new space archive called `name` (see [`spacearchive`])
+    pub async fn export_space(
+        &self,
+        ksid: ObjectID,
+        name: String,
+    ) -> Result<(), SpaceArchiveError> {
+        if ksid.eq(&SYSTEM) {
+            return Err(DdlError::ProtectedObject.into());
+        }
+        let store = self.clone_store();
+        let ret = tokio::task::spawn_blocking(move || spacearchive::export(&store, &ksid, &name))
+            .await
+            .expect("space export thread panicked");
+        match &ret {
+            Ok(()) => log::info!("Successfully exported keyspace"),
+            Err(SpaceArchiveError::Io(e)) => {
+                log::error!("Failed to export keyspace with error: {e}")
+            }
+            Err(_) => {}
+        }
+        ret
+    }
+
+    /// Import the keyspace in the space archive called `name`, as `target` if given or under
+    /// its original name otherwise. The keyspace must not exist
+    pub async fn import_space(
+        &self,
+        name: String,
+        target: Option<ObjectID>,
+    ) -> Result<(), SpaceArchiveError> {
+        if target.as_ref().is_some_and(|target| target.eq(&SYSTEM)) {
+            return Err(DdlError::ProtectedObject.into());
+        }
+        if registry::is_read_only() {
+            return Err(DdlError::ReadOnly.into());
+        }
+        // verifying and reading the archive can take a while, so don't block the runtime
+        let ret = tokio::task::spawn_blocking(move || spacearchive::import(&name))
+            .await
+            .expect("space import thread panicked");
+        let (ksid, keyspace) = match ret {
+            Ok(ret) => ret,
+            Err(SpaceArchiveError::Corrupted(reason)) => {
+                log::error!("Refusing to import corrupted space archive: {reason}");
+                return Err(SpaceArchiveError::Corrupted(reason));
+            }
+            Err(SpaceArchiveError::Io(e)) => {
+                log::error!("Failed to import keyspace with error: {e}");
+                return Err(SpaceArchiveError::Io(e));
+            }
+            Err(e) => return Err(e),
+        };
+        let target = target.unwrap_or(ksid);
+        if target.eq(&SYSTEM) {
+            return Err(DdlError::ProtectedObject.into());
+        }
+        self.install_keyspace(target, keyspace)?;
+        log::info!("Successfully imported keyspace");
+        Ok(())
+    }
+
+    /// Force drop a keyspace (see [`Corestore::drop_keyspace`] for why this doesn't take the
+    /// keyspace's DDL lock)
     pub fn force_drop_keyspace(&self, ksid: ObjectID) -> KeyspaceResult<()> {
+        let flush_lock = registry::lock_flush_state();
+        // the events can't be looked up once the tables are gone, and holding a reference to
+        // any of the tables would fail the drop
+        let drop_events: Vec<_> = self
+            .store
+            .get_keyspace_atomic_ref(&ksid)
+            .map(|ks| {
+                ks.tables
+                    .iter()
+                    .filter_map(|table| table.value().key_events().drop_event())
+                    .collect()
+            })
+            .unwrap_or_default();
         // trip switch is handled by memstore here
-        self.store.force_drop_keyspace(ksid)
+        let ret = self.store.force_drop_keyspace(ksid);
+        drop(flush_lock);
+        if ret.is_ok() {
+            for (channel, table) in drop_events {
+                pubsub::publish(&channel, &table);
+            }
+        }
+        ret
     }
     pub fn strong_count(&self) -> usize {
         Arc::strong_count(&self.store)
@@ -353,12 +803,8 @@ impl Corestore {
         Ok(match ksid {
             Some(keyspace_name) => {
                 // inspect the provided keyspace
-                let ksid = if keyspace_name.len() > 64 {
-                    return util::err(P::RSTRING_BAD_CONTAINER_NAME);
-                } else {
-                    keyspace_name
-                };
-                let ks = match self.get_keyspace(ksid) {
+                blueql::util::validate_entity_name::<P>(keyspace_name)?;
+                let ks = match self.get_keyspace(keyspace_name) {
                     Some(kspace) => kspace,
                     None => return util::err(P::RSTRING_CONTAINER_NOT_FOUND),
                 };
@@ -371,6 +817,37 @@ impl Corestore {
             }
         })
     }
+    /// Returns the entity catalog (see [`catalog`])
+    pub fn entities(&self) -> EntityCatalog {
+        self.store.setup_entities()
+    }
+    /// Returns what the entity catalog records for `table` (or the current table), along with
+    /// its shard count, as `key = value` lines
+    pub fn describe_entity<P: ProtocolSpec>(
+        &self,
+        table: &Option<Entity>,
+    ) -> ActionResult<Vec<String>> {
+        let (ksid, tblid) = match table {
+            Some(tbl) => translate_ddl_error::<P, _>(self.get_entity_ids(tbl))?,
+            None => match self.get_ids() {
+                (Some(ksid), Some(tblid)) => (ksid.clone(), tblid.clone()),
+                _ => return util::err(P::RSTRING_DEFAULT_UNSET),
+            },
+        };
+        let table = self
+            .store
+            .get_keyspace_atomic_ref(&ksid)
+            .and_then(|ks| ks.get_table_atomic_ref(&tblid));
+        match (catalog::get(&self.entities(), &ksid, &tblid), table) {
+            (Some(record), Some(table)) => {
+                let mut ret = record.render();
+                ret.push(format!("shards = {}", table.shard_count()));
+                ret.push(format!("memory = {}", table.memory_usage()));
+                Ok(ret)
+            }
+            _ => util::err(P::RSTRING_CONTAINER_NOT_FOUND),
+        }
+    }
     pub fn describe_table<P: ProtocolSpec>(&self, table: &Option<Entity>) -> ActionResult<String> {
         let r = match table {
             Some(tbl) => translate_ddl_error::<P, Arc<Table>>(self.get_table(tbl))?.describe_self(),
@@ -0,0 +1,212 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Object IDs
+//!
+//! Keyspace and table names. Almost every name is short, so names of up to
+//! [`INLINE_CAP`] bytes are stored inline and only longer names spill to the heap.
+//! Names are always persisted with a length prefix, so where a name lives in
+//! memory has no bearing on the on-disk format.
+
+use {
+    super::array::Array,
+    core::{
+        borrow::Borrow,
+        cmp::Ordering,
+        fmt,
+        hash::{Hash, Hasher},
+        mem::MaybeUninit,
+        ops, str,
+    },
+};
+
+/// The number of bytes that are stored inline
+pub const INLINE_CAP: usize = 64;
+
+/// A keyspace or table name
+#[derive(Clone)]
+pub struct ObjectID {
+    repr: Repr,
+}
+
+#[derive(Clone)]
+enum Repr {
+    Inline(Array<u8, INLINE_CAP>),
+    Heap(Box<[u8]>),
+}
+
+impl ObjectID {
+    /// The maximum length of a name. A table's name is also the name of its file in
+    /// backups, so this is the size of the name field in a (ustar) tar header
+    pub const MAX_LENGTH: usize = 100;
+    /// Create an ID from a partially initialized array (see [`Array::from_const`])
+    ///
+    /// ## Safety
+    /// The first `init_len` bytes must be initialized and must be valid UTF-8
+    pub const unsafe fn from_const(array: [MaybeUninit<u8>; INLINE_CAP], init_len: u16) -> Self {
+        Self {
+            repr: Repr::Inline(Array::from_const(array, init_len)),
+        }
+    }
+    /// Create an ID from a slice, or return `None` if it's longer than [`Self::MAX_LENGTH`]
+    pub fn try_from_slice(slice: impl AsRef<[u8]>) -> Option<Self> {
+        let slice = slice.as_ref();
+        if slice.len() > Self::MAX_LENGTH {
+            None
+        } else {
+            Some(unsafe {
+                // UNSAFE(@ohsayan): we just checked the length
+                Self::from_slice(slice)
+            })
+        }
+    }
+    /// Create an ID from a slice
+    ///
+    /// ## Safety
+    /// The slice must not be longer than [`Self::MAX_LENGTH`] and must be valid UTF-8
+    /// if the ID is ever used through [`Self::as_str`]
+    pub unsafe fn from_slice(slice: impl AsRef<[u8]>) -> Self {
+        let slice = slice.as_ref();
+        debug_assert!(slice.len() <= Self::MAX_LENGTH);
+        let repr = if slice.len() <= INLINE_CAP {
+            Repr::Inline(Array::from_slice(slice))
+        } else {
+            Repr::Heap(slice.into())
+        };
+        Self { repr }
+    }
+    /// Get the name as a slice
+    pub fn as_slice(&self) -> &[u8] {
+        match &self.repr {
+            Repr::Inline(array) => array,
+            Repr::Heap(boxed) => boxed,
+        }
+    }
+    /// Get the name as a string
+    ///
+    /// ## Safety
+    /// The name must be valid UTF-8
+    pub unsafe fn as_str(&self) -> &str {
+        str::from_utf8_unchecked(self.as_slice())
+    }
+    /// Returns true if the name was moved to the heap
+    pub fn is_spilled(&self) -> bool {
+        matches!(self.repr, Repr::Heap(_))
+    }
+    /// Append a byte, moving the name to the heap if it doesn't fit inline anymore
+    pub fn push(&mut self, byte: u8) {
+        match &mut self.repr {
+            Repr::Inline(array) if !array.is_full() => array.push(byte),
+            _ => {
+                let mut name = self.as_slice().to_vec();
+                name.push(byte);
+                self.repr = Repr::Heap(name.into_boxed_slice());
+            }
+        }
+    }
+}
+
+impl ops::Deref for ObjectID {
+    type Target = [u8];
+    fn deref(&self) -> &Self::Target {
+        self.as_slice()
+    }
+}
+
+impl Hash for ObjectID {
+    fn hash<H: Hasher>(&self, hasher: &mut H) {
+        // must hash like the slice so that lookups through `Borrow<[u8]>` work
+        Hash::hash(self.as_slice(), hasher)
+    }
+}
+
+impl PartialEq for ObjectID {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_slice() == other.as_slice()
+    }
+}
+
+impl Eq for ObjectID {}
+
+impl PartialEq<[u8]> for ObjectID {
+    fn eq(&self, oth: &[u8]) -> bool {
+        self.as_slice() == oth
+    }
+}
+
+impl PartialEq<ObjectID> for [u8] {
+    fn eq(&self, oth: &ObjectID) -> bool {
+        self == oth.as_slice()
+    }
+}
+
+impl PartialOrd for ObjectID {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for ObjectID {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_slice().cmp(other.as_slice())
+    }
+}
+
+impl Borrow<[u8]> for ObjectID {
+    fn borrow(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl AsRef<[u8]> for ObjectID {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl fmt::Debug for ObjectID {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match str::from_utf8(self.as_slice()) {
+            Ok(st) => write!(f, "{:#?}", st),
+            Err(_) => self.as_slice().fmt(f),
+        }
+    }
+}
+
+#[test]
+fn test_objectid_inline_and_heap() {
+    let short = ObjectID::try_from_slice("a".repeat(INLINE_CAP)).unwrap();
+    assert!(!short.is_spilled());
+    let long = ObjectID::try_from_slice("a".repeat(INLINE_CAP + 1)).unwrap();
+    assert!(long.is_spilled());
+    assert_eq!(long.len(), INLINE_CAP + 1);
+    let mut pushed = short.clone();
+    pushed.push(b'a');
+    assert!(pushed.is_spilled());
+    assert_eq!(pushed, long);
+    assert!(ObjectID::try_from_slice("a".repeat(ObjectID::MAX_LENGTH)).is_some());
+    assert!(ObjectID::try_from_slice("a".repeat(ObjectID::MAX_LENGTH + 1)).is_none());
+}
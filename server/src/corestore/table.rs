@@ -29,12 +29,27 @@ use crate::corestore::{memstore::DdlError, KeyspaceResult};
 use crate::{
     actions::ActionResult,
     auth::Authmap,
-    corestore::{htable::Coremap, SharedSlice},
+    config::EvictionPolicy,
+    corestore::{catalog::EntityCatalog, htable::Coremap, map, usage::UsageLedger, SharedSlice},
     dbnet::prelude::Corestore,
-    kvengine::{KVEListmap, KVEStandard, LockedVec},
+    kvengine::{
+        bounds::ListBounds, dedup::DedupWindow, events::KeyEvents, expiry::ExpiryIndex,
+        hotspot::HotspotSampler, limits::SizeLimits, quota::Quota, throttle::WriteThrottle,
+        KVEDocument, KVEListmap, KVEMap, KVESet, KVESortedSet, KVEStandard, LockedDocument,
+        LockedMap, LockedSet, LockedSortedSet, LockedVec,
+    },
     protocol::interface::ProtocolSpec,
+    registry,
+    storage::v1::bytemarks::{self, ModelKind},
     util,
 };
+use {
+    core::sync::atomic::{AtomicBool, AtomicU64, Ordering},
+    std::sync::Arc,
+};
+
+/// The flush mark of tables that were never flushed
+const NEVER_FLUSHED: u64 = u64::MAX;
 
 pub trait DescribeTable {
     type Table;
@@ -79,9 +94,63 @@ impl DescribeTable for KVEList {
     }
 }
 
+pub struct KVEMapT;
+
+impl DescribeTable for KVEMapT {
+    type Table = KVEMap;
+    fn try_get(table: &Table) -> Option<&Self::Table> {
+        if let DataModel::KVExtMap(ref kvm) = table.model_store {
+            Some(kvm)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct KVESetT;
+
+impl DescribeTable for KVESetT {
+    type Table = KVESet;
+    fn try_get(table: &Table) -> Option<&Self::Table> {
+        if let DataModel::KVExtSet(ref kvs) = table.model_store {
+            Some(kvs)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct KVESortedSetT;
+
+impl DescribeTable for KVESortedSetT {
+    type Table = KVESortedSet;
+    fn try_get(table: &Table) -> Option<&Self::Table> {
+        if let DataModel::KVExtSortedSet(ref kvz) = table.model_store {
+            Some(kvz)
+        } else {
+            None
+        }
+    }
+}
+
+pub struct KVEDocumentT;
+
+impl DescribeTable for KVEDocumentT {
+    type Table = KVEDocument;
+    fn try_get(table: &Table) -> Option<&Self::Table> {
+        if let DataModel::KVExtDocument(ref kvd) = table.model_store {
+            Some(kvd)
+        } else {
+            None
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum SystemDataModel {
     Auth(Authmap),
+    Usage(UsageLedger),
+    Entities(EntityCatalog),
 }
 
 #[derive(Debug)]
@@ -100,12 +169,22 @@ impl SystemTable {
     pub fn new_auth(authmap: Authmap) -> Self {
         Self::new(SystemDataModel::Auth(authmap))
     }
+    pub fn new_usage(ledger: UsageLedger) -> Self {
+        Self::new(SystemDataModel::Usage(ledger))
+    }
+    pub fn new_entities(catalog: EntityCatalog) -> Self {
+        Self::new(SystemDataModel::Entities(catalog))
+    }
 }
 
 #[derive(Debug)]
 pub enum DataModel {
     KV(KVEStandard),
     KVExtListmap(KVEListmap),
+    KVExtMap(KVEMap),
+    KVExtSet(KVESet),
+    KVExtSortedSet(KVESortedSet),
+    KVExtDocument(KVEDocument),
 }
 
 // same 8 byte ptrs; any chance of optimizations?
@@ -117,6 +196,15 @@ pub struct Table {
     model_store: DataModel,
     /// is the table volatile
     volatile: bool,
+    /// the mutation count when the table was last flushed
+    flushed: AtomicU64,
+    /// if set, writes to the table are rejected (this isn't persisted)
+    read_only: AtomicBool,
+    /// set once the table is dropped; connections that still refer to it can't write to it
+    dropped: AtomicBool,
+    /// the number of shards the table was created with, or zero if it has the default number
+    /// of shards
+    shards: usize,
 }
 
 impl Table {
@@ -125,6 +213,10 @@ impl Table {
         Self {
             model_store: DataModel::KV(kve),
             volatile,
+            flushed: AtomicU64::new(NEVER_FLUSHED),
+            read_only: AtomicBool::new(false),
+            dropped: AtomicBool::new(false),
+            shards: 0,
         }
     }
     #[cfg(test)]
@@ -132,6 +224,10 @@ impl Table {
         Self {
             model_store: DataModel::KVExtListmap(kve),
             volatile,
+            flushed: AtomicU64::new(NEVER_FLUSHED),
+            read_only: AtomicBool::new(false),
+            dropped: AtomicBool::new(false),
+            shards: 0,
         }
     }
     /// Get the key/value store if the table is a key/value store
@@ -148,6 +244,22 @@ impl Table {
         match &self.model_store {
             DataModel::KV(kv) => kv.len(),
             DataModel::KVExtListmap(kv) => kv.len(),
+            DataModel::KVExtMap(kv) => kv.len(),
+            DataModel::KVExtSet(kv) => kv.len(),
+            DataModel::KVExtSortedSet(kv) => kv.len(),
+            DataModel::KVExtDocument(kv) => kv.len(),
+        }
+    }
+    /// Returns the approximate memory (in bytes) taken by this table's entries (see
+    /// [`MemoryTracker`](crate::kvengine::eviction::MemoryTracker))
+    pub fn memory_usage(&self) -> u64 {
+        match &self.model_store {
+            DataModel::KV(kv) => kv.memory().bytes(),
+            DataModel::KVExtListmap(kv) => kv.memory().bytes(),
+            DataModel::KVExtMap(kv) => kv.memory().bytes(),
+            DataModel::KVExtSet(kv) => kv.memory().bytes(),
+            DataModel::KVExtSortedSet(kv) => kv.memory().bytes(),
+            DataModel::KVExtDocument(kv) => kv.memory().bytes(),
         }
     }
     /// Returns this table's _description_
@@ -171,22 +283,200 @@ impl Table {
             6 if !self.is_volatile() => "Keymap { data:(str,list<binstr>), volatile:false }",
             7 if self.is_volatile() => "Keymap { data:(str,list<str>), volatile:true }",
             7 if !self.is_volatile() => "Keymap { data:(str,list<str>), volatile:false }",
+            // KVext => map
+            8 if self.is_volatile() => "Keymap { data:(binstr,map<binstr>), volatile:true }",
+            8 if !self.is_volatile() => "Keymap { data:(binstr,map<binstr>), volatile:false }",
+            9 if self.is_volatile() => "Keymap { data:(binstr,map<str>), volatile:true }",
+            9 if !self.is_volatile() => "Keymap { data:(binstr,map<str>), volatile:false }",
+            10 if self.is_volatile() => "Keymap { data:(str,map<binstr>), volatile:true }",
+            10 if !self.is_volatile() => "Keymap { data:(str,map<binstr>), volatile:false }",
+            11 if self.is_volatile() => "Keymap { data:(str,map<str>), volatile:true }",
+            11 if !self.is_volatile() => "Keymap { data:(str,map<str>), volatile:false }",
+            // KVext => set
+            12 if self.is_volatile() => "Keymap { data:(binstr,set<binstr>), volatile:true }",
+            12 if !self.is_volatile() => "Keymap { data:(binstr,set<binstr>), volatile:false }",
+            13 if self.is_volatile() => "Keymap { data:(binstr,set<str>), volatile:true }",
+            13 if !self.is_volatile() => "Keymap { data:(binstr,set<str>), volatile:false }",
+            14 if self.is_volatile() => "Keymap { data:(str,set<binstr>), volatile:true }",
+            14 if !self.is_volatile() => "Keymap { data:(str,set<binstr>), volatile:false }",
+            15 if self.is_volatile() => "Keymap { data:(str,set<str>), volatile:true }",
+            15 if !self.is_volatile() => "Keymap { data:(str,set<str>), volatile:false }",
+            // KVext => sorted set
+            16 if self.is_volatile() => "Keymap { data:(binstr,zset<binstr>), volatile:true }",
+            16 if !self.is_volatile() => "Keymap { data:(binstr,zset<binstr>), volatile:false }",
+            17 if self.is_volatile() => "Keymap { data:(binstr,zset<str>), volatile:true }",
+            17 if !self.is_volatile() => "Keymap { data:(binstr,zset<str>), volatile:false }",
+            18 if self.is_volatile() => "Keymap { data:(str,zset<binstr>), volatile:true }",
+            18 if !self.is_volatile() => "Keymap { data:(str,zset<binstr>), volatile:false }",
+            19 if self.is_volatile() => "Keymap { data:(str,zset<str>), volatile:true }",
+            19 if !self.is_volatile() => "Keymap { data:(str,zset<str>), volatile:false }",
+            // KVext => document
+            20 if self.is_volatile() => "Keymap { data:(binstr,json), volatile:true }",
+            20 if !self.is_volatile() => "Keymap { data:(binstr,json), volatile:false }",
+            21 if self.is_volatile() => "Keymap { data:(str,json), volatile:true }",
+            21 if !self.is_volatile() => "Keymap { data:(str,json), volatile:false }",
             _ => unsafe { impossible!() },
         }
     }
+    /// Returns a reference to this table's hotspot sampler
+    pub fn hotspots(&self) -> &HotspotSampler {
+        match &self.model_store {
+            DataModel::KV(kv) => kv.hotspots(),
+            DataModel::KVExtListmap(kv) => kv.hotspots(),
+            DataModel::KVExtMap(kv) => kv.hotspots(),
+            DataModel::KVExtSet(kv) => kv.hotspots(),
+            DataModel::KVExtSortedSet(kv) => kv.hotspots(),
+            DataModel::KVExtDocument(kv) => kv.hotspots(),
+        }
+    }
+    /// Returns a reference to this table's write throttle
+    pub fn write_throttle(&self) -> &WriteThrottle {
+        match &self.model_store {
+            DataModel::KV(kv) => kv.write_throttle(),
+            DataModel::KVExtListmap(kv) => kv.write_throttle(),
+            DataModel::KVExtMap(kv) => kv.write_throttle(),
+            DataModel::KVExtSet(kv) => kv.write_throttle(),
+            DataModel::KVExtSortedSet(kv) => kv.write_throttle(),
+            DataModel::KVExtDocument(kv) => kv.write_throttle(),
+        }
+    }
+    /// Returns a reference to this table's size limits
+    pub fn size_limits(&self) -> &SizeLimits {
+        match &self.model_store {
+            DataModel::KV(kv) => kv.size_limits(),
+            DataModel::KVExtListmap(kv) => kv.size_limits(),
+            DataModel::KVExtMap(kv) => kv.size_limits(),
+            DataModel::KVExtSet(kv) => kv.size_limits(),
+            DataModel::KVExtSortedSet(kv) => kv.size_limits(),
+            DataModel::KVExtDocument(kv) => kv.size_limits(),
+        }
+    }
+    /// Returns a reference to this table's dedup window
+    pub fn dedup_window(&self) -> &DedupWindow {
+        match &self.model_store {
+            DataModel::KV(kv) => kv.dedup_window(),
+            DataModel::KVExtListmap(kv) => kv.dedup_window(),
+            DataModel::KVExtMap(kv) => kv.dedup_window(),
+            DataModel::KVExtSet(kv) => kv.dedup_window(),
+            DataModel::KVExtSortedSet(kv) => kv.dedup_window(),
+            DataModel::KVExtDocument(kv) => kv.dedup_window(),
+        }
+    }
+    /// Returns a reference to this table's keyspace events
+    pub fn key_events(&self) -> &KeyEvents {
+        match &self.model_store {
+            DataModel::KV(kv) => kv.key_events(),
+            DataModel::KVExtListmap(kv) => kv.key_events(),
+            DataModel::KVExtMap(kv) => kv.key_events(),
+            DataModel::KVExtSet(kv) => kv.key_events(),
+            DataModel::KVExtSortedSet(kv) => kv.key_events(),
+            DataModel::KVExtDocument(kv) => kv.key_events(),
+        }
+    }
+    /// Count this table towards the quota of its keyspace
+    pub fn attach_quota(&self, quota: Arc<Quota>) {
+        match &self.model_store {
+            DataModel::KV(kv) => kv.memory().attach_quota(quota),
+            DataModel::KVExtListmap(kv) => kv.memory().attach_quota(quota),
+            DataModel::KVExtMap(kv) => kv.memory().attach_quota(quota),
+            DataModel::KVExtSet(kv) => kv.memory().attach_quota(quota),
+            DataModel::KVExtSortedSet(kv) => kv.memory().attach_quota(quota),
+            DataModel::KVExtDocument(kv) => kv.memory().attach_quota(quota),
+        }
+    }
+    /// Returns true if this table is in a keyspace that has hit its quota
+    pub fn is_over_quota(&self) -> bool {
+        match &self.model_store {
+            DataModel::KV(kv) => kv.is_over_quota(),
+            DataModel::KVExtListmap(kv) => kv.is_over_quota(),
+            DataModel::KVExtMap(kv) => kv.is_over_quota(),
+            DataModel::KVExtSet(kv) => kv.is_over_quota(),
+            DataModel::KVExtSortedSet(kv) => kv.is_over_quota(),
+            DataModel::KVExtDocument(kv) => kv.is_over_quota(),
+        }
+    }
+    /// Evict a key from this table as per `policy`. Returns false if there was nothing to evict
+    pub fn evict(&self, policy: EvictionPolicy) -> bool {
+        match &self.model_store {
+            DataModel::KV(kv) => kv.evict(policy),
+            DataModel::KVExtListmap(kv) => kv.evict(policy),
+            DataModel::KVExtMap(kv) => kv.evict(policy),
+            DataModel::KVExtSet(kv) => kv.evict(policy),
+            DataModel::KVExtSortedSet(kv) => kv.evict(policy),
+            DataModel::KVExtDocument(kv) => kv.evict(policy),
+        }
+    }
+    /// Start sampling hotspots in this table for the next `window` seconds
+    pub fn start_hotspot_sampling(&self, window: u64) {
+        match &self.model_store {
+            DataModel::KV(kv) => kv.start_hotspot_sampling(window),
+            DataModel::KVExtListmap(kv) => kv.start_hotspot_sampling(window),
+            DataModel::KVExtMap(kv) => kv.start_hotspot_sampling(window),
+            DataModel::KVExtSet(kv) => kv.start_hotspot_sampling(window),
+            DataModel::KVExtSortedSet(kv) => kv.start_hotspot_sampling(window),
+            DataModel::KVExtDocument(kv) => kv.start_hotspot_sampling(window),
+        }
+    }
     pub fn truncate_table(&self) {
         match self.model_store {
             DataModel::KV(ref kv) => kv.truncate_table(),
             DataModel::KVExtListmap(ref kv) => kv.truncate_table(),
+            DataModel::KVExtMap(ref kv) => kv.truncate_table(),
+            DataModel::KVExtSet(ref kv) => kv.truncate_table(),
+            DataModel::KVExtSortedSet(ref kv) => kv.truncate_table(),
+            DataModel::KVExtDocument(ref kv) => kv.truncate_table(),
         }
     }
     pub fn is_empty(&self) -> bool {
         self.count() == 0
     }
+    /// Returns the number of mutations made to this table so far
+    pub fn mutations(&self) -> u64 {
+        match &self.model_store {
+            DataModel::KV(kv) => kv.mutations(),
+            DataModel::KVExtListmap(kv) => kv.mutations(),
+            DataModel::KVExtMap(kv) => kv.mutations(),
+            DataModel::KVExtSet(kv) => kv.mutations(),
+            DataModel::KVExtSortedSet(kv) => kv.mutations(),
+            DataModel::KVExtDocument(kv) => kv.mutations(),
+        }
+    }
+    /// Returns true if the table has changed since it was flushed at `mutations` (see
+    /// [`Table::mark_flushed`]). Tables that were never flushed are always dirty
+    pub fn is_dirty(&self, mutations: u64) -> bool {
+        self.flushed.load(Ordering::Acquire) != mutations
+    }
+    /// Record that the table was flushed with all the changes up to `mutations` (which should
+    /// be read **before** serializing the table)
+    pub fn mark_flushed(&self, mutations: u64) {
+        self.flushed.store(mutations, Ordering::Release)
+    }
     /// Returns the storage type as an 8-bit uint
     pub const fn storage_type(&self) -> u8 {
         self.volatile as u8
     }
+    /// Returns true if writes to this table are rejected
+    pub fn is_read_only(&self) -> bool {
+        self.read_only.load(Ordering::Acquire)
+    }
+    /// Reject (or allow) writes to this table
+    pub fn set_read_only(&self, read_only: bool) {
+        self.read_only.store(read_only, Ordering::Release)
+    }
+    /// Returns true if the table was dropped
+    pub fn is_dropped(&self) -> bool {
+        self.dropped.load(Ordering::Acquire)
+    }
+    /// Mark the table as dropped, so that the connections that still refer to it can't
+    /// write to it anymore
+    pub fn mark_dropped(&self) {
+        self.dropped.store(true, Ordering::Release)
+    }
+    /// Returns true if a write to this table should be rejected, either because the table or
+    /// because the whole node is read-only
+    pub fn rejects_writes(&self) -> bool {
+        self.is_read_only() || registry::is_read_only()
+    }
     /// Returns the volatility of the table
     pub const fn is_volatile(&self) -> bool {
         self.volatile
@@ -201,6 +491,10 @@ impl Table {
         Self {
             volatile,
             model_store: DataModel::KV(KVEStandard::new(k_enc, v_enc, data)),
+            flushed: AtomicU64::new(NEVER_FLUSHED),
+            read_only: AtomicBool::new(false),
+            dropped: AtomicBool::new(false),
+            shards: 0,
         }
     }
     pub fn new_kve_listmap_with_data(
@@ -212,31 +506,164 @@ impl Table {
         Self {
             volatile,
             model_store: DataModel::KVExtListmap(KVEListmap::new(k_enc, payload_enc, data)),
+            flushed: AtomicU64::new(NEVER_FLUSHED),
+            read_only: AtomicBool::new(false),
+            dropped: AtomicBool::new(false),
+            shards: 0,
+        }
+    }
+    pub fn new_kve_map_with_data(
+        data: Coremap<SharedSlice, LockedMap>,
+        volatile: bool,
+        k_enc: bool,
+        payload_enc: bool,
+    ) -> Self {
+        Self {
+            volatile,
+            model_store: DataModel::KVExtMap(KVEMap::new(k_enc, payload_enc, data)),
+            flushed: AtomicU64::new(NEVER_FLUSHED),
+            read_only: AtomicBool::new(false),
+            dropped: AtomicBool::new(false),
+            shards: 0,
+        }
+    }
+    pub fn new_kve_set_with_data(
+        data: Coremap<SharedSlice, LockedSet>,
+        volatile: bool,
+        k_enc: bool,
+        payload_enc: bool,
+    ) -> Self {
+        Self {
+            volatile,
+            model_store: DataModel::KVExtSet(KVESet::new(k_enc, payload_enc, data)),
+            flushed: AtomicU64::new(NEVER_FLUSHED),
+            read_only: AtomicBool::new(false),
+            dropped: AtomicBool::new(false),
+            shards: 0,
+        }
+    }
+    pub fn new_kve_sorted_set_with_data(
+        data: Coremap<SharedSlice, LockedSortedSet>,
+        volatile: bool,
+        k_enc: bool,
+        payload_enc: bool,
+    ) -> Self {
+        Self {
+            volatile,
+            model_store: DataModel::KVExtSortedSet(KVESortedSet::new(k_enc, payload_enc, data)),
+            flushed: AtomicU64::new(NEVER_FLUSHED),
+            read_only: AtomicBool::new(false),
+            dropped: AtomicBool::new(false),
+            shards: 0,
         }
     }
+    pub fn new_kve_document_with_data(
+        data: Coremap<SharedSlice, LockedDocument>,
+        volatile: bool,
+        k_enc: bool,
+    ) -> Self {
+        Self {
+            volatile,
+            // documents are always valid UTF-8
+            model_store: DataModel::KVExtDocument(KVEDocument::new(k_enc, true, data)),
+            flushed: AtomicU64::new(NEVER_FLUSHED),
+            read_only: AtomicBool::new(false),
+            dropped: AtomicBool::new(false),
+            shards: 0,
+        }
+    }
+    /// Restore the deadlines of the expiring keys in this table
+    pub fn with_expiry(mut self, deadlines: Coremap<SharedSlice, u64>) -> Self {
+        let expiry = ExpiryIndex::new(deadlines);
+        match self.model_store {
+            DataModel::KV(ref mut kve) => kve.restore_expiry(expiry),
+            DataModel::KVExtListmap(ref mut kvl) => kvl.restore_expiry(expiry),
+            DataModel::KVExtMap(ref mut kvm) => kvm.restore_expiry(expiry),
+            DataModel::KVExtSet(ref mut kvs) => kvs.restore_expiry(expiry),
+            DataModel::KVExtSortedSet(ref mut kvz) => kvz.restore_expiry(expiry),
+            DataModel::KVExtDocument(ref mut kvd) => kvd.restore_expiry(expiry),
+        }
+        self
+    }
+    /// Restore the bounds of the lists in this table (a no-op for tables that aren't list
+    /// tables)
+    pub fn with_list_bounds(mut self, bounds: Coremap<SharedSlice, u64>) -> Self {
+        if let DataModel::KVExtListmap(ref mut kvl) = self.model_store {
+            kvl.restore_list_bounds(ListBounds::new(bounds));
+        }
+        self
+    }
+    /// Move the data of this table into (about, see [`map::normalize_shard_count`]) `shards`
+    /// shards instead of the default
+    pub fn with_shards(mut self, shards: usize) -> Self {
+        self.model_store = match self.model_store {
+            DataModel::KV(kve) => DataModel::KV(kve.with_shards(shards)),
+            DataModel::KVExtListmap(kvl) => DataModel::KVExtListmap(kvl.with_shards(shards)),
+            DataModel::KVExtMap(kvm) => DataModel::KVExtMap(kvm.with_shards(shards)),
+            DataModel::KVExtSet(kvs) => DataModel::KVExtSet(kvs.with_shards(shards)),
+            DataModel::KVExtSortedSet(kvz) => DataModel::KVExtSortedSet(kvz.with_shards(shards)),
+            DataModel::KVExtDocument(kvd) => DataModel::KVExtDocument(kvd.with_shards(shards)),
+        };
+        self.shards = map::normalize_shard_count(shards);
+        self
+    }
+    /// Returns the number of shards this table was created with, if it doesn't have the
+    /// default number of shards
+    pub fn configured_shards(&self) -> Option<usize> {
+        (self.shards != 0).then_some(self.shards)
+    }
+    /// Returns the number of shards of this table
+    pub fn shard_count(&self) -> usize {
+        match &self.model_store {
+            DataModel::KV(kv) => kv.shard_count(),
+            DataModel::KVExtListmap(kv) => kv.shard_count(),
+            DataModel::KVExtMap(kv) => kv.shard_count(),
+            DataModel::KVExtSet(kv) => kv.shard_count(),
+            DataModel::KVExtSortedSet(kv) => kv.shard_count(),
+            DataModel::KVExtDocument(kv) => kv.shard_count(),
+        }
+    }
+    /// Set the volatility of this table
+    pub fn with_volatility(mut self, volatile: bool) -> Self {
+        self.volatile = volatile;
+        self
+    }
     pub fn from_model_code(code: u8, volatile: bool) -> Option<Self> {
-        macro_rules! pkve {
-            ($kenc:expr, $venc:expr) => {
-                Self::new_pure_kve_with_data(Coremap::new(), volatile, $kenc, $venc)
-            };
-        }
-        macro_rules! listmap {
-            ($kenc:expr, $penc:expr) => {
-                Self::new_kve_listmap_with_data(Coremap::new(), volatile, $kenc, $penc)
-            };
-        }
-        let ret = match code {
-            // pure kve
-            0 => pkve!(false, false),
-            1 => pkve!(false, true),
-            2 => pkve!(true, true),
-            3 => pkve!(true, false),
-            // kvext: listmap
-            4 => listmap!(false, false),
-            5 => listmap!(false, true),
-            6 => listmap!(true, false),
-            7 => listmap!(true, true),
-            _ => return None,
+        let model = bytemarks::model(code).ok()?;
+        let ret = match model.kind {
+            ModelKind::KV => Self::new_pure_kve_with_data(
+                Coremap::new(),
+                volatile,
+                model.key_is_str,
+                model.value_is_str,
+            ),
+            ModelKind::KVList => Self::new_kve_listmap_with_data(
+                Coremap::new(),
+                volatile,
+                model.key_is_str,
+                model.value_is_str,
+            ),
+            ModelKind::KVMap => Self::new_kve_map_with_data(
+                Coremap::new(),
+                volatile,
+                model.key_is_str,
+                model.value_is_str,
+            ),
+            ModelKind::KVSet => Self::new_kve_set_with_data(
+                Coremap::new(),
+                volatile,
+                model.key_is_str,
+                model.value_is_str,
+            ),
+            ModelKind::KVSortedSet => Self::new_kve_sorted_set_with_data(
+                Coremap::new(),
+                volatile,
+                model.key_is_str,
+                model.value_is_str,
+            ),
+            ModelKind::KVDocument => {
+                Self::new_kve_document_with_data(Coremap::new(), volatile, model.key_is_str)
+            }
         };
         Some(ret)
     }
@@ -277,6 +704,43 @@ impl Table {
                 let (kenc, venc) = kvlistmap.get_encoding_tuple();
                 ((kenc as u8) << 1) + (venc as u8) + 4
             }
+            DataModel::KVExtMap(ref kvmap) => {
+                /*
+                bin,map<bin> => 8,
+                bin,map<str> => 9,
+                str,map<bin> => 10,
+                str,map<str> => 11
+                */
+                let (kenc, venc) = kvmap.get_encoding_tuple();
+                ((kenc as u8) << 1) + (venc as u8) + 8
+            }
+            DataModel::KVExtSet(ref kvset) => {
+                /*
+                bin,set<bin> => 12,
+                bin,set<str> => 13,
+                str,set<bin> => 14,
+                str,set<str> => 15
+                */
+                let (kenc, venc) = kvset.get_encoding_tuple();
+                ((kenc as u8) << 1) + (venc as u8) + 12
+            }
+            DataModel::KVExtSortedSet(ref kvzset) => {
+                /*
+                bin,zset<bin> => 16,
+                bin,zset<str> => 17,
+                str,zset<bin> => 18,
+                str,zset<str> => 19
+                */
+                let (kenc, venc) = kvzset.get_encoding_tuple();
+                ((kenc as u8) << 1) + (venc as u8) + 16
+            }
+            DataModel::KVExtDocument(ref kvdoc) => {
+                /*
+                bin,json => 20,
+                str,json => 21
+                */
+                kvdoc.is_key_encoded() as u8 + 20
+            }
         }
     }
     /// Returns the inner data model
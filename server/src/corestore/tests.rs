@@ -117,6 +117,38 @@ mod memstore_keyspace_tests {
         // should succeed because the keyspace is non-empty, but no table is referenced to
         assert!(ms.force_drop_keyspace(obj).is_ok());
     }
+
+    #[test]
+    fn test_rename_keyspace() {
+        let ms = Memstore::new_default();
+        let old = unsafe { ObjectID::from_slice("myks") };
+        let new = unsafe { ObjectID::from_slice("myks2") };
+        ms.create_keyspace(old.clone());
+        let ks_ref = ms.get_keyspace_atomic_ref(&old).unwrap();
+        ms.rename_keyspace(&old, new.clone()).unwrap();
+        assert!(ms.get_keyspace_atomic_ref(&old).is_none());
+        assert!(std::sync::Arc::ptr_eq(
+            &ks_ref,
+            &ms.get_keyspace_atomic_ref(&new).unwrap()
+        ));
+        assert_eq!(
+            ms.rename_keyspace(&old, new.clone()).unwrap_err(),
+            DdlError::ObjectNotFound
+        );
+        ms.create_keyspace(old.clone());
+        assert_eq!(
+            ms.rename_keyspace(&old, new).unwrap_err(),
+            DdlError::AlreadyExists
+        );
+        assert_eq!(
+            ms.rename_keyspace(&DEFAULT, old.clone()).unwrap_err(),
+            DdlError::ProtectedObject
+        );
+        assert_eq!(
+            ms.rename_keyspace(&old, SYSTEM).unwrap_err(),
+            DdlError::ProtectedObject
+        );
+    }
 }
 
 mod modelcode_tests {
@@ -168,3 +200,187 @@ mod modelcode_tests {
         assert_eq!(tbl4.get_model_code(), 7);
     }
 }
+
+mod table_tests {
+    use super::super::table::Table;
+
+    #[test]
+    fn test_table_read_only() {
+        let tbl = Table::new_default_kve();
+        assert!(!tbl.rejects_writes());
+        tbl.set_read_only(true);
+        assert!(tbl.is_read_only());
+        assert!(tbl.rejects_writes());
+        tbl.set_read_only(false);
+        assert!(!tbl.rejects_writes());
+    }
+
+    #[test]
+    fn test_table_memory_usage() {
+        use crate::{corestore::table::DataModel, kvengine::eviction};
+        let tbl = Table::new_default_kve();
+        assert_eq!(tbl.memory_usage(), 0);
+        let kve = match tbl.get_model_ref() {
+            DataModel::KV(kve) => kve,
+            _ => panic!("not a key/value table"),
+        };
+        kve.set("key".into(), "value".into()).unwrap();
+        let entry = eviction::entry_size(b"key", 5) as u64;
+        assert_eq!(tbl.memory_usage(), entry);
+        kve.update("key".into(), "longer value".into()).unwrap();
+        assert_eq!(tbl.memory_usage(), entry + 7);
+        kve.remove("key").unwrap();
+        assert_eq!(tbl.memory_usage(), 0);
+    }
+}
+
+mod ddl_tests {
+    use {
+        super::super::{catalog, memstore::*, Corestore},
+        crate::{blueql::Entity, storage::v1::sengine::SnapshotEngine},
+        std::{sync::Arc, thread},
+    };
+
+    const ROUNDS: usize = 200;
+
+    fn new_corestore() -> Corestore {
+        let sengine = Arc::new(SnapshotEngine::new_disabled());
+        Corestore::default_with_store(Memstore::new_default(), sengine)
+    }
+
+    #[test]
+    fn test_concurrent_create_drop_table() {
+        let db = new_corestore();
+        db.create_keyspace(unsafe { ObjectID::from_slice("races") })
+            .unwrap();
+        let threads: Vec<_> = (0..8)
+            .map(|i| {
+                let db = db.clone();
+                thread::spawn(move || {
+                    let entity = Entity::Full("races".into(), "tbl".into());
+                    for _ in 0..ROUNDS {
+                        if i % 2 == 0 {
+                            let _ = db.create_table(&entity, 0, true, None);
+                        } else {
+                            let _ = db.drop_table(&entity, true);
+                        }
+                    }
+                })
+            })
+            .collect();
+        threads.into_iter().for_each(|t| t.join().unwrap());
+        // the catalog must agree with the keyspace on whether the table exists
+        let ks = db
+            .clone_store()
+            .get_keyspace_atomic_ref(&b"races"[..])
+            .unwrap();
+        let exists = ks.get_table_atomic_ref(&b"tbl"[..]).is_some();
+        let recorded = catalog::get(&db.entities(), b"races", b"tbl").is_some();
+        assert_eq!(exists, recorded);
+    }
+
+    #[test]
+    fn test_concurrent_create_table_drop_keyspace() {
+        let db = new_corestore();
+        let ksid = unsafe { ObjectID::from_slice("races") };
+        let dropper = {
+            let db = db.clone();
+            thread::spawn(move || {
+                for _ in 0..ROUNDS {
+                    let _ = db.create_keyspace(ksid.clone());
+                    let _ = db.drop_keyspace(ksid.clone());
+                }
+            })
+        };
+        let creator = thread::spawn(move || {
+            let entity = Entity::Full("races".into(), "tbl".into());
+            for _ in 0..ROUNDS {
+                if db.create_table(&entity, 0, true, None).is_ok() {
+                    // the keyspace can't be dropped while it has a table, so the table must
+                    // not have ended up in a keyspace that was being dropped
+                    let ks = db.clone_store().get_keyspace_atomic_ref(&b"races"[..]);
+                    assert!(ks.unwrap().get_table_atomic_ref(&b"tbl"[..]).is_some());
+                    db.drop_table(&entity, true).unwrap();
+                }
+            }
+        });
+        dropper.join().unwrap();
+        creator.join().unwrap();
+    }
+
+    #[test]
+    fn test_alter_space_default_model() {
+        use crate::blueql::SpaceProperty;
+        let mut db = new_corestore();
+        let ksid = unsafe { ObjectID::from_slice("twitter") };
+        db.create_keyspace(ksid.clone()).unwrap();
+        let set_default = |db: &Corestore, model: Option<&str>| {
+            let property = SpaceProperty::DefaultModel(model.map(str::to_owned));
+            db.alter_keyspace(ksid.clone(), &property)
+        };
+        // the model has to exist
+        assert_eq!(
+            set_default(&db, Some("tweets")).unwrap_err(),
+            DdlError::ObjectNotFound
+        );
+        let tweets = Entity::Full("twitter".into(), "tweets".into());
+        db.create_table(&tweets, 0, true, None).unwrap();
+        set_default(&db, Some("tweets")).unwrap();
+        db.swap_entity(&Entity::Current("twitter".into())).unwrap();
+        assert_eq!(
+            db.get_ids().1.map(|tblid| tblid.as_ref()),
+            Some(&b"tweets"[..])
+        );
+        // once it's dropped (or unset), `use` only switches the keyspace
+        db.drop_table(&tweets, true).unwrap();
+        db.swap_entity(&Entity::Current("twitter".into())).unwrap();
+        assert!(db.get_ids().1.is_none());
+        set_default(&db, None).unwrap();
+        assert!(db.get_keyspace(&ksid).unwrap().default_model().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_import_into_read_only_table() {
+        use crate::corestore::export::ExportFormat;
+        let db = new_corestore();
+        let entity = Entity::Full("default".into(), "default".into());
+        let path = "test_import_into_read_only_table.json";
+        std::fs::write(path, "{\"key\":\"aGVsbG8=\",\"value\":\"d29ybGQ=\"}\n").unwrap();
+        let import = || db.import(&entity, path.to_owned(), ExportFormat::Json, false);
+        let table = db.get_table(&entity).unwrap();
+        table.set_read_only(true);
+        assert_eq!(import().await.unwrap_err(), DdlError::ReadOnly);
+        assert_eq!(table.count(), 0);
+        table.set_read_only(false);
+        assert_eq!(import().await.unwrap().imported, 1);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_rename_table_disk_failure() {
+        use crate::storage::v1::interface::DIR_KSROOT;
+        let db = new_corestore();
+        db.create_keyspace(unsafe { ObjectID::from_slice("renamefail") })
+            .unwrap();
+        let old = Entity::Full("renamefail".into(), "tbl".into());
+        db.create_table(&old, 0, false, None).unwrap();
+        // a directory in the way of the new name makes the rename fail on disk
+        let ks_path = format!("{DIR_KSROOT}/renamefail");
+        std::fs::create_dir_all(format!("{ks_path}/tbl2")).unwrap();
+        std::fs::write(format!("{ks_path}/tbl"), b"").unwrap();
+        let new = unsafe { ObjectID::from_slice("tbl2") };
+        assert_eq!(
+            db.rename_table(&old, new).unwrap_err(),
+            DdlError::DdlTransactionFailure
+        );
+        // so the table must still go by its old name
+        let ks = db
+            .clone_store()
+            .get_keyspace_atomic_ref(&b"renamefail"[..])
+            .unwrap();
+        assert!(ks.get_table_atomic_ref(&b"tbl"[..]).is_some());
+        assert!(ks.get_table_atomic_ref(&b"tbl2"[..]).is_none());
+        assert!(std::path::Path::new(&format!("{ks_path}/tbl")).is_file());
+        std::fs::remove_dir_all(ks_path).unwrap();
+    }
+}
@@ -0,0 +1,315 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Usage accounting
+//!
+//! Every keyspace counts the reads, writes and network bytes of the queries that run against it.
+//! The usage service periodically moves these counters (along with the on-disk size of the
+//! keyspace) into a bucket in the `system:usage` table, which is persisted like any other system
+//! table. `SYS USAGE <keyspace> <window>` adds up the buckets in the window to report what a
+//! keyspace used, so that tenants can be charged back without an external metering pipeline.
+//!
+//! Queries that don't write count as reads.
+
+use {
+    crate::{
+        corestore::{htable::Coremap, SharedSlice},
+        util,
+    },
+    core::sync::atomic::{AtomicU64, Ordering},
+    std::sync::Arc,
+};
+
+/// The span of a bucket, in seconds
+pub const BUCKET_SECS: u64 = 300;
+/// How long buckets are kept, in seconds (7 days)
+pub const RETENTION_SECS: u64 = 7 * 24 * 3600;
+
+/// The ledger of usage buckets. The keys are `<keyspace>:<bucket start>`
+pub type UsageLedger = Arc<Coremap<SharedSlice, SharedSlice>>;
+
+/// The live usage counters of a keyspace
+#[derive(Debug, Default)]
+pub struct UsageCounters {
+    reads: AtomicU64,
+    writes: AtomicU64,
+    net_in: AtomicU64,
+    net_out: AtomicU64,
+}
+
+impl UsageCounters {
+    /// Count a query
+    pub fn record(&self, is_write: bool) {
+        let counter = if is_write { &self.writes } else { &self.reads };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+    /// Count the bytes that a query read from and wrote to the network
+    pub fn record_network(&self, read: u64, written: u64) {
+        self.net_in.fetch_add(read, Ordering::Relaxed);
+        self.net_out.fetch_add(written, Ordering::Relaxed);
+    }
+    /// Returns the counters without resetting them
+    pub fn peek(&self) -> UsageSample {
+        UsageSample {
+            reads: self.reads.load(Ordering::Relaxed),
+            writes: self.writes.load(Ordering::Relaxed),
+            net_in: self.net_in.load(Ordering::Relaxed),
+            net_out: self.net_out.load(Ordering::Relaxed),
+            storage: 0,
+        }
+    }
+    /// Returns the counters and resets them
+    pub fn take(&self) -> UsageSample {
+        UsageSample {
+            reads: self.reads.swap(0, Ordering::Relaxed),
+            writes: self.writes.swap(0, Ordering::Relaxed),
+            net_in: self.net_in.swap(0, Ordering::Relaxed),
+            net_out: self.net_out.swap(0, Ordering::Relaxed),
+            storage: 0,
+        }
+    }
+}
+
+/// The usage of a keyspace over some span of time
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct UsageSample {
+    pub reads: u64,
+    pub writes: u64,
+    pub net_in: u64,
+    pub net_out: u64,
+    /// The on-disk size of the keyspace, in bytes
+    pub storage: u64,
+}
+
+impl UsageSample {
+    const SIZE: usize = 40;
+    fn encode(&self) -> Vec<u8> {
+        let mut ret = Vec::with_capacity(Self::SIZE);
+        for field in [
+            self.reads,
+            self.writes,
+            self.net_in,
+            self.net_out,
+            self.storage,
+        ] {
+            ret.extend_from_slice(&field.to_le_bytes());
+        }
+        ret
+    }
+    fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() != Self::SIZE {
+            return None;
+        }
+        let field = |i: usize| {
+            let mut bytes = [0u8; 8];
+            bytes.copy_from_slice(&data[i * 8..(i + 1) * 8]);
+            u64::from_le_bytes(bytes)
+        };
+        Some(Self {
+            reads: field(0),
+            writes: field(1),
+            net_in: field(2),
+            net_out: field(3),
+            storage: field(4),
+        })
+    }
+    fn is_idle(&self) -> bool {
+        self.reads == 0 && self.writes == 0 && self.net_in == 0 && self.net_out == 0
+    }
+}
+
+/// A usage report for a keyspace
+#[derive(Debug, PartialEq, Eq)]
+pub struct UsageReport {
+    /// The window, in seconds
+    pub window: u64,
+    /// The totals over the window (the storage is averaged over the buckets)
+    pub usage: UsageSample,
+}
+
+impl UsageReport {
+    /// Render the report as `key = value` lines
+    pub fn render(&self) -> Vec<String> {
+        vec![
+            format!("window = {}", self.window),
+            format!("reads = {}", self.usage.reads),
+            format!("writes = {}", self.usage.writes),
+            format!("network_in = {}", self.usage.net_in),
+            format!("network_out = {}", self.usage.net_out),
+            format!("storage_bytes = {}", self.usage.storage),
+        ]
+    }
+}
+
+/// Returns the start of the bucket that `time` (in seconds since the epoch) falls into
+pub const fn bucket_of(time: u64) -> u64 {
+    time - time % BUCKET_SECS
+}
+
+fn bucket_key(ksid: &[u8], start: u64) -> SharedSlice {
+    let mut key = ksid.to_vec();
+    key.push(b':');
+    key.extend_from_slice(start.to_string().as_bytes());
+    SharedSlice::from(key)
+}
+
+/// Split a bucket key into the keyspace and the bucket start
+fn split_key(key: &[u8]) -> Option<(&[u8], u64)> {
+    let sep = key.iter().rposition(|byte| *byte == b':')?;
+    let start = std::str::from_utf8(&key[sep + 1..]).ok()?.parse().ok()?;
+    Some((&key[..sep], start))
+}
+
+/// Record a bucket for a keyspace. Idle buckets aren't recorded, unless the keyspace has data
+pub fn record_bucket(ledger: &UsageLedger, ksid: &[u8], start: u64, sample: UsageSample) {
+    if sample.is_idle() && sample.storage == 0 {
+        return;
+    }
+    ledger.upsert(bucket_key(ksid, start), SharedSlice::from(sample.encode()));
+}
+
+/// Remove the buckets that are older than [`RETENTION_SECS`]. Returns the number of buckets
+/// that were removed
+pub fn prune(ledger: &UsageLedger, now: u64) -> usize {
+    let cutoff = now.saturating_sub(RETENTION_SECS);
+    let expired: Vec<SharedSlice> = ledger
+        .iter()
+        .filter(|bucket| match split_key(bucket.key()) {
+            Some((_, start)) => start < cutoff,
+            None => true,
+        })
+        .map(|bucket| bucket.key().clone())
+        .collect();
+    expired
+        .iter()
+        .filter(|key| ledger.true_if_removed(*key))
+        .count()
+}
+
+/// Add up the buckets of a keyspace that started in the last `window` seconds, along with
+/// `live` (the usage that hasn't been moved to a bucket yet). Returns `None` if the keyspace
+/// has no buckets at all and `live` is `None`
+pub fn summarize(
+    ledger: &UsageLedger,
+    ksid: &[u8],
+    window: u64,
+    now: u64,
+    live: Option<UsageSample>,
+) -> Option<UsageReport> {
+    let since = now.saturating_sub(window);
+    let mut known = live.is_some();
+    let mut usage = live.unwrap_or_default();
+    let (mut storage, mut buckets) = (0u64, 0u64);
+    for bucket in ledger.iter() {
+        let (ks, start) = match split_key(bucket.key()) {
+            Some(parts) => parts,
+            None => continue,
+        };
+        if ks != ksid {
+            continue;
+        }
+        known = true;
+        if start + BUCKET_SECS <= since {
+            continue;
+        }
+        if let Some(sample) = UsageSample::decode(bucket.value()) {
+            usage.reads += sample.reads;
+            usage.writes += sample.writes;
+            usage.net_in += sample.net_in;
+            usage.net_out += sample.net_out;
+            storage += sample.storage;
+            buckets += 1;
+        }
+    }
+    usage.storage = storage.checked_div(buckets).unwrap_or(0);
+    known.then_some(UsageReport { window, usage })
+}
+
+/// Parse a window like `90` (seconds), `30s`, `15m`, `6h` or `7d`. The window can't be zero or
+/// longer than [`RETENTION_SECS`]
+pub fn parse_window(window: &[u8]) -> Option<u64> {
+    let secs = util::parse_duration(std::str::from_utf8(window).ok()?)?;
+    (secs != 0 && secs <= RETENTION_SECS).then_some(secs)
+}
+
+#[test]
+fn test_usage_ledger() {
+    let ledger: UsageLedger = Arc::new(Coremap::new());
+    let counters = UsageCounters::default();
+    counters.record(false);
+    counters.record(true);
+    counters.record(false);
+    counters.record_network(100, 400);
+    let now = 10 * BUCKET_SECS;
+    let mut sample = counters.take();
+    assert_eq!(counters.peek(), UsageSample::default());
+    sample.storage = 1000;
+    self::record_bucket(&ledger, b"tenant", bucket_of(now - BUCKET_SECS), sample);
+    sample.storage = 3000;
+    self::record_bucket(&ledger, b"tenant", bucket_of(now - 5 * BUCKET_SECS), sample);
+    // idle keyspaces without data don't get buckets
+    self::record_bucket(&ledger, b"idle", bucket_of(now), UsageSample::default());
+    assert_eq!(ledger.len(), 2);
+    let report = self::summarize(&ledger, b"tenant", 2 * BUCKET_SECS, now, None).unwrap();
+    assert_eq!(
+        report.render(),
+        [
+            "window = 600",
+            "reads = 2",
+            "writes = 1",
+            "network_in = 100",
+            "network_out = 400",
+            "storage_bytes = 1000"
+        ]
+    );
+    let live = UsageSample {
+        reads: 1,
+        ..Default::default()
+    };
+    let report = self::summarize(&ledger, b"tenant", 3600, now, Some(live)).unwrap();
+    assert_eq!(report.usage.reads, 5);
+    assert_eq!(report.usage.storage, 2000);
+    assert!(self::summarize(&ledger, b"idle", 3600, now, None).is_none());
+    // expired buckets are pruned
+    assert_eq!(
+        self::prune(&ledger, now + RETENTION_SECS - 2 * BUCKET_SECS),
+        1
+    );
+    assert_eq!(ledger.len(), 1);
+}
+
+#[test]
+fn test_parse_window() {
+    assert_eq!(parse_window(b"90"), Some(90));
+    assert_eq!(parse_window(b"30s"), Some(30));
+    assert_eq!(parse_window(b"15m"), Some(900));
+    assert_eq!(parse_window(b"6H"), Some(6 * 3600));
+    assert_eq!(parse_window(b"7d"), Some(RETENTION_SECS));
+    assert_eq!(parse_window(b"8d"), None);
+    assert_eq!(parse_window(b"0"), None);
+    assert_eq!(parse_window(b"h"), None);
+    assert_eq!(parse_window(b"1w"), None);
+}
@@ -0,0 +1,203 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Clients
+//!
+//! Every connection that is handled by a listener is registered here along with some metadata
+//! (the peer, the user it logged in as, when it connected and the last action that it ran) so
+//! that administrators can list the connected clients (`CLIENT LIST`) and forcibly disconnect
+//! one of them by its ID (`CLIENT KILL <id>`). A client is deregistered as soon as its connection
+//! is dropped
+
+use {
+    crate::{corestore::lazy::Lazy, util::os},
+    parking_lot::{Mutex, RwLock},
+    std::{
+        collections::HashMap,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc, Weak,
+        },
+    },
+    tokio::sync::Notify,
+};
+
+/// The connected clients, by their ID
+type ClientMap = RwLock<HashMap<u64, Weak<Client>>>;
+
+/// The client registry
+static CLIENTS: Lazy<ClientMap, fn() -> ClientMap> = Lazy::new(|| RwLock::new(HashMap::new()));
+/// The ID of the next client
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+#[derive(Debug, Clone, PartialEq)]
+/// A snapshot of the metadata of a client
+pub struct ClientInfo {
+    pub id: u64,
+    /// the address of the peer (or the kind of socket if it has no address)
+    pub peer: String,
+    /// the user that the client is logged in as, if any
+    pub user: Option<String>,
+    /// when the client connected (seconds since the UNIX epoch)
+    pub connected_at: u64,
+    /// the last action that the client ran, if any
+    pub last_command: Option<String>,
+    /// when the client last ran an action (seconds since the UNIX epoch)
+    pub last_active: u64,
+}
+
+impl ClientInfo {
+    /// Render the metadata as a line of `field=value` pairs, where the age and idle time are
+    /// relative to `now` (in seconds since the UNIX epoch). Missing values are rendered as `-`
+    pub fn render(&self, now: u64) -> String {
+        format!(
+            "id={} peer={} user={} age={} idle={} cmd={}",
+            self.id,
+            self.peer,
+            self.user.as_deref().unwrap_or("-"),
+            now.saturating_sub(self.connected_at),
+            now.saturating_sub(self.last_active),
+            self.last_command.as_deref().unwrap_or("-"),
+        )
+    }
+}
+
+/// The metadata of a client that changes with every query
+struct Activity {
+    user: Option<String>,
+    last_command: Option<String>,
+    last_active: u64,
+}
+
+/// A connected client
+pub struct Client {
+    id: u64,
+    peer: String,
+    connected_at: u64,
+    activity: Mutex<Activity>,
+    kill: Notify,
+}
+
+impl Client {
+    /// Register a new client connected from `peer`. The client is deregistered when the returned
+    /// handle (and all its clones) are dropped
+    pub fn register(peer: String) -> Arc<Self> {
+        let now = os::get_epoch_secs();
+        let client = Arc::new(Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            peer,
+            connected_at: now,
+            activity: Mutex::new(Activity {
+                user: None,
+                last_command: None,
+                last_active: now,
+            }),
+            kill: Notify::new(),
+        });
+        CLIENTS.write().insert(client.id, Arc::downgrade(&client));
+        client
+    }
+    /// Returns the ID of this client
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+    /// Record that the client (logged in as `user`) ran `command`
+    pub fn record(&self, user: Option<&[u8]>, command: &[u8]) {
+        let mut activity = self.activity.lock();
+        activity.user = user.map(|user| String::from_utf8_lossy(user).into_owned());
+        activity.last_command = Some(String::from_utf8_lossy(command).to_ascii_uppercase());
+        activity.last_active = os::get_epoch_secs();
+    }
+    /// Returns a snapshot of the metadata of this client
+    pub fn info(&self) -> ClientInfo {
+        let activity = self.activity.lock();
+        ClientInfo {
+            id: self.id,
+            peer: self.peer.clone(),
+            user: activity.user.clone(),
+            connected_at: self.connected_at,
+            last_command: activity.last_command.clone(),
+            last_active: activity.last_active,
+        }
+    }
+    /// Completes once the client has been killed (see [`kill`])
+    pub async fn killed(&self) {
+        self.kill.notified().await
+    }
+}
+
+impl Drop for Client {
+    fn drop(&mut self) {
+        CLIENTS.write().remove(&self.id);
+    }
+}
+
+/// Returns the metadata of every connected client, ordered by their IDs
+pub fn list() -> Vec<ClientInfo> {
+    let mut clients: Vec<ClientInfo> = CLIENTS
+        .read()
+        .values()
+        .filter_map(Weak::upgrade)
+        .map(|client| client.info())
+        .collect();
+    clients.sort_unstable_by_key(|client| client.id);
+    clients
+}
+
+/// Disconnect the client with the given ID, returning false if there is no such client. The
+/// client is disconnected once the query that it is running (if any) completes
+pub fn kill(id: u64) -> bool {
+    let client = CLIENTS.read().get(&id).and_then(Weak::upgrade);
+    match client {
+        Some(client) => {
+            // this stores a permit, so the client is killed even if it isn't waiting right now
+            client.kill.notify_one();
+            true
+        }
+        None => false,
+    }
+}
+
+#[test]
+fn test_clients() {
+    let client = Client::register("127.0.0.1:2003".to_owned());
+    let id = client.id();
+    let info = list().into_iter().find(|info| info.id == id).unwrap();
+    assert_eq!(info.peer, "127.0.0.1:2003");
+    assert_eq!(info.last_command, None);
+    client.record(Some(b"root"), b"heya");
+    let info = client.info();
+    assert_eq!(info.user.as_deref(), Some("root"));
+    assert_eq!(info.last_command.as_deref(), Some("HEYA"));
+    assert_eq!(
+        info.render(info.connected_at + 5),
+        format!("id={id} peer=127.0.0.1:2003 user=root age=5 idle=5 cmd=HEYA")
+    );
+    assert!(kill(id));
+    drop(client);
+    assert!(!list().iter().any(|info| info.id == id));
+    assert!(!kill(id));
+}
@@ -0,0 +1,285 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Response compression
+//!
+//! A client can ask for the responses on its connection to be compressed by sending
+//! `HEYA COMPRESS <algorithm> [<algorithm> ...]`. The server picks the first algorithm that it
+//! supports and responds with its name (or `none` if it supports none of them, in which case
+//! nothing changes). Once that response has been sent, everything the server writes on the
+//! connection is sent in frames:
+//!
+//! ```text
+//! ~<original length>\n<payload length>\n<payload>
+//! ```
+//!
+//! If the payload length is smaller than the original length, the payload is compressed.
+//! Otherwise it holds the original bytes as-is (we don't bother compressing small writes, or
+//! writes that don't get any smaller). Decoding the frames in order gives the exact bytes that
+//! the server would have sent without compression. Queries are never compressed.
+//!
+//! The only algorithm is `lz4` (the LZ4 block format, without the frame format around it)
+
+use crate::corestore::buffers::Integer64;
+
+/// The first byte of a frame
+const FRAME_START: u8 = b'~';
+/// Writes smaller than this are sent as-is
+const MIN_COMPRESS_SIZE: usize = 256;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A compression algorithm
+pub enum Codec {
+    Lz4,
+}
+
+impl Codec {
+    /// Returns the codec with the given (lowercase) name
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"lz4" => Some(Self::Lz4),
+            _ => None,
+        }
+    }
+    pub const fn name(&self) -> &'static [u8] {
+        match self {
+            Self::Lz4 => b"lz4",
+        }
+    }
+    fn compress(&self, src: &[u8]) -> Vec<u8> {
+        match self {
+            Self::Lz4 => lz4::compress(src),
+        }
+    }
+    /// Append a frame with `src` to `out`
+    pub(super) fn encode_frame(&self, src: &[u8], out: &mut Vec<u8>) {
+        let compressed = if src.len() >= MIN_COMPRESS_SIZE {
+            Some(self.compress(src)).filter(|compressed| compressed.len() < src.len())
+        } else {
+            None
+        };
+        let payload = compressed.as_deref().unwrap_or(src);
+        out.push(FRAME_START);
+        out.extend_from_slice(&Integer64::from(src.len()));
+        out.push(b'\n');
+        out.extend_from_slice(&Integer64::from(payload.len()));
+        out.push(b'\n');
+        out.extend_from_slice(payload);
+    }
+}
+
+mod lz4 {
+    //! An LZ4 block compressor (greedy, with a single-entry hash table). See
+    //! <https://github.com/lz4/lz4/blob/dev/doc/lz4_Block_format.md> for the format
+
+    /// The shortest match
+    pub(super) const MIN_MATCH: usize = 4;
+    /// The last five bytes are always literals
+    const LAST_LITERALS: usize = 5;
+    /// The last match must start at least twelve bytes before the end of the block
+    const MF_LIMIT: usize = 12;
+    /// The largest offset that can be encoded
+    const MAX_DISTANCE: usize = u16::MAX as usize;
+    const HASH_LOG: u32 = 12;
+
+    fn read_u32(src: &[u8], pos: usize) -> u32 {
+        u32::from_le_bytes([src[pos], src[pos + 1], src[pos + 2], src[pos + 3]])
+    }
+
+    const fn hash(sequence: u32) -> usize {
+        (sequence.wrapping_mul(2654435761) >> (32 - HASH_LOG)) as usize
+    }
+
+    /// Write the part of a length that doesn't fit in the token
+    fn write_length(out: &mut Vec<u8>, mut len: usize) {
+        while len >= 255 {
+            out.push(255);
+            len -= 255;
+        }
+        out.push(len as u8);
+    }
+
+    fn write_literals(out: &mut Vec<u8>, literals: &[u8]) {
+        if literals.len() >= 15 {
+            write_length(out, literals.len() - 15);
+        }
+        out.extend_from_slice(literals);
+    }
+
+    pub fn compress(src: &[u8]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(src.len() / 2 + 16);
+        let mut anchor = 0;
+        if src.len() > MF_LIMIT {
+            // positions are stored plus one, so that zero means "nothing here"
+            let mut table = vec![0usize; 1 << HASH_LOG];
+            let match_end_limit = src.len() - LAST_LITERALS;
+            let mut pos = 0;
+            while pos <= src.len() - MF_LIMIT {
+                let sequence = read_u32(src, pos);
+                let slot = &mut table[hash(sequence)];
+                let candidate = *slot;
+                *slot = pos + 1;
+                let is_match = candidate != 0
+                    && pos - (candidate - 1) <= MAX_DISTANCE
+                    && read_u32(src, candidate - 1) == sequence;
+                if !is_match {
+                    pos += 1;
+                    continue;
+                }
+                let candidate = candidate - 1;
+                let mut match_len = MIN_MATCH;
+                while pos + match_len < match_end_limit
+                    && src[candidate + match_len] == src[pos + match_len]
+                {
+                    match_len += 1;
+                }
+                // the sequence: token, literals, offset and the rest of the match length
+                let literals = &src[anchor..pos];
+                let extra_match_len = match_len - MIN_MATCH;
+                out.push(((literals.len().min(15) as u8) << 4) | extra_match_len.min(15) as u8);
+                write_literals(&mut out, literals);
+                out.extend_from_slice(&((pos - candidate) as u16).to_le_bytes());
+                if extra_match_len >= 15 {
+                    write_length(&mut out, extra_match_len - 15);
+                }
+                pos += match_len;
+                anchor = pos;
+            }
+        }
+        // the last sequence only has literals
+        let literals = &src[anchor..];
+        out.push((literals.len().min(15) as u8) << 4);
+        write_literals(&mut out, literals);
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lz4, Codec};
+
+    /// Decompress an LZ4 block (what a client does)
+    fn decompress(src: &[u8]) -> Vec<u8> {
+        fn read_length(src: &[u8], i: &mut usize, mut len: usize) -> usize {
+            if len == 15 {
+                loop {
+                    let byte = src[*i];
+                    *i += 1;
+                    len += byte as usize;
+                    if byte != 255 {
+                        break;
+                    }
+                }
+            }
+            len
+        }
+        let mut out = Vec::new();
+        let mut i = 0;
+        loop {
+            let token = src[i];
+            i += 1;
+            let literals = read_length(src, &mut i, (token >> 4) as usize);
+            out.extend_from_slice(&src[i..i + literals]);
+            i += literals;
+            if i == src.len() {
+                return out;
+            }
+            let offset = u16::from_le_bytes([src[i], src[i + 1]]) as usize;
+            i += 2;
+            let match_len = read_length(src, &mut i, (token & 15) as usize) + lz4::MIN_MATCH;
+            let start = out.len() - offset;
+            for j in start..start + match_len {
+                out.push(out[j]);
+            }
+        }
+    }
+
+    fn inputs() -> [Vec<u8>; 7] {
+        let mut state = 0x2545F491u32;
+        let noise = (0..10_000)
+            .map(|_| {
+                // xorshift, so that there's (almost) nothing to match
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                state as u8
+            })
+            .collect();
+        [
+            vec![],
+            b"hello".to_vec(),
+            b"+4\nHEY!+4\nHEY!+4\nHEY!+4\nHEY!+4\nHEY!+4\nHEY!".to_vec(),
+            vec![0; 100_000],
+            (0..100_000u32).map(|i| (i * 7 % 251) as u8).collect(),
+            noise,
+            b"+5\nhello".repeat(1000),
+        ]
+    }
+
+    #[test]
+    fn test_lz4_roundtrip() {
+        for input in inputs() {
+            assert_eq!(decompress(&lz4::compress(&input)), input);
+        }
+        // repetitive data should actually get smaller
+        assert!(lz4::compress(&[b'x'; 4096]).len() < 64);
+    }
+
+    #[test]
+    fn test_lz4_reference_decompress() {
+        // what we send must be readable by clients that use the reference implementation
+        for input in inputs() {
+            let decompressed =
+                ::lz4::block::decompress(&lz4::compress(&input), Some(input.len() as i32));
+            assert_eq!(decompressed.unwrap(), input);
+        }
+    }
+
+    #[test]
+    fn test_lz4_reference_compress() {
+        // and the decompressor that the tests use must agree with the reference implementation
+        for input in inputs() {
+            let compressed = ::lz4::block::compress(&input, None, false).unwrap();
+            assert_eq!(decompress(&compressed), input);
+        }
+    }
+
+    #[test]
+    fn test_encode_frame() {
+        // small writes are sent as-is
+        let mut out = Vec::new();
+        Codec::Lz4.encode_frame(b"+4\nHEY!", &mut out);
+        assert_eq!(out, b"~7\n7\n+4\nHEY!");
+        // larger writes are compressed
+        let body = b"+5\nhello".repeat(100);
+        let mut out = Vec::new();
+        Codec::Lz4.encode_frame(&body, &mut out);
+        assert!(out.starts_with(b"~800\n"));
+        let payload = &out[out.iter().skip(5).position(|b| *b == b'\n').unwrap() + 6..];
+        assert!(payload.len() < body.len());
+        assert_eq!(decompress(payload), body);
+    }
+}
@@ -25,22 +25,162 @@
 */
 
 use {
-    super::{BufferedSocketStream, QueryResult},
+    super::{
+        clients::Client,
+        compress::Codec,
+        cursor::Cursors,
+        pubsub::{Message, Subscriber},
+        BufferedSocketStream, QueryResult,
+    },
     crate::{
         corestore::buffers::Integer64,
-        protocol::{interface::ProtocolSpec, ParseError},
+        kvengine::throttle::WriteThrottle,
+        protocol::{self, interface::ProtocolSpec, ParseError},
+        queryengine::{deadline::Deadline, ratelimit},
         IoResult,
     },
     bytes::BytesMut,
     std::{
-        io::{Error as IoError, ErrorKind},
+        io::{Error as IoError, ErrorKind, IoSlice},
         marker::PhantomData,
+        pin::Pin,
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        task::{Context, Poll},
+    },
+    tokio::{
+        io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufWriter, ReadBuf},
+        sync::mpsc,
     },
-    tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter},
 };
 
-const BUF_WRITE_CAP: usize = 8192;
-const BUF_READ_CAP: usize = 8192;
+/// The default size of the read and write buffers of a connection
+pub const DEFAULT_BUFFER_SIZE: usize = 8192;
+/// The size of the read and write buffers of new connections
+static BUFFER_SIZE: AtomicUsize = AtomicUsize::new(DEFAULT_BUFFER_SIZE);
+
+/// Set the size of the read and write buffers of new connections
+pub fn set_buffer_size(size: usize) {
+    BUFFER_SIZE.store(size, Ordering::Release)
+}
+
+/// The state of response compression on a connection (see [`super::compress`])
+enum Compression {
+    Off,
+    /// Turned on, but only once everything written so far has been flushed
+    Pending(Codec),
+    On {
+        codec: Codec,
+        /// what was written since the last flush
+        plain: Vec<u8>,
+        /// the frame that is being written to the stream
+        frame: Vec<u8>,
+        /// how much of `frame` has been written
+        pos: usize,
+    },
+}
+
+/// A stream that counts the bytes written to it (and compresses them if asked to)
+pub struct Metered<T> {
+    inner: T,
+    written: u64,
+    compression: Compression,
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Metered<T> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        Pin::new(&mut self.get_mut().inner).poll_read(cx, buf)
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Metered<T> {
+    fn poll_write(self: Pin<&mut Self>, cx: &mut Context<'_>, buf: &[u8]) -> Poll<IoResult<usize>> {
+        let this = self.get_mut();
+        if let Compression::On { plain, .. } = &mut this.compression {
+            // hold on to it until the next flush
+            plain.extend_from_slice(buf);
+            return Poll::Ready(Ok(buf.len()));
+        }
+        let ret = Pin::new(&mut this.inner).poll_write(cx, buf);
+        if let Poll::Ready(Ok(written)) = ret {
+            this.written += written as u64;
+        }
+        ret
+    }
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<IoResult<usize>> {
+        let this = self.get_mut();
+        if let Compression::On { plain, .. } = &mut this.compression {
+            let len = plain.len();
+            bufs.iter().for_each(|buf| plain.extend_from_slice(buf));
+            return Poll::Ready(Ok(plain.len() - len));
+        }
+        let ret = Pin::new(&mut this.inner).poll_write_vectored(cx, bufs);
+        if let Poll::Ready(Ok(written)) = ret {
+            this.written += written as u64;
+        }
+        ret
+    }
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        let this = self.get_mut();
+        if let Compression::On {
+            codec,
+            plain,
+            frame,
+            pos,
+        } = &mut this.compression
+        {
+            // everything written since the last flush goes into a single frame
+            if *pos == frame.len() && !plain.is_empty() {
+                frame.clear();
+                codec.encode_frame(plain, frame);
+                plain.clear();
+                *pos = 0;
+            }
+            while *pos < frame.len() {
+                match Pin::new(&mut this.inner).poll_write(cx, &frame[*pos..]) {
+                    Poll::Ready(Ok(0)) => return Poll::Ready(Err(ErrorKind::WriteZero.into())),
+                    Poll::Ready(Ok(written)) => {
+                        *pos += written;
+                        this.written += written as u64;
+                    }
+                    Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+        }
+        let ret = Pin::new(&mut this.inner).poll_flush(cx);
+        if let (Poll::Ready(Ok(())), Compression::Pending(codec)) = (&ret, &this.compression) {
+            // everything before the switch has been sent as-is
+            this.compression = Compression::On {
+                codec: *codec,
+                plain: Vec::new(),
+                frame: Vec::new(),
+                pos: 0,
+            };
+        }
+        ret
+    }
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        // send whatever is left in the last frame
+        match self.as_mut().poll_flush(cx) {
+            Poll::Ready(Ok(())) => Pin::new(&mut self.get_mut().inner).poll_shutdown(cx),
+            ret => ret,
+        }
+    }
+}
 
 /// A generic connection type
 ///
@@ -48,27 +188,116 @@ const BUF_READ_CAP: usize = 8192;
 /// 1. A stream (TCP, TLS(TCP), UDS, ...)
 /// 2. A protocol (one that implements [`ProtocolSpec`])
 pub struct Connection<T, P> {
-    pub(super) stream: BufWriter<T>,
+    pub(super) stream: BufWriter<Metered<T>>,
     pub(super) buffer: BytesMut,
+    strict: bool,
+    /// the pub/sub subscriptions of this connection
+    subscriber: Subscriber,
+    /// the messages published to the channels this connection subscribed to
+    messages: mpsc::Receiver<Message>,
+    /// the rate limit of this connection
+    rate_limit: WriteThrottle,
+    /// the deadline of the query that is running
+    deadline: Deadline,
+    /// the cursors opened by this connection
+    cursors: Cursors,
+    /// the client registered for this connection (connections that aren't handled by a
+    /// listener, like HTTP sessions, aren't registered)
+    client: Option<Arc<Client>>,
     _marker: PhantomData<P>,
 }
 
 impl<T: BufferedSocketStream, P: ProtocolSpec> Connection<T, P> {
     pub fn new(stream: T) -> Self {
+        let buffer_size = BUFFER_SIZE.load(Ordering::Acquire);
+        let (subscriber, messages) = Subscriber::new();
         Connection {
-            stream: BufWriter::with_capacity(BUF_WRITE_CAP, stream),
-            buffer: BytesMut::with_capacity(BUF_READ_CAP),
+            stream: BufWriter::with_capacity(
+                buffer_size,
+                Metered {
+                    inner: stream,
+                    written: 0,
+                    compression: Compression::Off,
+                },
+            ),
+            buffer: BytesMut::with_capacity(buffer_size),
+            strict: protocol::is_strict(),
+            subscriber,
+            messages,
+            rate_limit: ratelimit::connection_bucket(),
+            deadline: Deadline::default(),
+            cursors: Cursors::default(),
+            client: None,
             _marker: PhantomData,
         }
     }
+    /// Returns the underlying stream (anything still buffered hasn't been written to it yet)
+    pub(super) fn stream_mut(&mut self) -> &mut T {
+        &mut self.stream.get_mut().inner
+    }
+    /// Returns the number of bytes written to the stream so far (excluding what is still
+    /// buffered)
+    pub(super) fn bytes_written(&self) -> u64 {
+        self.stream.get_ref().written
+    }
+    /// Returns the pub/sub subscriptions of this connection
+    pub fn subscriber(&mut self) -> &mut Subscriber {
+        &mut self.subscriber
+    }
+    /// Returns the rate limit of this connection
+    pub fn rate_limit(&self) -> &WriteThrottle {
+        &self.rate_limit
+    }
+    /// Returns the cursors opened by this connection
+    pub fn cursors(&mut self) -> &mut Cursors {
+        &mut self.cursors
+    }
+    /// Returns the client registered for this connection, if any
+    pub fn client(&self) -> Option<&Arc<Client>> {
+        self.client.as_ref()
+    }
+    /// Register this connection as a client connected from `peer`
+    pub(super) fn register_client(&mut self, peer: String) -> Arc<Client> {
+        let client = Client::register(peer);
+        self.client = Some(client.clone());
+        client
+    }
+    /// Returns the deadline of the query that is running
+    pub fn deadline(&self) -> Deadline {
+        self.deadline
+    }
+    /// Start the deadline of a new query
+    pub fn start_deadline(&mut self) {
+        self.deadline = Deadline::start();
+    }
+    /// Returns the codec that responses are compressed with (if any)
+    pub fn compression(&self) -> Option<Codec> {
+        match self.stream.get_ref().compression {
+            Compression::Off => None,
+            Compression::Pending(codec) | Compression::On { codec, .. } => Some(codec),
+        }
+    }
+    /// Compress everything written after the next flush with `codec`
+    pub fn enable_compression(&mut self, codec: Codec) {
+        let metered = self.stream.get_mut();
+        if let Compression::Off = metered.compression {
+            metered.compression = Compression::Pending(codec);
+        }
+    }
 }
 
 // protocol read
 impl<T: BufferedSocketStream, P: ProtocolSpec> Connection<T, P> {
-    /// Attempt to read a query
+    /// Attempt to read a query. If a pub/sub message arrives first, it is returned instead
+    /// (whatever was read of the query stays in the buffer)
     pub(super) async fn read_query(&mut self) -> IoResult<QueryResult> {
         loop {
-            match self.stream.read_buf(&mut self.buffer).await {
+            // both are cancel safe, so nothing is lost if the other one completes first
+            let read = tokio::select! {
+                read = self.stream.read_buf(&mut self.buffer) => read,
+                Some(message) = self.messages.recv() => return Ok(QueryResult::Message(message)),
+            };
+            match read {
                 Ok(0) => {
                     if self.buffer.is_empty() {
                         // buffer is empty, and the remote pulled off (simple disconnection)
@@ -82,9 +311,27 @@ impl<T: BufferedSocketStream, P: ProtocolSpec> Connection<T, P> {
                 Err(e) => return Err(e),
             }
             // see if we have buffered enough data to run anything
-            match P::decode_packet(self.buffer.as_ref()) {
+            let decoded = if self.strict {
+                P::decode_packet_strict(self.buffer.as_ref())
+            } else {
+                P::decode_packet(self.buffer.as_ref())
+            };
+            match decoded {
                 Ok(query_with_advance) => return Ok(QueryResult::Q(query_with_advance)),
+                Err(ParseError::NotEnough) if self.buffer.len() > protocol::max_query_size() => {
+                    // we can't tell where the query ends (it may not even be valid), so this
+                    // can't be skipped. close the connection
+                    self.write_error(P::FULLRESP_QUERY_TOO_LARGE).await?;
+                    return Ok(QueryResult::Disconnected);
+                }
                 Err(ParseError::NotEnough) => {}
+                Err(e) if e.is_fatal() => {
+                    // strict mode rejects the query before it is read completely, so we can't
+                    // tell where it ends. close the connection
+                    self.write_error(P::SKYHASH_PARSE_ERROR_LUT[e as usize - 1])
+                        .await?;
+                    return Ok(QueryResult::Disconnected);
+                }
                 Err(e) => {
                     self.write_error(P::SKYHASH_PARSE_ERROR_LUT[e as usize - 1])
                         .await?;
@@ -124,12 +371,41 @@ impl<T: BufferedSocketStream, P: ProtocolSpec> Connection<T, P> {
     pub async fn _write_raw(&mut self, raw: &[u8]) -> IoResult<()> {
         self.stream.write_all(raw).await
     }
+    /// Write a pub/sub message as an out-of-band response: `[message, <channel>, <payload>]`
+    /// or, if it was delivered through a pattern subscription,
+    /// `[pmessage, <pattern>, <channel>, <payload>]`
+    pub(super) async fn write_message(&mut self, message: &Message) -> IoResult<()> {
+        self.write_simple_query_header().await?;
+        match &message.pattern {
+            Some(pattern) => {
+                self.write_typed_non_null_array_header(4, P::TSYMBOL_BINARY)
+                    .await?;
+                self.write_typed_non_null_array_element(b"pmessage").await?;
+                self.write_typed_non_null_array_element(pattern).await?;
+            }
+            None => {
+                self.write_typed_non_null_array_header(3, P::TSYMBOL_BINARY)
+                    .await?;
+                self.write_typed_non_null_array_element(b"message").await?;
+            }
+        }
+        self.write_typed_non_null_array_element(&message.channel)
+            .await?;
+        self.write_typed_non_null_array_element(&message.payload)
+            .await?;
+        self.stream.flush().await
+    }
 }
 
 // protocol write (dataframe)
 impl<T: BufferedSocketStream, P: ProtocolSpec> Connection<T, P> {
     // monoelements
     /// Encode and write a length-prefixed monoelement
+    /// Echo the trace ID of a query (this is written before the response)
+    pub async fn write_trace_id(&mut self, trace_id: &[u8]) -> IoResult<()> {
+        self.write_mono_length_prefixed_with_tsymbol(trace_id, P::TSYMBOL_TRACE_ID)
+            .await
+    }
     pub async fn write_mono_length_prefixed_with_tsymbol(
         &mut self,
         data: &[u8],
@@ -183,6 +459,25 @@ impl<T: BufferedSocketStream, P: ProtocolSpec> Connection<T, P> {
         self.write_mono_with_tsymbol(float.to_string().as_bytes(), P::TSYMBOL_FLOAT)
             .await
     }
+    /// Encode and write an `f64`
+    pub async fn write_double(&mut self, float: f64) -> IoResult<()> {
+        self.write_mono_with_tsymbol(float.to_string().as_bytes(), P::TSYMBOL_FLOAT)
+            .await
+    }
+    /// Encode and write a boolean
+    pub async fn write_bool(&mut self, boolean: bool) -> IoResult<()> {
+        let body: &[u8] = if boolean { b"1" } else { b"0" };
+        self.write_mono_with_tsymbol(body, P::TSYMBOL_BOOL).await
+    }
+
+    // map
+    /// Write a map header (including the number of pairs). Every pair is then written as a key
+    /// element followed by a value element
+    pub async fn write_map_header(&mut self, len: usize) -> IoResult<()> {
+        self.stream.write_u8(P::TSYMBOL_MAP).await?;
+        self.stream.write_all(&Integer64::from(len)).await?;
+        self.stream.write_u8(P::LF).await
+    }
 
     // typed array
     /// Write a typed array header (including type information and size)
@@ -228,6 +523,8 @@ impl<T: BufferedSocketStream, P: ProtocolSpec> Connection<T, P> {
     }
     /// Encode and write typed non-null array element
     pub async fn write_typed_non_null_array_element(&mut self, element: &[u8]) -> IoResult<()> {
+        // the header is out, so there's no way to fail the query cleanly anymore
+        self.deadline.check_io()?;
         self.write_typed_array_element(element).await
     }
     /// Encode and write a typed non-null array
@@ -246,3 +543,29 @@ impl<T: BufferedSocketStream, P: ProtocolSpec> Connection<T, P> {
         Ok(())
     }
 }
+
+#[tokio::test]
+async fn test_write_large_body() {
+    use crate::protocol::Skyhash2;
+    let (client, server) = tokio::io::duplex(DEFAULT_BUFFER_SIZE);
+    let mut con = Connection::<_, Skyhash2>::new(server);
+    let small: Vec<u8> = b"sayan".to_vec();
+    let large: Vec<u8> = (0..DEFAULT_BUFFER_SIZE * 10 + 7).map(|i| i as u8).collect();
+    let writer = async move {
+        con.write_binary(&small).await.unwrap();
+        con.write_binary(&large).await.unwrap();
+        con.stream.flush().await.unwrap();
+    };
+    let reader = async move {
+        let mut client = client;
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        received
+    };
+    // the pipe is smaller than the large body, so it has to be read while it is streamed
+    let ((), received) = tokio::join!(writer, reader);
+    let mut expected = b"?5\nsayan?".to_vec();
+    expected.extend_from_slice(format!("{}\n", DEFAULT_BUFFER_SIZE * 10 + 7).as_bytes());
+    expected.extend((0..DEFAULT_BUFFER_SIZE * 10 + 7).map(|i| i as u8));
+    assert_eq!(received, expected);
+}
@@ -0,0 +1,171 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Cursors
+//!
+//! Actions that can return unbounded result sets can return them a page at a time instead: the
+//! result is taken once, and the first page is returned along with the ID of a cursor over the
+//! rest, which is then read with `FETCH <cursor>`. A cursor ID of zero means that there is
+//! nothing left. Cursors belong to the connection that opened them, and a cursor that isn't
+//! read for [`IDLE_TIMEOUT`] is dropped
+
+use {
+    crate::corestore::SharedSlice,
+    std::{
+        collections::HashMap,
+        time::{Duration, Instant},
+        vec::IntoIter,
+    },
+};
+
+/// How long a cursor is kept without being read
+pub const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+/// The number of cursors that a connection can have open. Opening another one drops the
+/// cursor that was read least recently
+pub const MAX_CURSORS: usize = 16;
+
+/// A page of a result set
+pub struct Page {
+    /// the ID of the cursor over the rest of the result set (zero if there is nothing left)
+    pub cursor: u64,
+    /// the tsymbol of the items
+    pub tsymbol: u8,
+    pub items: Vec<SharedSlice>,
+}
+
+/// What is left of a result set
+struct Cursor {
+    items: IntoIter<SharedSlice>,
+    tsymbol: u8,
+    page_size: usize,
+    last_used: Instant,
+}
+
+/// The open cursors of a connection
+#[derive(Default)]
+pub struct Cursors {
+    open: HashMap<u64, Cursor>,
+    last_id: u64,
+}
+
+impl Cursors {
+    /// Returns the first page of `items` (of `page_size` items) and opens a cursor over the
+    /// rest, if there's anything left
+    pub fn open(&mut self, items: Vec<SharedSlice>, tsymbol: u8, page_size: usize) -> Page {
+        self.expire();
+        let cursor = Cursor {
+            items: items.into_iter(),
+            tsymbol,
+            page_size,
+            last_used: Instant::now(),
+        };
+        if self.open.len() >= MAX_CURSORS {
+            let oldest = self
+                .open
+                .iter()
+                .min_by_key(|(id, cursor)| (cursor.last_used, **id))
+                .map(|(id, _)| *id);
+            if let Some(oldest) = oldest {
+                self.open.remove(&oldest);
+            }
+        }
+        self.last_id = self.last_id.checked_add(1).unwrap_or(1);
+        self.next_page(self.last_id, cursor)
+    }
+    /// Returns the next page of the cursor `id`, or `None` if there's no such cursor (it was
+    /// never opened, was read to the end or has expired)
+    pub fn fetch(&mut self, id: u64) -> Option<Page> {
+        self.expire();
+        let cursor = self.open.remove(&id)?;
+        Some(self.next_page(id, cursor))
+    }
+    fn next_page(&mut self, id: u64, mut cursor: Cursor) -> Page {
+        let items: Vec<SharedSlice> = cursor.items.by_ref().take(cursor.page_size).collect();
+        let tsymbol = cursor.tsymbol;
+        let id = if cursor.items.len() == 0 {
+            0
+        } else {
+            cursor.last_used = Instant::now();
+            self.open.insert(id, cursor);
+            id
+        };
+        Page {
+            cursor: id,
+            tsymbol,
+            items,
+        }
+    }
+    /// Drop the cursors that haven't been read for [`IDLE_TIMEOUT`]
+    fn expire(&mut self) {
+        self.open
+            .retain(|_, cursor| cursor.last_used.elapsed() < IDLE_TIMEOUT);
+    }
+}
+
+#[cfg(test)]
+fn items(count: usize) -> Vec<SharedSlice> {
+    (0..count)
+        .map(|i| SharedSlice::new(i.to_string().as_bytes()))
+        .collect()
+}
+
+#[test]
+fn test_cursor_pages() {
+    let mut cursors = Cursors::default();
+    let page = cursors.open(items(5), b'+', 2);
+    assert_ne!(page.cursor, 0);
+    assert_eq!(page.items, items(2));
+    let id = page.cursor;
+    let page = cursors.fetch(id).unwrap();
+    assert_eq!(page.cursor, id);
+    assert_eq!(page.items, items(4)[2..]);
+    let page = cursors.fetch(id).unwrap();
+    assert_eq!(page.cursor, 0);
+    assert_eq!(page.items, items(5)[4..]);
+    // the cursor was read to the end
+    assert!(cursors.fetch(id).is_none());
+    // everything fits in the first page
+    let page = cursors.open(items(2), b'+', 2);
+    assert_eq!(page.cursor, 0);
+    assert_eq!(page.items, items(2));
+    assert!(cursors.fetch(0).is_none());
+}
+
+#[test]
+fn test_cursor_limit() {
+    let mut cursors = Cursors::default();
+    let first = cursors.open(items(3), b'+', 1).cursor;
+    for _ in 1..MAX_CURSORS {
+        cursors.open(items(3), b'+', 1);
+    }
+    assert_eq!(cursors.fetch(first).unwrap().cursor, first);
+    // the least recently read cursor is dropped
+    let second = first + 1;
+    cursors.open(items(3), b'+', 1);
+    assert_eq!(cursors.open.len(), MAX_CURSORS);
+    assert!(cursors.fetch(second).is_none());
+    assert!(cursors.fetch(first).is_some());
+}
@@ -0,0 +1,705 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # HTTP gateway
+//!
+//! An optional HTTP/1.1 listener for clients that can't use a native driver (like `curl` or
+//! serverless functions). It exposes these endpoints:
+//! - `GET /k/<table>/<key>`: returns the value of `key`
+//! - `PUT /k/<table>/<key>`: sets `key` to the request body (overwriting any existing value)
+//! - `DELETE /k/<table>/<key>`: removes `key`
+//! - `POST /query`: runs the request body as a BlueQL query
+//!
+//! `<table>` is the full name of the table (`space.model`). Every request is run in a fresh
+//! [`Session`], so the table of one request has no effect on the next. If authn is enabled,
+//! requests have to use basic authentication with the username and the token as the password
+
+use {
+    super::{
+        iplimit::IpLimiter,
+        listener::BaseListener,
+        session::{self, Element, Session, CODE_NIL, CODE_OKAY},
+        NetBackoff,
+    },
+    crate::{
+        auth::AuthProvider, corestore::export::json_string, corestore::Corestore,
+        util::error::SkyResult, IoResult,
+    },
+    bytes::{Buf, BytesMut},
+    std::{
+        net::{IpAddr, SocketAddr},
+        sync::Arc,
+    },
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+        sync::{broadcast, mpsc, Semaphore},
+    },
+};
+
+/// The maximum size of the request line and the headers
+const MAX_HEAD_SIZE: usize = 8 * 1024;
+/// The maximum size of a request body
+const MAX_BODY_SIZE: usize = 64 * 1024 * 1024;
+/// The maximum number of headers in a request
+const MAX_HEADERS: usize = 64;
+/// The prefix of the key endpoints
+const KEY_PREFIX: &str = "/k/";
+/// The query endpoint
+const QUERY_PATH: &str = "/query";
+
+/// The HTTP listener
+pub struct HttpListener {
+    pub base: BaseListener,
+}
+
+impl HttpListener {
+    /// Accept an incoming connection
+    async fn accept(&mut self) -> IoResult<(TcpStream, SocketAddr)> {
+        let backoff = NetBackoff::new();
+        loop {
+            match self.base.listener.accept().await {
+                Ok(accepted) => return Ok(accepted),
+                Err(e) => {
+                    if backoff.should_disconnect() {
+                        return Err(e);
+                    }
+                }
+            }
+            backoff.spin().await;
+        }
+    }
+    /// Run the listener
+    pub async fn run(&mut self) -> IoResult<()> {
+        loop {
+            self.base.climit.acquire().await.unwrap().forget();
+            // SECURITY: as with the other listeners, errors in the accept loop must not take
+            // the server down
+            let (stream, addr) = skip_loop_err!(self.accept().await);
+            let permit = match self.base.iplimit.acquire(addr.ip()) {
+                Some(permit) => permit,
+                None => {
+                    self.base.climit.add_permits(1);
+                    tokio::spawn(refuse(stream));
+                    continue;
+                }
+            };
+            let mut handler = HttpHandler {
+                db: self.base.db.clone(),
+                stream,
+                buffer: BytesMut::with_capacity(super::DEFAULT_BUFFER_SIZE),
+                auth: self.base.auth.clone(),
+                climit: self.base.climit.clone(),
+                termination_signal: self.base.signal.subscribe(),
+                _term_sig_tx: self.base.terminate_tx.clone(),
+            };
+            tokio::spawn(async move {
+                // hold on to the IP's permit until the connection is closed
+                let _permit = permit;
+                if let Err(e) = handler.run().await {
+                    log::error!("Error: {}", e);
+                }
+            });
+        }
+    }
+}
+
+/// Run `listener` if there is one (otherwise this never returns)
+pub async fn run(listener: &mut Option<HttpListener>) -> IoResult<()> {
+    match listener {
+        Some(listener) => listener.run().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Start the HTTP listener on `port`
+pub async fn connect(
+    host: IpAddr,
+    port: u16,
+    climit: Arc<Semaphore>,
+    iplimit: Arc<IpLimiter>,
+    db: Corestore,
+    auth: AuthProvider,
+    signal: broadcast::Sender<()>,
+) -> SkyResult<HttpListener> {
+    // the PROXY protocol only applies to the native listeners
+    let base = BaseListener::init(&db, auth, host, port, climit, iplimit, None, signal).await?;
+    log::info!("HTTP listener started on http://{host}:{port}");
+    Ok(HttpListener { base })
+}
+
+#[derive(Debug, PartialEq)]
+/// The request line and the headers that we care about
+struct Head {
+    method: String,
+    path: String,
+    content_length: usize,
+    keep_alive: bool,
+    /// the credentials of a basic `Authorization` header
+    credentials: Option<(Vec<u8>, Vec<u8>)>,
+}
+
+#[derive(Debug, PartialEq)]
+enum ParseResult {
+    Head(Head, usize),
+    Incomplete,
+    Error(u16),
+}
+
+/// Parse the head of a request. If it is complete, the number of bytes it took is returned
+/// along with it
+fn parse_head(buf: &[u8]) -> ParseResult {
+    let end = match buf.windows(4).position(|w| w == b"\r\n\r\n") {
+        Some(end) if end + 4 <= MAX_HEAD_SIZE => end,
+        Some(_) => return ParseResult::Error(431),
+        None if buf.len() >= MAX_HEAD_SIZE => return ParseResult::Error(431),
+        None => return ParseResult::Incomplete,
+    };
+    let head = match std::str::from_utf8(&buf[..end]) {
+        Ok(head) => head,
+        Err(_) => return ParseResult::Error(400),
+    };
+    let mut lines = head.split("\r\n");
+    let mut request_line = lines.next().unwrap_or_default().split(' ');
+    let (method, path, version) = match (
+        request_line.next(),
+        request_line.next(),
+        request_line.next(),
+        request_line.next(),
+    ) {
+        (Some(method), Some(path), Some(version), None) => (method, path, version),
+        _ => return ParseResult::Error(400),
+    };
+    let mut keep_alive = match version {
+        "HTTP/1.1" => true,
+        "HTTP/1.0" => false,
+        _ => return ParseResult::Error(505),
+    };
+    let mut content_length = 0;
+    let mut credentials = None;
+    for (i, line) in lines.enumerate() {
+        if i == MAX_HEADERS {
+            return ParseResult::Error(431);
+        }
+        let (name, value) = match line.split_once(':') {
+            Some((name, value)) => (name, value.trim()),
+            None => return ParseResult::Error(400),
+        };
+        if name.eq_ignore_ascii_case("content-length") {
+            content_length = match value.parse() {
+                Ok(len) if len <= MAX_BODY_SIZE => len,
+                Ok(_) => return ParseResult::Error(413),
+                Err(_) => return ParseResult::Error(400),
+            };
+        } else if name.eq_ignore_ascii_case("transfer-encoding") {
+            // we need to know the size of the body upfront
+            return ParseResult::Error(411);
+        } else if name.eq_ignore_ascii_case("connection") {
+            if value.eq_ignore_ascii_case("close") {
+                keep_alive = false;
+            } else if value.eq_ignore_ascii_case("keep-alive") {
+                keep_alive = true;
+            }
+        } else if name.eq_ignore_ascii_case("authorization") {
+            credentials = match self::basic_credentials(value) {
+                Some(credentials) => Some(credentials),
+                None => return ParseResult::Error(401),
+            };
+        }
+    }
+    let head = Head {
+        method: method.to_owned(),
+        path: path.to_owned(),
+        content_length,
+        keep_alive,
+        credentials,
+    };
+    ParseResult::Head(head, end + 4)
+}
+
+/// Returns the username and the token in a basic `Authorization` header
+fn basic_credentials(value: &str) -> Option<(Vec<u8>, Vec<u8>)> {
+    let (scheme, encoded) = value.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = base64::decode(encoded.trim()).ok()?;
+    let split = decoded.iter().position(|b| *b == b':')?;
+    Some((decoded[..split].to_owned(), decoded[split + 1..].to_owned()))
+}
+
+/// Decode a percent-encoded path segment
+fn percent_decode(segment: &str) -> Option<Vec<u8>> {
+    let mut decoded = Vec::with_capacity(segment.len());
+    let mut bytes = segment.bytes();
+    while let Some(byte) = bytes.next() {
+        if byte == b'%' {
+            let hex = [bytes.next()?, bytes.next()?];
+            let hex = std::str::from_utf8(&hex).ok()?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+        } else {
+            decoded.push(byte);
+        }
+    }
+    Some(decoded)
+}
+
+/// Returns true if `table` is a valid `space.model`. This is checked before the table is used
+/// in a query, so that the path can't be used to run something else
+fn is_table_name(table: &str) -> bool {
+    let valid = |part: &str| {
+        part.bytes().next().is_some_and(|b| b.is_ascii_alphabetic())
+            && part.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+    };
+    table
+        .split_once('.')
+        .is_some_and(|(space, model)| valid(space) && valid(model))
+}
+
+#[derive(Debug, PartialEq)]
+/// What a request asks for
+enum Route {
+    /// A key action (like `GET`), run on the given table
+    Key {
+        table: String,
+        action: &'static [u8],
+        key: Vec<u8>,
+    },
+    /// A BlueQL query
+    Query,
+}
+
+/// Route a request, or return the status to reply with if it can't be routed
+fn route(method: &str, path: &str) -> Result<Route, u16> {
+    // we don't have any use for the query string
+    let path = path.split_once('?').map_or(path, |(path, _)| path);
+    if path == QUERY_PATH {
+        return match method {
+            "POST" => Ok(Route::Query),
+            _ => Err(405),
+        };
+    }
+    let (table, key) = path
+        .strip_prefix(KEY_PREFIX)
+        .and_then(|path| path.split_once('/'))
+        .ok_or(404u16)?;
+    if !self::is_table_name(table) {
+        return Err(400);
+    }
+    let key = self::percent_decode(key).ok_or(400u16)?;
+    if key.is_empty() {
+        return Err(404);
+    }
+    let action: &'static [u8] = match method {
+        "GET" => b"GET",
+        "PUT" => b"USET",
+        "DELETE" => b"DEL",
+        _ => return Err(405),
+    };
+    Ok(Route::Key {
+        table: table.to_owned(),
+        action,
+        key,
+    })
+}
+
+/// A response to a request
+struct Response {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+    /// extra headers (each ending with CRLF)
+    headers: String,
+}
+
+impl Response {
+    fn new(status: u16, content_type: &'static str, body: Vec<u8>) -> Self {
+        Self {
+            status,
+            content_type,
+            body,
+            headers: String::new(),
+        }
+    }
+    fn empty(status: u16) -> Self {
+        let mut ret = Self::new(status, "text/plain", Vec::new());
+        if status == 401 {
+            ret.headers
+                .push_str("WWW-Authenticate: Basic realm=\"skytable\"\r\n");
+        }
+        ret
+    }
+    /// Write the response to `out`
+    fn write(&self, keep_alive: bool, out: &mut Vec<u8>) {
+        let mut head = format!(
+            "HTTP/1.1 {} {}\r\nContent-Length: {}\r\n",
+            self.status,
+            self::reason(self.status),
+            self.body.len()
+        );
+        if !self.body.is_empty() {
+            head.push_str(&format!("Content-Type: {}\r\n", self.content_type));
+        }
+        if !keep_alive {
+            head.push_str("Connection: close\r\n");
+        }
+        head.push_str(&self.headers);
+        head.push_str("\r\n");
+        out.extend_from_slice(head.as_bytes());
+        out.extend_from_slice(&self.body);
+    }
+}
+
+/// Returns the reason phrase for `status`
+const fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        403 => "Forbidden",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        411 => "Length Required",
+        413 => "Payload Too Large",
+        429 => "Too Many Requests",
+        431 => "Request Header Fields Too Large",
+        500 => "Internal Server Error",
+        503 => "Service Unavailable",
+        505 => "HTTP Version Not Supported",
+        507 => "Insufficient Storage",
+        _ => "Unknown",
+    }
+}
+
+/// Translate the response to an action into an HTTP response
+fn respond(action: Option<&[u8]>, element: Option<Element<'_>>) -> Response {
+    let element = match element {
+        Some(element) => element,
+        None => return Response::empty(500),
+    };
+    match element {
+        // a delete that removed nothing means that the key wasn't there
+        Element::Int(b"0") if action == Some(b"DEL") => Response::empty(404),
+        // a set has nothing to return
+        Element::Int(_) if action == Some(b"USET") => Response::empty(204),
+        Element::Code(CODE_OKAY) => Response::empty(204),
+        Element::Code(CODE_NIL) => Response::empty(404),
+        Element::Code(code) => self::respond_code(code),
+        Element::Str(value) => Response::new(200, "application/octet-stream", value.to_owned()),
+        Element::Int(value) | Element::Float(value) => {
+            Response::new(200, "text/plain", value.to_owned())
+        }
+        Element::Bool(value) => Response::new(200, "text/plain", value.to_string().into_bytes()),
+        element @ (Element::Array(_) | Element::Map(_)) => {
+            let mut body = String::new();
+            json_value(&mut body, &element);
+            Response::new(200, "application/json", body.into_bytes())
+        }
+    }
+}
+
+/// Append `element` as a JSON value. Strings and response codes become JSON strings, and so do
+/// floats that JSON can't represent (like `NaN`)
+fn json_value(out: &mut String, element: &Element<'_>) {
+    match element {
+        Element::Code(value) | Element::Str(value) => {
+            json_string(out, &String::from_utf8_lossy(value))
+        }
+        Element::Int(value) => out.push_str(&String::from_utf8_lossy(value)),
+        Element::Float(value) => {
+            let value = String::from_utf8_lossy(value);
+            if value.parse::<f64>().is_ok_and(f64::is_finite) {
+                out.push_str(&value)
+            } else {
+                json_string(out, &value)
+            }
+        }
+        Element::Bool(value) => out.push_str(if *value { "true" } else { "false" }),
+        Element::Array(elements) => {
+            out.push('[');
+            for (i, element) in elements.iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                match element {
+                    Some(element) => json_string(out, &String::from_utf8_lossy(element)),
+                    None => out.push_str("null"),
+                }
+            }
+            out.push(']');
+        }
+        Element::Map(pairs) => {
+            out.push('{');
+            for (i, (key, value)) in pairs.iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+                // JSON keys have to be strings
+                match key {
+                    Element::Code(key)
+                    | Element::Str(key)
+                    | Element::Int(key)
+                    | Element::Float(key) => json_string(out, &String::from_utf8_lossy(key)),
+                    key => {
+                        let mut text = String::new();
+                        json_value(&mut text, key);
+                        json_string(out, &text)
+                    }
+                }
+                out.push(':');
+                json_value(out, value);
+            }
+            out.push('}');
+        }
+    }
+}
+
+/// Translate an error code (or an error string) into an HTTP response
+fn respond_code(code: &[u8]) -> Response {
+    let status = match code {
+        b"container-not-found" | b"unknown-model" => 404,
+        b"10" => 401,
+        b"11" => 403,
+        b"5" => 500,
+        b"err-out-of-memory" | b"err-quota-exceeded" => 507,
+        b"err-too-large" => 413,
+        b"err-access-after-termsig" => 503,
+        _ => 400,
+    };
+    let mut ret = Response::new(status, "text/plain", code.to_owned());
+    if let Some(retry_after_ms) = code
+        .strip_prefix(b"err-throttled-retry-after-")
+        .and_then(|ms| std::str::from_utf8(ms).ok())
+        .and_then(|ms| ms.parse::<u64>().ok())
+    {
+        ret.status = 429;
+        ret.headers = format!("Retry-After: {}\r\n", retry_after_ms.div_ceil(1000));
+    }
+    ret
+}
+
+/// Refuse a connection because its IP has too many connections open
+async fn refuse(mut stream: TcpStream) {
+    let mut out = Vec::new();
+    Response::new(429, "text/plain", b"err-too-many-connections".to_vec()).write(false, &mut out);
+    let _ = stream.write_all(&out).await;
+}
+
+/// Handles an HTTP connection
+struct HttpHandler {
+    db: Corestore,
+    stream: TcpStream,
+    buffer: BytesMut,
+    auth: AuthProvider,
+    climit: Arc<Semaphore>,
+    termination_signal: broadcast::Receiver<()>,
+    _term_sig_tx: mpsc::Sender<()>,
+}
+
+impl HttpHandler {
+    async fn run(&mut self) -> IoResult<()> {
+        let mut out = Vec::new();
+        loop {
+            let (head, advance) = match parse_head(&self.buffer) {
+                ParseResult::Head(head, advance) => (head, advance),
+                ParseResult::Incomplete => {
+                    if !self.read().await? {
+                        return Ok(());
+                    }
+                    continue;
+                }
+                ParseResult::Error(status) => {
+                    Response::empty(status).write(false, &mut out);
+                    return self.stream.write_all(&out).await;
+                }
+            };
+            while self.buffer.len() < advance + head.content_length {
+                if !self.read().await? {
+                    return Ok(());
+                }
+            }
+            self.buffer.advance(advance);
+            let body = self.buffer.split_to(head.content_length);
+            let response = self.execute(&head, &body).await?;
+            response.write(head.keep_alive, &mut out);
+            self.stream.write_all(&out).await?;
+            out.clear();
+            if !head.keep_alive {
+                return Ok(());
+            }
+        }
+    }
+    /// Read more data into the buffer. Returns false if the connection was closed (or if the
+    /// server is shutting down)
+    async fn read(&mut self) -> IoResult<bool> {
+        let read = tokio::select! {
+            read = self.stream.read_buf(&mut self.buffer) => read?,
+            _ = self.termination_signal.recv() => return Ok(false),
+        };
+        Ok(read != 0)
+    }
+    /// Execute a request
+    async fn execute(&mut self, head: &Head, body: &[u8]) -> IoResult<Response> {
+        let route = match self::route(&head.method, &head.path) {
+            Ok(route) => route,
+            Err(status) => return Ok(Response::empty(status)),
+        };
+        let mut session = Session::new(self.db.clone(), self.auth.clone());
+        if !session.authenticated() {
+            let (user, token) = match &head.credentials {
+                Some(credentials) => credentials,
+                None => return Ok(Response::empty(401)),
+            };
+            let login = [
+                b"AUTH".to_vec(),
+                b"LOGIN".to_vec(),
+                user.to_owned(),
+                token.to_owned(),
+            ];
+            let response = session.execute_stage(&login).await?;
+            if !session::decode(&response).is_some_and(|element| element.succeeded()) {
+                return Ok(Response::empty(401));
+            }
+        }
+        match route {
+            Route::Query => {
+                let response = session.execute_stage(&[body.to_owned()]).await?;
+                Ok(self::respond(None, session::decode(&response)))
+            }
+            Route::Key { table, action, key } => {
+                let response = session
+                    .execute_stage(&[format!("use {table}").into_bytes()])
+                    .await?;
+                match session::decode(&response) {
+                    Some(element) if element.succeeded() => {}
+                    element => return Ok(self::respond(None, element)),
+                }
+                let mut stage = vec![action.to_vec(), key];
+                if action == b"USET" {
+                    stage.push(body.to_owned());
+                }
+                let response = session.execute_stage(&stage).await?;
+                Ok(self::respond(Some(action), session::decode(&response)))
+            }
+        }
+    }
+}
+
+impl Drop for HttpHandler {
+    fn drop(&mut self) {
+        // return the permit to the semaphore (even if there was a panic)
+        self.climit.add_permits(1);
+    }
+}
+
+#[test]
+fn test_parse_head() {
+    let request =
+        b"GET /k/default.default/x HTTP/1.1\r\nHost: localhost\r\nContent-Length: 3\r\n\r\nabc";
+    assert_eq!(
+        parse_head(request),
+        ParseResult::Head(
+            Head {
+                method: "GET".to_owned(),
+                path: "/k/default.default/x".to_owned(),
+                content_length: 3,
+                keep_alive: true,
+                credentials: None,
+            },
+            request.len() - 3
+        )
+    );
+    let request = b"PUT /query HTTP/1.0\r\nAuthorization: Basic dXNlcjp0b2tlbjp4\r\n\r\n";
+    match parse_head(request) {
+        ParseResult::Head(head, _) => {
+            assert!(!head.keep_alive);
+            assert_eq!(
+                head.credentials,
+                Some((b"user".to_vec(), b"token:x".to_vec()))
+            );
+        }
+        other => panic!("bad result: {other:?}"),
+    }
+    assert_eq!(parse_head(b"GET / HTTP/1.1\r\n"), ParseResult::Incomplete);
+    assert_eq!(parse_head(b"GET / HTTP/2\r\n\r\n"), ParseResult::Error(505));
+    assert_eq!(
+        parse_head(b"POST /query HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n"),
+        ParseResult::Error(411)
+    );
+    assert_eq!(parse_head(&[b'a'; MAX_HEAD_SIZE]), ParseResult::Error(431));
+}
+
+#[test]
+fn test_route() {
+    assert_eq!(
+        route("GET", "/k/ks.tbl/hello%20world"),
+        Ok(Route::Key {
+            table: "ks.tbl".to_owned(),
+            action: b"GET",
+            key: b"hello world".to_vec(),
+        })
+    );
+    assert_eq!(route("POST", "/query?pretty"), Ok(Route::Query));
+    assert_eq!(route("GET", "/query"), Err(405));
+    assert_eq!(route("PATCH", "/k/ks.tbl/x"), Err(405));
+    assert_eq!(route("GET", "/k/ks.tbl"), Err(404));
+    assert_eq!(route("GET", "/k/ks.tbl/"), Err(404));
+    assert_eq!(route("GET", "/k/tbl/x"), Err(400));
+    assert_eq!(route("GET", "/k/ks.tbl;drop/x"), Err(400));
+    assert_eq!(route("GET", "/k/a.b.c/x"), Err(400));
+    assert_eq!(route("GET", "/k/ks.tbl/%zz"), Err(400));
+}
+
+#[test]
+fn test_respond() {
+    let status = |action, element| respond(action, Some(element)).status;
+    assert_eq!(status(None, Element::Code(b"0")), 204);
+    assert_eq!(status(None, Element::Code(b"1")), 404);
+    assert_eq!(status(None, Element::Code(b"10")), 401);
+    assert_eq!(status(None, Element::Code(b"err-out-of-memory")), 507);
+    assert_eq!(status(Some(b"DEL"), Element::Int(b"0")), 404);
+    assert_eq!(status(Some(b"DEL"), Element::Int(b"1")), 200);
+    assert_eq!(status(Some(b"USET"), Element::Int(b"1")), 204);
+    let response = respond(None, Some(Element::Code(b"err-throttled-retry-after-1500")));
+    assert_eq!(response.status, 429);
+    assert_eq!(response.headers, "Retry-After: 2\r\n");
+    let response = respond(None, Some(Element::Array(vec![Some(b"a\"b"), None])));
+    assert_eq!(response.body, b"[\"a\\\"b\",null]");
+    let response = respond(
+        None,
+        Some(Element::Map(vec![
+            (Element::Str(b"used"), Element::Int(b"42")),
+            (Element::Str(b"ratio"), Element::Float(b"NaN")),
+            (Element::Str(b"healthy"), Element::Bool(true)),
+        ])),
+    );
+    assert_eq!(
+        response.body,
+        b"{\"used\":42,\"ratio\":\"NaN\",\"healthy\":true}"
+    );
+    assert_eq!(respond(None, Some(Element::Bool(false))).body, b"false");
+}
@@ -0,0 +1,135 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Per-IP connection limits
+//!
+//! `maxcon` caps the connections of the server as a whole, which lets a single misbehaving
+//! client (like one that leaks connections) take the entire budget. An [`IpLimiter`] also caps
+//! the connections from each source IP: the acceptor takes an [`IpPermit`] for every connection
+//! it accepts (and refuses the connection if it can't), and the permit is given back once the
+//! connection is closed. There is one limiter for all the TCP listeners
+
+use {
+    super::{BufferedSocketStream, Connection},
+    crate::protocol::interface::ProtocolSpec,
+    parking_lot::Mutex,
+    std::{collections::HashMap, net::IpAddr, sync::Arc},
+};
+
+/// Tracks the number of open connections from every source IP
+pub struct IpLimiter {
+    /// the maximum number of connections from a single IP (zero means no limit)
+    max: usize,
+    connections: Mutex<HashMap<IpAddr, usize>>,
+}
+
+impl IpLimiter {
+    /// Create a new limiter that allows `max` connections from every IP. If `max` is zero,
+    /// there is no limit
+    pub fn new(max: usize) -> Arc<Self> {
+        Arc::new(Self {
+            max,
+            connections: Mutex::new(HashMap::new()),
+        })
+    }
+    /// Take a permit for a connection from `ip`, unless `ip` already has as many connections as
+    /// it is allowed
+    pub fn acquire(self: &Arc<Self>, ip: IpAddr) -> Option<IpPermit> {
+        if self.max == 0 {
+            return Some(IpPermit { limiter: None, ip });
+        }
+        let mut connections = self.connections.lock();
+        let count = connections.entry(ip).or_insert(0);
+        if *count == self.max {
+            return None;
+        }
+        *count += 1;
+        Some(IpPermit {
+            limiter: Some(self.clone()),
+            ip,
+        })
+    }
+}
+
+/// A permit for a connection, which is given back to the limiter when dropped
+pub struct IpPermit {
+    /// the limiter (unset if there is no limit)
+    limiter: Option<Arc<IpLimiter>>,
+    ip: IpAddr,
+}
+
+impl Drop for IpPermit {
+    fn drop(&mut self) {
+        if let Some(limiter) = &self.limiter {
+            let mut connections = limiter.connections.lock();
+            if let Some(count) = connections.get_mut(&self.ip) {
+                *count -= 1;
+                if *count == 0 {
+                    // don't keep the entries of every IP that ever connected
+                    connections.remove(&self.ip);
+                }
+            }
+        }
+    }
+}
+
+/// Refuse a connection because its IP has too many connections open. The error is sent as the
+/// response to the first query, so that the client sees it instead of a reset connection
+pub async fn refuse<C: BufferedSocketStream, P: ProtocolSpec>(stream: C) {
+    let mut con = Connection::<C, P>::new(stream);
+    if con.write_simple_query_header().await.is_ok() {
+        let _ = con.write_error(P::RSTRING_TOO_MANY_CONNECTIONS).await;
+    }
+}
+
+#[test]
+fn test_ip_limiter() {
+    let limiter = IpLimiter::new(2);
+    let count = |ip| limiter.connections.lock().get(&ip).copied().unwrap_or(0);
+    let (a, b): (IpAddr, IpAddr) = ("10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap());
+    let first = limiter.acquire(a).unwrap();
+    let second = limiter.acquire(a).unwrap();
+    assert!(limiter.acquire(a).is_none());
+    // other IPs have their own budget
+    let other = limiter.acquire(b).unwrap();
+    assert_eq!(count(a), 2);
+    drop(first);
+    assert_eq!(count(a), 1);
+    let third = limiter.acquire(a).unwrap();
+    drop((second, third, other));
+    assert_eq!(count(a), 0);
+    assert!(limiter.connections.lock().is_empty());
+}
+
+#[test]
+fn test_ip_limiter_unlimited() {
+    let limiter = IpLimiter::new(0);
+    let ip: IpAddr = "::1".parse().unwrap();
+    let count = |ip| limiter.connections.lock().get(&ip).copied().unwrap_or(0);
+    let permits: Vec<_> = (0..100).map(|_| limiter.acquire(ip).unwrap()).collect();
+    assert_eq!(permits.len(), 100);
+    assert_eq!(count(ip), 0);
+}
@@ -26,17 +26,22 @@
 
 use {
     super::{
+        iplimit::IpLimiter,
         tcp::{Listener, ListenerV1},
         tls::{SslListener, SslListenerV1},
     },
     crate::{
         auth::AuthProvider,
-        config::{PortConfig, ProtocolVersion, SslOpts},
+        config::{ListenConfig, PortConfig, ProtocolVersion, SslOpts, TrustedProxies},
         corestore::Corestore,
         util::error::{Error, SkyResult},
         IoResult,
     },
-    core::future::Future,
+    core::{
+        future::{poll_fn, Future},
+        pin::Pin,
+        task::Poll,
+    },
     std::{net::IpAddr, sync::Arc},
     tokio::{
         net::TcpListener,
@@ -54,6 +59,11 @@ pub struct BaseListener {
     pub listener: TcpListener,
     /// The maximum number of connections
     pub climit: Arc<Semaphore>,
+    /// The maximum number of connections from a single IP
+    pub iplimit: Arc<IpLimiter>,
+    /// The proxies whose connections start with a PROXY protocol header (unset if the PROXY
+    /// protocol is disabled)
+    pub proxy: Option<Arc<TrustedProxies>>,
     /// The shutdown broadcaster
     pub signal: broadcast::Sender<()>,
     // When all `Sender`s are dropped - the `Receiver` gets a `None` value
@@ -63,12 +73,15 @@ pub struct BaseListener {
 }
 
 impl BaseListener {
+    #[allow(clippy::too_many_arguments)]
     pub async fn init(
         db: &Corestore,
         auth: AuthProvider,
         host: IpAddr,
         port: u16,
         semaphore: Arc<Semaphore>,
+        iplimit: Arc<IpLimiter>,
+        proxy: Option<Arc<TrustedProxies>>,
         signal: broadcast::Sender<()>,
     ) -> SkyResult<Self> {
         let (terminate_tx, terminate_rx) = mpsc::channel(1);
@@ -80,6 +93,8 @@ impl BaseListener {
             auth,
             listener,
             climit: semaphore,
+            iplimit,
+            proxy,
             signal,
             terminate_tx,
             terminate_rx,
@@ -108,6 +123,8 @@ impl BaseListener {
 /// - The `Multi` variant holds both an `SslListener` and a `Listener`
 ///     This variant enables listening to both secure and insecure sockets at the same time
 ///     asynchronously
+/// - The `Many` variant holds the listeners for any number of additional addresses (along
+///   with the main listener)
 #[allow(clippy::large_enum_variant)]
 pub enum MultiListener {
     SecureOnly(SslListener),
@@ -116,6 +133,7 @@ pub enum MultiListener {
     InsecureOnlyV1(ListenerV1),
     Multi(Listener, SslListener),
     MultiV1(ListenerV1, SslListenerV1),
+    Many(Vec<MultiListener>),
 }
 
 async fn wait_on_port_futures(
@@ -132,6 +150,31 @@ async fn wait_on_port_futures(
     Ok(())
 }
 
+/// Run all the listeners until each one of them has returned
+async fn wait_on_all_ports(listeners: &mut [MultiListener]) -> IoResult<()> {
+    let mut futures: Vec<Pin<Box<dyn Future<Output = IoResult<()>> + '_>>> = listeners
+        .iter_mut()
+        .map(|listener| Box::pin(listener.run_server()) as _)
+        .collect();
+    poll_fn(|cx| {
+        futures.retain_mut(|future| match future.as_mut().poll(cx) {
+            Poll::Ready(ret) => {
+                if let Err(e) = ret {
+                    log::error!("Listener failed with: {}", e);
+                }
+                false
+            }
+            Poll::Pending => true,
+        });
+        if futures.is_empty() {
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    })
+    .await
+}
+
 impl MultiListener {
     /// Create a new `InsecureOnly` listener
     pub fn new_insecure_only(base: BaseListener, protocol: ProtocolVersion) -> Self {
@@ -153,6 +196,7 @@ impl MultiListener {
                     ssl.chain,
                     base,
                     ssl.passfile,
+                    ssl.clientca,
                 )?;
                 MultiListener::SecureOnly(listener)
             }
@@ -162,6 +206,7 @@ impl MultiListener {
                     ssl.chain,
                     base,
                     ssl.passfile,
+                    ssl.clientca,
                 )?;
                 MultiListener::SecureOnlyV1(listener)
             }
@@ -182,6 +227,7 @@ impl MultiListener {
                     ssl.chain,
                     ssl_base_listener,
                     ssl.passfile,
+                    ssl.clientca,
                 )?;
                 let insecure_listener = Listener::new(tcp_base_listener);
                 MultiListener::Multi(insecure_listener, secure_listener)
@@ -192,6 +238,7 @@ impl MultiListener {
                     ssl.chain,
                     ssl_base_listener,
                     ssl.passfile,
+                    ssl.clientca,
                 )?;
                 let insecure_listener = ListenerV1::new(tcp_base_listener);
                 MultiListener::MultiV1(insecure_listener, secure_listener)
@@ -215,6 +262,7 @@ impl MultiListener {
             MultiListener::MultiV1(insecure_listener, secure_listener) => {
                 wait_on_port_futures(insecure_listener.run(), secure_listener.run()).await
             }
+            MultiListener::Many(listeners) => wait_on_all_ports(listeners).await,
         }
     }
     /// Signal the ports to shut down and only return after they have shut down
@@ -235,20 +283,28 @@ impl MultiListener {
                 insecure.base.release_self().await;
                 secure.base.release_self().await;
             }
+            MultiListener::Many(listeners) => {
+                for listener in listeners {
+                    Box::pin(listener.finish_with_termsig()).await;
+                }
+            }
         }
     }
 }
 
 /// Initialize the database networking
+#[allow(clippy::too_many_arguments)]
 pub async fn connect(
     ports: PortConfig,
+    listen: ListenConfig,
     protocol: ProtocolVersion,
-    maxcon: usize,
+    climit: Arc<Semaphore>,
+    iplimit: Arc<IpLimiter>,
+    proxy: Option<Arc<TrustedProxies>>,
     db: Corestore,
     auth: AuthProvider,
     signal: broadcast::Sender<()>,
 ) -> SkyResult<MultiListener> {
-    let climit = Arc::new(Semaphore::new(maxcon));
     let base_listener_init = |host, port| {
         BaseListener::init(
             &db,
@@ -256,10 +312,17 @@ pub async fn connect(
             host,
             port,
             climit.clone(),
+            iplimit.clone(),
+            proxy.clone(),
             signal.clone(),
         )
     };
-    let description = ports.get_description();
+    let mut description = ports.get_description();
+    // the secure listeners on the additional addresses use the same TLS settings
+    let ssl = match &ports {
+        PortConfig::SecureOnly { ssl, .. } | PortConfig::Multi { ssl, .. } => Some(ssl.clone()),
+        PortConfig::InsecureOnly { .. } => None,
+    };
     let server = match ports {
         PortConfig::InsecureOnly { host, port } => {
             MultiListener::new_insecure_only(base_listener_init(host, port).await?, protocol)
@@ -275,6 +338,23 @@ pub async fn connect(
             MultiListener::new_multi(secure_listener, insecure_listener, ssl, protocol).await?
         }
     };
+    let server = if listen.addrs.is_empty() {
+        server
+    } else {
+        let mut listeners = vec![server];
+        for addr in listen.addrs {
+            let base = base_listener_init(addr.addr.ip(), addr.addr.port()).await?;
+            let listener = match (addr.secure, &ssl) {
+                (true, Some(ssl)) => MultiListener::new_secure_only(base, ssl.clone(), protocol)?,
+                // the configuration doesn't allow secure addresses without TLS
+                (true, None) => unreachable!("secure listener without TLS settings"),
+                (false, _) => MultiListener::new_insecure_only(base, protocol),
+            };
+            description.push_str(&format!(" and {addr}"));
+            listeners.push(listener);
+        }
+        MultiListener::Many(listeners)
+    };
     log::info!("Server started on {description}");
     Ok(server)
 }
@@ -25,7 +25,7 @@
 */
 
 use {
-    self::connection::Connection,
+    self::{clients::Client, connection::Connection},
     crate::{
         actions::{ActionError, ActionResult},
         auth::AuthProvider,
@@ -51,15 +51,29 @@ pub type QueryWithAdvance = (Query, usize);
 pub const MAXIMUM_CONNECTION_LIMIT: usize = 50000;
 use crate::queryengine;
 
+pub use self::connection::{set_buffer_size, DEFAULT_BUFFER_SIZE};
+pub use self::iplimit::IpLimiter;
 pub use self::listener::connect;
 
+pub mod clients;
+pub mod compress;
 mod connection;
+pub mod cursor;
 #[macro_use]
 mod macros;
+pub mod http;
+mod iplimit;
 mod listener;
 pub mod prelude;
+mod proxy;
+pub mod pubsub;
+pub mod resp;
+pub mod session;
 mod tcp;
-mod tls;
+pub mod tls;
+#[cfg(unix)]
+pub mod unix;
+pub mod websocket;
 
 /// This is a "marker trait" that ensures that no silly types are
 /// passed into the [`Connection`] type
@@ -73,6 +87,8 @@ enum QueryResult {
     NextLoop,
     /// The client disconnected
     Disconnected,
+    /// A pub/sub message to write out
+    Message(pubsub::Message),
 }
 
 /// A backoff implementation that is meant to be used in connection loops
@@ -107,7 +123,9 @@ pub struct AuthProviderHandle {
 
 impl AuthProviderHandle {
     pub fn new(provider: AuthProvider) -> Self {
-        let auth_good = !provider.is_enabled();
+        // a provider may already have a user if the connection was authenticated using
+        // a client certificate
+        let auth_good = !provider.is_enabled() || provider.current_user().is_some();
         Self {
             provider,
             auth_good,
@@ -147,6 +165,8 @@ pub struct ConnectionHandler<C, P> {
     auth: AuthProviderHandle,
     /// check for termination signals
     termination_signal: broadcast::Receiver<()>,
+    /// the client that this connection is registered as (see [`clients`])
+    client: Arc<Client>,
     /// the sender that we drop when we're done with handling a connection (used for gracefule exit)
     _term_sig_tx: mpsc::Sender<()>,
 }
@@ -158,19 +178,23 @@ where
 {
     /// Create a new connection handler
     pub fn new(
-        db: Corestore,
-        con: Connection<C, P>,
+        mut db: Corestore,
+        mut con: Connection<C, P>,
+        peer: String,
         auth_data: AuthProvider,
         climit: Arc<Semaphore>,
         termination_signal: broadcast::Receiver<()>,
         _term_sig_tx: mpsc::Sender<()>,
     ) -> Self {
+        db.use_default_entity();
+        let client = con.register_client(peer);
         Self {
             db,
             con,
             climit,
             auth: AuthProviderHandle::new(auth_data),
             termination_signal,
+            client,
             _term_sig_tx,
         }
     }
@@ -181,6 +205,10 @@ where
                 _ = self.termination_signal.recv() => {
                     return Ok(());
                 }
+                _ = self.client.killed() => {
+                    log::info!("Client {} was killed", self.client.id());
+                    return Ok(());
+                }
             };
             match packet {
                 Ok(QueryResult::Q((query, advance))) => {
@@ -194,11 +222,17 @@ where
                     let eptr_at_start = sptr_at_start + len_at_start;
                     {
                         // The actual execution (the assertions are just debug build sanity checks)
+                        let written = self.con.bytes_written();
                         match self.execute_query(query).await {
                             Ok(()) => {}
                             Err(ActionError::ActionError(e)) => self.con.write_error(e).await?,
                             Err(ActionError::IoError(e)) => return Err(e),
                         }
+                        // the traffic is billed to the keyspace in use after the query
+                        if let Ok(ks) = self.db.get_cks() {
+                            ks.usage()
+                                .record_network(advance as u64, self.con.bytes_written() - written);
+                        }
                     }
                     {
                         // do these assertions to ensure memory safety (this is just for sanity sake)
@@ -225,6 +259,7 @@ where
                 }
                 Ok(QueryResult::Disconnected) => return Ok(()),
                 Ok(QueryResult::NextLoop) => {}
+                Ok(QueryResult::Message(message)) => self.con.write_message(&message).await?,
                 Err(e) => return Err(e),
             }
         }
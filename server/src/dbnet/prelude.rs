@@ -33,7 +33,7 @@ pub use {
     crate::{
         actions::{ensure_boolean_or_aerr, ensure_length, translate_ddl_error},
         corestore::{
-            table::{KVEBlob, KVEList},
+            table::{KVEBlob, KVEDocumentT, KVEList, KVEMapT, KVESetT, KVESortedSetT},
             Corestore,
         },
         get_tbl, handle_entity, is_lowbit_set,
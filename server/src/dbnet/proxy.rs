@@ -0,0 +1,260 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # PROXY protocol
+//!
+//! Load balancers like HAProxy (or AWS' NLB) connect to `skyd` themselves, so the peer address
+//! of the connection is the load balancer's. If the PROXY protocol is enabled, the load balancer
+//! starts every connection with a header holding the address of the actual client, which we
+//! read before anything else (and before the TLS handshake). Only the connections from the
+//! trusted proxies are expected to have a header; anyone else could use it to pose as another
+//! client. Both the text (v1) and the binary (v2) headers are supported.
+//! See <https://www.haproxy.org/download/2.8/doc/proxy-protocol.txt> for the format
+
+use {
+    crate::{config::TrustedProxies, IoResult},
+    core::time::Duration,
+    std::{
+        io::{Error as IoError, ErrorKind},
+        net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    },
+    tokio::{
+        io::{AsyncRead, AsyncReadExt},
+        time,
+    },
+};
+
+/// How long we wait for the header before dropping the connection
+pub const HEADER_TIMEOUT: Duration = Duration::from_secs(5);
+/// The start of a v1 header
+const V1_PREFIX: &[u8] = b"PROXY ";
+/// The longest v1 header (including the CRLF)
+const V1_MAX_LEN: usize = 107;
+/// The start of a v2 header
+const V2_SIGNATURE: &[u8; 12] = b"\r\n\r\n\0\r\nQUIT\n";
+const V2_CMD_LOCAL: u8 = 0x0;
+const V2_CMD_PROXY: u8 = 0x1;
+const V2_FAMILY_INET: u8 = 0x1;
+const V2_FAMILY_INET6: u8 = 0x2;
+
+/// Returns the address of the client on the other end of `stream` (which was accepted from
+/// `addr`). That's `addr` unless the PROXY protocol is enabled (`trusted` is set) and `addr` is
+/// a trusted proxy, in which case it's read from the header
+pub async fn client_addr<S: AsyncRead + Unpin>(
+    trusted: Option<&TrustedProxies>,
+    stream: &mut S,
+    addr: SocketAddr,
+) -> IoResult<SocketAddr> {
+    match trusted {
+        Some(trusted) if trusted.contains(addr.ip()) => {
+            match time::timeout(HEADER_TIMEOUT, self::read_header(stream)).await {
+                Ok(client) => client.map(|client| client.unwrap_or(addr)),
+                Err(_) => Err(IoError::from(ErrorKind::TimedOut)),
+            }
+        }
+        _ => Ok(addr),
+    }
+}
+
+fn bad_header() -> IoError {
+    IoError::new(ErrorKind::InvalidData, "bad PROXY protocol header")
+}
+
+/// Read a PROXY protocol header from the stream, without reading anything that comes after it.
+/// Returns the address of the client, or `None` if the header doesn't have one (like for the
+/// health checks of the load balancer itself)
+pub async fn read_header<S: AsyncRead + Unpin>(stream: &mut S) -> IoResult<Option<SocketAddr>> {
+    // the first six bytes are enough to tell the versions apart
+    let mut start = [0u8; 6];
+    stream.read_exact(&mut start).await?;
+    if start == V1_PREFIX {
+        read_v1(stream).await
+    } else if start == V2_SIGNATURE[..6] {
+        read_v2(stream).await
+    } else {
+        Err(bad_header())
+    }
+}
+
+/// Read a v1 header like `PROXY TCP4 <src> <dst> <src port> <dst port>\r\n` (the prefix has
+/// already been read)
+async fn read_v1<S: AsyncRead + Unpin>(stream: &mut S) -> IoResult<Option<SocketAddr>> {
+    // the header is tiny and we can't read past it, so we read it a byte at a time
+    let mut line = Vec::with_capacity(V1_MAX_LEN);
+    while !line.ends_with(b"\r\n") {
+        if line.len() + V1_PREFIX.len() == V1_MAX_LEN {
+            return Err(bad_header());
+        }
+        line.push(stream.read_u8().await?);
+    }
+    line.truncate(line.len() - 2);
+    parse_v1(&line).ok_or_else(bad_header)
+}
+
+fn parse_v1(line: &[u8]) -> Option<Option<SocketAddr>> {
+    let line = core::str::from_utf8(line).ok()?;
+    let mut parts = line.split(' ');
+    match parts.next()? {
+        "TCP4" | "TCP6" => {}
+        // the rest of the line is to be ignored
+        "UNKNOWN" => return Some(None),
+        _ => return None,
+    }
+    let src: IpAddr = parts.next()?.parse().ok()?;
+    let _dst: IpAddr = parts.next()?.parse().ok()?;
+    let src_port: u16 = parts.next()?.parse().ok()?;
+    let _dst_port: u16 = parts.next()?.parse().ok()?;
+    if parts.next().is_some() {
+        return None;
+    }
+    Some(Some(SocketAddr::new(src, src_port)))
+}
+
+/// Read a v2 header (the first six bytes of the signature have already been read)
+async fn read_v2<S: AsyncRead + Unpin>(stream: &mut S) -> IoResult<Option<SocketAddr>> {
+    // the rest of the signature, the version and command, the family and the length
+    let mut head = [0u8; 10];
+    stream.read_exact(&mut head).await?;
+    if head[..6] != V2_SIGNATURE[6..] || head[6] >> 4 != 2 {
+        return Err(bad_header());
+    }
+    let command = head[6] & 0xF;
+    let family = head[7] >> 4;
+    let len = u16::from_be_bytes([head[8], head[9]]) as usize;
+    let mut addresses = vec![0u8; len];
+    stream.read_exact(&mut addresses).await?;
+    match command {
+        // sent by the load balancer itself, so there is no client
+        V2_CMD_LOCAL => Ok(None),
+        V2_CMD_PROXY => parse_v2_addresses(family, &addresses).ok_or_else(bad_header),
+        _ => Err(bad_header()),
+    }
+}
+
+fn parse_v2_addresses(family: u8, addresses: &[u8]) -> Option<Option<SocketAddr>> {
+    let src = match family {
+        V2_FAMILY_INET if addresses.len() >= 12 => {
+            let ip: [u8; 4] = addresses[..4].try_into().ok()?;
+            let port = u16::from_be_bytes([addresses[8], addresses[9]]);
+            SocketAddr::new(IpAddr::V4(Ipv4Addr::from(ip)), port)
+        }
+        V2_FAMILY_INET6 if addresses.len() >= 36 => {
+            let ip: [u8; 16] = addresses[..16].try_into().ok()?;
+            let port = u16::from_be_bytes([addresses[32], addresses[33]]);
+            SocketAddr::new(IpAddr::V6(Ipv6Addr::from(ip)), port)
+        }
+        V2_FAMILY_INET | V2_FAMILY_INET6 => return None,
+        // unspecified or Unix sockets; there is no IP to use
+        _ => return Some(None),
+    };
+    Some(Some(src))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn read(mut input: &[u8]) -> (IoResult<Option<SocketAddr>>, &[u8]) {
+        let ret = read_header(&mut input).await;
+        (ret, input)
+    }
+
+    #[tokio::test]
+    async fn test_v1() {
+        let (ret, rest) = read(b"PROXY TCP4 10.1.2.3 10.0.0.1 56324 2003\r\n*1\n4\nheya").await;
+        assert_eq!(ret.unwrap(), Some("10.1.2.3:56324".parse().unwrap()));
+        // the query is left untouched
+        assert_eq!(rest, b"*1\n4\nheya");
+        let (ret, _) = read(b"PROXY TCP6 ::1 ::2 56324 2003\r\n").await;
+        assert_eq!(ret.unwrap(), Some("[::1]:56324".parse().unwrap()));
+        let (ret, rest) = read(b"PROXY UNKNOWN whatever\r\nabc").await;
+        assert_eq!(ret.unwrap(), None);
+        assert_eq!(rest, b"abc");
+        for bad in [
+            &b"PROXY TCP4 10.1.2.3 10.0.0.1 56324\r\n"[..],
+            b"PROXY UDP4 10.1.2.3 10.0.0.1 56324 2003\r\n",
+            b"*1\n4\nheya",
+        ] {
+            assert_eq!(
+                read(bad).await.0.unwrap_err().kind(),
+                ErrorKind::InvalidData
+            );
+        }
+        // no CRLF in sight
+        let long = [&b"PROXY "[..], &[b'x'; 200]].concat();
+        assert_eq!(
+            read(&long).await.0.unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[tokio::test]
+    async fn test_v2() {
+        let mut header = V2_SIGNATURE.to_vec();
+        // PROXY, over TCP/IPv4
+        header.extend([0x21, 0x11, 0, 12]);
+        header.extend([10, 1, 2, 3, 10, 0, 0, 1]);
+        header.extend(56324u16.to_be_bytes());
+        header.extend(2003u16.to_be_bytes());
+        header.extend(b"*1\n4\nheya");
+        let (ret, rest) = read(&header).await;
+        assert_eq!(ret.unwrap(), Some("10.1.2.3:56324".parse().unwrap()));
+        assert_eq!(rest, b"*1\n4\nheya");
+        // LOCAL, with some TLVs that we skip
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend([0x20, 0x00, 0, 3, 1, 2, 3]);
+        header.extend(b"abc");
+        let (ret, rest) = read(&header).await;
+        assert_eq!(ret.unwrap(), None);
+        assert_eq!(rest, b"abc");
+        // version 1 isn't a thing in the binary format
+        let mut header = V2_SIGNATURE.to_vec();
+        header.extend([0x11, 0x11, 0, 0]);
+        assert_eq!(
+            read(&header).await.0.unwrap_err().kind(),
+            ErrorKind::InvalidData
+        );
+    }
+
+    #[tokio::test]
+    async fn test_client_addr_trusted_only() {
+        let trusted: TrustedProxies = "10.0.0.0/8".parse().unwrap();
+        let input = b"PROXY TCP4 192.168.1.2 10.0.0.1 56324 2003\r\n*1\n4\nheya";
+        let proxy: SocketAddr = "10.0.0.1:40000".parse().unwrap();
+        let stranger: SocketAddr = "192.168.1.3:40000".parse().unwrap();
+        let mut stream = &input[..];
+        let addr = client_addr(Some(&trusted), &mut stream, proxy).await;
+        assert_eq!(addr.unwrap(), "192.168.1.2:56324".parse().unwrap());
+        assert_eq!(stream, b"*1\n4\nheya");
+        // anyone else's header is left alone (and will fail as a query)
+        for trusted in [Some(&trusted), None] {
+            let mut stream = &input[..];
+            let addr = client_addr(trusted, &mut stream, stranger).await;
+            assert_eq!(addr.unwrap(), stranger);
+            assert_eq!(stream, &input[..]);
+        }
+    }
+}
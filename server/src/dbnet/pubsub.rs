@@ -0,0 +1,273 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Pub/sub
+//!
+//! Connections subscribe to channels (or to glob patterns of channel names, see
+//! [`util::glob_match`]) and every message that is published to a channel is fanned out to the
+//! connections that subscribed to it. Every connection has a bounded queue of messages that is
+//! drained between queries; each message is written as an out-of-band response. If the queue of
+//! a connection is full (it isn't reading its messages fast enough), the messages that don't fit
+//! are dropped for that connection
+
+use {
+    crate::{
+        corestore::{lazy::Lazy, SharedSlice},
+        util,
+    },
+    parking_lot::RwLock,
+    std::{
+        collections::{HashMap, HashSet},
+        sync::atomic::{AtomicU64, Ordering},
+    },
+    tokio::sync::mpsc::{self, error::TrySendError},
+};
+
+/// The number of messages that can be queued for a connection
+pub const QUEUE_SIZE: usize = 1024;
+
+/// The subscribers of a channel or a pattern, by their ID
+type Subscribers = HashMap<u64, mpsc::Sender<Message>>;
+/// The subscribers of every channel (or every pattern)
+type SubscriberMap = RwLock<HashMap<SharedSlice, Subscribers>>;
+
+/// The channel registry
+static REGISTRY: Lazy<ChannelRegistry, fn() -> ChannelRegistry> = Lazy::new(ChannelRegistry::new);
+/// The ID of the next subscriber
+static NEXT_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Debug, Clone, PartialEq)]
+/// A message published to a channel
+pub struct Message {
+    /// the pattern that the channel matched, if the message was delivered through a pattern
+    /// subscription
+    pub pattern: Option<SharedSlice>,
+    /// the channel that the message was published to
+    pub channel: SharedSlice,
+    /// the message itself
+    pub payload: SharedSlice,
+}
+
+/// The subscribers of every channel and every pattern
+struct ChannelRegistry {
+    channels: SubscriberMap,
+    patterns: SubscriberMap,
+}
+
+impl ChannelRegistry {
+    fn new() -> Self {
+        Self {
+            channels: RwLock::new(HashMap::new()),
+            patterns: RwLock::new(HashMap::new()),
+        }
+    }
+}
+
+/// Add `id` to the subscribers of `name`
+fn add(map: &SubscriberMap, name: &SharedSlice, id: u64, tx: &mpsc::Sender<Message>) {
+    map.write()
+        .entry(name.clone())
+        .or_default()
+        .insert(id, tx.clone());
+}
+
+/// Remove `id` from the subscribers of `name`, forgetting `name` if that was its last subscriber
+fn remove(map: &SubscriberMap, name: &[u8], id: u64) {
+    let mut map = map.write();
+    if let Some(subscribers) = map.get_mut(name) {
+        subscribers.remove(&id);
+        if subscribers.is_empty() {
+            map.remove(name);
+        }
+    }
+}
+
+/// Queue a message for a subscriber, returning true if it was queued
+fn deliver(tx: &mpsc::Sender<Message>, message: Message) -> bool {
+    match tx.try_send(message) {
+        Ok(()) => true,
+        Err(TrySendError::Full(_)) => {
+            log::warn!("Dropped a pub/sub message for a subscriber that isn't keeping up");
+            false
+        }
+        // the connection is going away
+        Err(TrySendError::Closed(_)) => false,
+    }
+}
+
+/// Publish `payload` to `channel`, returning the number of subscriptions (channel and pattern)
+/// that it was delivered to
+pub fn publish(channel: &[u8], payload: &[u8]) -> usize {
+    let (channel, payload) = (SharedSlice::from(channel), SharedSlice::from(payload));
+    let mut delivered = 0;
+    if let Some(subscribers) = REGISTRY.channels.read().get(channel.as_ref()) {
+        for tx in subscribers.values() {
+            let message = Message {
+                pattern: None,
+                channel: channel.clone(),
+                payload: payload.clone(),
+            };
+            delivered += self::deliver(tx, message) as usize;
+        }
+    }
+    for (pattern, subscribers) in REGISTRY.patterns.read().iter() {
+        if !util::glob_match(pattern, &channel) {
+            continue;
+        }
+        for tx in subscribers.values() {
+            let message = Message {
+                pattern: Some(pattern.clone()),
+                channel: channel.clone(),
+                payload: payload.clone(),
+            };
+            delivered += self::deliver(tx, message) as usize;
+        }
+    }
+    delivered
+}
+
+/// The subscriptions of a connection. The subscriptions are removed from the registry when
+/// this is dropped
+pub struct Subscriber {
+    id: u64,
+    tx: mpsc::Sender<Message>,
+    channels: HashSet<SharedSlice>,
+    patterns: HashSet<SharedSlice>,
+}
+
+impl Subscriber {
+    /// Returns a new subscriber (without any subscriptions) along with the receiving end of its
+    /// queue
+    pub fn new() -> (Self, mpsc::Receiver<Message>) {
+        let (tx, rx) = mpsc::channel(QUEUE_SIZE);
+        let subscriber = Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            tx,
+            channels: HashSet::new(),
+            patterns: HashSet::new(),
+        };
+        (subscriber, rx)
+    }
+    /// Returns the number of channels and patterns subscribed to
+    pub fn count(&self) -> usize {
+        self.channels.len() + self.patterns.len()
+    }
+    /// Subscribe to `channel`
+    pub fn subscribe(&mut self, channel: &[u8]) {
+        let channel = SharedSlice::from(channel);
+        if !self.channels.contains(&channel) {
+            self::add(&REGISTRY.channels, &channel, self.id, &self.tx);
+            self.channels.insert(channel);
+        }
+    }
+    /// Subscribe to the channels that match `pattern`
+    pub fn psubscribe(&mut self, pattern: &[u8]) {
+        let pattern = SharedSlice::from(pattern);
+        if !self.patterns.contains(&pattern) {
+            self::add(&REGISTRY.patterns, &pattern, self.id, &self.tx);
+            self.patterns.insert(pattern);
+        }
+    }
+    /// Unsubscribe from `channel`
+    pub fn unsubscribe(&mut self, channel: &[u8]) {
+        if self.channels.remove(channel) {
+            self::remove(&REGISTRY.channels, channel, self.id);
+        }
+    }
+    /// Unsubscribe from `pattern`
+    pub fn punsubscribe(&mut self, pattern: &[u8]) {
+        if self.patterns.remove(pattern) {
+            self::remove(&REGISTRY.patterns, pattern, self.id);
+        }
+    }
+    /// Unsubscribe from every channel
+    pub fn unsubscribe_all(&mut self) {
+        for channel in self.channels.drain() {
+            self::remove(&REGISTRY.channels, &channel, self.id);
+        }
+    }
+    /// Unsubscribe from every pattern
+    pub fn punsubscribe_all(&mut self) {
+        for pattern in self.patterns.drain() {
+            self::remove(&REGISTRY.patterns, &pattern, self.id);
+        }
+    }
+}
+
+impl Drop for Subscriber {
+    fn drop(&mut self) {
+        self.unsubscribe_all();
+        self.punsubscribe_all();
+    }
+}
+
+#[test]
+fn test_pubsub() {
+    let (mut first, mut first_rx) = Subscriber::new();
+    let (mut second, mut second_rx) = Subscriber::new();
+    first.subscribe(b"pubsub_news");
+    first.subscribe(b"pubsub_news");
+    second.psubscribe(b"pubsub_*");
+    assert_eq!((first.count(), second.count()), (1, 1));
+    assert_eq!(publish(b"pubsub_news", b"hello"), 2);
+    assert_eq!(
+        first_rx.try_recv().unwrap(),
+        Message {
+            pattern: None,
+            channel: "pubsub_news".into(),
+            payload: "hello".into(),
+        }
+    );
+    assert_eq!(
+        second_rx.try_recv().unwrap(),
+        Message {
+            pattern: Some("pubsub_*".into()),
+            channel: "pubsub_news".into(),
+            payload: "hello".into(),
+        }
+    );
+    assert_eq!(publish(b"pubsub_sports", b"goal"), 1);
+    assert!(first_rx.try_recv().is_err());
+    assert_eq!(second_rx.try_recv().unwrap().channel, "pubsub_sports");
+    first.unsubscribe(b"pubsub_news");
+    assert_eq!(publish(b"pubsub_news", b"hello"), 1);
+    // dropping the subscriber removes its subscriptions
+    drop(second);
+    assert_eq!(publish(b"pubsub_news", b"hello"), 0);
+}
+
+#[test]
+fn test_pubsub_full_queue() {
+    let (mut subscriber, mut rx) = Subscriber::new();
+    subscriber.subscribe(b"pubsubfull");
+    for _ in 0..QUEUE_SIZE {
+        assert_eq!(publish(b"pubsubfull", b"hello"), 1);
+    }
+    // messages that don't fit are dropped
+    assert_eq!(publish(b"pubsubfull", b"hello"), 0);
+    assert!(rx.try_recv().is_ok());
+    assert_eq!(publish(b"pubsubfull", b"hello"), 1);
+}
@@ -0,0 +1,316 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Redis commands
+//!
+//! This module maps the Redis commands that we support onto the actions that implement them.
+//! A few commands (like `PING`) are answered without running any action, and the others are
+//! mapped onto an action depending on their options (like `SET key value EX 10 NX`, which is a
+//! `SETX key value EX 10 NX`)
+
+use super::reply::{self, Reply};
+
+/// The commands that we support
+const COMMANDS: [&[u8]; 42] = [
+    b"PING",
+    b"ECHO",
+    b"SELECT",
+    b"QUIT",
+    b"COMMAND",
+    b"CLIENT",
+    b"AUTH",
+    b"USE",
+    b"GET",
+    b"GETDEL",
+    b"GETSET",
+    b"SET",
+    b"SETNX",
+    b"MGET",
+    b"MSET",
+    b"DEL",
+    b"EXISTS",
+    b"KEYS",
+    b"RANDOMKEY",
+    b"EXPIRE",
+    b"PEXPIRE",
+    b"TTL",
+    b"PTTL",
+    b"PERSIST",
+    b"INCR",
+    b"DECR",
+    b"INCRBY",
+    b"DECRBY",
+    b"STRLEN",
+    b"HSET",
+    b"HGET",
+    b"HDEL",
+    b"HGETALL",
+    b"SADD",
+    b"SREM",
+    b"SMEMBERS",
+    b"SISMEMBER",
+    b"PFADD",
+    b"PFCOUNT",
+    b"PFMERGE",
+    b"DBSIZE",
+    b"FLUSHDB",
+];
+
+/// A stage, that is, an action along with its arguments
+pub type Stage = Vec<Vec<u8>>;
+
+#[derive(Debug, PartialEq)]
+/// What to do for a command
+pub enum Command {
+    /// Reply with this, without running any action
+    Local(Vec<u8>),
+    /// Reply with `+OK` and close the connection
+    Quit,
+    /// Run these stages one after the other (for as long as they succeed) and reply with the
+    /// response to the last one that ran
+    Run(Vec<Stage>, Reply),
+}
+
+/// Map a command (the name is the first argument) onto what has to be done for it
+pub fn translate(mut args: Vec<Vec<u8>>) -> Command {
+    if args.is_empty() {
+        // Redis ignores empty commands
+        return Command::Local(Vec::new());
+    }
+    let name = args.remove(0).to_ascii_uppercase();
+    let argc = args.len();
+    let run = |action: &[u8], args: Vec<Vec<u8>>, reply| {
+        Command::Run(vec![self::stage(action, args)], reply)
+    };
+    match (name.as_slice(), argc) {
+        (b"PING", 0) => self::local(|out| reply::simple(out, b"PONG")),
+        (b"PING" | b"ECHO", 1) => self::local(|out| reply::bulk(out, &args[0])),
+        (b"SELECT", 1) if args[0] == b"0" => self::ok(),
+        (b"SELECT", 1) => self::error("ERR DB index is out of range"),
+        (b"QUIT", 0) => Command::Quit,
+        (b"COMMAND", _) => self::local(|out| reply::array_header(out, 0)),
+        (b"CLIENT", 1..) => self::ok(),
+        (b"AUTH", 1) => run(b"AUTH", self::args([b"LOGIN", b"root"], args), Reply::Ok),
+        (b"AUTH", 2) => run(b"AUTH", self::args([b"LOGIN"], args), Reply::Ok),
+        (b"USE", 1) => {
+            let mut query = b"use ".to_vec();
+            query.extend_from_slice(&args[0]);
+            Command::Run(vec![vec![query]], Reply::Ok)
+        }
+        (b"GET", 1) => run(b"GET", args, Reply::Native),
+        (b"GETDEL", 1) => run(b"GETDEL", args, Reply::Native),
+        (b"GETSET", 2) => run(b"GETSET", args, Reply::Native),
+        (b"SET", 2..) => self::set(args),
+        (b"SETNX", 2) => run(b"SET", args, Reply::Flag),
+        (b"MGET", 1..) => run(b"MGET", args, Reply::Native),
+        (b"MSET", _) if argc != 0 && argc.is_multiple_of(2) => run(b"USET", args, Reply::Ok),
+        (b"DEL", 1..) => run(b"DEL", args, Reply::Native),
+        (b"EXISTS", 1..) => run(b"EXISTS", args, Reply::Native),
+        (b"KEYS", 1) => run(b"KEYS", args, Reply::Native),
+        (b"RANDOMKEY", 0) => run(b"RANDOMKEY", args, Reply::Native),
+        (b"EXPIRE", 2) => run(b"EXPIRE", args, Reply::Flag),
+        (b"PEXPIRE", 2) => run(b"PEXPIRE", args, Reply::Flag),
+        (b"TTL", 1) => run(b"TTL", args, Reply::Ttl),
+        (b"PTTL", 1) => run(b"PTTL", args, Reply::Ttl),
+        (b"PERSIST", 1) => run(b"PERSIST", args, Reply::Flag),
+        (b"INCR", 1) => {
+            args.push(b"1".to_vec());
+            run(b"INCRBY", args, Reply::Integer)
+        }
+        (b"DECR", 1) => {
+            args.push(b"1".to_vec());
+            run(b"DECRBY", args, Reply::Integer)
+        }
+        (b"INCRBY", 2) => run(b"INCRBY", args, Reply::Integer),
+        (b"DECRBY", 2) => run(b"DECRBY", args, Reply::Integer),
+        (b"STRLEN", 1) => run(b"KEYLEN", args, Reply::Count),
+        (b"HSET", _) if argc >= 3 && argc % 2 == 1 => run(b"HSET", args, Reply::Native),
+        (b"HGET", 2) => run(b"HGET", args, Reply::Native),
+        (b"HDEL", 2..) => run(b"HDEL", args, Reply::Count),
+        (b"HGETALL", 1) => run(b"HGETALL", args, Reply::List),
+        (b"SADD", 2..) => run(b"SADD", args, Reply::Native),
+        (b"SREM", 2..) => run(b"SREM", args, Reply::Count),
+        (b"SMEMBERS", 1) => run(b"SMEMBERS", args, Reply::List),
+        (b"SISMEMBER", 2) => run(b"SISMEMBER", args, Reply::Native),
+        (b"PFADD", 1..) => run(b"PFADD", args, Reply::Native),
+        (b"PFCOUNT", 1..) => run(b"PFCOUNT", args, Reply::Native),
+        (b"PFMERGE", 1..) => run(b"PFMERGE", args, Reply::Ok),
+        (b"DBSIZE", 0) => run(b"DBSIZE", args, Reply::Native),
+        (b"FLUSHDB", 0) => run(b"FLUSHDB", args, Reply::Ok),
+        (name, _) if COMMANDS.contains(&name) => self::error(&format!(
+            "ERR wrong number of arguments for '{}' command",
+            String::from_utf8_lossy(name).to_lowercase()
+        )),
+        _ => self::error(&format!(
+            "ERR unknown command '{}'",
+            String::from_utf8_lossy(&name)
+        )),
+    }
+}
+
+/// Map `SET key value [NX|XX] [EX seconds|PX milliseconds]`
+fn set(mut args: Vec<Vec<u8>>) -> Command {
+    let options: Vec<Vec<u8>> = args.drain(2..).collect();
+    let mut condition: Option<&[u8]> = None;
+    let mut expiry: Option<(&[u8], u64)> = None;
+    let mut options = options.into_iter();
+    while let Some(option) = options.next() {
+        match option.to_ascii_uppercase().as_slice() {
+            b"NX" if condition.is_none() => condition = Some(b"NX"),
+            b"XX" if condition.is_none() => condition = Some(b"XX"),
+            unit @ (b"EX" | b"PX") if expiry.is_none() => {
+                let ttl = options
+                    .next()
+                    .and_then(|ttl| String::from_utf8(ttl).ok())
+                    .and_then(|ttl| ttl.parse::<u64>().ok())
+                    .filter(|ttl| *ttl != 0);
+                let ttl = match ttl {
+                    Some(ttl) => ttl,
+                    None => return self::error("ERR invalid expire time in 'set' command"),
+                };
+                let unit: &[u8] = if unit == b"EX" { b"EX" } else { b"PX" };
+                expiry = Some((unit, ttl));
+            }
+            _ => return self::error("ERR syntax error"),
+        }
+    }
+    let stage = match (expiry, condition) {
+        // the value and its time to live are written atomically
+        (Some((unit, ttl)), condition) => {
+            args.push(unit.to_vec());
+            args.push(ttl.to_string().into_bytes());
+            args.extend(condition.map(|condition| condition.to_vec()));
+            self::stage(b"SETX", args)
+        }
+        (None, None) => self::stage(b"USET", args),
+        (None, Some(b"NX")) => self::stage(b"SET", args),
+        (None, Some(_)) => self::stage(b"UPDATE", args),
+    };
+    Command::Run(vec![stage], Reply::Ok)
+}
+
+fn stage(action: &[u8], args: Vec<Vec<u8>>) -> Stage {
+    let mut stage = Vec::with_capacity(args.len() + 1);
+    stage.push(action.to_vec());
+    stage.extend(args);
+    stage
+}
+
+/// Returns `args` with `prefix` in front of them
+fn args<const N: usize>(prefix: [&[u8]; N], args: Vec<Vec<u8>>) -> Vec<Vec<u8>> {
+    prefix.iter().map(|arg| arg.to_vec()).chain(args).collect()
+}
+
+fn local(f: impl FnOnce(&mut Vec<u8>)) -> Command {
+    let mut out = Vec::new();
+    f(&mut out);
+    Command::Local(out)
+}
+
+fn ok() -> Command {
+    self::local(|out| reply::simple(out, b"OK"))
+}
+
+fn error(message: &str) -> Command {
+    self::local(|out| reply::error(out, message))
+}
+
+#[cfg(test)]
+fn command(args: &[&str]) -> Command {
+    self::translate(args.iter().map(|arg| arg.as_bytes().to_vec()).collect())
+}
+
+#[cfg(test)]
+fn stages(stages: &[&[&str]]) -> Vec<Stage> {
+    stages
+        .iter()
+        .map(|stage| stage.iter().map(|arg| arg.as_bytes().to_vec()).collect())
+        .collect()
+}
+
+#[test]
+fn test_translate() {
+    assert_eq!(command(&["ping"]), Command::Local(b"+PONG\r\n".to_vec()));
+    assert_eq!(
+        command(&["get", "x"]),
+        Command::Run(stages(&[&["GET", "x"]]), Reply::Native)
+    );
+    assert_eq!(
+        command(&["AUTH", "sayan"]),
+        Command::Run(stages(&[&["AUTH", "LOGIN", "root", "sayan"]]), Reply::Ok)
+    );
+    assert_eq!(
+        command(&["pttl", "x"]),
+        Command::Run(stages(&[&["PTTL", "x"]]), Reply::Ttl)
+    );
+    assert_eq!(
+        command(&["INCR", "x"]),
+        Command::Run(stages(&[&["INCRBY", "x", "1"]]), Reply::Integer)
+    );
+    assert_eq!(
+        command(&["pfmerge", "all", "a", "b"]),
+        Command::Run(stages(&[&["PFMERGE", "all", "a", "b"]]), Reply::Ok)
+    );
+    assert_eq!(
+        command(&["hset", "m", "f"]),
+        Command::Local(b"-ERR wrong number of arguments for 'hset' command\r\n".to_vec())
+    );
+    assert_eq!(
+        command(&["FOO"]),
+        Command::Local(b"-ERR unknown command 'FOO'\r\n".to_vec())
+    );
+}
+
+#[test]
+fn test_translate_set() {
+    assert_eq!(
+        command(&["SET", "x", "1"]),
+        Command::Run(stages(&[&["USET", "x", "1"]]), Reply::Ok)
+    );
+    assert_eq!(
+        command(&["SET", "x", "1", "nx"]),
+        Command::Run(stages(&[&["SET", "x", "1"]]), Reply::Ok)
+    );
+    assert_eq!(
+        command(&["SET", "x", "1", "nx", "px", "1500"]),
+        Command::Run(
+            stages(&[&["SETX", "x", "1", "PX", "1500", "NX"]]),
+            Reply::Ok
+        )
+    );
+    assert_eq!(
+        command(&["SET", "x", "1", "XX", "EX", "10"]),
+        Command::Run(stages(&[&["SETX", "x", "1", "EX", "10", "XX"]]), Reply::Ok)
+    );
+    assert_eq!(
+        command(&["SET", "x", "1", "NX", "XX"]),
+        Command::Local(b"-ERR syntax error\r\n".to_vec())
+    );
+    assert_eq!(
+        command(&["SET", "x", "1", "EX", "0"]),
+        Command::Local(b"-ERR invalid expire time in 'set' command\r\n".to_vec())
+    );
+}
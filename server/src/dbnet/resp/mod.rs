@@ -0,0 +1,251 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # RESP2 listener
+//!
+//! An optional second front-end that speaks RESP2, so that Redis client libraries can talk to
+//! `skyd` (say, while migrating). Every command is mapped onto the actions that implement it
+//! (see [`commands`]), which are run in an in-memory [`Session`] just like they would be for a
+//! Skyhash client. Their responses are then translated into RESP2 replies (see [`reply`]).
+//!
+//! Connections start in the default entity (and can switch with the `USE` command, which
+//! isn't a Redis command). Pub/sub isn't supported over RESP
+
+use {
+    self::{commands::Command, parser::ParseResult},
+    super::{
+        iplimit::IpLimiter,
+        listener::BaseListener,
+        session::{self, Session},
+        NetBackoff,
+    },
+    crate::{auth::AuthProvider, corestore::Corestore, util::error::SkyResult, IoResult},
+    bytes::{Buf, BytesMut},
+    std::{
+        net::{IpAddr, SocketAddr},
+        sync::Arc,
+    },
+    tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+        sync::{broadcast, mpsc, Semaphore},
+    },
+};
+
+mod commands;
+mod parser;
+mod reply;
+
+/// The RESP2 listener
+pub struct RespListener {
+    pub base: BaseListener,
+}
+
+impl RespListener {
+    /// Accept an incoming connection
+    async fn accept(&mut self) -> IoResult<(TcpStream, SocketAddr)> {
+        let backoff = NetBackoff::new();
+        loop {
+            match self.base.listener.accept().await {
+                Ok(accepted) => return Ok(accepted),
+                Err(e) => {
+                    if backoff.should_disconnect() {
+                        return Err(e);
+                    }
+                }
+            }
+            backoff.spin().await;
+        }
+    }
+    /// Run the listener
+    pub async fn run(&mut self) -> IoResult<()> {
+        loop {
+            self.base.climit.acquire().await.unwrap().forget();
+            // SECURITY: as with the other listeners, errors in the accept loop must not take
+            // the server down
+            let (stream, addr) = skip_loop_err!(self.accept().await);
+            let permit = match self.base.iplimit.acquire(addr.ip()) {
+                Some(permit) => permit,
+                None => {
+                    self.base.climit.add_permits(1);
+                    tokio::spawn(refuse(stream));
+                    continue;
+                }
+            };
+            let mut handler = RespHandler::new(
+                self.base.db.clone(),
+                stream,
+                self.base.auth.clone(),
+                self.base.climit.clone(),
+                self.base.signal.subscribe(),
+                self.base.terminate_tx.clone(),
+            );
+            tokio::spawn(async move {
+                // hold on to the IP's permit until the connection is closed
+                let _permit = permit;
+                if let Err(e) = handler.run().await {
+                    log::error!("Error: {}", e);
+                }
+            });
+        }
+    }
+}
+
+/// Run `listener` if there is one (otherwise this never returns)
+pub async fn run(listener: &mut Option<RespListener>) -> IoResult<()> {
+    match listener {
+        Some(listener) => listener.run().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Start the RESP2 listener on `port`
+pub async fn connect(
+    host: IpAddr,
+    port: u16,
+    climit: Arc<Semaphore>,
+    iplimit: Arc<IpLimiter>,
+    db: Corestore,
+    auth: AuthProvider,
+    signal: broadcast::Sender<()>,
+) -> SkyResult<RespListener> {
+    // the PROXY protocol only applies to the native listeners
+    let base = BaseListener::init(&db, auth, host, port, climit, iplimit, None, signal).await?;
+    log::info!("RESP listener started on resp://{host}:{port}");
+    Ok(RespListener { base })
+}
+
+/// Refuse a connection because its IP has too many connections open (with the error that
+/// Redis uses when it's out of clients)
+async fn refuse(mut stream: TcpStream) {
+    let mut out = Vec::new();
+    reply::error(&mut out, "ERR max number of clients reached");
+    let _ = stream.write_all(&out).await;
+}
+
+/// Handles a RESP2 connection
+struct RespHandler {
+    /// the client's socket
+    stream: TcpStream,
+    /// the commands read from the socket
+    buffer: BytesMut,
+    /// the session that actions are run in
+    session: Session,
+    climit: Arc<Semaphore>,
+    termination_signal: broadcast::Receiver<()>,
+    _term_sig_tx: mpsc::Sender<()>,
+}
+
+impl RespHandler {
+    fn new(
+        db: Corestore,
+        stream: TcpStream,
+        auth_data: AuthProvider,
+        climit: Arc<Semaphore>,
+        termination_signal: broadcast::Receiver<()>,
+        _term_sig_tx: mpsc::Sender<()>,
+    ) -> Self {
+        Self {
+            stream,
+            buffer: BytesMut::with_capacity(super::DEFAULT_BUFFER_SIZE),
+            session: Session::new(db, auth_data),
+            climit,
+            termination_signal,
+            _term_sig_tx,
+        }
+    }
+    async fn run(&mut self) -> IoResult<()> {
+        let mut out = Vec::new();
+        loop {
+            // run every command that has been read in full (pipelined commands are replied to
+            // in one write)
+            loop {
+                match parser::parse(&self.buffer) {
+                    ParseResult::Command(args, advance) => {
+                        self.buffer.advance(advance);
+                        if !self.execute(args, &mut out).await? {
+                            return self.stream.write_all(&out).await;
+                        }
+                    }
+                    ParseResult::Incomplete => break,
+                    ParseResult::Error(e) => {
+                        reply::error(&mut out, &format!("ERR Protocol error: {e}"));
+                        return self.stream.write_all(&out).await;
+                    }
+                }
+            }
+            if !out.is_empty() {
+                self.stream.write_all(&out).await?;
+                out.clear();
+            }
+            let read = tokio::select! {
+                read = self.stream.read_buf(&mut self.buffer) => read?,
+                _ = self.termination_signal.recv() => return Ok(()),
+            };
+            if read == 0 {
+                return Ok(());
+            }
+        }
+    }
+    /// Execute a command, writing the reply to `out`. Returns false if the connection has to
+    /// be closed
+    async fn execute(&mut self, args: Vec<Vec<u8>>, out: &mut Vec<u8>) -> IoResult<bool> {
+        let (stages, kind) = match commands::translate(args) {
+            Command::Local(local) => {
+                out.extend_from_slice(&local);
+                return Ok(true);
+            }
+            Command::Quit => {
+                reply::simple(out, b"OK");
+                return Ok(false);
+            }
+            Command::Run(stages, kind) => (stages, kind),
+        };
+        if !self.session.authenticated() && !stages[0][0].eq_ignore_ascii_case(b"AUTH") {
+            reply::error(out, "NOAUTH Authentication required.");
+            return Ok(true);
+        }
+        let mut response = Vec::new();
+        for stage in &stages {
+            response = self.session.execute_stage(stage).await?;
+            if !session::decode(&response).is_some_and(|element| element.succeeded()) {
+                break;
+            }
+        }
+        match session::decode(&response) {
+            Some(element) => reply::encode(kind, &element, out),
+            None => reply::error(out, "ERR unexpected response"),
+        }
+        Ok(true)
+    }
+}
+
+impl Drop for RespHandler {
+    fn drop(&mut self) {
+        // return the permit to the semaphore (even if there was a panic)
+        self.climit.add_permits(1);
+    }
+}
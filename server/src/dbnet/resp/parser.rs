@@ -0,0 +1,200 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # RESP2 request parser
+//!
+//! Clients send commands either as an array of bulk strings (`*2\r\n$3\r\nGET\r\n$1\r\nx\r\n`)
+//! or, when typed into a terminal, as an inline command (`GET x\r\n`). The parser never
+//! consumes a partial command: it either returns a full command along with the number of bytes
+//! it took up, or asks for more data
+
+/// The largest bulk string that a client can send (same as Redis)
+const MAX_BULK_LENGTH: usize = 512 * 1024 * 1024;
+/// The most arguments that a command can have
+const MAX_ARGUMENTS: usize = 1024 * 1024;
+/// The longest inline command
+const MAX_INLINE_LENGTH: usize = 64 * 1024;
+
+#[derive(Debug, PartialEq)]
+/// The result of parsing a command
+pub enum ParseResult {
+    /// A command with its arguments (the name is the first one), and the number of bytes that
+    /// it took up
+    Command(Vec<Vec<u8>>, usize),
+    /// The buffer doesn't hold a full command yet
+    Incomplete,
+    /// The command is malformed. The connection can't be used after this since there's no way
+    /// to tell where the next command starts
+    Error(&'static str),
+}
+
+/// Parse the command at the start of `buf`
+pub fn parse(buf: &[u8]) -> ParseResult {
+    let mut parser = Parser { buf, cursor: 0 };
+    let ret = match buf.first() {
+        None => return ParseResult::Incomplete,
+        Some(b'*') => parser.parse_array(),
+        Some(_) => parser.parse_inline(),
+    };
+    match ret {
+        Ok(Some(command)) => ParseResult::Command(command, parser.cursor),
+        Ok(None) => ParseResult::Incomplete,
+        Err(e) => ParseResult::Error(e),
+    }
+}
+
+type ParserResult<T> = Result<Option<T>, &'static str>;
+
+struct Parser<'a> {
+    buf: &'a [u8],
+    cursor: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// Read up to the next CRLF (which is skipped)
+    fn read_line(&mut self) -> Option<&'a [u8]> {
+        let rest = &self.buf[self.cursor..];
+        let end = rest.windows(2).position(|w| w == b"\r\n")?;
+        self.cursor += end + 2;
+        Some(&rest[..end])
+    }
+    /// Read a line holding `<tsymbol><number>`
+    fn read_length(&mut self, tsymbol: u8, max: usize) -> ParserResult<usize> {
+        let line = match self.read_line() {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+        match line.split_first() {
+            Some((first, digits)) if *first == tsymbol => self::parse_length(digits, max).map(Some),
+            _ => Err("expected a length"),
+        }
+    }
+    fn parse_array(&mut self) -> ParserResult<Vec<Vec<u8>>> {
+        let count = match self.read_length(b'*', MAX_ARGUMENTS)? {
+            Some(count) => count,
+            None => return Ok(None),
+        };
+        if count == 0 {
+            return Err("empty command");
+        }
+        let mut args = Vec::with_capacity(count.min(64));
+        for _ in 0..count {
+            let len = match self.read_length(b'$', MAX_BULK_LENGTH)? {
+                Some(len) => len,
+                None => return Ok(None),
+            };
+            let rest = &self.buf[self.cursor..];
+            if rest.len() < len + 2 {
+                return Ok(None);
+            }
+            if &rest[len..len + 2] != b"\r\n" {
+                return Err("expected CRLF after a bulk string");
+            }
+            args.push(rest[..len].to_vec());
+            self.cursor += len + 2;
+        }
+        Ok(Some(args))
+    }
+    fn parse_inline(&mut self) -> ParserResult<Vec<Vec<u8>>> {
+        // terminals may only send LF
+        let end = match self.buf.iter().position(|b| *b == b'\n') {
+            Some(end) => end,
+            None if self.buf.len() > MAX_INLINE_LENGTH => return Err("inline command too long"),
+            None => return Ok(None),
+        };
+        self.cursor = end + 1;
+        let line = &self.buf[..end];
+        let line = line.strip_suffix(b"\r").unwrap_or(line);
+        let args: Vec<Vec<u8>> = line
+            .split(|b| b.is_ascii_whitespace())
+            .filter(|arg| !arg.is_empty())
+            .map(|arg| arg.to_vec())
+            .collect();
+        if args.is_empty() {
+            return Err("empty command");
+        }
+        Ok(Some(args))
+    }
+}
+
+fn parse_length(digits: &[u8], max: usize) -> Result<usize, &'static str> {
+    let len = std::str::from_utf8(digits)
+        .ok()
+        .and_then(|digits| digits.parse::<usize>().ok())
+        .ok_or("invalid length")?;
+    if len > max {
+        Err("length out of range")
+    } else {
+        Ok(len)
+    }
+}
+
+#[test]
+fn test_parse_array() {
+    let command = b"*2\r\n$3\r\nGET\r\n$5\r\nsayan\r\n";
+    assert_eq!(
+        parse(command),
+        ParseResult::Command(vec![b"GET".to_vec(), b"sayan".to_vec()], command.len())
+    );
+    // binary-safe, and whatever follows is left alone
+    let mut pipelined = b"*2\r\n$3\r\nGET\r\n$4\r\na\r\nb\r\n".to_vec();
+    pipelined.extend_from_slice(b"*1\r\n$4\r\nPING\r\n");
+    assert_eq!(
+        parse(&pipelined),
+        ParseResult::Command(vec![b"GET".to_vec(), b"a\r\nb".to_vec()], 23)
+    );
+    for end in 0..command.len() {
+        assert_eq!(parse(&command[..end]), ParseResult::Incomplete);
+    }
+}
+
+#[test]
+fn test_parse_inline() {
+    assert_eq!(
+        parse(b"SET  x 10\r\n"),
+        ParseResult::Command(vec![b"SET".to_vec(), b"x".to_vec(), b"10".to_vec()], 11)
+    );
+    assert_eq!(
+        parse(b"PING\n"),
+        ParseResult::Command(vec![b"PING".to_vec()], 5)
+    );
+    assert_eq!(parse(b"PING"), ParseResult::Incomplete);
+}
+
+#[test]
+fn test_parse_bad_command() {
+    assert_eq!(parse(b"*0\r\n"), ParseResult::Error("empty command"));
+    assert_eq!(parse(b"*x\r\n"), ParseResult::Error("invalid length"));
+    assert_eq!(
+        parse(b"*1\r\n+GET\r\n"),
+        ParseResult::Error("expected a length")
+    );
+    assert_eq!(
+        parse(b"*1\r\n$3\r\nGETX\r\n"),
+        ParseResult::Error("expected CRLF after a bulk string")
+    );
+    assert_eq!(parse(b"\r\n"), ParseResult::Error("empty command"));
+}
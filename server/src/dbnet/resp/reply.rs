@@ -0,0 +1,201 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # RESP2 replies
+//!
+//! Commands are run as Skytable actions, so their responses are written in Skyhash 2. This
+//! module encodes those responses (once decoded) as RESP2 replies, adjusting them where Redis
+//! replies differently (for example, `EXPIRE` replies with `1` or `0` instead of a response
+//! code)
+
+use crate::dbnet::session::{Element, CODE_NIL, CODE_OKAY, CODE_OVERWRITE};
+
+/// The response string of `TTL` for keys that don't expire
+const CODE_NO_EXPIRY: &[u8] = b"no-expiry";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// How a response is turned into a reply
+pub enum Reply {
+    /// as it is
+    Native,
+    /// `+OK` if the action succeeded, and a null if it found (or didn't find) the key
+    Ok,
+    /// `1` if the action succeeded and `0` if it found (or didn't find) the key
+    Flag,
+    /// a count that is zero when the key doesn't exist
+    Count,
+    /// an array that is empty when the key doesn't exist
+    List,
+    /// an integer that Skytable returns as a string
+    Integer,
+    /// the TTL, with `-2` for missing keys and `-1` for keys that don't expire
+    Ttl,
+}
+
+/// Encode `element` as a reply (adjusted for `reply`) into `out`
+pub fn encode(reply: Reply, element: &Element<'_>, out: &mut Vec<u8>) {
+    match (reply, element) {
+        (Reply::Ok, Element::Code(CODE_OKAY) | Element::Int(_)) => self::simple(out, b"OK"),
+        (Reply::Ok, Element::Code(CODE_NIL | CODE_OVERWRITE)) => self::null(out),
+        (Reply::Flag, Element::Code(CODE_OKAY)) => self::integer(out, b"1"),
+        (Reply::Flag, Element::Code(CODE_NIL | CODE_OVERWRITE)) => self::integer(out, b"0"),
+        (Reply::Count, Element::Code(CODE_NIL)) => self::integer(out, b"0"),
+        (Reply::List, Element::Code(CODE_NIL)) => self::array_header(out, 0),
+        (Reply::Integer, Element::Str(value)) => self::integer(out, value),
+        (Reply::Ttl, Element::Code(CODE_NIL)) => self::integer(out, b"-2"),
+        (Reply::Ttl, Element::Code(CODE_NO_EXPIRY)) => self::integer(out, b"-1"),
+        (_, Element::Code(CODE_OKAY)) => self::simple(out, b"OK"),
+        (_, Element::Code(CODE_NIL)) => self::null(out),
+        (_, Element::Code(code)) => self::error(out, &self::error_for(code)),
+        (_, Element::Str(value) | Element::Float(value)) => self::bulk(out, value),
+        (_, Element::Int(value)) => self::integer(out, value),
+        (_, Element::Bool(value)) => self::integer(out, if *value { b"1" } else { b"0" }),
+        (_, Element::Map(pairs)) => {
+            // RESP2 has no maps, so this is a flat array of keys and values
+            self::array_header(out, pairs.len() * 2);
+            for (key, value) in pairs {
+                self::encode(Reply::Native, key, out);
+                self::encode(Reply::Native, value, out);
+            }
+        }
+        (_, Element::Array(elements)) => {
+            self::array_header(out, elements.len());
+            for element in elements {
+                match element {
+                    Some(element) => self::bulk(out, element),
+                    None => self::null(out),
+                }
+            }
+        }
+    }
+}
+
+/// Returns the error message (including the Redis error prefix) for a response code
+fn error_for(code: &[u8]) -> String {
+    let message = match code {
+        CODE_OVERWRITE => "ERR key already exists",
+        b"3" => "ERR wrong number of arguments",
+        b"4" => "ERR protocol error",
+        b"5" => "ERR server error",
+        b"7" => "ERR value is not an integer or out of range",
+        b"9" => "ERR invalid encoding",
+        b"10" => "WRONGPASS invalid username-password pair",
+        b"11" => "NOPERM this user has no permissions to run this command",
+        b"wrong-model" => "WRONGTYPE Operation against a key holding the wrong kind of value",
+        b"default-container-unset" => "ERR no model in use",
+        b"err-read-only" => "READONLY You can't write against a read only model",
+        b"err-out-of-memory" => "OOM command not allowed when the memory limit is hit",
+        code => return format!("ERR {}", String::from_utf8_lossy(code)),
+    };
+    message.to_owned()
+}
+
+/// Write a simple string
+pub fn simple(out: &mut Vec<u8>, string: &[u8]) {
+    out.push(b'+');
+    out.extend_from_slice(string);
+    out.extend_from_slice(b"\r\n");
+}
+
+/// Write an error (`message` has to start with the error prefix, like `ERR`)
+pub fn error(out: &mut Vec<u8>, message: &str) {
+    out.push(b'-');
+    // errors are single lines
+    out.extend(
+        message
+            .bytes()
+            .map(|b| if b == b'\r' || b == b'\n' { b' ' } else { b }),
+    );
+    out.extend_from_slice(b"\r\n");
+}
+
+/// Write an integer (given in decimal)
+pub fn integer(out: &mut Vec<u8>, digits: &[u8]) {
+    out.push(b':');
+    out.extend_from_slice(digits);
+    out.extend_from_slice(b"\r\n");
+}
+
+/// Write a bulk string
+pub fn bulk(out: &mut Vec<u8>, string: &[u8]) {
+    out.push(b'$');
+    out.extend_from_slice(string.len().to_string().as_bytes());
+    out.extend_from_slice(b"\r\n");
+    out.extend_from_slice(string);
+    out.extend_from_slice(b"\r\n");
+}
+
+/// Write a null bulk string
+pub fn null(out: &mut Vec<u8>) {
+    out.extend_from_slice(b"$-1\r\n");
+}
+
+/// Write the header of an array with `len` elements
+pub fn array_header(out: &mut Vec<u8>, len: usize) {
+    out.push(b'*');
+    out.extend_from_slice(len.to_string().as_bytes());
+    out.extend_from_slice(b"\r\n");
+}
+
+#[test]
+fn test_encode() {
+    let encoded = |reply, element| {
+        let mut out = Vec::new();
+        encode(reply, &element, &mut out);
+        String::from_utf8(out).unwrap()
+    };
+    assert_eq!(encoded(Reply::Native, Element::Code(b"0")), "+OK\r\n");
+    assert_eq!(encoded(Reply::Native, Element::Code(b"1")), "$-1\r\n");
+    assert_eq!(encoded(Reply::Ok, Element::Int(b"1")), "+OK\r\n");
+    assert_eq!(encoded(Reply::Ok, Element::Code(b"2")), "$-1\r\n");
+    assert_eq!(encoded(Reply::Flag, Element::Code(b"1")), ":0\r\n");
+    assert_eq!(encoded(Reply::Count, Element::Code(b"1")), ":0\r\n");
+    assert_eq!(encoded(Reply::List, Element::Code(b"1")), "*0\r\n");
+    assert_eq!(encoded(Reply::Integer, Element::Str(b"-3")), ":-3\r\n");
+    assert_eq!(encoded(Reply::Ttl, Element::Code(b"1")), ":-2\r\n");
+    assert_eq!(encoded(Reply::Ttl, Element::Code(b"no-expiry")), ":-1\r\n");
+    assert_eq!(encoded(Reply::Ttl, Element::Int(b"10")), ":10\r\n");
+    assert_eq!(
+        encoded(Reply::Native, Element::Code(b"wrong-model")),
+        "-WRONGTYPE Operation against a key holding the wrong kind of value\r\n"
+    );
+    assert_eq!(
+        encoded(Reply::Native, Element::Code(b"err-too-large")),
+        "-ERR err-too-large\r\n"
+    );
+    assert_eq!(
+        encoded(Reply::Native, Element::Array(vec![Some(b"a"), None])),
+        "*2\r\n$1\r\na\r\n$-1\r\n"
+    );
+    assert_eq!(encoded(Reply::Native, Element::Bool(true)), ":1\r\n");
+    assert_eq!(
+        encoded(
+            Reply::Native,
+            Element::Map(vec![(Element::Str(b"used"), Element::Int(b"42"))])
+        ),
+        "*2\r\n$4\r\nused\r\n:42\r\n"
+    );
+}
@@ -0,0 +1,273 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # In-memory sessions
+//!
+//! The front-ends that don't speak Skyhash (like the RESP2 and HTTP listeners) run actions on
+//! an in-memory connection: a [`Session`] runs a stage just like it would be run for a Skyhash
+//! client, and returns the response that was written. The response can then be decoded (see
+//! [`decode`]) and translated into whatever the client speaks
+
+use {
+    super::{AuthProviderHandle, BufferedSocketStream},
+    crate::{
+        actions::ActionError,
+        auth::AuthProvider,
+        corestore::Corestore,
+        dbnet::Connection,
+        protocol::{Skyhash2, UnsafeSlice},
+        queryengine, IoResult,
+    },
+    std::{
+        pin::Pin,
+        task::{Context, Poll},
+    },
+    tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt, ReadBuf},
+};
+
+/// Response code 0: Okay
+pub const CODE_OKAY: &[u8] = b"0";
+/// Response code 1: Nil
+pub const CODE_NIL: &[u8] = b"1";
+/// Response code 2: Overwrite error
+pub const CODE_OVERWRITE: &[u8] = b"2";
+
+/// The in-memory stream that actions are run on. It collects everything written to it (and
+/// never has anything to read)
+#[derive(Default)]
+pub struct ResponseBuffer(Vec<u8>);
+
+impl AsyncRead for ResponseBuffer {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        _buf: &mut ReadBuf<'_>,
+    ) -> Poll<IoResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl AsyncWrite for ResponseBuffer {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<IoResult<usize>> {
+        self.get_mut().0.extend_from_slice(buf);
+        Poll::Ready(Ok(buf.len()))
+    }
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<IoResult<()>> {
+        Poll::Ready(Ok(()))
+    }
+}
+
+impl BufferedSocketStream for ResponseBuffer {}
+
+/// A session that runs stages on an in-memory connection. It starts in the default entity
+pub struct Session {
+    db: Corestore,
+    con: Connection<ResponseBuffer, Skyhash2>,
+    auth: AuthProviderHandle,
+}
+
+impl Session {
+    pub fn new(mut db: Corestore, auth: AuthProvider) -> Self {
+        db.use_default_entity();
+        Self {
+            db,
+            con: Connection::new(ResponseBuffer::default()),
+            auth: AuthProviderHandle::new(auth),
+        }
+    }
+    /// Returns true if the session is authenticated (or if authn is disabled)
+    pub const fn authenticated(&self) -> bool {
+        self.auth.authenticated()
+    }
+    /// Run a stage, returning its (Skyhash 2) response. Until the session is authenticated,
+    /// only `AUTH` can be run
+    pub async fn execute_stage(&mut self, stage: &[Vec<u8>]) -> IoResult<Vec<u8>> {
+        let Self { db, con, auth } = self;
+        let stage: Vec<UnsafeSlice> = stage
+            .iter()
+            .map(|arg| UnsafeSlice::new(arg.as_ptr(), arg.len()))
+            .collect();
+        con.write_simple_query_header().await?;
+        let ret = if auth.authenticated() {
+            queryengine::execute_stage(db, con, auth, &stage).await
+        } else {
+            queryengine::execute_stage_noauth(con, auth, &stage).await
+        };
+        match ret {
+            Ok(()) => {}
+            Err(ActionError::ActionError(e)) => con.write_error(e).await?,
+            Err(ActionError::IoError(e)) => return Err(e),
+        }
+        con.stream.flush().await?;
+        Ok(std::mem::take(&mut con.stream_mut().0))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+/// A response element, as written by the Skyhash 2 writer
+pub enum Element<'a> {
+    /// a response code or a response string
+    Code(&'a [u8]),
+    /// a string or a blob
+    Str(&'a [u8]),
+    /// an integer
+    Int(&'a [u8]),
+    /// a float
+    Float(&'a [u8]),
+    /// a boolean
+    Bool(bool),
+    /// a map of key/value pairs
+    Map(Vec<(Element<'a>, Element<'a>)>),
+    /// a typed array (which may have null elements)
+    Array(Vec<Option<&'a [u8]>>),
+}
+
+impl<'a> Element<'a> {
+    /// Returns true if this element doesn't report a failure, so that the rest of the actions
+    /// of a command can run
+    pub fn succeeded(&self) -> bool {
+        match self {
+            Self::Code(code) => *code == CODE_OKAY,
+            _ => true,
+        }
+    }
+}
+
+/// Decode the response to a simple query (skipping the trace ID, if it was traced)
+pub fn decode(response: &[u8]) -> Option<Element<'_>> {
+    let mut decoder = Decoder {
+        buf: response.strip_prefix(b"*")?,
+    };
+    if let Some(rest) = decoder.buf.strip_prefix(b"#") {
+        decoder.buf = rest;
+        let len = decoder.length()?;
+        decoder.take(len)?;
+    }
+    decoder.element()
+}
+
+struct Decoder<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Decoder<'a> {
+    fn line(&mut self) -> Option<&'a [u8]> {
+        let end = self.buf.iter().position(|b| *b == b'\n')?;
+        let line = &self.buf[..end];
+        self.buf = &self.buf[end + 1..];
+        Some(line)
+    }
+    fn length(&mut self) -> Option<usize> {
+        std::str::from_utf8(self.line()?).ok()?.parse().ok()
+    }
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if self.buf.len() < len {
+            return None;
+        }
+        let (body, rest) = self.buf.split_at(len);
+        self.buf = rest;
+        Some(body)
+    }
+    fn element(&mut self) -> Option<Element<'a>> {
+        let (tsymbol, rest) = self.buf.split_first()?;
+        self.buf = rest;
+        let element = match tsymbol {
+            b'!' => Element::Code(self.line()?),
+            b'+' | b'?' => {
+                let len = self.length()?;
+                Element::Str(self.take(len)?)
+            }
+            b':' => Element::Int(self.line()?),
+            b'%' => Element::Float(self.line()?),
+            b'=' => match self.line()? {
+                b"0" => Element::Bool(false),
+                b"1" => Element::Bool(true),
+                _ => return None,
+            },
+            b'{' => {
+                let count = self.length()?;
+                let mut pairs = Vec::with_capacity(count.min(self.buf.len()));
+                for _ in 0..count {
+                    pairs.push((self.element()?, self.element()?));
+                }
+                Element::Map(pairs)
+            }
+            b'@' | b'^' => {
+                // skip the type of the elements
+                self.take(1)?;
+                let count = self.length()?;
+                let mut elements = Vec::with_capacity(count);
+                for _ in 0..count {
+                    if self.buf.first() == Some(&b'\0') {
+                        self.take(1)?;
+                        elements.push(None);
+                    } else {
+                        let len = self.length()?;
+                        elements.push(Some(self.take(len)?));
+                    }
+                }
+                Element::Array(elements)
+            }
+            _ => return None,
+        };
+        Some(element)
+    }
+}
+
+#[test]
+fn test_decode() {
+    assert_eq!(decode(b"*!0\n"), Some(Element::Code(b"0")));
+    assert_eq!(
+        decode(b"*!err-overflow\n"),
+        Some(Element::Code(b"err-overflow"))
+    );
+    assert_eq!(decode(b"*?5\nsayan"), Some(Element::Str(b"sayan")));
+    assert_eq!(decode(b"*:42\n"), Some(Element::Int(b"42")));
+    assert_eq!(
+        decode(b"*@+3\n1\na\x002\nbc"),
+        Some(Element::Array(vec![Some(b"a"), None, Some(b"bc")]))
+    );
+    assert_eq!(decode(b"*^?1\n0\n"), Some(Element::Array(vec![Some(b"")])));
+    assert_eq!(decode(b"*?5\nsay"), None);
+    assert_eq!(decode(b"*#4\nabcd:42\n"), Some(Element::Int(b"42")));
+    assert_eq!(decode(b"*=1\n"), Some(Element::Bool(true)));
+    assert_eq!(decode(b"*=2\n"), None);
+    assert_eq!(
+        decode(b"*{2\n+4\nused:42\n+7\nhealthy=0\n"),
+        Some(Element::Map(vec![
+            (Element::Str(b"used"), Element::Int(b"42")),
+            (Element::Str(b"healthy"), Element::Bool(false)),
+        ]))
+    );
+    assert_eq!(decode(b"*{2\n+4\nused:42\n"), None);
+}
@@ -28,11 +28,14 @@ pub use protocol::{ParseResult, Query};
 use {
     super::NetBackoff,
     crate::{
-        dbnet::{listener::BaseListener, BufferedSocketStream, Connection, ConnectionHandler},
+        dbnet::{
+            iplimit, listener::BaseListener, proxy, BufferedSocketStream, Connection,
+            ConnectionHandler,
+        },
         protocol::{self, interface::ProtocolSpec, Skyhash1, Skyhash2},
         IoResult,
     },
-    std::marker::PhantomData,
+    std::{marker::PhantomData, net::SocketAddr},
     tokio::net::TcpStream,
 };
 
@@ -54,13 +57,12 @@ impl<P: ProtocolSpec + 'static> RawListener<P> {
             _marker: PhantomData,
         }
     }
-    /// Accept an incoming connection
-    async fn accept(&mut self) -> IoResult<TcpStream> {
+    /// Accept an incoming connection, returning it along with the address of its peer
+    async fn accept(&mut self) -> IoResult<(TcpStream, SocketAddr)> {
         let backoff = NetBackoff::new();
         loop {
             match self.base.listener.accept().await {
-                // We don't need the bindaddr
-                Ok((stream, _)) => return Ok(stream),
+                Ok(accepted) => return Ok(accepted),
                 Err(e) => {
                     if backoff.should_disconnect() {
                         // Too many retries, goodbye user
@@ -86,18 +88,43 @@ impl<P: ProtocolSpec + 'static> RawListener<P> {
              can arise and it will flood the log and might also result
              in a crash
             */
-            let stream = skip_loop_err!(self.accept().await);
-            let mut chandle = ConnectionHandler::<TcpStream, P>::new(
-                self.base.db.clone(),
-                Connection::new(stream),
-                self.base.auth.clone(),
-                self.base.climit.clone(),
-                self.base.signal.subscribe(),
-                self.base.terminate_tx.clone(),
-            );
+            let (mut stream, peer) = skip_loop_err!(self.accept().await);
+            let base = &self.base;
+            let (db, auth, climit) = (base.db.clone(), base.auth.clone(), base.climit.clone());
+            let (iplimit, proxy) = (base.iplimit.clone(), base.proxy.clone());
+            let (signal, terminate_tx) = (base.signal.subscribe(), base.terminate_tx.clone());
+            // the PROXY protocol header can take a while to show up, so everything else is done
+            // without holding up the next connection
             tokio::spawn(async move {
+                let addr = match proxy::client_addr(proxy.as_deref(), &mut stream, peer).await {
+                    Ok(addr) => addr,
+                    Err(e) => {
+                        // a bad (or missing) PROXY protocol header
+                        log::debug!("Dropping connection from {peer}: {e}");
+                        climit.add_permits(1);
+                        return;
+                    }
+                };
+                let _permit = match iplimit.acquire(addr.ip()) {
+                    // hold on to the IP's permit until the connection is closed
+                    Some(permit) => permit,
+                    None => {
+                        // we won't be needing the permit that we took
+                        climit.add_permits(1);
+                        return iplimit::refuse::<TcpStream, P>(stream).await;
+                    }
+                };
+                let mut chandle = ConnectionHandler::<TcpStream, P>::new(
+                    db,
+                    Connection::new(stream),
+                    addr.to_string(),
+                    auth,
+                    climit,
+                    signal,
+                    terminate_tx,
+                );
                 if let Err(e) = chandle.run().await {
-                    log::error!("Error: {}", e);
+                    log::error!("Error ({addr}): {e}");
                 }
             });
         }
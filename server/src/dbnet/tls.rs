@@ -26,47 +26,81 @@
 
 use {
     crate::{
+        config::TrustedProxies,
         dbnet::{
-            listener::BaseListener, BufferedSocketStream, Connection, ConnectionHandler, NetBackoff,
+            iplimit, listener::BaseListener, proxy, BufferedSocketStream, Connection,
+            ConnectionHandler, NetBackoff,
         },
         protocol::{interface::ProtocolSpec, Skyhash1, Skyhash2},
         util::error::{Error, SkyResult},
         IoResult,
     },
     openssl::{
+        nid::Nid,
         pkey::PKey,
         rsa::Rsa,
-        ssl::{Ssl, SslAcceptor, SslFiletype, SslMethod},
+        ssl::{Ssl, SslAcceptor, SslFiletype, SslMethod, SslVerifyMode},
+        x509::X509Name,
+    },
+    std::{
+        fs,
+        marker::PhantomData,
+        net::SocketAddr,
+        pin::Pin,
+        sync::atomic::{AtomicU64, Ordering},
     },
-    std::{fs, marker::PhantomData, pin::Pin},
     tokio::net::TcpStream,
     tokio_openssl::SslStream,
 };
 
 impl BufferedSocketStream for SslStream<TcpStream> {}
 
+/// Bumped every time a reload of the TLS certificates is requested. Each listener compares
+/// this with the generation it last built its acceptor for
+static RELOAD_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Request all TLS listeners to reload their certificates and keys from disk. Listeners rebuild
+/// their acceptor before accepting the next connection, so existing connections are unaffected
+pub fn request_reload() {
+    RELOAD_GENERATION.fetch_add(1, Ordering::Release);
+}
+
+#[cfg(unix)]
+/// Request a reload of the TLS certificates every time we receive a `SIGHUP`
+pub async fn reload_on_sighup() {
+    use tokio::signal::unix::{signal, SignalKind};
+    let mut sighup = match signal(SignalKind::hangup()) {
+        Ok(sig) => sig,
+        Err(e) => {
+            log::error!("Failed to bind to SIGHUP with error: {e}");
+            return;
+        }
+    };
+    while sighup.recv().await.is_some() {
+        log::info!("Received SIGHUP. Reloading TLS certificates");
+        request_reload();
+    }
+}
+
 pub type SslListener = SslListenerRaw<Skyhash2>;
 pub type SslListenerV1 = SslListenerRaw<Skyhash1>;
 
-pub struct SslListenerRaw<P> {
-    pub base: BaseListener,
-    acceptor: SslAcceptor,
-    _marker: PhantomData<P>,
+/// The files that an acceptor is built from (kept around for reloads)
+struct AcceptorSource {
+    key_file: String,
+    chain_file: String,
+    tls_passfile: Option<String>,
+    client_ca: Option<String>,
 }
 
-impl<P: ProtocolSpec + 'static> SslListenerRaw<P> {
-    pub fn new_pem_based_ssl_connection(
-        key_file: String,
-        chain_file: String,
-        base: BaseListener,
-        tls_passfile: Option<String>,
-    ) -> SkyResult<SslListenerRaw<P>> {
+impl AcceptorSource {
+    fn build(&self) -> SkyResult<SslAcceptor> {
         let mut acceptor_builder = SslAcceptor::mozilla_intermediate(SslMethod::tls())?;
         // cert is the same for both
-        acceptor_builder.set_certificate_chain_file(chain_file)?;
-        if let Some(tls_passfile) = tls_passfile {
+        acceptor_builder.set_certificate_chain_file(&self.chain_file)?;
+        if let Some(tls_passfile) = &self.tls_passfile {
             // first read in the private key
-            let tls_private_key = fs::read(key_file)
+            let tls_private_key = fs::read(&self.key_file)
                 .map_err(|e| Error::ioerror_extra(e, "reading TLS private key"))?;
             // read the passphrase because the passphrase file stream was provided
             let tls_keyfile_stream = fs::read(tls_passfile)
@@ -78,31 +112,82 @@ impl<P: ProtocolSpec + 'static> SslListenerRaw<P> {
             acceptor_builder.set_private_key(&pkey)?;
         } else {
             // no passphrase, needs interactive
-            acceptor_builder.set_private_key_file(key_file, SslFiletype::PEM)?;
+            acceptor_builder.set_private_key_file(&self.key_file, SslFiletype::PEM)?;
         }
+        if let Some(client_ca) = &self.client_ca {
+            // only accept clients with a certificate signed by one of these CAs
+            acceptor_builder.set_ca_file(client_ca)?;
+            acceptor_builder.set_client_ca_list(X509Name::load_client_ca_file(client_ca)?);
+            acceptor_builder.set_verify(SslVerifyMode::PEER | SslVerifyMode::FAIL_IF_NO_PEER_CERT);
+        }
+        Ok(acceptor_builder.build())
+    }
+}
+
+pub struct SslListenerRaw<P> {
+    pub base: BaseListener,
+    acceptor: SslAcceptor,
+    source: AcceptorSource,
+    /// the reload generation that `acceptor` was built for
+    generation: u64,
+    _marker: PhantomData<P>,
+}
+
+impl<P: ProtocolSpec + 'static> SslListenerRaw<P> {
+    pub fn new_pem_based_ssl_connection(
+        key_file: String,
+        chain_file: String,
+        base: BaseListener,
+        tls_passfile: Option<String>,
+        client_ca: Option<String>,
+    ) -> SkyResult<SslListenerRaw<P>> {
+        let source = AcceptorSource {
+            key_file,
+            chain_file,
+            tls_passfile,
+            client_ca,
+        };
+        let generation = RELOAD_GENERATION.load(Ordering::Acquire);
         Ok(Self {
-            acceptor: acceptor_builder.build(),
+            acceptor: source.build()?,
+            source,
+            generation,
             base,
             _marker: PhantomData,
         })
     }
-    async fn accept(&mut self) -> SkyResult<SslStream<TcpStream>> {
+    /// Whether clients have to present a certificate (mutual TLS)
+    fn verify_clients(&self) -> bool {
+        self.source.client_ca.is_some()
+    }
+    /// Rebuild the acceptor if a reload was requested since it was last built. If the new
+    /// certificates can't be loaded, we keep using the old ones
+    fn reload_if_requested(&mut self) {
+        let generation = RELOAD_GENERATION.load(Ordering::Acquire);
+        if generation == self.generation {
+            return;
+        }
+        self.generation = generation;
+        match self.source.build() {
+            Ok(acceptor) => {
+                self.acceptor = acceptor;
+                log::info!("Reloaded TLS certificates");
+            }
+            Err(e) => {
+                log::error!("Failed to reload TLS certificates (still using the old ones): {e}");
+            }
+        }
+    }
+    /// Accept an incoming connection, returning it along with the address of its peer
+    async fn accept(&mut self) -> IoResult<(TcpStream, SocketAddr)> {
         let backoff = NetBackoff::new();
         loop {
             match self.base.listener.accept().await {
-                // We don't need the bindaddr
-                // We get the encrypted stream which we need to decrypt
-                // by using the acceptor
-                Ok((stream, _)) => {
-                    let ssl = Ssl::new(self.acceptor.context())?;
-                    let mut stream = SslStream::new(ssl, stream)?;
-                    Pin::new(&mut stream).accept().await?;
-                    return Ok(stream);
-                }
+                Ok(accepted) => return Ok(accepted),
                 Err(e) => {
                     if backoff.should_disconnect() {
                         // Too many retries, goodbye user
-                        return Err(e.into());
+                        return Err(e);
                     }
                 }
             }
@@ -123,20 +208,86 @@ impl<P: ProtocolSpec + 'static> SslListenerRaw<P> {
              can arise and it will flood the log and might also result
              in a crash
             */
-            let stream = skip_loop_err!(self.accept().await);
-            let mut sslhandle = ConnectionHandler::<SslStream<TcpStream>, P>::new(
-                self.base.db.clone(),
-                Connection::new(stream),
-                self.base.auth.clone(),
-                self.base.climit.clone(),
-                self.base.signal.subscribe(),
-                self.base.terminate_tx.clone(),
-            );
+            let (stream, peer) = skip_loop_err!(self.accept().await);
+            self.reload_if_requested();
+            let ssl = skip_loop_err!(Ssl::new(self.acceptor.context()));
+            let verify_clients = self.verify_clients();
+            let base = &self.base;
+            let (db, mut auth, climit) = (base.db.clone(), base.auth.clone(), base.climit.clone());
+            let (iplimit, proxy) = (base.iplimit.clone(), base.proxy.clone());
+            let (signal, terminate_tx) = (base.signal.subscribe(), base.terminate_tx.clone());
+            // the PROXY protocol header and the handshake can take a while, so they're done
+            // without holding up the next connection
             tokio::spawn(async move {
+                let (stream, addr) =
+                    match self::establish(proxy.as_deref(), ssl, stream, peer).await {
+                        Ok(established) => established,
+                        Err(e) => {
+                            log::debug!("Dropping connection from {peer}: {e}");
+                            climit.add_permits(1);
+                            return;
+                        }
+                    };
+                // the check comes after the handshake, so that the error can be sent to the client
+                let _permit = match iplimit.acquire(addr.ip()) {
+                    // hold on to the IP's permit until the connection is closed
+                    Some(permit) => permit,
+                    None => {
+                        // we won't be needing the permit that we took
+                        climit.add_permits(1);
+                        return iplimit::refuse::<SslStream<TcpStream>, P>(stream).await;
+                    }
+                };
+                if verify_clients {
+                    // map the certificate's CN to a user; if there's no such user, the client
+                    // will have to log in like everyone else
+                    if let Some(cn) = peer_common_name(&stream) {
+                        auth.login_with_certificate(&cn);
+                    }
+                }
+                let mut sslhandle = ConnectionHandler::<SslStream<TcpStream>, P>::new(
+                    db,
+                    Connection::new(stream),
+                    addr.to_string(),
+                    auth,
+                    climit,
+                    signal,
+                    terminate_tx,
+                );
                 if let Err(e) = sslhandle.run().await {
-                    log::error!("Error: {}", e);
+                    log::error!("Error ({addr}): {e}");
                 }
             });
         }
     }
 }
+
+/// Read the PROXY protocol header (if there is one) and do the handshake, returning the
+/// decrypted stream along with the address of the client
+async fn establish(
+    proxy: Option<&TrustedProxies>,
+    ssl: Ssl,
+    mut stream: TcpStream,
+    peer: SocketAddr,
+) -> SkyResult<(SslStream<TcpStream>, SocketAddr)> {
+    // the PROXY protocol header comes before the handshake
+    let addr = proxy::client_addr(proxy, &mut stream, peer).await?;
+    // We get the encrypted stream which we need to decrypt
+    // by using the acceptor
+    let mut stream = SslStream::new(ssl, stream)?;
+    Pin::new(&mut stream).accept().await?;
+    Ok((stream, addr))
+}
+
+/// Returns the common name (CN) from the subject of the peer's certificate, if any
+fn peer_common_name(stream: &SslStream<TcpStream>) -> Option<Vec<u8>> {
+    let cert = stream.ssl().peer_certificate()?;
+    let cn = cert
+        .subject_name()
+        .entries_by_nid(Nid::COMMONNAME)
+        .next()?
+        .data()
+        .as_slice()
+        .to_owned();
+    Some(cn)
+}
@@ -0,0 +1,195 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Unix domain socket listener
+//!
+//! Co-located applications and sidecars can connect to `skyd` over a Unix domain socket,
+//! skipping the TCP stack. Access is controlled with filesystem permissions: the socket file is
+//! only readable and writable by the user that `skyd` runs as and its group (who can be given
+//! access by adding them to that group)
+
+use {
+    super::NetBackoff,
+    crate::{
+        auth::AuthProvider,
+        config::ProtocolVersion,
+        corestore::Corestore,
+        dbnet::{BufferedSocketStream, Connection, ConnectionHandler},
+        protocol::{interface::ProtocolSpec, Skyhash1, Skyhash2},
+        util::error::{Error, SkyResult},
+        IoResult,
+    },
+    std::{
+        fs,
+        io::ErrorKind,
+        os::unix::fs::{FileTypeExt, PermissionsExt},
+        path::PathBuf,
+        sync::Arc,
+    },
+    tokio::{
+        net::{UnixListener, UnixStream},
+        sync::{broadcast, mpsc, Semaphore},
+    },
+};
+
+/// The permissions of the socket file (read and write for the owner and the group)
+const SOCKET_MODE: u32 = 0o660;
+
+impl BufferedSocketStream for UnixStream {}
+
+/// The Unix domain socket listener
+pub struct UnixSockListener {
+    /// An atomic reference to the coretable
+    db: Corestore,
+    /// The auth provider
+    auth: AuthProvider,
+    /// The incoming connection listener
+    listener: UnixListener,
+    /// The path of the socket file
+    path: PathBuf,
+    /// The protocol that clients speak
+    protocol: ProtocolVersion,
+    /// The maximum number of connections
+    climit: Arc<Semaphore>,
+    /// The shutdown broadcaster
+    signal: broadcast::Sender<()>,
+    // see [`BaseListener`](super::listener::BaseListener)
+    terminate_tx: mpsc::Sender<()>,
+    terminate_rx: mpsc::Receiver<()>,
+}
+
+impl UnixSockListener {
+    /// Accept an incoming connection
+    async fn accept(&mut self) -> IoResult<UnixStream> {
+        let backoff = NetBackoff::new();
+        loop {
+            match self.listener.accept().await {
+                Ok((stream, _)) => return Ok(stream),
+                Err(e) => {
+                    if backoff.should_disconnect() {
+                        return Err(e);
+                    }
+                }
+            }
+            backoff.spin().await;
+        }
+    }
+    /// Run the listener
+    pub async fn run(&mut self) -> IoResult<()> {
+        loop {
+            self.climit.acquire().await.unwrap().forget();
+            // SECURITY: as with the other listeners, errors in the accept loop must not take
+            // the server down
+            let stream = skip_loop_err!(self.accept().await);
+            match self.protocol {
+                ProtocolVersion::V2 => self.spawn::<Skyhash2>(stream),
+                ProtocolVersion::V1 => self.spawn::<Skyhash1>(stream),
+            }
+        }
+    }
+    fn spawn<P: ProtocolSpec + 'static>(&self, stream: UnixStream) {
+        let mut chandle = ConnectionHandler::<UnixStream, P>::new(
+            self.db.clone(),
+            Connection::new(stream),
+            "unix".to_owned(),
+            self.auth.clone(),
+            self.climit.clone(),
+            self.signal.subscribe(),
+            self.terminate_tx.clone(),
+        );
+        tokio::spawn(async move {
+            if let Err(e) = chandle.run().await {
+                log::error!("Error: {}", e);
+            }
+        });
+    }
+    /// Signal the connections to shut down, wait for them to do so and remove the socket file
+    pub async fn release_self(self) {
+        let Self {
+            mut terminate_rx,
+            terminate_tx,
+            signal,
+            path,
+            ..
+        } = self;
+        drop(signal);
+        drop(terminate_tx);
+        let _ = terminate_rx.recv().await;
+        if let Err(e) = fs::remove_file(&path) {
+            log::warn!("Failed to remove the socket file `{}`: {e}", path.display());
+        }
+    }
+}
+
+/// Run `listener` if there is one (otherwise this never returns)
+pub async fn run(listener: &mut Option<UnixSockListener>) -> IoResult<()> {
+    match listener {
+        Some(listener) => listener.run().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Start listening on the Unix domain socket at `path`
+pub async fn connect(
+    path: &str,
+    protocol: ProtocolVersion,
+    climit: Arc<Semaphore>,
+    db: Corestore,
+    auth: AuthProvider,
+    signal: broadcast::Sender<()>,
+) -> SkyResult<UnixSockListener> {
+    let path = PathBuf::from(path);
+    // a socket file left behind by a crash would stop us from binding
+    match fs::symlink_metadata(&path) {
+        Ok(meta) if meta.file_type().is_socket() => fs::remove_file(&path)
+            .map_err(|e| Error::ioerror_extra(e, "removing a stale socket file"))?,
+        Ok(_) => {
+            return Err(Error::OtherError(format!(
+                "`{}` exists and isn't a socket file",
+                path.display()
+            )))
+        }
+        Err(e) if e.kind() == ErrorKind::NotFound => {}
+        Err(e) => return Err(Error::ioerror_extra(e, "checking the socket file")),
+    }
+    let listener = UnixListener::bind(&path)
+        .map_err(|e| Error::ioerror_extra(e, format!("binding to `{}`", path.display())))?;
+    fs::set_permissions(&path, fs::Permissions::from_mode(SOCKET_MODE))
+        .map_err(|e| Error::ioerror_extra(e, "setting the permissions of the socket file"))?;
+    let (terminate_tx, terminate_rx) = mpsc::channel(1);
+    log::info!("Server started on unix://{}", path.display());
+    Ok(UnixSockListener {
+        db,
+        auth,
+        listener,
+        path,
+        protocol,
+        climit,
+        signal,
+        terminate_tx,
+        terminate_rx,
+    })
+}
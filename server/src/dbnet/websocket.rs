@@ -0,0 +1,513 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # WebSocket listener
+//!
+//! Browser-based dashboards (and environments where only HTTP(S) egress is allowed) can't open
+//! raw TCP connections, so `skyd` can also speak Skyhash over WebSocket (RFC 6455). After the
+//! HTTP upgrade, the payloads of the data frames in each direction make up the same byte stream
+//! that a TCP client would see: a client sends a query in one or more binary (or text) frames,
+//! and every response is sent in a binary frame (a very large response may be split across
+//! several, which have to be concatenated). For `wss://`, put a TLS-terminating proxy in front.
+//!
+//! Each connection is bridged to a regular [`ConnectionHandler`] through an in-memory pipe:
+//! one task unwraps incoming frames into the pipe, and another wraps whatever the handler
+//! writes into outgoing frames (and answers pings and close frames)
+
+use {
+    super::{
+        iplimit::{IpLimiter, IpPermit},
+        listener::BaseListener,
+        BufferedSocketStream, NetBackoff, DEFAULT_BUFFER_SIZE,
+    },
+    crate::{
+        auth::AuthProvider,
+        config::ProtocolVersion,
+        corestore::Corestore,
+        dbnet::{Connection, ConnectionHandler},
+        protocol::{interface::ProtocolSpec, Skyhash1, Skyhash2},
+        util::error::SkyResult,
+        IoResult,
+    },
+    std::{
+        io::ErrorKind,
+        net::{IpAddr, SocketAddr},
+        sync::Arc,
+    },
+    tokio::{
+        io::{self, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, DuplexStream},
+        net::TcpStream,
+        sync::{broadcast, mpsc, Semaphore},
+    },
+};
+
+/// The GUID that is appended to the client's key to compute the accept key
+const GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+/// The largest upgrade request that we accept
+const MAX_HANDSHAKE_SIZE: usize = 8192;
+/// The largest payload of a control frame
+const MAX_CONTROL_PAYLOAD: u64 = 125;
+/// The number of control frames (pongs and closes) that can be waiting to be sent
+const CONTROL_QUEUE_SIZE: usize = 16;
+/// Close code: normal closure
+const CLOSE_NORMAL: u16 = 1000;
+/// Close code: protocol error
+const CLOSE_PROTOCOL_ERROR: u16 = 1002;
+
+/// Frame opcodes
+mod opcode {
+    pub const CONTINUATION: u8 = 0x0;
+    pub const TEXT: u8 = 0x1;
+    pub const BINARY: u8 = 0x2;
+    pub const CLOSE: u8 = 0x8;
+    pub const PING: u8 = 0x9;
+    pub const PONG: u8 = 0xA;
+}
+
+impl BufferedSocketStream for DuplexStream {}
+
+/// The WebSocket listener
+pub struct WebSocketListener {
+    pub base: BaseListener,
+    /// The protocol that clients speak (inside the frames)
+    protocol: ProtocolVersion,
+}
+
+impl WebSocketListener {
+    /// Accept an incoming connection
+    async fn accept(&mut self) -> IoResult<(TcpStream, SocketAddr)> {
+        let backoff = NetBackoff::new();
+        loop {
+            match self.base.listener.accept().await {
+                Ok(accepted) => return Ok(accepted),
+                Err(e) => {
+                    if backoff.should_disconnect() {
+                        return Err(e);
+                    }
+                }
+            }
+            backoff.spin().await;
+        }
+    }
+    /// Run the listener
+    pub async fn run(&mut self) -> IoResult<()> {
+        loop {
+            self.base.climit.acquire().await.unwrap().forget();
+            // SECURITY: as with the other listeners, errors in the accept loop must not take
+            // the server down
+            let (stream, addr) = skip_loop_err!(self.accept().await);
+            let permit = match self.base.iplimit.acquire(addr.ip()) {
+                Some(permit) => permit,
+                None => {
+                    self.base.climit.add_permits(1);
+                    tokio::spawn(refuse(stream));
+                    continue;
+                }
+            };
+            match self.protocol {
+                ProtocolVersion::V2 => self.spawn::<Skyhash2>(stream, addr, permit),
+                ProtocolVersion::V1 => self.spawn::<Skyhash1>(stream, addr, permit),
+            }
+        }
+    }
+    fn spawn<P: ProtocolSpec + 'static>(
+        &self,
+        mut stream: TcpStream,
+        addr: SocketAddr,
+        permit: IpPermit,
+    ) {
+        let base = &self.base;
+        let (db, auth, climit) = (base.db.clone(), base.auth.clone(), base.climit.clone());
+        let (signal, terminate_tx) = (base.signal.subscribe(), base.terminate_tx.clone());
+        tokio::spawn(async move {
+            // hold on to the IP's permit until the connection is closed
+            let _permit = permit;
+            if !matches!(self::handshake(&mut stream).await, Ok(true)) {
+                // we never got to create a handler, so the permit has to be returned here
+                climit.add_permits(1);
+                return;
+            }
+            let (ours, theirs) = io::duplex(DEFAULT_BUFFER_SIZE);
+            let mut chandle = ConnectionHandler::<DuplexStream, P>::new(
+                db,
+                Connection::new(theirs),
+                addr.to_string(),
+                auth,
+                climit,
+                signal,
+                terminate_tx,
+            );
+            let (socket_rx, socket_tx) = stream.into_split();
+            let (handler_rx, handler_tx) = io::split(ours);
+            let (control_tx, control_rx) = mpsc::channel(CONTROL_QUEUE_SIZE);
+            let reader = tokio::spawn(self::read_frames(socket_rx, handler_tx, control_tx));
+            let writer = tokio::spawn(self::write_frames(handler_rx, socket_tx, control_rx));
+            if let Err(e) = chandle.run().await {
+                log::error!("Error: {}", e);
+            }
+            // dropping the handler closes the pipe, which lets the writer send a close frame
+            drop(chandle);
+            let _ = writer.await;
+            reader.abort();
+        });
+    }
+}
+
+/// Run `listener` if there is one (otherwise this never returns)
+pub async fn run(listener: &mut Option<WebSocketListener>) -> IoResult<()> {
+    match listener {
+        Some(listener) => listener.run().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Start the WebSocket listener on `port`
+#[allow(clippy::too_many_arguments)]
+pub async fn connect(
+    host: IpAddr,
+    port: u16,
+    protocol: ProtocolVersion,
+    climit: Arc<Semaphore>,
+    iplimit: Arc<IpLimiter>,
+    db: Corestore,
+    auth: AuthProvider,
+    signal: broadcast::Sender<()>,
+) -> SkyResult<WebSocketListener> {
+    // the PROXY protocol only applies to the native listeners
+    let base = BaseListener::init(&db, auth, host, port, climit, iplimit, None, signal).await?;
+    log::info!("WebSocket listener started on ws://{host}:{port}");
+    Ok(WebSocketListener { base, protocol })
+}
+
+/// Refuse a connection because its IP has too many connections open (this is sent instead of
+/// the handshake, so the client sees an ordinary HTTP error)
+async fn refuse(mut stream: TcpStream) {
+    let _ = stream
+        .write_all(
+            b"HTTP/1.1 429 Too Many Requests\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+        )
+        .await;
+}
+
+/// Read the upgrade request and reply to it. Returns true if the connection was upgraded
+async fn handshake(stream: &mut TcpStream) -> IoResult<bool> {
+    let mut request = Vec::with_capacity(1024);
+    let end = loop {
+        if let Some(end) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+            break end;
+        }
+        if request.len() > MAX_HANDSHAKE_SIZE {
+            stream
+                .write_all(b"HTTP/1.1 431 Request Header Fields Too Large\r\n\r\n")
+                .await?;
+            return Ok(false);
+        }
+        if stream.read_buf(&mut request).await? == 0 {
+            return Ok(false);
+        }
+    };
+    match self::accept_key(&request[..end]) {
+        Some(accept) => {
+            let response = format!(
+                "HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Accept: {accept}\r\n\r\n"
+            );
+            stream.write_all(response.as_bytes()).await?;
+            Ok(true)
+        }
+        None => {
+            stream
+                .write_all(b"HTTP/1.1 400 Bad Request\r\nContent-Length: 0\r\n\r\n")
+                .await?;
+            Ok(false)
+        }
+    }
+}
+
+/// Validate an upgrade request (without the terminating empty line), returning the value of
+/// the `Sec-WebSocket-Accept` header for it
+fn accept_key(request: &[u8]) -> Option<String> {
+    let request = std::str::from_utf8(request).ok()?;
+    let mut lines = request.split("\r\n");
+    if !lines.next()?.starts_with("GET ") {
+        return None;
+    }
+    let (mut upgrade, mut key, mut version) = (false, None, false);
+    for line in lines {
+        let (name, value) = line.split_once(':')?;
+        let value = value.trim();
+        match name.trim().to_ascii_lowercase().as_str() {
+            "upgrade" => {
+                upgrade = value
+                    .split(',')
+                    .any(|token| token.trim().eq_ignore_ascii_case("websocket"))
+            }
+            "sec-websocket-key" => key = Some(value),
+            "sec-websocket-version" => version = value == "13",
+            _ => {}
+        }
+    }
+    if !(upgrade && version) {
+        return None;
+    }
+    let mut key = key?.to_owned();
+    key.push_str(GUID);
+    Some(base64::encode(openssl::sha::sha1(key.as_bytes())))
+}
+
+#[derive(Debug, PartialEq)]
+/// A frame header
+struct FrameHeader {
+    opcode: u8,
+    /// the masking key (clients have to mask every frame)
+    mask: Option<[u8; 4]>,
+    len: u64,
+}
+
+/// Read a frame header
+async fn read_header<R: AsyncRead + Unpin>(socket: &mut R) -> IoResult<FrameHeader> {
+    let mut head = [0u8; 2];
+    socket.read_exact(&mut head).await?;
+    let opcode = head[0] & 0x0F;
+    let len = match head[1] & 0x7F {
+        126 => socket.read_u16().await? as u64,
+        127 => socket.read_u64().await?,
+        len => len as u64,
+    };
+    let mask = if head[1] & 0x80 != 0 {
+        let mut mask = [0u8; 4];
+        socket.read_exact(&mut mask).await?;
+        Some(mask)
+    } else {
+        None
+    };
+    Ok(FrameHeader { opcode, mask, len })
+}
+
+/// Unmask a part of a payload that starts at `offset`
+fn unmask(data: &mut [u8], mask: [u8; 4], offset: usize) {
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte ^= mask[(offset + i) % 4];
+    }
+}
+
+/// Write a frame (servers never mask their frames)
+async fn write_frame<W: AsyncWrite + Unpin>(
+    socket: &mut W,
+    opcode: u8,
+    payload: &[u8],
+) -> IoResult<()> {
+    let mut head = Vec::with_capacity(10);
+    // we never fragment our frames
+    head.push(0x80 | opcode);
+    match payload.len() {
+        len if len < 126 => head.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            head.push(126);
+            head.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            head.push(127);
+            head.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+    socket.write_all(&head).await?;
+    socket.write_all(payload).await?;
+    socket.flush().await
+}
+
+/// A control frame for the writer to send
+#[derive(Debug, PartialEq)]
+enum Control {
+    Pong(Vec<u8>),
+    /// a close frame with this payload (the status code, in network byte order)
+    Close(Vec<u8>),
+}
+
+/// Unwrap the frames read from the socket, writing the data to the handler
+async fn read_frames<R, W>(
+    mut socket: R,
+    mut handler: W,
+    control: mpsc::Sender<Control>,
+) -> IoResult<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut chunk = vec![0u8; DEFAULT_BUFFER_SIZE];
+    let ret = loop {
+        let header = match self::read_header(&mut socket).await {
+            Ok(header) => header,
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break Ok(()),
+            Err(e) => break Err(e),
+        };
+        let mask = match header.mask {
+            Some(mask) => mask,
+            None => {
+                let _ = control.send(self::close(CLOSE_PROTOCOL_ERROR)).await;
+                break Ok(());
+            }
+        };
+        match header.opcode {
+            opcode::CONTINUATION | opcode::TEXT | opcode::BINARY => {
+                // stream the payload to the handler so that large frames are never buffered
+                let (mut remaining, mut offset) = (header.len, 0);
+                while remaining != 0 {
+                    let len = remaining.min(chunk.len() as u64) as usize;
+                    socket.read_exact(&mut chunk[..len]).await?;
+                    self::unmask(&mut chunk[..len], mask, offset);
+                    if handler.write_all(&chunk[..len]).await.is_err() {
+                        // the handler has gone away
+                        return Ok(());
+                    }
+                    remaining -= len as u64;
+                    offset += len;
+                }
+            }
+            opcode::CLOSE | opcode::PING | opcode::PONG if header.len <= MAX_CONTROL_PAYLOAD => {
+                let mut payload = vec![0u8; header.len as usize];
+                socket.read_exact(&mut payload).await?;
+                self::unmask(&mut payload, mask, 0);
+                match header.opcode {
+                    opcode::PING => {
+                        let _ = control.send(Control::Pong(payload)).await;
+                    }
+                    opcode::CLOSE => {
+                        // echo the status code
+                        payload.truncate(2);
+                        let _ = control.send(Control::Close(payload)).await;
+                        break Ok(());
+                    }
+                    _ => {}
+                }
+            }
+            _ => {
+                let _ = control.send(self::close(CLOSE_PROTOCOL_ERROR)).await;
+                break Ok(());
+            }
+        }
+    };
+    // let the handler know that there won't be any more queries
+    let _ = handler.shutdown().await;
+    ret
+}
+
+/// Wrap what the handler writes into frames, and send the control frames
+async fn write_frames<R, W>(
+    mut handler: R,
+    mut socket: W,
+    mut control: mpsc::Receiver<Control>,
+) -> IoResult<()>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = vec![0u8; DEFAULT_BUFFER_SIZE];
+    let mut closed = false;
+    loop {
+        tokio::select! {
+            read = handler.read(&mut buf) => {
+                let read = read?;
+                if read == 0 {
+                    if !closed {
+                        let code = CLOSE_NORMAL.to_be_bytes();
+                        let _ = self::write_frame(&mut socket, opcode::CLOSE, &code).await;
+                    }
+                    return Ok(());
+                }
+                if !closed {
+                    self::write_frame(&mut socket, opcode::BINARY, &buf[..read]).await?;
+                }
+            }
+            Some(frame) = control.recv(), if !closed => match frame {
+                Control::Pong(payload) => {
+                    self::write_frame(&mut socket, opcode::PONG, &payload).await?
+                }
+                Control::Close(payload) => {
+                    // nothing can be sent after a close frame, but the handler's output still
+                    // has to be drained so that it can finish
+                    self::write_frame(&mut socket, opcode::CLOSE, &payload).await?;
+                    closed = true;
+                }
+            },
+        }
+    }
+}
+
+fn close(code: u16) -> Control {
+    Control::Close(code.to_be_bytes().to_vec())
+}
+
+#[test]
+fn test_accept_key() {
+    // the example from RFC 6455
+    let request = "GET /chat HTTP/1.1\r\nHost: server.example.com\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\nSec-WebSocket-Version: 13";
+    assert_eq!(
+        accept_key(request.as_bytes()).as_deref(),
+        Some("s3pPLMBiTxaQ9kYGzzhZRbK+xOo=")
+    );
+    // not an upgrade
+    let request = "GET / HTTP/1.1\r\nHost: server.example.com";
+    assert_eq!(accept_key(request.as_bytes()), None);
+}
+
+#[tokio::test]
+async fn test_read_frames() {
+    let mask = [1, 2, 3, 4];
+    let masked = |opcode: u8, payload: &[u8]| {
+        let mut frame = vec![0x80 | opcode, 0x80 | payload.len() as u8];
+        frame.extend_from_slice(&mask);
+        let mut payload = payload.to_vec();
+        unmask(&mut payload, mask, 0);
+        frame.extend(payload);
+        frame
+    };
+    let mut socket = masked(opcode::BINARY, b"*1\n4\nheya");
+    socket.extend(masked(opcode::PING, b"hi"));
+    socket.extend(masked(opcode::CLOSE, &CLOSE_NORMAL.to_be_bytes()));
+    let (mut handler, handler_tx) = io::duplex(DEFAULT_BUFFER_SIZE);
+    let (control_tx, mut control_rx) = mpsc::channel(CONTROL_QUEUE_SIZE);
+    read_frames(socket.as_slice(), handler_tx, control_tx)
+        .await
+        .unwrap();
+    let mut query = Vec::new();
+    handler.read_to_end(&mut query).await.unwrap();
+    assert_eq!(query, b"*1\n4\nheya");
+    assert_eq!(control_rx.recv().await, Some(Control::Pong(b"hi".to_vec())));
+    assert_eq!(control_rx.recv().await, Some(close(CLOSE_NORMAL)));
+}
+
+#[tokio::test]
+async fn test_write_frames() {
+    let (handler, mut handler_tx) = io::duplex(DEFAULT_BUFFER_SIZE);
+    let (mut socket, socket_tx) = io::duplex(DEFAULT_BUFFER_SIZE);
+    let (_control_tx, control_rx) = mpsc::channel(CONTROL_QUEUE_SIZE);
+    handler_tx.write_all(b"*!0\n").await.unwrap();
+    drop(handler_tx);
+    write_frames(handler, socket_tx, control_rx).await.unwrap();
+    let mut frames = Vec::new();
+    socket.read_to_end(&mut frames).await.unwrap();
+    assert_eq!(frames, b"\x82\x04*!0\n\x88\x02\x03\xe8");
+}
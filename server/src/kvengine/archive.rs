@@ -0,0 +1,472 @@
+/*
+ * Created on Sat Oct 22 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Cold data archival
+//!
+//! When an archive policy is set, every key/value table keeps an _access clock_: the day (since
+//! the epoch) on which each key was last accessed. A periodic sweep moves the values of keys that
+//! have been idle for more than the configured number of days into an on-disk archive segment
+//! and leaves a small stub (the offset and length of the value) in memory. Any access to an
+//! archived key transparently faults the value back in.
+//!
+//! The clock is keyed by the hash of a key rather than a copy of it. Keys whose hashes collide
+//! share a clock entry, which at worst keeps one of them hot for longer than it should be.
+//!
+//! Segments are read and written at explicit offsets, so the stubs can be read without holding
+//! any lock: faulting in a value never waits on a sweep or on a flush. The locks only guard the
+//! in-memory bookkeeping of a move.
+//!
+//! Archive segments are scratch files: a flush always writes out the archived values along with
+//! the hot ones, so the segments are discarded on startup and everything is loaded hot. Space in
+//! a segment is only reclaimed once every value in it has been faulted back in.
+
+use {
+    crate::{
+        corestore::{htable::Coremap, SharedSlice},
+        storage::v1::fsync,
+        util::os,
+        IoResult,
+    },
+    core::{
+        hash::{Hash, Hasher},
+        sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+    },
+    parking_lot::{Mutex, RwLock, RwLockReadGuard},
+    std::{
+        collections::{hash_map::DefaultHasher, HashMap, HashSet},
+        fs::{self, File, OpenOptions},
+        io::ErrorKind,
+        sync::Arc,
+    },
+};
+
+/// The directory that holds the archive segments
+pub const DIR_ARCHIVE: &str = "data/archive";
+const SECS_PER_DAY: u64 = 86400;
+
+/// The number of days after which an idle key is archived. Zero if archival is disabled
+static IDLE_DAYS: AtomicU64 = AtomicU64::new(0);
+/// The ID of the next archive segment
+static SEGMENT_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Set the archive policy and clear out any segments left behind by a previous run
+pub fn init(idle_days: u64) -> IoResult<()> {
+    match fs::remove_dir_all(DIR_ARCHIVE) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    IDLE_DAYS.store(idle_days, Ordering::Release);
+    Ok(())
+}
+
+/// Returns true if the access clock is being maintained
+fn is_enabled() -> bool {
+    IDLE_DAYS.load(Ordering::Relaxed) != 0
+}
+
+fn today() -> u64 {
+    os::get_epoch_secs() / SECS_PER_DAY
+}
+
+/// The hash that a key's access clock is kept under
+fn clock_hash(key: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[derive(Debug, Clone)]
+/// The in-memory stub for an archived value. The stub keeps its segment alive, so it can be
+/// read even if the segment is reset or the archive is cleared in the meantime
+struct Stub {
+    segment: Arc<Segment>,
+    offset: u64,
+    len: usize,
+}
+
+impl Stub {
+    fn read(&self) -> IoResult<Vec<u8>> {
+        let mut value = vec![0; self.len];
+        os::read_exact_at(&self.segment.file, &mut value, self.offset)?;
+        Ok(value)
+    }
+    /// Returns true if both stubs point to the same value
+    fn is(&self, other: &Stub) -> bool {
+        Arc::ptr_eq(&self.segment, &other.segment) && self.offset == other.offset
+    }
+}
+
+#[derive(Debug)]
+/// An archive segment on disk
+struct Segment {
+    path: String,
+    file: File,
+    /// the offset at which the next value is appended (only sweeps append, one at a time)
+    len: AtomicU64,
+}
+
+impl Segment {
+    fn create() -> IoResult<Self> {
+        fs::create_dir_all(DIR_ARCHIVE)?;
+        let path = format!(
+            "{DIR_ARCHIVE}/{id}.seg",
+            id = SEGMENT_ID.fetch_add(1, Ordering::Relaxed)
+        );
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)?;
+        Ok(Self {
+            path,
+            file,
+            len: AtomicU64::new(0),
+        })
+    }
+    fn append(self: &Arc<Self>, value: &[u8]) -> IoResult<Stub> {
+        let offset = self.len.load(Ordering::Acquire);
+        os::write_all_at(&self.file, value, offset)?;
+        self.len
+            .store(offset + value.len() as u64, Ordering::Release);
+        Ok(Stub {
+            segment: self.clone(),
+            offset,
+            len: value.len(),
+        })
+    }
+    fn reset(&self) -> IoResult<()> {
+        self.file.set_len(0)?;
+        self.len.store(0, Ordering::Release);
+        Ok(())
+    }
+}
+
+impl Drop for Segment {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[derive(Debug, Default)]
+/// The archive for a key/value table
+pub struct ColdArchive {
+    /// the day on which each key (by [`clock_hash`]) was last accessed
+    clock: Coremap<u64, u64>,
+    /// the stubs for the archived values
+    stubs: Coremap<SharedSlice, Stub>,
+    /// the number of archived values
+    archived: AtomicUsize,
+    /// the total size of the archived values
+    archived_bytes: AtomicU64,
+    /// the segment that sweeps append to (created on first use). a sweep holds the write lock
+    /// and a freeze holds a read lock, so nothing is archived while the archive is frozen
+    segment: RwLock<Option<Arc<Segment>>>,
+    /// held while a value is moved in or out of the archive in memory (never across I/O)
+    moving: Mutex<()>,
+}
+
+impl ColdArchive {
+    /// Returns the number of archived values
+    pub fn len(&self) -> usize {
+        self.archived.load(Ordering::Acquire)
+    }
+    /// Returns true if nothing is archived
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Returns the total size of the archived values
+    pub fn archived_bytes(&self) -> u64 {
+        self.archived_bytes.load(Ordering::Acquire)
+    }
+    /// Returns true if the value of `key` is archived
+    pub fn is_archived(&self, key: &[u8]) -> bool {
+        self.stubs.contains_key(key)
+    }
+    /// Returns up to `count` archived keys
+    pub fn get_keys(&self, count: usize) -> Vec<SharedSlice> {
+        self.stubs.get_keys(count)
+    }
+    /// Returns a page of at most `count` archived keys starting at `cursor`, along with the
+    /// cursor of the next page (see [`Coremap::scan_keys`])
+    pub fn scan_keys(&self, cursor: u64, count: usize) -> (Vec<SharedSlice>, u64) {
+        self.stubs.scan_keys(cursor, count)
+    }
+    /// Returns a uniformly random archived key, if there are any
+    pub fn random_key(&self) -> Option<SharedSlice> {
+        self.stubs.random_key()
+    }
+    #[inline(always)]
+    /// Record an access to `key`, faulting its value back into `data` if it was archived
+    pub fn touch(&self, data: &Coremap<SharedSlice, SharedSlice>, key: &[u8]) {
+        if !self.is_empty() && self.stubs.contains_key(key) {
+            self.fault_in(data, key);
+        }
+        if is_enabled() {
+            let (hash, today) = (clock_hash(key), today());
+            if self.clock.get(&hash).is_none_or(|day| *day != today) {
+                self.clock.upsert(hash, today);
+            }
+        }
+    }
+    #[cold]
+    #[inline(never)]
+    fn fault_in(&self, data: &Coremap<SharedSlice, SharedSlice>, key: &[u8]) {
+        let stub = match self.stubs.get_cloned(key) {
+            Some(stub) => stub,
+            // someone else faulted it in
+            None => return,
+        };
+        let value = match stub.read() {
+            Ok(value) => value,
+            Err(e) => return log::error!("Failed to fault in archived value: {e}"),
+        };
+        let _moving = self.moving.lock();
+        // the stub may have been faulted in by someone else (or the archive cleared) while we
+        // were reading it
+        if !self.stubs.get(key).is_some_and(|current| current.is(&stub)) {
+            return;
+        }
+        // insert before removing the stub, so that a key is never missing from both
+        data.true_if_insert(key.into(), value.into());
+        self.stubs.remove(key);
+        self.archived_bytes
+            .fetch_sub(stub.len as u64, Ordering::AcqRel);
+        self.archived.fetch_sub(1, Ordering::AcqRel);
+    }
+    /// Move the values of the keys in `data` that have been idle for more than `idle_days`
+    /// days into the archive. Returns the number of values that were archived
+    pub fn archive_idle(
+        &self,
+        data: &Coremap<SharedSlice, SharedSlice>,
+        idle_days: u64,
+    ) -> IoResult<usize> {
+        self.archive_accessed_before(data, today().saturating_sub(idle_days))
+    }
+    /// Archive the values of the keys in `data` that were last accessed before `cutoff`
+    pub(super) fn archive_accessed_before(
+        &self,
+        data: &Coremap<SharedSlice, SharedSlice>,
+        cutoff: u64,
+    ) -> IoResult<usize> {
+        let today = today();
+        let mut segment = self.segment.write();
+        let mut live = HashSet::with_capacity(data.len());
+        let mut idle = Vec::new();
+        for kv in data.iter() {
+            let hash = clock_hash(kv.key());
+            live.insert(hash);
+            match self.clock.get_cloned(&hash) {
+                Some(day) if day < cutoff => idle.push((kv.key().clone(), kv.value().clone())),
+                Some(_) => {}
+                // never accessed since startup; start the clock now
+                None => self.clock.upsert(hash, today),
+            }
+        }
+        // drop the clock for keys that no longer exist
+        let removed: Vec<u64> = self
+            .clock
+            .iter()
+            .map(|kv| *kv.key())
+            .filter(|hash| !live.contains(hash))
+            .collect();
+        removed.into_iter().for_each(|hash| {
+            self.clock.remove(&hash);
+        });
+        if idle.is_empty() {
+            return Ok(0);
+        }
+        let segment = match segment.as_ref() {
+            // every value in it has been faulted in; reclaim the space
+            Some(current) if Arc::strong_count(current) == 1 => {
+                current.reset()?;
+                current.clone()
+            }
+            Some(current) => current.clone(),
+            None => segment.insert(Arc::new(Segment::create()?)).clone(),
+        };
+        let mut stubs = Vec::with_capacity(idle.len());
+        for (key, value) in idle {
+            let stub = segment.append(&value)?;
+            stubs.push((key, value, stub));
+        }
+        fsync::sync_data(&segment.file, &segment.path)?;
+        let mut archived = 0;
+        for (key, value, stub) in stubs {
+            let _moving = self.moving.lock();
+            let len = stub.len as u64;
+            // add the stub before removing the value, so that a key is never missing from both
+            self.stubs.upsert(key.clone(), stub);
+            if data.true_remove_if(&key, |_, current| current == &value) {
+                self.clock.remove(&clock_hash(&key));
+                self.archived_bytes.fetch_add(len, Ordering::AcqRel);
+                self.archived.fetch_add(1, Ordering::AcqRel);
+                archived += 1;
+            } else {
+                // updated since we looked at it, so it's no longer idle
+                self.stubs.remove(&key);
+            }
+        }
+        Ok(archived)
+    }
+    /// Freeze the archive, so that nothing is archived until the returned guard is dropped.
+    /// Values can still be faulted in, but the guard keeps seeing the values that were
+    /// archived when it was created
+    pub fn freeze(&self) -> FrozenArchive<'_> {
+        let _segment = self.segment.read();
+        let stubs = self
+            .stubs
+            .iter()
+            .map(|kv| (kv.key().clone(), kv.value().clone()))
+            .collect();
+        FrozenArchive { _segment, stubs }
+    }
+    /// Discard everything in the archive. The segment is reset by the next sweep
+    pub fn clear(&self) {
+        let _moving = self.moving.lock();
+        self.stubs.clear();
+        self.clock.clear();
+        self.archived.store(0, Ordering::Release);
+        self.archived_bytes.store(0, Ordering::Release);
+    }
+}
+
+/// A snapshot of the values that were archived when the archive was frozen. Since the values
+/// archived at that point may be faulted in while the snapshot is in use, a key that is in the
+/// snapshot should be skipped when it is also found with the hot values (see
+/// [`FrozenArchive::contains`])
+pub struct FrozenArchive<'a> {
+    _segment: RwLockReadGuard<'a, Option<Arc<Segment>>>,
+    stubs: HashMap<SharedSlice, Stub>,
+}
+
+impl<'a> FrozenArchive<'a> {
+    /// Returns the number of archived values
+    pub fn len(&self) -> usize {
+        self.stubs.len()
+    }
+    /// Returns true if nothing is archived
+    pub fn is_empty(&self) -> bool {
+        self.stubs.is_empty()
+    }
+    /// Returns true if the value of `key` is in the snapshot
+    pub fn contains(&self, key: &[u8]) -> bool {
+        self.stubs.contains_key(key)
+    }
+    /// Call `f` with every archived key and its value
+    pub fn for_each(&mut self, mut f: impl FnMut(&[u8], &[u8]) -> IoResult<()>) -> IoResult<()> {
+        for (key, stub) in self.stubs.iter() {
+            let value = stub.read()?;
+            f(key, &value)?;
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_archive_fault_in() {
+    let data: Coremap<SharedSlice, SharedSlice> = Coremap::new();
+    data.upsert("hot".into(), "fire".into());
+    data.upsert("cold".into(), "ice".into());
+    let archive = ColdArchive::default();
+    // the first sweep starts the clock for keys that it hasn't seen
+    assert_eq!(
+        archive.archive_accessed_before(&data, today() + 1).unwrap(),
+        0
+    );
+    // nothing has been accessed since, so everything is archived
+    assert_eq!(
+        archive.archive_accessed_before(&data, today() + 1).unwrap(),
+        2
+    );
+    assert_eq!(data.len(), 0);
+    assert_eq!(archive.len(), 2);
+    assert_eq!(archive.archived_bytes(), 7);
+    // flushing sees every archived value, even if it's faulted in while the archive is frozen
+    let mut frozen = archive.freeze();
+    archive.touch(&data, b"hot");
+    assert_eq!(data.get_cloned(b"hot".as_ref()).unwrap(), "fire");
+    assert_eq!(archive.len(), 1);
+    assert_eq!(archive.archived_bytes(), 3);
+    assert!(frozen.contains(b"hot"));
+    let mut seen = Vec::new();
+    frozen
+        .for_each(|k, v| {
+            seen.push((k.to_owned(), v.to_owned()));
+            Ok(())
+        })
+        .unwrap();
+    drop(frozen);
+    seen.sort();
+    assert_eq!(
+        seen,
+        vec![
+            (b"cold".to_vec(), b"ice".to_vec()),
+            (b"hot".to_vec(), b"fire".to_vec())
+        ]
+    );
+    // and the other
+    archive.touch(&data, b"cold");
+    assert_eq!(data.get_cloned(b"cold".as_ref()).unwrap(), "ice");
+    assert!(archive.is_empty());
+    assert_eq!(archive.archived_bytes(), 0);
+}
+
+#[test]
+fn test_archive_segment_reuse() {
+    let data: Coremap<SharedSlice, SharedSlice> = Coremap::new();
+    data.upsert("k".into(), "v1".into());
+    let archive = ColdArchive::default();
+    archive.archive_accessed_before(&data, today() + 1).unwrap();
+    assert_eq!(
+        archive.archive_accessed_before(&data, today() + 1).unwrap(),
+        1
+    );
+    let segment = archive.segment.read().clone().unwrap();
+    assert_eq!(segment.len.load(Ordering::Acquire), 2);
+    archive.touch(&data, b"k");
+    assert!(archive.is_empty());
+    // everything was faulted in, so the next sweep starts the segment over
+    data.upsert("k".into(), "v2".into());
+    drop(segment);
+    archive.archive_accessed_before(&data, today() + 1).unwrap();
+    assert_eq!(
+        archive.archive_accessed_before(&data, today() + 1).unwrap(),
+        1
+    );
+    assert_eq!(
+        archive
+            .segment
+            .read()
+            .as_ref()
+            .unwrap()
+            .len
+            .load(Ordering::Acquire),
+        2
+    );
+    archive.touch(&data, b"k");
+    assert_eq!(data.get_cloned(b"k".as_ref()).unwrap(), "v2");
+}
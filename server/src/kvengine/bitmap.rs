@@ -0,0 +1,88 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Bitmaps
+//!
+//! Any binary value can be used as a bitmap. Bits are numbered from the most significant bit of
+//! the first byte, so bit 0 is `0x80` of byte 0, bit 7 is `0x01` of byte 0 and bit 8 is `0x80`
+//! of byte 1. A bit past the end of a value is unset, and setting one pads the value with zero
+//! bytes (see [`KVEStandard::set_bit`]).
+//!
+//! [`KVEStandard::set_bit`]: super::KVEStandard::set_bit
+
+/// Returns the index of the byte that holds bit `offset`, and the mask for the bit in that byte
+pub const fn locate(offset: usize) -> (usize, u8) {
+    (offset / 8, 0x80 >> (offset % 8))
+}
+
+/// Returns true if bit `offset` of `bitmap` is set
+pub fn get(bitmap: &[u8], offset: usize) -> bool {
+    let (byte, mask) = locate(offset);
+    bitmap.get(byte).is_some_and(|byte| byte & mask != 0)
+}
+
+/// Returns the number of set bits in `bytes`. This counts eight bytes at a time, which the
+/// compiler turns into a single `popcnt` where the target has one
+pub fn popcount(bytes: &[u8]) -> usize {
+    let words = bytes.chunks_exact(8);
+    let tail = words.remainder();
+    let count: u32 = words
+        .map(|word| {
+            // UNWRAP: `chunks_exact` only returns chunks of eight bytes
+            u64::from_ne_bytes(word.try_into().unwrap()).count_ones()
+        })
+        .sum::<u32>()
+        + tail.iter().map(|byte| byte.count_ones()).sum::<u32>();
+    count as usize
+}
+
+#[test]
+fn test_get_bit() {
+    let bitmap = [0b1000_0001, 0b0100_0000];
+    assert!(get(&bitmap, 0));
+    assert!(!get(&bitmap, 1));
+    assert!(get(&bitmap, 7));
+    assert!(get(&bitmap, 9));
+    // past the end
+    assert!(!get(&bitmap, 16));
+    assert!(!get(&[], 0));
+}
+
+#[test]
+fn test_popcount() {
+    assert_eq!(popcount(&[]), 0);
+    assert_eq!(popcount(&[0xFF; 3]), 24);
+    let bytes: Vec<u8> = (0..=255).collect();
+    assert_eq!(
+        popcount(&bytes),
+        bytes.iter().map(|b| b.count_ones() as usize).sum::<usize>()
+    );
+    // with a tail that isn't a whole word
+    assert_eq!(
+        popcount(&bytes[3..]),
+        popcount(&bytes) - popcount(&bytes[..3])
+    );
+}
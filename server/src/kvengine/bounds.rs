@@ -0,0 +1,103 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # List bounds
+//!
+//! A list can be given a maximum length with `LMOD <list> maxlen <n>`, after which every push
+//! that takes it past `n` elements drops its oldest (first) elements. This keeps lists that are
+//! used as activity feeds from growing without bound. Like the deadlines of expiring keys, the
+//! bounds are kept in a [`ListBounds`] index next to the table's data and are flushed along
+//! with it, and a bound goes away along with its list.
+
+use {
+    crate::corestore::{htable::Coremap, SharedSlice},
+    core::sync::atomic::{AtomicUsize, Ordering},
+};
+
+#[derive(Debug, Default)]
+/// The maximum lengths of the lists in a table that have one
+pub struct ListBounds {
+    bounds: Coremap<SharedSlice, u64>,
+    /// the number of bounds, so that tables without any don't have to look them up
+    count: AtomicUsize,
+}
+
+impl ListBounds {
+    /// Create an index with the given bounds
+    pub fn new(bounds: Coremap<SharedSlice, u64>) -> Self {
+        let count = AtomicUsize::new(bounds.len());
+        Self { bounds, count }
+    }
+    /// Returns true if no list has a bound
+    pub fn is_empty(&self) -> bool {
+        self.count.load(Ordering::Acquire) == 0
+    }
+    /// Returns a reference to the bounds
+    pub fn bounds(&self) -> &Coremap<SharedSlice, u64> {
+        &self.bounds
+    }
+    /// Set the bound of `key`
+    pub fn set(&self, key: SharedSlice, bound: usize) {
+        if self.bounds.true_if_insert(key.clone(), bound as u64) {
+            self.count.fetch_add(1, Ordering::Release);
+        } else {
+            self.bounds.upsert(key, bound as u64);
+        }
+    }
+    /// Returns the bound of `key`, if it has one
+    pub fn get(&self, key: &[u8]) -> Option<usize> {
+        if self.is_empty() {
+            return None;
+        }
+        self.bounds.get(key).map(|bound| *bound as usize)
+    }
+    /// Remove the bound of `key`. Returns true if it had one
+    pub fn remove(&self, key: &[u8]) -> bool {
+        let removed = !self.is_empty() && self.bounds.true_if_removed(key);
+        if removed {
+            self.count.fetch_sub(1, Ordering::Release);
+        }
+        removed
+    }
+    /// Remove every bound
+    pub fn clear(&self) {
+        self.bounds.clear();
+        self.count.store(0, Ordering::Release);
+    }
+}
+
+#[test]
+fn test_list_bounds() {
+    let bounds = ListBounds::default();
+    assert!(bounds.is_empty());
+    bounds.set("feed".into(), 10);
+    bounds.set("feed".into(), 5);
+    assert_eq!(bounds.get(b"feed"), Some(5));
+    assert_eq!(bounds.get(b"other"), None);
+    assert!(bounds.remove(b"feed"));
+    assert!(!bounds.remove(b"feed"));
+    assert!(bounds.is_empty());
+}
@@ -0,0 +1,166 @@
+/*
+ * Created on Sun Nov 06 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Write deduplication
+//!
+//! An opt-in window that suppresses writes carrying a request ID that was already seen, so that
+//! at-least-once producers can safely retry increments and list pushes. Request IDs are kept as
+//! 64-bit fingerprints in two rotating generations, so an ID is remembered for at least one
+//! window (and at most two) and the memory used is bounded by the write rate. A request ID is
+//! forgotten if its write fails, so that the write can be retried.
+
+use {
+    ahash::RandomState,
+    core::{
+        mem,
+        sync::atomic::{AtomicU64, Ordering},
+    },
+    parking_lot::Mutex,
+    std::{
+        collections::HashSet,
+        time::{Duration, Instant},
+    },
+};
+
+#[derive(Debug, Default)]
+/// A window that suppresses duplicate writes on a table
+pub struct DedupWindow {
+    /// the window (in milliseconds). zero if deduplication is off
+    window: AtomicU64,
+    /// the fingerprints of the request IDs seen recently
+    seen: Mutex<Generations>,
+    /// the keyed hasher for fingerprints (so that clients can't pick colliding IDs)
+    hasher: RandomState,
+    /// the number of writes that were suppressed
+    suppressed: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Generations {
+    /// the fingerprints seen since `started`
+    current: HashSet<u64>,
+    /// the fingerprints seen in the window before that
+    previous: HashSet<u64>,
+    /// when the current generation was started
+    started: Instant,
+}
+
+impl Default for Generations {
+    fn default() -> Self {
+        Self {
+            current: HashSet::new(),
+            previous: HashSet::new(),
+            started: Instant::now(),
+        }
+    }
+}
+
+impl DedupWindow {
+    /// Suppress duplicate request IDs seen within `window`, forgetting every request ID seen so
+    /// far. A window of zero turns deduplication off
+    pub fn set_window(&self, window: Duration) {
+        let mut seen = self.seen.lock();
+        *seen = Generations::default();
+        self.window
+            .store(window.as_millis() as u64, Ordering::Release);
+    }
+    #[inline(always)]
+    /// Returns true if writes are being deduplicated
+    pub fn is_enabled(&self) -> bool {
+        self.window.load(Ordering::Relaxed) != 0
+    }
+    /// Returns the number of writes that were suppressed so far
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed.load(Ordering::Relaxed)
+    }
+    /// Record a request ID. This returns false if it was already seen within the window, in
+    /// which case the write must be suppressed
+    pub fn record(&self, request_id: &[u8]) -> bool {
+        let fingerprint = self.hasher.hash_one(request_id);
+        let mut seen = self.seen.lock();
+        // load this under the lock so that we see the generations `set_window` reset for it
+        let window = Duration::from_millis(self.window.load(Ordering::Acquire));
+        if window.is_zero() {
+            return true;
+        }
+        let age = seen.started.elapsed();
+        if age >= window {
+            let current = mem::take(&mut seen.current);
+            if age >= window * 2 {
+                // nothing from the current generation is in the window anymore
+                seen.previous.clear();
+            } else {
+                seen.previous = current;
+            }
+            seen.started = Instant::now();
+        }
+        if seen.previous.contains(&fingerprint) || !seen.current.insert(fingerprint) {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            false
+        } else {
+            true
+        }
+    }
+    /// Forget a request ID that was recorded for a write that then failed, so that a retry
+    /// isn't suppressed
+    pub fn forget(&self, request_id: &[u8]) {
+        let fingerprint = self.hasher.hash_one(request_id);
+        let mut seen = self.seen.lock();
+        seen.current.remove(&fingerprint);
+        seen.previous.remove(&fingerprint);
+    }
+}
+
+#[test]
+fn test_dedup_window() {
+    let dedup = DedupWindow::default();
+    assert!(!dedup.is_enabled());
+    assert!(dedup.record(b"req-1"));
+    assert!(dedup.record(b"req-1"));
+    dedup.set_window(Duration::from_millis(100));
+    assert!(dedup.record(b"req-1"));
+    assert!(!dedup.record(b"req-1"));
+    assert!(dedup.record(b"req-2"));
+    assert_eq!(dedup.suppressed(), 1);
+    // still remembered after a rotation
+    std::thread::sleep(Duration::from_millis(120));
+    assert!(!dedup.record(b"req-2"));
+    // but forgotten once both generations have gone past the window
+    std::thread::sleep(Duration::from_millis(220));
+    assert!(dedup.record(b"req-1"));
+    assert!(dedup.record(b"req-2"));
+    assert_eq!(dedup.suppressed(), 2);
+    // a failed write is forgotten, so its retry goes through (once)
+    assert!(!dedup.record(b"req-1"));
+    dedup.forget(b"req-1");
+    assert!(dedup.record(b"req-1"));
+    assert!(!dedup.record(b"req-1"));
+    assert_eq!(dedup.suppressed(), 4);
+    // and turn it off
+    dedup.set_window(Duration::ZERO);
+    assert!(!dedup.is_enabled());
+    assert!(dedup.record(b"req-1"));
+}
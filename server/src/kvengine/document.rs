@@ -0,0 +1,654 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Documents
+//!
+//! A document is a parsed JSON value. Documents are parsed (and hence validated) when they're
+//! written, so a table only ever holds well-formed JSON, and parts of a document can be read or
+//! written with a path like `$.users[0].name`. On disk, documents are written in a compact
+//! binary encoding (see [`Json::encode`]) so that loading them doesn't need a JSON parser.
+
+use core::{mem, str};
+
+/// The depth of nested arrays and objects allowed in a document
+pub const MAX_DEPTH: usize = 64;
+
+// the tags of the binary encoding
+const TAG_NULL: u8 = 0;
+const TAG_FALSE: u8 = 1;
+const TAG_TRUE: u8 = 2;
+const TAG_NUMBER: u8 = 3;
+const TAG_STR: u8 = 4;
+const TAG_ARRAY: u8 = 5;
+const TAG_OBJECT: u8 = 6;
+
+#[derive(Debug, Clone, PartialEq)]
+/// A JSON value. Objects keep their fields in the order in which they were added, and never
+/// have two fields with the same name
+pub enum Json {
+    Null,
+    Bool(bool),
+    /// a finite number
+    Number(f64),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+/// The reasons that a value can't be set at a path
+pub enum SetError {
+    /// the document doesn't exist
+    NoDocument,
+    /// the parent of the path doesn't exist (or isn't an object or array)
+    NoParent,
+    /// the document would end up nested more than [`MAX_DEPTH`] levels deep
+    TooDeep,
+}
+
+impl Json {
+    /// Parse a JSON document. Returns `None` if it isn't valid JSON, if it's nested more than
+    /// [`MAX_DEPTH`] levels deep, if an object has two fields with the same name or if a number
+    /// is out of range
+    pub fn parse(text: &[u8]) -> Option<Self> {
+        let mut parser = Parser {
+            text: str::from_utf8(text).ok()?,
+            pos: 0,
+        };
+        let value = parser.parse_value(0)?;
+        parser.skip_whitespace();
+        (parser.pos == parser.text.len()).then_some(value)
+    }
+    /// Returns the deepest level of nesting in this value (zero for a scalar)
+    pub fn depth(&self) -> usize {
+        match self {
+            Self::Array(elements) => 1 + elements.iter().map(Self::depth).max().unwrap_or(0),
+            Self::Object(fields) => 1 + fields.iter().map(|(_, v)| v.depth()).max().unwrap_or(0),
+            _ => 0,
+        }
+    }
+    /// Returns the approximate memory taken by this value
+    pub fn footprint(&self) -> usize {
+        mem::size_of::<Self>()
+            + match self {
+                Self::Str(s) => s.len(),
+                Self::Array(elements) => elements.iter().map(Self::footprint).sum(),
+                Self::Object(fields) => fields
+                    .iter()
+                    .map(|(name, value)| name.len() + mem::size_of::<String>() + value.footprint())
+                    .sum(),
+                _ => 0,
+            }
+    }
+    /// Returns the value at `path`, if there is one
+    pub fn get(&self, path: &Path) -> Option<&Self> {
+        path.steps
+            .iter()
+            .try_fold(self, |value, step| match (value, step) {
+                (Self::Object(fields), Step::Field(name)) => {
+                    fields.iter().find(|(f, _)| f == name).map(|(_, v)| v)
+                }
+                (Self::Array(elements), Step::Index(i)) => elements.get(*i),
+                _ => None,
+            })
+    }
+    fn get_mut(&mut self, step: &Step) -> Option<&mut Self> {
+        match (self, step) {
+            (Self::Object(fields), Step::Field(name)) => {
+                fields.iter_mut().find(|(f, _)| f == name).map(|(_, v)| v)
+            }
+            (Self::Array(elements), Step::Index(i)) => elements.get_mut(*i),
+            _ => None,
+        }
+    }
+    /// Set the value at `path`, adding the field (or appending the element, if the index is
+    /// the length of the array) if it doesn't exist. Returns the value that was replaced
+    pub fn set(&mut self, path: &Path, value: Self) -> Result<Option<Self>, SetError> {
+        if path.steps.len() + value.depth() > MAX_DEPTH {
+            return Err(SetError::TooDeep);
+        }
+        let (last, parents) = match path.steps.split_last() {
+            Some(split) => split,
+            None => return Ok(Some(mem::replace(self, value))),
+        };
+        let parent = parents
+            .iter()
+            .try_fold(self, |value, step| value.get_mut(step))
+            .ok_or(SetError::NoParent)?;
+        match (parent, last) {
+            (Self::Object(fields), Step::Field(name)) => {
+                match fields.iter_mut().find(|(f, _)| f == name) {
+                    Some((_, slot)) => Ok(Some(mem::replace(slot, value))),
+                    None => {
+                        fields.push((name.clone(), value));
+                        Ok(None)
+                    }
+                }
+            }
+            (Self::Array(elements), Step::Index(i)) if *i < elements.len() => {
+                Ok(Some(mem::replace(&mut elements[*i], value)))
+            }
+            (Self::Array(elements), Step::Index(i)) if *i == elements.len() => {
+                elements.push(value);
+                Ok(None)
+            }
+            _ => Err(SetError::NoParent),
+        }
+    }
+    /// Returns this value as (compact) JSON text
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        self.render_into(&mut out);
+        out
+    }
+    fn render_into(&self, out: &mut String) {
+        match self {
+            Self::Null => out.push_str("null"),
+            Self::Bool(true) => out.push_str("true"),
+            Self::Bool(false) => out.push_str("false"),
+            Self::Number(n) => out.push_str(&n.to_string()),
+            Self::Str(s) => render_string(out, s),
+            Self::Array(elements) => {
+                out.push('[');
+                for (i, element) in elements.iter().enumerate() {
+                    if i != 0 {
+                        out.push(',');
+                    }
+                    element.render_into(out);
+                }
+                out.push(']');
+            }
+            Self::Object(fields) => {
+                out.push('{');
+                for (i, (name, value)) in fields.iter().enumerate() {
+                    if i != 0 {
+                        out.push(',');
+                    }
+                    render_string(out, name);
+                    out.push(':');
+                    value.render_into(out);
+                }
+                out.push('}');
+            }
+        }
+    }
+    /// Append the binary encoding of this value to `out`:
+    /// - `null`, `false` and `true` are a single tag byte
+    /// - a number is a tag byte followed by the (little-endian) bits of the `f64`
+    /// - a string is a tag byte followed by its length (as a varint) and its bytes
+    /// - an array is a tag byte followed by its length (as a varint) and its elements
+    /// - an object is a tag byte followed by its length (as a varint) and its fields, every
+    ///   field being the name (encoded like a string, without the tag) and then the value
+    ///
+    /// Varints are unsigned LEB128
+    pub fn encode(&self, out: &mut Vec<u8>) {
+        match self {
+            Self::Null => out.push(TAG_NULL),
+            Self::Bool(false) => out.push(TAG_FALSE),
+            Self::Bool(true) => out.push(TAG_TRUE),
+            Self::Number(n) => {
+                out.push(TAG_NUMBER);
+                out.extend_from_slice(&n.to_bits().to_le_bytes());
+            }
+            Self::Str(s) => {
+                out.push(TAG_STR);
+                encode_bytes(out, s.as_bytes());
+            }
+            Self::Array(elements) => {
+                out.push(TAG_ARRAY);
+                encode_varint(out, elements.len());
+                elements.iter().for_each(|element| element.encode(out));
+            }
+            Self::Object(fields) => {
+                out.push(TAG_OBJECT);
+                encode_varint(out, fields.len());
+                for (name, value) in fields {
+                    encode_bytes(out, name.as_bytes());
+                    value.encode(out);
+                }
+            }
+        }
+    }
+    /// Decode a value written by [`Json::encode`]. Returns `None` if `bytes` isn't exactly one
+    /// well-formed value
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        let mut decoder = Decoder { bytes, pos: 0 };
+        let value = decoder.decode_value(0)?;
+        (decoder.pos == bytes.len()).then_some(value)
+    }
+}
+
+/// Push `s` as a quoted JSON string
+fn render_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+fn encode_varint(out: &mut Vec<u8>, mut n: usize) {
+    while n >= 0x80 {
+        out.push((n as u8) | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn encode_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    encode_varint(out, bytes.len());
+    out.extend_from_slice(bytes);
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Field(String),
+    Index(usize),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A path into a document: `$` (the document itself) followed by any number of `.<field>` and
+/// `[<index>]` steps
+pub struct Path {
+    steps: Vec<Step>,
+}
+
+impl Path {
+    /// Parse a path. Returns `None` if it isn't a valid path
+    pub fn parse(path: &[u8]) -> Option<Self> {
+        let path = str::from_utf8(path).ok()?.strip_prefix('$')?;
+        let mut steps = Vec::new();
+        let mut rest = path;
+        while !rest.is_empty() {
+            if let Some(after) = rest.strip_prefix('.') {
+                let end = after.find(['.', '[']).unwrap_or(after.len());
+                if end == 0 {
+                    return None;
+                }
+                steps.push(Step::Field(after[..end].to_owned()));
+                rest = &after[end..];
+            } else if let Some(after) = rest.strip_prefix('[') {
+                let end = after.find(']')?;
+                let index = &after[..end];
+                if index.is_empty() || !index.bytes().all(|b| b.is_ascii_digit()) {
+                    return None;
+                }
+                steps.push(Step::Index(index.parse().ok()?));
+                rest = &after[end + 1..];
+            } else {
+                return None;
+            }
+        }
+        Some(Self { steps })
+    }
+    /// Returns true if this is the path of the document itself (`$`)
+    pub fn is_root(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+/// A JSON parser
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).copied()
+    }
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+    fn eat(&mut self, byte: u8) -> bool {
+        self.skip_whitespace();
+        let found = self.peek() == Some(byte);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+    fn eat_literal(&mut self, literal: &str, value: Json) -> Option<Json> {
+        if self.text[self.pos..].starts_with(literal) {
+            self.pos += literal.len();
+            Some(value)
+        } else {
+            None
+        }
+    }
+    /// Parse a value nested `depth` levels deep
+    fn parse_value(&mut self, depth: usize) -> Option<Json> {
+        self.skip_whitespace();
+        match self.peek()? {
+            b'[' | b'{' if depth == MAX_DEPTH => None,
+            b'n' => self.eat_literal("null", Json::Null),
+            b't' => self.eat_literal("true", Json::Bool(true)),
+            b'f' => self.eat_literal("false", Json::Bool(false)),
+            b'"' => self.parse_string().map(Json::Str),
+            b'-' | b'0'..=b'9' => self.parse_number(),
+            b'[' => {
+                self.pos += 1;
+                let mut elements = Vec::new();
+                if self.eat(b']') {
+                    return Some(Json::Array(elements));
+                }
+                loop {
+                    elements.push(self.parse_value(depth + 1)?);
+                    if self.eat(b']') {
+                        return Some(Json::Array(elements));
+                    }
+                    if !self.eat(b',') {
+                        return None;
+                    }
+                }
+            }
+            b'{' => {
+                self.pos += 1;
+                let mut fields: Vec<(String, Json)> = Vec::new();
+                if self.eat(b'}') {
+                    return Some(Json::Object(fields));
+                }
+                loop {
+                    self.skip_whitespace();
+                    if self.peek() != Some(b'"') {
+                        return None;
+                    }
+                    let name = self.parse_string()?;
+                    if !self.eat(b':') || fields.iter().any(|(f, _)| *f == name) {
+                        return None;
+                    }
+                    fields.push((name, self.parse_value(depth + 1)?));
+                    if self.eat(b'}') {
+                        return Some(Json::Object(fields));
+                    }
+                    if !self.eat(b',') {
+                        return None;
+                    }
+                }
+            }
+            _ => None,
+        }
+    }
+    /// Parse a number: `-?(0|[1-9][0-9]*)(.[0-9]+)?([eE][+-]?[0-9]+)?`
+    fn parse_number(&mut self) -> Option<Json> {
+        let start = self.pos;
+        let bytes = self.text.as_bytes();
+        let digits = |pos: &mut usize| {
+            let from = *pos;
+            while bytes.get(*pos).is_some_and(u8::is_ascii_digit) {
+                *pos += 1;
+            }
+            *pos - from
+        };
+        let mut pos = self.pos;
+        if bytes[pos] == b'-' {
+            pos += 1;
+        }
+        match digits(&mut pos) {
+            0 => return None,
+            n if n > 1 && bytes[pos - n] == b'0' => return None,
+            _ => {}
+        }
+        if bytes.get(pos) == Some(&b'.') {
+            pos += 1;
+            if digits(&mut pos) == 0 {
+                return None;
+            }
+        }
+        if matches!(bytes.get(pos), Some(b'e' | b'E')) {
+            pos += 1;
+            if matches!(bytes.get(pos), Some(b'+' | b'-')) {
+                pos += 1;
+            }
+            if digits(&mut pos) == 0 {
+                return None;
+            }
+        }
+        self.pos = pos;
+        let number: f64 = self.text[start..pos].parse().ok()?;
+        number.is_finite().then_some(Json::Number(number))
+    }
+    /// Parse a string, starting at its opening quote
+    fn parse_string(&mut self) -> Option<String> {
+        self.pos += 1;
+        let mut out = String::new();
+        loop {
+            let rest = &self.text[self.pos..];
+            let end = rest.find(['"', '\\'])?;
+            if rest[..end].bytes().any(|b| b < 0x20) {
+                // control characters have to be escaped
+                return None;
+            }
+            out.push_str(&rest[..end]);
+            self.pos += end + 1;
+            if rest.as_bytes()[end] == b'"' {
+                return Some(out);
+            }
+            let escaped = match self.peek()? {
+                b'"' => '"',
+                b'\\' => '\\',
+                b'/' => '/',
+                b'b' => '\u{8}',
+                b'f' => '\u{c}',
+                b'n' => '\n',
+                b'r' => '\r',
+                b't' => '\t',
+                b'u' => {
+                    self.pos += 1;
+                    let high = self.parse_hex4()?;
+                    let c = if (0xD800..0xDC00).contains(&high) {
+                        // a surrogate pair
+                        if !self.text[self.pos..].starts_with("\\u") {
+                            return None;
+                        }
+                        self.pos += 2;
+                        let low = self.parse_hex4()?;
+                        if !(0xDC00..0xE000).contains(&low) {
+                            return None;
+                        }
+                        char::from_u32(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+                    } else {
+                        char::from_u32(high)
+                    };
+                    out.push(c?);
+                    continue;
+                }
+                _ => return None,
+            };
+            out.push(escaped);
+            self.pos += 1;
+        }
+    }
+    fn parse_hex4(&mut self) -> Option<u32> {
+        let hex = self
+            .text
+            .get(self.pos..self.pos + 4)
+            .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+            .and_then(|hex| u32::from_str_radix(hex, 16).ok())?;
+        self.pos += 4;
+        Some(hex)
+    }
+}
+
+/// A decoder for the binary encoding
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn next_byte(&mut self) -> Option<u8> {
+        let byte = *self.bytes.get(self.pos)?;
+        self.pos += 1;
+        Some(byte)
+    }
+    fn next_slice(&mut self, len: usize) -> Option<&'a [u8]> {
+        let slice = self.bytes.get(self.pos..self.pos.checked_add(len)?)?;
+        self.pos += len;
+        Some(slice)
+    }
+    fn next_varint(&mut self) -> Option<usize> {
+        let mut n: u64 = 0;
+        for shift in (0..64).step_by(7) {
+            let byte = self.next_byte()?;
+            n |= ((byte & 0x7F) as u64) << shift;
+            if byte & 0x80 == 0 {
+                return usize::try_from(n).ok();
+            }
+        }
+        None
+    }
+    fn next_string(&mut self) -> Option<String> {
+        let len = self.next_varint()?;
+        let bytes = self.next_slice(len)?;
+        str::from_utf8(bytes).ok().map(ToOwned::to_owned)
+    }
+    /// Decode a value nested `depth` levels deep
+    fn decode_value(&mut self, depth: usize) -> Option<Json> {
+        let value = match self.next_byte()? {
+            TAG_ARRAY | TAG_OBJECT if depth == MAX_DEPTH => return None,
+            TAG_NULL => Json::Null,
+            TAG_FALSE => Json::Bool(false),
+            TAG_TRUE => Json::Bool(true),
+            TAG_NUMBER => {
+                let bits = self.next_slice(8)?.try_into().ok()?;
+                let number = f64::from_bits(u64::from_le_bytes(bits));
+                if !number.is_finite() {
+                    return None;
+                }
+                Json::Number(number)
+            }
+            TAG_STR => Json::Str(self.next_string()?),
+            TAG_ARRAY => {
+                let len = self.next_varint()?;
+                // every element takes at least a byte, so don't trust a larger length
+                let mut elements = Vec::with_capacity(len.min(self.bytes.len() - self.pos));
+                for _ in 0..len {
+                    elements.push(self.decode_value(depth + 1)?);
+                }
+                Json::Array(elements)
+            }
+            TAG_OBJECT => {
+                let len = self.next_varint()?;
+                let mut fields = Vec::with_capacity(len.min(self.bytes.len() - self.pos));
+                for _ in 0..len {
+                    let name = self.next_string()?;
+                    fields.push((name, self.decode_value(depth + 1)?));
+                }
+                Json::Object(fields)
+            }
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+#[test]
+fn test_parse_and_render() {
+    let doc = br#" {"name": "sky", "tags": ["db", "nosql"], "stars": 1.5e3, "ok": true,
+        "none": null, "esc": "a\"b\u00e9\ud83d\ude00\n"} "#;
+    let doc = Json::parse(doc).unwrap();
+    assert_eq!(
+        doc.render(),
+        "{\"name\":\"sky\",\"tags\":[\"db\",\"nosql\"],\"stars\":1500,\"ok\":true,\"none\":null,\
+         \"esc\":\"a\\\"bé😀\\n\"}"
+    );
+    for bad in [
+        &b"{\"a\":1,}"[..],
+        b"[1 2]",
+        b"{\"a\":1,\"a\":2}",
+        b"01",
+        b"1.",
+        b"1e999",
+        b"nul",
+        b"\"\x01\"",
+        b"\"\\ud83d\"",
+        b"[] []",
+        b"\xFF",
+    ] {
+        assert_eq!(Json::parse(bad), None, "{}", String::from_utf8_lossy(bad));
+    }
+    let deep = "[".repeat(MAX_DEPTH + 1) + &"]".repeat(MAX_DEPTH + 1);
+    assert_eq!(Json::parse(deep.as_bytes()), None);
+    let deep = "[".repeat(MAX_DEPTH) + &"]".repeat(MAX_DEPTH);
+    assert_eq!(Json::parse(deep.as_bytes()).unwrap().depth(), MAX_DEPTH);
+}
+
+#[test]
+fn test_paths() {
+    let mut doc = Json::parse(br#"{"a":{"b":[1,{"c":2}]}}"#).unwrap();
+    let path = |p: &str| Path::parse(p.as_bytes()).unwrap();
+    assert_eq!(doc.get(&path("$.a.b[1].c")), Some(&Json::Number(2.0)));
+    assert_eq!(doc.get(&path("$.a.b[2]")), None);
+    assert_eq!(doc.get(&path("$.a[0]")), None);
+    assert_eq!(doc.get(&path("$")), Some(&doc.clone()));
+    for bad in ["", "a", "$.", "$..a", "$[", "$[x]", "$[-1]", "$a"] {
+        assert_eq!(Path::parse(bad.as_bytes()), None, "{bad}");
+    }
+    // replace, add a field and append an element
+    let old = doc.set(&path("$.a.b[0]"), Json::Bool(true)).unwrap();
+    assert_eq!(old, Some(Json::Number(1.0)));
+    assert_eq!(doc.set(&path("$.a.d"), Json::Null), Ok(None));
+    assert_eq!(doc.set(&path("$.a.b[2]"), Json::Str("x".into())), Ok(None));
+    assert_eq!(doc.render(), r#"{"a":{"b":[true,{"c":2},"x"],"d":null}}"#);
+    // the parent has to exist
+    assert_eq!(doc.set(&path("$.x.y"), Json::Null), Err(SetError::NoParent));
+    assert_eq!(
+        doc.set(&path("$.a.b[4]"), Json::Null),
+        Err(SetError::NoParent)
+    );
+    let deep = Json::parse(("[".repeat(MAX_DEPTH) + &"]".repeat(MAX_DEPTH)).as_bytes()).unwrap();
+    assert_eq!(doc.set(&path("$.a.d"), deep), Err(SetError::TooDeep));
+}
+
+#[test]
+fn test_binary_encoding() {
+    let doc = r#"{"s":"hé","n":-0.25,"a":[null,true,false,[],{}]}"#;
+    let doc = Json::parse(doc.as_bytes()).unwrap();
+    let mut encoded = Vec::new();
+    doc.encode(&mut encoded);
+    assert_eq!(Json::decode(&encoded).unwrap(), doc);
+    // truncated, trailing data and bad tags are all rejected
+    assert_eq!(Json::decode(&encoded[..encoded.len() - 1]), None);
+    encoded.push(TAG_NULL);
+    assert_eq!(Json::decode(&encoded), None);
+    assert_eq!(Json::decode(&[7]), None);
+    // lengths don't have to be trusted
+    assert_eq!(
+        Json::decode(&[TAG_ARRAY, 0xFF, 0xFF, 0xFF, 0xFF, 0x0F]),
+        None
+    );
+}
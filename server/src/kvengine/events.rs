@@ -0,0 +1,181 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Keyspace events
+//!
+//! An opt-in set of events that a table publishes (see [`pubsub`]) whenever its data changes,
+//! so that applications can react to changes without polling. The events of a table are
+//! published on the `__keyevent__:<space>:<model>:<event>` channels, with the key (or, for
+//! [`KeyEvent::Drop`], the name of the model) as the message. When no event is enabled, the only
+//! cost is a single atomic load per change.
+
+use {
+    crate::{corestore::memstore::ObjectID, dbnet::pubsub, util::compiler},
+    core::sync::atomic::{AtomicU8, Ordering},
+    parking_lot::RwLock,
+};
+
+/// The prefix of the channels that events are published on
+pub const CHANNEL_PREFIX: &[u8] = b"__keyevent__";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+/// An event that a table can publish
+pub enum KeyEvent {
+    /// a key was set (or its value was changed)
+    Set = 1,
+    /// a key was deleted (or evicted)
+    Del = 2,
+    /// a key was removed because it expired
+    Expired = 4,
+    /// the table was dropped
+    Drop = 8,
+}
+
+impl KeyEvent {
+    /// The mask with every event enabled
+    pub const ALL: u8 = 0b1111;
+    /// Returns the event with the given name
+    pub fn from_name(name: &[u8]) -> Option<Self> {
+        match name {
+            b"set" => Some(Self::Set),
+            b"del" => Some(Self::Del),
+            b"expired" => Some(Self::Expired),
+            b"drop" => Some(Self::Drop),
+            _ => None,
+        }
+    }
+    /// Returns the name of the event, which is also the last segment of its channel
+    pub const fn name(&self) -> &'static [u8] {
+        match self {
+            Self::Set => b"set",
+            Self::Del => b"del",
+            Self::Expired => b"expired",
+            Self::Drop => b"drop",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+/// The events enabled on a table
+pub struct KeyEvents {
+    /// the enabled events ([`KeyEvent`]s or-ed together). zero if events are off
+    mask: AtomicU8,
+    /// the space and model that the events are published for (set when events are enabled,
+    /// and kept up to date across renames)
+    entity: RwLock<Option<(ObjectID, ObjectID)>>,
+}
+
+impl KeyEvents {
+    /// Publish the events in `mask` for the model `tblid` of the space `ksid`. A mask of zero
+    /// turns events off
+    pub fn set_mask(&self, ksid: ObjectID, tblid: ObjectID, mask: u8) {
+        *self.entity.write() = Some((ksid, tblid));
+        self.mask.store(mask, Ordering::Release);
+    }
+    /// Returns the enabled events
+    pub fn mask(&self) -> u8 {
+        self.mask.load(Ordering::Acquire)
+    }
+    #[inline(always)]
+    /// Returns true if `event` is enabled
+    pub fn is_enabled(&self, event: KeyEvent) -> bool {
+        self.mask.load(Ordering::Relaxed) & event as u8 != 0
+    }
+    /// Record that the model was renamed to `tblid`
+    pub fn renamed_table(&self, tblid: &ObjectID) {
+        if let Some((_, table)) = self.entity.write().as_mut() {
+            *table = tblid.clone();
+        }
+    }
+    /// Record that the space of the model was renamed to `ksid`
+    pub fn renamed_keyspace(&self, ksid: &ObjectID) {
+        if let Some((keyspace, _)) = self.entity.write().as_mut() {
+            *keyspace = ksid.clone();
+        }
+    }
+    /// Returns the channel that `event` is published on
+    pub fn channel(&self, event: KeyEvent) -> Option<Vec<u8>> {
+        let entity = self.entity.read();
+        let (ksid, tblid) = entity.as_ref()?;
+        let mut channel = CHANNEL_PREFIX.to_owned();
+        for segment in [ksid.as_ref(), tblid.as_ref(), event.name()] {
+            channel.push(b':');
+            channel.extend_from_slice(segment);
+        }
+        Some(channel)
+    }
+    #[inline(always)]
+    /// Publish `event` for `key` if it's enabled
+    pub fn notify(&self, event: KeyEvent, key: &[u8]) {
+        if compiler::unlikely(self.is_enabled(event)) {
+            self.publish(event, key)
+        }
+    }
+    /// Returns the channel and the message of [`KeyEvent::Drop`] if it's enabled. The model is
+    /// usually gone by the time the event can be published, so this has to be called before the
+    /// model is dropped (and the event published with [`pubsub::publish`] once it's dropped)
+    pub fn drop_event(&self) -> Option<(Vec<u8>, ObjectID)> {
+        if !self.is_enabled(KeyEvent::Drop) {
+            return None;
+        }
+        let channel = self.channel(KeyEvent::Drop)?;
+        let table = self.entity.read().as_ref()?.1.clone();
+        Some((channel, table))
+    }
+    fn publish(&self, event: KeyEvent, key: &[u8]) {
+        if let Some(channel) = self.channel(event) {
+            pubsub::publish(&channel, key);
+        }
+    }
+}
+
+#[test]
+fn test_key_events() {
+    let (mut subscriber, mut rx) = pubsub::Subscriber::new();
+    subscriber.psubscribe(b"__keyevent__:eventks:*");
+    let events = KeyEvents::default();
+    // nothing is published until events are enabled
+    events.notify(KeyEvent::Set, b"sayan");
+    assert!(rx.try_recv().is_err());
+    let (ksid, tblid) = unsafe {
+        (
+            ObjectID::from_slice("eventks"),
+            ObjectID::from_slice("eventtbl"),
+        )
+    };
+    events.set_mask(ksid, tblid, KeyEvent::Set as u8 | KeyEvent::Drop as u8);
+    events.notify(KeyEvent::Set, b"sayan");
+    events.notify(KeyEvent::Del, b"sayan");
+    let message = rx.try_recv().unwrap();
+    assert_eq!(message.channel, "__keyevent__:eventks:eventtbl:set");
+    assert_eq!(message.payload, "sayan");
+    assert!(rx.try_recv().is_err());
+    events.renamed_table(&unsafe { ObjectID::from_slice("eventtbl2") });
+    let (channel, table) = events.drop_event().unwrap();
+    assert_eq!(channel, b"__keyevent__:eventks:eventtbl2:drop");
+    assert_eq!(table.as_ref(), b"eventtbl2");
+}
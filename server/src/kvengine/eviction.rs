@@ -0,0 +1,368 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Memory limits and eviction
+//!
+//! When `maxmemory` is set, every table keeps a running estimate of the memory taken by its keys
+//! and values (with a fixed overhead for every entry), and the estimates of all the tables add up
+//! to the node's usage. Once the usage hits the limit, writes that allocate either evict keys to
+//! make room or are rejected, depending on the eviction policy.
+//!
+//! Like Redis, eviction is approximate: we sample a handful of keys (from a different shard each
+//! time) and evict the one that was least recently (LRU) or least frequently (LFU) used. The LRU
+//! and LFU policies keep an access stamp for every key; LFU hit counts are halved for every
+//! minute that a key sits idle, so that keys that used to be hot eventually become evictable.
+//! Archived values, expiry deadlines and the stamps themselves aren't counted.
+//!
+//! The same estimates are counted towards the quota of the keyspace that a table is in (see
+//! [`quota`](super::quota)) and reported by `SYS MEMORY`, whether or not there's a memory limit.
+
+use {
+    super::{expiry, quota::Quota},
+    crate::{
+        config::EvictionPolicy,
+        corestore::{htable::Coremap, SharedSlice},
+    },
+    core::sync::atomic::{AtomicU32, AtomicU64, AtomicU8, AtomicUsize, Ordering},
+    std::sync::{Arc, OnceLock},
+};
+
+/// The approximate overhead of an entry (its slot in the table and the headers of its key and
+/// value)
+pub const ENTRY_OVERHEAD: usize = 64;
+/// The approximate overhead of a list element
+pub const ELEMENT_OVERHEAD: usize = 16;
+/// The number of keys sampled for every eviction
+pub const EVICTION_SAMPLES: usize = 5;
+/// The maximum number of keys evicted to make room for a single write
+pub const MAX_EVICTIONS_PER_WRITE: usize = 128;
+/// LFU hit counts are halved after every period of this many milliseconds without a hit
+const LFU_DECAY_MS: u64 = 60_000;
+
+/// The memory limit in bytes. Zero if there is no limit
+static MAXMEMORY: AtomicU64 = AtomicU64::new(0);
+/// The eviction policy
+static POLICY: AtomicU8 = AtomicU8::new(EvictionPolicy::default() as u8);
+/// The approximate memory used across all tables (only maintained if there's a limit)
+static USED: AtomicU64 = AtomicU64::new(0);
+
+/// Set the memory limit (zero for no limit) and the eviction policy. This must be called before
+/// any table is loaded
+pub fn init(maxmemory: u64, policy: EvictionPolicy) {
+    MAXMEMORY.store(maxmemory, Ordering::Release);
+    POLICY.store(policy as u8, Ordering::Release);
+}
+
+#[inline(always)]
+/// Returns true if there's a memory limit (and usage is being accounted for)
+pub fn is_enabled() -> bool {
+    MAXMEMORY.load(Ordering::Relaxed) != 0
+}
+
+/// Returns true if access stamps are being kept
+fn is_tracking() -> bool {
+    is_enabled() && policy() != EvictionPolicy::Reject
+}
+
+/// Returns the eviction policy
+pub fn policy() -> EvictionPolicy {
+    EvictionPolicy::from_u8(POLICY.load(Ordering::Relaxed))
+}
+
+/// Returns the approximate memory used across all tables. Zero if there is no limit
+pub fn used() -> u64 {
+    USED.load(Ordering::Relaxed)
+}
+
+#[inline(always)]
+/// Returns true if there's a memory limit and it has been hit
+pub fn is_over_limit() -> bool {
+    let maxmemory = MAXMEMORY.load(Ordering::Relaxed);
+    maxmemory != 0 && used() >= maxmemory
+}
+
+/// Returns the approximate size of a list element
+pub fn element_size(element: &[u8]) -> usize {
+    element.len() + ELEMENT_OVERHEAD
+}
+
+/// Returns the approximate size of a map field along with its value
+pub fn field_size(field: &[u8], value: &[u8]) -> usize {
+    field.len() + value.len() + ELEMENT_OVERHEAD
+}
+
+/// Returns the approximate size of a sorted set member along with its score
+pub fn scored_size(member: &[u8]) -> usize {
+    member.len() + core::mem::size_of::<f64>() + ELEMENT_OVERHEAD
+}
+
+/// Returns the approximate size of an entry with the given key and a value of `value_size` bytes
+pub fn entry_size(key: &[u8], value_size: usize) -> usize {
+    key.len() + value_size + ENTRY_OVERHEAD
+}
+
+pub(super) fn saturating_release(counter: &AtomicU64, bytes: u64) {
+    // the estimates can drift (for example, if a list is changed in place), so never underflow
+    let _ = counter.fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
+        Some(current.saturating_sub(bytes))
+    });
+}
+
+/// Returns `hits` after decaying it for the time elapsed between `last` and `now`
+fn decayed(hits: u32, last: u64, now: u64) -> u32 {
+    let periods = now.saturating_sub(last) / LFU_DECAY_MS;
+    if periods >= u32::BITS as u64 {
+        0
+    } else {
+        hits >> periods
+    }
+}
+
+#[derive(Debug)]
+/// The access stamp of a key
+struct Stamp {
+    /// the time of the last access (milliseconds since the epoch)
+    last: AtomicU64,
+    /// the (decaying) number of hits
+    hits: AtomicU32,
+}
+
+impl Stamp {
+    fn new(now: u64) -> Self {
+        Self {
+            last: AtomicU64::new(now),
+            hits: AtomicU32::new(1),
+        }
+    }
+    fn hit(&self, now: u64) {
+        // racing hits may be lost, which is fine for an estimate
+        let last = self.last.swap(now, Ordering::Relaxed);
+        let hits = decayed(self.hits.load(Ordering::Relaxed), last, now);
+        self.hits.store(hits.saturating_add(1), Ordering::Relaxed);
+    }
+    /// Returns the eviction score of the key: the key with the lowest score is evicted first
+    fn score(&self, policy: EvictionPolicy, now: u64) -> (u64, u64) {
+        let last = self.last.load(Ordering::Relaxed);
+        match policy {
+            EvictionPolicy::Lfu => (
+                decayed(self.hits.load(Ordering::Relaxed), last, now) as u64,
+                last,
+            ),
+            _ => (last, 0),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+/// The memory accounting and access stamps of a table
+pub struct MemoryTracker {
+    /// the approximate memory taken by the table's entries
+    bytes: AtomicU64,
+    /// the number of entries in the table
+    entries: AtomicU64,
+    /// the quota of the keyspace that the table is in, once it has been added to one
+    quota: OnceLock<Arc<Quota>>,
+    /// the access stamps (only kept by the LRU and LFU policies)
+    stamps: Coremap<SharedSlice, Stamp>,
+    /// the number of evictions so far, used to pick the next shard to sample
+    cursor: AtomicUsize,
+}
+
+impl MemoryTracker {
+    /// Account for the `entries` entries (taking `bytes` bytes) that the table was loaded with
+    pub fn loaded(&self, entries: usize, bytes: usize) {
+        self.entries.fetch_add(entries as u64, Ordering::AcqRel);
+        self.charge(bytes);
+    }
+    /// Returns the approximate memory (in bytes) taken by the table's entries
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Acquire)
+    }
+    /// Count the table towards `quota` from now on. This is a no-op if the table already counts
+    /// towards a quota
+    pub fn attach_quota(&self, quota: Arc<Quota>) {
+        if self.quota.set(quota).is_ok() {
+            self.with_quota(|quota| {
+                quota.charge(
+                    self.entries.load(Ordering::Acquire),
+                    self.bytes.load(Ordering::Acquire),
+                )
+            });
+        }
+    }
+    /// Returns true if the table counts towards a quota that has been hit
+    pub fn is_over_quota(&self) -> bool {
+        self.quota
+            .get()
+            .map(|quota| quota.is_over_limit())
+            .unwrap_or(false)
+    }
+    #[inline(always)]
+    fn with_quota(&self, f: impl FnOnce(&Quota)) {
+        if let Some(quota) = self.quota.get() {
+            f(quota)
+        }
+    }
+    /// Account for `bytes` more memory
+    pub fn charge(&self, bytes: usize) {
+        self.bytes.fetch_add(bytes as u64, Ordering::AcqRel);
+        if is_enabled() {
+            USED.fetch_add(bytes as u64, Ordering::AcqRel);
+        }
+        self.with_quota(|quota| quota.charge(0, bytes as u64));
+    }
+    /// Account for `bytes` less memory
+    pub fn release(&self, bytes: usize) {
+        saturating_release(&self.bytes, bytes as u64);
+        if is_enabled() {
+            saturating_release(&USED, bytes as u64);
+        }
+        self.with_quota(|quota| quota.release(0, bytes as u64));
+    }
+    /// Account for a value that changed from `old` to `new` bytes
+    pub fn resize(&self, old: usize, new: usize) {
+        self.charge(new);
+        self.release(old);
+    }
+    /// Account for a new entry of `bytes` bytes for `key`
+    pub fn inserted(&self, key: &SharedSlice, bytes: usize) {
+        self.charge(bytes);
+        self.entries.fetch_add(1, Ordering::AcqRel);
+        self.with_quota(|quota| quota.charge(1, 0));
+        if is_tracking() {
+            self.stamp(key, expiry::now_ms());
+        }
+    }
+    /// Account for the removal of the entry of `bytes` bytes for `key`
+    pub fn removed(&self, key: &[u8], bytes: usize) {
+        self.release(bytes);
+        saturating_release(&self.entries, 1);
+        self.with_quota(|quota| quota.release(1, 0));
+        if is_tracking() {
+            self.stamps.remove(key);
+        }
+    }
+    /// Account for the removal of every entry
+    pub fn clear(&self) {
+        self.release_all();
+        self.stamps.clear();
+    }
+    /// Stop counting the table's entries towards the node's usage and its quota
+    fn release_all(&self) {
+        let bytes = self.bytes.swap(0, Ordering::AcqRel);
+        let entries = self.entries.swap(0, Ordering::AcqRel);
+        if is_enabled() {
+            saturating_release(&USED, bytes);
+        }
+        self.with_quota(|quota| quota.release(entries, bytes));
+    }
+    #[inline(always)]
+    /// Record an access to `key` (if it exists in `data`)
+    pub fn touch<T>(&self, data: &Coremap<SharedSlice, T>, key: &[u8]) {
+        if is_tracking() {
+            let now = expiry::now_ms();
+            // keys loaded from disk only get a stamp once they're accessed
+            if !self.restamp(key, now) && data.contains_key(key) {
+                self.stamps.upsert(key.into(), Stamp::new(now));
+            }
+        }
+    }
+    /// Record a hit on `key` if it has a stamp. Returns false if it doesn't
+    fn restamp(&self, key: &[u8], now: u64) -> bool {
+        self.stamps.get(key).map(|stamp| stamp.hit(now)).is_some()
+    }
+    fn stamp(&self, key: &SharedSlice, now: u64) {
+        if !self.restamp(key, now) {
+            self.stamps.upsert(key.clone(), Stamp::new(now));
+        }
+    }
+    /// Returns the shard that the next eviction should sample, out of `shard_count` shards
+    pub fn next_shard(&self, shard_count: usize) -> usize {
+        self.cursor.fetch_add(1, Ordering::Relaxed) % shard_count.max(1)
+    }
+    /// Pick the key to evict out of `sample`, as per `policy`. Keys without a stamp are picked
+    /// first
+    pub fn pick_victim(
+        &self,
+        sample: Vec<SharedSlice>,
+        policy: EvictionPolicy,
+        now: u64,
+    ) -> Option<SharedSlice> {
+        sample.into_iter().min_by_key(|key| {
+            self.stamps
+                .get(key.as_slice())
+                .map(|stamp| stamp.score(policy, now))
+                .unwrap_or((0, 0))
+        })
+    }
+}
+
+impl Drop for MemoryTracker {
+    fn drop(&mut self) {
+        self.release_all();
+    }
+}
+
+#[test]
+fn test_pick_victim() {
+    let tracker = MemoryTracker::default();
+    let (a, b, c): (SharedSlice, SharedSlice, SharedSlice) = ("a".into(), "b".into(), "c".into());
+    tracker.stamp(&a, 1_000);
+    tracker.stamp(&a, 1_100);
+    tracker.stamp(&a, 1_200);
+    tracker.stamp(&b, 2_000);
+    let sample = || vec![a.clone(), b.clone()];
+    // a was used least recently, but b was used least frequently
+    assert_eq!(
+        tracker.pick_victim(sample(), EvictionPolicy::Lru, 3_000),
+        Some(a.clone())
+    );
+    assert_eq!(
+        tracker.pick_victim(sample(), EvictionPolicy::Lfu, 3_000),
+        Some(b.clone())
+    );
+    // after two idle minutes, the hits of a have decayed
+    assert_eq!(
+        tracker.pick_victim(sample(), EvictionPolicy::Lfu, 1_200 + 2 * LFU_DECAY_MS),
+        Some(a.clone())
+    );
+    // keys without a stamp go first
+    assert_eq!(
+        tracker.pick_victim(vec![a.clone(), c.clone()], EvictionPolicy::Lru, 3_000),
+        Some(c)
+    );
+    assert_eq!(
+        tracker.pick_victim(vec![], EvictionPolicy::Lru, 3_000),
+        None
+    );
+}
+
+#[test]
+fn test_decayed() {
+    assert_eq!(decayed(8, 0, LFU_DECAY_MS - 1), 8);
+    assert_eq!(decayed(8, 0, 2 * LFU_DECAY_MS), 2);
+    assert_eq!(decayed(8, 0, u64::MAX), 0);
+}
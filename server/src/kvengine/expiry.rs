@@ -0,0 +1,147 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Key expiry
+//!
+//! Keys can be given a time to live with `EXPIRE`. Their deadlines (in milliseconds since the
+//! UNIX epoch) are kept in an [`ExpiryIndex`] next to the table's data and are flushed along with
+//! it. An expired key is removed the next time it is accessed, and the expiry service
+//! periodically sweeps every table for expired keys that nobody accessed. Any write that stores
+//! a new value for a key (or removes it) clears its deadline.
+
+use {
+    crate::corestore::{htable::Coremap, SharedSlice},
+    core::sync::atomic::{AtomicUsize, Ordering},
+    std::time::{SystemTime, UNIX_EPOCH},
+};
+
+/// Returns the number of milliseconds elapsed since the UNIX epoch
+pub fn now_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[derive(Debug, Default)]
+/// The deadlines of the keys in a table that have a time to live
+///
+/// A deadline can outlive its key if the key is removed while `EXPIRE` runs, so a write that
+/// creates a key clears any deadline left behind for it. This is done with the new entry locked,
+/// so that an `EXPIRE` on the new key can't be lost
+pub struct ExpiryIndex {
+    deadlines: Coremap<SharedSlice, u64>,
+    /// the number of deadlines, so that tables without any don't have to look them up
+    count: AtomicUsize,
+}
+
+impl ExpiryIndex {
+    /// Create an index with the given deadlines
+    pub fn new(deadlines: Coremap<SharedSlice, u64>) -> Self {
+        let count = AtomicUsize::new(deadlines.len());
+        Self { deadlines, count }
+    }
+    /// Returns true if no key has a deadline
+    pub fn is_empty(&self) -> bool {
+        self.count.load(Ordering::Acquire) == 0
+    }
+    /// Returns a reference to the deadlines
+    pub fn deadlines(&self) -> &Coremap<SharedSlice, u64> {
+        &self.deadlines
+    }
+    /// Set the deadline of `key`
+    pub fn set(&self, key: SharedSlice, deadline: u64) {
+        if self.deadlines.true_if_insert(key.clone(), deadline) {
+            self.count.fetch_add(1, Ordering::Release);
+        } else {
+            self.deadlines.upsert(key, deadline);
+        }
+    }
+    /// Returns the deadline of `key`, if it has one
+    pub fn get(&self, key: &[u8]) -> Option<u64> {
+        if self.is_empty() {
+            return None;
+        }
+        self.deadlines.get(key).map(|deadline| *deadline)
+    }
+    /// Returns true if `key` has a deadline and it has passed at `now`
+    pub fn is_due(&self, key: &[u8], now: u64) -> bool {
+        self.get(key).is_some_and(|deadline| deadline <= now)
+    }
+    /// Remove the deadline of `key`. Returns true if it had one
+    pub fn remove(&self, key: &[u8]) -> bool {
+        let removed = !self.is_empty() && self.deadlines.true_if_removed(key);
+        if removed {
+            self.count.fetch_sub(1, Ordering::Release);
+        }
+        removed
+    }
+    /// Remove the deadline of `key` if it has passed at `now`. Returns true if it was removed
+    pub fn remove_if_due(&self, key: &[u8], now: u64) -> bool {
+        let removed = !self.is_empty()
+            && self
+                .deadlines
+                .remove_if(key, |_, deadline| *deadline <= now)
+                .is_some();
+        if removed {
+            self.count.fetch_sub(1, Ordering::Release);
+        }
+        removed
+    }
+    /// Returns up to `limit` keys whose deadlines have passed at `now`
+    pub fn due(&self, now: u64, limit: usize) -> Vec<SharedSlice> {
+        if self.is_empty() {
+            return Vec::new();
+        }
+        self.deadlines
+            .iter()
+            .filter(|kv| *kv.value() <= now)
+            .take(limit)
+            .map(|kv| kv.key().clone())
+            .collect()
+    }
+    /// Remove every deadline
+    pub fn clear(&self) {
+        self.deadlines.clear();
+        self.count.store(0, Ordering::Release);
+    }
+}
+
+#[test]
+fn test_expiry_index() {
+    let index = ExpiryIndex::default();
+    assert!(index.is_empty());
+    index.set("a".into(), 100);
+    index.set("b".into(), 200);
+    index.set("a".into(), 150);
+    assert_eq!(index.get(b"a"), Some(150));
+    assert_eq!(index.due(160, 10), [SharedSlice::from("a")]);
+    assert!(!index.remove_if_due(b"b", 160));
+    assert!(index.remove_if_due(b"a", 160));
+    assert!(!index.remove(b"a"));
+    assert!(index.remove(b"b"));
+    assert!(index.is_empty());
+}
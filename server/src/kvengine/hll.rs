@@ -0,0 +1,285 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # HyperLogLog sketches
+//!
+//! A sketch approximates the number of distinct elements that were added to it (with a standard
+//! error of about 0.81%) in at most 12KB, however many elements there are. Sketches are stored
+//! as plain values of key/value tables, so they're persisted like any other value. The
+//! serialized form starts with [`MAGIC`] and an encoding byte:
+//! - [`SPARSE`]: the registers that aren't zero, as `(index: u16 BE, rank: u8)` triples in
+//!   ascending order of index. Most sketches of small sets take a few hundred bytes
+//! - [`DENSE`]: all the registers, packed six bits apiece (least significant bits first)
+//!
+//! A sketch switches to the dense encoding once that's smaller, and never switches back.
+//! Elements are hashed with MurmurHash64A, which (unlike the hasher of our maps) gives the same
+//! hash for an element across restarts and releases, as it must for sketches on disk.
+
+/// The seed for the hash of the elements
+const SEED: u64 = 0xADC8_3B19;
+/// The number of bits of the hash that pick a register
+const PRECISION: u32 = 14;
+/// The number of registers
+const REGISTERS: usize = 1 << PRECISION;
+/// The highest rank that a register can hold
+const MAX_RANK: u8 = (64 - PRECISION + 1) as u8;
+/// The number of bytes taken by the packed registers of a dense sketch
+const DENSE_LEN: usize = REGISTERS * 6 / 8;
+/// The start of every sketch
+pub const MAGIC: &[u8; 4] = b"HYLL";
+/// The encoding byte of a sparse sketch
+pub const SPARSE: u8 = 0;
+/// The encoding byte of a dense sketch
+pub const DENSE: u8 = 1;
+/// The length of the header (the magic and the encoding byte)
+const HEADER_LEN: usize = MAGIC.len() + 1;
+/// The largest serialized sketch
+pub const MAX_ENCODED_LEN: usize = HEADER_LEN + DENSE_LEN;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+/// A decoded sketch, with a byte for every register
+pub struct HyperLogLog {
+    registers: Vec<u8>,
+}
+
+impl Default for HyperLogLog {
+    fn default() -> Self {
+        Self {
+            registers: vec![0; REGISTERS],
+        }
+    }
+}
+
+impl HyperLogLog {
+    /// Decode a serialized sketch. Returns `None` if `bytes` isn't one
+    pub fn decode(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < HEADER_LEN || &bytes[..MAGIC.len()] != MAGIC {
+            return None;
+        }
+        let body = &bytes[HEADER_LEN..];
+        let mut sketch = Self::default();
+        match bytes[MAGIC.len()] {
+            SPARSE if body.len().is_multiple_of(3) => {
+                let mut next = 0;
+                for triple in body.chunks_exact(3) {
+                    let index = u16::from_be_bytes([triple[0], triple[1]]) as usize;
+                    let rank = triple[2];
+                    if index < next || index >= REGISTERS || rank == 0 || rank > MAX_RANK {
+                        return None;
+                    }
+                    sketch.registers[index] = rank;
+                    next = index + 1;
+                }
+            }
+            DENSE if body.len() == DENSE_LEN => {
+                for (index, register) in sketch.registers.iter_mut().enumerate() {
+                    let bit = index * 6;
+                    let window =
+                        body[bit / 8] as u16 | (*body.get(bit / 8 + 1).unwrap_or(&0) as u16) << 8;
+                    let rank = ((window >> (bit % 8)) & 0x3F) as u8;
+                    if rank > MAX_RANK {
+                        return None;
+                    }
+                    *register = rank;
+                }
+            }
+            _ => return None,
+        }
+        Some(sketch)
+    }
+    /// Serialize the sketch, using whichever encoding is smaller
+    pub fn encode(&self) -> Vec<u8> {
+        let used = self.registers.iter().filter(|rank| **rank != 0).count();
+        let mut bytes = Vec::with_capacity(HEADER_LEN + DENSE_LEN.min(used * 3));
+        bytes.extend_from_slice(MAGIC);
+        if used * 3 < DENSE_LEN {
+            bytes.push(SPARSE);
+            for (index, rank) in self.registers.iter().enumerate() {
+                if *rank != 0 {
+                    bytes.extend_from_slice(&(index as u16).to_be_bytes());
+                    bytes.push(*rank);
+                }
+            }
+        } else {
+            bytes.push(DENSE);
+            let mut dense = vec![0u8; DENSE_LEN];
+            for (index, rank) in self.registers.iter().enumerate() {
+                let bit = index * 6;
+                let window = (*rank as u16) << (bit % 8);
+                dense[bit / 8] |= window as u8;
+                if let Some(byte) = dense.get_mut(bit / 8 + 1) {
+                    *byte |= (window >> 8) as u8;
+                }
+            }
+            bytes.extend_from_slice(&dense);
+        }
+        bytes
+    }
+    /// Add an element. Returns true if the sketch changed
+    pub fn add(&mut self, element: &[u8]) -> bool {
+        let hash = self::murmur_hash64a(element, SEED);
+        let index = (hash & (REGISTERS as u64 - 1)) as usize;
+        // the position of the first set bit of the rest of the hash, with a sentinel bit so
+        // that a hash of all zeros still gets a rank
+        let rank = ((hash >> PRECISION) | 1 << (64 - PRECISION)).trailing_zeros() as u8 + 1;
+        if rank > self.registers[index] {
+            self.registers[index] = rank;
+            true
+        } else {
+            false
+        }
+    }
+    /// Merge `other` into this sketch, which then approximates the union of both. Returns
+    /// true if the sketch changed
+    pub fn merge(&mut self, other: &Self) -> bool {
+        let mut changed = false;
+        for (ours, theirs) in self.registers.iter_mut().zip(other.registers.iter()) {
+            if *theirs > *ours {
+                *ours = *theirs;
+                changed = true;
+            }
+        }
+        changed
+    }
+    /// Returns the approximate number of distinct elements added to the sketch
+    pub fn count(&self) -> u64 {
+        let m = REGISTERS as f64;
+        let (sum, zeros) = self
+            .registers
+            .iter()
+            .fold((0.0, 0usize), |(sum, zeros), rank| {
+                (
+                    sum + 2f64.powi(-(*rank as i32)),
+                    zeros + (*rank == 0) as usize,
+                )
+            });
+        let alpha = 0.7213 / (1.0 + 1.079 / m);
+        let estimate = alpha * m * m / sum;
+        if estimate <= 2.5 * m && zeros != 0 {
+            // linear counting is more accurate for small sets
+            (m * (m / zeros as f64).ln()).round() as u64
+        } else {
+            estimate.round() as u64
+        }
+    }
+}
+
+/// MurmurHash64A by Austin Appleby (public domain)
+fn murmur_hash64a(bytes: &[u8], seed: u64) -> u64 {
+    const M: u64 = 0xC6A4_A793_5BD1_E995;
+    const R: u32 = 47;
+    let mut h = seed ^ (bytes.len() as u64).wrapping_mul(M);
+    let blocks = bytes.chunks_exact(8);
+    let tail = blocks.remainder();
+    for block in blocks {
+        // UNWRAP: `chunks_exact` only returns chunks of eight bytes
+        let mut k = u64::from_le_bytes(block.try_into().unwrap());
+        k = k.wrapping_mul(M);
+        k ^= k >> R;
+        k = k.wrapping_mul(M);
+        h ^= k;
+        h = h.wrapping_mul(M);
+    }
+    if !tail.is_empty() {
+        for (i, byte) in tail.iter().enumerate() {
+            h ^= (*byte as u64) << (8 * i);
+        }
+        h = h.wrapping_mul(M);
+    }
+    h ^= h >> R;
+    h = h.wrapping_mul(M);
+    h ^= h >> R;
+    h
+}
+
+#[test]
+fn test_count_is_close() {
+    let mut sketch = HyperLogLog::default();
+    for i in 0..100_000u32 {
+        sketch.add(&i.to_le_bytes());
+    }
+    let count = sketch.count() as f64;
+    assert!((count - 100_000.0).abs() / 100_000.0 < 0.03, "{count}");
+    // adding the same elements again changes nothing
+    assert!(!sketch.add(&7u32.to_le_bytes()));
+    let mut small = HyperLogLog::default();
+    for i in 0..100u32 {
+        small.add(&i.to_le_bytes());
+    }
+    assert!((98..=102).contains(&small.count()));
+    assert_eq!(HyperLogLog::default().count(), 0);
+}
+
+#[test]
+fn test_encode_decode() {
+    let mut sketch = HyperLogLog::default();
+    assert_eq!(sketch.encode(), b"HYLL\x00");
+    for i in 0..100u32 {
+        sketch.add(&i.to_le_bytes());
+    }
+    let sparse = sketch.encode();
+    assert_eq!(sparse[4], SPARSE);
+    assert!(sparse.len() <= HEADER_LEN + 300);
+    assert_eq!(HyperLogLog::decode(&sparse).unwrap(), sketch);
+    for i in 0..100_000u32 {
+        sketch.add(&i.to_le_bytes());
+    }
+    let dense = sketch.encode();
+    assert_eq!(dense[4], DENSE);
+    assert_eq!(dense.len(), MAX_ENCODED_LEN);
+    assert_eq!(HyperLogLog::decode(&dense).unwrap(), sketch);
+    // anything else isn't a sketch
+    assert!(HyperLogLog::decode(b"hello").is_none());
+    assert!(HyperLogLog::decode(b"HYLL\x00\x00\x01").is_none());
+    assert!(HyperLogLog::decode(b"HYLL\x00\xFF\xFF\x01").is_none());
+    assert!(HyperLogLog::decode(b"HYLL\x00\x00\x02\x01\x00\x01\x01").is_none());
+    assert!(HyperLogLog::decode(&dense[..dense.len() - 1]).is_none());
+}
+
+#[test]
+fn test_merge() {
+    let (mut a, mut b) = (HyperLogLog::default(), HyperLogLog::default());
+    for i in 0..1000u32 {
+        a.add(&i.to_le_bytes());
+        b.add(&(i + 500).to_le_bytes());
+    }
+    assert!(a.merge(&b));
+    assert!(!a.merge(&b));
+    let count = a.count() as f64;
+    assert!((count - 1500.0).abs() / 1500.0 < 0.03, "{count}");
+}
+
+#[test]
+fn test_murmur_hash64a() {
+    // the hashes must never change, or sketches on disk would count elements twice
+    assert_eq!(murmur_hash64a(b"", 0), 0);
+    assert_eq!(murmur_hash64a(b"hello", SEED), 0x0F65_6F01_EECF_E400);
+    assert_eq!(murmur_hash64a(b"skytable", SEED), 0x92BC_C084_A596_90EF);
+    assert_eq!(
+        murmur_hash64a(b"a longer element, past one block", SEED),
+        0xEF5A_E239_0B79_83BD
+    );
+}
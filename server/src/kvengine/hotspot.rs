@@ -0,0 +1,244 @@
+/*
+ * Created on Tue Oct 18 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Hotspot sampling
+//!
+//! An opt-in collector that records which shards (and keys) of a table are being hit over a
+//! time window. It's meant for diagnosing shard imbalance, so when it isn't running the only
+//! cost is a single atomic load per access.
+
+use {
+    crate::util::{compiler, os},
+    core::sync::atomic::{AtomicU64, Ordering},
+    parking_lot::{Mutex, RwLock},
+    std::collections::HashMap,
+};
+
+/// We only look at one in every `KEY_SAMPLE_RATE` hits when counting per-key hits
+const KEY_SAMPLE_RATE: u64 = 8;
+/// The maximum number of distinct keys that we track in a window
+const MAX_TRACKED_KEYS: usize = 4096;
+/// The width of the bars in the rendered heatmap
+const HEATMAP_WIDTH: u64 = 32;
+
+#[derive(Debug, Default)]
+/// A sampler that records the shards and keys hit over a time window
+pub struct HotspotSampler {
+    /// the end of the current window (seconds since the epoch). zero if sampling is off or the
+    /// window has closed
+    deadline: AtomicU64,
+    /// the end of the current (or last) window (seconds since the epoch), kept for the summary
+    ends: AtomicU64,
+    /// the start of the current window (seconds since the epoch). zero if never started
+    started: AtomicU64,
+    /// the total number of hits in this window
+    hits: AtomicU64,
+    /// per-shard hit counts
+    shards: RwLock<Box<[AtomicU64]>>,
+    /// sampled per-key hit counts
+    keys: Mutex<HashMap<Box<[u8]>, u64>>,
+}
+
+impl HotspotSampler {
+    /// Start a new sampling window of `window` seconds over `shard_count` shards. Anything
+    /// collected in the previous window is discarded
+    pub fn start(&self, window: u64, shard_count: usize) {
+        // disable while we reset, so that no one records into stale counters
+        self.deadline.store(0, Ordering::Release);
+        *self.shards.write() = (0..shard_count).map(|_| AtomicU64::new(0)).collect();
+        self.keys.lock().clear();
+        self.hits.store(0, Ordering::Release);
+        let now = os::get_epoch_secs();
+        let ends = now.saturating_add(window.max(1));
+        self.started.store(now, Ordering::Release);
+        self.ends.store(ends, Ordering::Release);
+        self.deadline.store(ends, Ordering::Release);
+    }
+    /// Stop sampling and discard anything that was collected
+    pub fn stop(&self) {
+        self.deadline.store(0, Ordering::Release);
+        self.started.store(0, Ordering::Release);
+        self.ends.store(0, Ordering::Release);
+        *self.shards.write() = Box::new([]);
+        self.keys.lock().clear();
+    }
+    #[inline(always)]
+    /// Returns true if a window is open (or has elapsed, but no hit has been recorded since)
+    pub fn is_enabled(&self) -> bool {
+        self.deadline.load(Ordering::Relaxed) != 0
+    }
+    /// Record a hit on `key` that lives in `shard`
+    pub fn record(&self, shard: usize, key: &[u8]) {
+        let deadline = self.deadline.load(Ordering::Acquire);
+        if deadline == 0 {
+            return;
+        }
+        if os::get_epoch_secs() >= deadline {
+            // the window has closed; keep what we have for the summary, and turn sampling off
+            // (unless it was restarted in the meantime) so that later hits don't come here
+            let _ =
+                self.deadline
+                    .compare_exchange(deadline, 0, Ordering::AcqRel, Ordering::Relaxed);
+            return;
+        }
+        let hit = self.hits.fetch_add(1, Ordering::Relaxed);
+        if let Some(counter) = self.shards.read().get(shard) {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+        if compiler::unlikely(hit.is_multiple_of(KEY_SAMPLE_RATE)) {
+            let mut keys = self.keys.lock();
+            if let Some(count) = keys.get_mut(key) {
+                *count += 1;
+            } else if keys.len() < MAX_TRACKED_KEYS {
+                keys.insert(key.into(), 1);
+            }
+        }
+    }
+    /// Returns a summary of the current (or last) window with at most `top` keys. Returns
+    /// [`None`] if sampling was never started
+    pub fn summary(&self, top: usize) -> Option<HotspotSummary> {
+        let started = self.started.load(Ordering::Acquire);
+        if started == 0 {
+            return None;
+        }
+        let ends = self.ends.load(Ordering::Acquire);
+        let shard_hits: Vec<u64> = self
+            .shards
+            .read()
+            .iter()
+            .map(|hits| hits.load(Ordering::Relaxed))
+            .collect();
+        let mut top_keys: Vec<(Box<[u8]>, u64)> = self
+            .keys
+            .lock()
+            .iter()
+            .map(|(key, hits)| (key.clone(), hits * KEY_SAMPLE_RATE))
+            .collect();
+        top_keys.sort_unstable_by(|(_, a), (_, b)| b.cmp(a));
+        top_keys.truncate(top);
+        Some(HotspotSummary {
+            window: ends.saturating_sub(started),
+            elapsed: os::get_epoch_secs().min(ends).saturating_sub(started),
+            total_hits: self.hits.load(Ordering::Relaxed),
+            shard_hits,
+            top_keys,
+        })
+    }
+}
+
+#[derive(Debug, PartialEq)]
+/// A summary of a sampling window
+pub struct HotspotSummary {
+    /// the length of the window in seconds
+    pub window: u64,
+    /// the number of seconds of the window that have elapsed
+    pub elapsed: u64,
+    /// the total number of hits
+    pub total_hits: u64,
+    /// the hits per shard
+    pub shard_hits: Vec<u64>,
+    /// the hottest keys along with their (estimated) hits, hottest first
+    pub top_keys: Vec<(Box<[u8]>, u64)>,
+}
+
+impl HotspotSummary {
+    /// Render this summary as lines of text: a header, one heatmap row for every shard that
+    /// was hit and one line for each of the hottest keys
+    pub fn render(&self) -> Vec<String> {
+        let mut lines = Vec::with_capacity(2 + self.shard_hits.len() + self.top_keys.len());
+        lines.push(format!(
+            "window: {}s ({}s elapsed), shards: {}, hits: {}",
+            self.window,
+            self.elapsed,
+            self.shard_hits.len(),
+            self.total_hits
+        ));
+        let max = self.shard_hits.iter().copied().max().unwrap_or(0).max(1);
+        let total = self.total_hits.max(1) as f64;
+        for (shard, hits) in self.shard_hits.iter().enumerate() {
+            if *hits == 0 {
+                continue;
+            }
+            let bar = "#".repeat((hits * HEATMAP_WIDTH).div_ceil(max) as usize);
+            lines.push(format!(
+                "shard {shard}: {bar} {hits} ({:.2}%)",
+                (*hits as f64 / total) * 100.0
+            ));
+        }
+        for (key, hits) in self.top_keys.iter() {
+            lines.push(format!("key {}: ~{hits}", String::from_utf8_lossy(key)));
+        }
+        lines
+    }
+}
+
+#[test]
+fn test_hotspot_sampler() {
+    let sampler = HotspotSampler::default();
+    assert!(sampler.summary(10).is_none());
+    // not started; nothing is recorded
+    sampler.record(0, b"hello");
+    sampler.start(60, 4);
+    for _ in 0..KEY_SAMPLE_RATE * 4 {
+        sampler.record(1, b"hello");
+    }
+    sampler.record(3, b"world");
+    let summary = sampler.summary(10).unwrap();
+    assert_eq!(summary.window, 60);
+    assert_eq!(summary.total_hits, KEY_SAMPLE_RATE * 4 + 1);
+    assert_eq!(summary.shard_hits, vec![0, KEY_SAMPLE_RATE * 4, 0, 1]);
+    // every `KEY_SAMPLE_RATE`th hit is sampled, so both keys are seen
+    assert_eq!(
+        summary.top_keys,
+        vec![
+            (b"hello".to_vec().into_boxed_slice(), KEY_SAMPLE_RATE * 4),
+            (b"world".to_vec().into_boxed_slice(), KEY_SAMPLE_RATE)
+        ]
+    );
+    let rendered = summary.render();
+    // header + two shards + two keys
+    assert_eq!(rendered.len(), 5);
+    assert!(rendered[1].starts_with("shard 1: "));
+    sampler.stop();
+    assert!(sampler.summary(10).is_none());
+}
+
+#[test]
+fn test_hotspot_sampler_window_closes() {
+    let sampler = HotspotSampler::default();
+    sampler.start(60, 4);
+    sampler.record(1, b"hello");
+    // pretend that the window has elapsed
+    let now = os::get_epoch_secs();
+    sampler.deadline.store(now, Ordering::Release);
+    assert!(sampler.is_enabled());
+    sampler.record(1, b"hello");
+    // the first hit after the window closes turns sampling off, and isn't counted
+    assert!(!sampler.is_enabled());
+    let summary = sampler.summary(10).unwrap();
+    assert_eq!(summary.total_hits, 1);
+    assert_eq!(summary.window, 60);
+}
@@ -0,0 +1,144 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Value indexes
+//!
+//! An opt-in inverted index from values to the keys holding them, so that finding every key
+//! with a given value doesn't need a scan of the table. The engine updates the index while it
+//! holds the lock on the entry that is being changed, so index updates for a key happen in
+//! the same order as the changes to its value. See [`KVEStandard::enable_index`] for how the
+//! index is built when it's turned on.
+//!
+//! The memory used by the index is not counted towards `maxmemory`.
+//!
+//! [`KVEStandard::enable_index`]: super::KVEStandard::enable_index
+
+use {
+    crate::corestore::SharedSlice,
+    core::sync::atomic::{AtomicBool, Ordering},
+    parking_lot::RwLock,
+    std::collections::{HashMap, HashSet},
+};
+
+#[derive(Debug, Default)]
+/// An inverted index from the values of a table to their keys
+pub struct ValueIndex {
+    /// set if the index is maintained
+    enabled: AtomicBool,
+    /// the keys holding each value
+    keys: RwLock<HashMap<SharedSlice, HashSet<SharedSlice>>>,
+}
+
+impl ValueIndex {
+    /// Returns true if the index is maintained
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Acquire)
+    }
+    /// Start maintaining an (empty) index. Returns false if it was already maintained
+    pub fn enable(&self) -> bool {
+        if self.is_enabled() {
+            return false;
+        }
+        self.clear();
+        !self.enabled.swap(true, Ordering::AcqRel)
+    }
+    /// Stop maintaining the index and drop it
+    pub fn disable(&self) {
+        self.enabled.store(false, Ordering::Release);
+        self.clear();
+    }
+    /// Add the given `(key, value)` pairs to the index
+    pub fn extend(&self, entries: Vec<(SharedSlice, SharedSlice)>) {
+        let mut keys = self.keys.write();
+        for (key, value) in entries {
+            keys.entry(value).or_default().insert(key);
+        }
+    }
+    #[inline(always)]
+    /// Record that the value of `key` changed from `old` to `new` (`None` if there wasn't one)
+    pub fn replaced(
+        &self,
+        key: &SharedSlice,
+        old: Option<&SharedSlice>,
+        new: Option<&SharedSlice>,
+    ) {
+        if !self.is_enabled() || old == new {
+            return;
+        }
+        let mut keys = self.keys.write();
+        if let Some(old) = old {
+            if let Some(holders) = keys.get_mut(old) {
+                holders.remove(key);
+                if holders.is_empty() {
+                    keys.remove(old);
+                }
+            }
+        }
+        if let Some(new) = new {
+            keys.entry(new.clone()).or_default().insert(key.clone());
+        }
+    }
+    /// Returns the keys holding `value`, or `None` if the index isn't maintained
+    pub fn keys_of(&self, value: &[u8]) -> Option<Vec<SharedSlice>> {
+        if !self.is_enabled() {
+            return None;
+        }
+        Some(
+            self.keys
+                .read()
+                .get(value)
+                .map(|holders| holders.iter().cloned().collect())
+                .unwrap_or_default(),
+        )
+    }
+    /// Drop every entry in the index (it stays maintained if it was)
+    pub fn clear(&self) {
+        self.keys.write().clear();
+    }
+}
+
+#[test]
+fn test_value_index() {
+    let index = ValueIndex::default();
+    let (k1, k2) = (SharedSlice::from("k1"), SharedSlice::from("k2"));
+    let (v1, v2) = (SharedSlice::from("v1"), SharedSlice::from("v2"));
+    // nothing is recorded until the index is turned on
+    index.replaced(&k1, None, Some(&v1));
+    assert_eq!(index.keys_of(b"v1"), None);
+    assert!(index.enable());
+    assert!(!index.enable());
+    index.extend(vec![(k1.clone(), v1.clone())]);
+    index.replaced(&k2, None, Some(&v1));
+    let mut keys = index.keys_of(b"v1").unwrap();
+    keys.sort_by(|a, b| a.as_slice().cmp(b.as_slice()));
+    assert_eq!(keys, vec![k1.clone(), k2.clone()]);
+    index.replaced(&k1, Some(&v1), Some(&v2));
+    index.replaced(&k2, Some(&v1), None);
+    assert!(index.keys_of(b"v1").unwrap().is_empty());
+    assert_eq!(index.keys_of(b"v2").unwrap(), vec![k1]);
+    index.disable();
+    assert_eq!(index.keys_of(b"v2"), None);
+}
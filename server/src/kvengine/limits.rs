@@ -0,0 +1,116 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Size limits
+//!
+//! Keys and values can be capped in size, node-wide (with `server.max_key_size` and
+//! `server.max_value_size`) and per table (with `SYS MAXSIZE`), so that a single misbehaving
+//! client can't insert a huge value that stalls every flush after it. A table's own limit takes
+//! precedence over the node-wide one. Writes with a key or value over its limit are rejected
+//! with `err-too-large` before they reach the table. A limit of zero means no limit.
+
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// The node-wide maximum key size (in bytes)
+static MAX_KEY_SIZE: AtomicU64 = AtomicU64::new(0);
+/// The node-wide maximum value size (in bytes)
+static MAX_VALUE_SIZE: AtomicU64 = AtomicU64::new(0);
+
+/// Set the node-wide size limits (in bytes). Zero means no limit
+pub fn init(max_key_size: u64, max_value_size: u64) {
+    MAX_KEY_SIZE.store(max_key_size, Ordering::Release);
+    MAX_VALUE_SIZE.store(max_value_size, Ordering::Release);
+}
+
+/// Returns `size` if it isn't zero, and `fallback` otherwise
+fn or_fallback(size: u64, fallback: &AtomicU64) -> u64 {
+    if size != 0 {
+        size
+    } else {
+        fallback.load(Ordering::Relaxed)
+    }
+}
+
+/// Returns true if `len` fits within `limit`
+fn fits(len: usize, limit: u64) -> bool {
+    limit == 0 || len as u64 <= limit
+}
+
+#[derive(Debug, Default)]
+/// The size limits of a table. Zero means that the node-wide limit applies
+pub struct SizeLimits {
+    max_key_size: AtomicU64,
+    max_value_size: AtomicU64,
+}
+
+impl SizeLimits {
+    /// Set the maximum key size (in bytes) for the table. Zero falls back to the node-wide limit
+    pub fn set_max_key_size(&self, size: u64) {
+        self.max_key_size.store(size, Ordering::Release)
+    }
+    /// Set the maximum value size (in bytes) for the table. Zero falls back to the node-wide
+    /// limit
+    pub fn set_max_value_size(&self, size: u64) {
+        self.max_value_size.store(size, Ordering::Release)
+    }
+    /// Returns the effective maximum key size (in bytes), or zero if there is no limit
+    pub fn max_key_size(&self) -> u64 {
+        self::or_fallback(self.max_key_size.load(Ordering::Acquire), &MAX_KEY_SIZE)
+    }
+    /// Returns the effective maximum value size (in bytes), or zero if there is no limit
+    pub fn max_value_size(&self) -> u64 {
+        self::or_fallback(self.max_value_size.load(Ordering::Acquire), &MAX_VALUE_SIZE)
+    }
+    /// Returns true if either limit is in effect
+    pub fn is_enabled(&self) -> bool {
+        self.max_key_size() != 0 || self.max_value_size() != 0
+    }
+    /// Returns true if a key of `len` bytes is allowed
+    pub fn allows_key(&self, len: usize) -> bool {
+        self::fits(len, self.max_key_size())
+    }
+    /// Returns true if a value of `len` bytes is allowed
+    pub fn allows_value(&self, len: usize) -> bool {
+        self::fits(len, self.max_value_size())
+    }
+}
+
+#[test]
+fn test_size_limits() {
+    let limits = SizeLimits::default();
+    assert!(!limits.is_enabled());
+    assert!(limits.allows_key(usize::MAX));
+    limits.set_max_key_size(4);
+    assert!(limits.is_enabled());
+    assert!(limits.allows_key(4));
+    assert!(!limits.allows_key(5));
+    assert!(limits.allows_value(usize::MAX));
+    limits.set_max_value_size(8);
+    assert!(limits.allows_value(8));
+    assert!(!limits.allows_value(9));
+    limits.set_max_key_size(0);
+    assert!(limits.allows_key(5));
+}
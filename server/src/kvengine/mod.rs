@@ -26,22 +26,80 @@
 
 #![allow(dead_code)] // TODO(@ohsayan): Clean this up later
 
+pub mod archive;
+pub mod bitmap;
+pub mod bounds;
+pub mod dedup;
+pub mod document;
 pub mod encoding;
+pub mod events;
+pub mod eviction;
+pub mod expiry;
+pub mod hll;
+pub mod hotspot;
+pub mod index;
+pub mod limits;
+pub mod quota;
+pub mod sortedset;
 #[cfg(test)]
 mod tests;
+pub mod throttle;
+pub mod txn;
+pub mod version;
 
 use {
-    self::encoding::{ENCODING_LUT, ENCODING_LUT_PAIR},
+    self::{
+        archive::ColdArchive,
+        bounds::ListBounds,
+        dedup::DedupWindow,
+        document::{Json, Path, SetError},
+        encoding::{ENCODING_LUT, ENCODING_LUT_PAIR},
+        events::{KeyEvent, KeyEvents},
+        eviction::MemoryTracker,
+        expiry::ExpiryIndex,
+        hll::HyperLogLog,
+        hotspot::HotspotSampler,
+        index::ValueIndex,
+        limits::SizeLimits,
+        sortedset::SortedSet,
+        throttle::WriteThrottle,
+        txn::{Transaction, TxnError, Write},
+        version::EntryVersions,
+    },
     crate::{
-        corestore::{booltable::BoolTable, htable::Coremap, map::bref::Ref, SharedSlice},
-        util::compiler,
+        config::EvictionPolicy,
+        corestore::{
+            booltable::BoolTable,
+            htable::Coremap,
+            map::{
+                bref::{Entry, Ref},
+                SCAN_CURSOR_USER_BIT,
+            },
+            SharedSlice,
+        },
+        util::{self, compiler},
+        IoResult,
     },
     parking_lot::RwLock,
+    rand::Rng,
+    std::{
+        collections::{HashMap, HashSet},
+        mem,
+        sync::atomic::{AtomicU64, Ordering},
+    },
 };
 
 pub type KVEStandard = KVEngine<SharedSlice>;
 pub type KVEListmap = KVEngine<LockedVec>;
 pub type LockedVec = RwLock<Vec<SharedSlice>>;
+pub type KVEMap = KVEngine<LockedMap>;
+pub type LockedMap = RwLock<HashMap<SharedSlice, SharedSlice>>;
+pub type KVESet = KVEngine<LockedSet>;
+pub type LockedSet = RwLock<HashSet<SharedSlice>>;
+pub type KVESortedSet = KVEngine<LockedSortedSet>;
+pub type LockedSortedSet = RwLock<SortedSet>;
+pub type KVEDocument = KVEngine<LockedDocument>;
+pub type LockedDocument = RwLock<Json>;
 pub type SingleEncoder = fn(&[u8]) -> bool;
 pub type DoubleEncoder = fn(&[u8], &[u8]) -> bool;
 type EntryRef<'a, T> = Ref<'a, SharedSlice, T>;
@@ -50,9 +108,62 @@ type OptionRef<'a, T> = Option<Ref<'a, SharedSlice, T>>;
 type EncodingResultRef<'a, T> = EncodingResult<OptionRef<'a, T>>;
 
 const TSYMBOL_LUT: BoolTable<u8> = BoolTable::new(b'+', b'?');
+/// The number of times [`KVEngine::random_key`] picks again if it picked an expired key
+const RANDOM_KEY_ATTEMPTS: usize = 8;
+
+/// The reasons why adding to a counter (see [`KVEngine::incr_by`]) can fail
+#[derive(Debug, PartialEq, Eq)]
+pub enum IncrError {
+    /// the value isn't a (64-bit signed) integer
+    NotAnInteger,
+    /// the result doesn't fit in a 64-bit signed integer
+    Overflow,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+/// Diagnostics for an entry (see [`KVEngine::inspect_entry`])
+pub struct EntryInfo {
+    /// the shard of the table that holds the entry
+    pub shard: usize,
+    /// the number of shards of the table
+    pub shards: usize,
+    /// the approximate memory taken by the entry, as counted towards `maxmemory`
+    pub footprint: usize,
+    /// how the value is held (see [`KVEValue::encoding`])
+    pub encoding: &'static str,
+    /// the time the entry has left to live in milliseconds, if it has a deadline
+    pub ttl_ms: Option<u64>,
+}
+
+/// When [`KVEngine::set_with_expiry`] writes a value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetCondition {
+    /// only if the key doesn't exist (like `SET`)
+    Missing,
+    /// only if the key exists (like `UPDATE`)
+    Exists,
+    /// whether or not the key exists (like `USET`)
+    Always,
+}
 
-pub trait KVEValue {
+pub trait KVEValue: Sized {
     fn verify_encoding(&self, e_v: bool) -> EncodingResult<()>;
+    /// Returns the approximate memory taken by the value
+    fn footprint(&self) -> usize;
+    /// Returns how the value is held (for diagnostics)
+    fn encoding(&self) -> &'static str;
+    /// Called on every access to `key`, before the access is made
+    fn on_access(_archive: &ColdArchive, _data: &Coremap<SharedSlice, Self>, _key: &[u8]) {}
+    /// Called whenever the value of `key` changes from `old` to `new` (`None` if there isn't
+    /// one), while the entry for `key` is locked
+    fn on_change(
+        _index: &ValueIndex,
+        _versions: &EntryVersions,
+        _key: &SharedSlice,
+        _old: Option<&Self>,
+        _new: Option<&Self>,
+    ) {
+    }
 }
 
 impl KVEValue for SharedSlice {
@@ -63,6 +174,47 @@ impl KVEValue for SharedSlice {
             Err(())
         }
     }
+    fn footprint(&self) -> usize {
+        self.len()
+    }
+    fn encoding(&self) -> &'static str {
+        if let Some(kind) = self
+            .get(hll::MAGIC.len())
+            .filter(|_| self.starts_with(hll::MAGIC))
+        {
+            if HyperLogLog::decode(self).is_some() {
+                return if *kind == hll::SPARSE {
+                    "hyperloglog-sparse"
+                } else {
+                    "hyperloglog-dense"
+                };
+            }
+        }
+        let is_int = core::str::from_utf8(self)
+            .ok()
+            .and_then(|value| value.parse::<i64>().ok())
+            .is_some();
+        if is_int {
+            "int"
+        } else {
+            "raw"
+        }
+    }
+    #[inline(always)]
+    fn on_access(archive: &ColdArchive, data: &Coremap<SharedSlice, Self>, key: &[u8]) {
+        archive.touch(data, key)
+    }
+    #[inline(always)]
+    fn on_change(
+        index: &ValueIndex,
+        versions: &EntryVersions,
+        key: &SharedSlice,
+        old: Option<&Self>,
+        new: Option<&Self>,
+    ) {
+        index.replaced(key, old, new);
+        versions.changed(key, new.is_some());
+    }
 }
 
 impl KVEValue for LockedVec {
@@ -74,6 +226,88 @@ impl KVEValue for LockedVec {
             Err(())
         }
     }
+    fn footprint(&self) -> usize {
+        self.read()
+            .iter()
+            .map(|element| eviction::element_size(element))
+            .sum()
+    }
+    fn encoding(&self) -> &'static str {
+        "list"
+    }
+}
+
+impl KVEValue for LockedMap {
+    fn verify_encoding(&self, e_v: bool) -> EncodingResult<()> {
+        let func = ENCODING_LUT[e_v];
+        if self.read().iter().all(|(f, v)| func(f) && func(v)) {
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+    fn footprint(&self) -> usize {
+        self.read()
+            .iter()
+            .map(|(field, value)| eviction::field_size(field, value))
+            .sum()
+    }
+    fn encoding(&self) -> &'static str {
+        "map"
+    }
+}
+
+impl KVEValue for LockedSet {
+    fn verify_encoding(&self, e_v: bool) -> EncodingResult<()> {
+        let func = ENCODING_LUT[e_v];
+        if self.read().iter().all(|member| func(member)) {
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+    fn footprint(&self) -> usize {
+        self.read()
+            .iter()
+            .map(|member| eviction::element_size(member))
+            .sum()
+    }
+    fn encoding(&self) -> &'static str {
+        "set"
+    }
+}
+
+impl KVEValue for LockedSortedSet {
+    fn verify_encoding(&self, e_v: bool) -> EncodingResult<()> {
+        let func = ENCODING_LUT[e_v];
+        if self.read().iter().all(|(member, _)| func(member)) {
+            Ok(())
+        } else {
+            Err(())
+        }
+    }
+    fn footprint(&self) -> usize {
+        self.read()
+            .iter()
+            .map(|(member, _)| eviction::scored_size(member))
+            .sum()
+    }
+    fn encoding(&self) -> &'static str {
+        "zset"
+    }
+}
+
+impl KVEValue for LockedDocument {
+    fn verify_encoding(&self, _: bool) -> EncodingResult<()> {
+        // a document was parsed from (valid) UTF-8, and every string in it is a `String`
+        Ok(())
+    }
+    fn footprint(&self) -> usize {
+        self.read().footprint()
+    }
+    fn encoding(&self) -> &'static str {
+        "json"
+    }
 }
 
 #[derive(Debug)]
@@ -81,30 +315,325 @@ pub struct KVEngine<T> {
     data: Coremap<SharedSlice, T>,
     e_k: bool,
     e_v: bool,
+    hotspots: HotspotSampler,
+    archive: ColdArchive,
+    throttle: WriteThrottle,
+    limits: SizeLimits,
+    dedup: DedupWindow,
+    expiry: ExpiryIndex,
+    /// the maximum lengths of lists (only used by list tables)
+    bounds: ListBounds,
+    memory: MemoryTracker,
+    index: ValueIndex,
+    versions: EntryVersions,
+    events: KeyEvents,
+    /// the number of mutations made so far (used to skip flushing unchanged tables)
+    mutations: AtomicU64,
 }
 
 // basic method impls
-impl<T> KVEngine<T> {
+impl<T: KVEValue> KVEngine<T> {
     /// Create a new KVEBlob
     pub fn new(e_k: bool, e_v: bool, data: Coremap<SharedSlice, T>) -> Self {
-        Self { data, e_k, e_v }
+        let memory = MemoryTracker::default();
+        memory.loaded(
+            data.len(),
+            data.iter()
+                .map(|kv| eviction::entry_size(kv.key(), kv.value().footprint()))
+                .sum(),
+        );
+        Self {
+            data,
+            e_k,
+            e_v,
+            hotspots: HotspotSampler::default(),
+            archive: ColdArchive::default(),
+            throttle: WriteThrottle::default(),
+            limits: SizeLimits::default(),
+            dedup: DedupWindow::default(),
+            expiry: ExpiryIndex::default(),
+            bounds: ListBounds::default(),
+            memory,
+            index: ValueIndex::default(),
+            versions: EntryVersions::default(),
+            events: KeyEvents::default(),
+            mutations: AtomicU64::new(0),
+        }
     }
     /// Create a new empty KVEBlob
     pub fn init(e_k: bool, e_v: bool) -> Self {
         Self::new(e_k, e_v, Default::default())
     }
-    /// Number of KV pairs
+    /// Move the data into a map with (about) `shards` shards
+    pub fn with_shards(mut self, shards: usize) -> Self {
+        let data = mem::replace(&mut self.data, Coremap::with_shards(shards));
+        for (key, value) in data {
+            self.data.true_if_insert(key, value);
+        }
+        self
+    }
+    /// Returns the number of shards in the map holding the data
+    pub fn shard_count(&self) -> usize {
+        self.data.shard_count()
+    }
+    /// Number of KV pairs (including the archived ones)
     pub fn len(&self) -> usize {
-        self.data.len()
+        self.data.len() + self.archive.len()
     }
-    /// Delete all the key/value pairs
+    /// Delete all the key/value pairs. The versions are kept, since a write can race with the
+    /// truncation, and a key that is set again must never get a version it had before
     pub fn truncate_table(&self) {
-        self.data.clear()
+        self.archive.clear();
+        self.data.clear();
+        self.expiry.clear();
+        self.bounds.clear();
+        self.memory.clear();
+        self.index.clear();
+        self.mark_dirty();
+    }
+    /// Record a mutation. This must be called **after** the data has been changed, by anyone
+    /// who mutates the data without going through the engine (for example, using
+    /// [`KVEngine::get_inner_ref`])
+    pub fn mark_dirty(&self) {
+        self.mutations.fetch_add(1, Ordering::Release);
+    }
+    /// Returns the number of mutations made so far
+    pub fn mutations(&self) -> u64 {
+        self.mutations.load(Ordering::Acquire)
+    }
+    /// Calls [`KVEngine::mark_dirty`] if `changed` is true, and returns `changed`
+    fn mark_dirty_if(&self, changed: bool) -> bool {
+        if changed {
+            self.mark_dirty();
+        }
+        changed
+    }
+    /// Returns a reference to the archive for this table
+    pub fn archive(&self) -> &ColdArchive {
+        &self.archive
     }
     /// Returns a reference to the inner structure
     pub fn get_inner_ref(&self) -> &Coremap<SharedSlice, T> {
         &self.data
     }
+    /// Returns a page of at most `count` keys (including the keys of archived values) starting
+    /// at `cursor`, along with the cursor of the next page (see [`Coremap::scan_keys`]). The keys
+    /// in memory come first, and the cursors for the archived keys have [`SCAN_CURSOR_USER_BIT`]
+    /// set. A key that is archived or faulted in during the scan may be missed
+    ///
+    /// Keys whose deadlines have passed are left out (even if they haven't been removed yet),
+    /// so a page may have fewer than `count` keys even if there are more pages
+    pub fn scan_keys(&self, cursor: u64, count: usize) -> (Vec<SharedSlice>, u64) {
+        let (mut keys, next) = self._scan_keys(cursor, count);
+        self.retain_unexpired(&mut keys);
+        (keys, next)
+    }
+    fn _scan_keys(&self, cursor: u64, count: usize) -> (Vec<SharedSlice>, u64) {
+        let archived = |cursor, count| match self.archive.scan_keys(cursor, count) {
+            (keys, 0) => (keys, 0),
+            (keys, next) => (keys, next | SCAN_CURSOR_USER_BIT),
+        };
+        if cursor & SCAN_CURSOR_USER_BIT != 0 {
+            return archived(cursor & !SCAN_CURSOR_USER_BIT, count);
+        }
+        let (mut keys, next) = self.data.scan_keys(cursor, count);
+        if next != 0 || self.archive.is_empty() {
+            return (keys, next);
+        }
+        // we're done with the keys in memory and the page has room left
+        let (cold, next) = archived(0, count.max(1) - keys.len());
+        keys.extend(cold);
+        (keys, next)
+    }
+    /// Returns every key (including the keys of archived values) that matches the glob
+    /// `pattern` (see [`util::glob_match`]), leaving out the keys whose deadlines have passed
+    pub fn match_keys(&self, pattern: &[u8]) -> Vec<SharedSlice> {
+        let matches = |key: &SharedSlice| util::glob_match(pattern, key);
+        let mut keys: Vec<SharedSlice> = self
+            .data
+            .iter()
+            .map(|kv| kv.key().clone())
+            .filter(matches)
+            .collect();
+        if !self.archive.is_empty() {
+            let archived = self.archive.get_keys(self.archive.len());
+            keys.extend(archived.into_iter().filter(matches));
+        }
+        self.retain_unexpired(&mut keys);
+        keys
+    }
+    /// Leave out the keys whose deadlines have passed but that haven't been removed yet, so
+    /// that listing the keys agrees with reading them
+    fn retain_unexpired(&self, keys: &mut Vec<SharedSlice>) {
+        if compiler::unlikely(!self.expiry.is_empty()) {
+            let now = expiry::now_ms();
+            keys.retain(|key| !self.expiry.is_due(key, now));
+        }
+    }
+    /// Returns the keys that match the glob `pattern` among the page of at most `count` keys
+    /// that starts at `cursor` (see [`Self::scan_keys`]), along with the cursor of the next page
+    pub fn scan_matching(
+        &self,
+        pattern: &[u8],
+        cursor: u64,
+        count: usize,
+    ) -> (Vec<SharedSlice>, u64) {
+        let (mut keys, next) = self.scan_keys(cursor, count);
+        keys.retain(|key| util::glob_match(pattern, key));
+        (keys, next)
+    }
+    /// Removes at most `limit` of the keys that match the glob `pattern` among the page of at
+    /// most `count` keys that starts at `cursor` (see [`Self::scan_matching`]). Returns how many
+    /// were removed along with the cursor of the next page
+    pub fn remove_matching(
+        &self,
+        pattern: &[u8],
+        cursor: u64,
+        count: usize,
+        limit: usize,
+    ) -> (usize, u64) {
+        let (keys, next) = self.scan_matching(pattern, cursor, count);
+        let removed = keys
+            .into_iter()
+            .take(limit)
+            .filter(|key| self.remove_unchecked(key))
+            .count();
+        (removed, next)
+    }
+    /// Returns a random key (which may be the key of an archived value), or `None` if the table
+    /// is empty. A key whose deadline has passed is removed (like a read would) and we pick
+    /// again
+    pub fn random_key(&self) -> Option<SharedSlice> {
+        for _ in 0..RANDOM_KEY_ATTEMPTS {
+            let key = self._random_key()?;
+            let now = expiry::now_ms();
+            if compiler::likely(!self.expiry.is_due(&key, now)) {
+                return Some(key);
+            }
+            T::on_access(&self.archive, &self.data, &key);
+            self._expire_if_due(&key, now);
+        }
+        None
+    }
+    fn _random_key(&self) -> Option<SharedSlice> {
+        let cold = self.archive.len();
+        if cold != 0 {
+            // counting the keys in memory means locking every shard, so we only do it if we
+            // have to pick between memory and the archive
+            let hot = self.data.len();
+            if rand::thread_rng().gen_range(0..hot + cold) >= hot {
+                if let Some(key) = self.archive.random_key() {
+                    return Some(key);
+                }
+            }
+        }
+        self.data.random_key().or_else(|| self.archive.random_key())
+    }
+    /// Returns a reference to the expiry index for this table
+    pub fn expiry(&self) -> &ExpiryIndex {
+        &self.expiry
+    }
+    /// Replace the expiry index for this table (used when the table is restored from disk)
+    pub fn restore_expiry(&mut self, expiry: ExpiryIndex) {
+        self.expiry = expiry;
+    }
+    /// Remove `key` if its deadline has passed. Returns true if it was removed
+    pub fn expire_if_due(&self, key: &[u8]) -> bool {
+        if compiler::likely(self.expiry.is_empty()) {
+            return false;
+        }
+        self._expire_if_due(key, expiry::now_ms())
+    }
+    fn _expire_if_due(&self, key: &[u8], now: u64) -> bool {
+        let expired = self.expiry.remove_if_due(key, now);
+        if expired {
+            self.remove_entry(key, KeyEvent::Expired);
+            self.mark_dirty();
+        }
+        expired
+    }
+    /// Remove the entry for `key` and account for it, publishing `event` if it's enabled.
+    /// Returns the removed value
+    fn remove_entry(&self, key: &[u8], event: KeyEvent) -> Option<T> {
+        let removed = self.data.remove_if(key, |key, value| {
+            self.changed_with(key, Some(value), None, event);
+            true
+        });
+        removed.map(|(key, value)| {
+            self.memory
+                .removed(&key, eviction::entry_size(&key, value.footprint()));
+            value
+        })
+    }
+    /// Returns a reference to the memory tracker for this table. Anyone who changes the size of
+    /// a value in place (or adds an entry) without going through the engine has to account for
+    /// it here
+    pub fn memory(&self) -> &MemoryTracker {
+        &self.memory
+    }
+    /// Returns true if the table is in a keyspace that has hit its quota (see
+    /// [`quota`](self::quota)), in which case writes that allocate must be rejected
+    pub fn is_over_quota(&self) -> bool {
+        self.memory.is_over_quota()
+    }
+    /// Returns a reference to the value index for this table
+    pub fn index(&self) -> &ValueIndex {
+        &self.index
+    }
+    /// Record that the value of `key` changed from `old` to `new` (`None` if there isn't one).
+    /// Anyone who changes a value without going through the engine has to report it here (with
+    /// the entry still locked), so that the value index, the entry versions and the keyspace
+    /// events stay in sync
+    pub fn changed(&self, key: &SharedSlice, old: Option<&T>, new: Option<&T>) {
+        let event = if new.is_some() {
+            KeyEvent::Set
+        } else {
+            KeyEvent::Del
+        };
+        self.changed_with(key, old, new, event)
+    }
+    /// Same as [`KVEngine::changed`], but publishes `event` instead of the event implied by the
+    /// change. Events are published with the entry still locked, so a subscriber that reads
+    /// the key after receiving the event never sees the value from before the change
+    fn changed_with(&self, key: &SharedSlice, old: Option<&T>, new: Option<&T>, event: KeyEvent) {
+        T::on_change(&self.index, &self.versions, key, old, new);
+        if new.is_none() {
+            // a bound goes away along with its list
+            self.bounds.remove(key);
+        }
+        self.events.notify(event, key)
+    }
+    /// Returns a reference to the hotspot sampler for this table
+    pub fn hotspots(&self) -> &HotspotSampler {
+        &self.hotspots
+    }
+    /// Returns a reference to the write throttle for this table
+    pub fn write_throttle(&self) -> &WriteThrottle {
+        &self.throttle
+    }
+    /// Returns a reference to the size limits for this table
+    pub fn size_limits(&self) -> &SizeLimits {
+        &self.limits
+    }
+    /// Returns a reference to the dedup window for this table
+    pub fn dedup_window(&self) -> &DedupWindow {
+        &self.dedup
+    }
+    /// Returns a reference to the keyspace events of this table
+    pub fn key_events(&self) -> &KeyEvents {
+        &self.events
+    }
+    /// Start sampling hotspots for the next `window` seconds
+    pub fn start_hotspot_sampling(&self, window: u64) {
+        self.hotspots.start(window, self.data.shard_count())
+    }
+    #[inline(always)]
+    /// Record a hit on `key` if hotspot sampling is enabled
+    fn record_hit(&self, key: &[u8]) {
+        if compiler::unlikely(self.hotspots.is_enabled()) {
+            self.hotspots.record(self.data.shard_of(key), key)
+        }
+    }
     /// Check the encoding of the key
     pub fn is_key_ok(&self, key: &[u8]) -> bool {
         self._check_encoding(key, self.e_k)
@@ -163,6 +692,14 @@ impl<T> KVEngine<T> {
 
 // dict impls
 impl<T: KVEValue> KVEngine<T> {
+    #[inline(always)]
+    /// Record an access to `key`
+    fn access(&self, key: &[u8]) {
+        self.record_hit(key);
+        T::on_access(&self.archive, &self.data, key);
+        self.expire_if_due(key);
+        self.memory.touch(&self.data, key);
+    }
     /// Get the value of the given key
     pub fn get<Q: AsRef<[u8]>>(&self, key: Q) -> EncodingResultRef<T> {
         self.check_key_encoding(key.as_ref())
@@ -170,6 +707,7 @@ impl<T: KVEValue> KVEngine<T> {
     }
     /// Get the value of the given key without any encoding checks
     pub fn get_unchecked<Q: AsRef<[u8]>>(&self, key: Q) -> OptionRef<T> {
+        self.access(key.as_ref());
         self.data.get(key.as_ref())
     }
     /// Set the value of the given key
@@ -180,7 +718,21 @@ impl<T: KVEValue> KVEngine<T> {
     }
     /// Same as set, but doesn't check encoding. Caller must check encoding
     pub fn set_unchecked(&self, key: SharedSlice, val: T) -> bool {
-        self.data.true_if_insert(key, val)
+        self.access(&key);
+        let size = eviction::entry_size(&key, val.footprint());
+        let inserted = match self.data.fresh_entry(key.clone()) {
+            Some(fresh) => {
+                self.expiry.remove(&key);
+                self.changed(&key, None, Some(&val));
+                fresh.insert(val);
+                true
+            }
+            None => false,
+        };
+        if inserted {
+            self.memory.inserted(&key, size);
+        }
+        self.mark_dirty_if(inserted)
     }
     /// Check if the provided key exists
     pub fn exists<Q: AsRef<[u8]>>(&self, key: Q) -> EncodingResult<bool> {
@@ -188,6 +740,7 @@ impl<T: KVEValue> KVEngine<T> {
         Ok(self.exists_unchecked(key.as_ref()))
     }
     pub fn exists_unchecked<Q: AsRef<[u8]>>(&self, key: Q) -> bool {
+        self.access(key.as_ref());
         self.data.contains_key(key.as_ref())
     }
     /// Update the value of an existing key. Returns `true` if updated
@@ -198,7 +751,56 @@ impl<T: KVEValue> KVEngine<T> {
     }
     /// Update the value of an existing key without encoding checks
     pub fn update_unchecked(&self, key: SharedSlice, val: T) -> bool {
-        self.data.true_if_update(key, val)
+        self.access(&key);
+        let size = val.footprint();
+        match self.data.mut_entry(key.clone()) {
+            Some(mut entry) => {
+                self.expiry.remove(&key);
+                self.changed(&key, Some(entry.value()), Some(&val));
+                let old = entry.insert(val).footprint();
+                drop(entry);
+                self.memory.resize(old, size);
+                self.mark_dirty();
+                true
+            }
+            None => false,
+        }
+    }
+    /// Write `val` to `key` if `condition` holds and make it expire in `ttl_ms` milliseconds.
+    /// The deadline is set with the entry locked, so the value is never seen without it (which
+    /// is what makes this usable for locks). Returns false if nothing was written
+    pub fn set_with_expiry(
+        &self,
+        key: SharedSlice,
+        val: T,
+        condition: SetCondition,
+        ttl_ms: u64,
+    ) -> EncodingResult<bool> {
+        self.check_key_encoding(&key)?;
+        val.verify_encoding(self.e_v)?;
+        self.access(&key);
+        let size = val.footprint();
+        let deadline = expiry::now_ms().saturating_add(ttl_ms);
+        let old = match (self.data.entry(key.clone()), condition) {
+            (Entry::Occupied(mut entry), SetCondition::Exists | SetCondition::Always) => {
+                self.changed(&key, Some(entry.value()), Some(&val));
+                self.expiry.set(key.clone(), deadline);
+                Some(entry.insert(val))
+            }
+            (Entry::Vacant(entry), SetCondition::Missing | SetCondition::Always) => {
+                self.changed(&key, None, Some(&val));
+                self.expiry.set(key.clone(), deadline);
+                entry.insert(val);
+                None
+            }
+            _ => return Ok(false),
+        };
+        match old {
+            Some(old) => self.memory.resize(old.footprint(), size),
+            None => self.memory.inserted(&key, eviction::entry_size(&key, size)),
+        }
+        self.mark_dirty();
+        Ok(true)
     }
     /// Update or insert an entry
     pub fn upsert(&self, key: SharedSlice, val: T) -> EncodingResult<()> {
@@ -209,7 +811,95 @@ impl<T: KVEValue> KVEngine<T> {
     }
     /// Update or insert an entry without encoding checks
     pub fn upsert_unchecked(&self, key: SharedSlice, val: T) {
-        self.data.upsert(key, val)
+        self.swap_unchecked(key, val);
+    }
+    /// Update or insert an entry, returning the value that it replaced (if any). The old value
+    /// is taken out with the entry locked, so no other write can slip in between
+    pub fn swap(&self, key: SharedSlice, val: T) -> EncodingResult<Option<T>> {
+        self.check_key_encoding(&key)?;
+        val.verify_encoding(self.e_v)?;
+        Ok(self.swap_unchecked(key, val))
+    }
+    /// Same as [`KVEngine::swap`], but without encoding checks
+    pub fn swap_unchecked(&self, key: SharedSlice, val: T) -> Option<T> {
+        self.access(&key);
+        let size = val.footprint();
+        let old = match self.data.entry(key.clone()) {
+            Entry::Occupied(mut entry) => {
+                self.expiry.remove(&key);
+                self.changed(&key, Some(entry.value()), Some(&val));
+                Some(entry.insert(val))
+            }
+            Entry::Vacant(entry) => {
+                self.expiry.remove(&key);
+                self.changed(&key, None, Some(&val));
+                entry.insert(val);
+                None
+            }
+        };
+        match old {
+            Some(ref old) => self.memory.resize(old.footprint(), size),
+            None => self.memory.inserted(&key, eviction::entry_size(&key, size)),
+        }
+        self.mark_dirty();
+        old
+    }
+    /// Same as [`KVEngine::set_unchecked`], but for a batch of entries: the shards holding the
+    /// keys are locked once for the whole batch instead of once per key. Returns the number of
+    /// entries that were inserted (if a key appears more than once, only the first entry counts)
+    pub fn set_many_unchecked(&self, entries: Vec<(SharedSlice, T)>) -> usize {
+        self.prepare_batch(&entries);
+        let mut inserted = Vec::with_capacity(entries.len());
+        {
+            let mut shards = self.data.lock_shards_of(entries.iter().map(|(key, _)| key));
+            for (key, val) in entries {
+                if shards.get(&key).is_none() {
+                    self.expiry.remove(&key);
+                    self.changed(&key, None, Some(&val));
+                    let size = eviction::entry_size(&key, val.footprint());
+                    shards.insert(key.clone(), val);
+                    inserted.push((key, size));
+                }
+            }
+        }
+        // account only after the locks are released, since this may have to evict
+        for (key, size) in inserted.iter() {
+            self.memory.inserted(key, *size);
+        }
+        self.mutations
+            .fetch_add(inserted.len() as u64, Ordering::Release);
+        inserted.len()
+    }
+    /// Same as [`KVEngine::upsert_unchecked`], but for a batch of entries: the shards holding the
+    /// keys are locked once for the whole batch instead of once per key
+    pub fn upsert_many_unchecked(&self, entries: Vec<(SharedSlice, T)>) {
+        self.prepare_batch(&entries);
+        let count = entries.len();
+        let mut changes = Vec::with_capacity(count);
+        {
+            let mut shards = self.data.lock_shards_of(entries.iter().map(|(key, _)| key));
+            for (key, val) in entries {
+                self.expiry.remove(&key);
+                self.changed(&key, shards.get(&key), Some(&val));
+                let size = val.footprint();
+                let old = shards.insert(key.clone(), val).map(|old| old.footprint());
+                changes.push((key, old, size));
+            }
+        }
+        for (key, old, size) in changes {
+            match old {
+                Some(old) => self.memory.resize(old, size),
+                None => self.memory.inserted(&key, eviction::entry_size(&key, size)),
+            }
+        }
+        self.mutations.fetch_add(count as u64, Ordering::Release);
+    }
+    /// Do what a write does to a key before touching the data, for every key in `entries`. This
+    /// has to happen before the shards are locked since it may have to change the data
+    fn prepare_batch(&self, entries: &[(SharedSlice, T)]) {
+        for (key, _) in entries {
+            self.access(key);
+        }
     }
     /// Remove an entry
     pub fn remove<Q: AsRef<[u8]>>(&self, key: Q) -> EncodingResult<bool> {
@@ -218,7 +908,10 @@ impl<T: KVEValue> KVEngine<T> {
     }
     /// Remove an entry without encoding checks
     pub fn remove_unchecked<Q: AsRef<[u8]>>(&self, key: Q) -> bool {
-        self.data.true_if_removed(key.as_ref())
+        self.access(key.as_ref());
+        self.expiry.remove(key.as_ref());
+        let removed = self.remove_entry(key.as_ref(), KeyEvent::Del).is_some();
+        self.mark_dirty_if(removed)
     }
     /// Pop an entry
     pub fn pop<Q: AsRef<[u8]>>(&self, key: Q) -> EncodingResult<Option<T>> {
@@ -227,24 +920,590 @@ impl<T: KVEValue> KVEngine<T> {
     }
     /// Pop an entry without encoding checks
     pub fn pop_unchecked<Q: AsRef<[u8]>>(&self, key: Q) -> Option<T> {
-        self.data.remove(key.as_ref()).map(|(_, v)| v)
+        self.access(key.as_ref());
+        self.expiry.remove(key.as_ref());
+        let ret = self.remove_entry(key.as_ref(), KeyEvent::Del);
+        self.mark_dirty_if(ret.is_some());
+        ret
+    }
+    /// Expire `key` in `ttl_ms` milliseconds. Returns false if the key doesn't exist
+    pub fn set_expiry<Q: AsRef<[u8]>>(&self, key: Q, ttl_ms: u64) -> EncodingResult<bool> {
+        let key = key.as_ref();
+        self.check_key_encoding(key)?;
+        self.access(key);
+        let exists = self.data.contains_key(key);
+        if exists {
+            let deadline = expiry::now_ms().saturating_add(ttl_ms);
+            self.expiry.set(SharedSlice::new(key), deadline);
+            self.mark_dirty();
+        }
+        Ok(exists)
+    }
+    /// Returns the time to live of `key` in milliseconds: `None` if the key doesn't exist and
+    /// `Some(None)` if it doesn't expire
+    pub fn ttl<Q: AsRef<[u8]>>(&self, key: Q) -> EncodingResult<Option<Option<u64>>> {
+        let key = key.as_ref();
+        self.check_key_encoding(key)?;
+        self.access(key);
+        if !self.data.contains_key(key) {
+            return Ok(None);
+        }
+        let now = expiry::now_ms();
+        Ok(Some(
+            self.expiry
+                .get(key)
+                .map(|deadline| deadline.saturating_sub(now)),
+        ))
+    }
+    /// Returns diagnostics for the entry of `key`, or `None` if it doesn't exist. Unlike a read,
+    /// this isn't counted as a hit and doesn't change the eviction order of the entry (but an
+    /// archived value is brought back in to be looked at)
+    pub fn inspect_entry<Q: AsRef<[u8]>>(&self, key: Q) -> EncodingResult<Option<EntryInfo>> {
+        let key = key.as_ref();
+        self.check_key_encoding(key)?;
+        T::on_access(&self.archive, &self.data, key);
+        self.expire_if_due(key);
+        let now = expiry::now_ms();
+        Ok(self.data.get(key).map(|value| EntryInfo {
+            shard: self.data.shard_of(key),
+            shards: self.data.shard_count(),
+            footprint: eviction::entry_size(key, value.footprint()),
+            encoding: value.encoding(),
+            ttl_ms: self
+                .expiry
+                .get(key)
+                .map(|deadline| deadline.saturating_sub(now)),
+        }))
+    }
+    /// Remove the deadline of `key`. Returns true if it had one
+    pub fn persist<Q: AsRef<[u8]>>(&self, key: Q) -> EncodingResult<bool> {
+        let key = key.as_ref();
+        self.check_key_encoding(key)?;
+        self.access(key);
+        Ok(self.mark_dirty_if(self.data.contains_key(key) && self.expiry.remove(key)))
+    }
+    /// Remove up to `limit` keys whose deadlines have passed at `now`. Returns the number of
+    /// keys that were removed
+    pub fn expire_due(&self, now: u64, limit: usize) -> usize {
+        let mut expired = 0;
+        for key in self.expiry.due(now, limit) {
+            // archived values have to be faulted in before they can be removed
+            T::on_access(&self.archive, &self.data, &key);
+            expired += self._expire_if_due(&key, now) as usize;
+        }
+        expired
+    }
+    /// Evict the key picked by `policy` out of a sample of keys. Returns false if there was
+    /// nothing to evict
+    pub fn evict(&self, policy: EvictionPolicy) -> bool {
+        let shard = self.memory.next_shard(self.data.shard_count());
+        let sample = self.data.sample_keys(shard, eviction::EVICTION_SAMPLES);
+        match self.memory.pick_victim(sample, policy, expiry::now_ms()) {
+            Some(victim) => {
+                self.expiry.remove(&victim);
+                // someone may have removed it in the meantime, which is just as good
+                if self.remove_entry(&victim, KeyEvent::Del).is_some() {
+                    self.mark_dirty();
+                }
+                true
+            }
+            None => false,
+        }
     }
 }
 
-impl<T: Clone> KVEngine<T> {
+impl<T: Clone + KVEValue> KVEngine<T> {
     pub fn get_cloned<Q: AsRef<[u8]>>(&self, key: Q) -> EncodingResult<Option<T>> {
         self.check_key_encoding(key.as_ref())?;
         Ok(self.get_cloned_unchecked(key.as_ref()))
     }
     pub fn get_cloned_unchecked<Q: AsRef<[u8]>>(&self, key: Q) -> Option<T> {
+        self.access(key.as_ref());
         self.data.get_cloned(key.as_ref())
     }
 }
 
 impl KVEStandard {
     pub fn take_snapshot_unchecked<Q: AsRef<[u8]>>(&self, key: Q) -> Option<SharedSlice> {
+        self.access(key.as_ref());
         self.data.get_cloned(key.as_ref())
     }
+    /// Archive the values of keys that have been idle for more than `idle_days` days. Returns
+    /// the number of values that were archived
+    pub fn archive_idle(&self, idle_days: u64) -> IoResult<usize> {
+        self.archive.archive_idle(&self.data, idle_days)
+    }
+    /// Start maintaining the value index for this table (see [`ValueIndex`]). This is a no-op if
+    /// it is already maintained
+    ///
+    /// The archive is frozen while the index is built, so no values are archived meanwhile.
+    /// Writes (and values being faulted in) that race with the scan of the table can leave
+    /// stale entries behind, so once the scan has been added to the index, every entry from it
+    /// is checked again with its key locked
+    pub fn enable_index(&self) -> IoResult<()> {
+        if !self.index.enable() {
+            return Ok(());
+        }
+        let mut archive = self.archive.freeze();
+        let scanned: Vec<(SharedSlice, SharedSlice)> = self
+            .data
+            .iter()
+            .filter(|kv| !archive.contains(kv.key()))
+            .map(|kv| (kv.key().clone(), kv.value().clone()))
+            .collect();
+        let mut archived = Vec::with_capacity(archive.len());
+        let ret = archive.for_each(|key, value| {
+            archived.push((SharedSlice::new(key), SharedSlice::new(value)));
+            Ok(())
+        });
+        if let Err(e) = ret {
+            self.index.disable();
+            return Err(e);
+        }
+        self.index.extend(archived.clone());
+        self.index.extend(scanned.clone());
+        for (key, value) in scanned.into_iter().chain(archived) {
+            if self.archive.is_archived(&key) {
+                // the value of an archived key can't change without faulting it in first
+                continue;
+            }
+            // nothing is archived while the archive is frozen, so a key that's missing now
+            // has been removed
+            let entry = self.data.entry(key.clone());
+            let current = match &entry {
+                Entry::Occupied(entry) => Some(entry.value()),
+                Entry::Vacant(_) => None,
+            };
+            if current != Some(&value) {
+                self.index.replaced(&key, Some(&value), None);
+            }
+        }
+        Ok(())
+    }
+    /// Returns the keys whose value is `value`, or `None` if the value index isn't maintained
+    pub fn find_keys(&self, value: &[u8]) -> Option<Vec<SharedSlice>> {
+        self.index.keys_of(value)
+    }
+    /// Add `delta` to the integer held by `key` (a missing key counts as zero) and return the
+    /// result, which is stored in decimal. The value is read and written with the key locked,
+    /// so concurrent increments never lose updates. Unlike a write, this keeps the deadline of
+    /// the key
+    pub fn incr_by(&self, key: SharedSlice, delta: i64) -> EncodingResult<Result<i64, IncrError>> {
+        self.check_key_encoding(&key)?;
+        self.access(&key);
+        let (old, new, value) = match self.data.entry(key.clone()) {
+            Entry::Occupied(mut entry) => {
+                let current = match core::str::from_utf8(entry.value())
+                    .ok()
+                    .and_then(|value| value.parse::<i64>().ok())
+                {
+                    Some(current) => current,
+                    None => return Ok(Err(IncrError::NotAnInteger)),
+                };
+                let new = match current.checked_add(delta) {
+                    Some(new) => new,
+                    None => return Ok(Err(IncrError::Overflow)),
+                };
+                let value = SharedSlice::from(new.to_string());
+                self.changed(&key, Some(entry.value()), Some(&value));
+                (Some(entry.insert(value.clone())), new, value)
+            }
+            Entry::Vacant(entry) => {
+                let value = SharedSlice::from(delta.to_string());
+                self.changed(&key, None, Some(&value));
+                entry.insert(value.clone());
+                (None, delta, value)
+            }
+        };
+        match old {
+            Some(old) => self.memory.resize(old.len(), value.len()),
+            None => {
+                self.expiry.remove(&key);
+                self.memory
+                    .inserted(&key, eviction::entry_size(&key, value.len()));
+            }
+        }
+        self.mark_dirty();
+        Ok(Ok(new))
+    }
+    /// Overwrite the bytes of the value of `key` starting at `offset` with `data`, padding the
+    /// value with zero bytes if it's shorter than `offset` (a missing key counts as an empty
+    /// value), and return the new length of the value. Nothing is written if `data` is empty.
+    /// Like [`KVEngine::incr_by`], this keeps the deadline of the key
+    pub fn set_range(&self, key: SharedSlice, offset: usize, data: &[u8]) -> EncodingResult<usize> {
+        self.check_key_encoding(&key)?;
+        self.access(&key);
+        if data.is_empty() {
+            return Ok(self.data.get(&key).map_or(0, |value| value.len()));
+        }
+        let patched = |current: &[u8]| {
+            let end = offset + data.len();
+            let mut value = current.to_vec();
+            if value.len() < end {
+                value.resize(end, 0);
+            }
+            value[offset..end].copy_from_slice(data);
+            value
+        };
+        let (old, value) = match self.data.entry(key.clone()) {
+            Entry::Occupied(mut entry) => {
+                let value = patched(entry.value());
+                // patching a string can split one of its characters
+                self.check_value_encoding(&value)?;
+                let value = SharedSlice::from(value);
+                self.changed(&key, Some(entry.value()), Some(&value));
+                (Some(entry.insert(value.clone())), value)
+            }
+            Entry::Vacant(entry) => {
+                let value = patched(&[]);
+                self.check_value_encoding(&value)?;
+                let value = SharedSlice::from(value);
+                self.changed(&key, None, Some(&value));
+                entry.insert(value.clone());
+                (None, value)
+            }
+        };
+        match old {
+            Some(old) => self.memory.resize(old.len(), value.len()),
+            None => {
+                self.expiry.remove(&key);
+                self.memory
+                    .inserted(&key, eviction::entry_size(&key, value.len()));
+            }
+        }
+        self.mark_dirty();
+        Ok(value.len())
+    }
+    /// Set or clear bit `offset` of the value of `key` (see [`bitmap`](self::bitmap)), padding
+    /// the value with zero bytes if it's too short (a missing key counts as an empty value), and
+    /// return the previous state of the bit. Nothing is written if the bit doesn't change. Like
+    /// [`KVEngine::set_range`], this keeps the deadline of the key
+    pub fn set_bit(&self, key: SharedSlice, offset: usize, bit: bool) -> EncodingResult<bool> {
+        self.check_key_encoding(&key)?;
+        self.access(&key);
+        let (index, mask) = bitmap::locate(offset);
+        let patched = |current: &[u8]| {
+            let mut value = current.to_vec();
+            if value.len() <= index {
+                value.resize(index + 1, 0);
+            }
+            if bit {
+                value[index] |= mask;
+            } else {
+                value[index] &= !mask;
+            }
+            value
+        };
+        let (old, value) = match self.data.entry(key.clone()) {
+            Entry::Occupied(mut entry) => {
+                let was_set = bitmap::get(entry.value(), offset);
+                if was_set == bit && index < entry.value().len() {
+                    return Ok(was_set);
+                }
+                let value = patched(entry.value());
+                // flipping a bit can break one of the characters of a string
+                self.check_value_encoding(&value)?;
+                let value = SharedSlice::from(value);
+                self.changed(&key, Some(entry.value()), Some(&value));
+                (Some(entry.insert(value.clone())), value)
+            }
+            Entry::Vacant(entry) => {
+                let value = patched(&[]);
+                self.check_value_encoding(&value)?;
+                let value = SharedSlice::from(value);
+                self.changed(&key, None, Some(&value));
+                entry.insert(value.clone());
+                (None, value)
+            }
+        };
+        let was_set = match old {
+            Some(old) => {
+                self.memory.resize(old.len(), value.len());
+                bitmap::get(&old, offset)
+            }
+            None => {
+                self.expiry.remove(&key);
+                self.memory
+                    .inserted(&key, eviction::entry_size(&key, value.len()));
+                false
+            }
+        };
+        self.mark_dirty();
+        Ok(was_set)
+    }
+    /// Returns the state of bit `offset` of the value of `key`, which is unset if the key
+    /// doesn't exist or its value is too short
+    pub fn get_bit<Q: AsRef<[u8]>>(&self, key: Q, offset: usize) -> EncodingResult<bool> {
+        self.check_key_encoding(key.as_ref())?;
+        Ok(self
+            .get_unchecked(key)
+            .is_some_and(|value| bitmap::get(&value, offset)))
+    }
+    /// Returns the number of set bits in the bytes of the value of `key` from `start` to `end`
+    /// (both inclusive, and negative offsets count from the end, like `GETRANGE`). A missing key
+    /// has no set bits
+    pub fn bit_count<Q: AsRef<[u8]>>(&self, key: Q, start: i64, end: i64) -> EncodingResult<usize> {
+        self.check_key_encoding(key.as_ref())?;
+        Ok(self.get_unchecked(key).map_or(0, |value| {
+            bitmap::popcount(&value[util::resolve_range(value.len(), start, end)])
+        }))
+    }
+    /// Add `elements` to the sketch (see [`hll`](self::hll)) held by `key`, which is created if
+    /// it doesn't exist. Returns whether the sketch was created or changed, or `None` if the value
+    /// of `key` isn't a sketch
+    pub fn pf_add<'e>(
+        &self,
+        key: SharedSlice,
+        elements: impl IntoIterator<Item = &'e [u8]>,
+    ) -> EncodingResult<Option<bool>> {
+        self.update_sketch(key, |sketch| {
+            elements
+                .into_iter()
+                .fold(false, |changed, element| sketch.add(element) | changed)
+        })
+    }
+    /// Returns the approximate number of distinct elements in the union of the sketches held by
+    /// `keys` (a missing key counts as an empty sketch), or `None` if one of the values isn't a
+    /// sketch
+    pub fn pf_count<'k>(
+        &self,
+        keys: impl IntoIterator<Item = &'k [u8]>,
+    ) -> EncodingResult<Option<u64>> {
+        Ok(self.read_sketches(keys)?.map(|union| union.count()))
+    }
+    /// Merge the sketches held by `sources` into the sketch held by `dest`, which is created if
+    /// it doesn't exist. Returns `None` (and writes nothing) if one of the values isn't a sketch
+    pub fn pf_merge<'k>(
+        &self,
+        dest: SharedSlice,
+        sources: impl IntoIterator<Item = &'k [u8]>,
+    ) -> EncodingResult<Option<()>> {
+        // the sources are read before `dest` is locked, since they can share its shard
+        let union = match self.read_sketches(sources)? {
+            Some(union) => union,
+            None => return Ok(None),
+        };
+        Ok(self
+            .update_sketch(dest, |sketch| sketch.merge(&union))?
+            .map(|_| ()))
+    }
+    /// Returns the union of the sketches held by `keys`, or `None` if one of the values isn't a
+    /// sketch
+    fn read_sketches<'k>(
+        &self,
+        keys: impl IntoIterator<Item = &'k [u8]>,
+    ) -> EncodingResult<Option<HyperLogLog>> {
+        let mut union = HyperLogLog::default();
+        for key in keys {
+            self.check_key_encoding(key)?;
+            if let Some(value) = self.get_unchecked(key) {
+                match HyperLogLog::decode(&value) {
+                    Some(sketch) => union.merge(&sketch),
+                    None => return Ok(None),
+                };
+            }
+        }
+        Ok(Some(union))
+    }
+    /// Run `update` on the sketch held by `key` (an empty one if the key doesn't exist) with the
+    /// key locked, and write it back if `update` returns true. Like [`KVEngine::incr_by`], this
+    /// keeps the deadline of the key
+    fn update_sketch(
+        &self,
+        key: SharedSlice,
+        update: impl FnOnce(&mut HyperLogLog) -> bool,
+    ) -> EncodingResult<Option<bool>> {
+        self.check_key_encoding(&key)?;
+        self.access(&key);
+        let (old, value) = match self.data.entry(key.clone()) {
+            Entry::Occupied(mut entry) => {
+                let mut sketch = match HyperLogLog::decode(entry.value()) {
+                    Some(sketch) => sketch,
+                    None => return Ok(None),
+                };
+                if !update(&mut sketch) {
+                    return Ok(Some(false));
+                }
+                let value = sketch.encode();
+                self.check_value_encoding(&value)?;
+                let value = SharedSlice::from(value);
+                self.changed(&key, Some(entry.value()), Some(&value));
+                (Some(entry.insert(value.clone())), value)
+            }
+            Entry::Vacant(entry) => {
+                let mut sketch = HyperLogLog::default();
+                update(&mut sketch);
+                let value = sketch.encode();
+                self.check_value_encoding(&value)?;
+                let value = SharedSlice::from(value);
+                self.changed(&key, None, Some(&value));
+                entry.insert(value.clone());
+                (None, value)
+            }
+        };
+        match old {
+            Some(old) => self.memory.resize(old.len(), value.len()),
+            None => {
+                self.expiry.remove(&key);
+                self.memory
+                    .inserted(&key, eviction::entry_size(&key, value.len()));
+            }
+        }
+        self.mark_dirty();
+        Ok(Some(true))
+    }
+    /// Returns the version of `key` (see [`version`](self::version)), or `None` if it doesn't
+    /// exist
+    pub fn version_of(&self, key: &[u8]) -> EncodingResult<Option<u64>> {
+        self.check_key_encoding(key)?;
+        self.access(key);
+        // hold on to the entry so that it can't be written to while we read its version
+        let entry = self.data.get(key);
+        Ok(entry.map(|_| self.versions.of(key)))
+    }
+    /// Set `key` to `value` if its version is `expected` (zero if the key must not exist), and
+    /// return its new version. Otherwise nothing is written and the current version (zero if the
+    /// key doesn't exist) is returned as the error. Like any other write, this removes the
+    /// deadline of the key
+    pub fn compare_and_swap(
+        &self,
+        key: SharedSlice,
+        expected: u64,
+        value: SharedSlice,
+    ) -> EncodingResult<Result<u64, u64>> {
+        self.check_key_encoding(&key)?;
+        self.check_value_encoding(&value)?;
+        self.access(&key);
+        let size = value.len();
+        let (old, version) = match self.data.entry(key.clone()) {
+            Entry::Occupied(mut entry) => {
+                let current = self.versions.of(&key);
+                if current != expected {
+                    return Ok(Err(current));
+                }
+                self.changed(&key, Some(entry.value()), Some(&value));
+                (Some(entry.insert(value)), self.versions.of(&key))
+            }
+            Entry::Vacant(entry) => {
+                if expected != 0 {
+                    return Ok(Err(0));
+                }
+                self.changed(&key, None, Some(&value));
+                // read it before the entry is unlocked
+                let version = self.versions.of(&key);
+                entry.insert(value);
+                (None, version)
+            }
+        };
+        self.expiry.remove(&key);
+        match old {
+            Some(old) => self.memory.resize(old.len(), size),
+            None => self.memory.inserted(&key, eviction::entry_size(&key, size)),
+        }
+        self.mark_dirty();
+        Ok(Ok(version))
+    }
+    /// Set `key` to `value` if its current value is `expected` (compared byte for byte with the
+    /// entry locked). Returns `None` if the key doesn't exist and `Some(false)` if its value
+    /// isn't the expected one, when nothing is written. Like any other write, this removes the
+    /// deadline of the key
+    pub fn compare_value_and_swap(
+        &self,
+        key: SharedSlice,
+        expected: &[u8],
+        value: SharedSlice,
+    ) -> EncodingResult<Option<bool>> {
+        self.check_key_encoding(&key)?;
+        self.check_value_encoding(&value)?;
+        self.access(&key);
+        let size = value.len();
+        let old = match self.data.entry(key.clone()) {
+            Entry::Occupied(mut entry) => {
+                if entry.value().as_slice() != expected {
+                    return Ok(Some(false));
+                }
+                self.changed(&key, Some(entry.value()), Some(&value));
+                entry.insert(value)
+            }
+            Entry::Vacant(_) => return Ok(None),
+        };
+        self.expiry.remove(&key);
+        self.memory.resize(old.len(), size);
+        self.mark_dirty();
+        Ok(Some(true))
+    }
+    /// Commit a transaction, making either all of its writes or none of them (see
+    /// [`txn`](self::txn)). The shards of all the keys in the transaction are locked while the
+    /// writes are checked and made
+    pub fn commit(&self, txn: Transaction) -> EncodingResult<Result<(), TxnError>> {
+        for write in txn.writes() {
+            self.check_key_encoding(write.key())?;
+            if let Some(value) = write.value() {
+                self.check_value_encoding(value)?;
+            }
+        }
+        // this brings archived values back, so that all the keys are in the shards we lock
+        for write in txn.writes() {
+            self.access(write.key());
+        }
+        let mut changes = Vec::with_capacity(txn.len());
+        {
+            let keys = txn.writes().iter().map(|write| write.key().as_slice());
+            let mut shards = self.data.lock_shards_of(keys);
+            // whether every key exists, as of the writes checked so far
+            let mut exists: HashMap<&[u8], bool> = HashMap::new();
+            for write in txn.writes() {
+                let key = write.key().as_slice();
+                let present = *exists
+                    .entry(key)
+                    .or_insert_with(|| shards.get(key).is_some());
+                match write {
+                    Write::Set(..) if present => return Ok(Err(TxnError::Exists)),
+                    Write::Update(..) | Write::Delete(_) if !present => {
+                        return Ok(Err(TxnError::Missing))
+                    }
+                    _ => {}
+                }
+                exists.insert(key, !matches!(write, Write::Delete(_)));
+            }
+            for write in txn.writes() {
+                let key = write.key();
+                let (old, new) = match write.value() {
+                    Some(value) => (shards.insert(key.clone(), value.clone()), Some(value)),
+                    None => (shards.remove(key.as_slice()).map(|(_, old)| old), None),
+                };
+                self.changed(key, old.as_ref(), new);
+                changes.push((key, old.map(|old| old.len()), new.map(|new| new.len())));
+            }
+        }
+        for (key, old, new) in changes {
+            match (old, new) {
+                (Some(old), Some(new)) => self.memory.resize(old, new),
+                (None, Some(new)) => self.memory.inserted(key, eviction::entry_size(key, new)),
+                (Some(old), None) => self.memory.removed(key, eviction::entry_size(key, old)),
+                (None, None) => {}
+            }
+            // like any other write, this removes the deadline of the key
+            self.expiry.remove(key.as_slice());
+        }
+        self.mark_dirty_if(!txn.is_empty());
+        Ok(Ok(()))
+    }
+    /// Returns the total size of the keys and values held in memory
+    pub fn hot_bytes(&self) -> u64 {
+        self.data
+            .iter()
+            .map(|kv| (kv.key().len() + kv.value().len()) as u64)
+            .sum()
+    }
+    /// Returns up to `count` keys, including archived keys
+    pub fn get_keys(&self, count: usize) -> Vec<SharedSlice> {
+        let mut keys = self.data.get_keys(count);
+        if keys.len() < count {
+            keys.extend(self.archive.get_keys(count - keys.len()));
+        }
+        keys
+    }
     /// Returns an encoder that checks each key and each value in turn
     /// Usual usage:
     /// ```notest
@@ -259,13 +1518,26 @@ impl KVEStandard {
 
 // list impls
 impl KVEListmap {
+    /// Returns the total size of the list names and their elements
+    pub fn hot_bytes(&self) -> u64 {
+        self.data
+            .iter()
+            .map(|kv| {
+                let elements: usize = kv.value().read().iter().map(|e| e.len()).sum();
+                (kv.key().len() + elements) as u64
+            })
+            .sum()
+    }
     #[cfg(test)]
     pub fn add_list(&self, listname: SharedSlice) -> EncodingResult<bool> {
         self.check_key_encoding(&listname)?;
-        Ok(self.data.true_if_insert(listname, LockedVec::new(vec![])))
+        let added = self.data.true_if_insert(listname, LockedVec::new(vec![]));
+        Ok(self.mark_dirty_if(added))
     }
     pub fn list_len(&self, listname: &[u8]) -> EncodingResult<Option<usize>> {
         self.check_key_encoding(listname)?;
+        self.record_hit(listname);
+        self.expire_if_due(listname);
         Ok(self.data.get(listname).map(|list| list.read().len()))
     }
     pub fn list_cloned(
@@ -274,6 +1546,8 @@ impl KVEListmap {
         count: usize,
     ) -> EncodingResult<Option<Vec<SharedSlice>>> {
         self.check_key_encoding(listname)?;
+        self.record_hit(listname);
+        self.expire_if_due(listname);
         Ok(self
             .data
             .get(listname)
@@ -281,14 +1555,622 @@ impl KVEListmap {
     }
     pub fn list_cloned_full(&self, listname: &[u8]) -> EncodingResult<Option<Vec<SharedSlice>>> {
         self.check_key_encoding(listname)?;
+        self.record_hit(listname);
+        self.expire_if_due(listname);
         Ok(self
             .data
             .get(listname)
             .map(|list| list.read().iter().cloned().collect()))
     }
+    /// Returns a reference to the list bounds for this table
+    pub fn list_bounds(&self) -> &ListBounds {
+        &self.bounds
+    }
+    /// Replace the list bounds for this table (used when the table is restored from disk)
+    pub fn restore_list_bounds(&mut self, bounds: ListBounds) {
+        self.bounds = bounds;
+    }
+    /// Returns the bound of `listname`: `None` if the list doesn't exist and `Some(None)` if it
+    /// doesn't have one
+    pub fn list_bound(&self, listname: &[u8]) -> EncodingResult<Option<Option<usize>>> {
+        self.check_key_encoding(listname)?;
+        self.expire_if_due(listname);
+        Ok(self
+            .data
+            .contains_key(listname)
+            .then(|| self.bounds.get(listname)))
+    }
+    /// Bound `listname` to `bound` elements (or remove its bound if `bound` is `None`),
+    /// dropping its oldest elements if it's already longer. Returns false if the list doesn't
+    /// exist
+    pub fn set_list_bound(&self, listname: &[u8], bound: Option<usize>) -> EncodingResult<bool> {
+        self.check_key_encoding(listname)?;
+        self.expire_if_due(listname);
+        let list = match self.data.get(listname) {
+            Some(list) => list,
+            None => return Ok(false),
+        };
+        // holding the lock on the list keeps a push from slipping in between
+        let mut wlock = list.write();
+        match bound {
+            Some(bound) => {
+                self.bounds.set(SharedSlice::new(listname), bound);
+                self.enforce_bound(listname, &mut wlock);
+            }
+            None => {
+                self.bounds.remove(listname);
+            }
+        }
+        self.mark_dirty();
+        Ok(true)
+    }
+    /// Drop the oldest elements of `list` (the list `listname`, which has to be write-locked)
+    /// until it fits in its bound. Returns the number of elements that were dropped. Anyone who
+    /// grows a list without going through the engine has to call this
+    pub fn enforce_bound(&self, listname: &[u8], list: &mut Vec<SharedSlice>) -> usize {
+        let excess = match self.bounds.get(listname) {
+            Some(bound) if list.len() > bound => list.len() - bound,
+            _ => return 0,
+        };
+        let freed = list
+            .drain(..excess)
+            .map(|element| eviction::element_size(&element))
+            .sum();
+        self.memory.release(freed);
+        excess
+    }
+    /// Keep only the elements of `listname` from `start` to `stop` (both inclusive, and
+    /// counting from the end of the list if they're negative). Returns the number of elements
+    /// that were removed, or `None` if the list doesn't exist
+    pub fn trim_list(
+        &self,
+        listname: &[u8],
+        start: i64,
+        stop: i64,
+    ) -> EncodingResult<Option<usize>> {
+        self.check_key_encoding(listname)?;
+        self.expire_if_due(listname);
+        let list = match self.data.get(listname) {
+            Some(list) => list,
+            None => return Ok(None),
+        };
+        let mut wlock = list.write();
+        let keep = util::resolve_range(wlock.len(), start, stop);
+        let removed = wlock.len() - keep.len();
+        if removed != 0 {
+            let size = |element: SharedSlice| eviction::element_size(&element);
+            let freed: usize = wlock.drain(keep.end..).map(size).sum();
+            self.memory
+                .release(freed + wlock.drain(..keep.start).map(size).sum::<usize>());
+            self.mark_dirty();
+        }
+        Ok(Some(removed))
+    }
+    /// Insert `value` into `listname` at `index` (where an `index` equal to the length of the
+    /// list appends it), dropping the oldest elements of the list if that takes it past its
+    /// bound. Returns the new length of the list: `None` if the list doesn't exist and
+    /// `Some(None)` if `index` is out of bounds
+    pub fn list_insert(
+        &self,
+        listname: &[u8],
+        index: usize,
+        value: &[u8],
+    ) -> EncodingResult<Option<Option<usize>>> {
+        self.check_key_encoding(listname)?;
+        self.check_value_encoding(value)?;
+        self.expire_if_due(listname);
+        let list = match self.data.get(listname) {
+            Some(list) => list,
+            None => return Ok(None),
+        };
+        let mut wlock = list.write();
+        if index > wlock.len() {
+            return Ok(Some(None));
+        }
+        wlock.insert(index, SharedSlice::new(value));
+        self.memory.charge(eviction::element_size(value));
+        self.enforce_bound(listname, &mut wlock);
+        self.mark_dirty();
+        Ok(Some(Some(wlock.len())))
+    }
+    /// Remove up to `count` elements of `listname` that are equal to `value`, starting from the
+    /// front of the list if `count` is positive and from the back if it's negative. A `count`
+    /// of 0 removes all of them. Returns the number of elements that were removed, or `None` if
+    /// the list doesn't exist
+    pub fn list_remove(
+        &self,
+        listname: &[u8],
+        value: &[u8],
+        count: i64,
+    ) -> EncodingResult<Option<usize>> {
+        self.check_key_encoding(listname)?;
+        self.check_value_encoding(value)?;
+        self.expire_if_due(listname);
+        let list = match self.data.get(listname) {
+            Some(list) => list,
+            None => return Ok(None),
+        };
+        let mut wlock = list.write();
+        // the positions of the elements to remove, in order
+        let mut positions: Vec<usize> = wlock
+            .iter()
+            .enumerate()
+            .filter(|(_, element)| element.as_ref() == value)
+            .map(|(position, _)| position)
+            .collect();
+        let limit = count.unsigned_abs() as usize;
+        if count > 0 {
+            positions.truncate(limit);
+        } else if count < 0 {
+            positions.drain(..positions.len().saturating_sub(limit));
+        }
+        let removed = positions.len();
+        if removed != 0 {
+            let mut positions = positions.into_iter().peekable();
+            let mut position = 0;
+            wlock.retain(|_| {
+                let remove = positions.next_if_eq(&position).is_some();
+                position += 1;
+                !remove
+            });
+            self.memory.release(removed * eviction::element_size(value));
+            self.mark_dirty();
+        }
+        Ok(Some(removed))
+    }
+}
+
+// map impls
+impl KVEMap {
+    /// Returns the total size of the map names, their fields and their values
+    pub fn hot_bytes(&self) -> u64 {
+        self.data
+            .iter()
+            .map(|kv| {
+                let fields: usize = kv
+                    .value()
+                    .read()
+                    .iter()
+                    .map(|(f, v)| f.len() + v.len())
+                    .sum();
+                (kv.key().len() + fields) as u64
+            })
+            .sum()
+    }
+    /// Set the given fields of the map `key`, creating the map if it doesn't exist. Returns the
+    /// number of fields that were added (and not just updated)
+    pub fn map_set(
+        &self,
+        key: SharedSlice,
+        pairs: Vec<(SharedSlice, SharedSlice)>,
+    ) -> EncodingResult<usize> {
+        self.check_key_encoding(&key)?;
+        if !pairs
+            .iter()
+            .all(|(f, v)| self._check_encoding(f, self.e_v) && self._check_encoding(v, self.e_v))
+        {
+            return Err(());
+        }
+        self.access(&key);
+        let map = loop {
+            if let Some(map) = self.data.get(&key) {
+                break map;
+            }
+            if let Some(entry) = self.data.fresh_entry(key.clone()) {
+                self.expiry.remove(&key);
+                entry.insert(LockedMap::default());
+                self.memory.inserted(&key, eviction::entry_size(&key, 0));
+            }
+        };
+        let (mut added, mut grown, mut freed) = (0, 0, 0);
+        {
+            let mut wmap = map.write();
+            for (field, value) in pairs {
+                grown += eviction::field_size(&field, &value);
+                match wmap.get_mut(&field) {
+                    Some(slot) => {
+                        freed += eviction::field_size(&field, slot);
+                        *slot = value;
+                    }
+                    None => {
+                        wmap.insert(field, value);
+                        added += 1;
+                    }
+                }
+            }
+        }
+        self.memory.resize(freed, grown);
+        self.mark_dirty();
+        Ok(added)
+    }
+    /// Returns the value of `field` in the map `key`. The outer option is `None` if the map
+    /// doesn't exist
+    pub fn map_get(&self, key: &[u8], field: &[u8]) -> EncodingResult<Option<Option<SharedSlice>>> {
+        self.check_key_encoding(key)?;
+        self.access(key);
+        Ok(self.data.get(key).map(|map| map.read().get(field).cloned()))
+    }
+    /// Remove the given fields from the map `key`, and the map itself if it ends up empty.
+    /// Returns the number of fields that were removed or `None` if the map doesn't exist
+    pub fn map_del<'a>(
+        &self,
+        key: &[u8],
+        fields: impl Iterator<Item = &'a [u8]>,
+    ) -> EncodingResult<Option<usize>> {
+        self.check_key_encoding(key)?;
+        self.access(key);
+        let (removed, freed) = match self.data.get(key) {
+            Some(map) => {
+                let mut wmap = map.write();
+                fields.filter_map(|field| wmap.remove_entry(field)).fold(
+                    (0, 0),
+                    |(removed, freed), (field, value)| {
+                        (removed + 1, freed + eviction::field_size(&field, &value))
+                    },
+                )
+            }
+            None => return Ok(None),
+        };
+        if removed != 0 {
+            self.memory.release(freed);
+            if let Some((key, _)) = self.data.remove_if(key, |_, map| map.read().is_empty()) {
+                self.expiry.remove(&key);
+                self.memory.removed(&key, eviction::entry_size(&key, 0));
+            }
+            self.mark_dirty();
+        }
+        Ok(Some(removed))
+    }
+    /// Returns all the fields of the map `key` along with their values
+    pub fn map_cloned_full(
+        &self,
+        key: &[u8],
+    ) -> EncodingResult<Option<Vec<(SharedSlice, SharedSlice)>>> {
+        self.check_key_encoding(key)?;
+        self.access(key);
+        Ok(self.data.get(key).map(|map| {
+            map.read()
+                .iter()
+                .map(|(f, v)| (f.clone(), v.clone()))
+                .collect()
+        }))
+    }
+}
+
+// set impls
+impl KVESet {
+    /// Returns the total size of the set names and their members
+    pub fn hot_bytes(&self) -> u64 {
+        self.data
+            .iter()
+            .map(|kv| {
+                let members: usize = kv.value().read().iter().map(|m| m.len()).sum();
+                (kv.key().len() + members) as u64
+            })
+            .sum()
+    }
+    /// Add the given members to the set `key`, creating the set if it doesn't exist. Returns
+    /// the number of members that weren't already in the set
+    pub fn set_add(&self, key: SharedSlice, members: Vec<SharedSlice>) -> EncodingResult<usize> {
+        self.check_key_encoding(&key)?;
+        if !members.iter().all(|m| self._check_encoding(m, self.e_v)) {
+            return Err(());
+        }
+        self.access(&key);
+        let set = loop {
+            if let Some(set) = self.data.get(&key) {
+                break set;
+            }
+            if let Some(entry) = self.data.fresh_entry(key.clone()) {
+                self.expiry.remove(&key);
+                entry.insert(LockedSet::default());
+                self.memory.inserted(&key, eviction::entry_size(&key, 0));
+            }
+        };
+        let (mut added, mut grown) = (0, 0);
+        {
+            let mut wset = set.write();
+            for member in members {
+                let size = eviction::element_size(&member);
+                if wset.insert(member) {
+                    added += 1;
+                    grown += size;
+                }
+            }
+        }
+        self.memory.charge(grown);
+        self.mark_dirty_if(added != 0);
+        Ok(added)
+    }
+    /// Remove the given members from the set `key`, and the set itself if it ends up empty.
+    /// Returns the number of members that were removed or `None` if the set doesn't exist
+    pub fn set_remove<'a>(
+        &self,
+        key: &[u8],
+        members: impl Iterator<Item = &'a [u8]>,
+    ) -> EncodingResult<Option<usize>> {
+        self.check_key_encoding(key)?;
+        self.access(key);
+        let (removed, freed) = match self.data.get(key) {
+            Some(set) => {
+                let mut wset = set.write();
+                members.filter_map(|member| wset.take(member)).fold(
+                    (0, 0),
+                    |(removed, freed), member| {
+                        (removed + 1, freed + eviction::element_size(&member))
+                    },
+                )
+            }
+            None => return Ok(None),
+        };
+        if removed != 0 {
+            self.memory.release(freed);
+            if let Some((key, _)) = self.data.remove_if(key, |_, set| set.read().is_empty()) {
+                self.expiry.remove(&key);
+                self.memory.removed(&key, eviction::entry_size(&key, 0));
+            }
+            self.mark_dirty();
+        }
+        Ok(Some(removed))
+    }
+    /// Returns true if `member` is in the set `key`
+    pub fn set_contains(&self, key: &[u8], member: &[u8]) -> EncodingResult<bool> {
+        self.check_key_encoding(key)?;
+        self.access(key);
+        Ok(self
+            .data
+            .get(key)
+            .is_some_and(|set| set.read().contains(member)))
+    }
+    /// Returns the members of the set `key`
+    pub fn set_members(&self, key: &[u8]) -> EncodingResult<Option<Vec<SharedSlice>>> {
+        self.check_key_encoding(key)?;
+        self.access(key);
+        Ok(self
+            .data
+            .get(key)
+            .map(|set| set.read().iter().cloned().collect()))
+    }
+    /// Returns the members that are in any of the sets `keys`. Sets that don't exist are empty
+    pub fn set_union<'a>(
+        &self,
+        keys: impl Iterator<Item = &'a [u8]>,
+    ) -> EncodingResult<Vec<SharedSlice>> {
+        let mut union = HashSet::new();
+        for key in keys {
+            self.check_key_encoding(key)?;
+            self.access(key);
+            if let Some(set) = self.data.get(key) {
+                union.extend(set.read().iter().cloned());
+            }
+        }
+        Ok(union.into_iter().collect())
+    }
+    /// Returns the members that are in all of the sets `keys`. Sets that don't exist are empty
+    pub fn set_intersection<'a>(
+        &self,
+        mut keys: impl Iterator<Item = &'a [u8]>,
+    ) -> EncodingResult<Vec<SharedSlice>> {
+        let mut intersection: HashSet<SharedSlice> = match keys.next() {
+            Some(key) => match self.set_members(key)? {
+                Some(members) => members.into_iter().collect(),
+                None => HashSet::new(),
+            },
+            None => return Ok(Vec::new()),
+        };
+        for key in keys {
+            self.check_key_encoding(key)?;
+            self.access(key);
+            match self.data.get(key) {
+                Some(set) => {
+                    let rset = set.read();
+                    intersection.retain(|member| rset.contains(member));
+                }
+                None => intersection.clear(),
+            }
+        }
+        Ok(intersection.into_iter().collect())
+    }
+}
+
+// sorted set impls
+impl KVESortedSet {
+    /// Returns the total size of the sorted set names, their members and scores
+    pub fn hot_bytes(&self) -> u64 {
+        self.data
+            .iter()
+            .map(|kv| {
+                let zset = kv.value().read();
+                let members: usize = zset.iter().map(|(m, _)| m.len()).sum();
+                (kv.key().len() + members + zset.len() * core::mem::size_of::<f64>()) as u64
+            })
+            .sum()
+    }
+    /// Set the scores of the given members of the sorted set `key`, creating the sorted set
+    /// if it doesn't exist. Returns the number of members that weren't already in the set
+    pub fn zset_add(
+        &self,
+        key: SharedSlice,
+        members: Vec<(SharedSlice, f64)>,
+    ) -> EncodingResult<usize> {
+        self.check_key_encoding(&key)?;
+        if !members
+            .iter()
+            .all(|(m, _)| self._check_encoding(m, self.e_v))
+        {
+            return Err(());
+        }
+        self.access(&key);
+        let zset = loop {
+            if let Some(zset) = self.data.get(&key) {
+                break zset;
+            }
+            if let Some(entry) = self.data.fresh_entry(key.clone()) {
+                self.expiry.remove(&key);
+                entry.insert(LockedSortedSet::default());
+                self.memory.inserted(&key, eviction::entry_size(&key, 0));
+            }
+        };
+        let (mut added, mut grown, mut changed) = (0, 0, false);
+        {
+            let mut wzset = zset.write();
+            for (member, score) in members {
+                let size = eviction::scored_size(&member);
+                match wzset.insert(member, score) {
+                    Some(old) => changed |= old != score,
+                    None => {
+                        added += 1;
+                        grown += size;
+                        changed = true;
+                    }
+                }
+            }
+        }
+        self.memory.charge(grown);
+        self.mark_dirty_if(changed);
+        Ok(added)
+    }
+    /// Remove the given members from the sorted set `key`, and the sorted set itself if it
+    /// ends up empty. Returns the number of members that were removed or `None` if the sorted
+    /// set doesn't exist
+    pub fn zset_remove<'a>(
+        &self,
+        key: &[u8],
+        members: impl Iterator<Item = &'a [u8]>,
+    ) -> EncodingResult<Option<usize>> {
+        self.check_key_encoding(key)?;
+        self.access(key);
+        let (removed, freed) = match self.data.get(key) {
+            Some(zset) => {
+                let mut wzset = zset.write();
+                members.filter_map(|member| wzset.remove(member)).fold(
+                    (0, 0),
+                    |(removed, freed), (member, _)| {
+                        (removed + 1, freed + eviction::scored_size(&member))
+                    },
+                )
+            }
+            None => return Ok(None),
+        };
+        if removed != 0 {
+            self.memory.release(freed);
+            if let Some((key, _)) = self.data.remove_if(key, |_, zset| zset.read().is_empty()) {
+                self.expiry.remove(&key);
+                self.memory.removed(&key, eviction::entry_size(&key, 0));
+            }
+            self.mark_dirty();
+        }
+        Ok(Some(removed))
+    }
+    /// Returns the score of `member` in the sorted set `key`
+    pub fn zset_score(&self, key: &[u8], member: &[u8]) -> EncodingResult<Option<f64>> {
+        self.check_key_encoding(key)?;
+        self.access(key);
+        Ok(self
+            .data
+            .get(key)
+            .and_then(|zset| zset.read().score(member)))
+    }
+    /// Returns the rank of `member` in the sorted set `key`, counting from the lowest score
+    pub fn zset_rank(&self, key: &[u8], member: &[u8]) -> EncodingResult<Option<usize>> {
+        self.check_key_encoding(key)?;
+        self.access(key);
+        Ok(self.data.get(key).and_then(|zset| zset.read().rank(member)))
+    }
+    /// Returns the number of members in the sorted set `key` (zero if it doesn't exist)
+    pub fn zset_len(&self, key: &[u8]) -> EncodingResult<usize> {
+        self.check_key_encoding(key)?;
+        self.access(key);
+        Ok(self.data.get(key).map_or(0, |zset| zset.read().len()))
+    }
+    /// Returns the members of the sorted set `key` with a rank in `start..=stop`. Negative
+    /// ranks count from the highest score (so that `-1` is the last member)
+    pub fn zset_range(
+        &self,
+        key: &[u8],
+        start: i64,
+        stop: i64,
+    ) -> EncodingResult<Option<Vec<SharedSlice>>> {
+        self.check_key_encoding(key)?;
+        self.access(key);
+        Ok(self.data.get(key).map(|zset| {
+            let rzset = zset.read();
+            let len = rzset.len() as i64;
+            let resolve = |rank: i64| if rank < 0 { len + rank } else { rank };
+            let (start, stop) = (resolve(start).max(0), resolve(stop).min(len - 1));
+            if start > stop {
+                Vec::new()
+            } else {
+                rzset.range_by_rank(start as usize..=stop as usize)
+            }
+        }))
+    }
+    /// Returns the members of the sorted set `key` with a score in `min..=max`
+    pub fn zset_range_by_score(
+        &self,
+        key: &[u8],
+        min: f64,
+        max: f64,
+    ) -> EncodingResult<Option<Vec<SharedSlice>>> {
+        self.check_key_encoding(key)?;
+        self.access(key);
+        Ok(self
+            .data
+            .get(key)
+            .map(|zset| zset.read().range_by_score(min, max)))
+    }
+}
+
+// document impls
+impl KVEDocument {
+    /// Returns the total size of the document names and (approximately) their documents
+    pub fn hot_bytes(&self) -> u64 {
+        self.data
+            .iter()
+            .map(|kv| (kv.key().len() + kv.value().read().footprint()) as u64)
+            .sum()
+    }
+    /// Set the value at `path` in the document `key`. Setting the document itself (at `$`)
+    /// creates the document if it doesn't exist, while any other path needs an existing
+    /// document
+    pub fn doc_set(
+        &self,
+        key: SharedSlice,
+        path: &Path,
+        value: Json,
+    ) -> EncodingResult<Result<(), SetError>> {
+        self.check_key_encoding(&key)?;
+        if path.is_root() {
+            self.upsert_unchecked(key, LockedDocument::new(value));
+            return Ok(Ok(()));
+        }
+        self.access(&key);
+        let size = value.footprint();
+        let old = match self.data.get(&key) {
+            Some(doc) => match doc.write().set(path, value) {
+                Ok(old) => old,
+                Err(e) => return Ok(Err(e)),
+            },
+            None => return Ok(Err(SetError::NoDocument)),
+        };
+        self.memory
+            .resize(old.as_ref().map_or(0, Json::footprint), size);
+        self.mark_dirty();
+        Ok(Ok(()))
+    }
+    /// Returns the value at `path` in the document `key` as JSON text, or `None` if either
+    /// doesn't exist
+    pub fn doc_get(&self, key: &[u8], path: &Path) -> EncodingResult<Option<String>> {
+        self.check_key_encoding(key)?;
+        self.access(key);
+        Ok(self
+            .data
+            .get(key)
+            .and_then(|doc| doc.read().get(path).map(Json::render)))
+    }
 }
 
-impl<T> Default for KVEngine<T> {
+impl<T: KVEValue> Default for KVEngine<T> {
     fn default() -> Self {
         Self::init(false, false)
     }
@@ -0,0 +1,114 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Keyspace quotas
+//!
+//! A keyspace can cap the number of keys and the (approximate) memory taken by the entries of
+//! its tables, so that a tenant mapped to a keyspace can't take over the node. Every table in
+//! the keyspace counts towards the same [`Quota`] through its memory tracker, using the same
+//! estimates as `maxmemory`. Like `maxmemory`, the quota is checked before a write that
+//! allocates is run, so a write can take a keyspace slightly past its quota; once the quota
+//! has been hit, writes that allocate fail with `err-quota-exceeded` until some keys are
+//! removed.
+
+use {
+    super::eviction,
+    core::sync::atomic::{AtomicU64, Ordering},
+};
+
+#[derive(Debug, Default)]
+/// The quota of a keyspace and the usage counted towards it
+pub struct Quota {
+    /// the maximum number of keys (zero if there is no limit)
+    max_keys: AtomicU64,
+    /// the maximum number of bytes (zero if there is no limit)
+    max_bytes: AtomicU64,
+    /// the number of keys across the tables of the keyspace
+    keys: AtomicU64,
+    /// the approximate memory taken by the entries of the tables of the keyspace
+    bytes: AtomicU64,
+}
+
+impl Quota {
+    /// Returns the maximum number of keys (zero if there is no limit)
+    pub fn max_keys(&self) -> u64 {
+        self.max_keys.load(Ordering::Acquire)
+    }
+    /// Returns the maximum number of bytes (zero if there is no limit)
+    pub fn max_bytes(&self) -> u64 {
+        self.max_bytes.load(Ordering::Acquire)
+    }
+    /// Set the maximum number of keys (zero for no limit)
+    pub fn set_max_keys(&self, max_keys: u64) {
+        self.max_keys.store(max_keys, Ordering::Release)
+    }
+    /// Set the maximum number of bytes (zero for no limit)
+    pub fn set_max_bytes(&self, max_bytes: u64) {
+        self.max_bytes.store(max_bytes, Ordering::Release)
+    }
+    /// Returns the number of keys counted towards the quota
+    pub fn keys(&self) -> u64 {
+        self.keys.load(Ordering::Acquire)
+    }
+    /// Returns the number of bytes counted towards the quota
+    pub fn bytes(&self) -> u64 {
+        self.bytes.load(Ordering::Acquire)
+    }
+    /// Count `keys` more keys and `bytes` more bytes towards the quota
+    pub fn charge(&self, keys: u64, bytes: u64) {
+        self.keys.fetch_add(keys, Ordering::AcqRel);
+        self.bytes.fetch_add(bytes, Ordering::AcqRel);
+    }
+    /// Count `keys` fewer keys and `bytes` fewer bytes towards the quota
+    pub fn release(&self, keys: u64, bytes: u64) {
+        eviction::saturating_release(&self.keys, keys);
+        eviction::saturating_release(&self.bytes, bytes);
+    }
+    /// Returns true if either limit has been hit
+    pub fn is_over_limit(&self) -> bool {
+        let (max_keys, max_bytes) = (self.max_keys(), self.max_bytes());
+        (max_keys != 0 && self.keys() >= max_keys) || (max_bytes != 0 && self.bytes() >= max_bytes)
+    }
+}
+
+#[test]
+fn test_quota() {
+    let quota = Quota::default();
+    quota.charge(10, 1_000);
+    // there's no limit by default
+    assert!(!quota.is_over_limit());
+    quota.set_max_keys(10);
+    assert!(quota.is_over_limit());
+    quota.release(1, 100);
+    assert!(!quota.is_over_limit());
+    quota.set_max_bytes(900);
+    assert!(quota.is_over_limit());
+    quota.set_max_bytes(0);
+    // usage never goes below zero
+    quota.release(100, 10_000);
+    assert_eq!((quota.keys(), quota.bytes()), (0, 0));
+    assert!(!quota.is_over_limit());
+}
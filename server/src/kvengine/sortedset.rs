@@ -0,0 +1,207 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Sorted sets
+//!
+//! A sorted set keeps its members ordered by their scores (and members with the same score by
+//! their bytes). Every member is kept twice: in a hash map for score lookups and in a B-tree
+//! for ordered scans, so that ranges by rank or by score don't need a sort. Scores are `f64`s
+//! ordered with [`f64::total_cmp`]; `NaN` is never accepted as a score.
+
+use {
+    crate::corestore::SharedSlice,
+    core::{cmp::Ordering, ops::RangeInclusive},
+    std::collections::{BTreeSet, HashMap},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+/// A member of a sorted set along with its score, ordered by the score and then the member
+struct Ranked {
+    score: f64,
+    member: SharedSlice,
+}
+
+impl Eq for Ranked {}
+
+impl PartialOrd for Ranked {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Ranked {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.score
+            .total_cmp(&other.score)
+            .then_with(|| self.member.as_slice().cmp(other.member.as_slice()))
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+/// A set of members ordered by their scores
+pub struct SortedSet {
+    scores: HashMap<SharedSlice, f64>,
+    order: BTreeSet<Ranked>,
+}
+
+impl PartialEq for SortedSet {
+    fn eq(&self, other: &Self) -> bool {
+        self.scores == other.scores
+    }
+}
+
+impl SortedSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Returns the number of members
+    pub fn len(&self) -> usize {
+        self.scores.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.scores.is_empty()
+    }
+    /// Set the score of `member`, adding it if it isn't present. Returns the previous score
+    pub fn insert(&mut self, member: SharedSlice, score: f64) -> Option<f64> {
+        debug_assert!(!score.is_nan());
+        let old = self.scores.insert(member.clone(), score);
+        if let Some(old) = old {
+            self.order.remove(&Ranked {
+                score: old,
+                member: member.clone(),
+            });
+        }
+        self.order.insert(Ranked { score, member });
+        old
+    }
+    /// Remove `member`, returning it along with its score
+    pub fn remove(&mut self, member: &[u8]) -> Option<(SharedSlice, f64)> {
+        let (member, score) = self.scores.remove_entry(member)?;
+        self.order.remove(&Ranked {
+            score,
+            member: member.clone(),
+        });
+        Some((member, score))
+    }
+    /// Returns the score of `member`
+    pub fn score(&self, member: &[u8]) -> Option<f64> {
+        self.scores.get(member).copied()
+    }
+    /// Returns the (zero-based) rank of `member`, counting from the lowest score
+    pub fn rank(&self, member: &[u8]) -> Option<usize> {
+        let (member, score) = self.scores.get_key_value(member)?;
+        let ranked = Ranked {
+            score: *score,
+            member: member.clone(),
+        };
+        Some(self.order.range(..ranked).count())
+    }
+    /// Returns the members (in order) along with their scores
+    pub fn iter(&self) -> impl Iterator<Item = (&SharedSlice, f64)> {
+        self.order.iter().map(|r| (&r.member, r.score))
+    }
+    /// Returns the members with a rank in `ranks` (in order)
+    pub fn range_by_rank(&self, ranks: RangeInclusive<usize>) -> Vec<SharedSlice> {
+        let (start, end) = ranks.into_inner();
+        if start > end {
+            return Vec::new();
+        }
+        self.order
+            .iter()
+            .skip(start)
+            .take(end - start + 1)
+            .map(|r| r.member.clone())
+            .collect()
+    }
+    /// Returns the members with a score in `min..=max` (in order)
+    pub fn range_by_score(&self, min: f64, max: f64) -> Vec<SharedSlice> {
+        self.order
+            .iter()
+            .skip_while(|r| r.score < min)
+            .take_while(|r| r.score <= max)
+            .map(|r| r.member.clone())
+            .collect()
+    }
+}
+
+impl FromIterator<(SharedSlice, f64)> for SortedSet {
+    fn from_iter<I: IntoIterator<Item = (SharedSlice, f64)>>(iter: I) -> Self {
+        let mut set = Self::new();
+        for (member, score) in iter {
+            set.insert(member, score);
+        }
+        set
+    }
+}
+
+/// Parse a score, rejecting anything that isn't a number (including `NaN`)
+pub fn parse_score(score: &[u8]) -> Option<f64> {
+    core::str::from_utf8(score)
+        .ok()
+        .and_then(|score| score.parse::<f64>().ok())
+        .filter(|score| !score.is_nan())
+}
+
+#[test]
+fn test_sorted_set_order() {
+    let mut set: SortedSet = [
+        ("carol".into(), 30.0),
+        ("alice".into(), 10.0),
+        ("bob".into(), 20.0),
+        ("dave".into(), 20.0),
+    ]
+    .into_iter()
+    .collect();
+    assert_eq!(set.len(), 4);
+    assert_eq!(set.rank(b"alice"), Some(0));
+    // equal scores are ordered by the member
+    assert_eq!(set.rank(b"dave"), Some(2));
+    assert_eq!(set.rank(b"nobody"), None);
+    // moving a member keeps a single copy of it
+    assert_eq!(set.insert("alice".into(), 40.0), Some(10.0));
+    assert_eq!(set.len(), 4);
+    assert_eq!(set.rank(b"alice"), Some(3));
+    assert_eq!(
+        set.range_by_rank(0..=1),
+        ["bob", "dave"].map(SharedSlice::from)
+    );
+    assert_eq!(
+        set.range_by_score(20.0, 30.0),
+        ["bob", "dave", "carol"].map(SharedSlice::from)
+    );
+    assert!(set.range_by_score(31.0, 39.0).is_empty());
+    assert_eq!(set.remove(b"bob"), Some(("bob".into(), 20.0)));
+    assert_eq!(set.score(b"bob"), None);
+    assert_eq!(set.rank(b"carol"), Some(1));
+}
+
+#[test]
+fn test_parse_score() {
+    assert_eq!(parse_score(b"1.5"), Some(1.5));
+    assert_eq!(parse_score(b"-inf"), Some(f64::NEG_INFINITY));
+    assert_eq!(parse_score(b"NaN"), None);
+    assert_eq!(parse_score(b"ten"), None);
+}
@@ -24,7 +24,7 @@
  *
 */
 
-use super::{KVEStandard, SharedSlice};
+use super::{KVEListmap, KVEStandard, SharedSlice};
 
 #[test]
 fn test_ignore_encoding() {
@@ -109,3 +109,827 @@ fn test_encoder_validate_with_non_unicode() {
     let encoder = tbl.get_double_encoder();
     assert!(!encoder("hello".as_bytes(), b"Hello \xF0\x90\x80World"));
 }
+
+#[test]
+fn test_mutation_counter() {
+    let tbl = KVEStandard::default();
+    assert!(tbl.set("a".into(), "1".into()).unwrap());
+    assert_eq!(tbl.mutations(), 1);
+    // failed writes and reads don't count
+    assert!(!tbl.set("a".into(), "2".into()).unwrap());
+    assert!(!tbl.update("b".into(), "2".into()).unwrap());
+    assert!(!tbl.remove("b").unwrap());
+    assert!(tbl.get("a").unwrap().is_some());
+    assert_eq!(tbl.mutations(), 1);
+    assert!(tbl.update("a".into(), "2".into()).unwrap());
+    tbl.upsert("b".into(), "3".into()).unwrap();
+    assert!(tbl.pop("b").unwrap().is_some());
+    tbl.truncate_table();
+    assert_eq!(tbl.mutations(), 5);
+}
+
+#[test]
+fn test_key_expiry() {
+    let tbl = KVEStandard::default();
+    assert!(tbl.set("a".into(), "1".into()).unwrap());
+    assert!(tbl.set("b".into(), "2".into()).unwrap());
+    // missing keys can't expire
+    assert!(!tbl.set_expiry("c", 60_000).unwrap());
+    assert_eq!(tbl.ttl("c").unwrap(), None);
+    assert_eq!(tbl.ttl("a").unwrap(), Some(None));
+    assert!(tbl.set_expiry("a", 60_000).unwrap());
+    assert!(tbl.ttl("a").unwrap().unwrap().unwrap() > 59_000);
+    // PERSIST removes the deadline, and so does a write
+    assert!(tbl.persist("a").unwrap());
+    assert!(!tbl.persist("a").unwrap());
+    assert!(tbl.set_expiry("a", 60_000).unwrap());
+    assert!(tbl.update("a".into(), "3".into()).unwrap());
+    assert_eq!(tbl.ttl("a").unwrap(), Some(None));
+    // a key whose deadline has passed is gone the next time it's accessed
+    tbl.expiry().set("a".into(), 0);
+    assert!(tbl.get("a").unwrap().is_none());
+    // and the sweeper removes the rest
+    tbl.expiry().set("b".into(), 0);
+    assert_eq!(tbl.expire_due(super::expiry::now_ms(), 10), 1);
+    assert_eq!(tbl.len(), 0);
+    assert!(tbl.expiry().is_empty());
+    // a `SET` that doesn't write anything leaves the deadline alone
+    assert!(tbl.set("a".into(), "1".into()).unwrap());
+    assert!(tbl.set_expiry("a", 60_000).unwrap());
+    assert!(!tbl.set("a".into(), "2".into()).unwrap());
+    assert_eq!(tbl.set_many_unchecked(vec![("a".into(), "2".into())]), 0);
+    assert!(tbl.ttl("a").unwrap().unwrap().is_some());
+    // a deadline left behind by a removed key doesn't apply to a new one
+    tbl.expiry().set("c".into(), u64::MAX);
+    assert!(tbl.set("c".into(), "3".into()).unwrap());
+    assert_eq!(tbl.ttl("c").unwrap(), Some(None));
+}
+
+#[test]
+fn test_evict() {
+    use crate::config::EvictionPolicy;
+    let tbl = KVEStandard::default();
+    assert!(!tbl.evict(EvictionPolicy::Lru));
+    assert!(tbl.set("a".into(), "1".into()).unwrap());
+    assert!(tbl.set("b".into(), "2".into()).unwrap());
+    assert!(tbl.set_expiry("a", 60_000).unwrap());
+    assert!(tbl.evict(EvictionPolicy::Lru));
+    assert!(tbl.evict(EvictionPolicy::Lfu));
+    assert_eq!(tbl.len(), 0);
+    // evicted keys take their deadlines with them
+    assert!(tbl.expiry().is_empty());
+    assert!(!tbl.evict(EvictionPolicy::Lfu));
+}
+
+#[test]
+fn test_value_index() {
+    let tbl = KVEStandard::default();
+    let find = |value: &str| {
+        let mut keys = tbl.find_keys(value.as_bytes()).unwrap();
+        keys.sort_unstable_by(|a, b| a.as_slice().cmp(b.as_slice()));
+        keys
+    };
+    assert!(tbl.set("a".into(), "1".into()).unwrap());
+    assert!(tbl.set("b".into(), "1".into()).unwrap());
+    assert_eq!(tbl.find_keys(b"1"), None);
+    // existing keys are indexed when the index is turned on
+    tbl.enable_index().unwrap();
+    assert_eq!(find("1"), ["a", "b"].map(SharedSlice::from));
+    assert!(tbl.set("c".into(), "2".into()).unwrap());
+    assert!(tbl.update("a".into(), "2".into()).unwrap());
+    tbl.upsert("d".into(), "1".into()).unwrap();
+    assert_eq!(find("1"), ["b", "d"].map(SharedSlice::from));
+    assert_eq!(find("2"), ["a", "c"].map(SharedSlice::from));
+    assert!(tbl.remove("b").unwrap());
+    assert!(tbl.pop("c").unwrap().is_some());
+    tbl.expiry().set("d".into(), 0);
+    assert_eq!(tbl.expire_due(super::expiry::now_ms(), 10), 1);
+    assert!(find("1").is_empty());
+    assert_eq!(find("2"), [SharedSlice::from("a")]);
+    assert!(find("3").is_empty());
+    tbl.truncate_table();
+    assert!(find("2").is_empty());
+    tbl.index().disable();
+    assert_eq!(tbl.find_keys(b"2"), None);
+}
+
+#[test]
+fn test_map_fields() {
+    use super::KVEMap;
+    let tbl = KVEMap::init(true, true);
+    let fields = vec![("a".into(), "1".into()), ("b".into(), "2".into())];
+    assert_eq!(tbl.map_set("m".into(), fields).unwrap(), 2);
+    // updating a field doesn't add it
+    let fields = vec![("b".into(), "3".into()), ("c".into(), "4".into())];
+    assert_eq!(tbl.map_set("m".into(), fields).unwrap(), 1);
+    assert_eq!(tbl.map_get(b"m", b"b").unwrap(), Some(Some("3".into())));
+    assert_eq!(tbl.map_get(b"m", b"z").unwrap(), Some(None));
+    assert_eq!(tbl.map_get(b"nope", b"a").unwrap(), None);
+    // fields and values are encoded like the values of the table
+    let bad = vec![(SharedSlice::from(&[0xFF][..]), "1".into())];
+    assert!(tbl.map_set("m".into(), bad).is_err());
+    let fields = [&b"a"[..], b"z"];
+    assert_eq!(tbl.map_del(b"m", fields.into_iter()).unwrap(), Some(1));
+    assert_eq!(tbl.map_cloned_full(b"m").unwrap().unwrap().len(), 2);
+    // the map goes away with its last field
+    let fields = [&b"b"[..], b"c"];
+    assert_eq!(tbl.map_del(b"m", fields.into_iter()).unwrap(), Some(2));
+    assert_eq!(tbl.len(), 0);
+    assert_eq!(tbl.map_del(b"m", [&b"a"[..]].into_iter()).unwrap(), None);
+}
+
+#[test]
+fn test_set_members() {
+    use super::KVESet;
+    let tbl = KVESet::init(true, true);
+    let members = vec!["a".into(), "b".into(), "a".into()];
+    assert_eq!(tbl.set_add("s1".into(), members).unwrap(), 2);
+    assert_eq!(tbl.set_add("s1".into(), vec!["b".into()]).unwrap(), 0);
+    assert_eq!(
+        tbl.set_add("s2".into(), vec!["b".into(), "c".into()])
+            .unwrap(),
+        2
+    );
+    assert!(tbl.set_contains(b"s1", b"a").unwrap());
+    assert!(!tbl.set_contains(b"s1", b"c").unwrap());
+    assert!(!tbl.set_contains(b"nope", b"a").unwrap());
+    assert_eq!(tbl.set_members(b"nope").unwrap(), None);
+    // members are encoded like the values of the table
+    let bad = vec![SharedSlice::from(&[0xFF][..])];
+    assert!(tbl.set_add("s1".into(), bad).is_err());
+    // missing sets count as empty
+    let mut union = tbl
+        .set_union([&b"s1"[..], b"s2", b"nope"].into_iter())
+        .unwrap();
+    union.sort_unstable_by(|a, b| a.as_slice().cmp(b.as_slice()));
+    assert_eq!(union, ["a", "b", "c"].map(SharedSlice::from));
+    let inter = tbl
+        .set_intersection([&b"s1"[..], b"s2"].into_iter())
+        .unwrap();
+    assert_eq!(inter, vec![SharedSlice::from("b")]);
+    assert!(tbl
+        .set_intersection([&b"s1"[..], b"nope"].into_iter())
+        .unwrap()
+        .is_empty());
+    // the set goes away with its last member
+    let members = [&b"a"[..], b"b", b"z"];
+    assert_eq!(tbl.set_remove(b"s1", members.into_iter()).unwrap(), Some(2));
+    assert_eq!(tbl.len(), 1);
+    assert_eq!(
+        tbl.set_remove(b"s1", [&b"a"[..]].into_iter()).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn test_sorted_set_members() {
+    use super::KVESortedSet;
+    let tbl = KVESortedSet::init(true, true);
+    let members = vec![("a".into(), 3.0), ("b".into(), 1.0), ("c".into(), 2.0)];
+    assert_eq!(tbl.zset_add("z".into(), members).unwrap(), 3);
+    // updating a score doesn't add the member
+    let members = vec![("a".into(), 0.5), ("d".into(), 4.0)];
+    assert_eq!(tbl.zset_add("z".into(), members).unwrap(), 1);
+    assert_eq!(tbl.zset_len(b"z").unwrap(), 4);
+    assert_eq!(tbl.zset_len(b"nope").unwrap(), 0);
+    assert_eq!(tbl.zset_score(b"z", b"a").unwrap(), Some(0.5));
+    assert_eq!(tbl.zset_rank(b"z", b"c").unwrap(), Some(2));
+    assert_eq!(tbl.zset_rank(b"z", b"zz").unwrap(), None);
+    // members are encoded like the values of the table
+    let bad = vec![(SharedSlice::from(&[0xFF][..]), 1.0)];
+    assert!(tbl.zset_add("z".into(), bad).is_err());
+    let all = ["a", "b", "c", "d"].map(SharedSlice::from);
+    assert_eq!(tbl.zset_range(b"z", 0, -1).unwrap().unwrap(), all);
+    assert_eq!(tbl.zset_range(b"z", -2, 100).unwrap().unwrap(), all[2..]);
+    assert!(tbl.zset_range(b"z", 3, 1).unwrap().unwrap().is_empty());
+    assert_eq!(tbl.zset_range(b"nope", 0, -1).unwrap(), None);
+    let ranged = tbl.zset_range_by_score(b"z", 1.0, 2.0).unwrap().unwrap();
+    assert_eq!(ranged, all[1..3]);
+    let ranged = tbl
+        .zset_range_by_score(b"z", f64::NEG_INFINITY, 0.5)
+        .unwrap()
+        .unwrap();
+    assert_eq!(ranged, all[..1]);
+    // the sorted set goes away with its last member
+    let members = [&b"a"[..], b"b", b"c", b"d"];
+    assert_eq!(tbl.zset_remove(b"z", members.into_iter()).unwrap(), Some(4));
+    assert_eq!(tbl.len(), 0);
+    assert_eq!(
+        tbl.zset_remove(b"z", [&b"a"[..]].into_iter()).unwrap(),
+        None
+    );
+}
+
+#[test]
+fn test_document_paths() {
+    use super::{
+        document::{Json, Path, SetError},
+        KVEDocument,
+    };
+    let tbl = KVEDocument::init(true, true);
+    let path = |path: &str| Path::parse(path.as_bytes()).unwrap();
+    let json = |json: &str| Json::parse(json.as_bytes()).unwrap();
+    // only the document itself can be set on a missing document
+    assert_eq!(
+        tbl.doc_set("d".into(), &path("$.a"), json("1")).unwrap(),
+        Err(SetError::NoDocument)
+    );
+    let doc = json(r#"{"a":{"b":[1,2]}}"#);
+    tbl.doc_set("d".into(), &path("$"), doc).unwrap().unwrap();
+    assert_eq!(
+        tbl.doc_get(b"d", &path("$.a.b[1]")).unwrap(),
+        Some("2".into())
+    );
+    assert_eq!(tbl.doc_get(b"d", &path("$.a.c")).unwrap(), None);
+    assert_eq!(tbl.doc_get(b"nope", &path("$")).unwrap(), None);
+    // fields are added and elements are appended
+    let added = json(r#"{"x":null}"#);
+    tbl.doc_set("d".into(), &path("$.a.c"), added)
+        .unwrap()
+        .unwrap();
+    tbl.doc_set("d".into(), &path("$.a.b[2]"), json("3"))
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        tbl.doc_get(b"d", &path("$")).unwrap(),
+        Some(r#"{"a":{"b":[1,2,3],"c":{"x":null}}}"#.into())
+    );
+    assert_eq!(
+        tbl.doc_set("d".into(), &path("$.a.b[9]"), json("0"))
+            .unwrap(),
+        Err(SetError::NoParent)
+    );
+    // keys are encoded like the keys of the table
+    assert!(tbl.doc_get(&[0xFF], &path("$")).is_err());
+}
+
+#[test]
+fn test_match_keys() {
+    let tbl = KVEStandard::default();
+    for key in ["user:1", "user:2", "user:10", "session:1"] {
+        tbl.set(key.into(), "x".into()).unwrap();
+    }
+    let mut keys: Vec<Vec<u8>> = tbl
+        .match_keys(b"user:?")
+        .iter()
+        .map(|key| key.to_vec())
+        .collect();
+    keys.sort();
+    assert_eq!(keys, [b"user:1".to_vec(), b"user:2".to_vec()]);
+    assert_eq!(tbl.match_keys(b"*").len(), 4);
+    assert_eq!(tbl.match_keys(b"[su]*:10").len(), 1);
+    assert!(tbl.match_keys(b"nothing*").is_empty());
+    // a key whose deadline has passed isn't listed, even before the sweeper gets to it
+    tbl.expiry().set("user:1".into(), 0);
+    assert_eq!(tbl.match_keys(b"user:*").len(), 2);
+    assert_eq!(tbl.scan_keys(0, 10).0.len(), 3);
+    assert_eq!(tbl.len(), 4);
+}
+
+#[test]
+fn test_remove_matching() {
+    let tbl = KVEStandard::default();
+    for key in ["user:1", "user:2", "user:3", "session:1"] {
+        tbl.set(key.into(), "x".into()).unwrap();
+    }
+    let (keys, cursor) = tbl.scan_matching(b"user:*", 0, 10);
+    assert_eq!((keys.len(), cursor), (3, 0));
+    assert_eq!(tbl.remove_matching(b"user:*", 0, 10, 2), (2, 0));
+    assert_eq!(tbl.match_keys(b"user:*").len(), 1);
+    // every page only visits `count` keys
+    let (mut removed, mut cursor) = tbl.remove_matching(b"user:*", 0, 1, 10);
+    while cursor != 0 {
+        let (more, next) = tbl.remove_matching(b"user:*", cursor, 1, 10);
+        assert!(more <= 1);
+        removed += more;
+        cursor = next;
+    }
+    assert_eq!(removed, 1);
+    assert_eq!(tbl.remove_matching(b"user:*", 0, 10, 10), (0, 0));
+    assert!(tbl.exists("session:1".as_bytes()).unwrap());
+}
+
+#[test]
+fn test_random_key() {
+    let tbl = KVEStandard::default();
+    assert!(tbl.random_key().is_none());
+    tbl.set("a".into(), "1".into()).unwrap();
+    tbl.set("b".into(), "2".into()).unwrap();
+    let key = tbl.random_key().unwrap();
+    assert!(key.as_slice() == b"a" || key.as_slice() == b"b");
+    // an expired key is never picked (and is removed if it was)
+    tbl.expiry().set("a".into(), 0);
+    for _ in 0..10 {
+        assert_eq!(tbl.random_key().unwrap().as_slice(), b"b");
+    }
+}
+
+#[test]
+fn test_list_bound() {
+    let tbl = KVEListmap::init(false, false);
+    tbl.add_list("feed".into()).unwrap();
+    let push = |element: &str| {
+        let list = tbl.get_inner_ref().get(b"feed".as_ref()).unwrap();
+        let mut wlock = list.write();
+        wlock.push(element.into());
+        tbl.enforce_bound(b"feed", &mut wlock)
+    };
+    assert_eq!(push("a"), 0);
+    assert_eq!(push("b"), 0);
+    assert_eq!(push("c"), 0);
+    // bounding a list trims it right away
+    assert!(tbl.set_list_bound(b"feed", Some(2)).unwrap());
+    assert_eq!(
+        tbl.list_cloned_full(b"feed").unwrap().unwrap(),
+        [SharedSlice::from("b"), SharedSlice::from("c")]
+    );
+    assert_eq!(push("d"), 1);
+    assert_eq!(
+        tbl.list_cloned_full(b"feed").unwrap().unwrap(),
+        [SharedSlice::from("c"), SharedSlice::from("d")]
+    );
+    assert_eq!(tbl.list_bound(b"feed").unwrap(), Some(Some(2)));
+    assert_eq!(tbl.list_bound(b"nope").unwrap(), None);
+    assert!(!tbl.set_list_bound(b"nope", Some(2)).unwrap());
+    // the bound goes away with the list
+    assert!(tbl.remove(b"feed").unwrap());
+    tbl.add_list("feed".into()).unwrap();
+    assert_eq!(tbl.list_bound(b"feed").unwrap(), Some(None));
+}
+
+#[test]
+fn test_trim_list() {
+    let tbl = KVEListmap::init(false, false);
+    tbl.add_list("list".into()).unwrap();
+    tbl.get_inner_ref()
+        .get(b"list".as_ref())
+        .unwrap()
+        .write()
+        .extend(
+            ["a", "b", "c", "d", "e"]
+                .iter()
+                .map(|e| SharedSlice::from(*e)),
+        );
+    assert_eq!(tbl.trim_list(b"list", 1, -2).unwrap(), Some(2));
+    assert_eq!(
+        tbl.list_cloned_full(b"list").unwrap().unwrap(),
+        [SharedSlice::from("b"), "c".into(), "d".into()]
+    );
+    assert_eq!(tbl.trim_list(b"list", 0, -1).unwrap(), Some(0));
+    assert_eq!(tbl.trim_list(b"list", 5, 10).unwrap(), Some(3));
+    assert!(tbl.list_cloned_full(b"list").unwrap().unwrap().is_empty());
+    assert_eq!(tbl.trim_list(b"nope", 0, 1).unwrap(), None);
+}
+
+#[test]
+fn test_list_insert_remove() {
+    let tbl = KVEListmap::init(false, false);
+    tbl.add_list("list".into()).unwrap();
+    assert_eq!(tbl.list_insert(b"list", 0, b"b").unwrap(), Some(Some(1)));
+    assert_eq!(tbl.list_insert(b"list", 0, b"a").unwrap(), Some(Some(2)));
+    // appending is fine, but going past the end isn't
+    assert_eq!(tbl.list_insert(b"list", 2, b"a").unwrap(), Some(Some(3)));
+    assert_eq!(tbl.list_insert(b"list", 4, b"c").unwrap(), Some(None));
+    assert_eq!(tbl.list_insert(b"nope", 0, b"c").unwrap(), None);
+    assert_eq!(tbl.list_insert(b"list", 1, b"a").unwrap(), Some(Some(4)));
+    let contents = || -> Vec<Vec<u8>> {
+        let list = tbl.list_cloned_full(b"list").unwrap().unwrap();
+        list.iter().map(|element| element.to_vec()).collect()
+    };
+    assert_eq!(
+        contents(),
+        [b"a".to_vec(), b"a".to_vec(), b"b".to_vec(), b"a".to_vec()]
+    );
+    // remove from the back
+    assert_eq!(tbl.list_remove(b"list", b"a", -1).unwrap(), Some(1));
+    assert_eq!(contents(), [b"a".to_vec(), b"a".to_vec(), b"b".to_vec()]);
+    // and then from the front
+    assert_eq!(tbl.list_remove(b"list", b"a", 1).unwrap(), Some(1));
+    assert_eq!(contents(), [b"a".to_vec(), b"b".to_vec()]);
+    assert_eq!(tbl.list_remove(b"list", b"c", 0).unwrap(), Some(0));
+    assert_eq!(tbl.list_remove(b"list", b"a", 0).unwrap(), Some(1));
+    assert_eq!(contents(), [b"b".to_vec()]);
+    assert_eq!(tbl.list_remove(b"nope", b"a", 0).unwrap(), None);
+}
+
+#[test]
+fn test_set_with_expiry() {
+    use super::SetCondition;
+    let tbl = KVEStandard::init(true, true);
+    // `Exists` doesn't create a key
+    assert!(!tbl
+        .set_with_expiry("lock".into(), "a".into(), SetCondition::Exists, 60_000)
+        .unwrap());
+    assert!(tbl
+        .set_with_expiry("lock".into(), "a".into(), SetCondition::Missing, 60_000)
+        .unwrap());
+    assert!(matches!(
+        tbl.ttl("lock").unwrap(),
+        Some(Some(59_000..=60_000))
+    ));
+    // `Missing` doesn't overwrite a key (or its deadline)
+    assert!(!tbl
+        .set_with_expiry("lock".into(), "b".into(), SetCondition::Missing, 1_000)
+        .unwrap());
+    assert_eq!(tbl.get_cloned("lock").unwrap().unwrap(), "a".as_bytes());
+    assert!(matches!(
+        tbl.ttl("lock").unwrap(),
+        Some(Some(59_000..=60_000))
+    ));
+    assert!(tbl
+        .set_with_expiry("lock".into(), "c".into(), SetCondition::Always, 1_000)
+        .unwrap());
+    assert_eq!(tbl.get_cloned("lock").unwrap().unwrap(), "c".as_bytes());
+    assert!(matches!(tbl.ttl("lock").unwrap(), Some(Some(0..=1_000))));
+}
+
+#[test]
+fn test_swap() {
+    let tbl = KVEStandard::init(true, true);
+    assert!(tbl.swap("x".into(), "a".into()).unwrap().is_none());
+    assert_eq!(
+        tbl.swap("x".into(), "b".into()).unwrap().unwrap(),
+        "a".as_bytes()
+    );
+    assert_eq!(tbl.get_cloned("x").unwrap().unwrap(), "b".as_bytes());
+    // a swap also clears the time to live
+    tbl.set_expiry("x", 60_000).unwrap();
+    tbl.swap("x".into(), "c".into()).unwrap();
+    assert_eq!(tbl.ttl("x").unwrap(), Some(None));
+    assert!(tbl
+        .swap("x".into(), SharedSlice::from(b"\xF0\x90".to_vec()))
+        .is_err());
+}
+
+#[test]
+fn test_incr_by() {
+    use super::IncrError;
+    use std::{sync::Arc, thread};
+    let tbl = Arc::new(KVEStandard::init(true, true));
+    // a missing key counts as zero
+    assert_eq!(tbl.incr_by("c".into(), 5).unwrap(), Ok(5));
+    assert_eq!(tbl.incr_by("c".into(), -7).unwrap(), Ok(-2));
+    assert_eq!(tbl.get_cloned("c").unwrap().unwrap(), "-2".as_bytes());
+    tbl.set("word".into(), "ten".into()).unwrap();
+    assert_eq!(
+        tbl.incr_by("word".into(), 1).unwrap(),
+        Err(IncrError::NotAnInteger)
+    );
+    tbl.set("max".into(), i64::MAX.to_string().into()).unwrap();
+    assert_eq!(
+        tbl.incr_by("max".into(), 1).unwrap(),
+        Err(IncrError::Overflow)
+    );
+    assert_eq!(
+        tbl.get_cloned("max").unwrap().unwrap(),
+        i64::MAX.to_string().as_bytes()
+    );
+    // concurrent increments never lose updates
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let tbl = tbl.clone();
+            thread::spawn(move || {
+                for _ in 0..1000 {
+                    tbl.incr_by("hits".into(), 1).unwrap().unwrap();
+                }
+            })
+        })
+        .collect();
+    threads.into_iter().for_each(|t| t.join().unwrap());
+    assert_eq!(tbl.incr_by("hits".into(), 0).unwrap(), Ok(4000));
+}
+
+#[test]
+fn test_set_range() {
+    let tbl = KVEStandard::init(true, true);
+    tbl.set("k".into(), "hello world".into()).unwrap();
+    assert_eq!(tbl.set_range("k".into(), 6, b"skies").unwrap(), 11);
+    assert_eq!(
+        tbl.get_cloned("k").unwrap().unwrap(),
+        "hello skies".as_bytes()
+    );
+    // writing past the end extends the value
+    assert_eq!(tbl.set_range("k".into(), 8, b"ytable").unwrap(), 14);
+    assert_eq!(
+        tbl.get_cloned("k").unwrap().unwrap(),
+        "hello skytable".as_bytes()
+    );
+    // a missing key counts as empty, and the gap is padded with zeros
+    assert_eq!(tbl.set_range("new".into(), 2, b"x").unwrap(), 3);
+    assert_eq!(tbl.get_cloned("new").unwrap().unwrap(), b"\0\0x".as_slice());
+    // nothing is written for empty data
+    assert_eq!(tbl.set_range("none".into(), 4, b"").unwrap(), 0);
+    assert!(tbl.get_cloned("none").unwrap().is_none());
+    // the patched value must still be a valid string
+    tbl.set("utf8".into(), "ñ".into()).unwrap();
+    assert!(tbl.set_range("utf8".into(), 1, b"n").is_err());
+    assert_eq!(tbl.get_cloned("utf8").unwrap().unwrap(), "ñ".as_bytes());
+}
+
+#[test]
+fn test_bitmaps() {
+    let tbl = KVEStandard::init(false, false);
+    // a missing key has no set bits, and setting one pads the value
+    assert!(!tbl.get_bit("flags", 10).unwrap());
+    assert!(!tbl.set_bit("flags".into(), 10, true).unwrap());
+    assert_eq!(
+        tbl.get_cloned("flags").unwrap().unwrap(),
+        [0x00, 0x20].as_slice()
+    );
+    assert!(tbl.get_bit("flags", 10).unwrap());
+    assert!(tbl.set_bit("flags".into(), 10, true).unwrap());
+    tbl.set_bit("flags".into(), 0, true).unwrap();
+    tbl.set_bit("flags".into(), 23, true).unwrap();
+    assert_eq!(tbl.bit_count("flags", 0, -1).unwrap(), 3);
+    assert_eq!(tbl.bit_count("flags", 1, 1).unwrap(), 1);
+    assert_eq!(tbl.bit_count("flags", -1, -1).unwrap(), 1);
+    // clearing a bit doesn't shrink the value
+    assert!(tbl.set_bit("flags".into(), 23, false).unwrap());
+    assert_eq!(tbl.get_cloned("flags").unwrap().unwrap().len(), 3);
+    assert_eq!(tbl.bit_count("flags", 0, -1).unwrap(), 2);
+    assert_eq!(tbl.bit_count("nope", 0, -1).unwrap(), 0);
+    // the value of a string table has to stay a valid string
+    let tbl = KVEStandard::init(true, true);
+    tbl.set("s".into(), "a".into()).unwrap();
+    assert!(tbl.set_bit("s".into(), 0, true).is_err());
+    assert_eq!(tbl.get_cloned("s").unwrap().unwrap(), "a".as_bytes());
+}
+
+#[test]
+fn test_sketches() {
+    let tbl = KVEStandard::init(false, false);
+    assert_eq!(
+        tbl.pf_add("a".into(), [b"x".as_slice(), b"y"]).unwrap(),
+        Some(true)
+    );
+    // adding an element that's already there doesn't write anything
+    let version = tbl.version_of(b"a").unwrap();
+    assert_eq!(
+        tbl.pf_add("a".into(), [b"x".as_slice()]).unwrap(),
+        Some(false)
+    );
+    assert_eq!(tbl.version_of(b"a").unwrap(), version);
+    // a new key gets an empty sketch
+    assert_eq!(tbl.pf_add("b".into(), []).unwrap(), Some(true));
+    tbl.pf_add("b".into(), [b"y".as_slice(), b"z"]).unwrap();
+    assert_eq!(tbl.pf_count([b"a".as_slice()]).unwrap(), Some(2));
+    assert_eq!(
+        tbl.pf_count([b"a".as_slice(), b"b", b"missing"]).unwrap(),
+        Some(3)
+    );
+    assert_eq!(
+        tbl.pf_merge("all".into(), [b"a".as_slice(), b"b"]).unwrap(),
+        Some(())
+    );
+    assert_eq!(tbl.pf_count([b"all".as_slice()]).unwrap(), Some(3));
+    // values that aren't sketches are left alone
+    tbl.set("plain".into(), "hello".into()).unwrap();
+    assert_eq!(tbl.pf_add("plain".into(), [b"x".as_slice()]).unwrap(), None);
+    assert_eq!(tbl.pf_count([b"a".as_slice(), b"plain"]).unwrap(), None);
+    assert_eq!(
+        tbl.pf_merge("all".into(), [b"plain".as_slice()]).unwrap(),
+        None
+    );
+    assert_eq!(
+        tbl.get_cloned("plain").unwrap().unwrap(),
+        "hello".as_bytes()
+    );
+}
+
+#[test]
+fn test_inspect_entry() {
+    let tbl = KVEStandard::init(false, false);
+    assert!(tbl.inspect_entry("nope").unwrap().is_none());
+    tbl.set("n".into(), "-42".into()).unwrap();
+    tbl.set("s".into(), "hello".into()).unwrap();
+    tbl.pf_add("h".into(), [b"x".as_slice()]).unwrap();
+    let info = tbl.inspect_entry("n").unwrap().unwrap();
+    assert_eq!(info.encoding, "int");
+    assert_eq!(info.footprint, super::eviction::entry_size(b"n", 3));
+    assert!(info.shard < info.shards);
+    assert!(info.ttl_ms.is_none());
+    assert_eq!(tbl.inspect_entry("s").unwrap().unwrap().encoding, "raw");
+    assert_eq!(
+        tbl.inspect_entry("h").unwrap().unwrap().encoding,
+        "hyperloglog-sparse"
+    );
+    tbl.set_expiry("s", 60_000).unwrap();
+    assert!(matches!(
+        tbl.inspect_entry("s").unwrap().unwrap().ttl_ms,
+        Some(59_000..=60_000)
+    ));
+    let lists = KVEListmap::init(false, false);
+    lists.add_list("l".into()).unwrap();
+    assert_eq!(lists.inspect_entry("l").unwrap().unwrap().encoding, "list");
+}
+
+#[test]
+fn test_transaction_commit() {
+    use super::txn::{Transaction, TxnError};
+    let tbl = KVEStandard::init(true, true);
+    tbl.set("a".into(), "1".into()).unwrap();
+    let mut txn = Transaction::new();
+    txn.set("b".into(), "2".into())
+        .update("a".into(), "10".into())
+        .delete("b".into())
+        // the key was deleted by the write before, so it can be set again
+        .set("b".into(), "20".into());
+    assert_eq!(tbl.commit(txn).unwrap(), Ok(()));
+    assert_eq!(tbl.get_cloned("a").unwrap().unwrap(), "10".as_bytes());
+    assert_eq!(tbl.get_cloned("b").unwrap().unwrap(), "20".as_bytes());
+    // a failed precondition applies none of the writes
+    let mut txn = Transaction::new();
+    txn.upsert("c".into(), "3".into())
+        .set("a".into(), "11".into());
+    assert_eq!(tbl.commit(txn).unwrap(), Err(TxnError::Exists));
+    let mut txn = Transaction::new();
+    txn.delete("a".into()).update("missing".into(), "0".into());
+    assert_eq!(tbl.commit(txn).unwrap(), Err(TxnError::Missing));
+    assert!(tbl.get_cloned("c").unwrap().is_none());
+    assert_eq!(tbl.get_cloned("a").unwrap().unwrap(), "10".as_bytes());
+    // so does a bad encoding
+    let mut txn = Transaction::new();
+    txn.upsert("d".into(), "4".into())
+        .upsert("e".into(), SharedSlice::new(b"\xc3\x28"));
+    assert!(tbl.commit(txn).is_err());
+    assert!(tbl.get_cloned("d").unwrap().is_none());
+}
+
+#[test]
+fn test_compare_and_swap() {
+    let tbl = KVEStandard::init(true, true);
+    assert_eq!(tbl.version_of(b"k").unwrap(), None);
+    // zero means that the key must not exist
+    let v1 = tbl
+        .compare_and_swap("k".into(), 0, "a".into())
+        .unwrap()
+        .unwrap();
+    assert_eq!(tbl.version_of(b"k").unwrap(), Some(v1));
+    assert_eq!(
+        tbl.compare_and_swap("k".into(), 0, "b".into()).unwrap(),
+        Err(v1)
+    );
+    let v2 = tbl
+        .compare_and_swap("k".into(), v1, "b".into())
+        .unwrap()
+        .unwrap();
+    assert!(v2 > v1);
+    assert_eq!(tbl.get_cloned("k").unwrap().unwrap(), "b".as_bytes());
+    // any other write changes the version too
+    tbl.update("k".into(), "c".into()).unwrap();
+    let v3 = tbl.version_of(b"k").unwrap().unwrap();
+    assert!(v3 > v2);
+    assert_eq!(
+        tbl.compare_and_swap("k".into(), v2, "d".into()).unwrap(),
+        Err(v3)
+    );
+    assert_eq!(tbl.get_cloned("k").unwrap().unwrap(), "c".as_bytes());
+    // a key that is removed and set again doesn't get an old version back
+    tbl.remove("k").unwrap();
+    assert_eq!(
+        tbl.compare_and_swap("k".into(), v3, "e".into()).unwrap(),
+        Err(0)
+    );
+    tbl.set("k".into(), "e".into()).unwrap();
+    let v4 = tbl.version_of(b"k").unwrap().unwrap();
+    assert!(v4 > v3);
+    // and neither does one that is set again after a truncate
+    tbl.truncate_table();
+    assert_eq!(tbl.version_of(b"k").unwrap(), None);
+    tbl.set("k".into(), "f".into()).unwrap();
+    assert!(tbl.version_of(b"k").unwrap().unwrap() > v4);
+}
+
+#[test]
+fn test_compare_value_and_swap() {
+    let tbl = KVEStandard::init(true, true);
+    assert_eq!(
+        tbl.compare_value_and_swap("k".into(), b"a", "b".into())
+            .unwrap(),
+        None
+    );
+    assert!(tbl.get_cloned("k").unwrap().is_none());
+    tbl.set("k".into(), "a".into()).unwrap();
+    tbl.set_expiry("k", 60_000).unwrap();
+    assert_eq!(
+        tbl.compare_value_and_swap("k".into(), b"x", "b".into())
+            .unwrap(),
+        Some(false)
+    );
+    assert_eq!(tbl.get_cloned("k").unwrap().unwrap(), "a".as_bytes());
+    assert_eq!(
+        tbl.compare_value_and_swap("k".into(), b"a", "b".into())
+            .unwrap(),
+        Some(true)
+    );
+    assert_eq!(tbl.get_cloned("k").unwrap().unwrap(), "b".as_bytes());
+    assert_eq!(tbl.ttl("k").unwrap(), Some(None));
+}
+
+#[test]
+fn test_batched_writes() {
+    let tbl = KVEStandard::default();
+    tbl.enable_index().unwrap();
+    let entries = |pairs: &[(&str, &str)]| -> Vec<(SharedSlice, SharedSlice)> {
+        pairs
+            .iter()
+            .map(|(k, v)| (SharedSlice::from(*k), SharedSlice::from(*v)))
+            .collect()
+    };
+    assert!(tbl.set("a".into(), "0".into()).unwrap());
+    // existing keys are skipped, and so are repeated keys after the first
+    assert_eq!(
+        tbl.set_many_unchecked(entries(&[("a", "1"), ("b", "1"), ("c", "2"), ("b", "3")])),
+        2
+    );
+    assert_eq!(tbl.get("a").unwrap().unwrap().as_ref(), b"0");
+    assert_eq!(tbl.get("b").unwrap().unwrap().as_ref(), b"1");
+    assert_eq!(tbl.mutations(), 3);
+    // upserts overwrite existing keys
+    tbl.upsert_many_unchecked(entries(&[("a", "2"), ("d", "2")]));
+    assert_eq!(tbl.get("a").unwrap().unwrap().as_ref(), b"2");
+    assert_eq!(tbl.mutations(), 5);
+    let mut keys = tbl.find_keys(b"2").unwrap();
+    keys.sort_unstable_by(|a, b| a.as_slice().cmp(b.as_slice()));
+    assert_eq!(keys, ["a", "c", "d"].map(SharedSlice::from));
+}
+
+#[test]
+fn test_key_events() {
+    use super::events::KeyEvent;
+    use crate::{corestore::memstore::ObjectID, dbnet::pubsub::Subscriber};
+    let (mut subscriber, mut rx) = Subscriber::new();
+    subscriber.psubscribe(b"__keyevent__:kvevents:*");
+    let tbl = KVEStandard::default();
+    let (ksid, tblid) = unsafe {
+        (
+            ObjectID::from_slice("kvevents"),
+            ObjectID::from_slice("tbl"),
+        )
+    };
+    tbl.key_events().set_mask(ksid, tblid, KeyEvent::ALL);
+    let mut next = || {
+        let message = rx.try_recv().unwrap();
+        (message.channel, message.payload)
+    };
+    assert!(tbl.set("a".into(), "1".into()).unwrap());
+    assert_eq!(next(), ("__keyevent__:kvevents:tbl:set".into(), "a".into()));
+    assert!(tbl.update("a".into(), "2".into()).unwrap());
+    assert_eq!(next(), ("__keyevent__:kvevents:tbl:set".into(), "a".into()));
+    assert!(tbl.remove("a").unwrap());
+    assert_eq!(next(), ("__keyevent__:kvevents:tbl:del".into(), "a".into()));
+    // expired keys aren't reported as deleted
+    assert!(tbl.set("b".into(), "1".into()).unwrap());
+    next();
+    tbl.expiry().set("b".into(), 0);
+    assert_eq!(tbl.expire_due(super::expiry::now_ms(), 10), 1);
+    assert_eq!(
+        next(),
+        ("__keyevent__:kvevents:tbl:expired".into(), "b".into())
+    );
+    assert!(rx.try_recv().is_err());
+}
+
+#[test]
+fn test_scan_keys_with_archive() {
+    let tbl = KVEStandard::default();
+    for i in 0..100 {
+        tbl.set(i.to_string().into(), "value".into()).unwrap();
+    }
+    // archive half of them
+    let cold = tbl.get_inner_ref().get_keys(50);
+    let cold_data: super::Coremap<SharedSlice, SharedSlice> = super::Coremap::new();
+    for key in cold.iter() {
+        let (key, val) = tbl.get_inner_ref().remove(key).unwrap();
+        cold_data.upsert(key, val);
+    }
+    // the first sweep only starts the clock
+    assert_eq!(
+        tbl.archive
+            .archive_accessed_before(&cold_data, u64::MAX)
+            .unwrap(),
+        0
+    );
+    assert_eq!(
+        tbl.archive
+            .archive_accessed_before(&cold_data, u64::MAX)
+            .unwrap(),
+        50
+    );
+    let (mut seen, mut cursor) = (Vec::new(), 0);
+    loop {
+        let (keys, next) = tbl.scan_keys(cursor, 7);
+        assert!(keys.len() == 7 || next == 0);
+        seen.extend(keys);
+        if next == 0 {
+            break;
+        }
+        cursor = next;
+    }
+    let mut seen: Vec<usize> = seen
+        .iter()
+        .map(|key| String::from_utf8_lossy(key).parse().unwrap())
+        .collect();
+    seen.sort_unstable();
+    assert_eq!(seen, (0..100).collect::<Vec<_>>());
+}
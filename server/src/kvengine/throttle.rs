@@ -0,0 +1,127 @@
+/*
+ * Created on Wed Nov 02 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Write throttling
+//!
+//! An opt-in token bucket that caps the number of writes per second on a table, so that a
+//! single abusive table can't starve the others. When it isn't set, the only cost is a single
+//! atomic load per write.
+
+use {
+    core::sync::atomic::{AtomicU64, Ordering},
+    parking_lot::Mutex,
+    std::time::{Duration, Instant},
+};
+
+#[derive(Debug, Default)]
+/// A token bucket that caps the writes per second on a table
+pub struct WriteThrottle {
+    /// the cap (writes per second). zero if throttling is off
+    rate: AtomicU64,
+    /// the bucket. it holds at most `rate` tokens, so bursts are capped at a second's worth
+    bucket: Mutex<Bucket>,
+    /// the number of writes that were throttled
+    throttled: AtomicU64,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// the tokens left
+    tokens: f64,
+    /// when the bucket was last refilled
+    refilled: Instant,
+}
+
+impl Default for Bucket {
+    fn default() -> Self {
+        Self {
+            tokens: 0.0,
+            refilled: Instant::now(),
+        }
+    }
+}
+
+impl WriteThrottle {
+    /// Cap writes to `rate` per second, starting with a full bucket. A rate of zero turns
+    /// throttling off
+    pub fn set_rate(&self, rate: u64) {
+        let mut bucket = self.bucket.lock();
+        bucket.tokens = rate as f64;
+        bucket.refilled = Instant::now();
+        self.rate.store(rate, Ordering::Release);
+    }
+    #[inline(always)]
+    /// Returns true if writes are being throttled
+    pub fn is_enabled(&self) -> bool {
+        self.rate.load(Ordering::Relaxed) != 0
+    }
+    /// Returns the number of writes that were throttled so far
+    pub fn throttled(&self) -> u64 {
+        self.throttled.load(Ordering::Relaxed)
+    }
+    /// Take a token for a write. If there are none left, this returns how long the caller
+    /// should wait before retrying
+    pub fn try_acquire(&self) -> Result<(), Duration> {
+        let mut bucket = self.bucket.lock();
+        // load this under the lock so that we see the bucket `set_rate` reset for it
+        let rate = self.rate.load(Ordering::Acquire) as f64;
+        if rate == 0.0 {
+            return Ok(());
+        }
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.refilled).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(rate);
+        bucket.refilled = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            self.throttled.fetch_add(1, Ordering::Relaxed);
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / rate))
+        }
+    }
+}
+
+#[test]
+fn test_write_throttle() {
+    let throttle = WriteThrottle::default();
+    assert!(!throttle.is_enabled());
+    assert!(throttle.try_acquire().is_ok());
+    throttle.set_rate(10);
+    // a full bucket allows a burst of `rate` writes
+    for _ in 0..10 {
+        assert!(throttle.try_acquire().is_ok());
+    }
+    let wait = throttle.try_acquire().unwrap_err();
+    assert!(wait <= Duration::from_millis(100));
+    assert_eq!(throttle.throttled(), 1);
+    std::thread::sleep(wait);
+    assert!(throttle.try_acquire().is_ok());
+    // and turn it off
+    throttle.set_rate(0);
+    assert!(!throttle.is_enabled());
+    assert!(throttle.try_acquire().is_ok());
+}
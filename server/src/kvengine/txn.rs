@@ -0,0 +1,124 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Transactions
+//!
+//! A [`Transaction`] queues writes to a key/value table so that they can be committed together
+//! (see [`KVEStandard::commit`]). Like strong actions, a transaction either makes all of its
+//! writes or none of them: a `set` needs a missing key and an `update` or a `delete` needs an
+//! existing key, taking the writes queued before it into account. On top of that, the shards of
+//! the keys stay locked while the writes are made, so no reader ever sees some of the writes of
+//! a transaction without the others.
+//!
+//! [`KVEStandard::commit`]: super::KVEStandard::commit
+
+use crate::corestore::SharedSlice;
+
+/// A write queued in a [`Transaction`]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Write {
+    /// Set a key that doesn't exist
+    Set(SharedSlice, SharedSlice),
+    /// Update a key that exists
+    Update(SharedSlice, SharedSlice),
+    /// Set or update a key
+    Upsert(SharedSlice, SharedSlice),
+    /// Delete a key that exists
+    Delete(SharedSlice),
+}
+
+impl Write {
+    /// Returns the key that is written to
+    pub fn key(&self) -> &SharedSlice {
+        match self {
+            Self::Set(key, _) | Self::Update(key, _) | Self::Upsert(key, _) | Self::Delete(key) => {
+                key
+            }
+        }
+    }
+    /// Returns the value that is written (if any)
+    pub fn value(&self) -> Option<&SharedSlice> {
+        match self {
+            Self::Set(_, value) | Self::Update(_, value) | Self::Upsert(_, value) => Some(value),
+            Self::Delete(_) => None,
+        }
+    }
+}
+
+/// The reasons why a transaction can fail to commit (in which case none of its writes are made)
+#[derive(Debug, PartialEq, Eq)]
+pub enum TxnError {
+    /// a `set` was queued for a key that exists
+    Exists,
+    /// an `update` or a `delete` was queued for a key that doesn't exist
+    Missing,
+}
+
+/// A builder for a batch of writes to a key/value table
+#[derive(Debug, Clone, Default)]
+pub struct Transaction {
+    writes: Vec<Write>,
+}
+
+impl Transaction {
+    /// Create an empty transaction
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Queue a `set`
+    pub fn set(&mut self, key: SharedSlice, value: SharedSlice) -> &mut Self {
+        self.push(Write::Set(key, value))
+    }
+    /// Queue an `update`
+    pub fn update(&mut self, key: SharedSlice, value: SharedSlice) -> &mut Self {
+        self.push(Write::Update(key, value))
+    }
+    /// Queue an `upsert`
+    pub fn upsert(&mut self, key: SharedSlice, value: SharedSlice) -> &mut Self {
+        self.push(Write::Upsert(key, value))
+    }
+    /// Queue a `delete`
+    pub fn delete(&mut self, key: SharedSlice) -> &mut Self {
+        self.push(Write::Delete(key))
+    }
+    /// Queue a write
+    pub fn push(&mut self, write: Write) -> &mut Self {
+        self.writes.push(write);
+        self
+    }
+    /// Returns the number of queued writes
+    pub fn len(&self) -> usize {
+        self.writes.len()
+    }
+    /// Returns true if no writes were queued
+    pub fn is_empty(&self) -> bool {
+        self.writes.is_empty()
+    }
+    /// Returns the queued writes, in order
+    pub fn writes(&self) -> &[Write] {
+        &self.writes
+    }
+}
@@ -0,0 +1,109 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Entry versions
+//!
+//! Every key in a key/value table has a version that changes on every write to it, which lets
+//! clients do a read-modify-write without holding a lock: they read the version and the value,
+//! and then only write the new value if the version is still the same (see
+//! [`KVEStandard::compare_and_swap`]). The engine changes the version while it holds the lock
+//! on the entry that is being written to.
+//!
+//! Versions are handed out from a counter that is shared by the whole table, so a key that is
+//! removed and set again never gets a version it had before. Versions aren't stored on disk;
+//! instead, the keys loaded from disk all start with a version that is derived from the time the
+//! table was loaded, and the counter starts from there. Hence a version handed out before a
+//! restart won't be handed out again (as long as the clock doesn't go back).
+//!
+//! The memory used by the versions is not counted towards `maxmemory`.
+//!
+//! [`KVEStandard::compare_and_swap`]: super::KVEStandard::compare_and_swap
+
+use {
+    super::expiry,
+    crate::corestore::{htable::Coremap, SharedSlice},
+    core::sync::atomic::{AtomicU64, Ordering},
+};
+
+/// The number of versions that can be handed out every millisecond without running into the
+/// versions of a restart that happens a millisecond later
+const VERSIONS_PER_MS_BITS: u32 = 20;
+
+#[derive(Debug)]
+/// The versions of the keys of a table
+pub struct EntryVersions {
+    /// the version of every key that was written to since the table was loaded
+    versions: Coremap<SharedSlice, u64>,
+    /// the last version that was handed out
+    last: AtomicU64,
+    /// the version of the keys that haven't been written to since the table was loaded
+    base: u64,
+}
+
+impl Default for EntryVersions {
+    fn default() -> Self {
+        Self::with_base(expiry::now_ms() << VERSIONS_PER_MS_BITS)
+    }
+}
+
+impl EntryVersions {
+    fn with_base(base: u64) -> Self {
+        Self {
+            versions: Coremap::new(),
+            last: AtomicU64::new(base),
+            base,
+        }
+    }
+    /// Record a write to `key`, with the entry for `key` locked. `exists` is false if the key
+    /// was removed
+    pub fn changed(&self, key: &SharedSlice, exists: bool) {
+        if exists {
+            let version = self.last.fetch_add(1, Ordering::AcqRel) + 1;
+            self.versions.upsert(key.clone(), version);
+        } else {
+            self.versions.remove(key.as_slice());
+        }
+    }
+    /// Returns the version of `key`, which must exist. The entry for `key` has to be locked
+    /// to get a version that matches its value
+    pub fn of(&self, key: &[u8]) -> u64 {
+        self.versions.get(key).map(|v| *v).unwrap_or(self.base)
+    }
+}
+
+#[test]
+fn test_entry_versions() {
+    let versions = EntryVersions::with_base(100);
+    let key = SharedSlice::from("k");
+    assert_eq!(versions.of(b"k"), 100);
+    versions.changed(&key, true);
+    assert_eq!(versions.of(b"k"), 101);
+    versions.changed(&SharedSlice::from("other"), true);
+    versions.changed(&key, false);
+    versions.changed(&key, true);
+    // a key that is set again never gets an old version back
+    assert_eq!(versions.of(b"k"), 103);
+}
@@ -36,7 +36,11 @@
 //! the modules for their respective documentation.
 
 use {
-    crate::{config::ConfigurationSet, diskstore::flock::FileLock, util::exit_error},
+    crate::{
+        config::{ConfigurationSet, OfflineTask},
+        diskstore::flock::FileLock,
+        util::exit_error,
+    },
     env_logger::Builder,
     libsky::{URL, VERSION},
     std::{env, process},
@@ -54,6 +58,8 @@ mod corestore;
 mod dbnet;
 mod diskstore;
 mod kvengine;
+#[cfg(all(feature = "plugins", unix))]
+mod plugins;
 mod protocol;
 mod queryengine;
 pub mod registry;
@@ -99,11 +105,20 @@ fn main() {
         .enable_all()
         .build()
         .unwrap();
-    let (cfg, restore_file) = check_args_and_get_cfg();
+    let (cfg, restore_file, task) = check_args_and_get_cfg();
     // check if any other process is using the data directory and lock it if not (else error)
     // important: create the pid_file just here and nowhere else because check_args can also
     // involve passing --help or wrong arguments which can falsely create a PID file
     let pid_file = run_pre_startup_tasks();
+    if let Some(task) = task {
+        // run the task on the data directory and exit without starting up
+        let okay = services::run_offline_task(task);
+        if !(services::pre_shutdown_cleanup(pid_file, None) && okay) {
+            process::exit(1);
+        }
+        return;
+    }
+    registry::set_effective_config(cfg.effective_settings());
     let db = runtime.block_on(async move { arbiter::run(cfg, restore_file).await });
     // Make sure all background workers terminate
     drop(runtime);
@@ -127,7 +142,7 @@ fn main() {
 
 /// This function checks the command line arguments and either returns a config object
 /// or prints an error to `stderr` and terminates the server
-fn check_args_and_get_cfg() -> (ConfigurationSet, Option<String>) {
+fn check_args_and_get_cfg() -> (ConfigurationSet, Option<String>, Option<OfflineTask>) {
     match config::get_config() {
         Ok(cfg) => {
             if cfg.is_artful() {
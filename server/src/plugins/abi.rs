@@ -0,0 +1,149 @@
+/*
+ * Created on Mon Oct 17 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Plugin ABI
+//!
+//! This module defines the `#[repr(C)]` types that are shared between the server and a
+//! plugin library. **Any change to the layout of these types must bump [`ABI_VERSION`]**,
+//! since plugins are built separately and we have no other way to tell if they agree with
+//! us on the layout.
+//!
+//! A plugin library must export a symbol named [`PLUGIN_INIT_SYMBOL`] with the signature
+//! of [`PluginInit`]. The returned [`PluginVTable`] (and every string and action it points
+//! to) must stay valid for as long as the library is loaded (which is until the server exits).
+
+use core::{ffi::c_void, slice};
+use libc::c_char;
+
+/// The current version of the plugin ABI
+pub const ABI_VERSION: u32 = 1;
+/// The name of the symbol that the loader looks up in every plugin library
+pub const PLUGIN_INIT_SYMBOL: &[u8] = b"sky_plugin_init\0";
+/// An arity value that indicates that the action accepts any number of arguments
+pub const ARITY_VARIADIC: i32 = -1;
+
+// handler status codes
+/// The handler ran successfully. If the handler set a reply, it is returned to the
+/// client, else the client receives an `Okay`
+pub const STATUS_OKAY: i32 = 0;
+/// The handler (or the host) found nothing
+pub const STATUS_NIL: i32 = 1;
+/// The action was used incorrectly
+pub const STATUS_ACTION_ERR: i32 = 2;
+/// The supplied data did not pass the table's encoding checks
+pub const STATUS_ENCODING_ERR: i32 = 3;
+/// The handler (or the host) failed for some other reason
+pub const STATUS_SERVER_ERR: i32 = 4;
+/// The host turned a write away (say, because the table is read-only or its keyspace is over
+/// quota). The client is told why, whatever the handler returns
+pub const STATUS_REJECTED: i32 = 5;
+
+/// A borrowed, immutable byte slice
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct PluginSlice {
+    pub ptr: *const u8,
+    pub len: usize,
+}
+
+impl PluginSlice {
+    pub fn new(slice: &[u8]) -> Self {
+        Self {
+            ptr: slice.as_ptr(),
+            len: slice.len(),
+        }
+    }
+    /// ## Safety
+    /// The caller must ensure that the slice is valid for the lifetime `'a`
+    pub unsafe fn as_slice<'a>(&self) -> &'a [u8] {
+        if self.len == 0 {
+            &[]
+        } else {
+            slice::from_raw_parts(self.ptr, self.len)
+        }
+    }
+}
+
+/// The reply set by a handler. This is opaque to plugins: they can only set it through
+/// [`HostApi::reply`]
+#[derive(Debug, Default)]
+pub struct PluginReply {
+    pub(super) data: Option<Vec<u8>>,
+}
+
+/// The embedded engine API that the host exposes to handlers. All the KV functions
+/// operate on the connection's current table, which is passed in as the opaque `ctx`
+#[repr(C)]
+pub struct HostApi {
+    pub abi_version: u32,
+    /// Get the value of `key` into `reply`. Returns [`STATUS_OKAY`] if the key was found,
+    /// [`STATUS_NIL`] if it wasn't
+    pub get: extern "C" fn(ctx: *const c_void, key: PluginSlice, reply: *mut PluginReply) -> i32,
+    /// Set `key` to `val`. Returns [`STATUS_OKAY`] if the key was set, [`STATUS_NIL`]
+    /// if it already existed and [`STATUS_REJECTED`] if the write was turned away
+    pub set: extern "C" fn(ctx: *const c_void, key: PluginSlice, val: PluginSlice) -> i32,
+    /// Remove `key`. Returns [`STATUS_OKAY`] if the key was removed, [`STATUS_NIL`] if it
+    /// didn't exist and [`STATUS_REJECTED`] if the removal was turned away
+    pub del: extern "C" fn(ctx: *const c_void, key: PluginSlice) -> i32,
+    /// Set the data that will be returned to the client (the data is copied)
+    pub reply: extern "C" fn(reply: *mut PluginReply, data: PluginSlice),
+}
+
+/// An action handler. The handler is called with the host API, the opaque context for the
+/// host API, the arguments to the action (excluding the action name) and the reply. It must
+/// return one of the `STATUS_*` codes. Handlers run on the worker thread for the connection,
+/// so they shouldn't block for long and **must not unwind**
+pub type PluginHandler = extern "C" fn(
+    api: *const HostApi,
+    ctx: *const c_void,
+    argv: *const PluginSlice,
+    argc: usize,
+    reply: *mut PluginReply,
+) -> i32;
+
+/// A single action exposed by a plugin
+#[repr(C)]
+pub struct ActionVTable {
+    /// The (null terminated) name of the action. This is case insensitive
+    pub name: *const c_char,
+    /// The exact number of arguments accepted, or [`ARITY_VARIADIC`]
+    pub arity: i32,
+    pub handler: PluginHandler,
+}
+
+/// The table returned by the init function of a plugin
+#[repr(C)]
+pub struct PluginVTable {
+    /// Must be equal to [`ABI_VERSION`]
+    pub abi_version: u32,
+    /// The (null terminated) name of the plugin
+    pub name: *const c_char,
+    pub actions: *const ActionVTable,
+    pub action_count: usize,
+}
+
+/// The signature of the init function exported by a plugin
+pub type PluginInit = unsafe extern "C" fn() -> *const PluginVTable;
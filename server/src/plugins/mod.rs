@@ -0,0 +1,408 @@
+/*
+ * Created on Mon Oct 17 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Plugins (experimental)
+//!
+//! This module implements a loader for shared libraries that expose additional actions, so
+//! that niche functionality doesn't have to live in-tree. It is only available when the
+//! server is built with the `plugins` feature (and on Unix-like systems).
+//!
+//! On startup, every shared library in [`DIR_PLUGINS`] is loaded and its actions are added
+//! to the dispatch table. A plugin action is only looked up if the query didn't match a
+//! builtin action, so plugins cannot override builtins. See [`abi`] for what a plugin
+//! needs to export.
+
+pub mod abi;
+#[cfg(test)]
+mod tests;
+
+use {
+    self::abi::{
+        ActionVTable, HostApi, PluginHandler, PluginInit, PluginReply, PluginSlice, PluginVTable,
+        ABI_VERSION, ARITY_VARIADIC, PLUGIN_INIT_SYMBOL, STATUS_ACTION_ERR, STATUS_ENCODING_ERR,
+        STATUS_NIL, STATUS_OKAY, STATUS_REJECTED, STATUS_SERVER_ERR,
+    },
+    crate::{
+        corestore::{
+            memstore::{Memstore, WriteRejection},
+            table::Table,
+            SharedSlice,
+        },
+        dbnet::prelude::*,
+        kvengine::KVEStandard,
+        util::error::{Error, SkyResult},
+    },
+    core::{cell::Cell, ffi::c_void},
+    parking_lot::{const_rwlock, RwLock},
+    std::{
+        ffi::{CStr, CString},
+        fs,
+        io::ErrorKind,
+        os::unix::ffi::OsStrExt,
+        path::Path,
+    },
+};
+
+/// The directory from which plugins are loaded
+pub const DIR_PLUGINS: &str = "plugins";
+/// File extensions that we consider to be shared libraries
+const PLUGIN_EXTENSIONS: [&str; 2] = ["so", "dylib"];
+
+/// The host API handed to every plugin handler
+static HOST_API: HostApi = HostApi {
+    abi_version: ABI_VERSION,
+    get: host_get,
+    set: host_set,
+    del: host_del,
+    reply: host_reply,
+};
+
+/// A list of (uppercased action name, action) pairs
+type ActionList = Vec<(Box<[u8]>, PluginAction)>;
+
+/// All actions registered by plugins. This is only written to during startup
+static ACTIONS: RwLock<ActionList> = const_rwlock(Vec::new());
+
+#[derive(Debug, Clone, Copy)]
+/// An action registered by a plugin
+pub struct PluginAction {
+    arity: i32,
+    handler: PluginHandler,
+}
+
+/// Returns the plugin action with the given (uppercased) name, if one was registered
+pub fn get_action(name: &[u8]) -> Option<PluginAction> {
+    ACTIONS
+        .read()
+        .iter()
+        .find(|(action_name, _)| action_name.as_ref() == name)
+        .map(|(_, action)| *action)
+}
+
+/// Load all plugins in `dir`. If the directory doesn't exist, nothing is loaded
+pub fn load_dir(dir: impl AsRef<Path>) -> SkyResult<()> {
+    let dir = dir.as_ref();
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(e) => return Err(Error::ioerror_extra(e, "reading the plugin directory")),
+    };
+    for entry in entries {
+        let path = entry
+            .map_err(|e| Error::ioerror_extra(e, "reading the plugin directory"))?
+            .path();
+        let is_library = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(|ext| PLUGIN_EXTENSIONS.contains(&ext))
+            .unwrap_or(false);
+        if path.is_file() && is_library {
+            load_plugin(&path)?;
+        }
+    }
+    Ok(())
+}
+
+/// Load the plugin at `path` and register all its actions
+fn load_plugin(path: &Path) -> SkyResult<()> {
+    let err = |msg: String| Error::OtherError(format!("failed to load plugin {path:?}: {msg}"));
+    let cpath = CString::new(path.as_os_str().as_bytes())
+        .map_err(|_| err("path contains a null byte".to_owned()))?;
+    let vtable = unsafe {
+        // UNSAFE(@ohsayan): Loading a library runs its initializers. That's what the user
+        // asked for by placing the library in the plugin directory. We never `dlclose` the
+        // handle since the actions must stay valid until we exit
+        let lib = libc::dlopen(cpath.as_ptr(), libc::RTLD_NOW | libc::RTLD_LOCAL);
+        if lib.is_null() {
+            return Err(err(dlerror()));
+        }
+        let init = libc::dlsym(lib, PLUGIN_INIT_SYMBOL.as_ptr() as *const _);
+        if init.is_null() {
+            return Err(err(dlerror()));
+        }
+        let init: PluginInit = core::mem::transmute(init);
+        let vtable = init();
+        if vtable.is_null() {
+            return Err(err("init returned a null vtable".to_owned()));
+        }
+        &*vtable
+    };
+    if vtable.abi_version != ABI_VERSION {
+        return Err(err(format!(
+            "plugin was built for ABI version {} but the server uses ABI version {ABI_VERSION}",
+            vtable.abi_version
+        )));
+    }
+    let (name, actions) = unsafe {
+        // UNSAFE(@ohsayan): The ABI requires the vtable to be valid for the lifetime of the
+        // library, which we never unload
+        read_vtable(vtable)
+    }
+    .map_err(err)?;
+    let mut registered = ACTIONS.write();
+    for (action_name, _) in actions.iter() {
+        if registered.iter().any(|(n, _)| n == action_name) {
+            return Err(err(format!(
+                "action `{}` was already registered",
+                String::from_utf8_lossy(action_name)
+            )));
+        }
+    }
+    log::info!("Loaded plugin `{name}` with {} action(s)", actions.len());
+    registered.extend(actions);
+    Ok(())
+}
+
+/// Read the name and actions from a plugin vtable
+unsafe fn read_vtable(vtable: &PluginVTable) -> Result<(String, ActionList), String> {
+    if vtable.name.is_null() || (vtable.actions.is_null() && vtable.action_count != 0) {
+        return Err("vtable contains a null pointer".to_owned());
+    }
+    let name = CStr::from_ptr(vtable.name).to_string_lossy().into_owned();
+    let raw_actions: &[ActionVTable] = if vtable.action_count == 0 {
+        &[]
+    } else {
+        core::slice::from_raw_parts(vtable.actions, vtable.action_count)
+    };
+    let mut actions: ActionList = Vec::with_capacity(raw_actions.len());
+    for action in raw_actions {
+        if action.name.is_null() {
+            return Err("action has a null name".to_owned());
+        }
+        let action_name = CStr::from_ptr(action.name).to_bytes().to_ascii_uppercase();
+        if action_name.is_empty() || !action_name.iter().all(u8::is_ascii_alphanumeric) {
+            return Err(format!(
+                "bad action name `{}`",
+                String::from_utf8_lossy(&action_name)
+            ));
+        }
+        if action.arity < ARITY_VARIADIC {
+            return Err(format!("bad arity {} for action", action.arity));
+        }
+        if actions.iter().any(|(n, _)| **n == *action_name) {
+            return Err(format!(
+                "action `{}` is exported more than once",
+                String::from_utf8_lossy(&action_name)
+            ));
+        }
+        actions.push((
+            action_name.into_boxed_slice(),
+            PluginAction {
+                arity: action.arity,
+                handler: action.handler,
+            },
+        ));
+    }
+    Ok((name, actions))
+}
+
+fn dlerror() -> String {
+    unsafe {
+        // UNSAFE(@ohsayan): dlerror either returns null or a valid C string
+        let e = libc::dlerror();
+        if e.is_null() {
+            "unknown error".to_owned()
+        } else {
+            CStr::from_ptr(e).to_string_lossy().into_owned()
+        }
+    }
+}
+
+/// The context that is handed to a plugin handler (as the opaque `ctx`) for the host API.
+/// Writes through the host API go through the same gates as a query's writes
+struct HostContext<'a> {
+    store: &'a Memstore,
+    table: &'a Table,
+    kve: &'a KVEStandard,
+    /// the request ID, if the action was run with `ONCE`
+    request_id: Option<&'a [u8]>,
+    /// set once the handler's first write has been checked against the dedup window
+    deduplicated: Cell<bool>,
+    /// set if the request ID was recorded in the dedup window
+    recorded: Cell<bool>,
+    /// why a write was turned away, if one was
+    rejection: Cell<Option<HostRejection>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+/// Why a write through the host API was turned away
+enum HostRejection {
+    Write(WriteRejection),
+    Duplicate,
+}
+
+impl<'a> HostContext<'a> {
+    /// Run the write gates for a write through the host API. The first write of a handler that
+    /// was run with `ONCE` also records the request ID
+    fn admit_write(&self, allocating: bool) -> Result<(), HostRejection> {
+        self.store
+            .admit_write(self.table, allocating)
+            .map_err(HostRejection::Write)?;
+        if let Some(request_id) = self.request_id {
+            let dedup = self.table.dedup_window();
+            if !self.deduplicated.replace(true) && dedup.is_enabled() {
+                if !dedup.record(request_id) {
+                    return Err(HostRejection::Duplicate);
+                }
+                self.recorded.set(true);
+            }
+        }
+        Ok(())
+    }
+    /// Run the write gates, returning the status code for the handler if the write was turned
+    /// away
+    fn gate_write(&self, allocating: bool) -> Option<i32> {
+        if !registry::state_okay() {
+            return Some(STATUS_SERVER_ERR);
+        }
+        match self.admit_write(allocating) {
+            Ok(()) => None,
+            Err(rejection) => {
+                self.rejection.set(Some(rejection));
+                Some(STATUS_REJECTED)
+            }
+        }
+    }
+}
+
+// host API impls. The context is always a `&HostContext` (see `execute`)
+
+extern "C" fn host_get(ctx: *const c_void, key: PluginSlice, reply: *mut PluginReply) -> i32 {
+    let ctx = unsafe { &*(ctx as *const HostContext) };
+    match ctx.kve.get_cloned(unsafe { key.as_slice() }) {
+        Ok(Some(val)) => {
+            unsafe { (*reply).data = Some(val.as_ref().to_vec()) };
+            STATUS_OKAY
+        }
+        Ok(None) => STATUS_NIL,
+        Err(()) => STATUS_ENCODING_ERR,
+    }
+}
+
+extern "C" fn host_set(ctx: *const c_void, key: PluginSlice, val: PluginSlice) -> i32 {
+    let ctx = unsafe { &*(ctx as *const HostContext) };
+    if let Some(status) = ctx.gate_write(true) {
+        return status;
+    }
+    let (key, val) = unsafe { (key.as_slice(), val.as_slice()) };
+    match ctx.kve.set(SharedSlice::new(key), SharedSlice::new(val)) {
+        Ok(true) => STATUS_OKAY,
+        Ok(false) => STATUS_NIL,
+        Err(()) => STATUS_ENCODING_ERR,
+    }
+}
+
+extern "C" fn host_del(ctx: *const c_void, key: PluginSlice) -> i32 {
+    let ctx = unsafe { &*(ctx as *const HostContext) };
+    if let Some(status) = ctx.gate_write(false) {
+        return status;
+    }
+    match ctx.kve.remove(unsafe { key.as_slice() }) {
+        Ok(true) => STATUS_OKAY,
+        Ok(false) => STATUS_NIL,
+        Err(()) => STATUS_ENCODING_ERR,
+    }
+}
+
+extern "C" fn host_reply(reply: *mut PluginReply, data: PluginSlice) {
+    unsafe { (*reply).data = Some(data.as_slice().to_vec()) }
+}
+
+action! {
+    /// Run a plugin action against the current table
+    fn execute(
+        handle: &Corestore,
+        con: &mut Connection<C, P>,
+        action: PluginAction,
+        request_id: Option<&[u8]>,
+        act: ActionIter<'a>
+    ) {
+        ensure_boolean_or_aerr::<P>(
+            action.arity == ARITY_VARIADIC || act.len() == action.arity as usize,
+        )?;
+        let kve = handle.get_table_with::<P, KVEBlob>()?;
+        let ctx = HostContext {
+            store: handle.get_store(),
+            table: handle.get_ctable_ref().unwrap_or_custom_aerr(P::RSTRING_DEFAULT_UNSET)?,
+            kve,
+            request_id,
+            deduplicated: Cell::new(false),
+            recorded: Cell::new(false),
+            rejection: Cell::new(None),
+        };
+        let mut reply = PluginReply::default();
+        let status = {
+            let argv: Vec<PluginSlice> = act.map(PluginSlice::new).collect();
+            (action.handler)(
+                &HOST_API,
+                &ctx as *const HostContext as *const c_void,
+                argv.as_ptr(),
+                argv.len(),
+                &mut reply,
+            )
+        };
+        let failed = ctx.rejection.get().is_some() || !matches!(status, STATUS_OKAY | STATUS_NIL);
+        if let (true, true, Some(request_id)) = (failed, ctx.recorded.get(), request_id) {
+            // so that a retry isn't suppressed
+            ctx.table.dedup_window().forget(request_id);
+        }
+        if let Some(rejection) = ctx.rejection.get() {
+            // the client is told why a write was turned away, whatever the handler returned
+            let response = match rejection {
+                HostRejection::Write(WriteRejection::Dropped) => {
+                    P::RSTRING_CONTAINER_NOT_FOUND.to_owned()
+                }
+                HostRejection::Write(WriteRejection::ReadOnly) => P::RSTRING_READ_ONLY.to_owned(),
+                HostRejection::Write(WriteRejection::Throttled(retry_after_ms)) => {
+                    P::rstring_throttled(retry_after_ms)
+                }
+                HostRejection::Write(WriteRejection::OutOfMemory) => {
+                    P::RSTRING_OUT_OF_MEMORY.to_owned()
+                }
+                HostRejection::Write(WriteRejection::QuotaExceeded) => {
+                    P::RSTRING_QUOTA_EXCEEDED.to_owned()
+                }
+                HostRejection::Duplicate => P::RSTRING_DUPLICATE_REQUEST.to_owned(),
+            };
+            con._write_raw(&response).await?;
+            return Ok(());
+        }
+        match status {
+            STATUS_OKAY => match reply.data {
+                Some(data) => {
+                    con.write_mono_length_prefixed_with_tsymbol(&data, P::TSYMBOL_BINARY)
+                        .await?
+                }
+                None => con._write_raw(P::RCODE_OKAY).await?,
+            },
+            STATUS_NIL => con._write_raw(P::RCODE_NIL).await?,
+            STATUS_ACTION_ERR => return util::err(P::RCODE_ACTION_ERR),
+            STATUS_ENCODING_ERR => return util::err(P::RCODE_ENCODING_ERROR),
+            _ => return util::err(P::RCODE_SERVER_ERR),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,175 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+use {
+    super::*,
+    std::{env, fs, path::PathBuf, process::Command},
+};
+
+/// The declarations a plugin needs, mirroring [`abi`]
+const PRELUDE: &str = r#"
+#include <stddef.h>
+#include <stdint.h>
+typedef struct { const uint8_t *ptr; size_t len; } slice_t;
+typedef struct {
+    uint32_t abi_version;
+    int32_t (*get)(const void *, slice_t, void *);
+    int32_t (*set)(const void *, slice_t, slice_t);
+    int32_t (*del)(const void *, slice_t);
+    void (*reply)(void *, slice_t);
+} host_api_t;
+typedef int32_t (*handler_t)(const host_api_t *, const void *, const slice_t *, size_t, void *);
+typedef struct { const char *name; int32_t arity; handler_t handler; } action_t;
+typedef struct {
+    uint32_t abi_version;
+    const char *name;
+    const action_t *actions;
+    size_t action_count;
+} vtable_t;
+"#;
+
+/// Build a plugin library from `source` (appended to [`PRELUDE`]) and return its path
+fn build_plugin(name: &str, source: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("skyd-plugin-tests-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let src = dir.join(format!("{name}.c"));
+    let lib = dir.join(format!("{name}.so"));
+    fs::write(&src, format!("{PRELUDE}{source}")).unwrap();
+    let status = Command::new("cc")
+        .args(["-shared", "-fPIC", "-o"])
+        .arg(&lib)
+        .arg(&src)
+        .status()
+        .unwrap();
+    assert!(status.success(), "failed to build plugin {name}");
+    lib
+}
+
+fn load_err(path: &Path) -> String {
+    match load_plugin(path) {
+        Err(Error::OtherError(e)) => e,
+        other => panic!("expected the plugin to be rejected, got {other:?}"),
+    }
+}
+
+#[test]
+fn test_load_good_plugin() {
+    let lib = build_plugin(
+        "good",
+        r#"
+static int32_t put(const host_api_t *api, const void *ctx, const slice_t *argv, size_t argc,
+                   void *reply) {
+    return api->set(ctx, argv[0], argv[1]);
+}
+static const action_t actions[] = {{"testput", 2, put}};
+static const vtable_t vtable = {1, "good", actions, 1};
+const vtable_t *sky_plugin_init(void) { return &vtable; }
+"#,
+    );
+    load_plugin(&lib).unwrap();
+    let action = get_action(b"TESTPUT").unwrap();
+    assert_eq!(action.arity, 2);
+    // loading it again would register the same action twice
+    assert!(load_err(&lib).contains("already registered"));
+    // writes through the host API go through the write gates
+    let store = Memstore::new_empty();
+    let table = Table::new_default_kve();
+    let ctx = HostContext {
+        store: &store,
+        table: &table,
+        kve: table.get_kvstore().unwrap(),
+        request_id: None,
+        deduplicated: Cell::new(false),
+        recorded: Cell::new(false),
+        rejection: Cell::new(None),
+    };
+    let run = |key: &[u8], val: &[u8]| {
+        let argv = [PluginSlice::new(key), PluginSlice::new(val)];
+        let mut reply = PluginReply::default();
+        (action.handler)(
+            &HOST_API,
+            &ctx as *const HostContext as *const c_void,
+            argv.as_ptr(),
+            argv.len(),
+            &mut reply,
+        )
+    };
+    assert_eq!(run(b"hello", b"world"), STATUS_OKAY);
+    assert_eq!(run(b"hello", b"world"), STATUS_NIL);
+    assert!(ctx.rejection.get().is_none());
+    table.set_read_only(true);
+    assert_eq!(run(b"hi", b"there"), STATUS_REJECTED);
+    assert!(matches!(
+        ctx.rejection.get(),
+        Some(HostRejection::Write(WriteRejection::ReadOnly))
+    ));
+    assert!(!ctx.kve.exists(b"hi").unwrap());
+}
+
+#[test]
+fn test_load_plugin_bad_abi_version() {
+    let lib = build_plugin(
+        "badabi",
+        r#"
+static const vtable_t vtable = {999, "badabi", NULL, 0};
+const vtable_t *sky_plugin_init(void) { return &vtable; }
+"#,
+    );
+    let e = load_err(&lib);
+    assert!(e.contains("built for ABI version 999"), "{e}");
+}
+
+#[test]
+fn test_load_plugin_missing_symbol() {
+    let lib = build_plugin(
+        "nosymbol",
+        r#"
+const vtable_t *not_the_init(void) { return NULL; }
+"#,
+    );
+    let e = load_err(&lib);
+    assert!(e.contains("sky_plugin_init"), "{e}");
+}
+
+#[test]
+fn test_load_plugin_duplicate_action() {
+    let lib = build_plugin(
+        "duplicate",
+        r#"
+static int32_t nop(const host_api_t *api, const void *ctx, const slice_t *argv, size_t argc,
+                   void *reply) {
+    return 0;
+}
+static const action_t actions[] = {{"dupaction", 0, nop}, {"DupAction", 1, nop}};
+static const vtable_t vtable = {1, "duplicate", actions, 2};
+const vtable_t *sky_plugin_init(void) { return &vtable; }
+"#,
+    );
+    let e = load_err(&lib);
+    assert!(e.contains("exported more than once"), "{e}");
+    // and none of its actions were registered
+    assert!(get_action(b"DUPACTION").is_none());
+}
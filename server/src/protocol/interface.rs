@@ -59,6 +59,14 @@ pub trait ProtocolSpec: Send + Sync {
     const TSYMBOL_ARRAY: u8;
     /// Type symbol for a flat array
     const TSYMBOL_FLAT_ARRAY: u8;
+    /// Type symbol for booleans
+    const TSYMBOL_BOOL: u8;
+    /// Type symbol for maps (a count of pairs, each of which is a key element followed by a
+    /// value element)
+    const TSYMBOL_MAP: u8;
+    /// Type symbol for the trace ID that is echoed before the response of a traced query (a
+    /// metadata element)
+    const TSYMBOL_TRACE_ID: u8;
 
     // charset
     /// The line-feed character or separator
@@ -109,6 +117,24 @@ pub trait ProtocolSpec: Send + Sync {
     const RSTRING_SNAPSHOT_ILLEGAL_NAME: &'static [u8];
     /// Respstring when a **very bad error** happens (use after termsig)
     const RSTRING_ERR_ACCESS_AFTER_TERMSIG: &'static [u8];
+    /// Respstring when a write carries a request ID that was already seen
+    const RSTRING_DUPLICATE_REQUEST: &'static [u8];
+    /// Respstring when `TTL` is run on a key that doesn't expire
+    const RSTRING_NO_EXPIRY: &'static [u8];
+    /// Respstring when a write is rejected because the memory limit was hit
+    const RSTRING_OUT_OF_MEMORY: &'static [u8];
+    /// Respstring when a write is rejected because the keyspace has hit its quota
+    const RSTRING_QUOTA_EXCEEDED: &'static [u8];
+    /// Respstring when a write is rejected because the table or the node is read-only
+    const RSTRING_READ_ONLY: &'static [u8];
+    /// Respstring when a write is rejected because a key or value is over its size limit
+    const RSTRING_TOO_LARGE: &'static [u8];
+    /// Respstring when a connection is refused because its IP has too many connections open
+    const RSTRING_TOO_MANY_CONNECTIONS: &'static [u8];
+    /// Respstring when a query is aborted because it ran past the query timeout
+    const RSTRING_TIMEOUT: &'static [u8];
+    /// Respstring when a cursor doesn't exist (or has expired)
+    const RSTRING_CURSOR_NOT_FOUND: &'static [u8];
     /// Respstring when the default container is unset
     const RSTRING_DEFAULT_UNSET: &'static [u8];
     /// Respstring when the container is not found
@@ -160,6 +186,12 @@ pub trait ProtocolSpec: Send + Sync {
     const FULLRESP_RCODE_PACKET_ERR: &'static [u8];
     /// A **full response** for a wrongtype error
     const FULLRESP_RCODE_WRONG_TYPE: &'static [u8];
+    /// A **full response** for a frame form that strict mode rejects
+    const FULLRESP_DEPRECATED_FRAME: &'static [u8];
+    /// A **full response** for an element that is too long for strict mode
+    const FULLRESP_ELEMENT_TOO_LONG: &'static [u8];
+    /// A **full response** for a query that is larger than the maximum query size
+    const FULLRESP_QUERY_TOO_LARGE: &'static [u8];
 
     // LUTs
     /// A LUT for SET operations
@@ -183,11 +215,14 @@ pub trait ProtocolSpec: Send + Sync {
         Self::RCODE_OKAY,
         Self::RCODE_NIL,
     );
-    const SKYHASH_PARSE_ERROR_LUT: [&'static [u8]; 4] = [
+    const SKYHASH_PARSE_ERROR_LUT: [&'static [u8]; 7] = [
         Self::FULLRESP_RCODE_PACKET_ERR,
         Self::FULLRESP_RCODE_PACKET_ERR,
         Self::FULLRESP_RCODE_WRONG_TYPE,
         Self::FULLRESP_RCODE_WRONG_TYPE,
+        Self::FULLRESP_DEPRECATED_FRAME,
+        Self::FULLRESP_ELEMENT_TOO_LONG,
+        Self::FULLRESP_QUERY_TOO_LARGE,
     ];
 
     // auth error respstrings
@@ -219,4 +254,11 @@ pub trait ProtocolSpec: Send + Sync {
     const NEEDS_TERMINAL_LF: bool;
 
     fn decode_packet(input: &[u8]) -> Result<QueryWithAdvance, ParseError>;
+    /// Same as [`Self::decode_packet`], but for strict mode
+    fn decode_packet_strict(input: &[u8]) -> Result<QueryWithAdvance, ParseError>;
+
+    // dynamic respstrings
+    /// Returns the respstring for a throttled write, with the suggested wait (in milliseconds)
+    /// before retrying
+    fn rstring_throttled(retry_after_ms: u64) -> Vec<u8>;
 }
@@ -28,7 +28,10 @@
 use self::interface::ProtocolSpec;
 use {
     crate::corestore::heap_array::HeapArray,
-    core::{fmt, slice},
+    core::{
+        fmt, slice,
+        sync::atomic::{AtomicBool, AtomicUsize, Ordering},
+    },
 };
 // pub mods
 pub mod interface;
@@ -49,6 +52,36 @@ pub const LATEST_PROTOCOL_VERSION: f32 = Skyhash2::PROTOCOL_VERSION;
 /// The latest protocol version supported by this version (`Skyhash-x.y`)
 pub const LATEST_PROTOCOL_VERSIONSTRING: &str = Skyhash2::PROTOCOL_VERSIONSTRING;
 
+/// The largest element (in bytes) that a client can send in strict mode
+pub const STRICT_MAX_ELEMENT_SIZE: usize = 16 * 1024 * 1024;
+/// Whether the server runs in strict mode
+static STRICT: AtomicBool = AtomicBool::new(false);
+/// The maximum size of a query in bytes
+static MAX_QUERY_SIZE: AtomicUsize = AtomicUsize::new(usize::MAX);
+
+/// Turn strict mode on or off. In strict mode, the server rejects (instead of leniently
+/// accepting) frame forms that are only supported for compatibility, unknown action names
+/// and elements that are longer than [`STRICT_MAX_ELEMENT_SIZE`]
+pub fn set_strict(strict: bool) {
+    STRICT.store(strict, Ordering::Release)
+}
+
+/// Returns true if the server runs in strict mode
+pub fn is_strict() -> bool {
+    STRICT.load(Ordering::Acquire)
+}
+
+/// Set the maximum size of a query in bytes (zero means no limit)
+pub fn set_max_query_size(size: usize) {
+    let size = if size == 0 { usize::MAX } else { size };
+    MAX_QUERY_SIZE.store(size, Ordering::Release)
+}
+
+/// Returns the maximum size of a query in bytes
+pub fn max_query_size() -> usize {
+    MAX_QUERY_SIZE.load(Ordering::Acquire)
+}
+
 #[derive(PartialEq)]
 /// As its name says, an [`UnsafeSlice`] is a terribly unsafe slice. It's guarantess are
 /// very C-like, your ptr goes dangling -- and everything is unsafe.
@@ -112,6 +145,23 @@ pub enum ParseError {
     DatatypeParseFailure = 3u8,
     /// The client supplied the wrong query data type for the given query
     WrongType = 4u8,
+    /// The query uses a frame form that is only accepted for compatibility (strict mode only)
+    DeprecatedFrame = 5u8,
+    /// An element is longer than [`STRICT_MAX_ELEMENT_SIZE`] (strict mode only)
+    ElementTooLong = 6u8,
+    /// The query is larger than the maximum query size (see [`set_max_query_size`])
+    QueryTooLarge = 7u8,
+}
+
+impl ParseError {
+    /// Returns true if the connection can't recover from this error, because we don't know where
+    /// the rejected query ends
+    pub const fn is_fatal(&self) -> bool {
+        matches!(
+            self,
+            Self::DeprecatedFrame | Self::ElementTooLong | Self::QueryTooLarge
+        )
+    }
 }
 
 /// A generic result to indicate parsing errors thorugh the [`ParseError`] enum
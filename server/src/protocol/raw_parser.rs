@@ -25,7 +25,7 @@
 */
 
 use {
-    super::{ParseError, ParseResult, UnsafeSlice},
+    super::{ParseError, ParseResult, UnsafeSlice, STRICT_MAX_ELEMENT_SIZE},
     core::mem::transmute,
 };
 
@@ -46,6 +46,7 @@ These distinctions reduce the likelihood of "accidentally incorrect impls" (we c
 /// - `cursor_ptr` -> Should point to the current position in the buffer for the parser
 /// - `cursor_ptr_mut` -> a mutable reference to the cursor
 /// - `data_end_ptr` -> a ptr to one byte past the allocated area of the buffer
+/// - `data_start_ptr` -> a ptr to the start of the buffer (where the query starts)
 ///
 /// All implementors of `RawParser` get a free implementation for `RawParserMeta` and `RawParserExt`
 ///
@@ -56,6 +57,12 @@ pub(super) unsafe trait RawParser {
     fn cursor_ptr(&self) -> *const u8;
     fn cursor_ptr_mut(&mut self) -> &mut *const u8;
     fn data_end_ptr(&self) -> *const u8;
+    fn data_start_ptr(&self) -> *const u8;
+    /// Returns true if the parser should reject the frame forms that are only accepted for
+    /// compatibility
+    fn is_strict(&self) -> bool;
+    /// Returns the maximum size of a query in bytes
+    fn max_query_size(&self) -> usize;
 }
 
 /// The `RawParserMeta` trait builds on top of the `RawParser` trait to provide low-level interactions
@@ -66,6 +73,10 @@ pub(super) trait RawParserMeta: RawParser {
     fn remaining(&self) -> usize {
         self.data_end_ptr() as usize - self.cursor_ptr() as usize
     }
+    /// Check how many bytes of the query we have read so far
+    fn consumed(&self) -> usize {
+        self.cursor_ptr() as usize - self.data_start_ptr() as usize
+    }
     /// Check if we have `size` bytes remaining
     fn has_remaining(&self, size: usize) -> bool {
         self.remaining() >= size
@@ -152,6 +163,10 @@ pub(super) trait RawParserExt: RawParser + RawParserMeta {
     fn read_usize(&mut self) -> ParseResult<usize> {
         let line = self.read_line_pedantic()?;
         let bytes = unsafe { line.as_slice() };
+        if self.is_strict() && bytes.len() > 1 && bytes[0] == b'0' {
+            // zero-padded sizes
+            return Err(ParseError::DeprecatedFrame);
+        }
         let mut ret = 0usize;
         for byte in bytes {
             if byte.is_ascii_digit() {
@@ -169,6 +184,29 @@ pub(super) trait RawParserExt: RawParser + RawParserMeta {
         }
         Ok(ret)
     }
+    /// Read the size of an element, which, in strict mode, can't exceed [`STRICT_MAX_ELEMENT_SIZE`]
+    /// (and which can't take the query past the maximum query size either)
+    fn read_element_size(&mut self) -> ParseResult<usize> {
+        let size = self.read_usize()?;
+        if self.is_strict() && size > STRICT_MAX_ELEMENT_SIZE {
+            Err(ParseError::ElementTooLong)
+        } else if self.consumed().saturating_add(size) > self.max_query_size() {
+            Err(ParseError::QueryTooLarge)
+        } else {
+            Ok(size)
+        }
+    }
+    /// Read the number of elements (or queries) that follow. Since every element takes at least
+    /// two bytes, this rejects counts that can't fit in the maximum query size (before anything
+    /// is allocated for them)
+    fn read_count(&mut self) -> ParseResult<usize> {
+        let count = self.read_usize()?;
+        if self.consumed().saturating_add(count.saturating_mul(2)) > self.max_query_size() {
+            Err(ParseError::QueryTooLarge)
+        } else {
+            Ok(count)
+        }
+    }
 }
 
 impl<T> RawParserExt for T where T: RawParser + RawParserMeta {}
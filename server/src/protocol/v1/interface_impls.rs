@@ -46,6 +46,9 @@ impl ProtocolSpec for Skyhash1 {
     const TSYMBOL_TYPED_NON_NULL_ARRAY: u8 = b'^';
     const TSYMBOL_ARRAY: u8 = b'&';
     const TSYMBOL_FLAT_ARRAY: u8 = b'_';
+    const TSYMBOL_BOOL: u8 = b'=';
+    const TSYMBOL_MAP: u8 = b'{';
+    const TSYMBOL_TRACE_ID: u8 = b'#';
 
     // typed array
     const TYPE_TYPED_ARRAY_ELEMENT_NULL: &'static [u8] = b"\0";
@@ -73,6 +76,15 @@ impl ProtocolSpec for Skyhash1 {
     const RSTRING_SNAPSHOT_DUPLICATE: &'static [u8] = eresp!("duplicate-snapshot");
     const RSTRING_SNAPSHOT_ILLEGAL_NAME: &'static [u8] = eresp!("err-invalid-snapshot-name");
     const RSTRING_ERR_ACCESS_AFTER_TERMSIG: &'static [u8] = eresp!("err-access-after-termsig");
+    const RSTRING_DUPLICATE_REQUEST: &'static [u8] = eresp!("duplicate-request");
+    const RSTRING_NO_EXPIRY: &'static [u8] = eresp!("no-expiry");
+    const RSTRING_OUT_OF_MEMORY: &'static [u8] = eresp!("err-out-of-memory");
+    const RSTRING_QUOTA_EXCEEDED: &'static [u8] = eresp!("err-quota-exceeded");
+    const RSTRING_READ_ONLY: &'static [u8] = eresp!("err-read-only");
+    const RSTRING_TOO_LARGE: &'static [u8] = eresp!("err-too-large");
+    const RSTRING_TOO_MANY_CONNECTIONS: &'static [u8] = eresp!("err-too-many-connections");
+    const RSTRING_TIMEOUT: &'static [u8] = eresp!("err-timeout");
+    const RSTRING_CURSOR_NOT_FOUND: &'static [u8] = eresp!("err-cursor-not-found");
 
     // keyspace related resps
     const RSTRING_DEFAULT_UNSET: &'static [u8] = eresp!("default-container-unset");
@@ -102,6 +114,9 @@ impl ProtocolSpec for Skyhash1 {
     // full responses
     const FULLRESP_RCODE_PACKET_ERR: &'static [u8] = b"*1\n!1\n4\n";
     const FULLRESP_RCODE_WRONG_TYPE: &'static [u8] = b"*1\n!1\n7\n";
+    const FULLRESP_DEPRECATED_FRAME: &'static [u8] = b"*1\n!20\nerr-deprecated-frame\n";
+    const FULLRESP_ELEMENT_TOO_LONG: &'static [u8] = b"*1\n!20\nerr-element-too-long\n";
+    const FULLRESP_QUERY_TOO_LARGE: &'static [u8] = b"*1\n!19\nerr-query-too-large\n";
 
     // auth rcodes/strings
     const AUTH_ERROR_ALREADYCLAIMED: &'static [u8] = eresp!("err-auth-already-claimed");
@@ -127,4 +142,13 @@ impl ProtocolSpec for Skyhash1 {
     fn decode_packet(input: &[u8]) -> Result<QueryWithAdvance, ParseError> {
         Skyhash1::parse(input)
     }
+
+    fn decode_packet_strict(input: &[u8]) -> Result<QueryWithAdvance, ParseError> {
+        Skyhash1::parse_strict(input)
+    }
+
+    fn rstring_throttled(retry_after_ms: u64) -> Vec<u8> {
+        let rstring = format!("err-throttled-retry-after-{retry_after_ms}");
+        format!("!{}\n{rstring}\n", rstring.len()).into_bytes()
+    }
 }
@@ -58,6 +58,9 @@ mod tests;
 pub struct Parser {
     end: *const u8,
     cursor: *const u8,
+    start: *const u8,
+    strict: bool,
+    max_query_size: usize,
 }
 
 unsafe impl RawParser for Parser {
@@ -70,6 +73,15 @@ unsafe impl RawParser for Parser {
     fn data_end_ptr(&self) -> *const u8 {
         self.end
     }
+    fn data_start_ptr(&self) -> *const u8 {
+        self.start
+    }
+    fn is_strict(&self) -> bool {
+        self.strict
+    }
+    fn max_query_size(&self) -> usize {
+        self.max_query_size
+    }
 }
 
 unsafe impl Send for Parser {}
@@ -82,6 +94,9 @@ impl Parser {
             Self {
                 end: slice.as_ptr().add(slice.len()),
                 cursor: slice.as_ptr(),
+                start: slice.as_ptr(),
+                strict: false,
+                max_query_size: super::max_query_size(),
             }
         }
     }
@@ -111,7 +126,7 @@ impl Parser {
     }
     /// Gets the _next element. **The cursor should be at the tsymbol (passed)**
     fn _next(&mut self) -> ParseResult<UnsafeSlice> {
-        let element_size = self.read_usize()?;
+        let element_size = self.read_element_size()?;
         self.read_until(element_size)
     }
 }
@@ -157,7 +172,11 @@ impl Parser {
                 // UNSAFE(@ohsayan): Just checked length
                 self.incr_cursor();
             }
-            let query_count = self.read_usize()?;
+            let query_count = self.read_count()?;
+            if self.strict && query_count == 0 {
+                // empty queries
+                return Err(ParseError::DeprecatedFrame);
+            }
             let mut writer = HeapArrayWriter::with_capacity(query_count);
             for i in 0..query_count {
                 unsafe {
@@ -222,7 +241,11 @@ impl Parser {
                 // UNSAFE(@ohsayan): Checked buffer len and incremented, so we're good
                 self.incr_cursor()
             };
-            let query_count = self.read_usize()?; // get the length
+            let query_count = self.read_count()?; // get the length
+            if self.strict && query_count == 0 {
+                // empty pipelines
+                return Err(ParseError::DeprecatedFrame);
+            }
             if query_count == 1 {
                 Ok(Query::Simple(self.parse_simple_query()?))
             } else {
@@ -233,9 +256,18 @@ impl Parser {
         }
     }
     pub fn parse(buf: &[u8]) -> ParseResult<QueryWithAdvance> {
+        Self::new(buf).parse_from(buf)
+    }
+    /// Same as [`Self::parse`], but rejects the frame forms that are only accepted for
+    /// compatibility and elements longer than [`super::STRICT_MAX_ELEMENT_SIZE`]
+    pub fn parse_strict(buf: &[u8]) -> ParseResult<QueryWithAdvance> {
         let mut slf = Self::new(buf);
-        let body = slf._parse()?;
-        let consumed = slf.cursor_ptr() as usize - buf.as_ptr() as usize;
+        slf.strict = true;
+        slf.parse_from(buf)
+    }
+    fn parse_from(mut self, buf: &[u8]) -> ParseResult<QueryWithAdvance> {
+        let body = self._parse()?;
+        let consumed = self.cursor_ptr() as usize - buf.as_ptr() as usize;
         Ok((body, consumed))
     }
 }
@@ -91,3 +91,45 @@ fn parse_pipelined_query_incomplete() {
         assert_eq!(Parser::parse(slice).unwrap_err(), ParseError::NotEnough);
     }
 }
+
+#[test]
+fn parse_strict() {
+    assert_eq!(Parser::parse_strict(SQPAYLOAD).unwrap().1, SQPAYLOAD.len());
+    assert_eq!(Parser::parse_strict(PQPAYLOAD).unwrap().1, PQPAYLOAD.len());
+    let deprecated: [&[u8]; 3] = [b"*0\n", b"*1\n~0\n", b"*1\n~1\n03\nGET\n"];
+    assert!(Parser::parse(b"*1\n~1\n03\nGET\n").is_ok());
+    for body in deprecated {
+        assert_eq!(
+            Parser::parse_strict(body).unwrap_err(),
+            ParseError::DeprecatedFrame
+        );
+    }
+    let body = b"*1\n~2\n3\nGET\n99999999\n";
+    assert_eq!(Parser::parse(body).unwrap_err(), ParseError::NotEnough);
+    assert_eq!(
+        Parser::parse_strict(body).unwrap_err(),
+        ParseError::ElementTooLong
+    );
+}
+
+#[test]
+fn parse_max_query_size() {
+    let parse = |body: &[u8], max_query_size: usize| {
+        let mut parser = Parser::new(body);
+        parser.max_query_size = max_query_size;
+        parser.parse_from(body).map(|(_, advance)| advance)
+    };
+    assert_eq!(parse(SQPAYLOAD, SQPAYLOAD.len()).unwrap(), SQPAYLOAD.len());
+    assert_eq!(
+        parse(SQPAYLOAD, SQPAYLOAD.len() - 2).unwrap_err(),
+        ParseError::QueryTooLarge
+    );
+    assert_eq!(
+        parse(b"*1\n~2\n3\nGET\n99999999\n", 1024).unwrap_err(),
+        ParseError::QueryTooLarge
+    );
+    assert_eq!(
+        parse(b"*99999999\n", 1024).unwrap_err(),
+        ParseError::QueryTooLarge
+    );
+}
@@ -43,6 +43,9 @@ mod tests;
 pub struct Parser {
     end: *const u8,
     cursor: *const u8,
+    start: *const u8,
+    strict: bool,
+    max_query_size: usize,
 }
 
 unsafe impl RawParser for Parser {
@@ -55,6 +58,15 @@ unsafe impl RawParser for Parser {
     fn data_end_ptr(&self) -> *const u8 {
         self.end
     }
+    fn data_start_ptr(&self) -> *const u8 {
+        self.start
+    }
+    fn is_strict(&self) -> bool {
+        self.strict
+    }
+    fn max_query_size(&self) -> usize {
+        self.max_query_size
+    }
 }
 
 unsafe impl Sync for Parser {}
@@ -67,6 +79,9 @@ impl Parser {
             Self {
                 end: slice.as_ptr().add(slice.len()),
                 cursor: slice.as_ptr(),
+                start: slice.as_ptr(),
+                strict: false,
+                max_query_size: super::max_query_size(),
             }
         }
     }
@@ -87,11 +102,15 @@ impl Parser {
     /// ...
     /// ```
     fn _next_simple_query(&mut self) -> ParseResult<HeapArray<UnsafeSlice>> {
-        let element_count = self.read_usize()?;
+        let element_count = self.read_count()?;
+        if self.strict && element_count == 0 {
+            // empty queries
+            return Err(ParseError::DeprecatedFrame);
+        }
         unsafe {
             let mut data = HeapArray::new_writer(element_count);
             for i in 0..element_count {
-                let element_size = self.read_usize()?;
+                let element_size = self.read_element_size()?;
                 let element = self.read_until(element_size)?;
                 data.write_to_index(i, element);
             }
@@ -141,7 +160,11 @@ impl Parser {
     /// x    -> Q2E2 itself
     /// ```
     fn next_pipeline(&mut self) -> ParseResult<PipelinedQuery> {
-        let query_count = self.read_usize()?;
+        let query_count = self.read_count()?;
+        if self.strict && query_count < 2 {
+            // empty pipelines and pipelines that should have been simple queries
+            return Err(ParseError::DeprecatedFrame);
+        }
         unsafe {
             let mut queries = HeapArray::new_writer(query_count);
             for i in 0..query_count {
@@ -178,9 +201,18 @@ impl Parser {
     // only expose this. don't expose Self::new since that'll be _relatively easier_ to
     // invalidate invariants for
     pub fn parse(buf: &[u8]) -> ParseResult<QueryWithAdvance> {
+        Self::new(buf).parse_from(buf)
+    }
+    /// Same as [`Self::parse`], but rejects the frame forms that are only accepted for
+    /// compatibility and elements longer than [`super::STRICT_MAX_ELEMENT_SIZE`]
+    pub fn parse_strict(buf: &[u8]) -> ParseResult<QueryWithAdvance> {
         let mut slf = Self::new(buf);
-        let body = slf._parse()?;
-        let consumed = slf.cursor_ptr() as usize - buf.as_ptr() as usize;
+        slf.strict = true;
+        slf.parse_from(buf)
+    }
+    fn parse_from(mut self, buf: &[u8]) -> ParseResult<QueryWithAdvance> {
+        let body = self._parse()?;
+        let consumed = self.cursor_ptr() as usize - buf.as_ptr() as usize;
         Ok((body, consumed))
     }
 }
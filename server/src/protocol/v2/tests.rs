@@ -643,3 +643,63 @@ fn test_iter() {
     assert_eq!(iter.next().unwrap(), "x".as_bytes());
     assert_eq!(iter.next().unwrap(), "100".as_bytes());
 }
+
+#[test]
+fn parse_strict() {
+    // conforming queries are okay
+    let body = v!(b"*3\n3\nSET1\nx3\n100");
+    assert_eq!(Parser::parse_strict(&body).unwrap().1, body.len());
+    let body = v!(b"$2\n3\n3\nSET1\nx3\n1002\n3\nGET1\nx");
+    assert_eq!(Parser::parse_strict(&body).unwrap().1, body.len());
+    // frame forms that are only accepted for compatibility
+    assert!(Parser::parse(b"*03\n3\nSET1\nx3\n100").is_ok());
+    assert!(Parser::parse(b"$1\n2\n3\nGET1\nx").is_ok());
+    let deprecated: [&[u8]; 5] = [
+        b"*03\n3\nSET1\nx3\n100",
+        b"*3\n03\nSET1\nx3\n100",
+        b"*0\n",
+        b"$0\n",
+        b"$1\n2\n3\nGET1\nx",
+    ];
+    for body in deprecated {
+        assert_eq!(
+            Parser::parse_strict(body).unwrap_err(),
+            ParseError::DeprecatedFrame
+        );
+    }
+    // over-length elements are rejected before they're read
+    let body = v!(b"*2\n3\nGET99999999\n");
+    assert_eq!(Parser::parse(&body).unwrap_err(), ParseError::NotEnough);
+    assert_eq!(
+        Parser::parse_strict(&body).unwrap_err(),
+        ParseError::ElementTooLong
+    );
+}
+
+#[test]
+fn parse_max_query_size() {
+    let parse = |body: &[u8], max_query_size: usize| {
+        let mut parser = Parser::new(body);
+        parser.max_query_size = max_query_size;
+        parser.parse_from(body).map(|(_, advance)| advance)
+    };
+    let body = b"*2\n3\nGET1\nx";
+    assert_eq!(parse(body, body.len()).unwrap(), body.len());
+    assert_eq!(
+        parse(body, body.len() - 1).unwrap_err(),
+        ParseError::QueryTooLarge
+    );
+    // sizes and counts are rejected before the rest of the query is read
+    assert_eq!(
+        parse(b"*2\n3\nGET99999999\n", 1024).unwrap_err(),
+        ParseError::QueryTooLarge
+    );
+    assert_eq!(
+        parse(b"*99999999\n", 1024).unwrap_err(),
+        ParseError::QueryTooLarge
+    );
+    assert_eq!(
+        parse(b"$99999999\n", 1024).unwrap_err(),
+        ParseError::QueryTooLarge
+    );
+}
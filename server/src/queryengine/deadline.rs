@@ -0,0 +1,103 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Query deadlines
+//!
+//! An opt-in limit on how long a single query may run (`server.query_timeout`). Every query
+//! starts a [`Deadline`] that long-running actions check as they go: if it passes before the
+//! action has written anything, the query fails with `err-timeout`. If the response is already
+//! partially written, it can't be completed, so the connection is closed instead of stalling
+//! until the rest of the response is written
+
+use {
+    crate::{actions::ActionResult, protocol::interface::ProtocolSpec},
+    core::sync::atomic::{AtomicU64, Ordering},
+    std::{
+        io::{Error as IoError, ErrorKind},
+        time::{Duration, Instant},
+    },
+};
+
+/// The maximum execution time of a query in milliseconds. Zero if there is no limit
+static QUERY_TIMEOUT: AtomicU64 = AtomicU64::new(0);
+
+/// Set the query timeout. This must be called before any connection is accepted
+pub fn init(timeout_ms: u64) {
+    QUERY_TIMEOUT.store(timeout_ms, Ordering::Release);
+}
+
+/// The point in time by which a query has to finish (if there is a limit)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Deadline {
+    at: Option<Instant>,
+}
+
+impl Deadline {
+    /// Start the deadline of a new query
+    pub fn start() -> Self {
+        Self::after(QUERY_TIMEOUT.load(Ordering::Acquire))
+    }
+    fn after(timeout_ms: u64) -> Self {
+        let at = match timeout_ms {
+            0 => None,
+            ms => Some(Instant::now() + Duration::from_millis(ms)),
+        };
+        Self { at }
+    }
+    /// Returns true if the deadline has passed
+    pub fn is_expired(&self) -> bool {
+        self.at.is_some_and(|at| Instant::now() >= at)
+    }
+    /// Returns `err-timeout` if the deadline has passed (for use before anything has been
+    /// written)
+    pub fn check<P: ProtocolSpec>(&self) -> ActionResult<()> {
+        if self.is_expired() {
+            Err(P::RSTRING_TIMEOUT.into())
+        } else {
+            Ok(())
+        }
+    }
+    /// Returns a timeout error if the deadline has passed (for use once the response has
+    /// been partially written)
+    pub fn check_io(&self) -> Result<(), IoError> {
+        if self.is_expired() {
+            Err(IoError::new(ErrorKind::TimedOut, "query timed out"))
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[test]
+fn test_deadline() {
+    assert!(!Deadline::default().is_expired());
+    assert!(!Deadline::after(0).is_expired());
+    assert!(!Deadline::after(60_000).is_expired());
+    let deadline = Deadline::after(1);
+    std::thread::sleep(Duration::from_millis(5));
+    assert!(deadline.is_expired());
+    assert_eq!(deadline.check_io().unwrap_err().kind(), ErrorKind::TimedOut);
+}
@@ -31,16 +31,50 @@ use crate::{
     admin, auth, blueql,
     corestore::Corestore,
     dbnet::{prelude::*, BufferedSocketStream},
+    kvengine::eviction,
     protocol::{iter::AnyArrayIter, PipelinedQuery, SimpleQuery, UnsafeSlice},
+    util::compiler,
 };
 
+pub mod deadline;
+pub mod ratelimit;
+
 pub type ActionIter<'a> = AnyArrayIter<'a>;
 
 const ACTION_AUTH: &[u8] = b"auth";
+/// The prefix that tags a stage with a request ID (`ONCE <request ID> <action> ...`)
+const PREFIX_ONCE: &[u8] = b"ONCE";
+/// The prefix of stages that carry a trace ID (`TRACE <id> <action> ...`)
+const PREFIX_TRACE: &[u8] = b"TRACE";
+/// The maximum length of a trace ID
+const MAX_TRACE_ID_LEN: usize = 128;
+/// The actions that write to the current table, and are hence subject to its write throttle,
+/// dedup window and read-only flag
+// kept packed (rustfmt would put every name on its own line) so the lists stay easy to scan
+#[rustfmt::skip]
+const WRITE_ACTIONS: [&[u8]; 39] = [
+    b"SET", b"SETX", b"UPDATE", b"DEL", b"DELMATCH", b"MSET", b"MUPDATE", b"SSET", b"SDEL",
+    b"SUPDATE", b"USET", b"GETSET", b"POP", b"GETDEL", b"MPOP", b"LSET", b"LMOD", b"LTRIM",
+    b"LINSERT", b"LREM", b"HSET", b"HDEL", b"SADD", b"SREM", b"ZADD", b"ZREM", b"JSET", b"INCRBY",
+    b"DECRBY", b"EXPIRE", b"PEXPIRE", b"PERSIST", b"EXEC", b"CAS", b"SETIFEQ", b"SETRANGE",
+    b"SETBIT", b"PFADD", b"PFMERGE",
+];
+/// The writes that can allocate, and are hence subject to the memory limit
+#[rustfmt::skip]
+const ALLOCATING_ACTIONS: [&[u8]; 25] = [
+    b"SET", b"SETX", b"UPDATE", b"MSET", b"MUPDATE", b"SSET", b"SUPDATE", b"USET", b"GETSET",
+    b"LSET", b"LMOD", b"LINSERT", b"HSET", b"SADD", b"ZADD", b"JSET", b"INCRBY", b"DECRBY", b"EXEC",
+    b"CAS", b"SETIFEQ", b"SETRANGE", b"SETBIT", b"PFADD", b"PFMERGE",
+];
+/// The writes that take key/value pairs (rather than a key followed by values), which matters
+/// for the size limits
+const PAIR_ACTIONS: [&[u8]; 5] = [b"MSET", b"MUPDATE", b"SSET", b"SUPDATE", b"USET"];
+/// The actions that are run (rather than queued) while a transaction is open
+const TXN_ACTIONS: [&[u8]; 3] = [b"MULTI", b"EXEC", b"DISCARD"];
 
 macro_rules! gen_constants_and_matches {
     (
-        $con:expr, $buf:ident, $db:ident, $($action:ident => $fns:path),*,
+        $con:expr, $buf:ident, $db:ident, $request_id:ident, $($action:ident => $fns:path),*,
         {$($action2:ident => $fns2:expr),*}
     ) => {
         mod tags {
@@ -63,6 +97,20 @@ macro_rules! gen_constants_and_matches {
                 tags::$action2 => $fns2.await?,
             )*
             _ => {
+                #[cfg(all(feature = "plugins", unix))]
+                if let Some(action) = crate::plugins::get_action(&first) {
+                    let request_id = $request_id.map(|id| unsafe {
+                        // UNSAFE(@ohsayan): The presence of the connection guarantees that this
+                        // won't suddenly become invalid
+                        id.as_slice()
+                    });
+                    crate::plugins::execute($db, $con, action, request_id, $buf).await?;
+                    return Ok(());
+                }
+                if crate::protocol::is_strict() && self::is_action_name(first_slice) {
+                    // don't let an unknown action be parsed as BlueQL
+                    return util::err(P::RCODE_UNKNOWN_ACTION);
+                }
                 blueql::execute($db, $con, first_slice, $buf.len()).await?;
             }
         }
@@ -77,16 +125,7 @@ action! {
         auth: &mut AuthProviderHandle,
         buf: SimpleQuery
     ) {
-        let bufref = buf.as_slice();
-        let mut iter = unsafe {
-            // UNSAFE(@ohsayan): The presence of the connection guarantees that this
-            // won't suddenly become invalid
-            AnyArrayIter::new(bufref.iter())
-        };
-        match iter.next_lowercase().unwrap_or_custom_aerr(P::RCODE_PACKET_ERR)?.as_ref() {
-            ACTION_AUTH => auth::auth_login_only(con, auth, iter).await,
-            _ => util::err(P::AUTH_CODE_BAD_CREDENTIALS),
-        }
+        self::execute_stage_noauth(con, auth, buf.as_slice()).await
     }
     //// Execute a simple query
     fn execute_simple(
@@ -99,12 +138,167 @@ action! {
     }
 }
 
-async fn execute_stage<'a, P: ProtocolSpec, C: BufferedSocketStream>(
+/// Execute a stage for an anonymous user (who can only log in)
+pub async fn execute_stage_noauth<P: ProtocolSpec, C: BufferedSocketStream>(
+    con: &mut Connection<C, P>,
+    auth: &mut AuthProviderHandle,
+    buf: &[UnsafeSlice],
+) -> ActionResult<()> {
+    let mut iter = unsafe {
+        // UNSAFE(@ohsayan): The presence of the connection guarantees that this
+        // won't suddenly become invalid
+        AnyArrayIter::new(buf.iter())
+    };
+    match iter
+        .next_lowercase()
+        .unwrap_or_custom_aerr(P::RCODE_PACKET_ERR)?
+        .as_ref()
+    {
+        ACTION_AUTH => auth::auth_login_only(con, auth, iter).await,
+        _ => util::err(P::AUTH_CODE_BAD_CREDENTIALS),
+    }
+}
+
+/// Execute a stage. A stage prefixed with `TRACE <id>` has the trace ID echoed (as a metadata
+/// element) before its response, and the trace ID is added to the logs about the stage
+pub async fn execute_stage<P: ProtocolSpec, C: BufferedSocketStream>(
+    db: &mut Corestore,
+    con: &mut Connection<C, P>,
+    auth: &mut AuthProviderHandle,
+    buf: &[UnsafeSlice],
+) -> ActionResult<()> {
+    con.start_deadline();
+    let (trace_id, buf) = match self::first_slice(buf) {
+        Some(first) if first.eq_ignore_ascii_case(PREFIX_TRACE) => {
+            ensure_boolean_or_aerr::<P>(buf.len() >= 3)?;
+            let trace_id = unsafe {
+                // UNSAFE(@ohsayan): The presence of the connection guarantees that this
+                // won't suddenly become invalid
+                buf[1].as_slice()
+            };
+            ensure_boolean_or_aerr::<P>(trace_id.len() <= MAX_TRACE_ID_LEN)?;
+            con.write_trace_id(trace_id).await?;
+            (Some(String::from_utf8_lossy(trace_id)), &buf[2..])
+        }
+        _ => (None, buf),
+    };
+    let ret = self::run_stage(db, con, auth, buf).await;
+    let trace = match &trace_id {
+        Some(trace_id) => format!(" (trace `{trace_id}`)"),
+        None => String::new(),
+    };
+    if con.deadline().is_expired() {
+        log::warn!("Query ran past the query timeout{trace}");
+    }
+    match &ret {
+        Err(ActionError::IoError(e)) if trace_id.is_some() => {
+            log::error!("Query failed{trace}: {e}")
+        }
+        Err(ActionError::ActionError(e)) if *e == P::RCODE_SERVER_ERR => {
+            log::error!("Query failed with a server error{trace}")
+        }
+        _ => {}
+    }
+    ret
+}
+
+/// Execute a stage (without its trace ID)
+async fn run_stage<P: ProtocolSpec, C: BufferedSocketStream>(
     db: &mut Corestore,
     con: &mut Connection<C, P>,
     auth: &mut AuthProviderHandle,
     buf: &[UnsafeSlice],
 ) -> ActionResult<()> {
+    if let Some(retry_after_ms) = ratelimit::check(con.rate_limit(), auth.provider().current_user())
+    {
+        con._write_raw(&P::rstring_throttled(retry_after_ms))
+            .await?;
+        return Ok(());
+    }
+    let (request_id, buf) = match self::first_slice(buf) {
+        Some(first) if first.eq_ignore_ascii_case(PREFIX_ONCE) => {
+            ensure_boolean_or_aerr::<P>(buf.len() >= 3)?;
+            (Some(&buf[1]), &buf[2..])
+        }
+        _ => (None, buf),
+    };
+    if let (Some(client), Some(action)) = (con.client(), self::first_slice(buf)) {
+        client.record(auth.provider().current_user(), action);
+    }
+    if self::is_too_large(db, buf) {
+        con._write_raw(P::RSTRING_TOO_LARGE).await?;
+        return Ok(());
+    }
+    if db.txn_mut().is_some() && !self::is_one_of(buf, &TXN_ACTIONS) {
+        // a transaction is open, so writes are queued until EXEC
+        return actions::txn::queue(db, con, buf).await;
+    }
+    if let Ok(ks) = db.get_cks() {
+        ks.usage().record(self::is_write(buf));
+    }
+    if self::is_dropped_write(db, buf) {
+        con._write_raw(P::RSTRING_CONTAINER_NOT_FOUND).await?;
+        return Ok(());
+    }
+    if self::is_read_only_write(db, buf) {
+        con._write_raw(P::RSTRING_READ_ONLY).await?;
+        return Ok(());
+    }
+    if let Some(retry_after_ms) = self::throttle_write(db, buf) {
+        con._write_raw(&P::rstring_throttled(retry_after_ms))
+            .await?;
+        return Ok(());
+    }
+    if !self::reserve_memory(db, buf) {
+        con._write_raw(P::RSTRING_OUT_OF_MEMORY).await?;
+        return Ok(());
+    }
+    if self::is_over_quota(db, buf) {
+        con._write_raw(P::RSTRING_QUOTA_EXCEEDED).await?;
+        return Ok(());
+    }
+    let recorded = match request_id {
+        Some(request_id) => match self::record_write(db, request_id, buf) {
+            Some(true) => Some(request_id),
+            Some(false) => {
+                con._write_raw(P::RSTRING_DUPLICATE_REQUEST).await?;
+                return Ok(());
+            }
+            None => None,
+        },
+        None => None,
+    };
+    let before = recorded
+        .and(db.get_ctable_ref())
+        .map(|table| table.mutations());
+    let ret = self::dispatch(db, con, auth, request_id, buf).await;
+    if let Some(request_id) = recorded {
+        // if the write failed, a retry must not be suppressed. most actions report failures
+        // to the client themselves, so we go by whether the table changed. an I/O error doesn't
+        // count since the write may well have gone through
+        let failed = match &ret {
+            Ok(()) => db.get_ctable_ref().map(|table| table.mutations()) == before,
+            Err(ActionError::ActionError(_)) => true,
+            Err(ActionError::IoError(_)) => false,
+        };
+        if failed {
+            self::forget_write(db, request_id);
+        }
+    }
+    ret
+}
+
+/// Run the action for a stage that has passed all the gates
+async fn dispatch<P: ProtocolSpec, C: BufferedSocketStream>(
+    db: &mut Corestore,
+    con: &mut Connection<C, P>,
+    auth: &mut AuthProviderHandle,
+    request_id: Option<&UnsafeSlice>,
+    buf: &[UnsafeSlice],
+) -> ActionResult<()> {
+    // only plugin actions need this
+    #[cfg(not(all(feature = "plugins", unix)))]
+    let _ = request_id;
     let mut iter = unsafe {
         // UNSAFE(@ohsayan): The presence of the connection guarantees that this
         // won't suddenly become invalid
@@ -112,15 +306,25 @@ async fn execute_stage<'a, P: ProtocolSpec, C: BufferedSocketStream>(
     };
     {
         gen_constants_and_matches!(
-            con, iter, db,
+            con, iter, db, request_id,
             GET => actions::get::get,
             SET => actions::set::set,
+            SETX => actions::set::setx,
+            GETSET => actions::set::getset,
             UPDATE => actions::update::update,
             DEL => actions::del::del,
+            DELMATCH => actions::delmatch::delmatch,
             HEYA => actions::heya::heya,
             EXISTS => actions::exists::exists,
+            TYPE => actions::keytype::keytype,
+            EXPIRE => actions::expire::expire,
+            PEXPIRE => actions::expire::pexpire,
+            TTL => actions::expire::ttl,
+            PTTL => actions::expire::pttl,
+            PERSIST => actions::expire::persist,
             MSET => actions::mset::mset,
             MGET => actions::mget::mget,
+            XMGET => actions::mget::xmget,
             MUPDATE => actions::mupdate::mupdate,
             SSET => actions::strong::sset,
             SDEL => actions::strong::sdel,
@@ -129,24 +333,242 @@ async fn execute_stage<'a, P: ProtocolSpec, C: BufferedSocketStream>(
             FLUSHDB => actions::flushdb::flushdb,
             USET => actions::uset::uset,
             KEYLEN => actions::keylen::keylen,
+            GETRANGE => actions::range::getrange,
+            SETRANGE => actions::range::setrange,
+            SETBIT => actions::bitmap::setbit,
+            GETBIT => actions::bitmap::getbit,
+            BITCOUNT => actions::bitmap::bitcount,
+            PFADD => actions::hll::pfadd,
+            PFCOUNT => actions::hll::pfcount,
+            PFMERGE => actions::hll::pfmerge,
             MKSNAP => admin::mksnap::mksnap,
+            BACKUP => admin::backup::backup,
+            EXPORT => admin::export::export,
+            IMPORT => admin::import::import,
+            RESTORE => admin::restore::restore,
             LSKEYS => actions::lskeys::lskeys,
+            FINDKEYS => actions::findkeys::findkeys,
+            KEYS => actions::keys::keys,
+            RANDOMKEY => actions::randomkey::randomkey,
+            SCAN => actions::scan::scan,
+            FETCH => actions::fetch::fetch,
             POP => actions::pop::pop,
+            // `POP` is already atomic, and `GETDEL` is what other stores call it
+            GETDEL => actions::pop::pop,
             MPOP => actions::mpop::mpop,
             LSET => actions::lists::lset,
             LGET => actions::lists::lget::lget,
             LMOD => actions::lists::lmod::lmod,
+            LTRIM => actions::lists::ltrim,
+            LINSERT => actions::lists::linsert,
+            LREM => actions::lists::lrem,
+            SORT => actions::lists::sort::sort,
+            HSET => actions::maps::hset,
+            HGET => actions::maps::hget,
+            HDEL => actions::maps::hdel,
+            HGETALL => actions::maps::hgetall,
+            SADD => actions::sets::sadd,
+            SREM => actions::sets::srem,
+            SISMEMBER => actions::sets::sismember,
+            SMEMBERS => actions::sets::smembers,
+            SUNION => actions::sets::sunion,
+            SINTER => actions::sets::sinter,
+            ZADD => actions::zsets::zadd,
+            ZREM => actions::zsets::zrem,
+            ZSCORE => actions::zsets::zscore,
+            ZRANK => actions::zsets::zrank,
+            ZCARD => actions::zsets::zcard,
+            ZRANGE => actions::zsets::zrange,
+            ZRANGEBYSCORE => actions::zsets::zrangebyscore,
+            JSET => actions::documents::jset,
+            JGET => actions::documents::jget,
+            INCRBY => actions::incr::incrby,
+            DECRBY => actions::incr::decrby,
+            MULTI => actions::txn::multi,
+            EXEC => actions::txn::exec,
+            DISCARD => actions::txn::discard,
+            CAS => actions::cas::cas,
+            VERSION => actions::cas::version,
+            SETIFEQ => actions::cas::setifeq,
+            COPYTABLE => actions::copytable::copytable,
             WHEREAMI => actions::whereami::whereami,
+            SUBSCRIBE => actions::pubsub::subscribe,
+            PSUBSCRIBE => actions::pubsub::psubscribe,
+            UNSUBSCRIBE => actions::pubsub::unsubscribe,
+            PUNSUBSCRIBE => actions::pubsub::punsubscribe,
+            PUBLISH => actions::pubsub::publish,
             SYS => admin::sys::sys,
+            DEBUG => admin::debug::debug,
             {
                 // actions that need other arguments
-                AUTH => auth::auth(con, auth, iter)
+                AUTH => auth::auth(con, auth, iter),
+                CLIENT => admin::client::client(db, con, auth, iter)
             }
         );
     }
     Ok(())
 }
 
+/// Returns true if the stage is a write and the current table was dropped (by another
+/// connection) after this connection switched to it
+fn is_dropped_write(db: &Corestore, buf: &[UnsafeSlice]) -> bool {
+    match db.get_ctable_ref() {
+        Some(table) => table.is_dropped() && self::is_write(buf),
+        None => false,
+    }
+}
+
+/// Returns true if the stage is a write and either the current table or the node is read-only
+fn is_read_only_write(db: &Corestore, buf: &[UnsafeSlice]) -> bool {
+    let rejects_writes = match db.get_ctable_ref() {
+        Some(table) => table.rejects_writes(),
+        None => registry::is_read_only(),
+    };
+    rejects_writes && self::is_write(buf)
+}
+
+/// Returns true if the stage allocates and one of its keys or values is over the current
+/// table's size limits. Pair actions alternate keys and values; for everything else the first
+/// argument is the key and the rest are values
+fn is_too_large(db: &Corestore, buf: &[UnsafeSlice]) -> bool {
+    let limits = match db.get_ctable_ref() {
+        Some(table) => table.size_limits(),
+        None => return false,
+    };
+    if compiler::likely(!limits.is_enabled()) || !self::is_allocating(buf) {
+        return false;
+    }
+    let len = |slice: &UnsafeSlice| unsafe {
+        // UNSAFE(@ohsayan): The presence of the connection guarantees that this
+        // won't suddenly become invalid
+        slice.as_slice().len()
+    };
+    let args = &buf[1..];
+    if self::is_one_of(buf, &PAIR_ACTIONS) {
+        args.chunks(2).any(|pair| {
+            !limits.allows_key(len(&pair[0]))
+                || pair
+                    .get(1)
+                    .is_some_and(|val| !limits.allows_value(len(val)))
+        })
+    } else {
+        match args.split_first() {
+            Some((key, values)) => {
+                !limits.allows_key(len(key))
+                    || values.iter().any(|val| !limits.allows_value(len(val)))
+            }
+            None => false,
+        }
+    }
+}
+
+/// If the stage is a write and the current table's write throttle has run out of tokens, this
+/// returns how long (in milliseconds) the client should wait before retrying
+fn throttle_write(db: &Corestore, buf: &[UnsafeSlice]) -> Option<u64> {
+    let throttle = db.get_ctable_ref()?.write_throttle();
+    if !throttle.is_enabled() || !self::is_write(buf) {
+        return None;
+    }
+    throttle
+        .try_acquire()
+        .err()
+        .map(|wait| (wait.as_millis() as u64).max(1))
+}
+
+/// If the stage is a write and the current table's dedup window is on, this records its request
+/// ID and returns false if the window had already seen it. Returns `None` if the stage isn't
+/// deduplicated
+fn record_write(db: &Corestore, request_id: &UnsafeSlice, buf: &[UnsafeSlice]) -> Option<bool> {
+    let dedup = db.get_ctable_ref()?.dedup_window();
+    if !dedup.is_enabled() || !self::is_write(buf) {
+        return None;
+    }
+    let request_id = unsafe {
+        // UNSAFE(@ohsayan): The presence of the connection guarantees that this won't suddenly
+        // become invalid
+        request_id.as_slice()
+    };
+    Some(dedup.record(request_id))
+}
+
+/// Forget the request ID of a write that failed (see [`record_write`])
+fn forget_write(db: &Corestore, request_id: &UnsafeSlice) {
+    if let Some(table) = db.get_ctable_ref() {
+        table.dedup_window().forget(unsafe {
+            // UNSAFE(@ohsayan): The presence of the connection guarantees that this won't suddenly
+            // become invalid
+            request_id.as_slice()
+        });
+    }
+}
+
+/// If the stage allocates and the memory limit has been hit, this evicts keys to make room
+/// (starting with the current table) if the eviction policy allows it. Returns false if the
+/// stage should be rejected
+fn reserve_memory(db: &Corestore, buf: &[UnsafeSlice]) -> bool {
+    if compiler::likely(!eviction::is_over_limit()) || !self::is_allocating(buf) {
+        return true;
+    }
+    db.get_store().make_room(db.get_ctable_ref())
+}
+
+/// Returns true if the stage allocates and the current table is in a keyspace that has hit its
+/// quota
+fn is_over_quota(db: &Corestore, buf: &[UnsafeSlice]) -> bool {
+    match db.get_ctable_ref() {
+        Some(table) => table.is_over_quota() && self::is_allocating(buf),
+        None => false,
+    }
+}
+
+/// Returns true if the stage is one of the [`WRITE_ACTIONS`]. `DELMATCH <pattern> COUNT` only
+/// counts the keys that would be removed, so it's a read
+fn is_write(buf: &[UnsafeSlice]) -> bool {
+    self::is_one_of(buf, &WRITE_ACTIONS) && !self::is_delmatch_count(buf)
+}
+
+fn is_delmatch_count(buf: &[UnsafeSlice]) -> bool {
+    buf.len() == 3
+        && self::is_one_of(buf, &[b"DELMATCH"])
+        && unsafe {
+            // UNSAFE(@ohsayan): The presence of the connection guarantees that this won't suddenly
+            // become invalid
+            buf[2].as_slice()
+        }
+        .eq_ignore_ascii_case(b"COUNT")
+}
+
+/// Returns true if the stage is one of the [`ALLOCATING_ACTIONS`]
+fn is_allocating(buf: &[UnsafeSlice]) -> bool {
+    self::is_one_of(buf, &ALLOCATING_ACTIONS)
+}
+
+fn is_one_of(buf: &[UnsafeSlice], actions: &[&[u8]]) -> bool {
+    match self::first_slice(buf) {
+        Some(action) => actions
+            .iter()
+            .any(|candidate| candidate.eq_ignore_ascii_case(action)),
+        None => false,
+    }
+}
+
+/// Returns true if the element looks like an action name rather than a BlueQL statement (BlueQL
+/// statements always have more than one word)
+fn is_action_name(element: &[u8]) -> bool {
+    !element.is_empty()
+        && element
+            .iter()
+            .all(|byte| byte.is_ascii_alphanumeric() || *byte == b'_')
+}
+
+fn first_slice(buf: &[UnsafeSlice]) -> Option<&[u8]> {
+    buf.first().map(|first| unsafe {
+        // UNSAFE(@ohsayan): The presence of the connection guarantees that this
+        // won't suddenly become invalid
+        first.as_slice()
+    })
+}
+
 /// Execute a stage **completely**. This means that action errors are never propagated
 /// over the try operator
 async fn execute_stage_pedantic<'a, C: BufferedSocketStream, P: ProtocolSpec>(
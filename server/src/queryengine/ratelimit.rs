@@ -0,0 +1,105 @@
+/*
+ * Created on Tue Nov 08 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Rate limits
+//!
+//! Opt-in token buckets that cap the number of queries per second, so that an abusive workload
+//! is slowed down instead of starving the node. There are two kinds of limits:
+//! - a limit for every connection (`ratelimit.connection`), with a bucket per connection
+//! - limits for users (`ratelimit.users`), with a bucket per user that is shared by all of the
+//!   user's connections
+//!
+//! A query has to get past both. Queries over a limit fail with
+//! `err-throttled-retry-after-<ms>`, just like throttled writes (the buckets are the same
+//! [`WriteThrottle`]s). The limits are only set on startup, so that a client can't lift its
+//! own limit.
+
+use {
+    crate::{config::RateLimitConfig, kvengine::throttle::WriteThrottle},
+    core::sync::atomic::{AtomicU64, Ordering},
+    std::{collections::HashMap, sync::OnceLock},
+};
+
+/// The limit for every connection (queries per second). Zero if there is no limit
+static CONNECTION_RATE: AtomicU64 = AtomicU64::new(0);
+/// The buckets of the users that have a limit
+static USERS: OnceLock<HashMap<Box<[u8]>, WriteThrottle>> = OnceLock::new();
+
+/// Set the rate limits. This must be called before any connection is accepted
+pub fn init(config: &RateLimitConfig) {
+    CONNECTION_RATE.store(config.connection, Ordering::Release);
+    let users = config
+        .users
+        .iter()
+        .map(|(user, rate)| {
+            let bucket = WriteThrottle::default();
+            bucket.set_rate(*rate);
+            (user.as_bytes().into(), bucket)
+        })
+        .collect();
+    let _ = USERS.set(users);
+}
+
+/// Returns the bucket for a new connection
+pub fn connection_bucket() -> WriteThrottle {
+    let bucket = WriteThrottle::default();
+    let rate = CONNECTION_RATE.load(Ordering::Acquire);
+    if rate != 0 {
+        bucket.set_rate(rate);
+    }
+    bucket
+}
+
+/// Take a token from the connection's bucket and from the bucket of the user (if the user has
+/// a limit). If a limit was hit, this returns how long (in milliseconds) the client should wait
+/// before retrying
+pub fn check(connection: &WriteThrottle, user: Option<&[u8]>) -> Option<u64> {
+    let user = user.and_then(|user| USERS.get()?.get(user));
+    let ret = connection
+        .try_acquire()
+        .and_then(|()| user.map_or(Ok(()), |user| user.try_acquire()));
+    ret.err().map(|wait| (wait.as_millis() as u64).max(1))
+}
+
+#[test]
+fn test_check() {
+    let connection = WriteThrottle::default();
+    connection.set_rate(2);
+    assert_eq!(check(&connection, None), None);
+    assert_eq!(check(&connection, Some(b"nobody")), None);
+    assert!(check(&connection, None).is_some());
+    let unlimited = WriteThrottle::default();
+    for _ in 0..100 {
+        assert_eq!(check(&unlimited, None), None);
+    }
+    // the user's bucket is shared by all of the user's connections
+    init(&RateLimitConfig {
+        connection: 0,
+        users: vec![("ratelimited".to_owned(), 1)],
+    });
+    assert_eq!(check(&unlimited, Some(b"ratelimited")), None);
+    assert!(check(&WriteThrottle::default(), Some(b"ratelimited")).is_some());
+}
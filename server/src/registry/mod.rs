@@ -30,7 +30,13 @@
 //!
 
 use {
-    crate::corestore::lock::{QLGuard, QuickLock},
+    crate::{
+        config::DefaultEntity,
+        corestore::{
+            lazy::Once,
+            lock::{QLGuard, QuickLock},
+        },
+    },
     core::sync::atomic::{AtomicBool, Ordering},
 };
 
@@ -81,6 +87,12 @@ static FLUSH_STATE: QuickLock<()> = QuickLock::new(());
 /// The preload trip switch
 static PRELOAD_TRIPSWITCH: Trip = Trip::new_untripped();
 static CLEANUP_TRIPSWITCH: Trip = Trip::new_untripped();
+/// The node-wide read-only switch
+static READ_ONLY: AtomicBool = AtomicBool::new(false);
+/// The effective configuration (as `key = value` lines)
+static EFFECTIVE_CONFIG: Once<Vec<String>> = Once::new();
+/// The entity that new connections start in, if it isn't `default.default`
+static DEFAULT_ENTITY: Once<DefaultEntity> = Once::new();
 
 /// Check the global system state
 pub fn state_okay() -> bool {
@@ -103,6 +115,16 @@ pub fn unpoison() {
     GLOBAL_STATE.store(true, ORD_REL)
 }
 
+/// Returns true if the node rejects all writes
+pub fn is_read_only() -> bool {
+    READ_ONLY.load(ORD_ACQ)
+}
+
+/// Make the node reject (or allow) all writes
+pub fn set_read_only(read_only: bool) {
+    READ_ONLY.store(read_only, ORD_REL)
+}
+
 /// Get a static reference to the global preload trip switch
 pub fn get_preload_tripswitch() -> &'static Trip {
     &PRELOAD_TRIPSWITCH
@@ -112,3 +134,23 @@ pub fn get_preload_tripswitch() -> &'static Trip {
 pub fn get_cleanup_tripswitch() -> &'static Trip {
     &CLEANUP_TRIPSWITCH
 }
+
+/// Record the effective configuration. Only the first call has any effect
+pub fn set_effective_config(settings: Vec<String>) {
+    EFFECTIVE_CONFIG.set(settings);
+}
+
+/// Returns the effective configuration, if it was recorded
+pub fn get_effective_config() -> &'static [String] {
+    EFFECTIVE_CONFIG.get().map_or(&[], Vec::as_slice)
+}
+
+/// Record the entity that new connections start in. Only the first call has any effect
+pub fn set_default_entity(entity: DefaultEntity) {
+    DEFAULT_ENTITY.set(entity);
+}
+
+/// Returns the entity that new connections start in, if one was recorded
+pub fn get_default_entity() -> Option<&'static DefaultEntity> {
+    DEFAULT_ENTITY.get()
+}
@@ -0,0 +1,86 @@
+/*
+ * Created on Sun Oct 23 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+use {
+    crate::{
+        config::ArchivePolicy,
+        corestore::{memstore::Memstore, table::DataModel, Corestore},
+    },
+    tokio::{
+        sync::broadcast::Receiver,
+        time::{self, Duration},
+    },
+};
+
+/// How often the archive service looks for idle keys
+const SWEEP_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// The archive service periodically moves the values of idle keys in every key/value table
+/// into the table's archive. If archival is disabled, this function immediately returns
+pub async fn archive_service(
+    handle: Corestore,
+    policy: ArchivePolicy,
+    mut terminator: Receiver<()>,
+) {
+    if let ArchivePolicy::Enabled(idle_days) = policy {
+        loop {
+            tokio::select! {
+                _ = time::sleep_until(time::Instant::now() + SWEEP_INTERVAL) => {
+                    let cloned_handle = handle.clone();
+                    tokio::task::spawn_blocking(move || {
+                        archive_blocking_section(cloned_handle.get_store(), idle_days)
+                    }).await.expect("Something caused the archive service to panic");
+                }
+                _ = terminator.recv() => {
+                    break;
+                }
+            }
+        }
+    }
+    log::info!("Archive service has exited");
+}
+
+/// Archive idle keys across all the key/value tables
+fn archive_blocking_section(store: &Memstore, idle_days: u64) {
+    let mut archived = 0;
+    for ks in store.keyspaces.iter() {
+        for tbl in ks.value().tables.iter() {
+            if let DataModel::KV(kve) = tbl.value().get_model_ref() {
+                match kve.archive_idle(idle_days) {
+                    Ok(count) => archived += count,
+                    Err(e) => log::error!(
+                        "Failed to archive idle keys in table `{}:{}`: {e}",
+                        String::from_utf8_lossy(ks.key()),
+                        String::from_utf8_lossy(tbl.key())
+                    ),
+                }
+            }
+        }
+    }
+    if archived != 0 {
+        log::info!("Archived {archived} idle key(s)");
+    }
+}
@@ -27,9 +27,10 @@
 use {
     crate::{
         config::BGSave,
-        corestore::Corestore,
+        corestore::{catalog, Corestore},
         registry,
         storage::{self, v1::flush::Autoflush},
+        util::os,
         IoResult,
     },
     tokio::{
@@ -80,13 +81,35 @@ pub async fn bgsave_scheduler(handle: Corestore, bgsave_cfg: BGSave, mut termina
 ///
 /// This function just hides away the BGSAVE blocking section from the _public API_
 pub fn run_bgsave(handle: &Corestore) -> IoResult<()> {
-    storage::v1::flush::flush_full(Autoflush, handle.get_store())
+    let store = handle.get_store();
+    catalog::refresh(&handle.entities(), store, os::get_epoch_secs());
+    storage::v1::flush::flush_full(Autoflush, store)
+}
+
+/// Run a scheduled bgsave
+///
+/// Unlike [`run_bgsave`], this skips keyspaces whose flush interval hasn't elapsed since
+/// they were last flushed by a scheduled bgsave
+pub fn run_bgsave_scheduled(handle: &Corestore) -> IoResult<()> {
+    let now = os::get_epoch_secs();
+    let store = handle.get_store();
+    storage::v1::flush::flush_full_where(Autoflush, store, |ks| ks.is_flush_due(now))?;
+    // only record the flush once everything has hit the disk
+    store
+        .keyspaces
+        .iter()
+        .filter(|ks| ks.value().is_flush_due(now))
+        .for_each(|ks| ks.value().mark_flushed(now));
+    // the catalog now has the flush times of this cycle (and they hit the disk with the
+    // next one)
+    catalog::refresh(&handle.entities(), store, now);
+    Ok(())
 }
 
 /// This just wraps around [`_bgsave_blocking_section`] and prints nice log messages depending on the outcome
 fn bgsave_blocking_section(handle: Corestore) -> bool {
     registry::lock_flush_state();
-    match run_bgsave(&handle) {
+    match run_bgsave_scheduled(&handle) {
         Ok(_) => {
             log::info!("BGSAVE completed successfully");
             registry::unpoison();
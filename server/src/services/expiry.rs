@@ -0,0 +1,82 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+use {
+    crate::{
+        corestore::{memstore::Memstore, table::DataModel, Corestore},
+        kvengine::expiry,
+    },
+    tokio::{
+        sync::broadcast::Receiver,
+        time::{self, Duration},
+    },
+};
+
+/// How often the expiry service sweeps for expired keys
+const SWEEP_INTERVAL: Duration = Duration::from_secs(1);
+/// The most keys removed from a table in one sweep (the rest are picked up by the next sweeps),
+/// so that a sweep never holds up the blocking pool for long
+const SWEEP_LIMIT: usize = 1000;
+
+/// The expiry service periodically removes the expired keys that nobody accessed (accessed keys
+/// are expired when they're accessed) from every table
+pub async fn expiry_service(handle: Corestore, mut terminator: Receiver<()>) {
+    loop {
+        tokio::select! {
+            _ = time::sleep_until(time::Instant::now() + SWEEP_INTERVAL) => {
+                let cloned_handle = handle.clone();
+                tokio::task::spawn_blocking(move || {
+                    expiry_blocking_section(cloned_handle.get_store())
+                }).await.expect("Something caused the expiry service to panic");
+            }
+            _ = terminator.recv() => {
+                break;
+            }
+        }
+    }
+    log::info!("Expiry service has exited");
+}
+
+/// Remove expired keys across all the tables
+fn expiry_blocking_section(store: &Memstore) {
+    let now = expiry::now_ms();
+    let mut expired = 0;
+    for ks in store.keyspaces.iter() {
+        for tbl in ks.value().tables.iter() {
+            expired += match tbl.value().get_model_ref() {
+                DataModel::KV(kve) => kve.expire_due(now, SWEEP_LIMIT),
+                DataModel::KVExtListmap(kvl) => kvl.expire_due(now, SWEEP_LIMIT),
+                DataModel::KVExtMap(kvm) => kvm.expire_due(now, SWEEP_LIMIT),
+                DataModel::KVExtSet(kvs) => kvs.expire_due(now, SWEEP_LIMIT),
+                DataModel::KVExtSortedSet(kvz) => kvz.expire_due(now, SWEEP_LIMIT),
+                DataModel::KVExtDocument(kvd) => kvd.expire_due(now, SWEEP_LIMIT),
+            };
+        }
+    }
+    if expired != 0 {
+        log::trace!("Expired {expired} key(s)");
+    }
+}
@@ -0,0 +1,60 @@
+/*
+ * Created on Tue Nov 01 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+use {
+    crate::{config::SyncPolicy, registry, storage::v1::fsync},
+    tokio::{
+        sync::broadcast::Receiver,
+        time::{self, Duration},
+    },
+};
+
+/// How often files are synced with the `everysec` policy
+const SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// The fsync service syncs the files written by flushes about once every second, if the sync
+/// policy is `everysec`. Otherwise, this function immediately returns
+pub async fn fsync_service(policy: SyncPolicy, mut terminator: Receiver<()>) {
+    if policy == SyncPolicy::EverySec {
+        loop {
+            tokio::select! {
+                _ = time::sleep_until(time::Instant::now() + SYNC_INTERVAL) => {
+                    let ret = tokio::task::spawn_blocking(fsync::sync_pending)
+                        .await
+                        .expect("Something caused the fsync service to panic");
+                    if ret.is_err() {
+                        // the files were queued again; the next BGSAVE will unpoison the state
+                        registry::poison();
+                    }
+                }
+                _ = terminator.recv() => {
+                    break;
+                }
+            }
+        }
+    }
+    log::info!("Fsync service has exited");
+}
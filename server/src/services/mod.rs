@@ -24,10 +24,23 @@
  *
 */
 
+pub mod archive;
 pub mod bgsave;
+pub mod expiry;
+pub mod fsync;
 pub mod snapshot;
+pub mod usage;
 use crate::{
-    corestore::memstore::Memstore, diskstore::flock::FileLock, storage, util::os, IoResult,
+    config::OfflineTask,
+    corestore::{
+        export::{self, ExportError, ExportFormat, ExportTarget},
+        import,
+        memstore::{DdlError, Memstore, SYSTEM},
+    },
+    diskstore::flock::FileLock,
+    storage,
+    util::os,
+    IoResult,
 };
 
 pub fn restore_data(src: Option<String>) -> IoResult<()> {
@@ -39,6 +52,159 @@ pub fn restore_data(src: Option<String>) -> IoResult<()> {
     Ok(())
 }
 
+/// Run a task on the data directory. Returns false if the task failed
+pub fn run_offline_task(task: OfflineTask) -> bool {
+    match task {
+        OfflineTask::Repair => self::repair_data(),
+        OfflineTask::Export(target, format) => self::export_data(&target, format),
+        OfflineTask::Import {
+            target,
+            file,
+            format,
+            continue_on_error,
+        } => self::import_data(&target, &file, format, continue_on_error),
+    }
+}
+
+/// Repair the data directory, logging what was salvaged and what had to be dropped
+fn repair_data() -> bool {
+    match storage::v1::repair::repair() {
+        Ok(Some(report)) => {
+            log::info!(
+                "Salvaged {} table(s) with {} key(s)",
+                report.tables,
+                report.keys
+            );
+            if report.findings.is_empty() {
+                log::info!("Nothing had to be dropped");
+            }
+            for finding in report.findings.iter() {
+                log::warn!("Repair: {finding}");
+            }
+            true
+        }
+        Ok(None) => {
+            log::info!("There is no data directory to repair");
+            true
+        }
+        Err(e) => {
+            log::error!("Failed to repair data directory: {e}");
+            false
+        }
+    }
+}
+
+/// Export a keyspace or a table (`<keyspace>` or `<keyspace>:<table>`) from the data directory
+fn export_data(target: &str, format: ExportFormat) -> bool {
+    let target = match ExportTarget::parse(target) {
+        Some(target) => target,
+        None => {
+            log::error!(
+                "Bad export target `{target}`. Expected `<keyspace>` or `<keyspace>:<table>`"
+            );
+            return false;
+        }
+    };
+    let store = match self::read_store("export from") {
+        Some(store) => store,
+        None => return false,
+    };
+    match export::export(&store, &target, format) {
+        Ok(path) => {
+            log::info!("Exported data to `{path}`");
+            true
+        }
+        Err(ExportError::Ddl(DdlError::ProtectedObject)) => {
+            log::error!("The system keyspace can't be exported");
+            false
+        }
+        Err(ExportError::Ddl(_)) => {
+            log::error!("The keyspace or table to export doesn't exist");
+            false
+        }
+        Err(ExportError::Io(e)) => {
+            log::error!("Failed to export data: {e}");
+            false
+        }
+    }
+}
+
+/// Import a file into a table (`<keyspace>:<table>`) in the data directory
+fn import_data(target: &str, file: &str, format: ExportFormat, continue_on_error: bool) -> bool {
+    let (ksid, tblid) = match ExportTarget::parse(target) {
+        Some(ExportTarget::Table(ksid, tblid)) => (ksid, tblid),
+        _ => {
+            log::error!("Bad import target `{target}`. Expected `<keyspace>:<table>`");
+            return false;
+        }
+    };
+    if ksid.eq(&SYSTEM) {
+        log::error!("Data can't be imported into the system keyspace");
+        return false;
+    }
+    let store = match self::read_store("import into") {
+        Some(store) => store,
+        None => return false,
+    };
+    let table = match store
+        .get_keyspace_atomic_ref(&ksid)
+        .and_then(|ks| ks.get_table_atomic_ref(&tblid))
+    {
+        Some(table) => table,
+        None => {
+            log::error!("The table to import into doesn't exist");
+            return false;
+        }
+    };
+    let report = match import::import_file(&store, &table, file, format, continue_on_error) {
+        Ok(report) => report,
+        Err(e) => {
+            log::error!("Failed to import `{file}`: {e}");
+            return false;
+        }
+    };
+    for error in report.errors.iter() {
+        log::warn!("Import: {error}");
+    }
+    log::info!(
+        "Imported {} record(s) with {} bad record(s)",
+        report.imported,
+        report.failed
+    );
+    if let Some(rejection) = report.stopped {
+        log::warn!("Import stopped early: {}", rejection.as_str());
+    }
+    // even if the import stopped at a bad record, everything before it was imported
+    if let Err(e) = storage::v1::flush::flush_full(storage::v1::flush::Autoflush, &store) {
+        log::error!("Failed to save imported data: {e}");
+        return false;
+    }
+    report.stopped.is_none() && (report.failed == 0 || continue_on_error)
+}
+
+/// Read the data directory, logging why if it can't be read. `purpose` says what the data
+/// directory was needed for
+fn read_store(purpose: &str) -> Option<Memstore> {
+    match storage::v1::unflush::is_new_instance() {
+        Ok(false) => {}
+        Ok(true) => {
+            log::error!("There is no data directory to {purpose}");
+            return None;
+        }
+        Err(e) => {
+            log::error!("Failed to read data directory: {e}");
+            return None;
+        }
+    }
+    match storage::v1::unflush::read_full() {
+        Ok(store) => Some(store),
+        Err(e) => {
+            log::error!("Failed to read data directory: {e}");
+            None
+        }
+    }
+}
+
 pub fn pre_shutdown_cleanup(mut pid_file: FileLock, mr: Option<&Memstore>) -> bool {
     if let Err(e) = pid_file.unlock() {
         log::error!("Shutdown failure: Failed to unlock pid file: {}", e);
@@ -24,20 +24,62 @@
  *
 */
 
+pub mod s3;
+
 use {
     crate::{
         config::SnapshotConfig,
         corestore::Corestore,
         registry,
-        storage::v1::sengine::{SnapshotActionResult, SnapshotEngine},
+        storage::v1::{
+            interface::DIR_ROOT,
+            sengine::{SnapshotActionResult, SnapshotEngine},
+        },
+        IoResult,
+    },
+    core::fmt,
+    std::{
+        fs::{self, File},
+        path::Path,
+        sync::Arc,
     },
-    std::sync::Arc,
     tokio::{
         sync::broadcast::Receiver,
         time::{self, Duration},
     },
 };
 
+/// A target that snapshots are shipped to once they have been written to the local disk
+///
+/// Implementors only need to store individual objects; [`SnapshotSink::ship`] takes care of
+/// walking a snapshot directory and naming each object after its path relative to the data
+/// directory (for example, `snaps/20221023-120000/PRELOAD`). Files are handed over as they are
+/// on disk, so that implementors can stream them instead of reading them into memory
+pub trait SnapshotSink: fmt::Debug + Send + Sync {
+    /// A human readable name for this sink, used in log messages
+    fn name(&self) -> &str;
+    /// Store the contents of `file` (which is positioned at its start) under the given `key`,
+    /// replacing any existing object
+    fn put(&self, key: &str, file: &mut File) -> IoResult<()>;
+    /// Ship every file in the snapshot directory at `dir`
+    fn ship(&self, dir: &Path) -> IoResult<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                self.ship(&path)?;
+            } else {
+                let key = path.strip_prefix(DIR_ROOT).unwrap_or(&path);
+                let key: Vec<_> = key
+                    .components()
+                    .map(|component| component.as_os_str().to_string_lossy())
+                    .collect();
+                self.put(&key.join("/"), &mut File::open(&path)?)?;
+            }
+        }
+        Ok(())
+    }
+}
+
 /// The snapshot service
 ///
 /// This service calls `SnapEngine::mksnap()` periodically to create snapshots. Whenever
@@ -57,7 +99,7 @@ pub async fn snapshot_service(
             return;
         }
         SnapshotConfig::Enabled(configuration) => {
-            let (duration, _, failsafe) = configuration.decompose();
+            let (duration, _, failsafe, keep_for, _) = configuration.decompose();
             let duration = Duration::from_secs(duration);
             loop {
                 tokio::select! {
@@ -75,6 +117,10 @@ pub async fn snapshot_service(
                         if succeeded {
                             // it passed, so unpoison the handle
                             registry::unpoison();
+                            // only prune once we have a fresh snapshot to fall back to
+                            if let Some(keep_for) = keep_for {
+                                engine.prune_expired(keep_for).await;
+                            }
                         } else if failsafe {
                             // mksnap returned false and we are set to stop writes if snapshotting failed
                             // so let's poison the handle
@@ -0,0 +1,80 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+use {
+    crate::{
+        corestore::{
+            memstore::{Memstore, SYSTEM},
+            usage::{self, BUCKET_SECS},
+            Corestore,
+        },
+        storage::v1::interface::DIR_KSROOT,
+        util::os,
+    },
+    tokio::{
+        sync::broadcast::Receiver,
+        time::{self, Duration},
+    },
+};
+
+/// The usage service moves the usage counters of every keyspace into a bucket in the usage
+/// ledger at the end of every bucket (and once more on termination)
+pub async fn usage_service(handle: Corestore, mut terminator: Receiver<()>) {
+    loop {
+        let now = os::get_epoch_secs();
+        let until_next = usage::bucket_of(now) + BUCKET_SECS - now;
+        let terminating = tokio::select! {
+            _ = time::sleep(Duration::from_secs(until_next)) => false,
+            _ = terminator.recv() => true,
+        };
+        let cloned_handle = handle.clone();
+        tokio::task::spawn_blocking(move || record_blocking_section(cloned_handle.get_store()))
+            .await
+            .expect("Something caused the usage service to panic");
+        if terminating {
+            break;
+        }
+    }
+    log::info!("Usage service has exited");
+}
+
+/// Record a bucket for every keyspace and prune the expired buckets
+fn record_blocking_section(store: &Memstore) {
+    let ledger = store.setup_usage();
+    let now = os::get_epoch_secs();
+    // the bucket that just ended
+    let start = usage::bucket_of(now.saturating_sub(1));
+    for ks in store.keyspaces.iter() {
+        if ks.key() == &SYSTEM {
+            continue;
+        }
+        let mut sample = ks.value().usage().take();
+        let ksid = unsafe { ks.key().as_str() };
+        sample.storage = os::dirsize(format!("{DIR_KSROOT}/{ksid}")).unwrap_or(0);
+        usage::record_bucket(&ledger, ks.key(), start, sample);
+    }
+    usage::prune(&ledger, now);
+}
@@ -33,6 +33,9 @@ matter what the reason. You can't just mess up someone's data because you found
 way to store things. That's why we'll version modules that correspond to version of Cyanstore. It is
 totally legal for one version to call data that correspond to other versions.
 
+`v2` only changes the file headers, so the v1 routines read and write v2 files while `v2`
+handles migrating older instances.
+
 ## How to break
 
 Whenever we're making changes, here's what we need to keep in mind:
@@ -66,10 +69,17 @@ in practice).
 */
 
 pub mod v1;
+pub mod v2;
 
 pub mod unflush {
     use crate::{corestore::memstore::Memstore, storage::v1::error::StorageEngineResult};
     pub fn read_full() -> StorageEngineResult<Memstore> {
-        super::v1::unflush::read_full()
+        // the v1 loader understands v2 headers; we just need to rewrite older instances
+        let is_v1 = super::v2::is_v1_instance()?;
+        let store = super::v1::unflush::read_full()?;
+        if is_v1 {
+            super::v2::migrate_from_v1(&store)?;
+        }
+        Ok(store)
     }
 }
@@ -0,0 +1,393 @@
+/*
+ * Created on Tue Oct 25 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Online backups
+//!
+//! A backup is a point-in-time copy of a set of keyspaces, packed into a single tar archive
+//! under [`DIR_BACKUPS`](super::interface::DIR_BACKUPS). The archive has the same layout as
+//! the keyspace root: a `PRELOAD` that lists the backed up keyspaces, followed by the
+//! `PARTMAP`, `KSMETA` and table files for every keyspace.
+//!
+//! The files are streamed into the archive while holding the global flush lock, so that no
+//! keyspace or table can be created and no flush can run midway through the backup. Tables are
+//! never held in memory: their records go straight into the archive through a
+//! [`StreamEncoder`], and the header of each entry is filled in once its size is known.
+
+use {
+    super::{
+        interface::{self, DIR_BACKUPS},
+        stream::{self, StreamEncoder},
+    },
+    crate::{
+        corestore::{
+            htable::Coremap,
+            memstore::{DdlError, Keyspace, Memstore, ObjectID, SYSTEM},
+        },
+        registry, IoResult,
+    },
+    std::{
+        fs::{self, File},
+        io::{Seek, SeekFrom, Write},
+        sync::Arc,
+    },
+};
+
+/// The size of a tar block
+pub(super) const BLOCK_SIZE: usize = 512;
+/// The largest size that fits in the (octal) size field of a tar header
+const MAX_OCTAL_SIZE: u64 = 0o77777777777;
+
+/// An error that occurred while creating a backup
+#[derive(Debug)]
+pub enum BackupError {
+    /// The keyspaces couldn't be backed up
+    Ddl(DdlError),
+    /// Writing the archive failed
+    Io(std::io::Error),
+}
+
+impl From<DdlError> for BackupError {
+    fn from(e: DdlError) -> Self {
+        Self::Ddl(e)
+    }
+}
+
+impl From<std::io::Error> for BackupError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+/// Returns the path to the archive for the backup with the given name
+pub fn backup_path(name: &str) -> String {
+    format!("{DIR_BACKUPS}/{name}.tar")
+}
+
+/// Create a backup called `name` that holds the given keyspaces
+pub fn create(store: &Memstore, name: &str, keyspaces: &[ObjectID]) -> Result<(), BackupError> {
+    let path = backup_path(name);
+    if fs::metadata(&path).is_ok() {
+        return Err(DdlError::AlreadyExists.into());
+    }
+    try_dir_ignore_existing!(DIR_BACKUPS)?;
+    // write to a temporary file first, so that a failed backup never leaves a partial archive
+    let tmp = format!("{path}_");
+    let mut archive = File::create(&tmp)?;
+    let ret = (|| {
+        self::write_files(&mut archive, store, keyspaces)?;
+        archive.write_all(&[0; BLOCK_SIZE * 2])?;
+        archive.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    })();
+    if ret.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    ret
+}
+
+/// Stream the files for the given keyspaces into `archive`, returning the name, size and CRC32C
+/// of every file that was written. The global flush lock is only held while the metadata is
+/// written (so that no DDL query changes the set of tables under us); the tables that the
+/// metadata lists are then streamed one at a time without it
+pub(super) fn write_files<W: Write + Seek>(
+    archive: &mut W,
+    store: &Memstore,
+    keyspaces: &[ObjectID],
+) -> Result<Vec<(String, u64, u32)>, BackupError> {
+    let mut files = Vec::new();
+    let mut tables = Vec::new();
+    {
+        let _flush_lock = registry::lock_flush_state();
+        let selected: Coremap<ObjectID, Arc<Keyspace>> = Coremap::new();
+        for ksid in keyspaces {
+            if ksid.eq(&SYSTEM) {
+                return Err(DdlError::ProtectedObject.into());
+            }
+            let ks = store
+                .get_keyspace_atomic_ref(ksid)
+                .ok_or(DdlError::ObjectNotFound)?;
+            selected.upsert(ksid.clone(), ks);
+        }
+        write_file(archive, &mut files, "PRELOAD".to_owned(), |w| {
+            super::preload::raw_generate_preload_for(w, &selected)
+        })?;
+        for ks in selected.iter() {
+            let ksid = unsafe { ks.key().as_str() };
+            let keyspace = ks.value().as_ref();
+            write_file(archive, &mut files, format!("{ksid}/PARTMAP"), |w| {
+                interface::serialize_partmap_into_slow_buffer(w, keyspace)
+            })?;
+            write_file(archive, &mut files, format!("{ksid}/KSMETA"), |w| {
+                interface::serialize_ksmeta_into_slow_buffer(w, keyspace)
+            })?;
+            // just like a flush, volatile tables only have an entry in the partmap
+            tables.extend(
+                keyspace
+                    .tables
+                    .iter()
+                    .filter(|table| !table.value().is_volatile())
+                    .map(|table| {
+                        let name = format!("{ksid}/{}", unsafe { table.key().as_str() });
+                        (name, table.value().clone())
+                    }),
+            );
+        }
+    }
+    for (name, table) in tables {
+        write_file(archive, &mut files, name, |w| {
+            stream::encode_table(w, table.as_ref())
+        })?;
+    }
+    Ok(files)
+}
+
+/// Stream a file into the archive with [`write_tar_entry_streamed`] and note its name, size
+/// and CRC32C
+fn write_file<W: Write + Seek>(
+    archive: &mut W,
+    files: &mut Vec<(String, u64, u32)>,
+    name: String,
+    encode: impl FnOnce(&mut StreamEncoder<&mut W>) -> IoResult<()>,
+) -> IoResult<()> {
+    let (size, crc) = self::write_tar_entry_streamed(archive, &name, encode)?;
+    files.push((name, size, crc));
+    Ok(())
+}
+
+/// Write a file into a (ustar) tar archive
+pub(super) fn write_tar_entry<W: Write>(w: &mut W, path: &str, data: &[u8]) -> IoResult<()> {
+    w.write_all(&self::tar_header(path, data.len() as u64))?;
+    w.write_all(data)?;
+    self::write_tar_padding(w, data.len() as u64)
+}
+
+/// Write a file into a (ustar) tar archive, where the file is streamed into the archive by
+/// `encode`. Since the size of the file isn't known until it has been written, the header is
+/// filled in last. Returns the size and CRC32C of the file
+pub(super) fn write_tar_entry_streamed<W: Write + Seek>(
+    w: &mut W,
+    path: &str,
+    encode: impl FnOnce(&mut StreamEncoder<&mut W>) -> IoResult<()>,
+) -> IoResult<(u64, u32)> {
+    let start = w.stream_position()?;
+    w.write_all(&[0; BLOCK_SIZE])?;
+    let mut encoder = StreamEncoder::new(&mut *w);
+    encode(&mut encoder)?;
+    let (size, crc) = encoder.finish()?;
+    w.seek(SeekFrom::Start(start))?;
+    w.write_all(&self::tar_header(path, size))?;
+    w.seek(SeekFrom::Start(start + BLOCK_SIZE as u64 + size))?;
+    self::write_tar_padding(w, size)?;
+    Ok((size, crc))
+}
+
+/// Generate the (ustar) tar header for a file of the given size
+fn tar_header(path: &str, size: u64) -> [u8; BLOCK_SIZE] {
+    let mut header = [0u8; BLOCK_SIZE];
+    // object IDs are at most `ObjectID::MAX_LENGTH` (100) bytes, so the directory always fits
+    // in the prefix and the file name always fits in the name field
+    let (prefix, name) = path.rsplit_once('/').unwrap_or(("", path));
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    write_octal(&mut header[100..108], 0o644);
+    write_octal(&mut header[108..116], 0);
+    write_octal(&mut header[116..124], 0);
+    if size <= MAX_OCTAL_SIZE {
+        write_octal(&mut header[124..136], size);
+    } else {
+        // files of 8GiB and more use the (GNU) base-256 encoding
+        header[124] = 0x80;
+        header[128..136].copy_from_slice(&size.to_be_bytes());
+    }
+    write_octal(&mut header[136..148], crate::util::os::get_epoch_secs());
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+    // the checksum is computed with the checksum field set to spaces
+    header[148..156].copy_from_slice(&[b' '; 8]);
+    let checksum: u64 = header.iter().map(|byte| *byte as u64).sum();
+    write_octal(&mut header[148..155], checksum);
+    header
+}
+
+/// Pad a file of the given size to a whole number of blocks
+fn write_tar_padding<W: Write>(w: &mut W, size: u64) -> IoResult<()> {
+    let padding = (BLOCK_SIZE - (size % BLOCK_SIZE as u64) as usize) % BLOCK_SIZE;
+    w.write_all(&[0; BLOCK_SIZE][..padding])
+}
+
+/// Read the files in a (ustar) tar archive, as written by [`write_tar_entry`]. Returns `None`
+/// if the archive is malformed
+pub(super) fn read_tar(mut archive: &[u8]) -> Option<Vec<(String, &[u8])>> {
+    let mut files = Vec::new();
+    while archive.len() >= BLOCK_SIZE {
+        let (header, rest) = archive.split_at(BLOCK_SIZE);
+        if header.iter().all(|byte| *byte == 0) {
+            // the end of the archive
+            return Some(files);
+        }
+        let stored = read_octal(&header[148..156])?;
+        let computed: u64 = header
+            .iter()
+            .enumerate()
+            .map(|(i, byte)| if (148..156).contains(&i) { b' ' } else { *byte } as u64)
+            .sum();
+        if stored != computed || header[156] != b'0' {
+            return None;
+        }
+        let field = |field: &[u8]| {
+            let len = field
+                .iter()
+                .position(|byte| *byte == 0)
+                .unwrap_or(field.len());
+            std::str::from_utf8(&field[..len]).ok().map(str::to_owned)
+        };
+        let (name, prefix) = (field(&header[..100])?, field(&header[345..500])?);
+        let path = if prefix.is_empty() {
+            name
+        } else {
+            format!("{prefix}/{name}")
+        };
+        let size = if header[124] & 0x80 != 0 {
+            let mut size = [0; 8];
+            size.copy_from_slice(&header[128..136]);
+            usize::try_from(u64::from_be_bytes(size)).ok()?
+        } else {
+            read_octal(&header[124..136])? as usize
+        };
+        let padded = size.checked_add((BLOCK_SIZE - size % BLOCK_SIZE) % BLOCK_SIZE)?;
+        if rest.len() < padded {
+            return None;
+        }
+        files.push((path, &rest[..size]));
+        archive = &rest[padded..];
+    }
+    // archives end with zeroed blocks
+    None
+}
+
+/// Read a NUL (or space) terminated octal number
+fn read_octal(field: &[u8]) -> Option<u64> {
+    let len = field
+        .iter()
+        .position(|byte| *byte == 0 || *byte == b' ')
+        .unwrap_or(field.len());
+    u64::from_str_radix(std::str::from_utf8(&field[..len]).ok()?, 8).ok()
+}
+
+/// Write `value` as a zero-padded, NUL terminated octal number that fills `field`
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = format!("{value:0width$o}", width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+    field[digits.len()] = 0;
+}
+
+#[test]
+fn test_tar_entry() {
+    let mut archive = Vec::new();
+    write_tar_entry(&mut archive, "twitter/users", b"hello").unwrap();
+    assert_eq!(archive.len(), BLOCK_SIZE * 2);
+    let header = &archive[..BLOCK_SIZE];
+    assert_eq!(&header[..6], b"users\0");
+    assert_eq!(&header[345..353], b"twitter\0");
+    assert_eq!(&header[124..136], b"00000000005\0");
+    assert_eq!(&header[257..263], b"ustar\0");
+    // verify the checksum
+    let stored = u64::from_str_radix(std::str::from_utf8(&header[148..154]).unwrap(), 8).unwrap();
+    let computed: u64 = header
+        .iter()
+        .enumerate()
+        .map(|(i, byte)| {
+            if (148..156).contains(&i) {
+                b' ' as u64
+            } else {
+                *byte as u64
+            }
+        })
+        .sum();
+    assert_eq!(stored, computed);
+    assert_eq!(&archive[BLOCK_SIZE..BLOCK_SIZE + 5], b"hello");
+}
+
+#[test]
+fn test_read_tar() {
+    let mut archive = Vec::new();
+    write_tar_entry(&mut archive, "PRELOAD", b"hello").unwrap();
+    write_tar_entry(&mut archive, "twitter/users", &[1; BLOCK_SIZE]).unwrap();
+    archive.extend([0; BLOCK_SIZE * 2]);
+    let files = read_tar(&archive).unwrap();
+    assert_eq!(
+        files,
+        [
+            ("PRELOAD".to_owned(), &b"hello"[..]),
+            ("twitter/users".to_owned(), &[1; BLOCK_SIZE][..])
+        ]
+    );
+    // a damaged header or a truncated archive is rejected
+    let mut damaged = archive.clone();
+    damaged[0] = b'X';
+    assert!(read_tar(&damaged).is_none());
+    assert!(read_tar(&archive[..BLOCK_SIZE * 2]).is_none());
+    // the longest names still fit the header
+    let longest = format!(
+        "{}/{}",
+        "k".repeat(ObjectID::MAX_LENGTH),
+        "t".repeat(ObjectID::MAX_LENGTH)
+    );
+    let mut archive = Vec::new();
+    write_tar_entry(&mut archive, &longest, b"hello").unwrap();
+    archive.extend([0; BLOCK_SIZE * 2]);
+    assert_eq!(read_tar(&archive).unwrap(), [(longest, &b"hello"[..])]);
+}
+
+#[test]
+fn test_tar_entry_streamed() {
+    let mut archive = std::io::Cursor::new(Vec::new());
+    let (size, crc) = write_tar_entry_streamed(&mut archive, "twitter/users", |w| {
+        for _ in 0..3 {
+            w.write_all(&[1; 300])?;
+        }
+        Ok(())
+    })
+    .unwrap();
+    assert_eq!((size, crc), (900, super::checksum::crc32c(&[1; 900])));
+    write_tar_entry(&mut archive, "PRELOAD", b"hello").unwrap();
+    let mut archive = archive.into_inner();
+    archive.extend([0; BLOCK_SIZE * 2]);
+    let files = read_tar(&archive).unwrap();
+    assert_eq!(
+        files,
+        [
+            ("twitter/users".to_owned(), &[1; 900][..]),
+            ("PRELOAD".to_owned(), &b"hello"[..])
+        ]
+    );
+    // sizes that don't fit in the octal field are written in base-256
+    let header = tar_header("twitter/users", 9 << 30);
+    assert_eq!(header[124], 0x80);
+    assert_eq!(&header[128..136], &(9u64 << 30).to_be_bytes());
+}
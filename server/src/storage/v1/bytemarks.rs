@@ -35,12 +35,23 @@
 //!
 //! Although ks/system and ks/default might _reside_ next to each other, their bytemarks are entirely
 //! different!
+//!
+//! ## Registry
+//!
+//! Model bytemarks are handed out from the [`MODEL_REGISTRY`]; bytemarks outside of it are either
+//! reserved for future models or for external ones. Readers should always go through [`model`] so
+//! that a bytemark they don't know about is reported as an [`UnknownBytemark`] rather than being
+//! misread.
+
+use core::fmt;
 
 // model
 /*
  * KVEBlob:
  * (1) Pure KVEBlob: [0, 3]
  * (2) KVExt/Listmap: [4, 7]
+ * (3) KVExt/Map: [8, 11]
+ * (4) KVExt/Set: [12, 15]
 */
 /// KVEBlob model bytemark with key:bin, val:bin
 pub const BYTEMARK_MODEL_KV_BIN_BIN: u8 = 0;
@@ -58,6 +69,34 @@ pub const BYTEMARK_MODEL_KV_BINSTR_LIST_STR: u8 = 5;
 pub const BYTEMARK_MODEL_KV_STR_LIST_BINSTR: u8 = 6;
 /// KVEBlob model bytemark with key:str, val: list<str>
 pub const BYTEMARK_MODEL_KV_STR_LIST_STR: u8 = 7;
+/// KVEBlob model bytemark with key:binstr, val: map<binstr>
+pub const BYTEMARK_MODEL_KV_BINSTR_MAP_BINSTR: u8 = 8;
+/// KVEBlob model bytemark with key:binstr, val: map<str>
+pub const BYTEMARK_MODEL_KV_BINSTR_MAP_STR: u8 = 9;
+/// KVEBlob model bytemark with key:str, val: map<binstr>
+pub const BYTEMARK_MODEL_KV_STR_MAP_BINSTR: u8 = 10;
+/// KVEBlob model bytemark with key:str, val: map<str>
+pub const BYTEMARK_MODEL_KV_STR_MAP_STR: u8 = 11;
+/// KVEBlob model bytemark with key:binstr, val: set<binstr>
+pub const BYTEMARK_MODEL_KV_BINSTR_SET_BINSTR: u8 = 12;
+/// KVEBlob model bytemark with key:binstr, val: set<str>
+pub const BYTEMARK_MODEL_KV_BINSTR_SET_STR: u8 = 13;
+/// KVEBlob model bytemark with key:str, val: set<binstr>
+pub const BYTEMARK_MODEL_KV_STR_SET_BINSTR: u8 = 14;
+/// KVEBlob model bytemark with key:str, val: set<str>
+pub const BYTEMARK_MODEL_KV_STR_SET_STR: u8 = 15;
+/// KVEBlob model bytemark with key:binstr, val: zset<binstr>
+pub const BYTEMARK_MODEL_KV_BINSTR_ZSET_BINSTR: u8 = 16;
+/// KVEBlob model bytemark with key:binstr, val: zset<str>
+pub const BYTEMARK_MODEL_KV_BINSTR_ZSET_STR: u8 = 17;
+/// KVEBlob model bytemark with key:str, val: zset<binstr>
+pub const BYTEMARK_MODEL_KV_STR_ZSET_BINSTR: u8 = 18;
+/// KVEBlob model bytemark with key:str, val: zset<str>
+pub const BYTEMARK_MODEL_KV_STR_ZSET_STR: u8 = 19;
+/// KVEBlob model bytemark with key:binstr, val: json
+pub const BYTEMARK_MODEL_KV_BINSTR_JSON: u8 = 20;
+/// KVEBlob model bytemark with key:str, val: json
+pub const BYTEMARK_MODEL_KV_STR_JSON: u8 = 21;
 
 // storage bym
 /// Persistent storage bytemark
@@ -67,3 +106,311 @@ pub const BYTEMARK_STORAGE_VOLATILE: u8 = 1;
 
 // system bym
 pub const SYSTEM_TABLE_AUTH: u8 = 0;
+pub const SYSTEM_TABLE_USAGE: u8 = 1;
+pub const SYSTEM_TABLE_ENTITIES: u8 = 2;
+
+// section bym
+/// Starts the key expiry section, which may follow the entries of a table:
+/// `[1B: BYTEMARK][8B: LEN]([8B: KLEN][?B: KEY][8B: DEADLINE])*`. Tables without expiring keys
+/// don't have one
+pub const BYTEMARK_SECTION_EXPIRY: u8 = 0xE7;
+/// Starts the list bounds section, which may follow the entries (and the key expiry section, if
+/// any) of a list table: `[1B: BYTEMARK][8B: LEN]([8B: KLEN][?B: KEY][8B: BOUND])*`. Tables
+/// without bounded lists don't have one
+pub const BYTEMARK_SECTION_LIST_BOUNDS: u8 = 0xB7;
+
+/*
+ * Registry
+ *
+ * Model bytemarks are a single byte, so we have to be careful about how we hand them out. The
+ * byte space is split into ranges:
+ * (1) Known: [0, 21] (the models listed in the registry below)
+ * (2) Reserved for new first-party models (typed columns, ...): [22, 127]
+ * (3) Reserved for external (third-party) models: [128, 254]
+ * (4) Invalid: 255
+ *
+ * A new model takes the next free bytemark from (2) and gets an entry in the registry. Older
+ * readers will then fail with a clear "unknown bytemark" error instead of misreading the data.
+*/
+
+/// The first bytemark reserved for new first-party models
+pub const BYTEMARK_MODEL_RESERVED_START: u8 = 22;
+/// The first bytemark reserved for external models
+pub const BYTEMARK_MODEL_EXTERNAL_START: u8 = 128;
+/// A bytemark that is never valid
+pub const BYTEMARK_MODEL_INVALID: u8 = 255;
+
+/// The kind of a model
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelKind {
+    /// A pure KVEBlob
+    KV,
+    /// A KVExt/Listmap
+    KVList,
+    /// A KVExt/Map
+    KVMap,
+    /// A KVExt/Set
+    KVSet,
+    /// A KVExt/SortedSet
+    KVSortedSet,
+    /// A KVExt/Document
+    KVDocument,
+}
+
+/// A model known to this version, as described by its bytemark
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ModelMark {
+    pub bytemark: u8,
+    pub kind: ModelKind,
+    pub key_is_str: bool,
+    pub value_is_str: bool,
+}
+
+impl ModelMark {
+    const fn new(bytemark: u8, kind: ModelKind, key_is_str: bool, value_is_str: bool) -> Self {
+        Self {
+            bytemark,
+            kind,
+            key_is_str,
+            value_is_str,
+        }
+    }
+}
+
+/// The registry of all the models that this version can read and write
+pub const MODEL_REGISTRY: [ModelMark; 22] = [
+    ModelMark::new(BYTEMARK_MODEL_KV_BIN_BIN, ModelKind::KV, false, false),
+    ModelMark::new(BYTEMARK_MODEL_KV_BIN_STR, ModelKind::KV, false, true),
+    ModelMark::new(BYTEMARK_MODEL_KV_STR_STR, ModelKind::KV, true, true),
+    ModelMark::new(BYTEMARK_MODEL_KV_STR_BIN, ModelKind::KV, true, false),
+    ModelMark::new(
+        BYTEMARK_MODEL_KV_BINSTR_LIST_BINSTR,
+        ModelKind::KVList,
+        false,
+        false,
+    ),
+    ModelMark::new(
+        BYTEMARK_MODEL_KV_BINSTR_LIST_STR,
+        ModelKind::KVList,
+        false,
+        true,
+    ),
+    ModelMark::new(
+        BYTEMARK_MODEL_KV_STR_LIST_BINSTR,
+        ModelKind::KVList,
+        true,
+        false,
+    ),
+    ModelMark::new(
+        BYTEMARK_MODEL_KV_STR_LIST_STR,
+        ModelKind::KVList,
+        true,
+        true,
+    ),
+    ModelMark::new(
+        BYTEMARK_MODEL_KV_BINSTR_MAP_BINSTR,
+        ModelKind::KVMap,
+        false,
+        false,
+    ),
+    ModelMark::new(
+        BYTEMARK_MODEL_KV_BINSTR_MAP_STR,
+        ModelKind::KVMap,
+        false,
+        true,
+    ),
+    ModelMark::new(
+        BYTEMARK_MODEL_KV_STR_MAP_BINSTR,
+        ModelKind::KVMap,
+        true,
+        false,
+    ),
+    ModelMark::new(BYTEMARK_MODEL_KV_STR_MAP_STR, ModelKind::KVMap, true, true),
+    ModelMark::new(
+        BYTEMARK_MODEL_KV_BINSTR_SET_BINSTR,
+        ModelKind::KVSet,
+        false,
+        false,
+    ),
+    ModelMark::new(
+        BYTEMARK_MODEL_KV_BINSTR_SET_STR,
+        ModelKind::KVSet,
+        false,
+        true,
+    ),
+    ModelMark::new(
+        BYTEMARK_MODEL_KV_STR_SET_BINSTR,
+        ModelKind::KVSet,
+        true,
+        false,
+    ),
+    ModelMark::new(BYTEMARK_MODEL_KV_STR_SET_STR, ModelKind::KVSet, true, true),
+    ModelMark::new(
+        BYTEMARK_MODEL_KV_BINSTR_ZSET_BINSTR,
+        ModelKind::KVSortedSet,
+        false,
+        false,
+    ),
+    ModelMark::new(
+        BYTEMARK_MODEL_KV_BINSTR_ZSET_STR,
+        ModelKind::KVSortedSet,
+        false,
+        true,
+    ),
+    ModelMark::new(
+        BYTEMARK_MODEL_KV_STR_ZSET_BINSTR,
+        ModelKind::KVSortedSet,
+        true,
+        false,
+    ),
+    ModelMark::new(
+        BYTEMARK_MODEL_KV_STR_ZSET_STR,
+        ModelKind::KVSortedSet,
+        true,
+        true,
+    ),
+    // documents are always valid UTF-8
+    ModelMark::new(
+        BYTEMARK_MODEL_KV_BINSTR_JSON,
+        ModelKind::KVDocument,
+        false,
+        true,
+    ),
+    ModelMark::new(
+        BYTEMARK_MODEL_KV_STR_JSON,
+        ModelKind::KVDocument,
+        true,
+        true,
+    ),
+];
+
+// every registered bytemark must sit at its own index, below the reserved ranges
+const _: () = {
+    let mut i = 0;
+    while i < MODEL_REGISTRY.len() {
+        assert!(MODEL_REGISTRY[i].bytemark as usize == i);
+        assert!(MODEL_REGISTRY[i].bytemark < BYTEMARK_MODEL_RESERVED_START);
+        i += 1;
+    }
+};
+
+/// The range that a bytemark falls into
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BytemarkRange {
+    /// A bytemark that this version knows about
+    Known,
+    /// Reserved for models added in newer versions
+    Reserved,
+    /// Reserved for external models
+    External,
+    /// Never valid
+    Invalid,
+}
+
+impl BytemarkRange {
+    /// Returns the range of the given model bytemark
+    pub const fn of_model(bytemark: u8) -> Self {
+        if (bytemark as usize) < MODEL_REGISTRY.len() {
+            Self::Known
+        } else if bytemark < BYTEMARK_MODEL_EXTERNAL_START {
+            Self::Reserved
+        } else if bytemark < BYTEMARK_MODEL_INVALID {
+            Self::External
+        } else {
+            Self::Invalid
+        }
+    }
+}
+
+/// A bytemark that this version doesn't know how to read
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownBytemark {
+    /// An unknown model bytemark
+    Model(u8),
+    /// An unknown storage bytemark
+    Storage(u8),
+    /// An unknown system table bytemark
+    System(u8),
+}
+
+impl fmt::Display for UnknownBytemark {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Model(bym) => match BytemarkRange::of_model(bym) {
+                BytemarkRange::Reserved => write!(
+                    f,
+                    "unknown model bytemark {bym} (a model from a newer version of Skytable)"
+                ),
+                BytemarkRange::External => {
+                    write!(f, "unknown model bytemark {bym} (an external model)")
+                }
+                _ => write!(f, "invalid model bytemark {bym}"),
+            },
+            Self::Storage(bym) => write!(f, "unknown storage bytemark {bym}"),
+            Self::System(bym) => write!(f, "unknown system table bytemark {bym}"),
+        }
+    }
+}
+
+/// Look up a model bytemark in the registry
+pub const fn model(bytemark: u8) -> Result<ModelMark, UnknownBytemark> {
+    if (bytemark as usize) < MODEL_REGISTRY.len() {
+        Ok(MODEL_REGISTRY[bytemark as usize])
+    } else {
+        Err(UnknownBytemark::Model(bytemark))
+    }
+}
+
+/// Look up a storage bytemark, returning true if the table is volatile
+pub const fn storage_is_volatile(bytemark: u8) -> Result<bool, UnknownBytemark> {
+    match bytemark {
+        BYTEMARK_STORAGE_PERSISTENT => Ok(false),
+        BYTEMARK_STORAGE_VOLATILE => Ok(true),
+        _ => Err(UnknownBytemark::Storage(bytemark)),
+    }
+}
+
+#[test]
+fn test_bytemark_registry() {
+    for code in 0..=u8::MAX {
+        let known = model(code).is_ok();
+        assert_eq!(known, BytemarkRange::of_model(code) == BytemarkRange::Known);
+    }
+    assert_eq!(
+        model(BYTEMARK_MODEL_KV_STR_LIST_BINSTR).unwrap(),
+        ModelMark::new(6, ModelKind::KVList, true, false)
+    );
+    assert_eq!(
+        model(BYTEMARK_MODEL_KV_BINSTR_MAP_STR).unwrap(),
+        ModelMark::new(9, ModelKind::KVMap, false, true)
+    );
+    assert_eq!(
+        model(BYTEMARK_MODEL_KV_STR_SET_BINSTR).unwrap(),
+        ModelMark::new(14, ModelKind::KVSet, true, false)
+    );
+    assert_eq!(
+        model(BYTEMARK_MODEL_KV_BINSTR_ZSET_STR).unwrap(),
+        ModelMark::new(17, ModelKind::KVSortedSet, false, true)
+    );
+    assert_eq!(
+        model(BYTEMARK_MODEL_KV_STR_JSON).unwrap(),
+        ModelMark::new(21, ModelKind::KVDocument, true, true)
+    );
+    assert_eq!(
+        model(22).unwrap_err().to_string(),
+        "unknown model bytemark 22 (a model from a newer version of Skytable)"
+    );
+    assert_eq!(
+        model(200).unwrap_err().to_string(),
+        "unknown model bytemark 200 (an external model)"
+    );
+    assert_eq!(
+        model(255).unwrap_err().to_string(),
+        "invalid model bytemark 255"
+    );
+    assert!(storage_is_volatile(BYTEMARK_STORAGE_VOLATILE).unwrap());
+    assert_eq!(
+        storage_is_volatile(2).unwrap_err().to_string(),
+        "unknown storage bytemark 2"
+    );
+}
@@ -0,0 +1,210 @@
+/*
+ * Created on Wed Oct 19 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Segment checksums
+//!
+//! Table files are written in checksummed segments. The layout is:
+//! ```text
+//! [MAGIC: 8B][segment 0: up to SEGMENT_SIZE B][CRC32C(segment 0): 4B LE][segment 1]...
+//! ```
+//! Every segment but the last is exactly [`SEGMENT_SIZE`] bytes long. The magic is chosen such
+//! that when it is read as the (native endian) `u64` entry count that starts a legacy
+//! (unchecksummed) table file, it is a count that no table could have ever had; this lets us
+//! continue to load files written by older versions.
+
+use {
+    super::error::{StorageEngineError, StorageEngineResult},
+    std::io::{Result as IoResult, Write},
+};
+
+/// The magic that starts a checksummed table file
+pub const MAGIC: [u8; 8] = *b"SKYCSUM\xFF";
+/// The size of a segment (excluding its checksum)
+pub const SEGMENT_SIZE: usize = 64 * 1024;
+/// The size of a segment's checksum
+const CHECKSUM_SIZE: usize = 4;
+/// The (reflected) CRC32C (Castagnoli) polynomial
+const POLY_CRC32C: u32 = 0x82F63B78;
+const CRC32C_TABLE: [u32; 256] = crc32c_table();
+
+const fn crc32c_table() -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut j = 0;
+        while j < 8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ POLY_CRC32C
+            } else {
+                crc >> 1
+            };
+            j += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+/// Returns the CRC32C of the given bytes
+pub fn crc32c(bytes: &[u8]) -> u32 {
+    self::crc32c_append(0, bytes)
+}
+
+/// Returns the CRC32C of some data followed by `bytes`, given the CRC32C `crc` of that data
+pub fn crc32c_append(crc: u32, bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!crc, |crc, byte| {
+        CRC32C_TABLE[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8)
+    })
+}
+
+/// A writer that splits everything written to it into checksummed segments. Make sure that you
+/// call [`Write::flush`] once you're done, or the last segment will never be written
+pub struct ChecksummedWriter<W: Write> {
+    inner: W,
+    segment: Vec<u8>,
+    wrote_magic: bool,
+}
+
+impl<W: Write> ChecksummedWriter<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner,
+            segment: Vec::with_capacity(SEGMENT_SIZE),
+            wrote_magic: false,
+        }
+    }
+    fn write_segment(&mut self) -> IoResult<()> {
+        if !self.wrote_magic {
+            self.inner.write_all(&MAGIC)?;
+            self.wrote_magic = true;
+        }
+        if !self.segment.is_empty() {
+            self.inner.write_all(&self.segment)?;
+            self.inner.write_all(&crc32c(&self.segment).to_le_bytes())?;
+            self.segment.clear();
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> Write for ChecksummedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let take = buf.len().min(SEGMENT_SIZE - self.segment.len());
+        self.segment.extend_from_slice(&buf[..take]);
+        if self.segment.len() == SEGMENT_SIZE {
+            self.write_segment()?;
+        }
+        Ok(take)
+    }
+    fn flush(&mut self) -> IoResult<()> {
+        self.write_segment()?;
+        self.inner.flush()
+    }
+}
+
+/// Returns true if `data` was written by a [`ChecksummedWriter`]
+pub fn is_checksummed(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+/// Verify every segment in the checksummed `data` in place and return the payloads of the
+/// segments, borrowed from `data`. `file` is used to report which file was corrupted and at what
+/// offset, where `data` starts at `base` in the file
+pub fn segments<'a>(data: &'a [u8], file: &str, base: usize) -> StorageEngineResult<Vec<&'a [u8]>> {
+    match self::segments_prefix(data, file, base) {
+        (segments, None) => Ok(segments),
+        (_, Some(e)) => Err(e),
+    }
+}
+
+/// Same as [`segments`], but stops at the first damaged segment instead of failing. Returns the
+/// payloads of the intact segments before it, along with the error for the damaged segment
+pub fn segments_prefix<'a>(
+    data: &'a [u8],
+    file: &str,
+    base: usize,
+) -> (Vec<&'a [u8]>, Option<StorageEngineError>) {
+    let mut segments = Vec::with_capacity(data.len() / SEGMENT_SIZE + 1);
+    let mut offset = base + MAGIC.len();
+    for chunk in data[MAGIC.len()..].chunks(SEGMENT_SIZE + CHECKSUM_SIZE) {
+        if chunk.len() <= CHECKSUM_SIZE {
+            let e = StorageEngineError::segment_checksum_mismatch(file, offset);
+            return (segments, Some(e));
+        }
+        let (segment, checksum) = chunk.split_at(chunk.len() - CHECKSUM_SIZE);
+        let expected = u32::from_le_bytes([checksum[0], checksum[1], checksum[2], checksum[3]]);
+        if crc32c(segment) != expected {
+            let e = StorageEngineError::segment_checksum_mismatch(file, offset);
+            return (segments, Some(e));
+        }
+        segments.push(segment);
+        offset += chunk.len();
+    }
+    (segments, None)
+}
+
+/// Same as [`segments_prefix`], but copies the payload of the intact segments into a single
+/// buffer
+pub fn verify_prefix(
+    data: &[u8],
+    file: &str,
+    base: usize,
+) -> (Vec<u8>, Option<StorageEngineError>) {
+    let (segments, e) = self::segments_prefix(data, file, base);
+    (segments.concat(), e)
+}
+
+#[test]
+fn test_crc32c() {
+    // the check value for CRC32C
+    assert_eq!(crc32c(b"123456789"), 0xE3069283);
+    assert_eq!(crc32c(b""), 0);
+    assert_eq!(crc32c_append(crc32c(b"1234"), b"56789"), 0xE3069283);
+}
+
+#[test]
+fn test_checksummed_roundtrip() {
+    let payload: Vec<u8> = (0..SEGMENT_SIZE * 2 + 100).map(|i| i as u8).collect();
+    let mut file = Vec::new();
+    let mut writer = ChecksummedWriter::new(&mut file);
+    writer.write_all(&payload).unwrap();
+    writer.flush().unwrap();
+    assert!(is_checksummed(&file));
+    assert_eq!(segments(&file, "ks/tbl", 0).unwrap().concat(), payload);
+    // corrupt a byte in the second segment
+    let second_segment = MAGIC.len() + SEGMENT_SIZE + CHECKSUM_SIZE;
+    file[second_segment + 10] ^= 0xFF;
+    assert_eq!(
+        segments(&file, "ks/tbl", 0).unwrap_err().to_string(),
+        format!("checksum mismatch in file `ks/tbl` for the segment at offset {second_segment}")
+    );
+    // but the first segment can still be salvaged
+    let (salvaged, e) = verify_prefix(&file, "ks/tbl", 0);
+    assert_eq!(salvaged, payload[..SEGMENT_SIZE]);
+    assert!(e.is_some());
+}
@@ -24,7 +24,10 @@
  *
 */
 
-use {crate::corestore::memstore::ObjectID, core::fmt, std::io::Error as IoError};
+use {
+    super::bytemarks::UnknownBytemark, crate::corestore::memstore::ObjectID, core::fmt,
+    std::io::Error as IoError,
+};
 
 pub type StorageEngineResult<T> = Result<T, StorageEngineError>;
 
@@ -49,12 +52,21 @@ pub enum StorageEngineError {
     CorruptedFile(String),
     /// The file contains bad metadata
     BadMetadata(String),
+    /// The file was written in a storage format that we don't support
+    UnsupportedFormat(String, u16),
+    /// A segment of the file failed checksum verification
+    ChecksumMismatch(String, usize),
+    /// The file uses a bytemark that we don't know about
+    UnknownBytemark(String, UnknownBytemark),
 }
 
 impl StorageEngineError {
     pub fn corrupted_partmap(ksid: &ObjectID) -> Self {
         Self::CorruptedFile(format!("{ksid}/PARTMAP", ksid = unsafe { ksid.as_str() }))
     }
+    pub fn corrupted_ksmeta(ksid: &ObjectID) -> Self {
+        Self::CorruptedFile(format!("{ksid}/KSMETA", ksid = unsafe { ksid.as_str() }))
+    }
     pub fn bad_metadata_in_table(ksid: &ObjectID, table: &ObjectID) -> Self {
         unsafe {
             Self::CorruptedFile(format!(
@@ -64,6 +76,25 @@ impl StorageEngineError {
             ))
         }
     }
+    pub fn unknown_bytemark_in_table(
+        ksid: &ObjectID,
+        table: &ObjectID,
+        bytemark: UnknownBytemark,
+    ) -> Self {
+        unsafe {
+            Self::UnknownBytemark(
+                format!(
+                    "{ksid}/{table}",
+                    ksid = ksid.as_str(),
+                    table = table.as_str()
+                ),
+                bytemark,
+            )
+        }
+    }
+    pub fn segment_checksum_mismatch(file: &str, offset: usize) -> Self {
+        Self::ChecksumMismatch(file.to_owned(), offset)
+    }
     pub fn corrupted_preload() -> Self {
         Self::CorruptedFile("PRELOAD".into())
     }
@@ -85,6 +116,15 @@ impl fmt::Display for StorageEngineError {
             Self::IoErrorExtra(ioe, extra) => write!(f, "I/O error while {extra}: {ioe}"),
             Self::CorruptedFile(cfile) => write!(f, "file `{cfile}` is corrupted"),
             Self::BadMetadata(file) => write!(f, "bad metadata in file `{file}`"),
+            Self::UnsupportedFormat(file, version) => write!(
+                f,
+                "file `{file}` was written in an unsupported storage format (v{version})"
+            ),
+            Self::ChecksumMismatch(file, offset) => write!(
+                f,
+                "checksum mismatch in file `{file}` for the segment at offset {offset}"
+            ),
+            Self::UnknownBytemark(file, bytemark) => write!(f, "{bytemark} in file `{file}`"),
         }
     }
 }
@@ -27,10 +27,11 @@
 //! # Flush routines
 //!
 //! This module contains multiple flush routines: at the memstore level, the keyspace level and
-//! the table level
+//! the table level. A full flush writes the tables of user keyspaces concurrently, on up to
+//! [`flush_workers`] threads
 
 use {
-    super::{bytemarks, interface},
+    super::{bytemarks, fsync, interface, preload::Ksmeta, stats::stats},
     crate::{
         corestore::{
             map::iter::BorrowedIter,
@@ -39,13 +40,38 @@ use {
             table::{DataModel, SystemDataModel, SystemTable, Table},
         },
         registry,
+        storage::v2::header::{FileKind, Header, ModelDescriptor},
         util::Wrapper,
         IoResult,
     },
     core::ops::Deref,
-    std::{io::Write, sync::Arc},
+    std::{
+        io::Write,
+        panic,
+        sync::{
+            atomic::{AtomicBool, AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+        time::Instant,
+    },
 };
 
+/// The default number of threads used to flush tables
+pub const DEFAULT_FLUSH_WORKERS: usize = 4;
+/// The number of threads used to flush tables
+static FLUSH_WORKERS: AtomicUsize = AtomicUsize::new(DEFAULT_FLUSH_WORKERS);
+
+/// Set the maximum number of threads that flush tables concurrently
+pub fn set_flush_workers(workers: usize) {
+    FLUSH_WORKERS.store(workers.max(1), Ordering::Release)
+}
+
+/// Returns the maximum number of threads that flush tables concurrently
+pub fn flush_workers() -> usize {
+    FLUSH_WORKERS.load(Ordering::Acquire)
+}
+
 pub trait StorageTarget {
     /// This storage target needs a reinit of the tree despite no preload trip.
     /// Exempli gratia: rsnap, snap
@@ -54,6 +80,16 @@ pub trait StorageTarget {
     ///
     /// Example cases where this doesn't apply: snapshots
     const SHOULD_UNTRIP_PRELOAD_TRIPSWITCH: bool;
+    /// This storage target overwrites the files of the previous flush, so tables that haven't
+    /// changed since then can be skipped
+    ///
+    /// Example cases where this doesn't apply: snapshots (every snapshot is a new directory)
+    const SKIPS_CLEAN_TABLES: bool;
+    /// This storage target keeps the previous generations of the `PRELOAD` to roll back to
+    /// (see [`super::preload`])
+    ///
+    /// Example cases where this doesn't apply: snapshots
+    const KEEPS_PRELOAD_GENERATIONS: bool;
     /// The root for this storage target. **Must not be separator terminated!**
     fn root(&self) -> String;
     /// Returns the path to the `PRELOAD_` **temporary file** ($ROOT/PRELOAD)
@@ -78,6 +114,14 @@ pub trait StorageTarget {
         p.push_str("PARTMAP_");
         p
     }
+    /// Returns the path to the `KSMETA_` for the given keyspace. **temporary file**
+    /// ($ROOT/{keyspace}/KSMETA)
+    fn ksmeta_target(&self, keyspace: &str) -> String {
+        let mut p = self.keyspace_target(keyspace);
+        p.push('/');
+        p.push_str("KSMETA_");
+        p
+    }
     /// Returns the path to the table file. **temporary file** ($ROOT/{keyspace}/{table}_)
     fn table_target(&self, keyspace: &str, table: &str) -> String {
         let mut p = self.keyspace_target(keyspace);
@@ -94,6 +138,8 @@ pub struct Autoflush;
 impl StorageTarget for Autoflush {
     const NEEDS_TREE_INIT: bool = false;
     const SHOULD_UNTRIP_PRELOAD_TRIPSWITCH: bool = true;
+    const SKIPS_CLEAN_TABLES: bool = true;
+    const KEEPS_PRELOAD_GENERATIONS: bool = true;
     fn root(&self) -> String {
         String::from(interface::DIR_KSROOT)
     }
@@ -113,6 +159,8 @@ impl<'a> RemoteSnapshot<'a> {
 impl<'a> StorageTarget for RemoteSnapshot<'a> {
     const NEEDS_TREE_INIT: bool = true;
     const SHOULD_UNTRIP_PRELOAD_TRIPSWITCH: bool = false;
+    const SKIPS_CLEAN_TABLES: bool = false;
+    const KEEPS_PRELOAD_GENERATIONS: bool = false;
     fn root(&self) -> String {
         let mut p = String::from(interface::DIR_RSNAPROOT);
         p.push('/');
@@ -135,6 +183,8 @@ impl LocalSnapshot {
 impl StorageTarget for LocalSnapshot {
     const NEEDS_TREE_INIT: bool = true;
     const SHOULD_UNTRIP_PRELOAD_TRIPSWITCH: bool = false;
+    const SKIPS_CLEAN_TABLES: bool = false;
+    const KEEPS_PRELOAD_GENERATIONS: bool = false;
     fn root(&self) -> String {
         let mut p = String::from(interface::DIR_SNAPROOT);
         p.push('/');
@@ -150,12 +200,31 @@ pub trait FlushableKeyspace<T: FlushableTable, U: Deref<Target = T>> {
     /// An iterator to the tables in this keyspace.
     /// All of them implement [`FlushableTable`]
     fn get_iter(&self) -> BorrowedIter<'_, ObjectID, U>;
+    /// The keyspace-level settings that are recorded in the `KSMETA`
+    fn ksmeta(&self) -> Ksmeta;
 }
 
 impl FlushableKeyspace<Table, Arc<Table>> for Keyspace {
     fn table_count(&self) -> usize {
         self.tables.len()
     }
+    fn ksmeta(&self) -> Ksmeta {
+        Ksmeta {
+            flush_interval: self.flush_interval(),
+            max_keys: self.quota().max_keys(),
+            max_bytes: self.quota().max_bytes(),
+            table_shards: self
+                .tables
+                .iter()
+                .filter_map(|table| {
+                    let shards = table.value().configured_shards()?;
+                    Some((table.key().clone(), shards as u64))
+                })
+                .collect(),
+            default_model: self.default_model(),
+            replication_strategy: self.replication_strategy(),
+        }
+    }
     fn get_iter(&self) -> BorrowedIter<'_, ObjectID, Arc<Table>> {
         self.tables.iter()
     }
@@ -165,6 +234,9 @@ impl FlushableKeyspace<SystemTable, Wrapper<SystemTable>> for SystemKeyspace {
     fn table_count(&self) -> usize {
         self.tables.len()
     }
+    fn ksmeta(&self) -> Ksmeta {
+        Ksmeta::default()
+    }
     fn get_iter(&self) -> BorrowedIter<'_, ObjectID, Wrapper<SystemTable>> {
         self.tables.iter()
     }
@@ -179,6 +251,8 @@ pub trait FlushableTable {
     fn write_table_to<W: Write>(&self, writer: &mut W) -> IoResult<()>;
     /// Returns the model code bytemark
     fn model_code(&self) -> u8;
+    /// Returns the (v2) header for this table's file
+    fn file_header(&self) -> Header;
 }
 
 impl FlushableTable for Table {
@@ -187,9 +261,32 @@ impl FlushableTable for Table {
     }
     fn write_table_to<W: Write>(&self, writer: &mut W) -> IoResult<()> {
         match self.get_model_ref() {
-            DataModel::KV(ref kve) => super::se::raw_serialize_map(kve.get_inner_ref(), writer),
+            DataModel::KV(ref kve) => {
+                // freezing the archive stops values from being archived while we write
+                let mut archive = kve.archive().freeze();
+                super::se::raw_serialize_archived_map(kve.get_inner_ref(), &mut archive, writer)?;
+                super::se::raw_serialize_expiry(kve.expiry(), writer)
+            }
             DataModel::KVExtListmap(ref kvl) => {
-                super::se::raw_serialize_list_map(kvl.get_inner_ref(), writer)
+                super::se::raw_serialize_list_map(kvl.get_inner_ref(), writer)?;
+                super::se::raw_serialize_expiry(kvl.expiry(), writer)?;
+                super::se::raw_serialize_list_bounds(kvl.list_bounds(), writer)
+            }
+            DataModel::KVExtMap(ref kvm) => {
+                super::se::raw_serialize_map_map(kvm.get_inner_ref(), writer)?;
+                super::se::raw_serialize_expiry(kvm.expiry(), writer)
+            }
+            DataModel::KVExtSet(ref kvs) => {
+                super::se::raw_serialize_set_map(kvs.get_inner_ref(), writer)?;
+                super::se::raw_serialize_expiry(kvs.expiry(), writer)
+            }
+            DataModel::KVExtSortedSet(ref kvz) => {
+                super::se::raw_serialize_sorted_set_map(kvz.get_inner_ref(), writer)?;
+                super::se::raw_serialize_expiry(kvz.expiry(), writer)
+            }
+            DataModel::KVExtDocument(ref kvd) => {
+                super::se::raw_serialize_document_map(kvd.get_inner_ref(), writer)?;
+                super::se::raw_serialize_expiry(kvd.expiry(), writer)
             }
         }
     }
@@ -199,6 +296,12 @@ impl FlushableTable for Table {
     fn model_code(&self) -> u8 {
         self.get_model_code()
     }
+    fn file_header(&self) -> Header {
+        match ModelDescriptor::from_model_code(self.get_model_code()) {
+            Ok(model) => Header::new(FileKind::Table, model),
+            Err(_) => unsafe { impossible!() },
+        }
+    }
 }
 
 impl FlushableTable for SystemTable {
@@ -208,6 +311,10 @@ impl FlushableTable for SystemTable {
     fn write_table_to<W: Write>(&self, writer: &mut W) -> IoResult<()> {
         match self.get_model_ref() {
             SystemDataModel::Auth(amap) => super::se::raw_serialize_map(amap.as_ref(), writer),
+            SystemDataModel::Usage(ledger) => super::se::raw_serialize_map(ledger.as_ref(), writer),
+            SystemDataModel::Entities(catalog) => {
+                super::se::raw_serialize_map(catalog.as_ref(), writer)
+            }
         }
     }
     fn storage_code(&self) -> u8 {
@@ -216,12 +323,38 @@ impl FlushableTable for SystemTable {
     fn model_code(&self) -> u8 {
         match self.get_model_ref() {
             SystemDataModel::Auth(_) => bytemarks::SYSTEM_TABLE_AUTH,
+            SystemDataModel::Usage(_) => bytemarks::SYSTEM_TABLE_USAGE,
+            SystemDataModel::Entities(_) => bytemarks::SYSTEM_TABLE_ENTITIES,
+        }
+    }
+    fn file_header(&self) -> Header {
+        match self.get_model_ref() {
+            SystemDataModel::Auth(_) => {
+                Header::new(FileKind::SystemTable, ModelDescriptor::SYSTEM_AUTH)
+            }
+            SystemDataModel::Usage(_) => {
+                Header::new(FileKind::SystemTable, ModelDescriptor::SYSTEM_USAGE)
+            }
+            SystemDataModel::Entities(_) => {
+                Header::new(FileKind::SystemTable, ModelDescriptor::SYSTEM_ENTITIES)
+            }
         }
     }
 }
 
 /// Flush the entire **preload + keyspaces + their partmaps**
-pub fn flush_full<T: StorageTarget>(target: T, store: &Memstore) -> IoResult<()> {
+pub fn flush_full<T: StorageTarget + Sync>(target: T, store: &Memstore) -> IoResult<()> {
+    self::flush_full_where(target, store, |_| true)
+}
+
+/// Same as [`flush_full`], except that user keyspaces for which `should_flush` returns false
+/// are skipped. System tables are always flushed
+pub fn flush_full_where<T: StorageTarget + Sync>(
+    target: T,
+    store: &Memstore,
+    should_flush: impl Fn(&Keyspace) -> bool,
+) -> IoResult<()> {
+    let start = Instant::now();
     // IMPORTANT: Just untrip and get the status at this exact point in time
     // don't spread it over two atomic accesses because another thread may have updated
     // it in-between. Even if it was untripped, we'll get the expected outcome here: false
@@ -235,20 +368,94 @@ pub fn flush_full<T: StorageTarget>(target: T, store: &Memstore) -> IoResult<()>
         super::interface::create_tree(&target, store)?;
         self::oneshot::flush_preload(&target, store)?;
     }
-    // flush userspace keyspaces
+    // flush userspace keyspaces: the partmaps and ksmetas go first and then the tables are
+    // flushed concurrently since they're independent of each other
+    let mut tables = Vec::new();
     for keyspace in store.keyspaces.iter() {
-        self::flush_keyspace_full(&target, keyspace.key(), keyspace.value().as_ref())?;
+        let (ksid, keyspace) = (keyspace.key(), keyspace.value().as_ref());
+        if should_flush(keyspace) {
+            self::oneshot::flush_partmap(&target, ksid, keyspace)?;
+            self::oneshot::flush_ksmeta(&target, ksid, keyspace)?;
+            tables.extend(
+                keyspace
+                    .tables
+                    .iter()
+                    .map(|table| (ksid.clone(), table.key().clone(), table.value().clone())),
+            );
+        }
     }
+    self::flush_tables(&target, &tables)?;
     // flush system tables
     // HACK(@ohsayan): DO NOT REORDER THIS. THE above loop will flush a PARTMAP and an empty
     // keyspace once. But this has to be done again! The system keyspace in the above loop is a
     // dummy one because it is located in a different field. So, we need to flush the actual
     // tables
     self::flush_keyspace_full(&target, &SYSTEM, &store.system)?;
+    stats().record_flush(start.elapsed());
+    Ok(())
+}
+
+/// Flushes the given tables (`(keyspace, table, table ref)`) using up to [`flush_workers`]
+/// threads. If a table fails to flush, the workers stop picking up new tables and the first
+/// error is returned
+fn flush_tables<T: StorageTarget + Sync>(
+    target: &T,
+    tables: &[(ObjectID, ObjectID, Arc<Table>)],
+) -> IoResult<()> {
+    let workers = flush_workers().min(tables.len());
+    if workers <= 1 {
+        return tables.iter().try_for_each(|(ksid, tableid, table)| {
+            self::flush_table_if_dirty(target, tableid, ksid, table.as_ref())
+        });
+    }
+    let next = AtomicUsize::new(0);
+    let failed = AtomicBool::new(false);
+    let worker = || {
+        while !failed.load(Ordering::Acquire) {
+            let (ksid, tableid, table) = match tables.get(next.fetch_add(1, Ordering::AcqRel)) {
+                Some(job) => job,
+                None => break,
+            };
+            if let Err(e) = self::flush_table_if_dirty(target, tableid, ksid, table.as_ref()) {
+                failed.store(true, Ordering::Release);
+                return Err(e);
+            }
+        }
+        Ok(())
+    };
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers).map(|_| scope.spawn(worker)).collect();
+        // the scope joins any workers left over if we return early
+        handles.into_iter().try_for_each(|handle| {
+            handle
+                .join()
+                .unwrap_or_else(|payload| panic::resume_unwind(payload))
+        })
+    })
+}
+
+/// Flushes a user table, unless the target allows skipping clean tables (see
+/// [`StorageTarget::SKIPS_CLEAN_TABLES`]) and the table hasn't changed since it was last flushed
+fn flush_table_if_dirty<T: StorageTarget>(
+    target: &T,
+    tableid: &ObjectID,
+    ksid: &ObjectID,
+    table: &Table,
+) -> IoResult<()> {
+    if !T::SKIPS_CLEAN_TABLES {
+        return self::oneshot::flush_table(target, tableid, ksid, table);
+    }
+    // read this before serializing: anything that changes while we write will leave the table
+    // dirty for the next flush
+    let mutations = table.mutations();
+    if table.is_dirty(mutations) {
+        self::oneshot::flush_table(target, tableid, ksid, table)?;
+        table.mark_flushed(mutations);
+    }
     Ok(())
 }
 
-/// Flushes the entire **keyspace + partmap**
+/// Flushes the entire **keyspace + partmap + ksmeta**
 pub fn flush_keyspace_full<T, U, Tbl, K>(target: &T, ksid: &ObjectID, keyspace: &K) -> IoResult<()>
 where
     T: StorageTarget,
@@ -257,6 +464,7 @@ where
     K: FlushableKeyspace<Tbl, U>,
 {
     self::oneshot::flush_partmap(target, ksid, keyspace)?;
+    self::oneshot::flush_ksmeta(target, ksid, keyspace)?;
     self::oneshot::flush_keyspace(target, ksid, keyspace)
 }
 
@@ -267,7 +475,11 @@ pub mod oneshot {
     //! files et al are handled
     //!
     use super::*;
-    use std::fs::{self, File};
+    use crate::storage::v1::preload::{self, PRELOAD_GENERATIONS};
+    use std::{
+        fs::{self, File},
+        io::Seek,
+    };
 
     #[inline(always)]
     fn cowfile(
@@ -276,8 +488,8 @@ pub mod oneshot {
     ) -> IoResult<()> {
         let mut f = File::create(cowfile_name)?;
         with_open(&mut f)?;
-        f.sync_all()?;
-        fs::rename(&cowfile_name, &cowfile_name[..cowfile_name.len() - 1])
+        stats().record_write(f.stream_position()?);
+        fsync::sync_and_rename(&f, cowfile_name, &cowfile_name[..cowfile_name.len() - 1])
     }
 
     /// No `partmap` handling. Just flushes the table to the expected location
@@ -294,7 +506,9 @@ pub mod oneshot {
             let path = unsafe { target.table_target(ksid.as_str(), tableid.as_str()) };
             cowfile(&path, |file| {
                 super::interface::serialize_table_into_slow_buffer(file, table)
-            })
+            })?;
+            stats().record_table_flushed();
+            Ok(())
         }
     }
 
@@ -326,11 +540,44 @@ pub mod oneshot {
         })
     }
 
+    /// Flushes a single ksmeta
+    pub fn flush_ksmeta<T, U, Tbl, K>(target: &T, ksid: &ObjectID, keyspace: &K) -> IoResult<()>
+    where
+        T: StorageTarget,
+        U: Deref<Target = Tbl>,
+        Tbl: FlushableTable,
+        K: FlushableKeyspace<Tbl, U>,
+    {
+        let path = unsafe { target.ksmeta_target(ksid.as_str()) };
+        cowfile(&path, |file| {
+            super::interface::serialize_ksmeta_into_slow_buffer(file, keyspace)
+        })
+    }
+
     // Flush the `PRELOAD`
     pub fn flush_preload<T: StorageTarget>(target: &T, store: &Memstore) -> IoResult<()> {
         let preloadtmp = target.preload_target();
         cowfile(&preloadtmp, |file| {
             super::interface::serialize_preload_into_slow_buffer(file, store)
-        })
+        })?;
+        if T::KEEPS_PRELOAD_GENERATIONS {
+            self::save_preload_generation(&target.root())?;
+        }
+        Ok(())
+    }
+
+    /// Save the `PRELOAD` under `root` as a new generation, removing the generations that are
+    /// past [`PRELOAD_GENERATIONS`]
+    fn save_preload_generation(root: &str) -> IoResult<()> {
+        let generations = preload::list_generations(root)?;
+        let next = generations.first().map_or(1, |latest| latest + 1);
+        let current = fs::read(format!("{root}/PRELOAD"))?;
+        let mut path = preload::generation_path(root, next);
+        path.push('_');
+        cowfile(&path, |file| file.write_all(&current))?;
+        for old in generations.iter().skip(PRELOAD_GENERATIONS - 1) {
+            fs::remove_file(preload::generation_path(root, *old))?;
+        }
+        Ok(())
     }
 }
@@ -0,0 +1,217 @@
+/*
+ * Created on Tue Nov 01 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Sync policy
+//!
+//! The sync policy decides when the files written by flushes are synced to disk:
+//! - `always`: every file is synced before it replaces the previous version (the default)
+//! - `everysec`: a file that replaces the previous version is synced before the rename, but the
+//!   rename itself (its directory) is synced in the background, about once every second (see
+//!   [`sync_pending`]). Files written in place are synced in the background too. A crash may
+//!   lose the flushes of the last second, but never leaves a renamed file without its data
+//! - `os`: files are never synced explicitly and the operating system decides when the data
+//!   hits the disk
+//!
+//! Flushed files replace the previous version with [`replace`]. On POSIX systems, that's a
+//! `rename`, which atomically swaps the files. On Windows, `rename` is a `MoveFileExW` that isn't
+//! guaranteed to survive a crash, so we use `ReplaceFileW` and then explicitly flush the replaced
+//! file.
+
+use {
+    crate::{config::SyncPolicy, IoResult},
+    std::{
+        fs::File,
+        io::ErrorKind,
+        path::Path,
+        sync::{
+            atomic::{AtomicU8, Ordering},
+            Mutex,
+        },
+    },
+};
+
+/// The sync policy
+static POLICY: AtomicU8 = AtomicU8::new(SyncPolicy::Always as u8);
+/// The files waiting to be synced by [`sync_pending`]
+static PENDING: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// Set the sync policy
+pub fn set_policy(policy: SyncPolicy) {
+    POLICY.store(policy as u8, Ordering::Release)
+}
+
+/// Returns the sync policy
+pub fn policy() -> SyncPolicy {
+    SyncPolicy::from_u8(POLICY.load(Ordering::Acquire))
+}
+
+fn defer(path: &str) {
+    PENDING
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .push(path.to_owned());
+}
+
+/// Replace the file at `to` with the freshly written `file` at `from`, syncing it according
+/// to the sync policy
+pub fn sync_and_rename(file: &File, from: &str, to: &str) -> IoResult<()> {
+    match self::policy() {
+        SyncPolicy::Always => {
+            file.sync_all()?;
+            self::replace(from, to)
+        }
+        SyncPolicy::EverySec => {
+            // the data has to hit the disk before the rename does, so only the directory
+            // entry is left to the next sweep
+            file.sync_data()?;
+            self::replace(from, to)?;
+            self::defer_dir(to);
+            Ok(())
+        }
+        SyncPolicy::Os => self::replace(from, to),
+    }
+}
+
+/// Queue the directory holding `path` to be synced, so that a rename in it hits the disk
+#[cfg(not(windows))]
+fn defer_dir(path: &str) {
+    match Path::new(path).parent().and_then(Path::to_str) {
+        Some(dir) if !dir.is_empty() => self::defer(dir),
+        _ => self::defer("."),
+    }
+}
+
+/// Directories can't be synced on Windows, and [`replace`] already flushes the replacement
+#[cfg(windows)]
+fn defer_dir(_: &str) {}
+
+/// Replace the file at `to` with the file at `from`, such that `to` is either the old or the
+/// new file even if we crash midway
+#[cfg(not(windows))]
+pub fn replace(from: &str, to: &str) -> IoResult<()> {
+    std::fs::rename(from, to)
+}
+
+/// Replace the file at `to` with the file at `from`, such that `to` is either the old or the
+/// new file even if we crash midway
+#[cfg(windows)]
+pub fn replace(from: &str, to: &str) -> IoResult<()> {
+    windows::replace(from, to)?;
+    // make sure that the replacement itself hits the disk (flushing needs write access)
+    std::fs::OpenOptions::new().write(true).open(to)?.sync_all()
+}
+
+#[cfg(windows)]
+mod windows {
+    use {
+        crate::IoResult,
+        std::{ffi::OsStr, io::Error, iter, os::windows::ffi::OsStrExt, path::Path, ptr},
+        winapi::um::winbase::{
+            MoveFileExW, ReplaceFileW, MOVEFILE_REPLACE_EXISTING, MOVEFILE_WRITE_THROUGH,
+        },
+    };
+
+    fn to_wide(path: &str) -> Vec<u16> {
+        OsStr::new(path)
+            .encode_wide()
+            .chain(iter::once(0))
+            .collect()
+    }
+
+    pub fn replace(from: &str, to: &str) -> IoResult<()> {
+        let (from_w, to_w) = (to_wide(from), to_wide(to));
+        let ret = unsafe {
+            // UNSAFE(@ohsayan): Both paths are NUL-terminated wide strings that outlive the call
+            if Path::new(to).exists() {
+                // ReplaceFileW keeps the attributes of the replaced file and never leaves
+                // us without a file at `to`
+                ReplaceFileW(
+                    to_w.as_ptr(),   // the file to replace
+                    from_w.as_ptr(), // the replacement
+                    ptr::null(),     // no backup
+                    0,               // flags
+                    ptr::null_mut(), // reserved
+                    ptr::null_mut(), // reserved
+                )
+            } else {
+                // there's nothing to replace (the first flush), so simply move it in place
+                MoveFileExW(
+                    from_w.as_ptr(),
+                    to_w.as_ptr(),
+                    MOVEFILE_REPLACE_EXISTING | MOVEFILE_WRITE_THROUGH,
+                )
+            }
+        };
+        if ret == 0 {
+            Err(Error::last_os_error())
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Sync the data of a file that was written in place (found at `path`) according to the
+/// sync policy
+pub fn sync_data(file: &File, path: &str) -> IoResult<()> {
+    match self::policy() {
+        SyncPolicy::Always => file.sync_data(),
+        SyncPolicy::EverySec => {
+            self::defer(path);
+            Ok(())
+        }
+        SyncPolicy::Os => Ok(()),
+    }
+}
+
+/// Sync all the files (and directories) that are waiting to be synced. Files that have been
+/// removed since are skipped, and files that fail to sync are queued again
+pub fn sync_pending() -> IoResult<()> {
+    let mut pending = std::mem::take(&mut *PENDING.lock().unwrap_or_else(|e| e.into_inner()));
+    pending.sort_unstable();
+    pending.dedup();
+    let mut ret = Ok(());
+    let mut failed = Vec::new();
+    for path in pending {
+        match File::open(&path).and_then(|file| file.sync_all()) {
+            Ok(()) => {}
+            Err(e) if e.kind() == ErrorKind::NotFound => {}
+            Err(e) => {
+                log::error!("Failed to sync `{path}`: {e}");
+                failed.push(path);
+                if ret.is_ok() {
+                    ret = Err(e);
+                }
+            }
+        }
+    }
+    if !failed.is_empty() {
+        PENDING
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .extend(failed);
+    }
+    ret
+}
@@ -30,9 +30,15 @@ use std::collections::HashMap;
 
 use {
     crate::{
-        corestore::memstore::Memstore,
+        corestore::memstore::{Keyspace, Memstore, ObjectID},
         registry,
-        storage::v1::flush::{FlushableKeyspace, FlushableTable, StorageTarget},
+        storage::{
+            v1::{
+                checksum::ChecksummedWriter,
+                flush::{self, Autoflush, FlushableKeyspace, FlushableTable, StorageTarget},
+            },
+            v2::header::{FileKind, Header, ModelDescriptor},
+        },
         IoResult,
     },
     core::ops::Deref,
@@ -40,6 +46,7 @@ use {
         collections::HashSet,
         fs,
         io::{BufWriter, Write},
+        path::Path,
     },
 };
 
@@ -47,7 +54,11 @@ pub const DIR_KSROOT: &str = "data/ks";
 pub const DIR_SNAPROOT: &str = "data/snaps";
 pub const DIR_RSNAPROOT: &str = "data/rsnap";
 pub const DIR_BACKUPS: &str = "data/backups";
+pub const DIR_EXPORTS: &str = "data/exports";
+pub const DIR_IMPORTS: &str = "data/imports";
+pub const DIR_SPACES: &str = "data/spaces";
 pub const DIR_ROOT: &str = "data";
+pub const DIR_REPAIRROOT: &str = "data/repair";
 
 /// Creates the directories for the keyspaces
 pub fn create_tree<T: StorageTarget>(target: &T, memroot: &Memstore) -> IoResult<()> {
@@ -82,6 +93,67 @@ pub fn create_tree_fresh<T: StorageTarget>(target: &T, memroot: &Memstore) -> Io
     self::create_tree(target, memroot)
 }
 
+/// Move the table `old` of the keyspace `ksid` on disk over to `new`, once it has been
+/// renamed in memory. The table file is linked under its new name before the `PARTMAP`
+/// is rewritten and the old name is only unlinked after that, so a crash at any point
+/// leaves behind a `PARTMAP` whose tables are all present on disk.
+///
+/// The caller must hold the flush lock
+pub fn rename_table(
+    ksid: &ObjectID,
+    keyspace: &Keyspace,
+    old: &ObjectID,
+    new: &ObjectID,
+) -> IoResult<()> {
+    let ks_path = concat_str!(DIR_KSROOT, "/", unsafe { ksid.as_str() });
+    if !Path::new(&ks_path).is_dir() {
+        // never flushed; the next BGSAVE will take care of it
+        return Ok(());
+    }
+    let (old_path, new_path) = unsafe {
+        (
+            concat_path!(&ks_path, old.as_str()),
+            concat_path!(&ks_path, new.as_str()),
+        )
+    };
+    let has_file = old_path.is_file();
+    if has_file {
+        // a leftover from a dropped table that hasn't been cleaned up yet
+        if new_path.is_file() {
+            fs::remove_file(&new_path)?;
+        }
+        fs::hard_link(&old_path, &new_path)?;
+    }
+    flush::oneshot::flush_partmap(&Autoflush, ksid, keyspace)?;
+    // the shard count of the table is recorded under its name
+    flush::oneshot::flush_ksmeta(&Autoflush, ksid, keyspace)?;
+    if has_file {
+        fs::remove_file(&old_path)?;
+    }
+    Ok(())
+}
+
+/// Move the directory of the keyspace `old` over to `new`, once it has been renamed in
+/// memory, and rewrite the `PRELOAD` so that it refers to the keyspace by its new name
+///
+/// The caller must hold the flush lock
+pub fn rename_keyspace(memroot: &Memstore, old: &ObjectID, new: &ObjectID) -> IoResult<()> {
+    let (old_path, new_path) = unsafe {
+        (
+            concat_str!(DIR_KSROOT, "/", old.as_str()),
+            concat_str!(DIR_KSROOT, "/", new.as_str()),
+        )
+    };
+    if Path::new(&old_path).is_dir() {
+        if Path::new(&new_path).is_dir() {
+            // a leftover from a dropped keyspace that hasn't been cleaned up yet
+            fs::remove_dir_all(&new_path)?;
+        }
+        fs::rename(&old_path, &new_path)?;
+    }
+    flush::oneshot::flush_preload(&Autoflush, memroot)
+}
+
 /// Clean up the tree
 ///
 /// **Warning**: Calling this is quite inefficient so consider calling it once or twice
@@ -91,10 +163,11 @@ pub fn cleanup_tree(memroot: &Memstore) -> IoResult<()> {
         log::info!("We're cleaning up ...");
         // only run a cleanup if someone tripped the switch
         // hashset because the fs itself will not allow duplicate entries
-        // the keyspaces directory will contain the PRELOAD file, but we'll just
-        // remove it from the list
+        // the keyspaces directory will contain the PRELOAD file (and its generations), but
+        // we'll just remove them from the list
         let mut dir_keyspaces: HashSet<String> = read_dir_to_col!(DIR_KSROOT);
         dir_keyspaces.remove("PRELOAD");
+        dir_keyspaces.retain(|name| super::preload::generation_of(name).is_none());
         let our_keyspaces: HashMap<String, HashSet<String>> = memroot
             .keyspaces
             .iter()
@@ -132,9 +205,10 @@ pub fn cleanup_tree(memroot: &Memstore) -> IoResult<()> {
             let ks_path = concat_str!(DIR_KSROOT, "/", keyspace.as_str());
             // read what is present in the tables directory
             let mut dir_tbls: HashSet<String> = read_dir_to_col!(&ks_path);
-            // in the list of directories we collected, remove PARTMAP because we should NOT
-            // delete it
+            // in the list of directories we collected, remove PARTMAP and KSMETA because we
+            // should NOT delete them
             dir_tbls.remove("PARTMAP");
+            dir_tbls.remove("KSMETA");
             // find what tables we should remove
             let tables_to_remove = dir_tbls.difference(&tables);
             for removed_table in tables_to_remove {
@@ -154,6 +228,8 @@ pub fn serialize_table_into_slow_buffer<T: Write, U: FlushableTable>(
     writable_item: &U,
 ) -> IoResult<()> {
     let mut buffer = BufWriter::new(buffer);
+    buffer.write_all(&writable_item.file_header().encode())?;
+    let mut buffer = ChecksummedWriter::new(buffer);
     writable_item.write_table_to(&mut buffer)?;
     buffer.flush()?;
     Ok(())
@@ -167,11 +243,25 @@ where
     K: FlushableKeyspace<Tbl, U>,
 {
     let mut buffer = BufWriter::new(buffer);
+    buffer.write_all(&Header::new(FileKind::Partmap, ModelDescriptor::NONE).encode())?;
     super::se::raw_serialize_partmap(&mut buffer, ks)?;
     buffer.flush()?;
     Ok(())
 }
 
+pub fn serialize_ksmeta_into_slow_buffer<T, U, Tbl, K>(buffer: &mut T, ks: &K) -> IoResult<()>
+where
+    T: Write,
+    U: Deref<Target = Tbl>,
+    Tbl: FlushableTable,
+    K: FlushableKeyspace<Tbl, U>,
+{
+    let mut buffer = BufWriter::new(buffer);
+    super::preload::raw_generate_ksmeta(&mut buffer, &ks.ksmeta())?;
+    buffer.flush()?;
+    Ok(())
+}
+
 pub fn serialize_preload_into_slow_buffer<T: Write>(
     buffer: &mut T,
     store: &Memstore,
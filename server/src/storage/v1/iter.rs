@@ -36,6 +36,7 @@
 use {
     crate::storage::v1::SharedSlice,
     core::{mem, ptr, slice},
+    std::borrow::Cow,
 };
 
 const SIZE_64BIT: usize = mem::size_of::<u64>();
@@ -63,33 +64,52 @@ pub(super) unsafe fn endian_set_big() {
 }
 
 /// A raw slice iterator by using raw pointers
+///
+/// The bytes can also be split across several slices, like the segments of a checksummed file
+/// (see [`RawSliceIter::segmented`]). A read that straddles two slices is stitched together into
+/// an owned buffer, so the slices never have to be copied into a contiguous buffer up front
 #[derive(Debug)]
 pub struct RawSliceIter<'a> {
     _base: &'a [u8],
     cursor: *const u8,
     terminal: *const u8,
+    /// the slices after the current one
+    rest: &'a [&'a [u8]],
+    /// the number of bytes in `rest`
+    rest_len: usize,
+    /// the number of bytes in the slices before the current one
+    consumed: usize,
 }
 
 impl<'a> RawSliceIter<'a> {
     /// Create a new slice iterator
     pub fn new(slice: &'a [u8]) -> Self {
+        Self::with_rest(slice, &[])
+    }
+    /// Create an iterator over the bytes of all the `segments`, one after the other
+    pub fn segmented(segments: &'a [&'a [u8]]) -> Self {
+        match segments.split_first() {
+            Some((first, rest)) => Self::with_rest(first, rest),
+            None => Self::new(&[]),
+        }
+    }
+    fn with_rest(slice: &'a [u8], rest: &'a [&'a [u8]]) -> Self {
         Self {
             cursor: slice.as_ptr(),
             terminal: unsafe { slice.as_ptr().add(slice.len()) },
             _base: slice,
+            rest,
+            rest_len: rest.iter().map(|slice| slice.len()).sum(),
+            consumed: 0,
         }
     }
-    /// Check the number of remaining bytes in the buffer
+    /// Check the number of remaining bytes in the current slice
     fn remaining(&self) -> usize {
         unsafe { self.terminal.offset_from(self.cursor) as usize }
     }
-    /// Increment the cursor by one
-    unsafe fn incr_cursor(&mut self) {
-        self.incr_cursor_by(1)
-    }
-    /// Check if the buffer was exhausted
-    fn exhausted(&self) -> bool {
-        self.cursor > self.terminal
+    /// Check the number of remaining bytes in all the slices
+    fn remaining_total(&self) -> usize {
+        self.remaining() + self.rest_len
     }
     /// Increment the cursor by the provided length
     unsafe fn incr_cursor_by(&mut self, ahead: usize) {
@@ -97,141 +117,99 @@ impl<'a> RawSliceIter<'a> {
             self.cursor = self.cursor.add(ahead)
         }
     }
-    /// Get the next 64-bit integer, casting it to an `usize`, respecting endianness
-    pub fn next_64bit_integer_to_usize(&mut self) -> Option<usize> {
-        if self.remaining() < 8 {
-            // we need 8 bytes to read a 64-bit integer, so nope
-            None
-        } else {
-            unsafe {
-                // sweet, something is left
-                let l = NATIVE_ENDIAN_READER(self.cursor);
-                // now forward the cursor
-                self.incr_cursor_by(SIZE_64BIT);
-                Some(l)
+    /// Move the cursor to the start of the next slice. Returns false if there is none
+    fn advance(&mut self) -> bool {
+        match self.rest.split_first() {
+            Some((next, rest)) => {
+                self.consumed += self._base.len();
+                self.rest_len -= next.len();
+                self.rest = rest;
+                self._base = next;
+                self.cursor = next.as_ptr();
+                self.terminal = unsafe { next.as_ptr().add(next.len()) };
+                true
             }
+            None => false,
         }
     }
-    /// Get a borrowed slice for the given length. The lifetime is important!
-    pub fn next_borrowed_slice(&mut self, len: usize) -> Option<&'a [u8]> {
-        if self.remaining() < len {
-            None
-        } else {
+    /// Get the next `len` bytes. They're borrowed unless they straddle two slices
+    pub fn next_slice(&mut self, len: usize) -> Option<Cow<'a, [u8]>> {
+        while len != 0 && self.remaining() == 0 && self.advance() {}
+        if self.remaining() >= len {
             unsafe {
                 let d = slice::from_raw_parts(self.cursor, len);
                 self.incr_cursor_by(len);
-                Some(d)
+                return Some(Cow::Borrowed(d));
             }
         }
+        if self.remaining_total() < len {
+            return None;
+        }
+        let mut stitched = Vec::with_capacity(len);
+        loop {
+            let take = (len - stitched.len()).min(self.remaining());
+            unsafe {
+                stitched.extend_from_slice(slice::from_raw_parts(self.cursor, take));
+                self.incr_cursor_by(take);
+            }
+            if stitched.len() == len {
+                break Some(Cow::Owned(stitched));
+            }
+            // we've checked that there are enough bytes left
+            self.advance();
+        }
+    }
+    /// Get the next 64-bit integer, casting it to an `usize`, respecting endianness
+    pub fn next_64bit_integer_to_usize(&mut self) -> Option<usize> {
+        // we need 8 bytes to read a 64-bit integer
+        let bytes = self.next_slice(SIZE_64BIT)?;
+        unsafe { Some(NATIVE_ENDIAN_READER(bytes.as_ptr())) }
     }
     /// Get the next 64-bit usize
     pub fn next_64bit_integer_pair_to_usize(&mut self) -> Option<(usize, usize)> {
-        if self.remaining() < SIZE_128BIT {
+        if self.remaining_total() < SIZE_128BIT {
             None
         } else {
-            unsafe {
-                let v1 = NATIVE_ENDIAN_READER(self.cursor);
-                self.incr_cursor_by(SIZE_64BIT);
-                let v2 = NATIVE_ENDIAN_READER(self.cursor);
-                self.incr_cursor_by(SIZE_64BIT);
-                Some((v1, v2))
-            }
+            let v1 = self.next_64bit_integer_to_usize()?;
+            let v2 = self.next_64bit_integer_to_usize()?;
+            Some((v1, v2))
         }
     }
+    /// Returns the next 8 bytes as the bits of an `f64` (scores are written with the host's
+    /// byte order)
+    pub fn next_64bit_float(&mut self) -> Option<f64> {
+        let bytes = self.next_slice(SIZE_64BIT)?;
+        let bits: u64 = unsafe { ptr::read_unaligned(bytes.as_ptr().cast()) };
+        Some(f64::from_bits(bits))
+    }
     /// Get the next owned [`Data`] with the provided length
     pub fn next_owned_data(&mut self, len: usize) -> Option<SharedSlice> {
-        if self.remaining() < len {
-            // not enough left
-            None
-        } else {
-            // we have something to look at
-            unsafe {
-                let d = slice::from_raw_parts(self.cursor, len);
-                let d = Some(SharedSlice::new(d));
-                self.incr_cursor_by(len);
-                d
-            }
+        match self.next_slice(len)? {
+            Cow::Borrowed(d) => Some(SharedSlice::new(d)),
+            Cow::Owned(d) => Some(SharedSlice::from(d)),
         }
     }
     /// Get the next 8-bit unsigned integer
     pub fn next_8bit_integer(&mut self) -> Option<u8> {
-        if self.exhausted() {
-            None
+        self.next_slice(1).map(|byte| byte[0])
+    }
+    /// Returns the next 8-bit unsigned integer without moving past it
+    pub fn peek_8bit_integer(&self) -> Option<u8> {
+        if self.remaining() == 0 {
+            self.rest
+                .iter()
+                .find(|slice| !slice.is_empty())
+                .map(|slice| slice[0])
         } else {
-            unsafe {
-                let x = ptr::read(self.cursor);
-                self.incr_cursor();
-                Some(x)
-            }
+            unsafe { Some(ptr::read(self.cursor)) }
         }
     }
+    /// Returns the offset of the cursor from the start of the bytes
+    pub fn position(&self) -> usize {
+        self.consumed + unsafe { self.cursor.offset_from(self._base.as_ptr()) as usize }
+    }
     /// Check if the cursor has reached end-of-allocation
     pub fn end_of_allocation(&self) -> bool {
-        self.cursor == self.terminal
-    }
-    /// Get a borrowed iterator. This is super safe, funny enough, because of the lifetime
-    /// bound that we add to the iterator object
-    pub fn get_borrowed_iter(&mut self) -> RawSliceIterBorrowed<'_> {
-        RawSliceIterBorrowed::new(self.cursor, self.terminal, &mut self.cursor)
-    }
-}
-
-#[derive(Debug)]
-pub struct RawSliceIterBorrowed<'a> {
-    cursor: *const u8,
-    end_ptr: *const u8,
-    mut_ptr: &'a mut *const u8,
-}
-
-impl<'a> RawSliceIterBorrowed<'a> {
-    fn new(
-        cursor: *const u8,
-        end_ptr: *const u8,
-        mut_ptr: &'a mut *const u8,
-    ) -> RawSliceIterBorrowed<'a> {
-        Self {
-            cursor,
-            end_ptr,
-            mut_ptr,
-        }
-    }
-    /// Check the number of remaining bytes in the buffer
-    fn remaining(&self) -> usize {
-        unsafe { self.end_ptr.offset_from(self.cursor) as usize }
-    }
-    /// Increment the cursor by the provided length
-    unsafe fn incr_cursor_by(&mut self, ahead: usize) {
-        {
-            self.cursor = self.cursor.add(ahead)
-        }
-    }
-    pub fn next_64bit_integer_to_usize(&mut self) -> Option<usize> {
-        if self.remaining() < 8 {
-            None
-        } else {
-            unsafe {
-                let size = NATIVE_ENDIAN_READER(self.cursor);
-                self.incr_cursor_by(SIZE_64BIT);
-                Some(size)
-            }
-        }
-    }
-    pub fn next_owned_data(&mut self, len: usize) -> Option<SharedSlice> {
-        if self.remaining() < len {
-            None
-        } else {
-            unsafe {
-                let d = slice::from_raw_parts(self.cursor, len);
-                let d = Some(SharedSlice::new(d));
-                self.incr_cursor_by(len);
-                d
-            }
-        }
-    }
-}
-
-impl<'a> Drop for RawSliceIterBorrowed<'a> {
-    fn drop(&mut self) {
-        *self.mut_ptr = self.cursor;
+        self.remaining_total() == 0
     }
 }
@@ -0,0 +1,160 @@
+/*
+ * Created on Sat Oct 29 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Memory-mapped files
+//!
+//! Table files are loaded through a read-only memory map instead of being read into a buffer.
+//! Files are parsed straight out of the mapped region, so the only copy that we make is the one
+//! that ends up in the table. For checksummed files, every segment is verified in place and the
+//! deserializer reads across the segments (only an entry that straddles two segments is
+//! stitched together). Since the mapped pages are backed by the file, the kernel can reclaim
+//! them under memory pressure instead of us holding a copy of the file in anonymous memory.
+//!
+//! On platforms without `mmap` we fall back to reading the file into memory.
+
+use {
+    crate::IoResult,
+    core::ops::Deref,
+    std::{fs::File, path::Path},
+};
+
+/// A read-only view into the contents of a file
+///
+/// **Warning:** the file must not be truncated while it is mapped. This holds for table files
+/// since they are only ever replaced by renaming a new file over them
+pub struct MappedFile {
+    map: __sys::Map,
+}
+
+impl MappedFile {
+    /// Map the file at `path`
+    pub fn open(path: impl AsRef<Path>) -> IoResult<Self> {
+        let file = File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+        Ok(Self {
+            map: __sys::Map::new(&file, len)?,
+        })
+    }
+}
+
+impl Deref for MappedFile {
+    type Target = [u8];
+    fn deref(&self) -> &[u8] {
+        self.map.as_slice()
+    }
+}
+
+#[cfg(unix)]
+mod __sys {
+    use {
+        crate::IoResult,
+        core::{ptr::NonNull, slice},
+        std::{fs::File, io::Error, os::unix::io::AsRawFd},
+    };
+
+    pub struct Map {
+        ptr: NonNull<u8>,
+        len: usize,
+    }
+
+    // UNSAFE(@ohsayan): The mapping is read-only and owned by us, so it's just like a `Box<[u8]>`
+    unsafe impl Send for Map {}
+    unsafe impl Sync for Map {}
+
+    impl Map {
+        pub fn new(file: &File, len: usize) -> IoResult<Self> {
+            if len == 0 {
+                // mmap will refuse to map an empty file
+                return Ok(Self {
+                    ptr: NonNull::dangling(),
+                    len,
+                });
+            }
+            unsafe {
+                // UNSAFE(@ohsayan): We've validated the length and the fd is owned by `file`
+                let ptr = libc::mmap(
+                    core::ptr::null_mut(),
+                    len,
+                    libc::PROT_READ,
+                    libc::MAP_PRIVATE,
+                    file.as_raw_fd(),
+                    0,
+                );
+                if ptr == libc::MAP_FAILED {
+                    return Err(Error::last_os_error());
+                }
+                // we read the file front to back, so ask for aggressive readahead. this is just
+                // a hint, so ignore failures
+                libc::madvise(ptr, len, libc::MADV_SEQUENTIAL);
+                Ok(Self {
+                    ptr: NonNull::new_unchecked(ptr as *mut u8),
+                    len,
+                })
+            }
+        }
+        pub fn as_slice(&self) -> &[u8] {
+            unsafe {
+                // UNSAFE(@ohsayan): The mapping (or the dangling pointer for empty files) is
+                // valid for `len` bytes for as long as we're alive
+                slice::from_raw_parts(self.ptr.as_ptr(), self.len)
+            }
+        }
+    }
+
+    impl Drop for Map {
+        fn drop(&mut self) {
+            if self.len != 0 {
+                unsafe {
+                    // UNSAFE(@ohsayan): We created this mapping and no one else can unmap it
+                    libc::munmap(self.ptr.as_ptr() as *mut libc::c_void, self.len);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod __sys {
+    use {
+        crate::IoResult,
+        std::{fs::File, io::Read},
+    };
+
+    pub struct Map {
+        data: Vec<u8>,
+    }
+
+    impl Map {
+        pub fn new(mut file: &File, len: usize) -> IoResult<Self> {
+            let mut data = Vec::with_capacity(len);
+            file.read_to_end(&mut data)?;
+            Ok(Self { data })
+        }
+        pub fn as_slice(&self) -> &[u8] {
+            &self.data
+        }
+    }
+}
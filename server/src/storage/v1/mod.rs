@@ -54,20 +54,32 @@ think of using them anywhere outside. This is a specialized parser built for the
 use {
     crate::corestore::{array::Array, htable::Coremap, SharedSlice},
     core::{hash::Hash, mem, slice},
-    std::{collections::HashSet, io::Write},
+    std::{
+        collections::{HashMap, HashSet},
+        io::Write,
+    },
 };
 
 // for some astronomical reasons do not mess with this
 #[macro_use]
 mod macros;
 // endof do not mess
+pub mod backup;
 pub mod bytemarks;
+pub mod checksum;
 pub mod error;
 pub mod flush;
+pub mod fsync;
 pub mod interface;
 pub mod iter;
+pub mod mmap;
 pub mod preload;
+pub mod repair;
 pub mod sengine;
+pub mod snapname;
+pub mod spacearchive;
+pub mod stats;
+pub mod stream;
 pub mod unflush;
 // test
 #[cfg(test)]
@@ -184,7 +196,10 @@ unsafe fn raw_byte_repr<'a, T: 'a>(len: &'a T) -> &'a [u8] {
 
 mod se {
     use super::*;
-    use crate::kvengine::LockedVec;
+    use crate::kvengine::{
+        archive::FrozenArchive, bounds::ListBounds, expiry::ExpiryIndex, LockedDocument, LockedMap,
+        LockedSet, LockedSortedSet, LockedVec,
+    };
     use crate::storage::v1::flush::FlushableKeyspace;
     use crate::storage::v1::flush::FlushableTable;
     use crate::IoResult;
@@ -234,6 +249,76 @@ mod se {
         Ok(())
     }
 
+    /// Serialize a map along with its archived entries and write it to a provided buffer.
+    /// The archived entries are written after the in-memory ones, using the same layout
+    /// as [`raw_serialize_map`]
+    pub fn raw_serialize_archived_map<W: Write>(
+        map: &Coremap<SharedSlice, SharedSlice>,
+        archive: &mut FrozenArchive,
+        w: &mut W,
+    ) -> IoResult<()> {
+        unsafe {
+            w.write_all(raw_byte_repr(&to_64bit_native_endian!(
+                map.len() + archive.len()
+            )))?;
+            // the values that are faulted in while we write are written from the archive
+            for kv in map.iter().filter(|kv| !archive.contains(kv.key())) {
+                let (k, v) = (kv.key(), kv.value());
+                w.write_all(raw_byte_repr(&to_64bit_native_endian!(k.len())))?;
+                w.write_all(raw_byte_repr(&to_64bit_native_endian!(v.len())))?;
+                w.write_all(k)?;
+                w.write_all(v)?;
+            }
+            archive.for_each(|k, v| {
+                w.write_all(raw_byte_repr(&to_64bit_native_endian!(k.len())))?;
+                w.write_all(raw_byte_repr(&to_64bit_native_endian!(v.len())))?;
+                w.write_all(k)?;
+                w.write_all(v)
+            })
+        }
+    }
+
+    /// Write the key expiry section for the given deadlines (see
+    /// [`BYTEMARK_SECTION_EXPIRY`](super::bytemarks::BYTEMARK_SECTION_EXPIRY)). Nothing is
+    /// written if no key has a deadline, so that such tables are written just as before
+    pub fn raw_serialize_expiry<W: Write>(expiry: &ExpiryIndex, w: &mut W) -> IoResult<()> {
+        if expiry.is_empty() {
+            return Ok(());
+        }
+        let bytemark = super::bytemarks::BYTEMARK_SECTION_EXPIRY;
+        self::raw_serialize_key_section(bytemark, expiry.deadlines(), w)
+    }
+
+    /// Write the list bounds section for the given bounds (see
+    /// [`BYTEMARK_SECTION_LIST_BOUNDS`](super::bytemarks::BYTEMARK_SECTION_LIST_BOUNDS)).
+    /// Nothing is written if no list has a bound
+    pub fn raw_serialize_list_bounds<W: Write>(bounds: &ListBounds, w: &mut W) -> IoResult<()> {
+        if bounds.is_empty() {
+            return Ok(());
+        }
+        let bytemark = super::bytemarks::BYTEMARK_SECTION_LIST_BOUNDS;
+        self::raw_serialize_key_section(bytemark, bounds.bounds(), w)
+    }
+
+    /// Write a section that maps keys to 64-bit integers, starting with `bytemark`
+    fn raw_serialize_key_section<W: Write>(
+        bytemark: u8,
+        section: &Coremap<SharedSlice, u64>,
+        w: &mut W,
+    ) -> IoResult<()> {
+        unsafe {
+            w.write_all(&[bytemark])?;
+            w.write_all(unsafe_sz_byte_repr!(section.len()))?;
+            for kv in section.iter() {
+                let (k, v) = (kv.key(), *kv.value());
+                w.write_all(unsafe_sz_byte_repr!(k.len()))?;
+                w.write_all(k)?;
+                w.write_all(unsafe_sz_byte_repr!(v))?;
+            }
+        }
+        Ok(())
+    }
+
     /// Serialize a set and write it to a provided buffer
     pub fn raw_serialize_set<W, K, V>(map: &Coremap<K, V>, w: &mut W) -> IoResult<()>
     where
@@ -311,6 +396,153 @@ mod se {
         }
         Ok(())
     }
+    pub fn raw_serialize_map_map<W>(
+        data: &Coremap<SharedSlice, LockedMap>,
+        w: &mut W,
+    ) -> IoResult<()>
+    where
+        W: Write,
+    {
+        /*
+        [8B: Extent]([8B: Key extent][?B: Key][8B: Field count]([8B: Field extent][?B: Field][8B: Value extent][?B: Value])*)*
+        */
+        unsafe {
+            // Extent
+            w.write_all(unsafe_sz_byte_repr!(data.len()))?;
+            // Enter iter
+            '_1: for key in data.iter() {
+                // key
+                let k = key.key();
+                // write the key extent
+                w.write_all(unsafe_sz_byte_repr!(k.len()))?;
+                // write the key
+                w.write_all(k)?;
+                // write the map payload
+                self::raw_serialize_nested_map(w, &key.value().read())?;
+            }
+        }
+        Ok(())
+    }
+    pub fn raw_serialize_set_map<W>(
+        data: &Coremap<SharedSlice, LockedSet>,
+        w: &mut W,
+    ) -> IoResult<()>
+    where
+        W: Write,
+    {
+        /*
+        [8B: Extent]([8B: Key extent][?B: Key][8B: Member count]([8B: Member extent][?B: Member])*)*
+        (a set is written just like a list)
+        */
+        unsafe {
+            // Extent
+            w.write_all(unsafe_sz_byte_repr!(data.len()))?;
+            // Enter iter
+            '_1: for key in data.iter() {
+                // key
+                let k = key.key();
+                // write the key extent
+                w.write_all(unsafe_sz_byte_repr!(k.len()))?;
+                // write the key
+                w.write_all(k)?;
+                // write the members
+                let members = key.value().read();
+                w.write_all(unsafe_sz_byte_repr!(members.len()))?;
+                for member in members.iter() {
+                    w.write_all(unsafe_sz_byte_repr!(member.len()))?;
+                    w.write_all(member)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    pub fn raw_serialize_sorted_set_map<W>(
+        data: &Coremap<SharedSlice, LockedSortedSet>,
+        w: &mut W,
+    ) -> IoResult<()>
+    where
+        W: Write,
+    {
+        /*
+        [8B: Extent]([8B: Key extent][?B: Key][8B: Member count]([8B: Score][8B: Member extent][?B: Member])*)*
+        (the members are written in order)
+        */
+        unsafe {
+            // Extent
+            w.write_all(unsafe_sz_byte_repr!(data.len()))?;
+            // Enter iter
+            '_1: for key in data.iter() {
+                // key
+                let k = key.key();
+                // write the key extent
+                w.write_all(unsafe_sz_byte_repr!(k.len()))?;
+                // write the key
+                w.write_all(k)?;
+                // write the members with their scores
+                let zset = key.value().read();
+                w.write_all(unsafe_sz_byte_repr!(zset.len()))?;
+                for (member, score) in zset.iter() {
+                    w.write_all(raw_byte_repr(&score.to_bits()))?;
+                    w.write_all(unsafe_sz_byte_repr!(member.len()))?;
+                    w.write_all(member)?;
+                }
+            }
+        }
+        Ok(())
+    }
+    pub fn raw_serialize_document_map<W>(
+        data: &Coremap<SharedSlice, LockedDocument>,
+        w: &mut W,
+    ) -> IoResult<()>
+    where
+        W: Write,
+    {
+        /*
+        [8B: Extent]([8B: Key extent][?B: Key][8B: Document extent][?B: Encoded document])*
+        */
+        let mut doc = Vec::new();
+        unsafe {
+            // Extent
+            w.write_all(unsafe_sz_byte_repr!(data.len()))?;
+            // Enter iter
+            '_1: for key in data.iter() {
+                // key
+                let k = key.key();
+                // write the key extent
+                w.write_all(unsafe_sz_byte_repr!(k.len()))?;
+                // write the key
+                w.write_all(k)?;
+                // write the encoded document
+                doc.clear();
+                key.value().read().encode(&mut doc);
+                w.write_all(unsafe_sz_byte_repr!(doc.len()))?;
+                w.write_all(&doc)?;
+            }
+        }
+        Ok(())
+    }
+    /// Serialize a map of byte slices: `[8B: Field count]([8B: Field extent][?B: Field][8B:
+    /// Value extent][?B: Value])*`
+    pub fn raw_serialize_nested_map<W>(
+        w: &mut W,
+        inp: &HashMap<SharedSlice, SharedSlice>,
+    ) -> IoResult<()>
+    where
+        W: Write,
+    {
+        unsafe {
+            // write extent
+            w.write_all(unsafe_sz_byte_repr!(inp.len()))?;
+            // now enter loop and write the fields with their values
+            for (field, value) in inp.iter() {
+                w.write_all(unsafe_sz_byte_repr!(field.len()))?;
+                w.write_all(field)?;
+                w.write_all(unsafe_sz_byte_repr!(value.len()))?;
+                w.write_all(value)?;
+            }
+        }
+        Ok(())
+    }
     /// Serialize a `[[u8]]` (i.e a slice of slices)
     pub fn raw_serialize_nested_list<'a, W, T: 'a + ?Sized, U: 'a>(
         w: &mut W,
@@ -340,12 +572,15 @@ mod se {
 }
 
 mod de {
-    use super::iter::{RawSliceIter, RawSliceIterBorrowed};
-    use super::{Array, Coremap, Hash, HashSet, SharedSlice};
-    use crate::kvengine::LockedVec;
+    use super::iter::RawSliceIter;
+    use super::{Array, Coremap, Hash, HashMap, HashSet, SharedSlice};
+    use crate::corestore::memstore::ObjectID;
+    use crate::kvengine::{
+        document::Json, sortedset::SortedSet, LockedDocument, LockedMap, LockedSet,
+        LockedSortedSet, LockedVec,
+    };
     use core::ptr;
     use parking_lot::RwLock;
-    use std::collections::HashMap;
 
     pub trait DeserializeFrom {
         fn is_expected_len(clen: usize) -> bool;
@@ -354,24 +589,79 @@ mod de {
 
     pub trait DeserializeInto: Sized {
         fn new_empty() -> Self;
-        fn from_slice(slice: &[u8]) -> Option<Self>;
+        fn from_iter(rawiter: RawSliceIter<'_>) -> Option<Self>;
     }
 
+    /// The entries of a table along with the deadlines of its expiring keys
+    pub type WithExpiry<T> = (Coremap<SharedSlice, T>, Coremap<SharedSlice, u64>);
+    /// The entries of a list table along with the deadlines of its expiring keys and the bounds
+    /// of its lists
+    pub type ListsWithMeta = (
+        Coremap<SharedSlice, LockedVec>,
+        Coremap<SharedSlice, u64>,
+        Coremap<SharedSlice, u64>,
+    );
+
     impl DeserializeInto for Coremap<SharedSlice, SharedSlice> {
         fn new_empty() -> Self {
             Coremap::new()
         }
-        fn from_slice(slice: &[u8]) -> Option<Self> {
-            self::deserialize_map(slice)
+        fn from_iter(rawiter: RawSliceIter<'_>) -> Option<Self> {
+            self::deserialize_map_with_expiry(rawiter).map(|(map, _)| map)
         }
     }
 
-    impl DeserializeInto for Coremap<SharedSlice, LockedVec> {
+    impl DeserializeInto for WithExpiry<SharedSlice> {
         fn new_empty() -> Self {
-            Coremap::new()
+            (Coremap::new(), Coremap::new())
+        }
+        fn from_iter(rawiter: RawSliceIter<'_>) -> Option<Self> {
+            self::deserialize_map_with_expiry(rawiter)
+        }
+    }
+
+    impl DeserializeInto for ListsWithMeta {
+        fn new_empty() -> Self {
+            (Coremap::new(), Coremap::new(), Coremap::new())
+        }
+        fn from_iter(rawiter: RawSliceIter<'_>) -> Option<Self> {
+            self::deserialize_list_map_with_meta(rawiter)
+        }
+    }
+
+    impl DeserializeInto for WithExpiry<LockedMap> {
+        fn new_empty() -> Self {
+            (Coremap::new(), Coremap::new())
+        }
+        fn from_iter(rawiter: RawSliceIter<'_>) -> Option<Self> {
+            self::deserialize_map_map_with_expiry(rawiter)
+        }
+    }
+
+    impl DeserializeInto for WithExpiry<LockedSet> {
+        fn new_empty() -> Self {
+            (Coremap::new(), Coremap::new())
         }
-        fn from_slice(slice: &[u8]) -> Option<Self> {
-            self::deserialize_list_map(slice)
+        fn from_iter(rawiter: RawSliceIter<'_>) -> Option<Self> {
+            self::deserialize_set_map_with_expiry(rawiter)
+        }
+    }
+
+    impl DeserializeInto for WithExpiry<LockedSortedSet> {
+        fn new_empty() -> Self {
+            (Coremap::new(), Coremap::new())
+        }
+        fn from_iter(rawiter: RawSliceIter<'_>) -> Option<Self> {
+            self::deserialize_sorted_set_map_with_expiry(rawiter)
+        }
+    }
+
+    impl DeserializeInto for WithExpiry<LockedDocument> {
+        fn new_empty() -> Self {
+            (Coremap::new(), Coremap::new())
+        }
+        fn from_iter(rawiter: RawSliceIter<'_>) -> Option<Self> {
+            self::deserialize_document_map_with_expiry(rawiter)
         }
     }
 
@@ -383,13 +673,18 @@ mod de {
         fn new_empty() -> Self {
             Coremap::new()
         }
-        fn from_slice(slice: &[u8]) -> Option<Self> {
-            self::deserialize_map_ctype(slice)
+        fn from_iter(rawiter: RawSliceIter<'_>) -> Option<Self> {
+            self::deserialize_map_ctype(rawiter)
         }
     }
 
     pub fn deserialize_into<T: DeserializeInto>(input: &[u8]) -> Option<T> {
-        T::from_slice(input)
+        T::from_iter(RawSliceIter::new(input))
+    }
+
+    /// Same as [`deserialize_into`], but for input that is split across `segments`
+    pub fn deserialize_segments_into<T: DeserializeInto>(segments: &[&[u8]]) -> Option<T> {
+        T::from_iter(RawSliceIter::segmented(segments))
     }
 
     impl<const N: usize> DeserializeFrom for Array<u8, N> {
@@ -401,6 +696,15 @@ mod de {
         }
     }
 
+    impl DeserializeFrom for ObjectID {
+        fn is_expected_len(clen: usize) -> bool {
+            clen <= ObjectID::MAX_LENGTH
+        }
+        fn from_slice(slice: &[u8]) -> Self {
+            unsafe { Self::from_slice(slice) }
+        }
+    }
+
     impl<const N: usize> DeserializeFrom for [u8; N] {
         fn is_expected_len(clen: usize) -> bool {
             clen == N
@@ -410,12 +714,11 @@ mod de {
         }
     }
 
-    pub fn deserialize_map_ctype<T, U>(data: &[u8]) -> Option<Coremap<T, U>>
+    pub fn deserialize_map_ctype<T, U>(mut rawiter: RawSliceIter<'_>) -> Option<Coremap<T, U>>
     where
         T: Eq + Hash + DeserializeFrom,
         U: DeserializeFrom,
     {
-        let mut rawiter = RawSliceIter::new(data);
         let len = rawiter.next_64bit_integer_to_usize()?;
         let map = Coremap::new();
         for _ in 0..len {
@@ -423,8 +726,8 @@ mod de {
             if !(T::is_expected_len(lenkey) && U::is_expected_len(lenval)) {
                 return None;
             }
-            let key = T::from_slice(rawiter.next_borrowed_slice(lenkey)?);
-            let value = U::from_slice(rawiter.next_borrowed_slice(lenval)?);
+            let key = T::from_slice(&rawiter.next_slice(lenkey)?);
+            let value = U::from_slice(&rawiter.next_slice(lenval)?);
             if !map.true_if_insert(key, value) {
                 // duplicates
                 return None;
@@ -448,7 +751,7 @@ mod de {
                 return None;
             }
             // get the key as a raw slice, we've already checked if end_ptr is less
-            let key = T::from_slice(rawiter.next_borrowed_slice(lenkey)?);
+            let key = T::from_slice(&rawiter.next_slice(lenkey)?);
             // push it in
             if !set.insert(key) {
                 // repeat?; that's not what we wanted
@@ -479,7 +782,7 @@ mod de {
                 return None;
             }
             // get the key as a raw slice, we've already checked if end_ptr is less
-            let key = T::from_slice(rawiter.next_borrowed_slice(lenkey)?);
+            let key = T::from_slice(&rawiter.next_slice(lenkey)?);
             let bytemark_a = rawiter.next_8bit_integer()?;
             let bytemark_b = rawiter.next_8bit_integer()?;
             // push it in
@@ -495,9 +798,18 @@ mod de {
             None
         }
     }
-    /// Deserialize a file that contains a serialized map. This also returns the model code
+    /// Deserialize a file that contains a serialized map. The deadlines of expiring keys (if
+    /// any) are discarded
+    #[cfg(test)]
     pub fn deserialize_map(data: &[u8]) -> Option<Coremap<SharedSlice, SharedSlice>> {
-        let mut rawiter = RawSliceIter::new(data);
+        self::deserialize_map_with_expiry(RawSliceIter::new(data)).map(|(map, _)| map)
+    }
+
+    /// Deserialize a file that contains a serialized map, along with the deadlines of its
+    /// expiring keys
+    pub fn deserialize_map_with_expiry(
+        mut rawiter: RawSliceIter<'_>,
+    ) -> Option<WithExpiry<SharedSlice>> {
         let len = rawiter.next_64bit_integer_to_usize()?;
         let hm = Coremap::try_with_capacity(len).ok()?;
         for _ in 0..len {
@@ -507,16 +819,61 @@ mod de {
             // push it in
             hm.upsert(key, val);
         }
+        let expiry = self::deserialize_expiry(&mut rawiter, &hm)?;
+        Some((hm, expiry))
+    }
+
+    /// Deserialize the (optional) key expiry section that follows the entries of a table. The
+    /// deadlines of keys that aren't in `map` are dropped
+    fn deserialize_expiry<T>(
+        rawiter: &mut RawSliceIter<'_>,
+        map: &Coremap<SharedSlice, T>,
+    ) -> Option<Coremap<SharedSlice, u64>> {
+        let bytemark = super::bytemarks::BYTEMARK_SECTION_EXPIRY;
+        let expiry = self::deserialize_key_section(rawiter, map, bytemark)?;
         if rawiter.end_of_allocation() {
-            Some(hm)
+            Some(expiry)
         } else {
             // nope, someone gave us more data
             None
         }
     }
 
+    /// Deserialize the section that maps keys to 64-bit integers and starts with `bytemark`,
+    /// if it's next (an empty section is returned if it isn't). The entries of keys that aren't
+    /// in `map` are dropped
+    fn deserialize_key_section<T>(
+        rawiter: &mut RawSliceIter<'_>,
+        map: &Coremap<SharedSlice, T>,
+        bytemark: u8,
+    ) -> Option<Coremap<SharedSlice, u64>> {
+        let section = Coremap::new();
+        if rawiter.peek_8bit_integer() != Some(bytemark) {
+            return Some(section);
+        }
+        rawiter.next_8bit_integer()?;
+        let len = rawiter.next_64bit_integer_to_usize()?;
+        for _ in 0..len {
+            let keylen = rawiter.next_64bit_integer_to_usize()?;
+            let key = rawiter.next_owned_data(keylen)?;
+            let value = rawiter.next_64bit_integer_to_usize()? as u64;
+            if map.contains_key(&key) {
+                section.upsert(key, value);
+            }
+        }
+        Some(section)
+    }
+
+    /// Deserialize a file that contains a serialized list map. The deadlines of expiring keys
+    /// and the bounds of lists (if any) are discarded
+    #[cfg(test)]
     pub fn deserialize_list_map(bytes: &[u8]) -> Option<Coremap<SharedSlice, LockedVec>> {
-        let mut rawiter = RawSliceIter::new(bytes);
+        self::deserialize_list_map_with_meta(RawSliceIter::new(bytes)).map(|(map, ..)| map)
+    }
+
+    /// Deserialize a file that contains a serialized list map, along with the deadlines of its
+    /// expiring keys and the bounds of its lists
+    pub fn deserialize_list_map_with_meta(mut rawiter: RawSliceIter<'_>) -> Option<ListsWithMeta> {
         // get the len
         let len = rawiter.next_64bit_integer_to_usize()?;
         // allocate a map
@@ -526,22 +883,179 @@ mod de {
             let keylen = rawiter.next_64bit_integer_to_usize()?;
             // get key
             let key = rawiter.next_owned_data(keylen)?;
-            let borrowed_iter = rawiter.get_borrowed_iter();
-            let list = self::deserialize_nested_list(borrowed_iter)?;
+            let list = self::deserialize_nested_list(&mut rawiter)?;
             // push it in
             map.true_if_insert(key, RwLock::new(list));
         }
+        let expiry_section = super::bytemarks::BYTEMARK_SECTION_EXPIRY;
+        let expiry = self::deserialize_key_section(&mut rawiter, &map, expiry_section)?;
+        let bounds_section = super::bytemarks::BYTEMARK_SECTION_LIST_BOUNDS;
+        let bounds = self::deserialize_key_section(&mut rawiter, &map, bounds_section)?;
         if rawiter.end_of_allocation() {
-            Some(map)
+            Some((map, expiry, bounds))
         } else {
-            // someone returned more data
+            // nope, someone gave us more data
             None
         }
     }
 
+    /// Deserialize a file that contains a serialized map of maps. The deadlines of expiring keys
+    /// (if any) are discarded
+    #[cfg(test)]
+    pub fn deserialize_map_map(bytes: &[u8]) -> Option<Coremap<SharedSlice, LockedMap>> {
+        self::deserialize_map_map_with_expiry(RawSliceIter::new(bytes)).map(|(map, _)| map)
+    }
+
+    /// Deserialize a file that contains a serialized map of maps, along with the deadlines of
+    /// its expiring keys
+    pub fn deserialize_map_map_with_expiry(
+        mut rawiter: RawSliceIter<'_>,
+    ) -> Option<WithExpiry<LockedMap>> {
+        // get the len
+        let len = rawiter.next_64bit_integer_to_usize()?;
+        // allocate a map
+        let map = Coremap::try_with_capacity(len).ok()?;
+        // now enter a loop
+        for _ in 0..len {
+            let keylen = rawiter.next_64bit_integer_to_usize()?;
+            // get key
+            let key = rawiter.next_owned_data(keylen)?;
+            let nested = self::deserialize_nested_map(&mut rawiter)?;
+            // push it in
+            map.true_if_insert(key, RwLock::new(nested));
+        }
+        let expiry = self::deserialize_expiry(&mut rawiter, &map)?;
+        Some((map, expiry))
+    }
+
+    /// Deserialize a file that contains a serialized map of sets. The deadlines of expiring keys
+    /// (if any) are discarded
+    #[cfg(test)]
+    pub fn deserialize_set_map(bytes: &[u8]) -> Option<Coremap<SharedSlice, LockedSet>> {
+        self::deserialize_set_map_with_expiry(RawSliceIter::new(bytes)).map(|(map, _)| map)
+    }
+
+    /// Deserialize a file that contains a serialized map of sets, along with the deadlines of
+    /// its expiring keys. The sets are written just like lists
+    pub fn deserialize_set_map_with_expiry(
+        mut rawiter: RawSliceIter<'_>,
+    ) -> Option<WithExpiry<LockedSet>> {
+        // get the len
+        let len = rawiter.next_64bit_integer_to_usize()?;
+        // allocate a map
+        let map = Coremap::try_with_capacity(len).ok()?;
+        // now enter a loop
+        for _ in 0..len {
+            let keylen = rawiter.next_64bit_integer_to_usize()?;
+            // get key
+            let key = rawiter.next_owned_data(keylen)?;
+            let members = self::deserialize_nested_list(&mut rawiter)?;
+            // push it in
+            map.true_if_insert(key, RwLock::new(members.into_iter().collect()));
+        }
+        let expiry = self::deserialize_expiry(&mut rawiter, &map)?;
+        Some((map, expiry))
+    }
+
+    /// Deserialize a file that contains a serialized map of sorted sets. The deadlines of
+    /// expiring keys (if any) are discarded
+    #[cfg(test)]
+    pub fn deserialize_sorted_set_map(
+        bytes: &[u8],
+    ) -> Option<Coremap<SharedSlice, LockedSortedSet>> {
+        self::deserialize_sorted_set_map_with_expiry(RawSliceIter::new(bytes)).map(|(map, _)| map)
+    }
+
+    /// Deserialize a file that contains a serialized map of sorted sets, along with the
+    /// deadlines of its expiring keys
+    pub fn deserialize_sorted_set_map_with_expiry(
+        mut rawiter: RawSliceIter<'_>,
+    ) -> Option<WithExpiry<LockedSortedSet>> {
+        // get the len
+        let len = rawiter.next_64bit_integer_to_usize()?;
+        // allocate a map
+        let map = Coremap::try_with_capacity(len).ok()?;
+        // now enter a loop
+        for _ in 0..len {
+            let keylen = rawiter.next_64bit_integer_to_usize()?;
+            // get key
+            let key = rawiter.next_owned_data(keylen)?;
+            let zset = self::deserialize_nested_sorted_set(&mut rawiter)?;
+            // push it in
+            map.true_if_insert(key, RwLock::new(zset));
+        }
+        let expiry = self::deserialize_expiry(&mut rawiter, &map)?;
+        Some((map, expiry))
+    }
+
+    /// Deserialize a file that contains a serialized map of documents. The deadlines of
+    /// expiring keys (if any) are discarded
+    #[cfg(test)]
+    pub fn deserialize_document_map(bytes: &[u8]) -> Option<Coremap<SharedSlice, LockedDocument>> {
+        self::deserialize_document_map_with_expiry(RawSliceIter::new(bytes)).map(|(map, _)| map)
+    }
+
+    /// Deserialize a file that contains a serialized map of documents, along with the deadlines
+    /// of its expiring keys
+    pub fn deserialize_document_map_with_expiry(
+        mut rawiter: RawSliceIter<'_>,
+    ) -> Option<WithExpiry<LockedDocument>> {
+        // get the len
+        let len = rawiter.next_64bit_integer_to_usize()?;
+        // allocate a map
+        let map = Coremap::try_with_capacity(len).ok()?;
+        // now enter a loop
+        for _ in 0..len {
+            let keylen = rawiter.next_64bit_integer_to_usize()?;
+            // get key
+            let key = rawiter.next_owned_data(keylen)?;
+            let doclen = rawiter.next_64bit_integer_to_usize()?;
+            // a document that doesn't decode is corrupted
+            let doc = Json::decode(&rawiter.next_slice(doclen)?)?;
+            // push it in
+            map.true_if_insert(key, RwLock::new(doc));
+        }
+        let expiry = self::deserialize_expiry(&mut rawiter, &map)?;
+        Some((map, expiry))
+    }
+
+    /// Deserialize a nested sorted set: `[EXTENT]([SCORE][MEMBER_EXT][MEMBER])*`
+    pub fn deserialize_nested_sorted_set(iter: &mut RawSliceIter<'_>) -> Option<SortedSet> {
+        let extent = iter.next_64bit_integer_to_usize()?;
+        let mut zset = SortedSet::new();
+        for _ in 0..extent {
+            let score = iter.next_64bit_float()?;
+            if score.is_nan() {
+                // we never write a NaN, so this is corrupted
+                return None;
+            }
+            let member_len = iter.next_64bit_integer_to_usize()?;
+            let member = iter.next_owned_data(member_len)?;
+            zset.insert(member, score);
+        }
+        Some(zset)
+    }
+
+    /// Deserialize a nested map: `[EXTENT]([FIELD_EXT][FIELD][VALUE_EXT][VALUE])*`
+    pub fn deserialize_nested_map(
+        iter: &mut RawSliceIter<'_>,
+    ) -> Option<HashMap<SharedSlice, SharedSlice>> {
+        let extent = iter.next_64bit_integer_to_usize()?;
+        let mut map = HashMap::new();
+        map.try_reserve(extent).ok()?;
+        for _ in 0..extent {
+            let field_len = iter.next_64bit_integer_to_usize()?;
+            let field = iter.next_owned_data(field_len)?;
+            let value_len = iter.next_64bit_integer_to_usize()?;
+            let value = iter.next_owned_data(value_len)?;
+            map.insert(field, value);
+        }
+        Some(map)
+    }
+
     /// Deserialize a nested list: `[EXTENT]([EL_EXT][EL])*`
     ///
-    pub fn deserialize_nested_list(mut iter: RawSliceIterBorrowed<'_>) -> Option<Vec<SharedSlice>> {
+    pub fn deserialize_nested_list(iter: &mut RawSliceIter<'_>) -> Option<Vec<SharedSlice>> {
         // get list payload len
         let list_payload_extent = iter.next_64bit_integer_to_usize()?;
         let mut list = Vec::new();
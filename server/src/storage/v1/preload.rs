@@ -31,22 +31,71 @@
 //! 1. the `PRELOAD` that is placed at the root directory
 //! 2. the `PARTMAP` preload that is placed in the ks directory
 //!
+//! Every keyspace directory also has a `KSMETA` file which holds keyspace-level settings
+//!
+//! ## Generations
+//!
+//! A node can't boot without its `PRELOAD`, so whenever BGSAVE writes a new `PRELOAD` it also
+//! keeps a copy of it as a numbered generation (`PRELOAD.1`, `PRELOAD.2` and so on). The last
+//! [`PRELOAD_GENERATIONS`] generations are kept, and if the `PRELOAD` can't be read on startup,
+//! we roll back to the latest generation that can be.
+//!
 
 use {
     crate::{
-        corestore::memstore::{Memstore, ObjectID},
-        storage::v1::error::{StorageEngineError, StorageEngineResult},
+        corestore::{
+            htable::Coremap,
+            memstore::{cluster::ReplicationStrategy, Memstore, ObjectID},
+        },
+        storage::{
+            v1::error::{StorageEngineError, StorageEngineResult},
+            v2::header::{self, FileKind, Header, ModelDescriptor},
+        },
         IoResult,
     },
     core::ptr,
     std::{
         collections::{HashMap, HashSet},
+        fs,
         io::Write,
     },
 };
 
 pub type LoadedPartfile = HashMap<ObjectID, (u8, u8)>;
 
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+/// The keyspace-level settings recorded in the `KSMETA`
+pub struct Ksmeta {
+    /// the flush interval in seconds
+    pub flush_interval: u64,
+    /// the maximum number of keys (zero if there is no limit)
+    pub max_keys: u64,
+    /// the maximum number of bytes (zero if there is no limit)
+    pub max_bytes: u64,
+    /// the tables that were created with a shard count, along with the shard count
+    pub table_shards: Vec<(ObjectID, u64)>,
+    /// the model that `use <space>` switches to
+    pub default_model: Option<ObjectID>,
+    /// the replication strategy
+    pub replication_strategy: ReplicationStrategy,
+}
+
+impl Ksmeta {
+    /// Returns true if the properties segment has to be written
+    fn has_properties(&self) -> bool {
+        self.default_model.is_some() || self.replication_strategy != ReplicationStrategy::default()
+    }
+}
+
+/// The table ID length that marks the start of the properties segment of a `KSMETA` (no table
+/// ID is this long)
+const KSMETA_PROPERTIES_MARK: u64 = u64::MAX;
+
+/// The number of `PRELOAD` generations that are kept
+pub const PRELOAD_GENERATIONS: usize = 5;
+/// The prefix of `PRELOAD` generations (followed by the generation number)
+const GENERATION_PREFIX: &str = "PRELOAD.";
+
 // our version and endian are based on nibbles
 
 const META_SEGMENT_LE: u8 = 0b1000_0000;
@@ -60,20 +109,37 @@ const META_SEGMENT: u8 = META_SEGMENT_BE;
 
 /// Generate the `PRELOAD` disk file for this instance
 /// ```text
+/// [32B: v2 header] => Header segment
 /// [1B: Endian Mark/Version Mark (padded)] => Meta segment
 /// [8B: Extent header] => Predata Segment
 /// ([8B: Partion ID len][8B: Parition ID (not padded)])* => Data segment
 /// ```
 ///
 pub(super) fn raw_generate_preload<W: Write>(w: &mut W, store: &Memstore) -> IoResult<()> {
+    self::raw_generate_preload_for(w, &store.keyspaces)
+}
+
+/// Generate a `PRELOAD` that only lists the given keyspaces
+pub(super) fn raw_generate_preload_for<W: Write, V>(
+    w: &mut W,
+    keyspaces: &Coremap<ObjectID, V>,
+) -> IoResult<()> {
+    w.write_all(&Header::new(FileKind::Preload, ModelDescriptor::NONE).encode())?;
     // generate the meta segment
     w.write_all(&[META_SEGMENT])?;
-    super::se::raw_serialize_set(&store.keyspaces, w)?;
+    super::se::raw_serialize_set(keyspaces, w)?;
     Ok(())
 }
 
 /// Reads the preload file and returns a set
 pub(super) fn read_preload_raw(preload: Vec<u8>) -> StorageEngineResult<HashSet<ObjectID>> {
+    // v1 preloads have no header
+    let preload = header::strip(
+        &preload,
+        "PRELOAD",
+        FileKind::Preload,
+        ModelDescriptor::NONE,
+    )?;
     if preload.len() < 16 {
         // nah, this is a bad disk file
         return Err(StorageEngineError::corrupted_preload());
@@ -95,3 +161,127 @@ pub(super) fn read_preload_raw(preload: Vec<u8>) -> StorageEngineResult<HashSet<
     super::de::deserialize_set_ctype(&preload[1..])
         .ok_or_else(StorageEngineError::corrupted_preload)
 }
+
+/// Generate the `KSMETA` disk file for a keyspace
+/// ```text
+/// [1B: Endian Mark/Version Mark (padded)] => Meta segment
+/// [8B: Flush interval][8B: Max keys][8B: Max bytes] => Data segment
+/// ([8B: Table ID len][?B: Table ID][8B: Shard count])* => Table segment
+/// ([8B: u64::MAX][1B: Replication strategy][8B: Default model len][?B: Default model])? => Properties segment
+/// ```
+///
+/// The properties segment is only written if a property differs from its default, and a
+/// default model length of zero means that there is no default model
+pub(super) fn raw_generate_ksmeta<W: Write>(w: &mut W, ksmeta: &Ksmeta) -> IoResult<()> {
+    w.write_all(&Header::new(FileKind::Ksmeta, ModelDescriptor::NONE).encode())?;
+    w.write_all(&[META_SEGMENT])?;
+    w.write_all(&ksmeta.flush_interval.to_ne_bytes())?;
+    w.write_all(&ksmeta.max_keys.to_ne_bytes())?;
+    w.write_all(&ksmeta.max_bytes.to_ne_bytes())?;
+    for (tblid, shards) in ksmeta.table_shards.iter() {
+        w.write_all(&(tblid.len() as u64).to_ne_bytes())?;
+        w.write_all(tblid)?;
+        w.write_all(&shards.to_ne_bytes())?;
+    }
+    if ksmeta.has_properties() {
+        let default_model = ksmeta.default_model.as_deref().unwrap_or_default();
+        w.write_all(&KSMETA_PROPERTIES_MARK.to_ne_bytes())?;
+        w.write_all(&[ksmeta.replication_strategy.code()])?;
+        w.write_all(&(default_model.len() as u64).to_ne_bytes())?;
+        w.write_all(default_model)?;
+    }
+    Ok(())
+}
+
+/// Reads a `KSMETA` file. A `KSMETA` written before quotas were introduced only has the flush
+/// interval, in which case there's no quota, and one written before table shard counts were
+/// introduced has no table segment. A missing properties segment means that every property has
+/// its default value
+pub(super) fn read_ksmeta_raw(ksid: &ObjectID, ksmeta: Vec<u8>) -> StorageEngineResult<Ksmeta> {
+    let file = format!("{}/KSMETA", unsafe { ksid.as_str() });
+    // v1 files have no header
+    let ksmeta = header::strip(&ksmeta, &file, FileKind::Ksmeta, ModelDescriptor::NONE)?;
+    if ksmeta.len() != 9 && ksmeta.len() < 25 {
+        return Err(StorageEngineError::corrupted_ksmeta(ksid));
+    }
+    let read_u64: fn([u8; 8]) -> u64 = match ksmeta[0] {
+        META_SEGMENT_BE => u64::from_be_bytes,
+        META_SEGMENT_LE => u64::from_le_bytes,
+        _ => return Err(StorageEngineError::BadMetadata(file)),
+    };
+    let read_field = |at: usize| {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(ksmeta.get(at..at + 8)?);
+        Some(read_u64(bytes))
+    };
+    let mut ret = Ksmeta {
+        flush_interval: read_field(1).unwrap_or(0),
+        max_keys: read_field(9).unwrap_or(0),
+        max_bytes: read_field(17).unwrap_or(0),
+        table_shards: Vec::new(),
+        default_model: None,
+        replication_strategy: ReplicationStrategy::default(),
+    };
+    let mut at = 25;
+    while at < ksmeta.len() {
+        if read_field(at) == Some(KSMETA_PROPERTIES_MARK) {
+            let properties = ksmeta.get(at + 8).and_then(|code| {
+                let replication_strategy = ReplicationStrategy::from_code(*code)?;
+                let len = usize::try_from(read_field(at + 9)?).ok()?;
+                let default_model = ksmeta.get(at + 17..)?;
+                if default_model.len() != len {
+                    return None;
+                }
+                let default_model = match len {
+                    0 => None,
+                    _ => Some(ObjectID::try_from_slice(default_model)?),
+                };
+                Some((replication_strategy, default_model))
+            });
+            match properties {
+                Some((replication_strategy, default_model)) => {
+                    ret.replication_strategy = replication_strategy;
+                    ret.default_model = default_model;
+                    break;
+                }
+                None => return Err(StorageEngineError::corrupted_ksmeta(ksid)),
+            }
+        }
+        let table = read_field(at).and_then(|len| {
+            let start = at + 8;
+            let end = start.checked_add(usize::try_from(len).ok()?)?;
+            let tblid = ObjectID::try_from_slice(ksmeta.get(start..end)?)?;
+            Some((tblid, read_field(end)?, end + 8))
+        });
+        match table {
+            Some((tblid, shards, next)) => {
+                ret.table_shards.push((tblid, shards));
+                at = next;
+            }
+            None => return Err(StorageEngineError::corrupted_ksmeta(ksid)),
+        }
+    }
+    Ok(ret)
+}
+
+/// Returns the generation number if `name` is the name of a `PRELOAD` generation
+pub fn generation_of(name: &str) -> Option<u64> {
+    name.strip_prefix(GENERATION_PREFIX)?.parse().ok()
+}
+
+/// Returns the path to a `PRELOAD` generation under `root`
+pub fn generation_path(root: &str, generation: u64) -> String {
+    format!("{root}/{GENERATION_PREFIX}{generation}")
+}
+
+/// Returns the `PRELOAD` generations under `root`, latest first
+pub fn list_generations(root: &str) -> IoResult<Vec<u64>> {
+    let mut generations = Vec::new();
+    for entry in fs::read_dir(root)? {
+        if let Some(generation) = self::generation_of(&entry?.file_name().to_string_lossy()) {
+            generations.push(generation);
+        }
+    }
+    generations.sort_unstable_by(|a, b| b.cmp(a));
+    Ok(generations)
+}
@@ -0,0 +1,419 @@
+/*
+ * Created on Fri Nov 04 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Repair
+//!
+//! Routines to salvage whatever can still be read from a damaged data directory. Every table
+//! file is verified segment by segment and then read entry by entry: we keep everything up to
+//! the first damaged segment (or the first entry that can't be decoded) and record exactly what
+//! had to be dropped. The salvaged store is then written out as a fresh tree, while the damaged
+//! tree is moved under [`DIR_REPAIRROOT`] along with the report.
+
+use {
+    super::{
+        bytemarks, checksum,
+        error::{ErrorContext, StorageEngineError, StorageEngineResult},
+        flush::{self, Autoflush},
+        interface::{self, DIR_KSROOT, DIR_REPAIRROOT},
+        iter::RawSliceIter,
+        preload::Ksmeta,
+        unflush::{self, TableSource, UnflushableTable},
+        Coremap,
+    },
+    crate::{
+        corestore::{
+            memstore::{Keyspace, Memstore, ObjectID, SystemKeyspace, DEFAULT, SYSTEM},
+            table::{SystemTable, Table},
+        },
+        kvengine::document::Json,
+        storage::v2::header::{
+            self, FileKind, ModelDescriptor, CONTAINER_DOCUMENT, CONTAINER_LIST, CONTAINER_MAP,
+            CONTAINER_SET, CONTAINER_SORTED_SET,
+        },
+        util::Wrapper,
+    },
+    chrono::prelude::Utc,
+    core::fmt,
+    std::{borrow::Cow, collections::HashSet, fs, path::Path, sync::Arc},
+};
+
+#[derive(Debug, PartialEq, Eq)]
+/// Something that had to be dropped or rebuilt during a repair
+pub enum Finding {
+    /// The `PRELOAD` couldn't be read and was rebuilt from the keyspace directories
+    RebuiltPreload(String),
+    /// The `KSMETA` of a keyspace couldn't be read and its settings were reset
+    ResetKsmeta(String, String),
+    /// An entire keyspace was dropped
+    DroppedKeyspace(String, String),
+    /// An entire table was dropped
+    DroppedTable(String, String),
+    /// Some keys were dropped from a table
+    DroppedKeys {
+        table: String,
+        /// the keys that could still be read, even though their values couldn't
+        keys: Vec<String>,
+        /// the number of keys that couldn't be read at all (if it is known)
+        unreadable: Option<usize>,
+        reason: String,
+    },
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RebuiltPreload(reason) => {
+                write!(
+                    f,
+                    "rebuilt PRELOAD from the keyspace directories ({reason})"
+                )
+            }
+            Self::ResetKsmeta(ks, reason) => {
+                write!(f, "reset the settings of keyspace `{ks}` ({reason})")
+            }
+            Self::DroppedKeyspace(ks, reason) => write!(f, "dropped keyspace `{ks}` ({reason})"),
+            Self::DroppedTable(table, reason) => write!(f, "dropped table `{table}` ({reason})"),
+            Self::DroppedKeys {
+                table,
+                keys,
+                unreadable,
+                reason,
+            } => {
+                write!(f, "dropped keys from table `{table}` ({reason}):")?;
+                for key in keys {
+                    write!(f, " {key:?}")?;
+                }
+                match unreadable {
+                    Some(0) => Ok(()),
+                    Some(n) => write!(f, " and {n} unreadable key(s)"),
+                    None => write!(f, " and an unknown number of unreadable keys"),
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+/// The result of a repair
+pub struct RepairReport {
+    /// the number of tables that were salvaged
+    pub tables: usize,
+    /// the number of keys that were salvaged
+    pub keys: usize,
+    /// everything that had to be dropped or rebuilt
+    pub findings: Vec<Finding>,
+}
+
+impl fmt::Display for RepairReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(
+            f,
+            "salvaged {} table(s) with {} key(s)",
+            self.tables, self.keys
+        )?;
+        for finding in self.findings.iter() {
+            writeln!(f, "{finding}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Repair the data directory and return a report of what was salvaged and what was dropped.
+/// Returns `None` if there is nothing to repair
+pub fn repair() -> StorageEngineResult<Option<RepairReport>> {
+    if !Path::new(DIR_KSROOT).is_dir() {
+        return Ok(None);
+    }
+    let mut report = RepairReport::default();
+    let store = self::salvage_store(&mut report)?;
+    // move the damaged tree out of the way and write out the salvaged one
+    let moved_to = format!("{DIR_REPAIRROOT}/{}", Utc::now().format("%Y%m%d-%H%M%S"));
+    fs::create_dir_all(DIR_REPAIRROOT).map_err_context("creating the repair directory")?;
+    fs::rename(DIR_KSROOT, &moved_to).map_err_context("moving the damaged tree")?;
+    let target = Autoflush;
+    interface::create_tree_fresh(&target, &store)?;
+    flush::oneshot::flush_preload(&target, &store)?;
+    flush::flush_full(target, &store)?;
+    fs::write(format!("{moved_to}/REPORT"), report.to_string())
+        .map_err_context("writing the repair report")?;
+    log::info!("Moved the damaged tree to `{moved_to}`");
+    Ok(Some(report))
+}
+
+/// Salvage everything that can be read from the tree into a store
+fn salvage_store(report: &mut RepairReport) -> StorageEngineResult<Memstore> {
+    let mut preload = match unflush::read_preload() {
+        Ok(preload) => preload,
+        Err(e) => {
+            report.findings.push(Finding::RebuiltPreload(e.to_string()));
+            self::list_keyspaces()?
+        }
+    };
+    preload.remove(&SYSTEM);
+    let system = SystemKeyspace::new(Coremap::new());
+    for (tblid, tbl) in self::salvage_tables::<SystemTable>(&SYSTEM, report).unwrap_or_default() {
+        system.tables.true_if_insert(tblid, Wrapper::new(tbl));
+    }
+    let ksmap = Coremap::with_capacity(preload.len());
+    for ksid in preload {
+        if let Some(ks) = self::salvage_keyspace(&ksid, report) {
+            ksmap.upsert(ksid, Arc::new(ks));
+        }
+    }
+    // the dropped default keyspace has already been reported, but we can't run without it
+    if !ksmap.contains_key(&DEFAULT) {
+        ksmap.upsert(DEFAULT, Arc::new(Keyspace::empty_default()));
+    }
+    // HACK(@ohsayan): Same as `read_full`; the system keyspace needs to be in the preload
+    ksmap.upsert(SYSTEM, Arc::new(Keyspace::empty()));
+    Ok(Memstore::init_with_all(ksmap, system))
+}
+
+/// List the keyspace directories in the tree; used when the `PRELOAD` can't be read
+fn list_keyspaces() -> StorageEngineResult<HashSet<ObjectID>> {
+    let mut keyspaces = HashSet::new();
+    for entry in fs::read_dir(DIR_KSROOT).map_err_context("listing keyspaces")? {
+        let entry = entry.map_err_context("listing keyspaces")?;
+        if !entry.path().is_dir() {
+            continue;
+        }
+        let name = entry.file_name();
+        if let Some(ksid) = name.to_str().and_then(ObjectID::try_from_slice) {
+            keyspaces.insert(ksid);
+        }
+    }
+    Ok(keyspaces)
+}
+
+/// Salvage a keyspace. Returns `None` if its `PARTMAP` couldn't be read
+pub(super) fn salvage_keyspace(ksid: &ObjectID, report: &mut RepairReport) -> Option<Keyspace> {
+    let tables = Coremap::new();
+    for (tblid, tbl) in self::salvage_tables::<Table>(ksid, report)? {
+        tables.true_if_insert(tblid, Arc::new(tbl));
+    }
+    let ksmeta = unflush::read_ksmeta(DIR_KSROOT, ksid).unwrap_or_else(|e| {
+        let ks = unsafe { ksid.as_str() }.to_owned();
+        report
+            .findings
+            .push(Finding::ResetKsmeta(ks, e.to_string()));
+        Ksmeta::default()
+    });
+    Some(unflush::keyspace_with_ksmeta(tables, ksmeta))
+}
+
+/// Salvage the tables in the `PARTMAP` of a keyspace. Returns `None` if the `PARTMAP` couldn't
+/// be read (since we wouldn't know how to read the tables)
+fn salvage_tables<T: UnflushableTable>(
+    ksid: &ObjectID,
+    report: &mut RepairReport,
+) -> Option<Vec<(ObjectID, T)>> {
+    let ks = unsafe { ksid.as_str() };
+    let partmap = match unflush::read_partmap(DIR_KSROOT, ksid) {
+        Ok(partmap) => partmap,
+        Err(e) => {
+            let reason = e.to_string();
+            report
+                .findings
+                .push(Finding::DroppedKeyspace(ks.to_owned(), reason));
+            return None;
+        }
+    };
+    let kind = if ksid == &SYSTEM {
+        FileKind::SystemTable
+    } else {
+        FileKind::Table
+    };
+    let mut tables = Vec::with_capacity(partmap.len());
+    for (tblid, (storage_type, model_code)) in partmap {
+        let table = format!("{ks}:{tbl}", tbl = unsafe { tblid.as_str() });
+        let file = format!("{DIR_KSROOT}/{ks}/{tbl}", tbl = unsafe { tblid.as_str() });
+        let salvaged = match bytemarks::storage_is_volatile(storage_type) {
+            Err(e) => {
+                Err(StorageEngineError::unknown_bytemark_in_table(ksid, &tblid, e).to_string())
+            }
+            // volatile tables have nothing to salvage
+            Ok(true) => T::unflush_table(&file, model_code, true).map_err(|e| e.to_string()),
+            Ok(false) => self::salvage_table(&table, &file, kind, model_code, report),
+        };
+        match salvaged {
+            Ok(tbl) => {
+                report.tables += 1;
+                tables.push((tblid, tbl));
+            }
+            Err(reason) => report.findings.push(Finding::DroppedTable(table, reason)),
+        }
+    }
+    Some(tables)
+}
+
+/// Salvage the entries of a table file. Returns the reason if the table had to be dropped
+fn salvage_table<T: UnflushableTable>(
+    table: &str,
+    file: &str,
+    kind: FileKind,
+    model_code: u8,
+    report: &mut RepairReport,
+) -> Result<T, String> {
+    let model = match kind {
+        FileKind::SystemTable => ModelDescriptor::from_system_code(model_code),
+        _ => ModelDescriptor::from_model_code(model_code),
+    }
+    .map_err(|e| StorageEngineError::UnknownBytemark(file.to_owned(), e).to_string())?;
+    let data = fs::read(file)
+        .map_err_context(format!("reading file {file}"))
+        .map_err(|e| e.to_string())?;
+    let body = header::strip(&data, file, kind, model).map_err(|e| e.to_string())?;
+    let (payload, damage) = if checksum::is_checksummed(body) {
+        checksum::verify_prefix(body, file, data.len() - body.len())
+    } else {
+        (body.to_vec(), None)
+    };
+    let salvage = self::salvage_entries(&payload, model.container);
+    let tbl = T::unflush_table_from(
+        TableSource::Payload(file, &salvage.payload),
+        model_code,
+        false,
+    )
+    .map_err(|e| e.to_string())?;
+    report.keys += salvage.salvaged;
+    let keys: Vec<String> = salvage.lost_key.into_iter().collect();
+    let unreadable = salvage
+        .declared
+        .map(|declared| declared.saturating_sub(salvage.salvaged + keys.len()));
+    if damage.is_some() || !keys.is_empty() || unreadable != Some(0) {
+        let reason = damage
+            .unwrap_or_else(|| StorageEngineError::CorruptedFile(file.to_owned()))
+            .to_string();
+        report.findings.push(Finding::DroppedKeys {
+            table: table.to_owned(),
+            keys,
+            unreadable,
+            reason,
+        });
+    }
+    Ok(tbl)
+}
+
+/// The entries that could be salvaged from the payload of a table file
+struct Salvage {
+    /// a payload with just the salvaged entries
+    payload: Vec<u8>,
+    /// the number of entries that the payload claimed to have (if it could be read)
+    declared: Option<usize>,
+    /// the number of entries that were salvaged
+    salvaged: usize,
+    /// the key of the first entry that couldn't be read completely (if the key could be read)
+    lost_key: Option<String>,
+}
+
+/// Read the entries in a table's payload until the first one that can't be read completely
+fn salvage_entries(payload: &[u8], container: u8) -> Salvage {
+    let mut iter = RawSliceIter::new(payload);
+    let declared = iter.next_64bit_integer_to_usize();
+    let mut salvaged = 0;
+    let mut end = iter.position();
+    let mut lost_key = None;
+    while salvaged < declared.unwrap_or(0) {
+        match self::read_entry(&mut iter, container) {
+            Ok(()) => {
+                salvaged += 1;
+                end = iter.position();
+            }
+            Err(key) => {
+                lost_key = key.map(|key| String::from_utf8_lossy(&key).into_owned());
+                break;
+            }
+        }
+    }
+    // rewrite the entry count, keeping the byte order that the file was written in
+    let mut count = (salvaged as u64).to_ne_bytes();
+    if let Some(declared) = declared {
+        let mut raw = [0u8; 8];
+        raw.copy_from_slice(&payload[..8]);
+        if u64::from_ne_bytes(raw) != declared as u64 {
+            count.reverse();
+        }
+    }
+    let mut salvaged_payload = count.to_vec();
+    if declared.is_some() {
+        salvaged_payload.extend_from_slice(&payload[8..end]);
+    }
+    Salvage {
+        payload: salvaged_payload,
+        declared,
+        salvaged,
+        lost_key,
+    }
+}
+
+/// Read the next entry. If the entry can't be read completely, this returns its key if the key
+/// itself could be read
+fn read_entry<'a>(iter: &mut RawSliceIter<'a>, container: u8) -> Result<(), Option<Cow<'a, [u8]>>> {
+    if container == CONTAINER_LIST
+        || container == CONTAINER_MAP
+        || container == CONTAINER_SET
+        || container == CONTAINER_SORTED_SET
+    {
+        // [KEYLEN][KEY][LISTLEN]([ELEMENTLEN][ELEMENT])* (a set is written like a list, a map
+        // has a field and a value for every element and a sorted set has an 8B score before
+        // every member)
+        let key = iter
+            .next_64bit_integer_to_usize()
+            .and_then(|len| iter.next_slice(len))
+            .ok_or(None)?;
+        let lost = || Some(key.clone());
+        let len = iter.next_64bit_integer_to_usize().ok_or_else(lost)?;
+        let elements = if container == CONTAINER_MAP {
+            len.checked_mul(2).ok_or_else(lost)?
+        } else {
+            len
+        };
+        for _ in 0..elements {
+            if container == CONTAINER_SORTED_SET {
+                iter.next_slice(8).ok_or_else(lost)?;
+            }
+            iter.next_64bit_integer_to_usize()
+                .and_then(|len| iter.next_slice(len))
+                .ok_or_else(lost)?;
+        }
+    } else if container == CONTAINER_DOCUMENT {
+        // [KEYLEN][KEY][DOCLEN][DOC] (a document that doesn't decode is lost too)
+        let key = iter
+            .next_64bit_integer_to_usize()
+            .and_then(|len| iter.next_slice(len))
+            .ok_or(None)?;
+        iter.next_64bit_integer_to_usize()
+            .and_then(|len| iter.next_slice(len))
+            .and_then(|doc| Json::decode(&doc))
+            .ok_or(Some(key))?;
+    } else {
+        // [KEYLEN][VALUELEN][KEY][VALUE]
+        let (keylen, valuelen) = iter.next_64bit_integer_pair_to_usize().ok_or(None)?;
+        let key = iter.next_slice(keylen).ok_or(None)?;
+        iter.next_slice(valuelen).ok_or(Some(key))?;
+    }
+    Ok(())
+}
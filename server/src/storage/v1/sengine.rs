@@ -26,21 +26,35 @@
 
 use {
     self::queue::Queue,
-    super::interface::{DIR_RSNAPROOT, DIR_SNAPROOT},
+    super::{
+        interface::{DIR_RSNAPROOT, DIR_SNAPROOT},
+        snapname::NameTemplate,
+    },
     crate::{
         corestore::{iarray::IArray, lazy::Lazy, lock::QuickLock, memstore::Memstore},
+        services::snapshot::SnapshotSink,
         storage::v1::flush::{LocalSnapshot, RemoteSnapshot},
     },
     chrono::prelude::Utc,
-    core::{fmt, str},
+    core::{fmt, str, time::Duration},
     regex::Regex,
-    std::{collections::HashSet, fs, io::Error as IoError, path::Path, sync::Arc},
+    std::{
+        collections::{HashMap, HashSet},
+        fs,
+        io::{Error as IoError, Result as IoResult},
+        path::Path,
+        sync::{
+            atomic::{AtomicU64, Ordering},
+            Arc,
+        },
+    },
 };
 
 type QStore = IArray<[String; 64]>;
 type SnapshotResult<T> = Result<T, SnapshotEngineError>;
 
-/// Matches any string which is in the following format:
+/// Matches any string which is in the following format (the names given by the default
+/// [`NameTemplate`], which are always recognized as snapshots):
 /// ```text
 /// YYYYMMDD-HHMMSS
 /// ```
@@ -90,6 +104,53 @@ pub struct SnapshotEngine {
     local_queue: QuickLock<Queue>,
     /// the remote snapshot lock
     remote_queue: QuickLock<HashSet<Box<[u8]>>>,
+    /// the sink that snapshots are shipped to (if any)
+    sink: Option<Arc<dyn SnapshotSink>>,
+    /// the template for the names of local snapshots
+    name_template: NameTemplate,
+    /// the next `{seq}` for the name template
+    seq: AtomicU64,
+    /// the snapshots that are being shipped to the sink
+    uploads: Arc<Uploads>,
+}
+
+/// The snapshots that are being shipped to the sink. A snapshot is pinned while its upload
+/// runs: if retention wants to remove it in the meantime, it's removed once the upload completes
+#[derive(Debug)]
+struct Uploads {
+    /// maps the directory of every pinned snapshot to whether it should be removed once its
+    /// upload completes
+    dirs: QuickLock<HashMap<String, bool>>,
+}
+
+impl Uploads {
+    fn new() -> Self {
+        Self {
+            dirs: QuickLock::new(HashMap::new()),
+        }
+    }
+    fn pin(&self, dir: String) {
+        self.dirs.lock().insert(dir, false);
+    }
+    /// Remove the snapshot at `dir` now or, if it's pinned, once its upload completes. Returns
+    /// false if the removal was deferred
+    fn remove(&self, dir: &str) -> IoResult<bool> {
+        if let Some(expired) = self.dirs.lock().get_mut(dir) {
+            *expired = true;
+            return Ok(false);
+        }
+        fs::remove_dir_all(dir).map(|_| true)
+    }
+    /// Unpin the snapshot at `dir`, removing it if retention wanted it gone while it was pinned
+    fn unpin(&self, dir: &str) {
+        let expired = self.dirs.lock().remove(dir).unwrap_or(false);
+        if expired {
+            match fs::remove_dir_all(dir) {
+                Ok(()) => log::info!("Successfully removed older snapshot"),
+                Err(e) => log::warn!("Failed to remove older snapshot (ignored): {e}"),
+            }
+        }
+    }
 }
 
 #[derive(Debug, PartialEq)]
@@ -108,6 +169,10 @@ impl SnapshotEngine {
             local_enabled: true,
             local_queue: QuickLock::new(Queue::new(maxlen, maxlen == 0)),
             remote_queue: QuickLock::new(HashSet::new()),
+            sink: None,
+            name_template: NameTemplate::new_default(),
+            seq: AtomicU64::new(1),
+            uploads: Arc::new(Uploads::new()),
         }
     }
     pub fn new_disabled() -> Self {
@@ -115,8 +180,41 @@ impl SnapshotEngine {
             local_enabled: false,
             local_queue: QuickLock::new(Queue::new(0, true)),
             remote_queue: QuickLock::new(HashSet::new()),
+            sink: None,
+            name_template: NameTemplate::new_default(),
+            seq: AtomicU64::new(1),
+            uploads: Arc::new(Uploads::new()),
         }
     }
+    /// Ship every snapshot to `sink` once it has been created
+    pub fn with_sink(mut self, sink: Arc<dyn SnapshotSink>) -> Self {
+        self.sink = Some(sink);
+        self
+    }
+    /// Name local snapshots after `template`
+    pub fn with_name_template(mut self, template: NameTemplate) -> Self {
+        self.name_template = template;
+        self
+    }
+    /// Ship the snapshot at `dir` to the sink (if one is set) in the background. The snapshot
+    /// is already safe on the local disk, so a failed upload is only logged instead of failing
+    /// the snapshot (which would poison the database under failsafe)
+    fn ship(&self, dir: String) {
+        let sink = match &self.sink {
+            Some(sink) => sink.clone(),
+            None => return,
+        };
+        let uploads = self.uploads.clone();
+        uploads.pin(dir.clone());
+        // we don't wait for this: uploads can take much longer than the snapshot itself
+        tokio::task::spawn_blocking(move || {
+            match sink.ship(Path::new(&dir)) {
+                Ok(()) => log::info!("Shipped snapshot to `{}`", sink.name()),
+                Err(e) => log::error!("Failed to ship snapshot to `{}`: {e}", sink.name()),
+            }
+            uploads.unpin(&dir);
+        });
+    }
     fn _parse_dir(
         dir: &str,
         is_okay: impl Fn(&str) -> bool,
@@ -140,11 +238,28 @@ impl SnapshotEngine {
     }
     pub fn parse_dir(&self) -> SnapshotResult<()> {
         let mut local_queue = self.local_queue.lock();
+        let mut snapshots = Vec::new();
         Self::_parse_dir(
             DIR_SNAPROOT,
-            |name| SNAP_MATCH.is_match(name),
-            |snapshot| local_queue.push(snapshot),
+            |name| self.name_template.matches(name) || SNAP_MATCH.is_match(name),
+            |snapshot| snapshots.push(snapshot),
         )?;
+        // names from a template don't always sort in the order they were created in, so go by
+        // the modification times instead
+        let mut snapshots = snapshots
+            .into_iter()
+            .map(|snap| {
+                let mtime = fs::metadata(concat_path!(DIR_SNAPROOT, &snap))?.modified()?;
+                Ok((mtime, snap))
+            })
+            .collect::<SnapshotResult<Vec<_>>>()?;
+        snapshots.sort();
+        for (_, snap) in snapshots {
+            if let Some(seq) = self.name_template.seq_of(&snap) {
+                self.seq.fetch_max(seq + 1, Ordering::Relaxed);
+            }
+            local_queue.push(snap);
+        }
         let mut remote_queue = self.remote_queue.lock();
         Self::_parse_dir(
             DIR_RSNAPROOT,
@@ -157,7 +272,49 @@ impl SnapshotEngine {
     }
     /// Generate the snapshot name
     fn get_snapname(&self) -> String {
-        Utc::now().format("%Y%m%d-%H%M%S").to_string()
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed);
+        self.name_template.render(Utc::now(), seq)
+    }
+    /// Remove the local snapshots that were created more than `keep_for` seconds ago (going by
+    /// the modification time of their directories). The most recent snapshot is always kept.
+    /// Returns the number of snapshots that were removed
+    pub async fn prune_expired(&self, keep_for: u64) -> usize {
+        if !self.local_enabled {
+            return 0;
+        }
+        let keep_for = Duration::from_secs(keep_for);
+        let mut queue = match self.local_queue.try_lock() {
+            Some(lck) => lck,
+            // a snapshot is being created; we'll prune on the next run
+            None => return 0,
+        };
+        let candidates = queue.all_but_latest();
+        let uploads = self.uploads.clone();
+        let removed = tokio::task::spawn_blocking(move || {
+            let mut removed = Vec::new();
+            for snap in candidates {
+                let path = format!("{DIR_SNAPROOT}/{snap}");
+                let age = fs::metadata(&path).and_then(|meta| meta.modified());
+                // the clock might have gone back, in which case the snapshot isn't expired
+                match age.map(|mtime| mtime.elapsed().unwrap_or_default()) {
+                    // snapshots that are still being shipped are removed once that's done
+                    Ok(age) if age > keep_for => match uploads.remove(&path) {
+                        Ok(_) => removed.push(snap),
+                        Err(e) => log::warn!("Failed to remove expired snapshot (ignored): {e}"),
+                    },
+                    Ok(_) => {}
+                    Err(e) => log::warn!("Failed to read the age of a snapshot (ignored): {e}"),
+                }
+            }
+            removed
+        })
+        .await
+        .expect("snapshot pruning thread panicked");
+        queue.remove_all(&removed);
+        if !removed.is_empty() {
+            log::info!("Removed {} expired snapshot(s)", removed.len());
+        }
+        removed.len()
     }
     fn _mksnap_blocking_section(store: &Memstore, name: String) -> SnapshotResult<()> {
         if Path::new(&format!("{DIR_SNAPROOT}/{name}")).exists() {
@@ -165,6 +322,7 @@ impl SnapshotEngine {
         } else {
             let snapshot = LocalSnapshot::new(name);
             super::flush::flush_full(snapshot, store)?;
+            super::stats::stats().record_snapshot(crate::util::os::get_epoch_secs());
             Ok(())
         }
     }
@@ -186,6 +344,7 @@ impl SnapshotEngine {
                 None => return SnapshotActionResult::Busy,
             };
             let name = self.get_snapname();
+            let snapdir = format!("{DIR_SNAPROOT}/{name}");
             let nameclone = name.clone();
             let todel = queue.add_new(name);
             let snap_create_result = tokio::task::spawn_blocking(move || {
@@ -209,17 +368,19 @@ impl SnapshotEngine {
 
             // Now delete the older snap (if any)
             if let Some(snap) = todel {
+                let uploads = self.uploads.clone();
                 tokio::task::spawn_blocking(move || {
-                    if let Err(e) = fs::remove_dir_all(concat_path!(DIR_SNAPROOT, snap)) {
-                        log::warn!("Failed to remove older snapshot (ignored): {}", e);
-                    } else {
-                        log::info!("Successfully removed older snapshot");
+                    match uploads.remove(&format!("{DIR_SNAPROOT}/{snap}")) {
+                        Ok(true) => log::info!("Successfully removed older snapshot"),
+                        Ok(false) => log::info!("Older snapshot will be removed once shipped"),
+                        Err(e) => log::warn!("Failed to remove older snapshot (ignored): {}", e),
                     }
                 })
                 .await
                 .expect("mksnap thread panicked");
             }
             drop(queue);
+            self.ship(snapdir);
             SnapshotActionResult::Ok
         } else {
             SnapshotActionResult::Disabled
@@ -255,6 +416,9 @@ impl SnapshotEngine {
             .await
             .expect("rmksnap thread panicked");
             assert!(remq.insert(name.to_owned().into_boxed_slice()));
+            if ret == SnapshotActionResult::Ok {
+                self.ship(format!("{DIR_RSNAPROOT}/{}", String::from_utf8_lossy(name)));
+            }
             ret
         }
     }
@@ -321,6 +485,25 @@ mod queue {
         pub fn pop_last(&mut self) -> Option<String> {
             self.queue.pop()
         }
+        /// Returns every item except the latest one
+        pub fn all_but_latest(&self) -> Vec<String> {
+            let len = self.queue.len().saturating_sub(1);
+            self.queue[..len].to_vec()
+        }
+        /// Remove the given items from the queue
+        pub fn remove_all(&mut self, items: &[String]) {
+            let mut i = 0;
+            while i < self.queue.len() {
+                if items.contains(&self.queue[i]) {
+                    unsafe {
+                        // SAFETY: We have already checked that the index is in bounds
+                        self.queue.remove(i);
+                    }
+                } else {
+                    i += 1;
+                }
+            }
+        }
     }
 
     #[test]
@@ -340,6 +523,20 @@ mod queue {
         );
     }
 
+    #[test]
+    fn test_queue_remove_all() {
+        let mut q = Queue::new(0, true);
+        q.add_new(String::from("snap1"));
+        q.add_new(String::from("snap2"));
+        q.add_new(String::from("snap3"));
+        let expired = q.all_but_latest();
+        assert_eq!(expired, vec![String::from("snap1"), String::from("snap2")]);
+        q.remove_all(&expired);
+        // the latest item is never returned
+        assert!(q.all_but_latest().is_empty());
+        assert_eq!(q.pop_last(), Some(String::from("snap3")));
+    }
+
     #[test]
     fn test_queue_dontpop() {
         // This means that items can only be added or all of them can be deleted
@@ -352,3 +549,20 @@ mod queue {
         assert!(q.add_new(String::from("snap6")).is_none());
     }
 }
+
+#[test]
+fn test_uploads_defer_removal() {
+    let uploads = Uploads::new();
+    let (pinned, unpinned) = ("uploads_pinned_snap", "uploads_unpinned_snap");
+    fs::create_dir_all(pinned).unwrap();
+    fs::create_dir_all(unpinned).unwrap();
+    uploads.pin(pinned.to_owned());
+    // retention can't remove a snapshot that's being shipped
+    assert!(!uploads.remove(pinned).unwrap());
+    assert!(Path::new(pinned).exists());
+    assert!(uploads.remove(unpinned).unwrap());
+    assert!(!Path::new(unpinned).exists());
+    // but it's removed once the upload completes
+    uploads.unpin(pinned);
+    assert!(!Path::new(pinned).exists());
+}
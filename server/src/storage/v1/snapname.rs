@@ -0,0 +1,219 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Snapshot names
+//!
+//! Local snapshots are named after a template, so that fleets that archive snapshots centrally
+//! can produce names that don't collide and still sort. A template is made of literal characters
+//! (ASCII letters, digits, `-`, `_` and `.`) and placeholders:
+//! - `{hostname}`: the hostname of this machine
+//! - `{date}`: the UTC date, as `YYYYMMDD`
+//! - `{time}`: the UTC time, as `HHMMSS`
+//! - `{timestamp}`: the number of seconds since the UNIX epoch
+//! - `{seq}`: a sequence number, zero-padded to [`SEQ_WIDTH`] digits, that picks up where the
+//!   existing snapshots left off
+//!
+//! A template needs a `{time}`, `{timestamp}` or `{seq}` so that two snapshots don't get the
+//! same name. The default template, `{date}-{time}`, gives the `YYYYMMDD-HHMMSS` names of
+//! older versions.
+
+use {
+    crate::util::os,
+    chrono::{DateTime, Utc},
+    core::{fmt, str::FromStr},
+    regex::Regex,
+};
+
+/// The default template
+pub const DEFAULT_TEMPLATE: &str = "{date}-{time}";
+/// The minimum number of digits in a rendered `{seq}`
+pub const SEQ_WIDTH: usize = 6;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Literal(String),
+    Hostname,
+    Date,
+    Time,
+    Timestamp,
+    Seq,
+}
+
+impl Part {
+    const fn is_unique(&self) -> bool {
+        matches!(self, Self::Time | Self::Timestamp | Self::Seq)
+    }
+}
+
+/// A snapshot name template (see the [module-level documentation](self))
+#[derive(Clone)]
+pub struct NameTemplate {
+    source: String,
+    parts: Vec<Part>,
+    /// matches the names rendered by this template
+    pattern: Regex,
+}
+
+impl NameTemplate {
+    /// Returns the default template
+    pub fn new_default() -> Self {
+        DEFAULT_TEMPLATE.parse().unwrap()
+    }
+    /// Render the name of a snapshot taken at `now` with the sequence number `seq`
+    pub fn render(&self, now: DateTime<Utc>, seq: u64) -> String {
+        let mut name = String::new();
+        for part in self.parts.iter() {
+            match part {
+                Part::Literal(literal) => name.push_str(literal),
+                Part::Hostname => name.push_str(&hostname()),
+                Part::Date => name.push_str(&now.format("%Y%m%d").to_string()),
+                Part::Time => name.push_str(&now.format("%H%M%S").to_string()),
+                Part::Timestamp => name.push_str(&now.timestamp().to_string()),
+                Part::Seq => name.push_str(&format!("{seq:0SEQ_WIDTH$}")),
+            }
+        }
+        name
+    }
+    /// Returns true if `name` could have been rendered by this template
+    pub fn matches(&self, name: &str) -> bool {
+        self.pattern.is_match(name)
+    }
+    /// Returns the sequence number in `name`, if this template has a `{seq}` and `name` matches
+    /// the template
+    pub fn seq_of(&self, name: &str) -> Option<u64> {
+        self.pattern
+            .captures(name)?
+            .name("seq")?
+            .as_str()
+            .parse()
+            .ok()
+    }
+}
+
+/// Returns the hostname with the characters that can't be used in a snapshot name replaced
+/// with `-`
+fn hostname() -> String {
+    os::hostname()
+        .chars()
+        .map(|c| if is_name_char(c) { c } else { '-' })
+        .collect()
+}
+
+const fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')
+}
+
+impl FromStr for NameTemplate {
+    type Err = ();
+    fn from_str(st: &str) -> Result<NameTemplate, Self::Err> {
+        let mut parts = Vec::new();
+        let mut pattern = String::from("^");
+        let mut rest = st;
+        while !rest.is_empty() {
+            if let Some(placeholder) = rest.strip_prefix('{') {
+                let end = placeholder.find('}').ok_or(())?;
+                let (part, regex) = match &placeholder[..end] {
+                    "hostname" => (Part::Hostname, "[A-Za-z0-9_.-]+"),
+                    "date" => (Part::Date, "[0-9]{8}"),
+                    "time" => (Part::Time, "[0-9]{6}"),
+                    "timestamp" => (Part::Timestamp, "[0-9]+"),
+                    // there can only be one sequence number
+                    "seq" if !parts.contains(&Part::Seq) => (Part::Seq, "(?P<seq>[0-9]+)"),
+                    _ => return Err(()),
+                };
+                parts.push(part);
+                pattern.push_str(regex);
+                rest = &placeholder[end + 1..];
+            } else {
+                let end = rest.find('{').unwrap_or(rest.len());
+                let literal = &rest[..end];
+                if !literal.chars().all(is_name_char) {
+                    return Err(());
+                }
+                parts.push(Part::Literal(literal.to_owned()));
+                pattern.push_str(&regex::escape(literal));
+                rest = &rest[end..];
+            }
+        }
+        // names can't be `.` or `..` either
+        if !parts.iter().any(Part::is_unique) || matches!(st, "." | "..") {
+            return Err(());
+        }
+        pattern.push('$');
+        Ok(Self {
+            source: st.to_owned(),
+            parts,
+            pattern: Regex::new(&pattern).map_err(|_| ())?,
+        })
+    }
+}
+
+impl PartialEq for NameTemplate {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl fmt::Debug for NameTemplate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("NameTemplate").field(&self.source).finish()
+    }
+}
+
+#[test]
+fn test_name_template() {
+    use chrono::TimeZone;
+    let now = Utc.with_ymd_and_hms(2022, 11, 7, 10, 30, 5).unwrap();
+    // the default template gives the old names
+    let template = NameTemplate::new_default();
+    assert_eq!(template.render(now, 1), "20221107-103005");
+    assert!(template.matches("20221107-103005"));
+    assert!(!template.matches("20221107-1030"));
+    assert_eq!(template.seq_of("20221107-103005"), None);
+    // a template with a sequence number
+    let template: NameTemplate = "node1_{date}.{seq}".parse().unwrap();
+    let name = template.render(now, 42);
+    assert_eq!(name, "node1_20221107.000042");
+    assert_eq!(template.seq_of(&name), Some(42));
+    assert_eq!(template.seq_of("node2_20221107.000042"), None);
+    assert_eq!(template.render(now, 1234567), "node1_20221107.1234567");
+    // the hostname is always a legal name
+    let template: NameTemplate = "{hostname}-{timestamp}".parse().unwrap();
+    assert!(template.matches(&template.render(now, 1)));
+    // bad templates
+    for bad in [
+        "",
+        "{date}",
+        "{hostname}",
+        "{seq}-{seq}",
+        "{date}-{time",
+        "{date}-{minute}",
+        "../{seq}",
+        "{date} {time}",
+    ] {
+        assert!(bad.parse::<NameTemplate>().is_err(), "{bad}");
+    }
+}
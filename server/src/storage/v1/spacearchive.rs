@@ -0,0 +1,260 @@
+/*
+ * Created on Sun Nov 06 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Space archives
+//!
+//! A space archive holds a single keyspace (its options, its tables along with their models
+//! and options, and all their data) in one tar file under [`DIR_SPACES`], so that a tenant can
+//! be moved to another node or environment in one step: export it on one node, copy the file
+//! over, and import it on the other.
+//!
+//! The archive has the layout of a [backup](super::backup) with a `MANIFEST` in front, that
+//! names the keyspace and lists every file with its size and CRC32C, and that ends with a
+//! CRC32C of its own. Nothing is imported unless the whole archive verifies. Access control in
+//! Skytable is global (there are no grants scoped to a keyspace), so archives carry no auth data.
+
+use {
+    super::{
+        backup::{self, BackupError, BLOCK_SIZE},
+        checksum,
+        interface::DIR_SPACES,
+        unflush,
+    },
+    crate::corestore::memstore::{DdlError, Keyspace, Memstore, ObjectID},
+    core::{fmt::Write as _, slice},
+    std::{
+        collections::HashSet,
+        fs::{self, File},
+        io::{self, ErrorKind, Seek, SeekFrom, Write},
+        path::Path,
+    },
+};
+
+/// The name of the manifest in an archive
+const MANIFEST: &str = "MANIFEST";
+/// The first line of a manifest
+const MANIFEST_MAGIC: &str = "skytable-space-archive 1";
+
+/// The files in an archive, by name
+type ArchiveFiles<'a> = Vec<(&'a str, &'a [u8])>;
+
+/// An error that occurred while exporting or importing a space archive
+#[derive(Debug)]
+pub enum SpaceArchiveError {
+    /// The keyspace can't be exported or imported
+    Ddl(DdlError),
+    /// The archive failed verification
+    Corrupted(String),
+    /// Reading or writing the archive failed
+    Io(std::io::Error),
+}
+
+impl From<DdlError> for SpaceArchiveError {
+    fn from(e: DdlError) -> Self {
+        Self::Ddl(e)
+    }
+}
+
+impl From<std::io::Error> for SpaceArchiveError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<BackupError> for SpaceArchiveError {
+    fn from(e: BackupError) -> Self {
+        match e {
+            BackupError::Ddl(e) => Self::Ddl(e),
+            BackupError::Io(e) => Self::Io(e),
+        }
+    }
+}
+
+fn corrupted(reason: impl ToString) -> SpaceArchiveError {
+    SpaceArchiveError::Corrupted(reason.to_string())
+}
+
+/// Returns the path to the archive with the given name
+pub fn archive_path(name: &str) -> String {
+    format!("{DIR_SPACES}/{name}")
+}
+
+/// Export the keyspace `ksid` to a new archive called `name`
+pub fn export(store: &Memstore, ksid: &ObjectID, name: &str) -> Result<(), SpaceArchiveError> {
+    let path = archive_path(name);
+    if fs::metadata(&path).is_ok() {
+        return Err(DdlError::AlreadyExists.into());
+    }
+    if let Some(parent) = Path::new(&path).parent() {
+        try_dir_ignore_existing!(parent)?;
+    }
+    // the manifest comes first, but it can only be generated once every file has been written.
+    // So the files are streamed into a staging archive that is then copied in after the manifest
+    let staging = format!("{path}__");
+    // write to a temporary file first, so that a failed export never leaves a partial archive
+    let tmp = format!("{path}_");
+    let ret = (|| {
+        let mut body = File::options()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&staging)?;
+        let files = backup::write_files(&mut body, store, slice::from_ref(ksid))?;
+        let manifest = self::manifest(ksid, &files);
+        let mut archive = File::create(&tmp)?;
+        backup::write_tar_entry(&mut archive, MANIFEST, manifest.as_bytes())?;
+        body.seek(SeekFrom::Start(0))?;
+        io::copy(&mut body, &mut archive)?;
+        archive.write_all(&[0; BLOCK_SIZE * 2])?;
+        archive.sync_all()?;
+        fs::rename(&tmp, &path)?;
+        Ok(())
+    })();
+    let _ = fs::remove_file(&staging);
+    if ret.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    ret
+}
+
+/// Generate the manifest for the given files (with their sizes and CRC32Cs)
+fn manifest(ksid: &ObjectID, files: &[(String, u64, u32)]) -> String {
+    let mut manifest = format!("{MANIFEST_MAGIC}\nkeyspace {}\n", unsafe { ksid.as_str() });
+    for (name, size, crc) in files {
+        let _ = writeln!(manifest, "file {crc:08x} {size} {name}");
+    }
+    let crc = checksum::crc32c(manifest.as_bytes());
+    let _ = writeln!(manifest, "end {crc:08x}");
+    manifest
+}
+
+/// Verify and read the archive called `name`, returning the ID of the keyspace in it and the
+/// keyspace
+pub fn import(name: &str) -> Result<(ObjectID, Keyspace), SpaceArchiveError> {
+    let path = archive_path(name);
+    let archive = match fs::read(&path) {
+        Ok(archive) => archive,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Err(DdlError::ObjectNotFound.into()),
+        Err(e) => return Err(e.into()),
+    };
+    let files = backup::read_tar(&archive).ok_or_else(|| corrupted("malformed tar archive"))?;
+    let (ksid, files) = self::verify(&files)?;
+    // lay the files out like a snapshot so that the keyspace is read just like one
+    let root = format!("{path}_");
+    let _ = fs::remove_dir_all(&root);
+    let ret = (|| {
+        for (name, data) in files {
+            let filepath = Path::new(&root).join(name);
+            if let Some(parent) = filepath.parent() {
+                try_dir_ignore_existing!(parent)?;
+            }
+            fs::write(filepath, data)?;
+        }
+        unflush::read_keyspace_from(&root, &ksid).map_err(corrupted)
+    })();
+    if let Err(e) = fs::remove_dir_all(&root) {
+        log::warn!("Failed to remove temporary directory `{root}`: {e}");
+    }
+    Ok((ksid, ret?))
+}
+
+/// Verify the files in an archive against its manifest, returning the ID of the keyspace and
+/// the files (without the manifest)
+fn verify<'a>(
+    files: &'a [(String, &'a [u8])],
+) -> Result<(ObjectID, ArchiveFiles<'a>), SpaceArchiveError> {
+    let (manifest, files) = match files.split_first() {
+        Some(((name, manifest), files)) if name == MANIFEST => (*manifest, files),
+        _ => return Err(corrupted("missing manifest")),
+    };
+    let manifest = std::str::from_utf8(manifest).map_err(|_| corrupted("bad manifest"))?;
+    // everything up to the last line is covered by the checksum on the last line
+    let body_len = manifest
+        .trim_end_matches('\n')
+        .rfind('\n')
+        .map_or(0, |pos| pos + 1);
+    let (body, end) = manifest.split_at(body_len);
+    let crc = format!("end {:08x}\n", checksum::crc32c(body.as_bytes()));
+    if end != crc {
+        return Err(corrupted("manifest checksum mismatch"));
+    }
+    let mut lines = body.lines();
+    if lines.next() != Some(MANIFEST_MAGIC) {
+        return Err(corrupted("not a space archive"));
+    }
+    let ksid = lines
+        .next()
+        .and_then(|line| line.strip_prefix("keyspace "))
+        .filter(|ksid| is_identifier(ksid))
+        .and_then(ObjectID::try_from_slice)
+        .ok_or_else(|| corrupted("bad keyspace in manifest"))?;
+    let ksid_str = unsafe { ksid.as_str() };
+    let mut listed = HashSet::new();
+    for line in lines {
+        let mut parts = line
+            .strip_prefix("file ")
+            .unwrap_or_default()
+            .splitn(3, ' ');
+        let (crc, size, name) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(crc), Some(size), Some(name)) => (crc, size, name),
+            _ => return Err(corrupted("bad manifest")),
+        };
+        // only allow the files of this keyspace, since they're written to disk
+        let name_okay = name == "PRELOAD"
+            || name
+                .strip_prefix(ksid_str)
+                .and_then(|name| name.strip_prefix('/'))
+                .is_some_and(is_identifier);
+        if !name_okay || !listed.insert(name) {
+            return Err(corrupted(format!("unexpected file `{name}` in manifest")));
+        }
+        let data = files
+            .iter()
+            .find(|(file, _)| file == name)
+            .map(|(_, data)| *data)
+            .ok_or_else(|| corrupted(format!("missing file `{name}`")))?;
+        if size != data.len().to_string() || crc != format!("{:08x}", checksum::crc32c(data)) {
+            return Err(corrupted(format!("checksum mismatch for `{name}`")));
+        }
+    }
+    if files.len() != listed.len() {
+        return Err(corrupted("files missing from manifest"));
+    }
+    let files = files
+        .iter()
+        .map(|(name, data)| (name.as_str(), *data))
+        .collect();
+    Ok((ksid, files))
+}
+
+/// Returns true if `name` is a plain identifier (which every keyspace and table name is)
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .bytes()
+            .all(|byte| byte.is_ascii_alphanumeric() || byte == b'_')
+}
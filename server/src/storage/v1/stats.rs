@@ -0,0 +1,205 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Storage statistics
+//!
+//! Counters and timings for the flush, snapshot and unflush paths. They're kept in a global
+//! [`StorageStats`] (see [`stats`]) that is updated as the storage engine does its work, and
+//! read with [`StorageStats::report`], which is what `SYS STATS STORAGE` returns.
+//!
+//! Every counter is a relaxed atomic, so a report isn't a consistent snapshot across counters
+//! (a flush may have been counted while its bytes haven't been yet), but each counter is exact.
+
+use {
+    crate::util::os,
+    core::{
+        sync::atomic::{AtomicU64, Ordering},
+        time::Duration,
+    },
+};
+
+static STATS: StorageStats = StorageStats::new();
+
+/// Returns the global storage statistics
+pub fn stats() -> &'static StorageStats {
+    &STATS
+}
+
+/// Statistics for the storage engine
+pub struct StorageStats {
+    /// bytes written to data files by flushes (including snapshots)
+    bytes_written: AtomicU64,
+    /// the number of table files written
+    tables_flushed: AtomicU64,
+    /// the number of full flushes that completed
+    flushes: AtomicU64,
+    /// the duration of the last full flush, in milliseconds
+    last_flush_ms: AtomicU64,
+    /// the total duration of all full flushes, in milliseconds
+    total_flush_ms: AtomicU64,
+    /// when the last local snapshot completed (in seconds since the epoch), or 0 if no snapshot
+    /// was created since startup
+    last_snapshot_at: AtomicU64,
+    /// bytes of table files read on startup
+    bytes_read: AtomicU64,
+    /// the number of table files read on startup
+    tables_loaded: AtomicU64,
+    /// the duration of the startup load, in milliseconds
+    load_ms: AtomicU64,
+}
+
+impl StorageStats {
+    const fn new() -> Self {
+        Self {
+            bytes_written: AtomicU64::new(0),
+            tables_flushed: AtomicU64::new(0),
+            flushes: AtomicU64::new(0),
+            last_flush_ms: AtomicU64::new(0),
+            total_flush_ms: AtomicU64::new(0),
+            last_snapshot_at: AtomicU64::new(0),
+            bytes_read: AtomicU64::new(0),
+            tables_loaded: AtomicU64::new(0),
+            load_ms: AtomicU64::new(0),
+        }
+    }
+    /// Record that `bytes` were written to a data file
+    pub(super) fn record_write(&self, bytes: u64) {
+        self.bytes_written.fetch_add(bytes, Ordering::Relaxed);
+    }
+    /// Record that a table file was written
+    pub(super) fn record_table_flushed(&self) {
+        self.tables_flushed.fetch_add(1, Ordering::Relaxed);
+    }
+    /// Record that a full flush completed in `took`
+    pub(super) fn record_flush(&self, took: Duration) {
+        let took = took.as_millis() as u64;
+        self.flushes.fetch_add(1, Ordering::Relaxed);
+        self.last_flush_ms.store(took, Ordering::Relaxed);
+        self.total_flush_ms.fetch_add(took, Ordering::Relaxed);
+    }
+    /// Record that a local snapshot completed at `at` (in seconds since the epoch)
+    pub(super) fn record_snapshot(&self, at: u64) {
+        self.last_snapshot_at.store(at, Ordering::Relaxed);
+    }
+    /// Record that a table file of `bytes` bytes was read
+    pub(super) fn record_table_loaded(&self, bytes: u64) {
+        self.tables_loaded.fetch_add(1, Ordering::Relaxed);
+        self.bytes_read.fetch_add(bytes, Ordering::Relaxed);
+    }
+    /// Record that the startup load completed in `took`
+    pub(super) fn record_load(&self, took: Duration) {
+        self.load_ms
+            .store(took.as_millis() as u64, Ordering::Relaxed);
+    }
+    /// Returns the current values of the statistics, with the snapshot age computed relative to
+    /// `now` (in seconds since the epoch)
+    pub fn report(&self, now: u64) -> StorageReport {
+        let last_snapshot_at = self.last_snapshot_at.load(Ordering::Relaxed);
+        StorageReport {
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            tables_flushed: self.tables_flushed.load(Ordering::Relaxed),
+            flushes: self.flushes.load(Ordering::Relaxed),
+            last_flush_ms: self.last_flush_ms.load(Ordering::Relaxed),
+            total_flush_ms: self.total_flush_ms.load(Ordering::Relaxed),
+            last_snapshot_age: match last_snapshot_at {
+                0 => None,
+                at => Some(now.saturating_sub(at)),
+            },
+            bytes_read: self.bytes_read.load(Ordering::Relaxed),
+            tables_loaded: self.tables_loaded.load(Ordering::Relaxed),
+            load_ms: self.load_ms.load(Ordering::Relaxed),
+        }
+    }
+    /// Same as [`StorageStats::report`], relative to the current time
+    pub fn report_now(&self) -> StorageReport {
+        self.report(os::get_epoch_secs())
+    }
+}
+
+/// The values of the [`StorageStats`] at some point in time
+#[derive(Debug, PartialEq)]
+pub struct StorageReport {
+    pub bytes_written: u64,
+    pub tables_flushed: u64,
+    pub flushes: u64,
+    pub last_flush_ms: u64,
+    pub total_flush_ms: u64,
+    /// seconds since the last local snapshot completed, if one did since startup
+    pub last_snapshot_age: Option<u64>,
+    pub bytes_read: u64,
+    pub tables_loaded: u64,
+    pub load_ms: u64,
+}
+
+impl StorageReport {
+    /// Returns the report as `key = value` strings
+    pub fn entries(&self) -> Vec<String> {
+        let snapshot_age = self
+            .last_snapshot_age
+            .map_or_else(|| "none".to_owned(), |age| age.to_string());
+        vec![
+            format!("flush.bytes_written = {}", self.bytes_written),
+            format!("flush.tables_flushed = {}", self.tables_flushed),
+            format!("flush.count = {}", self.flushes),
+            format!("flush.last_duration_ms = {}", self.last_flush_ms),
+            format!("flush.total_duration_ms = {}", self.total_flush_ms),
+            format!("snapshot.last_age_secs = {snapshot_age}"),
+            format!("unflush.bytes_read = {}", self.bytes_read),
+            format!("unflush.tables_loaded = {}", self.tables_loaded),
+            format!("unflush.duration_ms = {}", self.load_ms),
+        ]
+    }
+}
+
+#[test]
+fn test_storage_stats() {
+    let stats = StorageStats::new();
+    assert_eq!(stats.report(100).last_snapshot_age, None);
+    stats.record_write(4096);
+    stats.record_write(100);
+    stats.record_table_flushed();
+    stats.record_flush(Duration::from_millis(30));
+    stats.record_flush(Duration::from_millis(12));
+    stats.record_snapshot(90);
+    stats.record_table_loaded(512);
+    stats.record_load(Duration::from_millis(7));
+    let report = stats.report(100);
+    assert_eq!(
+        report,
+        StorageReport {
+            bytes_written: 4196,
+            tables_flushed: 1,
+            flushes: 2,
+            last_flush_ms: 12,
+            total_flush_ms: 42,
+            last_snapshot_age: Some(10),
+            bytes_read: 512,
+            tables_loaded: 1,
+            load_ms: 7,
+        }
+    );
+    assert_eq!(report.entries()[5], "snapshot.last_age_secs = 10");
+}
@@ -0,0 +1,104 @@
+/*
+ * Created on Mon Nov 07 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Streaming encoders
+//!
+//! Tables can hold gigabytes of data, so they're never serialized into memory before being
+//! written out. A [`StreamEncoder`] sits in front of the destination and passes every record
+//! through a fixed size buffer while keeping count of the size and CRC32C of what was written.
+//! This lets writers that need to know the size or checksum of a file (like the headers of a
+//! tar archive or the manifest of a space archive) get them without holding the file in memory.
+
+use {
+    super::{checksum, flush::FlushableTable},
+    crate::IoResult,
+    std::io::{BufWriter, Write},
+};
+
+/// The size of the buffer used by a [`StreamEncoder`]
+pub const STREAM_BUFFER_SIZE: usize = 64 * 1024;
+
+/// A buffered writer that keeps track of the size and CRC32C of everything written to it.
+/// Make sure that you call [`StreamEncoder::finish`] once you're done, or the buffered bytes
+/// will never reach the destination
+pub struct StreamEncoder<W: Write> {
+    inner: BufWriter<W>,
+    written: u64,
+    crc: u32,
+}
+
+impl<W: Write> StreamEncoder<W> {
+    pub fn new(inner: W) -> Self {
+        Self {
+            inner: BufWriter::with_capacity(STREAM_BUFFER_SIZE, inner),
+            written: 0,
+            crc: 0,
+        }
+    }
+    /// Flush everything to the destination, returning the size and CRC32C of what was written
+    pub fn finish(mut self) -> IoResult<(u64, u32)> {
+        self.inner.flush()?;
+        Ok((self.written, self.crc))
+    }
+}
+
+impl<W: Write> Write for StreamEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        let written = self.inner.write(buf)?;
+        self.crc = checksum::crc32c_append(self.crc, &buf[..written]);
+        self.written += written as u64;
+        Ok(written)
+    }
+    fn flush(&mut self) -> IoResult<()> {
+        self.inner.flush()
+    }
+}
+
+/// Encode a table file (its header followed by its checksummed records) into `encoder`, one
+/// record at a time
+pub fn encode_table<W: Write, U: FlushableTable>(
+    encoder: &mut StreamEncoder<W>,
+    table: &U,
+) -> IoResult<()> {
+    encoder.write_all(&table.file_header().encode())?;
+    let mut checksummed = checksum::ChecksummedWriter::new(&mut *encoder);
+    table.write_table_to(&mut checksummed)?;
+    checksummed.flush()
+}
+
+#[test]
+fn test_stream_encoder() {
+    let payload: Vec<u8> = (0..STREAM_BUFFER_SIZE * 3 + 7).map(|i| i as u8).collect();
+    let mut file = Vec::new();
+    let mut encoder = StreamEncoder::new(&mut file);
+    for record in payload.chunks(1000) {
+        encoder.write_all(record).unwrap();
+    }
+    let (size, crc) = encoder.finish().unwrap();
+    assert_eq!(size, payload.len() as u64);
+    assert_eq!(crc, checksum::crc32c(&payload));
+    assert_eq!(file, payload);
+}
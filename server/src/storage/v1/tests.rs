@@ -198,7 +198,8 @@ mod interface_tests {
 
 mod preload_tests {
     use super::*;
-    use crate::corestore::memstore::Memstore;
+    use crate::corestore::memstore::{Memstore, ObjectID};
+    use std::{fs, path::Path};
     #[test]
     fn test_preload() {
         let memstore = Memstore::new_default();
@@ -211,6 +212,144 @@ mod preload_tests {
             .collect();
         assert_veceq!(de, vec!["default".to_owned(), "system".to_owned()]);
     }
+    #[test]
+    fn test_preload_v1() {
+        // a v1 preload is a v2 preload without the header
+        let memstore = Memstore::new_default();
+        let mut v = Vec::new();
+        preload::raw_generate_preload(&mut v, &memstore).unwrap();
+        let v1 = v.split_off(crate::storage::v2::header::HEADER_SIZE);
+        let de: Vec<String> = preload::read_preload_raw(v1)
+            .unwrap()
+            .into_iter()
+            .map(|each| unsafe { each.as_str().to_owned() })
+            .collect();
+        assert_veceq!(de, vec!["default".to_owned(), "system".to_owned()]);
+    }
+    #[test]
+    fn test_preload_long_names() {
+        // keyspaces with names that don't fit inline in an `ObjectID`
+        let memstore = Memstore::new_default();
+        let long = "a".repeat(ObjectID::MAX_LENGTH);
+        assert!(memstore.create_keyspace(ObjectID::try_from_slice(&long).unwrap()));
+        let mut v = Vec::new();
+        preload::raw_generate_preload(&mut v, &memstore).unwrap();
+        let de: Vec<String> = preload::read_preload_raw(v)
+            .unwrap()
+            .into_iter()
+            .map(|each| unsafe { each.as_str().to_owned() })
+            .collect();
+        assert_veceq!(
+            de,
+            vec!["default".to_owned(), "system".to_owned(), long.clone()]
+        );
+    }
+    #[test]
+    fn test_ksmeta() {
+        let ksid = ObjectID::try_from_slice("twitter").unwrap();
+        let mut v = Vec::new();
+        let ksmeta = preload::Ksmeta {
+            flush_interval: 300,
+            max_keys: 1_000,
+            max_bytes: 1 << 20,
+            table_shards: vec![(ObjectID::try_from_slice("tweets").unwrap(), 256)],
+            default_model: Some(ObjectID::try_from_slice("tweets").unwrap()),
+            ..Default::default()
+        };
+        preload::raw_generate_ksmeta(&mut v, &ksmeta).unwrap();
+        assert!(crate::storage::v2::header::is_v2(&v));
+        assert_eq!(preload::read_ksmeta_raw(&ksid, v.clone()).unwrap(), ksmeta);
+        // truncated
+        v.pop();
+        assert!(preload::read_ksmeta_raw(&ksid, v.clone()).is_err());
+        // the older formats were written by v1, so they have no header
+        v.drain(..crate::storage::v2::header::HEADER_SIZE);
+        // written before space properties were introduced (or with the default properties)
+        v.truncate(25 + 8 + 6 + 8);
+        assert_eq!(
+            preload::read_ksmeta_raw(&ksid, v.clone()).unwrap(),
+            preload::Ksmeta {
+                default_model: None,
+                ..ksmeta.clone()
+            }
+        );
+        // written before table shard counts were introduced
+        v.truncate(25);
+        assert_eq!(
+            preload::read_ksmeta_raw(&ksid, v.clone()).unwrap(),
+            preload::Ksmeta {
+                table_shards: Vec::new(),
+                default_model: None,
+                ..ksmeta
+            }
+        );
+        v.pop();
+        assert!(preload::read_ksmeta_raw(&ksid, v.clone()).is_err());
+        // written before quotas were introduced
+        v.truncate(9);
+        assert_eq!(
+            preload::read_ksmeta_raw(&ksid, v).unwrap(),
+            preload::Ksmeta {
+                flush_interval: 300,
+                ..Default::default()
+            }
+        );
+    }
+
+    /// A target that keeps `PRELOAD` generations like BGSAVE does
+    struct Generations;
+
+    impl flush::StorageTarget for Generations {
+        const NEEDS_TREE_INIT: bool = false;
+        const SHOULD_UNTRIP_PRELOAD_TRIPSWITCH: bool = false;
+        const SKIPS_CLEAN_TABLES: bool = true;
+        const KEEPS_PRELOAD_GENERATIONS: bool = true;
+        fn root(&self) -> String {
+            "data/preloadgentest".to_owned()
+        }
+    }
+
+    #[test]
+    fn test_preload_generations_rollback() {
+        const ROOT: &str = "data/preloadgentest";
+        const LOST_TO: &str = "data/preloadgentest-lost";
+        let store = Memstore::new_default();
+        for ks in ["default", "system"] {
+            fs::create_dir_all(format!("{ROOT}/{ks}")).unwrap();
+        }
+        for _ in 0..6 {
+            flush::oneshot::flush_preload(&Generations, &store).unwrap();
+        }
+        // only the latest generations are kept
+        assert_eq!(
+            preload::list_generations(ROOT).unwrap(),
+            vec![6, 5, 4, 3, 2]
+        );
+        // the latest generation has a keyspace that the ones before it don't
+        assert!(store.create_keyspace(ObjectID::try_from_slice("newks").unwrap()));
+        fs::create_dir_all(format!("{ROOT}/newks")).unwrap();
+        flush::oneshot::flush_preload(&Generations, &store).unwrap();
+        assert_eq!(preload::list_generations(ROOT).unwrap().len(), 5);
+        // now corrupt both the PRELOAD and the latest generation
+        fs::write(format!("{ROOT}/PRELOAD"), b"garbage").unwrap();
+        fs::write(preload::generation_path(ROOT, 7), b"garbage").unwrap();
+        let restored: Vec<String> = unflush::read_preload_or_rollback_from(ROOT, LOST_TO)
+            .unwrap()
+            .into_iter()
+            .map(|each| unsafe { each.as_str().to_owned() })
+            .collect();
+        assert_veceq!(restored, vec!["default".to_owned(), "system".to_owned()]);
+        // the keyspace that was lost is moved out of the way
+        assert!(!Path::new(&format!("{ROOT}/newks")).exists());
+        assert!(Path::new(&format!("{LOST_TO}/newks")).is_dir());
+        // and the PRELOAD is restored
+        assert_eq!(
+            fs::read(format!("{ROOT}/PRELOAD")).unwrap(),
+            fs::read(preload::generation_path(ROOT, 6)).unwrap()
+        );
+        fs::remove_dir_all(ROOT).unwrap();
+        fs::remove_dir_all(LOST_TO).unwrap();
+    }
 }
 
 mod bytemark_set_tests {
@@ -237,6 +376,43 @@ mod bytemark_set_tests {
         assert_hmeq!(expected, ret);
     }
     #[test]
+    fn test_partmap_format_compat() {
+        // the layout is the same one that was written when names were capped at 64 bytes
+        let ks = Keyspace::empty();
+        let short = "s".repeat(64);
+        let long = "l".repeat(ObjectID::MAX_LENGTH);
+        unsafe {
+            ks.create_table(ObjectID::from_slice(&short), Table::new_default_kve());
+        }
+        let mut v = Vec::new();
+        se::raw_serialize_partmap(&mut v, &ks).unwrap();
+        let mut expected = Vec::new();
+        expected.extend_from_slice(&1u64.to_ne_bytes());
+        expected.extend_from_slice(&64u64.to_ne_bytes());
+        expected.extend_from_slice(short.as_bytes());
+        expected.push(bytemarks::BYTEMARK_STORAGE_PERSISTENT);
+        expected.push(bytemarks::BYTEMARK_MODEL_KV_BIN_BIN);
+        assert_eq!(v, expected);
+        // and longer names are written the same way
+        unsafe {
+            ks.create_table(ObjectID::from_slice(&long), Table::new_default_kve());
+        }
+        let mut v = Vec::new();
+        se::raw_serialize_partmap(&mut v, &ks).unwrap();
+        let ret: HashMap<ObjectID, (u8, u8)> = de::deserialize_set_ctype_bytemark(&v).unwrap();
+        assert_eq!(ret.len(), 2);
+        let long_id = ObjectID::try_from_slice(&long).unwrap();
+        assert!(long_id.is_spilled());
+        assert_eq!(
+            ret[&long_id],
+            (
+                bytemarks::BYTEMARK_STORAGE_PERSISTENT,
+                bytemarks::BYTEMARK_MODEL_KV_BIN_BIN
+            )
+        );
+        assert!(!ret.keys().find(|id| id.len() == 64).unwrap().is_spilled());
+    }
+    #[test]
     fn test_bytemark_volatility_mixed() {
         let ks = Keyspace::empty();
         unsafe {
@@ -463,6 +639,75 @@ mod flush_routines {
         );
     }
 
+    #[test]
+    fn test_flush_unflush_table_expiry() {
+        let tbl = Table::new_default_kve();
+        let kve = tbl.get_kvstore().unwrap();
+        kve.set("hello".into(), "world".into()).unwrap();
+        kve.set("forever".into(), "young".into()).unwrap();
+        kve.set_expiry("hello", 60_000).unwrap();
+        let deadline = kve.expiry().get(b"hello").unwrap();
+        let tblid = unsafe { ObjectID::from_slice("mytbl_ttl") };
+        let ksid = unsafe { ObjectID::from_slice("myks_ttl") };
+        fs::create_dir_all("data/ks/myks_ttl").unwrap();
+        super::flush::oneshot::flush_table(&Autoflush, &tblid, &ksid, &tbl).unwrap();
+        let ret = super::unflush::read_table::<Table>(
+            &ksid,
+            &tblid,
+            false,
+            bytemarks::BYTEMARK_MODEL_KV_BIN_BIN,
+        )
+        .unwrap();
+        let kve = ret.get_kvstore().unwrap();
+        assert_eq!(kve.len(), 2);
+        assert_eq!(kve.expiry().get(b"hello"), Some(deadline));
+        assert_eq!(kve.expiry().get(b"forever"), None);
+    }
+
+    #[test]
+    fn test_unflush_table_checksum_mismatch() {
+        let tbl = Table::new_default_kve();
+        tbl.get_kvstore()
+            .unwrap()
+            .set("hello".into(), "world".into())
+            .unwrap();
+        let tblid = unsafe { ObjectID::from_slice("mytbl2") };
+        let ksid = unsafe { ObjectID::from_slice("myks2") };
+        fs::create_dir_all("data/ks/myks2").unwrap();
+        super::flush::oneshot::flush_table(&Autoflush, &tblid, &ksid, &tbl).unwrap();
+        // flip a bit in the payload
+        let mut data = fs::read("data/ks/myks2/mytbl2").unwrap();
+        let last = data.len() - 5;
+        data[last] ^= 1;
+        fs::write("data/ks/myks2/mytbl2", data).unwrap();
+        let ret = super::unflush::read_table::<Table>(
+            &ksid,
+            &tblid,
+            false,
+            bytemarks::BYTEMARK_MODEL_KV_BIN_BIN,
+        )
+        .unwrap_err();
+        assert_eq!(
+            ret.to_string(),
+            "checksum mismatch in file `data/ks/myks2/mytbl2` for the segment at offset 40"
+        );
+    }
+
+    #[test]
+    fn test_unflush_table_unknown_bytemark() {
+        let tbl = Table::new_default_kve();
+        let tblid = unsafe { ObjectID::from_slice("mytbl3") };
+        let ksid = unsafe { ObjectID::from_slice("myks3") };
+        fs::create_dir_all("data/ks/myks3").unwrap();
+        super::flush::oneshot::flush_table(&Autoflush, &tblid, &ksid, &tbl).unwrap();
+        // a model from a newer version
+        let ret = super::unflush::read_table::<Table>(&ksid, &tblid, false, 22).unwrap_err();
+        assert_eq!(
+            ret.to_string(),
+            "unknown model bytemark 22 (a model from a newer version of Skytable) in file `data/ks/myks3/mytbl3`"
+        );
+    }
+
     #[test]
     fn test_flush_unflush_table_kvext_listmap() {
         let tbl = Table::new_kve_listmap_with_data(Coremap::new(), false, true, true);
@@ -497,6 +742,158 @@ mod flush_routines {
         }
     }
     #[test]
+    fn test_flush_unflush_table_list_bounds() {
+        let tbl = Table::new_kve_listmap_with_data(Coremap::new(), false, true, true);
+        if let DataModel::KVExtListmap(kvl) = tbl.get_model_ref() {
+            kvl.add_list("feed".into()).unwrap();
+            kvl.add_list("unbounded".into()).unwrap();
+            assert!(kvl.set_list_bound(b"feed", Some(2)).unwrap());
+            kvl.set_expiry("feed", 60_000).unwrap();
+        } else {
+            panic!("Bad model!");
+        }
+        let tblid = unsafe { ObjectID::from_slice("mylists2") };
+        let ksid = unsafe { ObjectID::from_slice("mylistyks2") };
+        fs::create_dir_all("data/ks/mylistyks2").unwrap();
+        super::flush::oneshot::flush_table(&Autoflush, &tblid, &ksid, &tbl).unwrap();
+        let ret = super::unflush::read_table::<Table>(
+            &ksid,
+            &tblid,
+            false,
+            bytemarks::BYTEMARK_MODEL_KV_STR_LIST_STR,
+        )
+        .unwrap();
+        if let DataModel::KVExtListmap(kvl) = ret.get_model_ref() {
+            assert_eq!(kvl.list_bound(b"feed").unwrap(), Some(Some(2)));
+            assert_eq!(kvl.list_bound(b"unbounded").unwrap(), Some(None));
+            assert!(kvl.expiry().get(b"feed").is_some());
+        } else {
+            panic!("Bad model!");
+        }
+    }
+    #[test]
+    fn test_flush_unflush_table_kvext_map() {
+        let tbl = Table::new_kve_map_with_data(Coremap::new(), false, true, false);
+        if let DataModel::KVExtMap(kvm) = tbl.get_model_ref() {
+            let fields = vec![
+                ("name".into(), "sayan".into()),
+                ("avatar".into(), SharedSlice::from(&[0xFF, 0x00][..])),
+            ];
+            kvm.map_set("user".into(), fields).unwrap();
+        } else {
+            panic!("Bad model!");
+        }
+        let tblid = unsafe { ObjectID::from_slice("mymaps1") };
+        let ksid = unsafe { ObjectID::from_slice("mymapks") };
+        fs::create_dir_all("data/ks/mymapks").unwrap();
+        super::flush::oneshot::flush_table(&Autoflush, &tblid, &ksid, &tbl).unwrap();
+        let ret = super::unflush::read_table::<Table>(
+            &ksid,
+            &tblid,
+            false,
+            bytemarks::BYTEMARK_MODEL_KV_STR_MAP_BINSTR,
+        )
+        .unwrap();
+        assert_eq!(ret.get_model_code(), 10);
+        if let DataModel::KVExtMap(kvm) = ret.get_model_ref() {
+            let avatar = kvm.map_get(b"user", b"avatar").unwrap().unwrap();
+            assert_eq!(avatar, Some(SharedSlice::from(&[0xFF, 0x00][..])));
+            let fields = kvm.map_cloned_full(b"user").unwrap().unwrap();
+            assert_eq!(fields.len(), 2);
+        } else {
+            panic!("Bad model!");
+        }
+    }
+    #[test]
+    fn test_flush_unflush_table_kvext_set() {
+        let tbl = Table::new_kve_set_with_data(Coremap::new(), false, true, true);
+        if let DataModel::KVExtSet(kvs) = tbl.get_model_ref() {
+            let members = vec!["rust".into(), "go".into(), "rust".into()];
+            assert_eq!(kvs.set_add("langs".into(), members).unwrap(), 2);
+        } else {
+            panic!("Bad model!");
+        }
+        let tblid = unsafe { ObjectID::from_slice("mysets1") };
+        let ksid = unsafe { ObjectID::from_slice("mysetks") };
+        fs::create_dir_all("data/ks/mysetks").unwrap();
+        super::flush::oneshot::flush_table(&Autoflush, &tblid, &ksid, &tbl).unwrap();
+        let ret = super::unflush::read_table::<Table>(
+            &ksid,
+            &tblid,
+            false,
+            bytemarks::BYTEMARK_MODEL_KV_STR_SET_STR,
+        )
+        .unwrap();
+        assert_eq!(ret.get_model_code(), 15);
+        if let DataModel::KVExtSet(kvs) = ret.get_model_ref() {
+            assert!(kvs.set_contains(b"langs", b"rust").unwrap());
+            assert!(kvs.set_contains(b"langs", b"go").unwrap());
+            assert_eq!(kvs.set_members(b"langs").unwrap().unwrap().len(), 2);
+        } else {
+            panic!("Bad model!");
+        }
+    }
+    #[test]
+    fn test_flush_unflush_table_kvext_sorted_set() {
+        let tbl = Table::new_kve_sorted_set_with_data(Coremap::new(), false, true, true);
+        if let DataModel::KVExtSortedSet(kvz) = tbl.get_model_ref() {
+            let members = vec![("alice".into(), 10.0), ("bob".into(), -2.5)];
+            assert_eq!(kvz.zset_add("board".into(), members).unwrap(), 2);
+        } else {
+            panic!("Bad model!");
+        }
+        let tblid = unsafe { ObjectID::from_slice("myzsets1") };
+        let ksid = unsafe { ObjectID::from_slice("myzsetks") };
+        fs::create_dir_all("data/ks/myzsetks").unwrap();
+        super::flush::oneshot::flush_table(&Autoflush, &tblid, &ksid, &tbl).unwrap();
+        let ret = super::unflush::read_table::<Table>(
+            &ksid,
+            &tblid,
+            false,
+            bytemarks::BYTEMARK_MODEL_KV_STR_ZSET_STR,
+        )
+        .unwrap();
+        assert_eq!(ret.get_model_code(), 19);
+        if let DataModel::KVExtSortedSet(kvz) = ret.get_model_ref() {
+            assert_eq!(kvz.zset_score(b"board", b"bob").unwrap(), Some(-2.5));
+            assert_eq!(kvz.zset_rank(b"board", b"alice").unwrap(), Some(1));
+        } else {
+            panic!("Bad model!");
+        }
+    }
+    #[test]
+    fn test_flush_unflush_table_kvext_document() {
+        use crate::kvengine::document::{Json, Path};
+        let tbl = Table::new_kve_document_with_data(Coremap::new(), false, true);
+        let doc = Json::parse(br#"{"name":"sayan","langs":["rust",null],"age":21.5}"#).unwrap();
+        if let DataModel::KVExtDocument(kvd) = tbl.get_model_ref() {
+            let root = Path::parse(b"$").unwrap();
+            kvd.doc_set("user".into(), &root, doc.clone())
+                .unwrap()
+                .unwrap();
+        } else {
+            panic!("Bad model!");
+        }
+        let tblid = unsafe { ObjectID::from_slice("mydocs1") };
+        let ksid = unsafe { ObjectID::from_slice("mydocks") };
+        fs::create_dir_all("data/ks/mydocks").unwrap();
+        super::flush::oneshot::flush_table(&Autoflush, &tblid, &ksid, &tbl).unwrap();
+        let ret = super::unflush::read_table::<Table>(
+            &ksid,
+            &tblid,
+            false,
+            bytemarks::BYTEMARK_MODEL_KV_STR_JSON,
+        )
+        .unwrap();
+        assert_eq!(ret.get_model_code(), 21);
+        if let DataModel::KVExtDocument(kvd) = ret.get_model_ref() {
+            let root = Path::parse(b"$").unwrap();
+            assert_eq!(kvd.doc_get(b"user", &root).unwrap(), Some(doc.render()));
+        } else {
+            panic!("Bad model!");
+        }
+    }
+    #[test]
     fn test_flush_unflush_keyspace() {
         // create the temp dir for this test
         fs::create_dir_all("data/ks/myks_1").unwrap();
@@ -578,7 +975,7 @@ mod list_tests {
         let mut v = Vec::new();
         se::raw_serialize_nested_list(&mut v, &mylist).unwrap();
         let mut rawiter = RawSliceIter::new(&v);
-        let de = { de::deserialize_nested_list(rawiter.get_borrowed_iter()).unwrap() };
+        let de = { de::deserialize_nested_list(&mut rawiter).unwrap() };
         assert_eq!(de, mylist);
     }
     #[test]
@@ -592,7 +989,7 @@ mod list_tests {
         let mut v = Vec::new();
         se::raw_serialize_nested_list(&mut v, &mylist).unwrap();
         let mut rawiter = RawSliceIter::new(&v);
-        let de = { de::deserialize_nested_list(rawiter.get_borrowed_iter()).unwrap() };
+        let de = { de::deserialize_nested_list(&mut rawiter).unwrap() };
         assert_eq!(de, mylist);
     }
     #[test]
@@ -601,7 +998,7 @@ mod list_tests {
         let mut v = Vec::new();
         se::raw_serialize_nested_list(&mut v, &mylist).unwrap();
         let mut rawiter = RawSliceIter::new(&v);
-        let de = { de::deserialize_nested_list(rawiter.get_borrowed_iter()).unwrap() };
+        let de = { de::deserialize_nested_list(&mut rawiter).unwrap() };
         assert_eq!(de, mylist);
     }
     #[test]
@@ -666,6 +1063,112 @@ mod list_tests {
     }
 }
 
+mod map_tests {
+    use super::{de, se};
+    use crate::corestore::{htable::Coremap, SharedSlice};
+    use crate::kvengine::LockedMap;
+    use std::collections::HashMap;
+    #[test]
+    fn test_map_map_se_de() {
+        let mymap: Coremap<SharedSlice, LockedMap> = Coremap::new();
+        let fields: HashMap<SharedSlice, SharedSlice> = [
+            ("name".into(), "sayan".into()),
+            ("lang".into(), "rust".into()),
+            ("empty".into(), "".into()),
+        ]
+        .into_iter()
+        .collect();
+        mymap.true_if_insert("user".into(), LockedMap::new(fields.clone()));
+        mymap.true_if_insert("nobody".into(), LockedMap::default());
+        let mut v = Vec::new();
+        se::raw_serialize_map_map(&mymap, &mut v).unwrap();
+        let de = de::deserialize_map_map(&v).unwrap();
+        assert_eq!(de.len(), 2);
+        assert_eq!(*de.get("user".as_bytes()).unwrap().read(), fields);
+        assert!(de.get("nobody".as_bytes()).unwrap().read().is_empty());
+        // a truncated map is corrupted
+        assert!(de::deserialize_map_map(&v[..v.len() - 3]).is_none());
+    }
+}
+
+mod set_tests {
+    use super::{de, se};
+    use crate::corestore::{htable::Coremap, SharedSlice};
+    use crate::kvengine::LockedSet;
+    use std::collections::HashSet;
+    #[test]
+    fn test_set_map_se_de() {
+        let mymap: Coremap<SharedSlice, LockedSet> = Coremap::new();
+        let members: HashSet<SharedSlice> = ["sayan".into(), "rust".into(), "".into()]
+            .into_iter()
+            .collect();
+        mymap.true_if_insert("tags".into(), LockedSet::new(members.clone()));
+        mymap.true_if_insert("nothing".into(), LockedSet::default());
+        let mut v = Vec::new();
+        se::raw_serialize_set_map(&mymap, &mut v).unwrap();
+        let de = de::deserialize_set_map(&v).unwrap();
+        assert_eq!(de.len(), 2);
+        assert_eq!(*de.get("tags".as_bytes()).unwrap().read(), members);
+        assert!(de.get("nothing".as_bytes()).unwrap().read().is_empty());
+        // a truncated set is corrupted
+        assert!(de::deserialize_set_map(&v[..v.len() - 3]).is_none());
+    }
+}
+
+mod sorted_set_tests {
+    use super::{de, se};
+    use crate::corestore::{htable::Coremap, SharedSlice};
+    use crate::kvengine::{sortedset::SortedSet, LockedSortedSet};
+    #[test]
+    fn test_sorted_set_map_se_de() {
+        let mymap: Coremap<SharedSlice, LockedSortedSet> = Coremap::new();
+        let members: SortedSet = [
+            ("alice".into(), 10.0),
+            ("bob".into(), f64::NEG_INFINITY),
+            ("".into(), 0.5),
+        ]
+        .into_iter()
+        .collect();
+        mymap.true_if_insert("board".into(), LockedSortedSet::new(members.clone()));
+        mymap.true_if_insert("nothing".into(), LockedSortedSet::default());
+        let mut v = Vec::new();
+        se::raw_serialize_sorted_set_map(&mymap, &mut v).unwrap();
+        let de = de::deserialize_sorted_set_map(&v).unwrap();
+        assert_eq!(de.len(), 2);
+        assert_eq!(*de.get("board".as_bytes()).unwrap().read(), members);
+        assert!(de.get("nothing".as_bytes()).unwrap().read().is_empty());
+        // a truncated sorted set is corrupted
+        assert!(de::deserialize_sorted_set_map(&v[..v.len() - 3]).is_none());
+    }
+}
+
+mod document_tests {
+    use super::{de, se};
+    use crate::corestore::{htable::Coremap, SharedSlice};
+    use crate::kvengine::{document::Json, LockedDocument};
+    #[test]
+    fn test_document_map_se_de() {
+        let mymap: Coremap<SharedSlice, LockedDocument> = Coremap::new();
+        let doc = Json::parse(br#"{"a":[1,2.5,{"b":"c"}],"d":true,"e":null}"#).unwrap();
+        mymap.true_if_insert("doc".into(), LockedDocument::new(doc.clone()));
+        mymap.true_if_insert(
+            "empty".into(),
+            LockedDocument::new(Json::Str(String::new())),
+        );
+        let mut v = Vec::new();
+        se::raw_serialize_document_map(&mymap, &mut v).unwrap();
+        let de = de::deserialize_document_map(&v).unwrap();
+        assert_eq!(de.len(), 2);
+        assert_eq!(*de.get("doc".as_bytes()).unwrap().read(), doc);
+        assert_eq!(
+            *de.get("empty".as_bytes()).unwrap().read(),
+            Json::Str(String::new())
+        );
+        // a truncated document is corrupted
+        assert!(de::deserialize_document_map(&v[..v.len() - 3]).is_none());
+    }
+}
+
 mod corruption_tests {
     use crate::corestore::htable::Coremap;
     use crate::corestore::SharedSlice;
@@ -756,13 +1259,16 @@ mod storage_target_directory_structure {
             FileKind::Dir("default"),
             FileKind::File("default/default"),
             FileKind::File("default/PARTMAP"),
+            FileKind::File("default/KSMETA"),
             // the superks keyspace
             FileKind::Dir("superks"),
             FileKind::File("superks/PARTMAP"),
+            FileKind::File("superks/KSMETA"),
             FileKind::File("superks/blueshark"),
             // the system keyspace
             FileKind::Dir("system"),
             FileKind::File("system/PARTMAP"),
+            FileKind::File("system/KSMETA"),
             FileKind::File("system/superauthy"),
             // the preload file
             FileKind::File("PRELOAD"),
@@ -826,3 +1332,607 @@ mod storage_target_directory_structure {
         fs::remove_dir_all("data/rsnap/wisnap").unwrap();
     }
 }
+
+mod backup_tests {
+    use crate::{
+        corestore::{
+            memstore::{DdlError, Memstore, ObjectID, SYSTEM},
+            table::Table,
+        },
+        storage::v1::backup::{self, BackupError},
+    };
+    use std::fs;
+
+    /// Returns the paths of the entries in a tar archive
+    fn tar_entries(archive: &[u8]) -> Vec<String> {
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        while archive[offset] != 0 {
+            let header = &archive[offset..offset + 512];
+            let field = |range: std::ops::Range<usize>| {
+                let field = &header[range];
+                let end = field
+                    .iter()
+                    .position(|byte| *byte == 0)
+                    .unwrap_or(field.len());
+                String::from_utf8(field[..end].to_vec()).unwrap()
+            };
+            let (name, prefix) = (field(0..100), field(345..500));
+            let size = usize::from_str_radix(&field(124..135), 8).unwrap();
+            entries.push(if prefix.is_empty() {
+                name
+            } else {
+                format!("{prefix}/{name}")
+            });
+            offset += 512 + size.div_ceil(512) * 512;
+        }
+        entries
+    }
+
+    #[test]
+    fn test_backup_keyspace() {
+        let store = Memstore::new_empty();
+        let ksid = unsafe { ObjectID::from_slice("mybackupks") };
+        assert!(store.create_keyspace(ksid.clone()));
+        let ks = store.get_keyspace_atomic_ref(&ksid).unwrap();
+        let tbl = Table::new_default_kve();
+        tbl.get_kvstore()
+            .unwrap()
+            .set("hello".into(), "world".into())
+            .unwrap();
+        assert!(ks.create_table(unsafe { ObjectID::from_slice("mytbl") }, tbl));
+        assert!(ks.create_table(
+            unsafe { ObjectID::from_slice("myvolatile") },
+            Table::new_kve_with_volatile(true)
+        ));
+        backup::create(&store, "mybackup1", std::slice::from_ref(&ksid)).unwrap();
+        let archive = fs::read(backup::backup_path("mybackup1")).unwrap();
+        fs::remove_file(backup::backup_path("mybackup1")).unwrap();
+        assert_eq!(
+            tar_entries(&archive),
+            [
+                "PRELOAD",
+                "mybackupks/PARTMAP",
+                "mybackupks/KSMETA",
+                "mybackupks/mytbl"
+            ]
+        );
+        // the system keyspace and missing keyspaces can't be backed up
+        assert!(matches!(
+            backup::create(&store, "mybackup2", &[SYSTEM]),
+            Err(BackupError::Ddl(DdlError::ProtectedObject))
+        ));
+        assert!(matches!(
+            backup::create(
+                &store,
+                "mybackup2",
+                &[ksid, unsafe { ObjectID::from_slice("nope") }]
+            ),
+            Err(BackupError::Ddl(DdlError::ObjectNotFound))
+        ));
+    }
+}
+
+mod restore_tests {
+    use crate::{
+        corestore::{
+            memstore::{Memstore, ObjectID},
+            table::Table,
+            SharedSlice,
+        },
+        storage::v1::{
+            flush::{self, LocalSnapshot},
+            unflush,
+        },
+    };
+    use std::fs;
+
+    #[test]
+    fn test_read_keyspace_from_snapshot() {
+        let store = Memstore::new_default();
+        let ksid = unsafe { ObjectID::from_slice("myrestoreks") };
+        assert!(store.create_keyspace(ksid.clone()));
+        let tbl = Table::new_default_kve();
+        tbl.get_kvstore()
+            .unwrap()
+            .set("hello".into(), "world".into())
+            .unwrap();
+        let ks = store.get_keyspace_atomic_ref(&ksid).unwrap();
+        assert!(ks.create_table(unsafe { ObjectID::from_slice("mytbl") }, tbl));
+        fs::create_dir_all("data/snaps/myrestoresnap").unwrap();
+        flush::flush_full(LocalSnapshot::new("myrestoresnap".to_owned()), &store).unwrap();
+        let restored = unflush::read_keyspace_from_snapshot("myrestoresnap", &ksid)
+            .unwrap()
+            .unwrap();
+        let restored_tbl = restored
+            .get_table_atomic_ref(&unsafe { ObjectID::from_slice("mytbl") })
+            .unwrap();
+        assert_eq!(
+            restored_tbl
+                .get_kvstore()
+                .unwrap()
+                .get(&SharedSlice::from("hello"))
+                .unwrap()
+                .unwrap()
+                .clone(),
+            SharedSlice::from("world")
+        );
+        // keyspaces and snapshots that don't exist
+        let nope = unsafe { ObjectID::from_slice("nope") };
+        assert!(unflush::read_keyspace_from_snapshot("myrestoresnap", &nope)
+            .unwrap()
+            .is_none());
+        assert!(unflush::read_keyspace_from_snapshot("nosnap", &ksid)
+            .unwrap()
+            .is_none());
+        fs::remove_dir_all("data/snaps/myrestoresnap").unwrap();
+    }
+}
+
+mod migration_tests {
+    use crate::{
+        corestore::{
+            memstore::{Keyspace, Memstore, ObjectID},
+            table::Table,
+            SharedSlice,
+        },
+        storage::{
+            v1::{
+                flush::{self, LocalSnapshot},
+                preload, unflush,
+            },
+            v2::{
+                self,
+                header::{FileKind, Header, HEADER_SIZE},
+            },
+        },
+        util::os::{self, EntryKind},
+    };
+    use std::fs;
+
+    const ROOT: &str = "data/snaps/mymigratesnap";
+
+    /// Returns the paths of all the files in the tree
+    fn files() -> Vec<String> {
+        os::rlistdir(ROOT)
+            .unwrap()
+            .into_iter()
+            .filter_map(|entry| match entry {
+                EntryKind::File(path) => Some(path),
+                EntryKind::Directory(_) => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_migrate_from_v1() {
+        let store = Memstore::new_default();
+        let ksid = unsafe { ObjectID::from_slice("mymigrateks") };
+        assert!(store.create_keyspace(ksid.clone()));
+        let tbl = Table::new_default_kve();
+        tbl.get_kvstore()
+            .unwrap()
+            .set("hello".into(), "world".into())
+            .unwrap();
+        let ks = store.get_keyspace_atomic_ref(&ksid).unwrap();
+        assert!(ks.create_table(unsafe { ObjectID::from_slice("mytbl") }, tbl));
+        // a v1 tree is a v2 tree without the headers
+        fs::create_dir_all(ROOT).unwrap();
+        flush::flush_full(LocalSnapshot::new("mymigratesnap".to_owned()), &store).unwrap();
+        for file in files() {
+            let mut data = fs::read(&file).unwrap();
+            data.drain(..HEADER_SIZE);
+            fs::write(&file, data).unwrap();
+        }
+        v2::migrate(ROOT, &store).unwrap();
+        // the PRELOAD is only written once, which is when its first generation is saved
+        assert_eq!(preload::list_generations(ROOT).unwrap(), [1]);
+        for file in files() {
+            let data = fs::read(&file).unwrap();
+            let name = file.rsplit('/').next().unwrap();
+            let kind = match name {
+                "PARTMAP" => FileKind::Partmap,
+                "KSMETA" => FileKind::Ksmeta,
+                _ if name.starts_with("PRELOAD") => FileKind::Preload,
+                _ if file.contains("/system/") => FileKind::SystemTable,
+                _ => FileKind::Table,
+            };
+            assert_eq!(Header::decode(&data, &file).unwrap().kind, kind, "{file}");
+        }
+        // now read every file back
+        let preload = preload::read_preload_raw(fs::read(format!("{ROOT}/PRELOAD")).unwrap());
+        assert!(preload.unwrap().contains(&ksid));
+        for ksid in ["default", "system", "mymigrateks"] {
+            let ksid = unsafe { ObjectID::from_slice(ksid) };
+            unflush::read_ksmeta(ROOT, &ksid).unwrap();
+            unflush::read_partmap(ROOT, &ksid).unwrap();
+        }
+        let restored: Keyspace = unflush::read_keyspace_from(ROOT, &ksid).unwrap();
+        let restored_tbl = restored
+            .get_table_atomic_ref(&unsafe { ObjectID::from_slice("mytbl") })
+            .unwrap();
+        assert_eq!(
+            restored_tbl
+                .get_kvstore()
+                .unwrap()
+                .get(SharedSlice::from("hello"))
+                .unwrap()
+                .unwrap()
+                .clone(),
+            SharedSlice::from("world")
+        );
+        fs::remove_dir_all(ROOT).unwrap();
+    }
+}
+
+mod mmap_tests {
+    use crate::storage::v1::mmap::MappedFile;
+    use std::fs;
+
+    #[test]
+    fn test_mapped_file() {
+        fs::write("mmap_test_file", b"hello, world").unwrap();
+        fs::write("mmap_test_empty", b"").unwrap();
+        let mapped = MappedFile::open("mmap_test_file").unwrap();
+        assert_eq!(&*mapped, b"hello, world");
+        let empty = MappedFile::open("mmap_test_empty").unwrap();
+        assert!(empty.is_empty());
+        drop((mapped, empty));
+        fs::remove_file("mmap_test_file").unwrap();
+        fs::remove_file("mmap_test_empty").unwrap();
+        assert!(MappedFile::open("mmap_test_file").is_err());
+    }
+
+    #[test]
+    fn test_checksummed_file_is_read_in_place() {
+        use crate::{
+            corestore::{table::Table, SharedSlice},
+            storage::v1::{
+                checksum::{self, ChecksummedWriter},
+                de::{self, WithExpiry},
+                flush::FlushableTable,
+            },
+        };
+        use std::io::Write;
+        // a table that takes up a few segments, with entries that straddle them
+        let tbl = Table::new_default_kve();
+        let kve = tbl.get_kvstore().unwrap();
+        for i in 0..200u8 {
+            let value = SharedSlice::from(vec![i; 1000]);
+            kve.set(format!("key{i}").into(), value).unwrap();
+        }
+        let mut file = Vec::new();
+        let mut writer = ChecksummedWriter::new(&mut file);
+        tbl.write_table_to(&mut writer).unwrap();
+        writer.flush().unwrap();
+        fs::write("mmap_test_checksummed", &file).unwrap();
+        let mapped = MappedFile::open("mmap_test_checksummed").unwrap();
+        let segments = checksum::segments(&mapped, "mmap_test_checksummed", 0).unwrap();
+        assert_eq!(segments.len(), file.len() / checksum::SEGMENT_SIZE + 1);
+        // every segment is borrowed from the mapping, so the payload is never copied out
+        let mapping = mapped.as_ptr_range();
+        assert!(segments
+            .iter()
+            .all(|segment| mapping.contains(&segment.as_ptr())
+                && segment.as_ptr_range().end <= mapping.end));
+        let (map, _): WithExpiry<SharedSlice> = de::deserialize_segments_into(&segments).unwrap();
+        assert_eq!(map.len(), 200);
+        for i in 0..200u8 {
+            assert_eq!(
+                map.get(format!("key{i}").as_bytes()).unwrap().as_ref(),
+                [i; 1000]
+            );
+        }
+        drop(mapped);
+        fs::remove_file("mmap_test_checksummed").unwrap();
+    }
+}
+
+mod parallel_flush_tests {
+    use crate::{
+        corestore::{
+            memstore::{Memstore, ObjectID},
+            table::Table,
+            SharedSlice,
+        },
+        storage::v1::{
+            flush::{self, LocalSnapshot},
+            unflush,
+        },
+    };
+    use std::fs;
+
+    #[test]
+    fn test_flush_full_concurrently() {
+        flush::set_flush_workers(3);
+        let store = Memstore::new_default();
+        let keyspaces = ["myparallelks1", "myparallelks2"];
+        for ks in keyspaces {
+            let ksid = unsafe { ObjectID::from_slice(ks) };
+            assert!(store.create_keyspace(ksid.clone()));
+            let ks = store.get_keyspace_atomic_ref(&ksid).unwrap();
+            for i in 0..5 {
+                let tbl = Table::new_default_kve();
+                tbl.get_kvstore()
+                    .unwrap()
+                    .set("table".into(), format!("mytbl{i}").into())
+                    .unwrap();
+                let tblid = unsafe { ObjectID::from_slice(format!("mytbl{i}")) };
+                assert!(ks.create_table(tblid, tbl));
+            }
+        }
+        fs::create_dir_all("data/snaps/myparallelsnap").unwrap();
+        flush::flush_full(LocalSnapshot::new("myparallelsnap".to_owned()), &store).unwrap();
+        for ks in keyspaces {
+            let ksid = unsafe { ObjectID::from_slice(ks) };
+            let restored = unflush::read_keyspace_from_snapshot("myparallelsnap", &ksid)
+                .unwrap()
+                .unwrap();
+            for i in 0..5 {
+                let tblid = unsafe { ObjectID::from_slice(format!("mytbl{i}")) };
+                let tbl = restored.get_table_atomic_ref(&tblid).unwrap();
+                assert_eq!(
+                    tbl.get_kvstore()
+                        .unwrap()
+                        .get(SharedSlice::from("table"))
+                        .unwrap()
+                        .unwrap()
+                        .clone(),
+                    SharedSlice::from(format!("mytbl{i}"))
+                );
+            }
+        }
+        fs::remove_dir_all("data/snaps/myparallelsnap").unwrap();
+    }
+}
+
+mod fsync_tests {
+    use crate::{config::SyncPolicy, storage::v1::fsync};
+    use std::fs::{self, File};
+
+    #[test]
+    fn test_sync_everysec() {
+        fs::create_dir_all("data/fsynctest").unwrap();
+        fsync::set_policy(SyncPolicy::EverySec);
+        let f = File::create("data/fsynctest/tbl_").unwrap();
+        fsync::sync_and_rename(&f, "data/fsynctest/tbl_", "data/fsynctest/tbl").unwrap();
+        let f = File::create("data/fsynctest/gone_").unwrap();
+        fsync::sync_and_rename(&f, "data/fsynctest/gone_", "data/fsynctest/gone").unwrap();
+        fsync::set_policy(SyncPolicy::Always);
+        // the rename isn't held back by the sync
+        assert!(fs::metadata("data/fsynctest/tbl").is_ok());
+        assert!(fs::metadata("data/fsynctest/tbl_").is_err());
+        // only the directory is left to sync
+        fs::remove_file("data/fsynctest/gone").unwrap();
+        fsync::sync_pending().unwrap();
+        // and directories that were removed in the meantime are skipped
+        let f = File::create("data/fsynctest/tbl_").unwrap();
+        fsync::set_policy(SyncPolicy::EverySec);
+        fsync::sync_and_rename(&f, "data/fsynctest/tbl_", "data/fsynctest/tbl").unwrap();
+        fsync::set_policy(SyncPolicy::Always);
+        fs::remove_dir_all("data/fsynctest").unwrap();
+        fsync::sync_pending().unwrap();
+    }
+
+    #[test]
+    fn test_replace() {
+        fs::create_dir_all("data/replacetest").unwrap();
+        // nothing to replace yet
+        fs::write("data/replacetest/tbl_", b"first").unwrap();
+        fsync::replace("data/replacetest/tbl_", "data/replacetest/tbl").unwrap();
+        assert_eq!(fs::read("data/replacetest/tbl").unwrap(), b"first");
+        // replace an existing file
+        fs::write("data/replacetest/tbl_", b"second").unwrap();
+        fsync::replace("data/replacetest/tbl_", "data/replacetest/tbl").unwrap();
+        assert_eq!(fs::read("data/replacetest/tbl").unwrap(), b"second");
+        assert!(fs::metadata("data/replacetest/tbl_").is_err());
+        // a missing replacement leaves the existing file alone
+        assert!(fsync::replace("data/replacetest/tbl_", "data/replacetest/tbl").is_err());
+        assert_eq!(fs::read("data/replacetest/tbl").unwrap(), b"second");
+        fs::remove_dir_all("data/replacetest").unwrap();
+    }
+}
+
+mod dirty_table_tests {
+    use crate::{
+        corestore::{
+            memstore::{Memstore, ObjectID},
+            table::Table,
+        },
+        storage::v1::flush::{self, StorageTarget},
+    };
+    use std::fs;
+
+    /// A target that overwrites its files like BGSAVE does
+    struct Overwrite;
+
+    impl StorageTarget for Overwrite {
+        const NEEDS_TREE_INIT: bool = true;
+        const SHOULD_UNTRIP_PRELOAD_TRIPSWITCH: bool = false;
+        const SKIPS_CLEAN_TABLES: bool = true;
+        const KEEPS_PRELOAD_GENERATIONS: bool = false;
+        fn root(&self) -> String {
+            "data/dirtytest".to_owned()
+        }
+    }
+
+    #[test]
+    fn test_flush_skips_clean_tables() {
+        const TABLE_FILE: &str = "data/dirtytest/mydirtyks/mytbl";
+        let store = Memstore::new_default();
+        let ksid = unsafe { ObjectID::from_slice("mydirtyks") };
+        assert!(store.create_keyspace(ksid.clone()));
+        let ks = store.get_keyspace_atomic_ref(&ksid).unwrap();
+        assert!(ks.create_table(
+            unsafe { ObjectID::from_slice("mytbl") },
+            Table::new_default_kve()
+        ));
+        let table = ks
+            .get_table_atomic_ref(&unsafe { ObjectID::from_slice("mytbl") })
+            .unwrap();
+        fs::create_dir_all("data/dirtytest").unwrap();
+        // never flushed, so it's written
+        flush::flush_full(Overwrite, &store).unwrap();
+        assert!(fs::metadata(TABLE_FILE).is_ok());
+        // unchanged, so it isn't written again
+        fs::remove_file(TABLE_FILE).unwrap();
+        flush::flush_full(Overwrite, &store).unwrap();
+        assert!(fs::metadata(TABLE_FILE).is_err());
+        // changed, so it's written again
+        table
+            .get_kvstore()
+            .unwrap()
+            .set("hello".into(), "world".into())
+            .unwrap();
+        flush::flush_full(Overwrite, &store).unwrap();
+        assert!(fs::metadata(TABLE_FILE).is_ok());
+        fs::remove_dir_all("data/dirtytest").unwrap();
+    }
+}
+
+mod repair_tests {
+    use crate::{
+        corestore::{
+            memstore::{Keyspace, ObjectID},
+            table::Table,
+        },
+        storage::v1::{
+            flush::{self, Autoflush},
+            repair::{self, Finding, RepairReport},
+        },
+    };
+    use std::fs;
+
+    #[test]
+    fn test_salvage_keyspace() {
+        const TABLE_FILE: &str = "data/ks/myrepairks/mytbl";
+        let ksid = unsafe { ObjectID::from_slice("myrepairks") };
+        let tblid = unsafe { ObjectID::from_slice("mytbl") };
+        let ks = Keyspace::empty();
+        let tbl = Table::new_default_kve();
+        for i in 0..1000 {
+            tbl.get_kvstore()
+                .unwrap()
+                .set(format!("key-{i:04}").into(), "v".repeat(100).into())
+                .unwrap();
+        }
+        assert!(ks.create_table(tblid.clone(), tbl));
+        assert!(ks.create_table(
+            unsafe { ObjectID::from_slice("mygonetbl") },
+            Table::new_default_kve()
+        ));
+        fs::create_dir_all("data/ks/myrepairks").unwrap();
+        flush::flush_keyspace_full(&Autoflush, &ksid, &ks).unwrap();
+        // damage the second segment of one table and lose the other table entirely
+        let mut data = fs::read(TABLE_FILE).unwrap();
+        data[65590] ^= 1;
+        fs::write(TABLE_FILE, data).unwrap();
+        fs::remove_file("data/ks/myrepairks/mygonetbl").unwrap();
+        let mut report = RepairReport::default();
+        let salvaged = repair::salvage_keyspace(&ksid, &mut report).unwrap();
+        fs::remove_dir_all("data/ks/myrepairks").unwrap();
+        // every entry that ended in the first segment is salvaged; 16B of lengths, 8B of key
+        // and 100B of value per entry
+        let kve = salvaged.get_table_atomic_ref(&tblid).unwrap();
+        let kve = kve.get_kvstore().unwrap();
+        assert_eq!(kve.len(), 528);
+        assert_eq!(report.tables, 1);
+        assert_eq!(report.keys, 528);
+        assert_eq!(report.findings.len(), 2);
+        assert!(report.findings.iter().any(|finding| matches!(
+            finding,
+            Finding::DroppedTable(table, _) if table == "myrepairks:mygonetbl"
+        )));
+        // the key of the entry that was cut off by the damaged segment can still be named
+        let (keys, unreadable, reason) = report
+            .findings
+            .iter()
+            .find_map(|finding| match finding {
+                Finding::DroppedKeys {
+                    table,
+                    keys,
+                    unreadable,
+                    reason,
+                } if table == "myrepairks:mytbl" => Some((keys, unreadable, reason)),
+                _ => None,
+            })
+            .unwrap();
+        assert_eq!(keys.len(), 1);
+        assert!(keys[0].starts_with("key-"));
+        assert!(kve.get(&keys[0]).unwrap().is_none());
+        assert_eq!(*unreadable, Some(471));
+        assert_eq!(
+            reason,
+            "checksum mismatch in file `data/ks/myrepairks/mytbl` for the segment at offset 65580"
+        );
+    }
+}
+
+mod spacearchive_tests {
+    use crate::{
+        corestore::{
+            memstore::{DdlError, Memstore, ObjectID},
+            table::Table,
+        },
+        storage::v1::spacearchive::{self, SpaceArchiveError},
+    };
+    use std::fs;
+
+    #[test]
+    fn test_space_archive_roundtrip() {
+        let store = Memstore::new_empty();
+        let ksid = unsafe { ObjectID::from_slice("myspaceks") };
+        let (tblid, volatileid) = unsafe {
+            (
+                ObjectID::from_slice("mytbl"),
+                ObjectID::from_slice("myvolatile"),
+            )
+        };
+        assert!(store.create_keyspace(ksid.clone()));
+        let ks = store.get_keyspace_atomic_ref(&ksid).unwrap();
+        let tbl = Table::new_default_kve();
+        tbl.get_kvstore()
+            .unwrap()
+            .set("hello".into(), "world".into())
+            .unwrap();
+        assert!(ks.create_table(tblid.clone(), tbl));
+        assert!(ks.create_table(volatileid.clone(), Table::new_kve_with_volatile(true)));
+        spacearchive::export(&store, &ksid, "myspace.tar").unwrap();
+        assert!(matches!(
+            spacearchive::export(&store, &ksid, "myspace.tar"),
+            Err(SpaceArchiveError::Ddl(DdlError::AlreadyExists))
+        ));
+        let (imported_ksid, imported) = spacearchive::import("myspace.tar").unwrap();
+        assert_eq!(imported_ksid, ksid);
+        let imported_tbl = imported.get_table_atomic_ref(&tblid).unwrap();
+        assert_eq!(
+            imported_tbl
+                .get_kvstore()
+                .unwrap()
+                .get_cloned("hello")
+                .unwrap()
+                .unwrap(),
+            "world".as_bytes()
+        );
+        assert!(imported
+            .get_table_atomic_ref(&volatileid)
+            .unwrap()
+            .is_volatile());
+        // a single flipped bit fails verification
+        let path = spacearchive::archive_path("myspace.tar");
+        let mut archive = fs::read(&path).unwrap();
+        let world = archive
+            .windows(5)
+            .position(|window| window == b"world")
+            .unwrap();
+        archive[world] ^= 1;
+        fs::write(&path, archive).unwrap();
+        let ret = spacearchive::import("myspace.tar");
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(ret, Err(SpaceArchiveError::Corrupted(_))));
+        assert!(matches!(
+            spacearchive::import("myspace.tar"),
+            Err(SpaceArchiveError::Ddl(DdlError::ObjectNotFound))
+        ));
+    }
+}
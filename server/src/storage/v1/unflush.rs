@@ -29,65 +29,101 @@
 //! Routines for unflushing data
 
 use {
-    super::bytemarks,
+    super::bytemarks::{self, ModelKind, UnknownBytemark},
     crate::{
         corestore::{
             memstore::{Keyspace, Memstore, ObjectID, SystemKeyspace, SYSTEM},
             table::{SystemTable, Table},
         },
         storage::v1::{
+            checksum,
             de::DeserializeInto,
             error::{ErrorContext, StorageEngineError, StorageEngineResult},
-            flush::Autoflush,
-            interface::DIR_KSROOT,
-            preload::LoadedPartfile,
+            flush::{Autoflush, FlushableTable},
+            fsync,
+            interface::{DIR_KSROOT, DIR_REPAIRROOT, DIR_RSNAPROOT, DIR_SNAPROOT},
+            mmap::MappedFile,
+            preload::{self, Ksmeta, LoadedPartfile},
             Coremap,
         },
+        storage::v2::header::{self, FileKind, ModelDescriptor},
         util::Wrapper,
     },
-    core::mem::transmute,
-    std::{fs, io::ErrorKind, path::Path, sync::Arc},
+    chrono::prelude::Utc,
+    std::{fs, io::ErrorKind, path::Path, sync::Arc, time::Instant},
 };
 
 type PreloadSet = std::collections::HashSet<ObjectID>;
-const PRELOAD_PATH: &str = "data/ks/PRELOAD";
 
 /// A keyspace that can be restored from disk storage
 pub trait UnflushableKeyspace: Sized {
-    /// Unflush routine for a keyspace
-    fn unflush_keyspace(partmap: LoadedPartfile, ksid: &ObjectID) -> StorageEngineResult<Self>;
+    /// Unflush routine for a keyspace stored under `root`
+    fn unflush_keyspace(
+        root: &str,
+        partmap: LoadedPartfile,
+        ksid: &ObjectID,
+    ) -> StorageEngineResult<Self>;
 }
 
 impl UnflushableKeyspace for Keyspace {
-    fn unflush_keyspace(partmap: LoadedPartfile, ksid: &ObjectID) -> StorageEngineResult<Self> {
+    fn unflush_keyspace(
+        root: &str,
+        partmap: LoadedPartfile,
+        ksid: &ObjectID,
+    ) -> StorageEngineResult<Self> {
         let ks: Coremap<ObjectID, Arc<Table>> = Coremap::with_capacity(partmap.len());
         for (tableid, (table_storage_type, model_code)) in partmap.into_iter() {
-            if table_storage_type > 1 {
-                return Err(StorageEngineError::bad_metadata_in_table(ksid, &tableid));
-            }
-            let is_volatile = table_storage_type == bytemarks::BYTEMARK_STORAGE_VOLATILE;
-            let tbl = self::read_table::<Table>(ksid, &tableid, is_volatile, model_code)?;
+            let is_volatile = bytemarks::storage_is_volatile(table_storage_type)
+                .map_err(|e| StorageEngineError::unknown_bytemark_in_table(ksid, &tableid, e))?;
+            let tbl =
+                self::read_table_from::<Table>(root, ksid, &tableid, is_volatile, model_code)?;
             ks.true_if_insert(tableid, Arc::new(tbl));
         }
-        Ok(Keyspace::init_with_all_def_strategy(ks))
+        let ksmeta = self::read_ksmeta(root, ksid)?;
+        Ok(self::keyspace_with_ksmeta(ks, ksmeta))
     }
 }
 
 impl UnflushableKeyspace for SystemKeyspace {
-    fn unflush_keyspace(partmap: LoadedPartfile, ksid: &ObjectID) -> StorageEngineResult<Self> {
+    fn unflush_keyspace(
+        root: &str,
+        partmap: LoadedPartfile,
+        ksid: &ObjectID,
+    ) -> StorageEngineResult<Self> {
         let ks: Coremap<ObjectID, Wrapper<SystemTable>> = Coremap::with_capacity(partmap.len());
         for (tableid, (table_storage_type, model_code)) in partmap.into_iter() {
-            if table_storage_type > 1 {
-                return Err(StorageEngineError::bad_metadata_in_table(ksid, &tableid));
-            }
-            let is_volatile = table_storage_type == bytemarks::BYTEMARK_STORAGE_VOLATILE;
-            let tbl = self::read_table::<SystemTable>(ksid, &tableid, is_volatile, model_code)?;
+            let is_volatile = bytemarks::storage_is_volatile(table_storage_type)
+                .map_err(|e| StorageEngineError::unknown_bytemark_in_table(ksid, &tableid, e))?;
+            let tbl = self::read_table_from::<SystemTable>(
+                root,
+                ksid,
+                &tableid,
+                is_volatile,
+                model_code,
+            )?;
             ks.true_if_insert(tableid, Wrapper::new(tbl));
         }
         Ok(SystemKeyspace::new(ks))
     }
 }
 
+/// The data that a table is restored from
+pub enum TableSource<'a> {
+    /// The table file at the given path
+    File(&'a Path),
+    /// The (already verified) payload of the table file with the given name
+    Payload(&'a str, &'a [u8]),
+}
+
+impl<'a> TableSource<'a> {
+    fn name(&self) -> String {
+        match self {
+            Self::File(path) => path.to_string_lossy().to_string(),
+            Self::Payload(file, _) => file.to_string(),
+        }
+    }
+}
+
 /// Tables that can be restored from disk storage
 pub trait UnflushableTable: Sized {
     /// Procedure to restore (deserialize) table from disk storage
@@ -95,44 +131,67 @@ pub trait UnflushableTable: Sized {
         filepath: impl AsRef<Path>,
         model_code: u8,
         volatile: bool,
+    ) -> StorageEngineResult<Self> {
+        Self::unflush_table_from(TableSource::File(filepath.as_ref()), model_code, volatile)
+    }
+    /// Same as [`UnflushableTable::unflush_table`], but restores the table from the given source
+    fn unflush_table_from(
+        source: TableSource<'_>,
+        model_code: u8,
+        volatile: bool,
     ) -> StorageEngineResult<Self>;
 }
 
-#[allow(clippy::transmute_int_to_bool)]
 impl UnflushableTable for Table {
-    fn unflush_table(
-        filepath: impl AsRef<Path>,
+    fn unflush_table_from(
+        source: TableSource<'_>,
         model_code: u8,
         volatile: bool,
     ) -> StorageEngineResult<Self> {
-        let ret = match model_code {
-            // pure KVEBlob: [0, 3]
-            x if x < 4 => {
-                let data = decode(filepath, volatile)?;
-                let (k_enc, v_enc) = unsafe {
-                    // UNSAFE(@ohsayan): Safe because of the above match. Just a lil bitmagic
-                    let key: bool = transmute(model_code >> 1);
-                    let value: bool = transmute(((model_code >> 1) + (model_code & 1)) % 2);
-                    (key, value)
-                };
-                Table::new_pure_kve_with_data(data, volatile, k_enc, v_enc)
+        let model = bytemarks::model(model_code)
+            .map_err(|e| StorageEngineError::UnknownBytemark(source.name(), e))?;
+        let ret = match model.kind {
+            ModelKind::KV => {
+                let (data, deadlines) = decode(&source, volatile, FileKind::Table, model_code)?;
+                Table::new_pure_kve_with_data(data, volatile, model.key_is_str, model.value_is_str)
+                    .with_expiry(deadlines)
             }
-            // KVExtlistmap: [4, 7]
-            x if x < 8 => {
-                let data = decode(filepath, volatile)?;
-                let (k_enc, v_enc) = unsafe {
-                    // UNSAFE(@ohsayan): Safe because of the above match. Just a lil bitmagic
-                    let code = model_code - 4;
-                    let key: bool = transmute(code >> 1);
-                    let value: bool = transmute(code % 2);
-                    (key, value)
-                };
-                Table::new_kve_listmap_with_data(data, volatile, k_enc, v_enc)
+            ModelKind::KVList => {
+                let (data, deadlines, bounds) =
+                    decode(&source, volatile, FileKind::Table, model_code)?;
+                Table::new_kve_listmap_with_data(
+                    data,
+                    volatile,
+                    model.key_is_str,
+                    model.value_is_str,
+                )
+                .with_expiry(deadlines)
+                .with_list_bounds(bounds)
             }
-            _ => {
-                return Err(StorageEngineError::BadMetadata(
-                    filepath.as_ref().to_string_lossy().to_string(),
-                ))
+            ModelKind::KVMap => {
+                let (data, deadlines) = decode(&source, volatile, FileKind::Table, model_code)?;
+                Table::new_kve_map_with_data(data, volatile, model.key_is_str, model.value_is_str)
+                    .with_expiry(deadlines)
+            }
+            ModelKind::KVSet => {
+                let (data, deadlines) = decode(&source, volatile, FileKind::Table, model_code)?;
+                Table::new_kve_set_with_data(data, volatile, model.key_is_str, model.value_is_str)
+                    .with_expiry(deadlines)
+            }
+            ModelKind::KVSortedSet => {
+                let (data, deadlines) = decode(&source, volatile, FileKind::Table, model_code)?;
+                Table::new_kve_sorted_set_with_data(
+                    data,
+                    volatile,
+                    model.key_is_str,
+                    model.value_is_str,
+                )
+                .with_expiry(deadlines)
+            }
+            ModelKind::KVDocument => {
+                let (data, deadlines) = decode(&source, volatile, FileKind::Table, model_code)?;
+                Table::new_kve_document_with_data(data, volatile, model.key_is_str)
+                    .with_expiry(deadlines)
             }
         };
         Ok(ret)
@@ -140,19 +199,28 @@ impl UnflushableTable for Table {
 }
 
 impl UnflushableTable for SystemTable {
-    fn unflush_table(
-        filepath: impl AsRef<Path>,
+    fn unflush_table_from(
+        source: TableSource<'_>,
         model_code: u8,
         volatile: bool,
     ) -> StorageEngineResult<Self> {
         match model_code {
-            0 => {
+            bytemarks::SYSTEM_TABLE_AUTH => {
                 // this is the authmap
-                let authmap = decode(filepath, volatile)?;
+                let authmap = decode(&source, volatile, FileKind::SystemTable, model_code)?;
                 Ok(SystemTable::new_auth(Arc::new(authmap)))
             }
-            _ => Err(StorageEngineError::BadMetadata(
-                filepath.as_ref().to_string_lossy().to_string(),
+            bytemarks::SYSTEM_TABLE_USAGE => {
+                let ledger = decode(&source, volatile, FileKind::SystemTable, model_code)?;
+                Ok(SystemTable::new_usage(Arc::new(ledger)))
+            }
+            bytemarks::SYSTEM_TABLE_ENTITIES => {
+                let catalog = decode(&source, volatile, FileKind::SystemTable, model_code)?;
+                Ok(SystemTable::new_entities(Arc::new(catalog)))
+            }
+            _ => Err(StorageEngineError::UnknownBytemark(
+                source.name(),
+                UnknownBytemark::System(model_code),
             )),
         }
     }
@@ -160,19 +228,36 @@ impl UnflushableTable for SystemTable {
 
 #[inline(always)]
 fn decode<T: DeserializeInto>(
-    filepath: impl AsRef<Path>,
+    source: &TableSource<'_>,
     volatile: bool,
+    kind: FileKind,
+    model_code: u8,
 ) -> StorageEngineResult<T> {
-    if volatile {
-        Ok(T::new_empty())
-    } else {
-        let data = fs::read(filepath.as_ref()).map_err_context(format!(
-            "reading file {}",
-            filepath.as_ref().to_string_lossy()
-        ))?;
-        super::de::deserialize_into(&data).ok_or_else(|| {
-            StorageEngineError::CorruptedFile(filepath.as_ref().to_string_lossy().to_string())
-        })
+    match source {
+        _ if volatile => Ok(T::new_empty()),
+        TableSource::Payload(file, payload) => super::de::deserialize_into(payload)
+            .ok_or_else(|| StorageEngineError::CorruptedFile(file.to_string())),
+        TableSource::File(filepath) => {
+            let file = filepath.to_string_lossy();
+            let data =
+                MappedFile::open(filepath).map_err_context(format!("reading file {file}"))?;
+            super::stats::stats().record_table_loaded(data.len() as u64);
+            let model = match kind {
+                FileKind::SystemTable => ModelDescriptor::from_system_code(model_code),
+                _ => ModelDescriptor::from_model_code(model_code),
+            }
+            .map_err(|e| StorageEngineError::UnknownBytemark(file.to_string(), e))?;
+            // v1 files have no header
+            let body = header::strip(&data, &file, kind, model)?;
+            let table = if checksum::is_checksummed(body) {
+                // the segments are verified and read right where they're mapped
+                let segments = checksum::segments(body, &file, data.len() - body.len())?;
+                super::de::deserialize_segments_into(&segments)
+            } else {
+                super::de::deserialize_into(body)
+            };
+            table.ok_or_else(|| StorageEngineError::CorruptedFile(file.to_string()))
+        }
     }
 }
 
@@ -180,39 +265,217 @@ fn decode<T: DeserializeInto>(
 ///
 /// This will take care of volatility and the model_code. Just make sure that you pass the proper
 /// keyspace ID and a valid table ID
+#[cfg(test)]
 pub fn read_table<T: UnflushableTable>(
     ksid: &ObjectID,
     tblid: &ObjectID,
     volatile: bool,
     model_code: u8,
 ) -> StorageEngineResult<T> {
-    let filepath = unsafe { concat_path!(DIR_KSROOT, ksid.as_str(), tblid.as_str()) };
+    self::read_table_from(DIR_KSROOT, ksid, tblid, volatile, model_code)
+}
+
+/// Same as [`read_table`], but reads the table from the tree under `root`
+fn read_table_from<T: UnflushableTable>(
+    root: &str,
+    ksid: &ObjectID,
+    tblid: &ObjectID,
+    volatile: bool,
+    model_code: u8,
+) -> StorageEngineResult<T> {
+    let filepath = unsafe { concat_path!(root, ksid.as_str(), tblid.as_str()) };
     let tbl = T::unflush_table(filepath, model_code, volatile)?;
     Ok(tbl)
 }
 
+/// Copy the entries of `table` (along with their deadlines) into a new table of the same
+/// model. The entries go through the serializer, so the copy is exactly what a restart would
+/// have loaded if `table` had just been flushed
+pub fn copy_table(table: &Table, volatile: bool) -> StorageEngineResult<Table> {
+    let mut payload = Vec::new();
+    table
+        .write_table_to(&mut payload)
+        .map_err_context("copying table")?;
+    // never volatile here, or we'd get an empty table back
+    let copy = Table::unflush_table_from(
+        TableSource::Payload("<copy>", &payload),
+        table.get_model_code(),
+        false,
+    )?;
+    let copy = copy.with_volatility(volatile);
+    Ok(match table.configured_shards() {
+        Some(shards) => copy.with_shards(shards),
+        None => copy,
+    })
+}
+
 /// Read an entire keyspace into a Coremap. You'll need to initialize the rest
 pub fn read_keyspace<K: UnflushableKeyspace>(ksid: &ObjectID) -> StorageEngineResult<K> {
-    let partmap = self::read_partmap(ksid)?;
-    K::unflush_keyspace(partmap, ksid)
+    self::read_keyspace_from(DIR_KSROOT, ksid)
+}
+
+/// Same as [`read_keyspace`], but reads the keyspace from the tree under `root`
+pub(super) fn read_keyspace_from<K: UnflushableKeyspace>(
+    root: &str,
+    ksid: &ObjectID,
+) -> StorageEngineResult<K> {
+    let partmap = self::read_partmap(root, ksid)?;
+    K::unflush_keyspace(root, partmap, ksid)
+}
+
+/// Read a keyspace from the snapshot with the given name, looking at local snapshots first
+/// and then at remote snapshots. Returns `None` if there is no such snapshot or if the
+/// keyspace isn't present in it
+pub fn read_keyspace_from_snapshot(
+    snapshot: &str,
+    ksid: &ObjectID,
+) -> StorageEngineResult<Option<Keyspace>> {
+    let root = [DIR_SNAPROOT, DIR_RSNAPROOT]
+        .iter()
+        .map(|snaproot| concat_str!(snaproot, "/", snapshot))
+        .find(|root| Path::new(root).is_dir());
+    let root = match root {
+        Some(root) => root,
+        None => return Ok(None),
+    };
+    if !self::read_preload_from(&root)?.contains(ksid) {
+        return Ok(None);
+    }
+    self::read_keyspace_from(&root, ksid).map(Some)
 }
 
 /// Read the `PARTMAP` for a given keyspace
-pub fn read_partmap(ksid: &ObjectID) -> StorageEngineResult<LoadedPartfile> {
+pub fn read_partmap(root: &str, ksid: &ObjectID) -> StorageEngineResult<LoadedPartfile> {
     let ksid_str = unsafe { ksid.as_str() };
-    let filepath = concat_path!(DIR_KSROOT, ksid_str, "PARTMAP");
+    let filepath = concat_path!(root, ksid_str, "PARTMAP");
     let partmap_raw = fs::read(&filepath)
         .map_err_context(format!("while reading {}", filepath.to_string_lossy()))?;
-    super::de::deserialize_set_ctype_bytemark(&partmap_raw)
+    // v1 files have no header
+    let partmap = header::strip(
+        &partmap_raw,
+        &filepath.to_string_lossy(),
+        FileKind::Partmap,
+        ModelDescriptor::NONE,
+    )?;
+    super::de::deserialize_set_ctype_bytemark(partmap)
         .ok_or_else(|| StorageEngineError::corrupted_partmap(ksid))
 }
 
+/// Read the `KSMETA` for a given keyspace. Data directories created before the `KSMETA` was
+/// introduced don't have one, in which case we use the defaults
+pub fn read_ksmeta(root: &str, ksid: &ObjectID) -> StorageEngineResult<Ksmeta> {
+    let ksid_str = unsafe { ksid.as_str() };
+    let filepath = concat_path!(root, ksid_str, "KSMETA");
+    match fs::read(&filepath) {
+        Ok(ksmeta_raw) => super::preload::read_ksmeta_raw(ksid, ksmeta_raw),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Ksmeta::default()),
+        Err(e) => Err(StorageEngineError::ioerror_extra(
+            e,
+            format!("while reading {}", filepath.to_string_lossy()),
+        )),
+    }
+}
+
+/// Create a keyspace with the given tables and the settings from its `KSMETA`
+pub fn keyspace_with_ksmeta(tables: Coremap<ObjectID, Arc<Table>>, ksmeta: Ksmeta) -> Keyspace {
+    for (tblid, shards) in ksmeta.table_shards {
+        if let Some((tblid, table)) = tables.remove(&tblid) {
+            // nobody else has the table yet, so it can't fail
+            let table = Arc::try_unwrap(table)
+                .map(|table| Arc::new(table.with_shards(shards as usize)))
+                .unwrap_or_else(|table| table);
+            tables.true_if_insert(tblid, table);
+        }
+    }
+    let keyspace = Keyspace::init_with_all(tables, ksmeta.flush_interval);
+    keyspace.quota().set_max_keys(ksmeta.max_keys);
+    keyspace.quota().set_max_bytes(ksmeta.max_bytes);
+    keyspace.set_default_model(ksmeta.default_model);
+    keyspace.set_replication_strategy(ksmeta.replication_strategy);
+    keyspace
+}
+
 /// Read the `PRELOAD`
 pub fn read_preload() -> StorageEngineResult<PreloadSet> {
-    let read = fs::read(PRELOAD_PATH).map_err_context("reading PRELOAD")?;
+    self::read_preload_from(DIR_KSROOT)
+}
+
+/// Read the `PRELOAD` from the tree under `root`
+fn read_preload_from(root: &str) -> StorageEngineResult<PreloadSet> {
+    let read = fs::read(concat_path!(root, "PRELOAD")).map_err_context("reading PRELOAD")?;
     super::preload::read_preload_raw(read)
 }
 
+/// Read the `PRELOAD`, rolling back to the latest `PRELOAD` generation that can be read if it
+/// can't be. If we roll back, the `PRELOAD` is restored from that generation and the keyspaces
+/// that aren't in the generation are moved under [`DIR_REPAIRROOT`] (so that they can be
+/// recovered by hand)
+fn read_preload_or_rollback() -> StorageEngineResult<PreloadSet> {
+    let lost_to = format!(
+        "{DIR_REPAIRROOT}/preload-{}",
+        Utc::now().format("%Y%m%d-%H%M%S")
+    );
+    self::read_preload_or_rollback_from(DIR_KSROOT, &lost_to)
+}
+
+/// Same as [`read_preload_or_rollback`], except that the tree is under `root` and the
+/// keyspaces that aren't in the generation are moved to `lost_to`
+pub(super) fn read_preload_or_rollback_from(
+    root: &str,
+    lost_to: &str,
+) -> StorageEngineResult<PreloadSet> {
+    let error = match self::read_preload_from(root) {
+        Ok(preload) => return Ok(preload),
+        Err(e) => e,
+    };
+    log::error!("Failed to read PRELOAD: {error}");
+    for generation in preload::list_generations(root).unwrap_or_default() {
+        let path = preload::generation_path(root, generation);
+        let read = fs::read(&path)
+            .map_err_context(format!("reading PRELOAD generation {generation}"))
+            .and_then(preload::read_preload_raw);
+        match read {
+            Ok(preload) => {
+                log::warn!("Rolling back to PRELOAD generation {generation}");
+                self::rollback_preload(root, &path, &preload, lost_to)?;
+                return Ok(preload);
+            }
+            Err(e) => log::error!("Can't roll back to PRELOAD generation {generation}: {e}"),
+        }
+    }
+    Err(error)
+}
+
+/// Restore the `PRELOAD` under `root` from the generation at `path`, and move the keyspaces
+/// that aren't in it to `lost_to`
+fn rollback_preload(
+    root: &str,
+    path: &str,
+    preload: &PreloadSet,
+    lost_to: &str,
+) -> StorageEngineResult<()> {
+    for entry in fs::read_dir(root).map_err_context("listing keyspaces")? {
+        let entry = entry.map_err_context("listing keyspaces")?;
+        let name = entry.file_name().to_string_lossy().to_string();
+        let listed = match ObjectID::try_from_slice(&name) {
+            Some(ksid) => preload.contains(&ksid),
+            None => false,
+        };
+        if !entry.path().is_dir() || listed {
+            continue;
+        }
+        fs::create_dir_all(lost_to).map_err_context("creating the repair directory")?;
+        fs::rename(entry.path(), concat_path!(lost_to, &name))
+            .map_err_context(format!("moving keyspace `{name}`"))?;
+        log::warn!(
+            "Keyspace `{name}` isn't in the PRELOAD generation and was moved to `{lost_to}/{name}`"
+        );
+    }
+    let tmp = concat_str!(root, "/PRELOAD_");
+    fs::copy(path, &tmp).map_err_context("restoring PRELOAD")?;
+    fsync::replace(&tmp, &concat_str!(root, "/PRELOAD")).map_err_context("restoring PRELOAD")
+}
+
 /// Read everything and return a [`Memstore`]
 ///
 /// If this is a new instance an empty store is returned while the directory tree
@@ -239,7 +502,8 @@ pub fn read_full() -> StorageEngineResult<Memstore> {
         super::flush::flush_full(target, &store)?;
         return Ok(store);
     }
-    let mut preload = self::read_preload()?;
+    let start = Instant::now();
+    let mut preload = self::read_preload_or_rollback()?;
     // HACK(@ohsayan): Pop off the preload from the serial read_keyspace list. It will fail
     assert!(preload.remove(&SYSTEM));
     let system_keyspace = self::read_keyspace::<SystemKeyspace>(&SYSTEM)?;
@@ -250,6 +514,7 @@ pub fn read_full() -> StorageEngineResult<Memstore> {
     }
     // HACK(@ohsayan): Now pop system back in here
     ksmap.upsert(SYSTEM, Arc::new(Keyspace::empty()));
+    super::stats::stats().record_load(start.elapsed());
     Ok(Memstore::init_with_all(ksmap, system_keyspace))
 }
 
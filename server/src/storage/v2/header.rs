@@ -0,0 +1,314 @@
+/*
+ * Created on Thu Oct 20 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Storage v2 file headers
+//!
+//! Every `PRELOAD`, `PARTMAP`, `KSMETA` and table file written by the v2 engine starts with a
+//! fixed-size header:
+//! ```text
+//! [8B: Magic]
+//! [2B: Format version (LE)]
+//! [1B: Endian of the body]
+//! [1B: File kind]
+//! [4B: Model descriptor: model code, key type, value type, value container]
+//! [4B: Compatible feature flags (LE)]
+//! [4B: Incompatible feature flags (LE)]
+//! [8B: Reserved (zeroed)]
+//! ```
+//! Readers ignore compatible flags that they don't understand, but must refuse to load a file
+//! that has an incompatible flag they don't know about. The reserved block is ignored, but must
+//! be written as zeroes.
+//!
+//! The magic can never be mistaken for a v1 file: a v1 `PRELOAD` or `KSMETA` starts with a meta
+//! segment that has the high bit set, and a v1 `PARTMAP` or table file starts with its entry
+//! count, which, for the magic, would be an absurdly large keyspace or table.
+
+use crate::storage::v1::{
+    bytemarks::{self, ModelKind, UnknownBytemark},
+    error::{StorageEngineError, StorageEngineResult},
+};
+
+/// The magic that starts every v2 file
+pub const MAGIC: [u8; 8] = *b"SKYSTORE";
+/// The current format version
+pub const FORMAT_VERSION: u16 = 2;
+/// The size of a header
+pub const HEADER_SIZE: usize = 32;
+/// Incompatible feature flags known to this version
+const KNOWN_INCOMPAT_FLAGS: u32 = 0;
+
+#[cfg(target_endian = "little")]
+const ENDIAN_NATIVE: u8 = 0;
+#[cfg(target_endian = "big")]
+const ENDIAN_NATIVE: u8 = 1;
+
+/// The type of a key or value
+pub const TYPE_BINSTR: u8 = 0;
+pub const TYPE_STR: u8 = 1;
+/// The container that holds values
+pub const CONTAINER_NONE: u8 = 0;
+pub const CONTAINER_LIST: u8 = 1;
+pub const CONTAINER_MAP: u8 = 2;
+pub const CONTAINER_SET: u8 = 3;
+pub const CONTAINER_SORTED_SET: u8 = 4;
+pub const CONTAINER_DOCUMENT: u8 = 5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+/// The kind of data a file holds
+pub enum FileKind {
+    Preload = 0,
+    Table = 1,
+    SystemTable = 2,
+    Partmap = 3,
+    Ksmeta = 4,
+}
+
+impl FileKind {
+    const fn from_raw(raw: u8) -> Option<Self> {
+        match raw {
+            0 => Some(Self::Preload),
+            1 => Some(Self::Table),
+            2 => Some(Self::SystemTable),
+            3 => Some(Self::Partmap),
+            4 => Some(Self::Ksmeta),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// Describes the model of the data in a table file
+pub struct ModelDescriptor {
+    pub model_code: u8,
+    pub key_type: u8,
+    pub value_type: u8,
+    pub container: u8,
+}
+
+impl ModelDescriptor {
+    /// The descriptor for files that don't hold a model
+    pub const NONE: Self = Self::new(0, TYPE_BINSTR, TYPE_BINSTR, CONTAINER_NONE);
+    /// The descriptor for the system auth table
+    pub const SYSTEM_AUTH: Self = Self::new(
+        bytemarks::SYSTEM_TABLE_AUTH,
+        TYPE_BINSTR,
+        TYPE_BINSTR,
+        CONTAINER_NONE,
+    );
+    /// The descriptor for the system usage table
+    pub const SYSTEM_USAGE: Self = Self::new(
+        bytemarks::SYSTEM_TABLE_USAGE,
+        TYPE_BINSTR,
+        TYPE_BINSTR,
+        CONTAINER_NONE,
+    );
+    /// The descriptor for the system entity catalog
+    pub const SYSTEM_ENTITIES: Self = Self::new(
+        bytemarks::SYSTEM_TABLE_ENTITIES,
+        TYPE_BINSTR,
+        TYPE_BINSTR,
+        CONTAINER_NONE,
+    );
+    const fn new(model_code: u8, key_type: u8, value_type: u8, container: u8) -> Self {
+        Self {
+            model_code,
+            key_type,
+            value_type,
+            container,
+        }
+    }
+    /// Returns the descriptor for a system table's model code
+    pub const fn from_system_code(model_code: u8) -> Result<Self, UnknownBytemark> {
+        match model_code {
+            bytemarks::SYSTEM_TABLE_AUTH => Ok(Self::SYSTEM_AUTH),
+            bytemarks::SYSTEM_TABLE_USAGE => Ok(Self::SYSTEM_USAGE),
+            bytemarks::SYSTEM_TABLE_ENTITIES => Ok(Self::SYSTEM_ENTITIES),
+            _ => Err(UnknownBytemark::System(model_code)),
+        }
+    }
+    /// Returns the descriptor for a user table's model code
+    pub const fn from_model_code(model_code: u8) -> Result<Self, UnknownBytemark> {
+        let model = match bytemarks::model(model_code) {
+            Ok(model) => model,
+            Err(e) => return Err(e),
+        };
+        let container = match model.kind {
+            ModelKind::KV => CONTAINER_NONE,
+            ModelKind::KVList => CONTAINER_LIST,
+            ModelKind::KVMap => CONTAINER_MAP,
+            ModelKind::KVSet => CONTAINER_SET,
+            ModelKind::KVSortedSet => CONTAINER_SORTED_SET,
+            ModelKind::KVDocument => CONTAINER_DOCUMENT,
+        };
+        Ok(Self::new(
+            model_code,
+            Self::type_of(model.key_is_str),
+            Self::type_of(model.value_is_str),
+            container,
+        ))
+    }
+    const fn type_of(is_str: bool) -> u8 {
+        if is_str {
+            TYPE_STR
+        } else {
+            TYPE_BINSTR
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+/// A v2 file header
+pub struct Header {
+    pub version: u16,
+    pub endian: u8,
+    pub kind: FileKind,
+    pub model: ModelDescriptor,
+    pub compat_flags: u32,
+    pub incompat_flags: u32,
+}
+
+impl Header {
+    /// Create a header for the current format version
+    pub const fn new(kind: FileKind, model: ModelDescriptor) -> Self {
+        Self {
+            version: FORMAT_VERSION,
+            endian: ENDIAN_NATIVE,
+            kind,
+            model,
+            compat_flags: 0,
+            incompat_flags: 0,
+        }
+    }
+    /// Encode the header
+    pub fn encode(&self) -> [u8; HEADER_SIZE] {
+        let mut ret = [0u8; HEADER_SIZE];
+        ret[..8].copy_from_slice(&MAGIC);
+        ret[8..10].copy_from_slice(&self.version.to_le_bytes());
+        ret[10] = self.endian;
+        ret[11] = self.kind as u8;
+        ret[12] = self.model.model_code;
+        ret[13] = self.model.key_type;
+        ret[14] = self.model.value_type;
+        ret[15] = self.model.container;
+        ret[16..20].copy_from_slice(&self.compat_flags.to_le_bytes());
+        ret[20..24].copy_from_slice(&self.incompat_flags.to_le_bytes());
+        // the rest is reserved
+        ret
+    }
+    /// Decode the header at the start of `data`, that was read from `file`. This will refuse
+    /// headers from newer (or unknown) format versions
+    pub fn decode(data: &[u8], file: &str) -> StorageEngineResult<Self> {
+        if !is_v2(data) || data.len() < HEADER_SIZE {
+            return Err(StorageEngineError::BadMetadata(file.to_owned()));
+        }
+        let version = u16::from_le_bytes([data[8], data[9]]);
+        let incompat_flags = u32::from_le_bytes([data[20], data[21], data[22], data[23]]);
+        if version != FORMAT_VERSION || incompat_flags & !KNOWN_INCOMPAT_FLAGS != 0 {
+            return Err(StorageEngineError::UnsupportedFormat(
+                file.to_owned(),
+                version,
+            ));
+        }
+        let kind = FileKind::from_raw(data[11])
+            .ok_or_else(|| StorageEngineError::BadMetadata(file.to_owned()))?;
+        Ok(Self {
+            version,
+            endian: data[10],
+            kind,
+            model: ModelDescriptor::new(data[12], data[13], data[14], data[15]),
+            compat_flags: u32::from_le_bytes([data[16], data[17], data[18], data[19]]),
+            incompat_flags,
+        })
+    }
+}
+
+/// Returns true if `data` starts with a v2 header
+pub fn is_v2(data: &[u8]) -> bool {
+    data.starts_with(&MAGIC)
+}
+
+/// Returns the body of a file after verifying that its header has the expected kind and model.
+/// v1 files (that have no header) are returned as is
+pub fn strip<'a>(
+    data: &'a [u8],
+    file: &str,
+    kind: FileKind,
+    model: ModelDescriptor,
+) -> StorageEngineResult<&'a [u8]> {
+    if !is_v2(data) {
+        return Ok(data);
+    }
+    let header = Header::decode(data, file)?;
+    if header.kind != kind || header.model != model || header.endian != ENDIAN_NATIVE {
+        return Err(StorageEngineError::BadMetadata(file.to_owned()));
+    }
+    Ok(&data[HEADER_SIZE..])
+}
+
+#[test]
+fn test_header_roundtrip() {
+    let model =
+        ModelDescriptor::from_model_code(bytemarks::BYTEMARK_MODEL_KV_STR_LIST_BINSTR).unwrap();
+    assert_eq!(
+        model,
+        ModelDescriptor::new(6, TYPE_STR, TYPE_BINSTR, CONTAINER_LIST)
+    );
+    let header = Header::new(FileKind::Table, model);
+    let mut file = header.encode().to_vec();
+    file.extend_from_slice(b"body");
+    assert_eq!(Header::decode(&file, "tbl").unwrap(), header);
+    assert_eq!(
+        strip(&file, "tbl", FileKind::Table, model).unwrap(),
+        b"body"
+    );
+    // v1 files pass through
+    assert_eq!(
+        strip(b"body", "tbl", FileKind::Table, model).unwrap(),
+        b"body"
+    );
+    // a different model is rejected
+    assert!(strip(&file, "tbl", FileKind::Table, ModelDescriptor::SYSTEM_AUTH).is_err());
+}
+
+#[test]
+fn test_header_reject_newer() {
+    let mut header = Header::new(FileKind::Preload, ModelDescriptor::NONE);
+    header.version = FORMAT_VERSION + 1;
+    assert_eq!(
+        Header::decode(&header.encode(), "PRELOAD")
+            .unwrap_err()
+            .to_string(),
+        "file `PRELOAD` was written in an unsupported storage format (v3)"
+    );
+    let mut header = Header::new(FileKind::Preload, ModelDescriptor::NONE);
+    header.incompat_flags = 1;
+    assert!(Header::decode(&header.encode(), "PRELOAD").is_err());
+    // unknown compatible flags are fine
+    let mut header = Header::new(FileKind::Preload, ModelDescriptor::NONE);
+    header.compat_flags = 1;
+    assert_eq!(Header::decode(&header.encode(), "PRELOAD").unwrap(), header);
+}
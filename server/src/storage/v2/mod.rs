@@ -0,0 +1,99 @@
+/*
+ * Created on Thu Oct 20 2022
+ *
+ * This file is a part of Skytable
+ * Skytable (formerly known as TerrabaseDB or Skybase) is a free and open-source
+ * NoSQL database written by Sayan Nandan ("the Author") with the
+ * vision to provide flexibility in data modelling without compromising
+ * on performance, queryability or scalability.
+ *
+ * Copyright (c) 2022, Sayan Nandan <ohsayan@outlook.com>
+ *
+ * This program is free software: you can redistribute it and/or modify
+ * it under the terms of the GNU Affero General Public License as published by
+ * the Free Software Foundation, either version 3 of the License, or
+ * (at your option) any later version.
+ *
+ * This program is distributed in the hope that it will be useful,
+ * but WITHOUT ANY WARRANTY; without even the implied warranty of
+ * MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the
+ * GNU Affero General Public License for more details.
+ *
+ * You should have received a copy of the GNU Affero General Public License
+ * along with this program. If not, see <https://www.gnu.org/licenses/>.
+ *
+*/
+
+//! # Storage engine (v2)
+//!
+//! v2 keeps the data encoding of v1, but every `PRELOAD`, `PARTMAP`, `KSMETA` and table file now
+//! starts with a self-describing [`header::Header`] that holds the format version and the model
+//! of the data.
+//! Instances created by older versions are migrated on startup (see [`migrate_from_v1`]).
+
+use {
+    crate::{
+        corestore::memstore::Memstore,
+        storage::v1::{
+            error::{ErrorContext, StorageEngineResult},
+            flush::{self, StorageTarget},
+            interface::DIR_KSROOT,
+        },
+    },
+    std::{
+        fs::File,
+        io::{ErrorKind, Read},
+    },
+};
+
+pub mod header;
+
+const PRELOAD_PATH: &str = "data/ks/PRELOAD";
+
+/// Returns true if this instance was created by the v1 storage engine (which is the case if
+/// there is a `PRELOAD` that has no header)
+pub fn is_v1_instance() -> StorageEngineResult<bool> {
+    let mut magic = [0u8; header::MAGIC.len()];
+    let mut file = match File::open(PRELOAD_PATH) {
+        Ok(file) => file,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e).map_err_context("opening PRELOAD"),
+    };
+    let read = file.read(&mut magic).map_err_context("reading PRELOAD")?;
+    Ok(!header::is_v2(&magic[..read]))
+}
+
+/// The storage target for the migration. It writes to the same files as BGSAVE, except that
+/// every table is rewritten (the tables that were just loaded aren't dirty) and the `PRELOAD`
+/// is never written by the flush (we write it ourselves once everything else is done)
+#[derive(Clone, Copy)]
+struct Migration<'a> {
+    root: &'a str,
+}
+
+impl<'a> StorageTarget for Migration<'a> {
+    const NEEDS_TREE_INIT: bool = false;
+    const SHOULD_UNTRIP_PRELOAD_TRIPSWITCH: bool = false;
+    const SKIPS_CLEAN_TABLES: bool = false;
+    const KEEPS_PRELOAD_GENERATIONS: bool = true;
+    fn root(&self) -> String {
+        self.root.to_owned()
+    }
+}
+
+/// Rewrite all the files of a store that was loaded from a v1 instance in the v2 format. The
+/// `PRELOAD` is written last, so if we crash midway, we'll simply migrate again on the next
+/// startup (the v1 loader can read both v1 and v2 files)
+pub fn migrate_from_v1(store: &Memstore) -> StorageEngineResult<()> {
+    log::info!("Migrating data files from storage v1 to v2");
+    self::migrate(DIR_KSROOT, store)?;
+    log::info!("Finished migrating data files to storage v2");
+    Ok(())
+}
+
+/// Same as [`migrate_from_v1`], except that the tree is under `root`
+pub(super) fn migrate(root: &str, store: &Memstore) -> StorageEngineResult<()> {
+    let target = Migration { root };
+    flush::flush_full(target, store).map_err_context("migrating tables to storage v2")?;
+    flush::oneshot::flush_preload(&target, store).map_err_context("migrating PRELOAD to storage v2")
+}
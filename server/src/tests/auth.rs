@@ -287,6 +287,24 @@ async fn auth_whoami_okay_rootuser() {
     )
 }
 
+// client list/kill
+#[sky_macros::dbtest_func(port = 2005, auth_testuser = true)]
+async fn client_list_fail_because_not_root() {
+    assert_auth_perm_error!(con, query!("client", "list"))
+}
+#[sky_macros::dbtest_func(port = 2005, auth_testuser = true)]
+async fn client_kill_fail_because_not_root() {
+    assert_auth_perm_error!(con, query!("client", "kill", "0"))
+}
+#[sky_macros::dbtest_func(port = 2005, auth_rootuser = true)]
+async fn client_kill_okay_because_root() {
+    runeq!(
+        con,
+        query!("client", "kill", "0"),
+        Element::RespCode(RespCode::NotFound)
+    )
+}
+
 mod syntax_checks {
     use super::{NOAUTH, ONLYAUTH};
     use crate::auth::provider::testsuite_data::{
@@ -122,6 +122,33 @@ mod __private {
             Element::RespCode(RespCode::Okay)
         );
     }
+    async fn test_drop_table_in_use() {
+        let mut rng = rand::thread_rng();
+        let tblname = utils::rand_alphastring(10, &mut rng);
+        let my_fqe = __MYKS__.to_owned() + "." + &tblname;
+        query.push(format!("create model {my_fqe}(string, string)"));
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::Okay)
+        );
+        let query = Query::from(format!("use {my_fqe}"));
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::Okay)
+        );
+        // we're still using it, but that doesn't stop the drop
+        let query = Query::from(format!("drop model {my_fqe}"));
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::Okay)
+        );
+        // but we can't write to it anymore
+        let query = query!("set", "x", "100");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::ErrorString("container-not-found".to_owned()))
+        );
+    }
     async fn test_use() {
         query.push(format!("USE {__MYENTITY__}"));
         assert_eq!(
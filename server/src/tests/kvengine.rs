@@ -50,6 +50,27 @@ mod __private {
         assert_eq!(resp, Element::String("sayan".to_owned()));
     }
 
+    /// Negotiate compression with no supported algorithm: compression stays off
+    async fn test_heya_compress_none() {
+        query.push("heya");
+        query.push("compress");
+        query.push("zstd");
+        query.push("brotli");
+        let resp = con.run_query_raw(&query).await.unwrap();
+        assert_eq!(resp, Element::String("none".to_owned()));
+        // responses still come back as-is
+        let resp = con.run_query_raw(&Query::from("heya")).await.unwrap();
+        assert_eq!(resp, Element::String("HEY!".to_owned()));
+    }
+
+    async fn test_heya_bad_subcommand() {
+        query.push("heya");
+        query.push("compres");
+        query.push("lz4");
+        let resp = con.run_query_raw(&query).await.unwrap();
+        assert_eq!(resp, Element::RespCode(RespCode::ActionError));
+    }
+
     /// Test a GET query: for a non-existing key
     async fn test_get_single_nil() {
         query.push("get");
@@ -364,6 +385,51 @@ mod __private {
         );
     }
 
+    /// Test an XMGET query that reads from two tables
+    async fn test_xmget_across_tables() {
+        setkeys!(
+            con,
+            "x":"100"
+        );
+        let other = libstress::utils::rand_alphastring(10, &mut rand::thread_rng());
+        runeq!(
+            con,
+            skytable::query!(format!("create model {__MYKS__}.{other}(string, string)")),
+            Element::RespCode(RespCode::Okay)
+        );
+        runeq!(
+            con,
+            skytable::query!(format!("use {__MYKS__}.{other}")),
+            Element::RespCode(RespCode::Okay)
+        );
+        setkeys!(
+            con,
+            "y":"200"
+        );
+        query.push("xmget");
+        query.push(format!("{__MYKS__}:{__MYTABLE__}:x"));
+        query.push(format!("{__MYKS__}:{other}:y"));
+        query.push(format!("{__MYKS__}:{other}:x"));
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::Array(Array::Str(vec![
+                Some("100".to_owned()),
+                Some("200".to_owned()),
+                None
+            ]))
+        );
+    }
+
+    /// Test an XMGET query that names a table that doesn't exist
+    async fn test_xmget_missing_table() {
+        query.push("xmget");
+        query.push(format!("{__MYKS__}:nosuchtable:x"));
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::ErrorString("container-not-found".to_owned()))
+        );
+    }
+
     /// Test an MSET query with a single non-existing key
     async fn test_mset_single_okay() {
         // first set the keys
@@ -982,6 +1048,147 @@ mod __private {
             Element::RespCode(RespCode::ActionError)
         );
     }
+    async fn test_setrange_getrange() {
+        setkeys!(
+            con,
+            "x":"hello world"
+        );
+        query.push("setrange");
+        query.push("x");
+        query.push("6");
+        query.push("skytable");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::UnsignedInt(14)
+        );
+        let mut query = Query::new();
+        query.push("getrange");
+        query.push("x");
+        query.push("-8");
+        query.push("-1");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::String("skytable".to_owned())
+        );
+    }
+    async fn test_getrange_nil() {
+        query.push("getrange");
+        query.push("x");
+        query.push("0");
+        query.push("-1");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::NotFound)
+        );
+    }
+    async fn test_setrange_bad_offset() {
+        query.push("setrange");
+        query.push("x");
+        query.push("-1");
+        query.push("y");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::Wrongtype)
+        );
+    }
+    async fn test_keys_pattern() {
+        setkeys!(
+            con,
+            "user:1":"a",
+            "user:2":"b",
+            "session:1":"c"
+        );
+        query.push("keys");
+        query.push("user:*");
+        if let Element::Array(Array::NonNullStr(mut keys)) =
+            con.run_query_raw(&query).await.unwrap()
+        {
+            keys.sort();
+            assert_eq!(keys, vec!["user:1".to_owned(), "user:2".to_owned()]);
+        } else {
+            panic!("Expected flat string array");
+        }
+    }
+    async fn test_keys_bad_option() {
+        query.push("keys");
+        query.push("*");
+        query.push("limit");
+        query.push("10");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::ErrorString("unknown-property".to_owned()))
+        );
+    }
+    async fn test_delmatch() {
+        setkeys!(
+            con,
+            "user:1":"a",
+            "user:2":"b",
+            "user:3":"c",
+            "session:1":"d"
+        );
+        query.push("delmatch");
+        query.push("user:*");
+        query.push("count");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::UnsignedInt(3)
+        );
+        let mut query = Query::new();
+        query.push("delmatch");
+        query.push("user:*");
+        query.push("limit");
+        query.push("2");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::UnsignedInt(2)
+        );
+        let mut query = Query::new();
+        query.push("delmatch");
+        query.push("user:*");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::UnsignedInt(1)
+        );
+        let mut query = Query::new();
+        query.push("exists");
+        query.push("session:1");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::UnsignedInt(1)
+        );
+    }
+    async fn test_delmatch_bad_limit() {
+        query.push("delmatch");
+        query.push("*");
+        query.push("limit");
+        query.push("0");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::ActionError)
+        );
+    }
+    async fn test_randomkey() {
+        setkeys!(
+            con,
+            "x":"100",
+            "y":"200"
+        );
+        query.push("randomkey");
+        let key = con.run_query_raw(&query).await.unwrap();
+        assert!(
+            key == Element::String("x".to_owned()) || key == Element::String("y".to_owned()),
+            "Unexpected key: {:?}",
+            key
+        );
+    }
+    async fn test_randomkey_empty() {
+        query.push("randomkey");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::NotFound)
+        );
+    }
     async fn test_mksnap_disabled() {
         query.push("mksnap");
         assert_eq!(
@@ -1008,6 +1215,49 @@ mod __private {
             ))
         );
     }
+    async fn test_fetch_unknown_cursor() {
+        query.push("fetch");
+        query.push("1");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::ErrorString("err-cursor-not-found".to_owned()))
+        );
+    }
+    async fn test_fetch_bad_cursor() {
+        query.push("fetch");
+        query.push("first");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::Wrongtype)
+        );
+    }
+    async fn test_client_list_has_self() {
+        query.push("client");
+        query.push("id");
+        let id = match con.run_query_raw(&query).await.unwrap() {
+            Element::UnsignedInt(id) => id,
+            other => panic!("Expected an integer, got {other:?}"),
+        };
+        let mut query = Query::new();
+        query.push("client");
+        query.push("list");
+        if let Element::Array(Array::NonNullStr(lines)) = con.run_query_raw(&query).await.unwrap() {
+            let prefix = format!("id={id} ");
+            let line = lines.iter().find(|line| line.starts_with(&prefix)).unwrap();
+            assert!(line.ends_with("cmd=CLIENT"));
+        } else {
+            panic!("Expected flat string array");
+        }
+    }
+    async fn test_client_kill_unknown() {
+        query.push("client");
+        query.push("kill");
+        query.push("0");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::NotFound)
+        );
+    }
     async fn test_lskeys_default() {
         query.push("uset");
         query.push("x");
@@ -1202,4 +1452,359 @@ mod __private {
             Element::RespCode(RespCode::NotFound)
         );
     }
+    async fn test_getdel() {
+        setkeys!(
+            con,
+            "x":"100"
+        );
+        query.push("getdel");
+        query.push("x");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::String("100".to_owned())
+        );
+        // only the first one gets it
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::NotFound)
+        );
+    }
+    async fn test_getset() {
+        query.push("getset");
+        query.push("x");
+        query.push("100");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::NotFound)
+        );
+        let mut query = Query::new();
+        query.push("getset");
+        query.push("x");
+        query.push("200");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::String("100".to_owned())
+        );
+        let mut query = Query::new();
+        query.push("get");
+        query.push("x");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::String("200".to_owned())
+        );
+    }
+    async fn test_getset_syntax_error() {
+        query.push("getset");
+        query.push("x");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::ActionError)
+        );
+    }
+    async fn test_bitmap() {
+        query.push("setbit");
+        query.push("flags");
+        query.push("9");
+        query.push("1");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::UnsignedInt(0)
+        );
+        let mut query = Query::new();
+        query.push("getbit");
+        query.push("flags");
+        query.push("9");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::UnsignedInt(1)
+        );
+        let mut query = Query::new();
+        query.push("getbit");
+        query.push("flags");
+        query.push("100");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::UnsignedInt(0)
+        );
+        let mut query = Query::new();
+        query.push("bitcount");
+        query.push("flags");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::UnsignedInt(1)
+        );
+        let mut query = Query::new();
+        query.push("bitcount");
+        query.push("flags");
+        query.push("0");
+        query.push("0");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::UnsignedInt(0)
+        );
+    }
+    async fn test_setbit_bad_bit() {
+        query.push("setbit");
+        query.push("flags");
+        query.push("9");
+        query.push("2");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::Wrongtype)
+        );
+    }
+    async fn test_hyperloglog() {
+        query.push("pfadd");
+        query.push("visitors");
+        query.push("alice");
+        query.push("bob");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::UnsignedInt(1)
+        );
+        let mut query = Query::new();
+        query.push("pfadd");
+        query.push("visitors");
+        query.push("alice");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::UnsignedInt(0)
+        );
+        let mut query = Query::new();
+        query.push("pfmerge");
+        query.push("all");
+        query.push("visitors");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::Okay)
+        );
+        let mut query = Query::new();
+        query.push("pfcount");
+        query.push("all");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::UnsignedInt(2)
+        );
+    }
+    async fn test_pfcount_wrongtype() {
+        setkeys!(
+            con,
+            "x":"100"
+        );
+        query.push("pfcount");
+        query.push("x");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::Wrongtype)
+        );
+    }
+    async fn test_type() {
+        setkeys!(
+            con,
+            "x":"100"
+        );
+        query.push("type");
+        query.push("x");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::String("str".to_owned())
+        );
+        let mut query = Query::new();
+        query.push("type");
+        query.push("y");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::NotFound)
+        );
+    }
+    async fn test_debug_object() {
+        setkeys!(
+            con,
+            "x":"100"
+        );
+        query.push("debug");
+        query.push("object");
+        query.push("x");
+        match con.run_query_raw(&query).await.unwrap() {
+            Element::String(line) => {
+                assert!(line.starts_with("shard="), "{line}");
+                assert!(line.ends_with("encoding=int ttl=-"), "{line}");
+            }
+            other => panic!("unexpected response: {other:?}"),
+        }
+        let mut query = Query::new();
+        query.push("debug");
+        query.push("object");
+        query.push("y");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::NotFound)
+        );
+    }
+    async fn test_setifeq() {
+        setkeys!(
+            con,
+            "x":"100"
+        );
+        query.push("setifeq");
+        query.push("x");
+        query.push("200");
+        query.push("300");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::ErrorString("err-value-mismatch".to_owned()))
+        );
+        let mut query = Query::new();
+        query.push("setifeq");
+        query.push("x");
+        query.push("100");
+        query.push("300");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::Okay)
+        );
+        let mut query = Query::new();
+        query.push("setifeq");
+        query.push("y");
+        query.push("100");
+        query.push("300");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::NotFound)
+        );
+    }
+    async fn test_expire_ttl_persist() {
+        setkeys!(
+            con,
+            "x":"100"
+        );
+        query.push("expire");
+        query.push("x");
+        query.push("60");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::Okay)
+        );
+        let mut query = Query::new();
+        query.push("ttl");
+        query.push("x");
+        assert!(matches!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::UnsignedInt(59..=60)
+        ));
+        let mut query = Query::new();
+        query.push("persist");
+        query.push("x");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::Okay)
+        );
+        let mut query = Query::new();
+        query.push("ttl");
+        query.push("x");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::ErrorString("no-expiry".to_owned()))
+        );
+    }
+    async fn test_pexpire_pttl() {
+        setkeys!(
+            con,
+            "x":"100"
+        );
+        query.push("pexpire");
+        query.push("x");
+        query.push("60000");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::Okay)
+        );
+        let mut query = Query::new();
+        query.push("pttl");
+        query.push("x");
+        assert!(matches!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::UnsignedInt(59000..=60000)
+        ));
+        let mut query = Query::new();
+        query.push("ttl");
+        query.push("x");
+        assert!(matches!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::UnsignedInt(59..=60)
+        ));
+    }
+    async fn test_setx_nx_xx() {
+        query.push("setx");
+        query.push("lock");
+        query.push("a");
+        query.push("xx");
+        query.push("ex");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::ErrorString("unknown-property".to_owned()))
+        );
+        let mut query = Query::new();
+        query.push("setx");
+        query.push("lock");
+        query.push("a");
+        query.push("ex");
+        query.push("60");
+        query.push("xx");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::NotFound)
+        );
+        let mut query = Query::new();
+        query.push("setx");
+        query.push("lock");
+        query.push("a");
+        query.push("ex");
+        query.push("60");
+        query.push("nx");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::Okay)
+        );
+        let mut query = Query::new();
+        query.push("setx");
+        query.push("lock");
+        query.push("b");
+        query.push("px");
+        query.push("60000");
+        query.push("nx");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::OverwriteError)
+        );
+        let mut query = Query::new();
+        query.push("ttl");
+        query.push("lock");
+        assert!(matches!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::UnsignedInt(59..=60)
+        ));
+    }
+    async fn test_expire_nil() {
+        query.push("expire");
+        query.push("x");
+        query.push("60");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::NotFound)
+        );
+    }
+    async fn test_expire_bad_ttl() {
+        setkeys!(
+            con,
+            "x":"100"
+        );
+        query.push("expire");
+        query.push("x");
+        query.push("0");
+        assert_eq!(
+            con.run_query_raw(&query).await.unwrap(),
+            Element::RespCode(RespCode::Wrongtype)
+        );
+    }
 }
@@ -418,6 +418,112 @@ mod __private {
         runeq!(con, q, Element::RespCode(RespCode::Wrongtype));
     }
 
+    async fn test_list_ltrim() {
+        lset!(con, "mylist", "1", "2", "3", "4", "5");
+        let q = query!("ltrim", "mylist", "1", "-2");
+        runeq!(con, q, Element::RespCode(RespCode::Okay));
+        let q = query!("lget", "mylist");
+        assert_skyhash_arrayeq!(str, con, q, "2", "3", "4");
+    }
+
+    async fn test_list_ltrim_nil() {
+        let q = query!("ltrim", "mylist", "0", "-1");
+        runeq!(con, q, Element::RespCode(RespCode::NotFound));
+    }
+
+    async fn test_list_ltrim_parse_fail() {
+        lset!(con, "mylist", "a", "b", "c");
+        let q = query!("ltrim", "mylist", "0", "1a");
+        runeq!(con, q, Element::RespCode(RespCode::Wrongtype));
+    }
+
+    async fn test_list_maxlen() {
+        lset!(con, "mylist", "1", "2", "3");
+        let q = query!("lget", "mylist", "maxlen");
+        runeq!(con, q, Element::UnsignedInt(0));
+        let q = query!("lmod", "mylist", "maxlen", "2");
+        runeq!(con, q, Element::RespCode(RespCode::Okay));
+        let q = query!("lget", "mylist");
+        assert_skyhash_arrayeq!(str, con, q, "2", "3");
+        let q = query!("lmod", "mylist", "push", "4", "5", "6");
+        runeq!(con, q, Element::RespCode(RespCode::Okay));
+        let q = query!("lget", "mylist");
+        assert_skyhash_arrayeq!(str, con, q, "5", "6");
+        let q = query!("lget", "mylist", "maxlen");
+        runeq!(con, q, Element::UnsignedInt(2));
+    }
+
+    async fn test_list_linsert() {
+        lset!(con, "mylist", "a", "c");
+        let q = query!("linsert", "mylist", "1", "b");
+        runeq!(con, q, Element::UnsignedInt(3));
+        let q = query!("linsert", "mylist", "3", "d");
+        runeq!(con, q, Element::UnsignedInt(4));
+        let q = query!("lget", "mylist");
+        assert_skyhash_arrayeq!(str, con, q, "a", "b", "c", "d");
+    }
+
+    async fn test_list_linsert_bad_index() {
+        lset!(con, "mylist", "a");
+        let q = query!("linsert", "mylist", "2", "b");
+        runeq!(
+            con,
+            q,
+            Element::RespCode(RespCode::ErrorString("bad-list-index".to_owned()))
+        );
+        let q = query!("linsert", "mylist", "-1", "b");
+        runeq!(con, q, Element::RespCode(RespCode::Wrongtype));
+    }
+
+    async fn test_list_lrem() {
+        lset!(con, "mylist", "a", "b", "a", "c", "a");
+        let q = query!("lrem", "mylist", "a", "-1");
+        runeq!(con, q, Element::UnsignedInt(1));
+        let q = query!("lget", "mylist");
+        assert_skyhash_arrayeq!(str, con, q, "a", "b", "a", "c");
+        let q = query!("lrem", "mylist", "a");
+        runeq!(con, q, Element::UnsignedInt(2));
+        let q = query!("lget", "mylist");
+        assert_skyhash_arrayeq!(str, con, q, "b", "c");
+    }
+
+    async fn test_list_lrem_nil() {
+        let q = query!("lrem", "mylist", "a");
+        runeq!(con, q, Element::RespCode(RespCode::NotFound));
+    }
+
+    async fn test_list_sort() {
+        lset!(con, "mylist", "10", "9", "x", "-1.5", "100");
+        let q = query!("sort", "mylist");
+        assert_skyhash_arrayeq!(str, con, q, "-1.5", "9", "10", "100", "x");
+        let q = query!("sort", "mylist", "alpha");
+        assert_skyhash_arrayeq!(str, con, q, "-1.5", "10", "100", "9", "x");
+        let q = query!("sort", "mylist", "desc", "limit", "1", "2");
+        assert_skyhash_arrayeq!(str, con, q, "100", "10");
+        // the list itself is left as it was
+        let q = query!("lget", "mylist", "first");
+        runeq!(con, q, Element::String("10".to_owned()));
+    }
+
+    async fn test_list_sort_bad_option() {
+        lset!(con, "mylist", "1");
+        let q = query!("sort", "mylist", "by", "weight");
+        runeq!(
+            con,
+            q,
+            Element::RespCode(RespCode::ErrorString("unknown-property".to_owned()))
+        );
+        let q = query!("sort", "mylist", "limit", "0", "ten");
+        runeq!(con, q, Element::RespCode(RespCode::Wrongtype));
+    }
+
+    // type tests
+    async fn test_type_list() {
+        lset!(con, "mylist", "a");
+        let q = query!("type", "mylist");
+        runeq!(con, q, Element::String("list<str>".to_owned()));
+    }
+
     // sanity tests
     async fn test_get_model_error() {
         query.push("GET");
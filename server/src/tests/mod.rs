@@ -57,7 +57,7 @@ mod sys {
         crate::protocol::{LATEST_PROTOCOL_VERSION, LATEST_PROTOCOL_VERSIONSTRING},
         libsky::VERSION,
         sky_macros::dbtest_func as dbtest,
-        skytable::{query, Element, RespCode},
+        skytable::{query, types::Array, Element, RespCode},
     };
 
     #[dbtest]
@@ -131,6 +131,214 @@ mod sys {
             Element::UnsignedInt
         )
     }
+    #[dbtest]
+    async fn sys_config_effective() {
+        let ret = con
+            .run_query_raw(&query!("sys", "config", "effective"))
+            .await
+            .unwrap();
+        match ret {
+            Element::Array(Array::NonNullStr(settings)) => {
+                assert!(settings.iter().any(|s| s.starts_with("server.profile = ")))
+            }
+            ret => panic!("expected the effective settings, got {ret:?}"),
+        }
+    }
+    #[dbtest]
+    async fn sys_compare_self() {
+        runeq!(
+            con,
+            query!("set", "x", "100"),
+            Element::RespCode(RespCode::Okay)
+        );
+        match con
+            .run_query_raw(&query!("sys", "compare", __MYENTITY__, __MYENTITY__))
+            .await
+            .unwrap()
+        {
+            Element::Array(Array::NonNullStr(diff)) => {
+                assert_eq!(&diff[..3], ["added = 0", "removed = 0", "changed = 0"])
+            }
+            ret => panic!("expected a diff, got {ret:?}"),
+        }
+    }
+    #[dbtest]
+    async fn sys_throttle() {
+        runeq!(
+            con,
+            query!("sys", "throttle", "1"),
+            Element::RespCode(RespCode::Okay)
+        );
+        runeq!(
+            con,
+            query!("set", "x", "100"),
+            Element::RespCode(RespCode::Okay)
+        );
+        match con.run_query_raw(&query!("set", "y", "200")).await.unwrap() {
+            Element::RespCode(RespCode::ErrorString(e)) => {
+                assert!(e.starts_with("err-throttled-retry-after-"))
+            }
+            ret => panic!("expected the write to be throttled, got {ret:?}"),
+        }
+        runmatch!(
+            con,
+            query!("sys", "metric", "throttled"),
+            Element::UnsignedInt
+        );
+        runeq!(
+            con,
+            query!("sys", "throttle", "off"),
+            Element::RespCode(RespCode::Okay)
+        );
+        runeq!(
+            con,
+            query!("set", "y", "200"),
+            Element::RespCode(RespCode::Okay)
+        );
+    }
+    #[dbtest]
+    async fn sys_dedup() {
+        runeq!(
+            con,
+            query!("sys", "dedup", "60"),
+            Element::RespCode(RespCode::Okay)
+        );
+        runeq!(
+            con,
+            query!("once", "req-1", "set", "x", "100"),
+            Element::RespCode(RespCode::Okay)
+        );
+        runeq!(
+            con,
+            query!("once", "req-1", "set", "x", "100"),
+            Element::RespCode(RespCode::ErrorString("duplicate-request".to_owned()))
+        );
+        runeq!(
+            con,
+            query!("once", "req-2", "set", "x", "100"),
+            Element::RespCode(RespCode::OverwriteError)
+        );
+        runmatch!(
+            con,
+            query!("sys", "metric", "deduplicated"),
+            Element::UnsignedInt
+        );
+        runeq!(
+            con,
+            query!("sys", "dedup", "off"),
+            Element::RespCode(RespCode::Okay)
+        );
+        runeq!(
+            con,
+            query!("once", "req-1", "del", "x"),
+            Element::UnsignedInt(1)
+        );
+    }
+    #[dbtest]
+    async fn sys_dedup_retry_after_failure() {
+        runeq!(
+            con,
+            query!("sys", "dedup", "60"),
+            Element::RespCode(RespCode::Okay)
+        );
+        runeq!(
+            con,
+            query!("set", "x", "100"),
+            Element::RespCode(RespCode::Okay)
+        );
+        // the write fails, so its request ID isn't remembered
+        runeq!(
+            con,
+            query!("once", "req-1", "set", "x", "200"),
+            Element::RespCode(RespCode::OverwriteError)
+        );
+        runeq!(con, query!("del", "x"), Element::UnsignedInt(1));
+        // and the retry goes through
+        runeq!(
+            con,
+            query!("once", "req-1", "set", "x", "200"),
+            Element::RespCode(RespCode::Okay)
+        );
+        runeq!(
+            con,
+            query!("once", "req-1", "set", "x", "200"),
+            Element::RespCode(RespCode::ErrorString("duplicate-request".to_owned()))
+        );
+        runeq!(
+            con,
+            query!("sys", "dedup", "off"),
+            Element::RespCode(RespCode::Okay)
+        );
+    }
+    #[dbtest]
+    async fn export_table() {
+        runeq!(
+            con,
+            query!("set", "x", "100"),
+            Element::RespCode(RespCode::Okay)
+        );
+        match con
+            .run_query_raw(&query!("export", "json", __MYENTITY__))
+            .await
+            .unwrap()
+        {
+            Element::String(path) => {
+                assert!(path.starts_with("data/exports/") && path.ends_with(".ndjson"))
+            }
+            ret => panic!("expected the path to the export, got {ret:?}"),
+        }
+    }
+    #[dbtest]
+    async fn space_archive() {
+        let mut rng = rand::thread_rng();
+        let name = libstress::utils::rand_alphastring(10, &mut rng);
+        let (archive, ksname) = (format!("{name}.tar"), format!("imported{name}"));
+        runeq!(
+            con,
+            query!("sys", "export", "space", __MYKS__, archive.as_str()),
+            Element::RespCode(RespCode::Okay)
+        );
+        runeq!(
+            con,
+            query!("sys", "import", "space", archive.as_str(), ksname.as_str()),
+            Element::RespCode(RespCode::Okay)
+        );
+        runeq!(
+            con,
+            query!(format!("drop space {ksname} force")),
+            Element::RespCode(RespCode::Okay)
+        );
+        runeq!(
+            con,
+            query!("sys", "import", "space", "missing.tar"),
+            Element::RespCode(RespCode::ErrorString("container-not-found".to_owned()))
+        );
+        runeq!(
+            con,
+            query!("sys", "export", "space", "system", "system.tar"),
+            Element::RespCode(RespCode::ErrorString("err-protected-object".to_owned()))
+        );
+    }
+    #[dbtest]
+    async fn import_errors() {
+        runeq!(
+            con,
+            query!("import", "json", "missing.ndjson", __MYENTITY__),
+            Element::RespCode(RespCode::ErrorString("file-not-found".to_owned()))
+        );
+        runeq!(
+            con,
+            query!("import", "json", "../missing.ndjson", __MYENTITY__),
+            Element::RespCode(RespCode::ErrorString(
+                "err-invalid-snapshot-name".to_owned()
+            ))
+        );
+        runeq!(
+            con,
+            query!("import", "xml", "missing.xml", __MYENTITY__),
+            Element::RespCode(RespCode::ErrorString("unknown-format".to_owned()))
+        );
+    }
 }
 
 use skytable::{query, Element, RespCode};
@@ -102,6 +102,119 @@ pub fn err<T, E>(e: impl Into<E>) -> Result<T, E> {
     Err(e.into())
 }
 
+/// Parse a duration like `90` (seconds), `30s`, `15m`, `6h` or `7d` into seconds
+pub fn parse_duration(duration: &str) -> Option<u64> {
+    let duration = duration.to_ascii_lowercase();
+    let (number, unit) = match duration.char_indices().last()? {
+        (i, 's') => (&duration[..i], 1),
+        (i, 'm') => (&duration[..i], 60),
+        (i, 'h') => (&duration[..i], 3600),
+        (i, 'd') => (&duration[..i], 24 * 3600),
+        _ => (duration.as_str(), 1),
+    };
+    number.parse::<u64>().ok()?.checked_mul(unit)
+}
+
+/// Parse a size like `1048576` (bytes), `512k`, `64m`, `2g` or `1t` (with an optional trailing
+/// `b`, like `64mb`) into bytes. The units are powers of 1024
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size = size.to_ascii_lowercase();
+    let size = size.strip_suffix('b').unwrap_or(&size);
+    let (number, unit) = match size.char_indices().last()? {
+        (i, 'k') => (&size[..i], 1 << 10),
+        (i, 'm') => (&size[..i], 1 << 20),
+        (i, 'g') => (&size[..i], 1 << 30),
+        (i, 't') => (&size[..i], 1 << 40),
+        _ => (size, 1),
+    };
+    number.parse::<u64>().ok()?.checked_mul(unit)
+}
+
+/// Returns the range of a sequence of `len` items (the bytes of a value, or the elements of a
+/// list) from `start` to `end`, both inclusive and counting from the end of the sequence if
+/// they're negative, clamped to the sequence
+pub fn resolve_range(len: usize, start: i64, end: i64) -> core::ops::Range<usize> {
+    let len = len as i64;
+    let resolve = |offset: i64| {
+        if offset < 0 {
+            (len + offset).max(0)
+        } else {
+            offset
+        }
+    };
+    let (start, end) = (resolve(start), resolve(end).min(len - 1));
+    if start > end {
+        0..0
+    } else {
+        start as usize..end as usize + 1
+    }
+}
+
+/// Returns true if `subject` matches the glob `pattern`. In a pattern, `*` matches any run of
+/// bytes, `?` matches any one byte, `[abc]`, `[a-z]` and `[^abc]` match one byte that is (or
+/// isn't) in the set and `\` escapes the byte that follows it
+pub fn glob_match(pattern: &[u8], subject: &[u8]) -> bool {
+    let (mut p, mut s) = (0, 0);
+    // where to pick up from if what follows the last `*` doesn't match
+    let mut backtrack = None;
+    while s < subject.len() {
+        let next = match pattern.get(p) {
+            Some(b'*') => {
+                backtrack = Some((p + 1, s));
+                p += 1;
+                continue;
+            }
+            Some(b'?') => Some(p + 1),
+            Some(b'[') => self::glob_match_class(&pattern[p + 1..], subject[s]).map(|l| p + 1 + l),
+            Some(b'\\') if p + 1 < pattern.len() => (pattern[p + 1] == subject[s]).then_some(p + 2),
+            Some(c) => (*c == subject[s]).then_some(p + 1),
+            None => None,
+        };
+        match (next, backtrack) {
+            (Some(next), _) => {
+                p = next;
+                s += 1;
+            }
+            (None, Some((star_p, star_s))) => {
+                // let the `*` swallow one more byte
+                backtrack = Some((star_p, star_s + 1));
+                p = star_p;
+                s = star_s + 1;
+            }
+            (None, None) => return false,
+        }
+    }
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+/// Match `byte` against the class that `class` starts with (right after the `[`). Returns the
+/// length of the class (including the closing `]`) if the byte is a match
+fn glob_match_class(class: &[u8], byte: u8) -> Option<usize> {
+    let negated = class.first() == Some(&b'^');
+    let mut i = negated as usize;
+    let mut matched = false;
+    loop {
+        match *class.get(i)? {
+            b']' => break,
+            b'\\' => {
+                matched |= *class.get(i + 1)? == byte;
+                i += 2;
+            }
+            lo => match (class.get(i + 1), class.get(i + 2)) {
+                (Some(b'-'), Some(&hi)) if hi != b']' => {
+                    matched |= (lo..=hi).contains(&byte);
+                    i += 3;
+                }
+                _ => {
+                    matched |= lo == byte;
+                    i += 1;
+                }
+            },
+        }
+    }
+    (matched != negated).then_some(i + 1)
+}
+
 /// This is used to hack around multiple trait system boundaries
 /// like deref coercion recursions
 #[derive(Debug)]
@@ -211,3 +324,33 @@ impl<'a, T: PartialEq> PartialEq<T> for Life<'a, T> {
 
 unsafe impl<'a, T: Send> Send for Life<'a, T> {}
 unsafe impl<'a, T: Sync> Sync for Life<'a, T> {}
+
+#[test]
+fn test_glob_match() {
+    assert!(glob_match(b"*", b""));
+    assert!(glob_match(b"user:*", b"user:1001"));
+    assert!(!glob_match(b"user:*", b"session:1001"));
+    assert!(glob_match(b"user:*:name", b"user:1001:name"));
+    assert!(!glob_match(b"user:*:name", b"user:1001:email"));
+    assert!(glob_match(b"h?llo", b"hallo"));
+    assert!(!glob_match(b"h?llo", b"hllo"));
+    assert!(glob_match(b"h[ae]llo", b"hello"));
+    assert!(!glob_match(b"h[^ae]llo", b"hello"));
+    assert!(glob_match(b"key[0-9]", b"key7"));
+    assert!(!glob_match(b"key[0-9]", b"keyx"));
+    assert!(glob_match(b"what\\?", b"what?"));
+    assert!(!glob_match(b"what\\?", b"whats"));
+    assert!(glob_match(b"*a*b*c", b"xxaxxbxxbc"));
+}
+
+#[test]
+fn test_resolve_range() {
+    assert_eq!(resolve_range(5, 0, -1), 0..5);
+    assert_eq!(resolve_range(5, 1, 2), 1..3);
+    assert_eq!(resolve_range(5, -3, -2), 2..4);
+    assert_eq!(resolve_range(5, 2, 100), 2..5);
+    assert_eq!(resolve_range(5, -100, 0), 0..1);
+    assert_eq!(resolve_range(5, 3, 1), 0..0);
+    assert_eq!(resolve_range(5, 10, 20), 0..0);
+    assert_eq!(resolve_range(0, 0, -1), 0..0);
+}
@@ -31,7 +31,12 @@ pub use windows::*;
 
 use {
     crate::IoResult,
-    std::{ffi::OsStr, fs, path::Path},
+    std::{
+        ffi::OsStr,
+        fs,
+        path::Path,
+        time::{SystemTime, UNIX_EPOCH},
+    },
 };
 
 #[cfg(unix)]
@@ -297,3 +302,78 @@ fn dir_size_inner(dir: fs::ReadDir) -> IoResult<u64> {
 pub fn dirsize(path: impl AsRef<Path>) -> IoResult<u64> {
     dir_size_inner(fs::read_dir(path.as_ref())?)
 }
+
+/// Fill `buf` with the bytes of `file` starting at `offset`, without using (or moving) the
+/// file's cursor, so that the file can be read from several threads at once
+pub fn read_exact_at(file: &fs::File, buf: &mut [u8], offset: u64) -> IoResult<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::FileExt::read_exact_at(file, buf, offset)
+    }
+    #[cfg(windows)]
+    {
+        let mut read = 0;
+        while read < buf.len() {
+            match std::os::windows::fs::FileExt::seek_read(
+                file,
+                &mut buf[read..],
+                offset + read as u64,
+            )? {
+                0 => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+                n => read += n,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Write all of `buf` to `file` starting at `offset`, without using (or moving) the file's
+/// cursor
+pub fn write_all_at(file: &fs::File, buf: &[u8], offset: u64) -> IoResult<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::FileExt::write_all_at(file, buf, offset)
+    }
+    #[cfg(windows)]
+    {
+        let mut written = 0;
+        while written < buf.len() {
+            match std::os::windows::fs::FileExt::seek_write(
+                file,
+                &buf[written..],
+                offset + written as u64,
+            )? {
+                0 => return Err(std::io::ErrorKind::WriteZero.into()),
+                n => written += n,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Returns the hostname of this machine, or `localhost` if it can't be found
+pub fn hostname() -> String {
+    #[cfg(unix)]
+    {
+        let mut buf = [0u8; 256];
+        if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } == 0 {
+            let len = buf.iter().position(|byte| *byte == 0).unwrap_or(buf.len());
+            match std::str::from_utf8(&buf[..len]) {
+                Ok(name) if !name.is_empty() => return name.to_owned(),
+                _ => {}
+            }
+        }
+    }
+    std::env::var("COMPUTERNAME")
+        .or_else(|_| std::env::var("HOSTNAME"))
+        .unwrap_or_else(|_| "localhost".to_owned())
+}
+
+/// Returns the number of seconds elapsed since the UNIX epoch
+pub fn get_epoch_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        // the clock is before the epoch; hardly a situation that we need to care about
+        .unwrap_or(0)
+}